@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
-use log::trace;
+use tracing::trace;
 use once_cell::sync::Lazy;
 use once_cell::sync::OnceCell;
 use rand::Rng;
@@ -105,16 +105,16 @@ fn verify_login() -> Result<String, ServiceResponse> {
 
     trace!("user already logged into azure");
     if let Some(subscription_id) = response["id"].as_str() {
-        log::trace!("Already logged into Azure.");
+        tracing::trace!("Already logged into Azure.");
         return Ok(subscription_id.to_string());
     } else {
         // If not logged in, prompt the user to log in
-        log::trace!("Not logged into Azure. Initiating login process...");
+        tracing::trace!("Not logged into Azure. Initiating login process...");
         let args = ["login"];
         print_cmd(&args);
         exec_os(&args)?;
 
-        log::trace!("Login to Azure succeeded!");
+        tracing::trace!("Login to Azure succeeded!");
 
         // After login, re-attempt to get the subscription ID
         let args = &["account", "show"];
@@ -394,7 +394,7 @@ pub fn create_cosmos_account(
     print_cmd(&cmd_args);
     let output = exec_os(&cmd_args)?;
 
-    log::trace!("stdout: {}", output);
+    tracing::trace!("stdout: {}", output);
     Ok(())
 }
 
@@ -552,7 +552,7 @@ pub fn create_database(
     resource_group: &str,
 ) -> Result<(), ServiceResponse> {
     if cosmos_database_exists(account_name, database_name, resource_group)? {
-        log::trace!("Database {} already exists.", database_name);
+        tracing::trace!("Database {} already exists.", database_name);
         return Ok(());
     }
 
@@ -624,7 +624,7 @@ pub fn create_collection(
     resource_group: &str,
 ) -> Result<(), ServiceResponse> {
     if cosmos_collection_exists(account_name, database_name, collection_name, resource_group)? {
-        log::trace!("Collection {} already exists", collection_name);
+        tracing::trace!("Collection {} already exists", collection_name);
         return Ok(());
     }
 
@@ -643,10 +643,12 @@ pub fn create_collection(
         resource_group,
         "--partition-key-path",
         "/partitionKey",
+        "--ttl", // on, but documents only expire if they set their own ttl (see PersistOutboxMessage)
+        "-1",
     ];
     print_cmd(&cmd_args);
     let output = exec_os(&cmd_args)?;
-    log::trace!("Output: {}", output);
+    tracing::trace!("Output: {}", output);
     Ok(())
 }
 /// Checks if a Cosmos SQL collection exists.
@@ -742,10 +744,10 @@ pub fn keyvault_exists(kv_name: &str) -> Result<bool, ServiceResponse> {
 
     // Check if the output contains the name of the Key Vault.
     if output.contains(kv_name) {
-        log::trace!("KV {} already exists", kv_name);
+        tracing::trace!("KV {} already exists", kv_name);
         Ok(true)
     } else {
-        log::trace!("{} does not exist", kv_name);
+        tracing::trace!("{} does not exist", kv_name);
         Ok(false)
     }
 }
@@ -954,7 +956,7 @@ pub fn send_email(to: &str, from: &str, subject: &str, msg: &str) -> Result<(),
 
     match exec_os(&args) {
         Ok(output) => {
-            log::trace!("Output: {}", output);
+            tracing::trace!("Output: {}", output);
             Ok(())
         }
         Err(error) => Err(format!("Failed to send email. Error: {:#?}", error)),
@@ -1049,8 +1051,8 @@ mod tests {
         tokio::runtime::Runtime::new()
             .expect("Failed to create Tokio runtime")
             .block_on(init_env_logger(
-                log::LevelFilter::Info,
-                log::LevelFilter::Error,
+                crate::LevelFilter::INFO,
+                crate::LevelFilter::ERROR,
             ));
         send_text_message(&SERVICE_CONFIG.test_phone_number, "this is a test")
             .expect("text message should be sent");
@@ -1061,8 +1063,8 @@ mod tests {
         tokio::runtime::Runtime::new()
             .expect("Failed to create Tokio runtime")
             .block_on(init_env_logger(
-                log::LevelFilter::Info,
-                log::LevelFilter::Error,
+                crate::LevelFilter::INFO,
+                crate::LevelFilter::ERROR,
             ));
         send_email(
             &SERVICE_CONFIG.test_email,
@@ -1111,8 +1113,8 @@ mod tests {
         tokio::runtime::Runtime::new()
             .expect("Failed to create Tokio runtime")
             .block_on(init_env_logger(
-                log::LevelFilter::Info,
-                log::LevelFilter::Error,
+                crate::LevelFilter::INFO,
+                crate::LevelFilter::ERROR,
             ));
 
         // make sure the user is logged in
@@ -1126,19 +1128,19 @@ mod tests {
         keyvault_exists(&kv_name).expect(&format!("Failed to find Key Vault named {}.", kv_name));
 
         // Create a test resource group
-        log::info!("creating resource group");
+        tracing::info!("creating resource group");
         create_resource_group(&resource_group, location).expect("Failed to create resource group.");
 
-        log::trace!("creating cosmosdb: {}", cosmos_account_name);
+        tracing::trace!("creating cosmosdb: {}", cosmos_account_name);
         //Add a Cosmos DB instance to it
         create_cosmos_account(&resource_group, &cosmos_account_name, location)
             .expect("Failed to create Cosmos DB instance.");
 
-        log::trace!("Creating database: {}", database_name);
+        tracing::trace!("Creating database: {}", database_name);
         create_database(&cosmos_account_name, &database_name, &resource_group)
             .expect("creating a cosmos db should succeed");
         // Create a collection in the Cosmos DB instance
-        log::trace!("Creating collection: {}", collection_name);
+        tracing::trace!("Creating collection: {}", collection_name);
         create_collection(
             &cosmos_account_name,
             &database_name,
@@ -1231,8 +1233,8 @@ mod tests {
         tokio::runtime::Runtime::new()
             .expect("Failed to create Tokio runtime")
             .block_on(init_env_logger(
-                log::LevelFilter::Info,
-                log::LevelFilter::Error,
+                crate::LevelFilter::INFO,
+                crate::LevelFilter::ERROR,
             ));
 
         let exists = cosmos_account_exists(
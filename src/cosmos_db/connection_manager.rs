@@ -0,0 +1,260 @@
+#![allow(dead_code)]
+/**
+ *  keeps track of the credentials we use to talk to Cosmos and whether the last attempt to use them worked.
+ *
+ *  UserDb is built for every request, so "rebuilding the clients" just means swapping the credentials that UserDb::new
+ *  picks up from here.  a background task (see start_monitor) re-reads the Cosmos secret from Key Vault every so
+ *  often, swaps in the new key if it was rotated, and then makes a cheap call to Cosmos to make sure the key works.
+ *  the result of that check is what /api/v1/ready reports.
+ */
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use azure_data_cosmos::prelude::AuthorizationToken;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    azure_setup::azure_wrapper::retrieve_cosmos_secrets_from_keyvault,
+    middleware::service_config::{ServiceConfig, SERVICE_CONFIG},
+    shared::{
+        error_reporting,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+        status_page,
+    },
+};
+
+use super::cosmosdb::{UserDb, UserDbTrait};
+
+lazy_static::lazy_static! {
+    static ref CONNECTION_STATE: Arc<RwLock<ConnectionState>> =
+        Arc::new(RwLock::new(ConnectionState::from_config(&SERVICE_CONFIG)));
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosmosCredentials {
+    pub account: String,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct DbHealth {
+    pub healthy: bool,
+    pub last_checked: Option<u64>, // seconds since the epoch. None until the first check finishes
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub credential_version: u32, // bumped every time the credentials are swapped
+}
+
+struct ConnectionState {
+    credentials: CosmosCredentials,
+    health: DbHealth,
+}
+
+impl ConnectionState {
+    fn from_config(config: &ServiceConfig) -> Self {
+        Self {
+            credentials: CosmosCredentials {
+                account: config.cosmos_account.clone(),
+                token: config.cosmos_token.clone(),
+            },
+            health: DbHealth {
+                healthy: false,
+                last_checked: None,
+                last_error: Some("the database has not been checked yet".to_owned()),
+                consecutive_failures: 0,
+                credential_version: 1,
+            },
+        }
+    }
+}
+
+pub struct ConnectionManager;
+
+impl ConnectionManager {
+    pub fn credentials() -> CosmosCredentials {
+        CONNECTION_STATE
+            .read()
+            .expect("the connection state lock should never be poisoned")
+            .credentials
+            .clone()
+    }
+
+    pub fn health() -> DbHealth {
+        CONNECTION_STATE
+            .read()
+            .expect("the connection state lock should never be poisoned")
+            .health
+            .clone()
+    }
+
+    /**
+     *  swap in a new Cosmos key.  a key that isn't valid base64 is rejected here rather than being handed to UserDb,
+     *  which would panic building the client.  returns true if the credentials actually changed.
+     */
+    pub fn rotate_credentials(token: &str) -> Result<bool, ServiceResponse> {
+        if let Err(e) = AuthorizationToken::primary_from_base64(token) {
+            return Err(ServiceResponse::new(
+                "the new cosmos key is not a valid key",
+                StatusCode::BAD_REQUEST,
+                ResponseType::ErrorInfo(format!("{}", e)),
+                GameError::HttpError(StatusCode::BAD_REQUEST),
+            ));
+        }
+
+        let mut state = CONNECTION_STATE
+            .write()
+            .expect("the connection state lock should never be poisoned");
+        if state.credentials.token == token {
+            return Ok(false);
+        }
+        state.credentials.token = token.to_owned();
+        state.health.credential_version += 1;
+        tracing::info!(
+            "cosmos credentials rotated. now on version {}",
+            state.health.credential_version
+        );
+        Ok(true)
+    }
+
+    fn record_check(result: &Result<(), ServiceResponse>) -> DbHealth {
+        let mut state = CONNECTION_STATE
+            .write()
+            .expect("the connection state lock should never be poisoned");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs();
+        state.health.last_checked = Some(now);
+        status_page::record_check(now, result.is_ok());
+        match result {
+            Ok(_) => {
+                state.health.healthy = true;
+                state.health.last_error = None;
+                state.health.consecutive_failures = 0;
+            }
+            Err(e) => {
+                state.health.healthy = false;
+                state.health.last_error = Some(e.message.clone());
+                state.health.consecutive_failures += 1;
+            }
+        }
+        state.health.clone()
+    }
+
+    /**
+     *  pick up a rotated key from Key Vault, then make sure we can still talk to the database.  Key Vault failures are
+     *  logged but don't make us unhealthy -- the key we already have may well still work.
+     */
+    pub async fn check_now() -> DbHealth {
+        let kv_name = SERVICE_CONFIG.kv_name.clone();
+        match tokio::task::spawn_blocking(move || retrieve_cosmos_secrets_from_keyvault(&kv_name)).await {
+            Ok(Ok(secret)) => match token_from_connection_string(&secret.connection_string) {
+                Some(token) => {
+                    if let Err(e) = Self::rotate_credentials(&token) {
+                        tracing::error!("ignoring the cosmos key in Key Vault: {}", e.message);
+                    }
+                }
+                None => tracing::error!("the cosmos secret in Key Vault has no AccountKey"),
+            },
+            Ok(Err(e)) => tracing::warn!("unable to refresh the cosmos key from Key Vault: {}", e),
+            Err(e) => tracing::warn!("Key Vault refresh task failed: {}", e),
+        }
+
+        let result = UserDb::new(false, &SERVICE_CONFIG).health_check().await;
+        let health = Self::record_check(&result);
+        if !health.healthy {
+            tracing::error!(
+                "database health check failed ({} in a row): {:?}",
+                health.consecutive_failures,
+                health.last_error
+            );
+            // report the outage once, not every time the monitor runs
+            if health.consecutive_failures == 1 {
+                error_reporting::report_background_failure(
+                    "db_health_check",
+                    health.last_error.as_deref().unwrap_or("unknown error"),
+                );
+            }
+        }
+        health
+    }
+
+    /**
+     *  check the connection now and then every interval for the life of the service
+     */
+    pub fn start_monitor(interval: Duration) -> tokio::task::JoinHandle<()> {
+        actix_web::rt::spawn(async move {
+            loop {
+                Self::check_now().await;
+                tokio::time::sleep(interval).await;
+            }
+        })
+    }
+
+    /**
+     *  the ServiceResponse /ready returns: OK with the health if the last check passed, SERVICE_UNAVAILABLE if not
+     */
+    pub fn readiness(health: DbHealth) -> Result<ServiceResponse, ServiceResponse> {
+        if health.healthy {
+            Ok(ServiceResponse::new(
+                "ready",
+                StatusCode::OK,
+                ResponseType::DbHealth(health),
+                GameError::NoError(String::default()),
+            ))
+        } else {
+            Err(ServiceResponse::new(
+                "the database is not available",
+                StatusCode::SERVICE_UNAVAILABLE,
+                ResponseType::DbHealth(health),
+                GameError::HttpError(StatusCode::SERVICE_UNAVAILABLE),
+            ))
+        }
+    }
+}
+
+/// pull the AccountKey out of a connection string like "AccountEndpoint=https://...;AccountKey=abc==;"
+pub fn token_from_connection_string(connection_string: &str) -> Option<String> {
+    connection_string
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .find(|(name, _)| name.trim() == "AccountKey")
+        .map(|(_, value)| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_from_connection_string() {
+        assert_eq!(
+            token_from_connection_string(
+                "AccountEndpoint=https://catan.documents.azure.com:443/;AccountKey=abc123==;"
+            ),
+            Some("abc123==".to_owned())
+        );
+        assert_eq!(
+            token_from_connection_string("AccountEndpoint=https://catan.documents.azure.com:443/;"),
+            None
+        );
+        assert_eq!(token_from_connection_string("AccountKey=;"), None);
+    }
+
+    #[test]
+    fn test_readiness() {
+        let mut health = ConnectionManager::health();
+        health.healthy = false;
+        let sr = ConnectionManager::readiness(health.clone()).expect_err("unhealthy isn't ready");
+        assert_eq!(sr.status, StatusCode::SERVICE_UNAVAILABLE);
+
+        health.healthy = true;
+        let sr = ConnectionManager::readiness(health).expect("healthy is ready");
+        assert_eq!(sr.status, StatusCode::OK);
+    }
+}
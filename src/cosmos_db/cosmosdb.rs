@@ -1,25 +1,34 @@
 #![allow(dead_code)]
 use crate::{
     log_and_return_azure_core_error,
+    macros::convert_status_code,
     middleware::service_config::ServiceConfig,
     new_not_found_error,
-    shared::service_models::PersistUser,
+    shared::service_models::{
+        PersistAchievement, PersistGame, PersistGameEvent, PersistGameStats, PersistOutboxMessage,
+        PersistRefreshToken, PersistUser,
+    },
     shared::shared_models::{UserProfile, GameError, ResponseType},
+    user_service::oauth::OAuthProvider,
 };
 use std::collections::HashMap;
 
+use super::connection_manager::ConnectionManager;
+
 /**
  *  this is the class that calls directly to CosmosDb --
  */
 use crate::{log_return_err, shared::shared_models::ServiceResponse};
 use azure_core::error::{ErrorKind, Result as AzureResult};
+use azure_core::request_options::IfMatchCondition;
 use azure_data_cosmos::prelude::{
     AuthorizationToken, CollectionClient, CosmosClient, DatabaseClient, Query, QueryCrossPartition,
 };
 
 use async_trait::async_trait;
-use futures::StreamExt;
-use log::info;
+use futures::{stream::BoxStream, StreamExt};
+use tracing::info;
+use serde::de::DeserializeOwned;
 /**
  *  we have 3 cosmos collections that we are currently using:  User, Profile, and (eventually) Game.
  *  this just makes sure we consistently use them throughout the code.
@@ -29,6 +38,11 @@ pub enum CosmosDocType {
     User,
     Profile,
     Game,
+    GameEvent,
+    GameStats,
+    Achievement,
+    Outbox,
+    RefreshToken,
 }
 
 pub struct CosmosCollectionNameValues {
@@ -36,7 +50,7 @@ pub struct CosmosCollectionNameValues {
     pub value: &'static str,
 }
 
-pub static COLLECTION_NAME_VALUES: [CosmosCollectionNameValues; 3] = [
+pub static COLLECTION_NAME_VALUES: [CosmosCollectionNameValues; 8] = [
     CosmosCollectionNameValues {
         name: CosmosDocType::User,
         value: "Users-Collection",
@@ -49,7 +63,30 @@ pub static COLLECTION_NAME_VALUES: [CosmosCollectionNameValues; 3] = [
         name: CosmosDocType::Game,
         value: "Game-Collection",
     },
+    CosmosCollectionNameValues {
+        name: CosmosDocType::GameEvent,
+        value: "GameEvent-Collection",
+    },
+    CosmosCollectionNameValues {
+        name: CosmosDocType::GameStats,
+        value: "GameStats-Collection",
+    },
+    CosmosCollectionNameValues {
+        name: CosmosDocType::Achievement,
+        value: "Achievement-Collection",
+    },
+    CosmosCollectionNameValues {
+        name: CosmosDocType::Outbox,
+        value: "Outbox-Collection",
+    },
+    CosmosCollectionNameValues {
+        name: CosmosDocType::RefreshToken,
+        value: "RefreshToken-Collection",
+    },
 ];
+/// every collection is partitioned on this field -- each document struct needs a member serialized with this name
+pub const PARTITION_KEY_PATH: &str = "/partitionKey";
+
 #[async_trait]
 pub trait UserDbTrait {
     async fn setupdb(&self) -> Result<(), ServiceResponse>;
@@ -61,7 +98,69 @@ pub trait UserDbTrait {
     async fn delete_user(&self, unique_id: &str) -> Result<(), ServiceResponse>;
     async fn find_user_by_id(&self, val: &str) -> Result<PersistUser, ServiceResponse>;
     async fn find_user_by_email(&self, val: &str) -> Result<PersistUser, ServiceResponse>;
+    /// the user who logs in with the provider's account `subject` (see oauth.rs)
+    async fn find_user_by_external_identity(
+        &self,
+        provider: OAuthProvider,
+        subject: &str,
+    ) -> Result<PersistUser, ServiceResponse>;
+    /// the user the KeyCloak realm's user `keycloak_id` was made for (see kc_proxy.rs)
+    async fn find_user_by_keycloak_id(&self, keycloak_id: &str) -> Result<PersistUser, ServiceResponse>;
     async fn get_connected_users(&self, connected_user_id: &str) -> Result<Vec<PersistUser>, ServiceResponse>;
+    async fn update_or_create_game(
+        &self,
+        game: &PersistGame,
+    ) -> Result<ServiceResponse, ServiceResponse>;
+    async fn find_game_by_id(&self, game_id: &str) -> Result<PersistGame, ServiceResponse>;
+    async fn list_games(&self, finished_since: u64) -> Result<Vec<PersistGame>, ServiceResponse>;
+    /// the count games that finished last, newest first
+    async fn list_recent_games(&self, count: usize) -> Result<Vec<PersistGame>, ServiceResponse>;
+    /// every user, one at a time as they come from the database -- for the listings and exports that are too big to
+    /// collect first
+    fn stream_users(&self) -> BoxStream<'_, Result<PersistUser, ServiceResponse>>;
+    /// list_games, one game at a time as they come from the database
+    fn stream_games(&self, finished_since: u64) -> BoxStream<'_, Result<PersistGame, ServiceResponse>>;
+    async fn append_game_event(
+        &self,
+        event: &PersistGameEvent,
+    ) -> Result<ServiceResponse, ServiceResponse>;
+    async fn find_game_events(&self, game_id: &str) -> Result<Vec<PersistGameEvent>, ServiceResponse>;
+    async fn update_game_stats(
+        &self,
+        stats: &PersistGameStats,
+    ) -> Result<ServiceResponse, ServiceResponse>;
+    async fn find_game_stats(&self, user_id: &str) -> Result<Vec<PersistGameStats>, ServiceResponse>;
+    /// fails with CONFLICT if the user already has the achievement
+    async fn add_achievement(
+        &self,
+        achievement: &PersistAchievement,
+    ) -> Result<ServiceResponse, ServiceResponse>;
+    async fn find_achievements(&self, user_id: &str) -> Result<Vec<PersistAchievement>, ServiceResponse>;
+    async fn add_to_outbox(
+        &self,
+        message: &PersistOutboxMessage,
+    ) -> Result<ServiceResponse, ServiceResponse>;
+    /// the user's messages that haven't expired by now, oldest first
+    async fn find_outbox(&self, user_id: &str, now: u64) -> Result<Vec<PersistOutboxMessage>, ServiceResponse>;
+    async fn delete_outbox_message(&self, id: &str) -> Result<(), ServiceResponse>;
+    async fn save_refresh_token(
+        &self,
+        token: &PersistRefreshToken,
+    ) -> Result<ServiceResponse, ServiceResponse>;
+    /// by the hash of the token.  NOT_FOUND if there isn't one, or it has expired by now
+    async fn find_refresh_token(&self, id: &str, now: u64) -> Result<PersistRefreshToken, ServiceResponse>;
+    /// mark a token found with find_refresh_token used -- only if it hasn't changed since.  false if it has: somebody
+    /// else used it first
+    async fn use_refresh_token(&self, token: &PersistRefreshToken) -> Result<bool, ServiceResponse>;
+    /// every token from the login the family started
+    async fn delete_refresh_tokens(&self, family_id: &str) -> Result<(), ServiceResponse>;
+    /// every token the user has, or only those of one session
+    async fn delete_user_refresh_tokens(
+        &self,
+        user_id: &str,
+        session_id: Option<&str>,
+    ) -> Result<(), ServiceResponse>;
+    async fn health_check(&self) -> Result<(), ServiceResponse>;
     fn get_collection_names(&self, is_test: bool) -> Vec<String> {
         COLLECTION_NAME_VALUES
             .iter()
@@ -89,7 +188,10 @@ pub struct UserDb {
 
 impl UserDb {
     pub fn new(is_test: bool, service_config: &'static ServiceConfig) -> Self {
-        let client = public_client(&service_config.cosmos_account, &service_config.cosmos_token);
+        // the credentials come from the ConnectionManager (and not the service_config) so that a rotated key is
+        // picked up without a restart
+        let credentials = ConnectionManager::credentials();
+        let client = public_client(&credentials.account, &credentials.token);
         let database_name;
         if is_test {
             database_name = service_config.cosmos_database_name.clone() + "-test";
@@ -126,13 +228,13 @@ impl UserDb {
         }
     }
     /**
-     * Execute an arbitrary query against the user database and return a list of users
+     * Execute an arbitrary query against one of the collections and return a list of documents
      */
-    async fn execute_query(
+    async fn execute_query<T: DeserializeOwned>(
         &self,
         collection_name: CosmosDocType,
         query_string: &str,
-    ) -> AzureResult<Vec<PersistUser>> {
+    ) -> AzureResult<Vec<T>> {
         let mut users = Vec::new();
         let query = Query::new(query_string.to_string());
         let collection = self.collection_clients.get(&collection_name).unwrap();
@@ -147,7 +249,7 @@ impl UserDb {
             match response {
                 Ok(response) => {
                     for doc in response.documents() {
-                        let user: PersistUser = serde_json::from_value(doc.clone())?;
+                        let user: T = serde_json::from_value(doc.clone())?;
                         users.push(user);
                     }
                     return Ok(users); // return user if found
@@ -159,6 +261,96 @@ impl UserDb {
         }
         Err(azure_core::Error::new(ErrorKind::Other, "User not found")) // return error if user not found
     }
+    /**
+     * like execute_query, but yields the documents as cosmos pages them in instead of collecting them, so only one page
+     * is in memory at a time however many documents the query finds
+     */
+    fn stream_query<'a, T: DeserializeOwned + Send + 'a>(
+        &'a self,
+        collection_name: CosmosDocType,
+        query_string: &str,
+    ) -> BoxStream<'a, AzureResult<T>> {
+        let query = Query::new(query_string.to_string());
+        let collection = self.collection_clients.get(&collection_name).unwrap();
+        collection
+            .query_documents(query)
+            .query_cross_partition(QueryCrossPartition::Yes)
+            .into_stream::<serde_json::Value>()
+            .flat_map(|page| {
+                let documents: Vec<AzureResult<T>> = match page {
+                    Ok(response) => response
+                        .documents()
+                        .map(|doc| serde_json::from_value(doc.clone()).map_err(azure_core::Error::from))
+                        .collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                futures::stream::iter(documents)
+            })
+            .boxed()
+    }
+    /**
+     *  delete refresh tokens one at a time, from their own partitions
+     */
+    async fn delete_tokens(&self, tokens: Vec<PersistRefreshToken>) -> Result<(), ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::RefreshToken).unwrap();
+        for token in tokens {
+            let doc_client = match collection.document_client(&token.id, &token.partition_key) {
+                Ok(client) => client,
+                Err(e) => log_and_return_azure_core_error!(e, "Failed to get document client"),
+            };
+            if let Err(e) = doc_client.delete_document().await {
+                log_and_return_azure_core_error!(e, "delete_refresh_tokens");
+            }
+        }
+        Ok(())
+    }
+    /**
+     *  make sure every collection we use exists and is partitioned the way our documents expect.  returns a
+     *  description of each problem found (and what to do about it) rather than stopping at the first one.
+     */
+    pub async fn verify_collections(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for item in &COLLECTION_NAME_VALUES {
+            let collection = self
+                .collection_clients
+                .get(&item.name)
+                .expect("this should be set in ::new");
+            match collection.get_collection().await {
+                Ok(response) => {
+                    let paths = &response.collection.partition_key.paths;
+                    if paths.len() != 1 || paths[0] != PARTITION_KEY_PATH {
+                        problems.push(format!(
+                            "collection {} in {} is partitioned on {:?} but the service expects [\"{}\"]. the partition \
+                             key can't be changed -- recreate the collection (see UserDb::setupdb)",
+                            collection.collection_name(),
+                            self.database_name,
+                            paths,
+                            PARTITION_KEY_PATH
+                        ));
+                    }
+                }
+                Err(e) => {
+                    let status = e.as_http_error().map(|http_err| convert_status_code(http_err.status()));
+                    if status == Some(StatusCode::NOT_FOUND) {
+                        problems.push(format!(
+                            "collection {} does not exist in database {}. run the service with --setup to create it",
+                            collection.collection_name(),
+                            self.database_name
+                        ));
+                    } else {
+                        problems.push(format!(
+                            "unable to read collection {} in database {}: {}. check COSMOS_ACCOUNT_NAME and \
+                             COSMOS_AUTH_TOKEN",
+                            collection.collection_name(),
+                            self.database_name,
+                            e
+                        ));
+                    }
+                }
+            }
+        }
+        problems
+    }
     fn collection_name(&self, col_type: &CosmosDocType) -> String {
         let collection_client = self
             .collection_clients
@@ -245,7 +437,7 @@ impl UserDbTrait for UserDb {
                 .unwrap()
                 // note: this is where the field for the partion key is set -- if you change anything, make sure this is
                 // a member of your document struct!
-                .create_collection(collection_client.collection_name(), "/partitionKey")
+                .create_collection(collection_client.collection_name(), PARTITION_KEY_PATH)
                 .await
             {
                 Ok(..) => {
@@ -272,7 +464,7 @@ impl UserDbTrait for UserDb {
      */
     async fn list(&self) -> Result<Vec<PersistUser>, ServiceResponse> {
         let query = r#"SELECT * FROM c WHERE c.partitionKey=1"#;
-        match self.execute_query(CosmosDocType::User, query).await {
+        match self.execute_query::<PersistUser>(CosmosDocType::User, query).await {
             Ok(users) => Ok(users),
             Err(e) => log_and_return_azure_core_error!(e, &format!("error in list()")),
         }
@@ -288,7 +480,7 @@ impl UserDbTrait for UserDb {
     ) -> Result<ServiceResponse, ServiceResponse> {
         let collection = self.collection_clients.get(&CosmosDocType::User).unwrap();
 
-        log::trace!("{}", serde_json::to_string(&user).unwrap());
+        tracing::trace!("{}", serde_json::to_string(&user).unwrap());
         match collection
             .create_document(user.clone())
             .is_upsert(true)
@@ -334,7 +526,7 @@ impl UserDbTrait for UserDb {
      */
     async fn find_user_by_id(&self, val: &str) -> Result<PersistUser, ServiceResponse> {
         let query = format!(r#"SELECT * FROM c WHERE c.id = '{}'"#, val);
-        match self.execute_query(CosmosDocType::User, &query).await {
+        match self.execute_query::<PersistUser>(CosmosDocType::User, &query).await {
             Ok(users) => {
                 if !users.is_empty() {
                     Ok(users.first().unwrap().clone()) // clone is necessary because `first()` returns a reference
@@ -352,7 +544,7 @@ impl UserDbTrait for UserDb {
             r#"SELECT * FROM c WHERE c.connected_user_id = '{}'"#,
             connected_user_id
         );
-        match self.execute_query(CosmosDocType::User, &query).await {
+        match self.execute_query::<PersistUser>(CosmosDocType::User, &query).await {
             Ok(users) => {
                 Ok(users)
             }
@@ -366,7 +558,7 @@ impl UserDbTrait for UserDb {
             r#"SELECT * FROM c WHERE c.user_profile.Pii.Email = '{}'"#,
             val
         );
-        match self.execute_query(CosmosDocType::User, &query).await {
+        match self.execute_query::<PersistUser>(CosmosDocType::User, &query).await {
             Ok(users) => {
                 if !users.is_empty() {
                     Ok(users.first().unwrap().clone())
@@ -380,7 +572,382 @@ impl UserDbTrait for UserDb {
         }
     }
 
-   
+    async fn find_user_by_external_identity(
+        &self,
+        provider: OAuthProvider,
+        subject: &str,
+    ) -> Result<PersistUser, ServiceResponse> {
+        let query = format!(
+            r#"SELECT VALUE c FROM c JOIN i IN c.external_identities WHERE i.Provider = '{:?}' AND i.Subject = '{}'"#,
+            provider, subject
+        );
+        match self.execute_query::<PersistUser>(CosmosDocType::User, &query).await {
+            Ok(users) => match users.first() {
+                Some(user) => Ok(user.clone()),
+                None => new_not_found_error!("not found"),
+            },
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_user_by_external_identity");
+            }
+        }
+    }
+
+    async fn find_user_by_keycloak_id(&self, keycloak_id: &str) -> Result<PersistUser, ServiceResponse> {
+        let query = format!(r#"SELECT * FROM c WHERE c.keycloak_id = '{}'"#, keycloak_id);
+        match self.execute_query::<PersistUser>(CosmosDocType::User, &query).await {
+            Ok(users) => match users.first() {
+                Some(user) => Ok(user.clone()),
+                None => new_not_found_error!("not found"),
+            },
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_user_by_keycloak_id");
+            }
+        }
+    }
+
+    async fn update_or_create_game(
+        &self,
+        game: &PersistGame,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::Game).unwrap();
+
+        match collection
+            .create_document(game.clone())
+            .is_upsert(true)
+            .await
+        {
+            Ok(..) => Ok(ServiceResponse::new_generic_ok("saved")),
+            Err(e) => log_and_return_azure_core_error!(e, "update_or_create_game"),
+        }
+    }
+
+    async fn find_game_by_id(&self, game_id: &str) -> Result<PersistGame, ServiceResponse> {
+        let query = format!(r#"SELECT * FROM c WHERE c.id = '{}'"#, game_id);
+        match self
+            .execute_query::<PersistGame>(CosmosDocType::Game, &query)
+            .await
+        {
+            Ok(games) => match games.first() {
+                Some(game) => Ok(game.clone()),
+                None => new_not_found_error!("not found"),
+            },
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_game_by_id");
+            }
+        }
+    }
+    /**
+     *  every finished game (the match history) that finished at or after finished_since, in seconds since the epoch
+     */
+    async fn list_games(&self, finished_since: u64) -> Result<Vec<PersistGame>, ServiceResponse> {
+        let query = format!(
+            r#"SELECT * FROM c WHERE c.finished_at >= {}"#,
+            finished_since
+        );
+        match self
+            .execute_query::<PersistGame>(CosmosDocType::Game, &query)
+            .await
+        {
+            Ok(games) => Ok(games),
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "list_games");
+            }
+        }
+    }
+    async fn list_recent_games(&self, count: usize) -> Result<Vec<PersistGame>, ServiceResponse> {
+        let query = format!(
+            r#"SELECT TOP {} * FROM c ORDER BY c.finished_at DESC"#,
+            count
+        );
+        match self
+            .execute_query::<PersistGame>(CosmosDocType::Game, &query)
+            .await
+        {
+            Ok(games) => Ok(games),
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "list_recent_games");
+            }
+        }
+    }
+    fn stream_users(&self) -> BoxStream<'_, Result<PersistUser, ServiceResponse>> {
+        let query = r#"SELECT * FROM c WHERE c.partitionKey=1"#;
+        self.stream_query::<PersistUser>(CosmosDocType::User, query)
+            .map(|user| match user {
+                Ok(user) => Ok(user),
+                Err(e) => {
+                    log_and_return_azure_core_error!(e, "stream_users");
+                }
+            })
+            .boxed()
+    }
+    fn stream_games(&self, finished_since: u64) -> BoxStream<'_, Result<PersistGame, ServiceResponse>> {
+        let query = format!(
+            r#"SELECT * FROM c WHERE c.finished_at >= {}"#,
+            finished_since
+        );
+        self.stream_query::<PersistGame>(CosmosDocType::Game, &query)
+            .map(|game| match game {
+                Ok(game) => Ok(game),
+                Err(e) => {
+                    log_and_return_azure_core_error!(e, "stream_games");
+                }
+            })
+            .boxed()
+    }
+    /**
+     *  events are upserted: an event that was undone is replaced by the next one with its game_index
+     */
+    async fn append_game_event(
+        &self,
+        event: &PersistGameEvent,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::GameEvent).unwrap();
+
+        match collection
+            .create_document(event.clone())
+            .is_upsert(true)
+            .await
+        {
+            Ok(..) => Ok(ServiceResponse::new_generic_ok("saved")),
+            Err(e) => log_and_return_azure_core_error!(e, "append_game_event"),
+        }
+    }
+
+    async fn find_game_events(&self, game_id: &str) -> Result<Vec<PersistGameEvent>, ServiceResponse> {
+        let query = format!(
+            r#"SELECT * FROM c WHERE c.game_id = '{}' ORDER BY c.game_index"#,
+            game_id
+        );
+        match self
+            .execute_query::<PersistGameEvent>(CosmosDocType::GameEvent, &query)
+            .await
+        {
+            Ok(events) => Ok(events),
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_game_events");
+            }
+        }
+    }
+    /**
+     *  upserted by game and player ("{game_id}:{user_id}"), so a game over step that is retried writes the same document
+     */
+    async fn update_game_stats(
+        &self,
+        stats: &PersistGameStats,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::GameStats).unwrap();
+
+        match collection
+            .create_document(stats.clone())
+            .is_upsert(true)
+            .await
+        {
+            Ok(..) => Ok(ServiceResponse::new_generic_ok("saved")),
+            Err(e) => log_and_return_azure_core_error!(e, "update_game_stats"),
+        }
+    }
+
+    async fn find_game_stats(&self, user_id: &str) -> Result<Vec<PersistGameStats>, ServiceResponse> {
+        let query = format!(
+            r#"SELECT * FROM c WHERE c.user_id = '{}' ORDER BY c.finished_at DESC"#,
+            user_id
+        );
+        match self
+            .execute_query::<PersistGameStats>(CosmosDocType::GameStats, &query)
+            .await
+        {
+            Ok(stats) => Ok(stats),
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_game_stats");
+            }
+        }
+    }
+    /**
+     *  not an upsert: the id is the user and the achievement, so Cosmos refuses a second copy with CONFLICT
+     */
+    async fn add_achievement(
+        &self,
+        achievement: &PersistAchievement,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::Achievement).unwrap();
+
+        match collection.create_document(achievement.clone()).await {
+            Ok(..) => Ok(ServiceResponse::new_generic_ok("saved")),
+            Err(e) => log_and_return_azure_core_error!(e, "add_achievement"),
+        }
+    }
+
+    async fn find_achievements(&self, user_id: &str) -> Result<Vec<PersistAchievement>, ServiceResponse> {
+        let query = format!(
+            r#"SELECT * FROM c WHERE c.user_id = '{}' ORDER BY c.unlocked_at"#,
+            user_id
+        );
+        match self
+            .execute_query::<PersistAchievement>(CosmosDocType::Achievement, &query)
+            .await
+        {
+            Ok(achievements) => Ok(achievements),
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_achievements");
+            }
+        }
+    }
+    async fn add_to_outbox(
+        &self,
+        message: &PersistOutboxMessage,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::Outbox).unwrap();
+
+        match collection.create_document(message.clone()).await {
+            Ok(..) => Ok(ServiceResponse::new_generic_ok("saved")),
+            Err(e) => log_and_return_azure_core_error!(e, "add_to_outbox"),
+        }
+    }
+    /**
+     *  Cosmos removes expired messages itself, but not always straight away, so they are filtered here too
+     */
+    async fn find_outbox(&self, user_id: &str, now: u64) -> Result<Vec<PersistOutboxMessage>, ServiceResponse> {
+        let query = format!(
+            r#"SELECT * FROM c WHERE c.user_id = '{}' ORDER BY c.queued_at"#,
+            user_id
+        );
+        match self
+            .execute_query::<PersistOutboxMessage>(CosmosDocType::Outbox, &query)
+            .await
+        {
+            Ok(messages) => {
+                let mut messages: Vec<PersistOutboxMessage> =
+                    messages.into_iter().filter(|m| !m.expired(now)).collect();
+                messages.sort_by_key(|m| (m.queued_at, m.message.user_sequence));
+                Ok(messages)
+            }
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_outbox");
+            }
+        }
+    }
+
+    async fn delete_outbox_message(&self, id: &str) -> Result<(), ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::Outbox).unwrap();
+
+        let doc_client = match collection.document_client(id, &1) {
+            Ok(client) => client,
+            Err(e) => log_and_return_azure_core_error!(e, "Failed to get document client"),
+        };
+
+        match doc_client.delete_document().await {
+            Ok(..) => Ok(()),
+            Err(e) => log_and_return_azure_core_error!(e, "delete_outbox_message"),
+        }
+    }
+    /**
+     *  upserted, so a token can be marked used
+     */
+    async fn save_refresh_token(
+        &self,
+        token: &PersistRefreshToken,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let collection = self.collection_clients.get(&CosmosDocType::RefreshToken).unwrap();
+
+        match collection.create_document(token.clone()).is_upsert(true).await {
+            Ok(..) => Ok(ServiceResponse::new_generic_ok("saved")),
+            Err(e) => log_and_return_azure_core_error!(e, "save_refresh_token"),
+        }
+    }
+
+    async fn find_refresh_token(&self, id: &str, now: u64) -> Result<PersistRefreshToken, ServiceResponse> {
+        let query = format!(r#"SELECT * FROM c WHERE c.id = '{}'"#, id);
+        match self
+            .execute_query::<PersistRefreshToken>(CosmosDocType::RefreshToken, &query)
+            .await
+        {
+            Ok(tokens) => match tokens.into_iter().find(|token| token.expires_at > now) {
+                Some(token) => Ok(token),
+                None => new_not_found_error!("not found"),
+            },
+            Err(e) => {
+                log_and_return_azure_core_error!(e, "find_refresh_token");
+            }
+        }
+    }
+
+    /**
+     *  a replace conditioned on the etag the token was read with, so when two refreshes race with the same token only
+     *  one of them gets to use it
+     */
+    async fn use_refresh_token(&self, token: &PersistRefreshToken) -> Result<bool, ServiceResponse> {
+        let etag = match &token.etag {
+            Some(etag) => etag.clone(),
+            None => return Ok(false), // not read from the collection, so there is nothing to compare
+        };
+        let collection = self.collection_clients.get(&CosmosDocType::RefreshToken).unwrap();
+        let doc_client = match collection.document_client(&token.id, &token.partition_key) {
+            Ok(client) => client,
+            Err(e) => log_and_return_azure_core_error!(e, "Failed to get document client"),
+        };
+        let mut used = token.clone();
+        used.used = true;
+        match doc_client
+            .replace_document(used)
+            .if_match_condition(IfMatchCondition::Match(etag))
+            .await
+        {
+            Ok(..) => Ok(true),
+            Err(e)
+                if e.as_http_error()
+                    .map_or(false, |http_err| http_err.status() == azure_core::StatusCode::PreconditionFailed) =>
+            {
+                Ok(false)
+            }
+            Err(e) => log_and_return_azure_core_error!(e, "use_refresh_token"),
+        }
+    }
+
+    async fn delete_refresh_tokens(&self, family_id: &str) -> Result<(), ServiceResponse> {
+        let query = format!(r#"SELECT * FROM c WHERE c.family_id = '{}'"#, family_id);
+        let tokens = match self
+            .execute_query::<PersistRefreshToken>(CosmosDocType::RefreshToken, &query)
+            .await
+        {
+            Ok(tokens) => tokens,
+            Err(e) => log_and_return_azure_core_error!(e, "delete_refresh_tokens"),
+        };
+        self.delete_tokens(tokens).await
+    }
+
+    async fn delete_user_refresh_tokens(
+        &self,
+        user_id: &str,
+        session_id: Option<&str>,
+    ) -> Result<(), ServiceResponse> {
+        let query = match session_id {
+            Some(session_id) => format!(
+                r#"SELECT * FROM c WHERE c.partitionKey = '{}' AND c.session_id = '{}'"#,
+                user_id, session_id
+            ),
+            None => format!(r#"SELECT * FROM c WHERE c.partitionKey = '{}'"#, user_id),
+        };
+        let tokens = match self
+            .execute_query::<PersistRefreshToken>(CosmosDocType::RefreshToken, &query)
+            .await
+        {
+            Ok(tokens) => tokens,
+            Err(e) => log_and_return_azure_core_error!(e, "delete_user_refresh_tokens"),
+        };
+        self.delete_tokens(tokens).await
+    }
+    /**
+     *  the cheapest call we can make that proves the credentials work and the database is there
+     */
+    async fn health_check(&self) -> Result<(), ServiceResponse> {
+        match self.database.as_ref().unwrap().get_database().await {
+            Ok(..) => Ok(()),
+            Err(e) => log_and_return_azure_core_error!(
+                e,
+                &format!("health check of {} failed", self.database_name)
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -398,7 +965,7 @@ mod tests {
 
     use super::*;
     use bcrypt::{hash, DEFAULT_COST};
-    use log::trace;
+    use tracing::trace;
     #[tokio::test]
 
     async fn test_e2e() {
@@ -407,7 +974,7 @@ mod tests {
     }
     pub async fn test_db_e2e(request_context: &RequestContext) {
         let user_db = &request_context.database;
-        init_env_logger(log::LevelFilter::Trace, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::TRACE, crate::LevelFilter::ERROR).await;
         verify_cosmosdb(&request_context)
             .await
             .expect("azure should be configured to run these tests");
@@ -441,7 +1008,7 @@ mod tests {
         assert!(test_user.user_profile.validated_email);
 
         // find user by email
-        log::trace!(
+        tracing::trace!(
             "looking for user {}",
             test_user.user_profile.get_email_or_panic()
         );
@@ -539,11 +1106,21 @@ mod tests {
                     user_id: None,
                     validated_email: false,
                     validated_phone: false,
+                    blocked_user_ids: Vec::new(),
                 },
            
                 phone_code: None,
                 roles: vec![Role::User, Role::TestUser],
                 connected_user_id: None,
+                must_reset_password: false,
+                push_tokens: Vec::new(),
+                public_results: false,
+                notification_preferences: Default::default(),
+                external_identities: Vec::new(),
+                automation_preferences: Default::default(),
+                pending_email: None,
+                keycloak_id: None,
+                counted_games: Vec::new(),
             };
 
             users.push(user);
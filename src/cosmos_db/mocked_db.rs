@@ -4,12 +4,17 @@ use std::{collections::HashMap, sync::Arc};
 use crate::{
     log_return_bad_id, new_not_found_error,
     shared::{
-        service_models::PersistUser,
+        service_models::{
+            PersistAchievement, PersistGame, PersistGameEvent, PersistGameStats,
+            PersistOutboxMessage, PersistRefreshToken, PersistUser,
+        },
         shared_models::{GameError, ResponseType, ServiceResponse, UserProfile},
     },
+    user_service::oauth::OAuthProvider,
 };
 use async_trait::async_trait;
-use log::trace;
+use futures::{stream::BoxStream, StreamExt};
+use tracing::trace;
 use reqwest::StatusCode;
 use tokio::sync::RwLock;
 
@@ -21,11 +26,23 @@ lazy_static::lazy_static! {
 
 pub struct TestDb {
     pub users: Arc<RwLock<HashMap<String, PersistUser>>>,
+    pub games: Arc<RwLock<HashMap<String, PersistGame>>>,
+    pub game_events: Arc<RwLock<HashMap<String, PersistGameEvent>>>,
+    pub game_stats: Arc<RwLock<HashMap<String, PersistGameStats>>>,
+    pub achievements: Arc<RwLock<HashMap<String, PersistAchievement>>>,
+    pub outbox: Arc<RwLock<HashMap<String, PersistOutboxMessage>>>,
+    pub refresh_tokens: Arc<RwLock<HashMap<String, PersistRefreshToken>>>,
 }
 impl TestDb {
     pub fn new() -> Self {
         Self {
             users: Arc::new(RwLock::new(HashMap::new())),
+            games: Arc::new(RwLock::new(HashMap::new())),
+            game_events: Arc::new(RwLock::new(HashMap::new())),
+            game_stats: Arc::new(RwLock::new(HashMap::new())),
+            achievements: Arc::new(RwLock::new(HashMap::new())),
+            outbox: Arc::new(RwLock::new(HashMap::new())),
+            refresh_tokens: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 }
@@ -33,6 +50,12 @@ impl TestDb {
 impl UserDbTrait for TestDb {
     async fn setupdb(&self) -> Result<(), ServiceResponse> {
         MOCKED_DB.users.write().await.clear();
+        MOCKED_DB.games.write().await.clear();
+        MOCKED_DB.game_events.write().await.clear();
+        MOCKED_DB.game_stats.write().await.clear();
+        MOCKED_DB.achievements.write().await.clear();
+        MOCKED_DB.outbox.write().await.clear();
+        MOCKED_DB.refresh_tokens.write().await.clear();
         Ok(())
     }
 
@@ -113,6 +136,243 @@ impl UserDbTrait for TestDb {
             None => new_not_found_error!("Not Found"),
         }
     }
+
+    async fn find_user_by_external_identity(
+        &self,
+        provider: OAuthProvider,
+        subject: &str,
+    ) -> Result<PersistUser, ServiceResponse> {
+        match MOCKED_DB.users.read().await.values().find(|user| {
+            user.external_identities
+                .iter()
+                .any(|identity| identity.provider == provider && identity.subject == subject)
+        }) {
+            Some(user) => Ok(user.clone()),
+            None => new_not_found_error!("Not Found"),
+        }
+    }
+    async fn find_user_by_keycloak_id(&self, keycloak_id: &str) -> Result<PersistUser, ServiceResponse> {
+        match MOCKED_DB
+            .users
+            .read()
+            .await
+            .values()
+            .find(|user| user.keycloak_id.as_deref() == Some(keycloak_id))
+        {
+            Some(user) => Ok(user.clone()),
+            None => new_not_found_error!("Not Found"),
+        }
+    }
+
+    async fn update_or_create_game(
+        &self,
+        game: &PersistGame,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        MOCKED_DB
+            .games
+            .write()
+            .await
+            .insert(game.id.clone(), game.clone());
+        Ok(ServiceResponse::new_generic_ok("saved"))
+    }
+
+    async fn find_game_by_id(&self, game_id: &str) -> Result<PersistGame, ServiceResponse> {
+        match MOCKED_DB.games.read().await.get(game_id) {
+            Some(game) => Ok(game.clone()),
+            None => new_not_found_error!("Not Found"),
+        }
+    }
+    async fn list_games(&self, finished_since: u64) -> Result<Vec<PersistGame>, ServiceResponse> {
+        Ok(MOCKED_DB
+            .games
+            .read()
+            .await
+            .values()
+            .filter(|game| game.finished_at >= finished_since)
+            .cloned()
+            .collect())
+    }
+    async fn list_recent_games(&self, count: usize) -> Result<Vec<PersistGame>, ServiceResponse> {
+        let mut games: Vec<PersistGame> = MOCKED_DB
+            .games
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect();
+        games.sort_by(|a, b| b.finished_at.cmp(&a.finished_at));
+        games.truncate(count);
+        Ok(games)
+    }
+    fn stream_users(&self) -> BoxStream<'_, Result<PersistUser, ServiceResponse>> {
+        futures::stream::once(async { MOCKED_DB.users.read().await.values().cloned().collect::<Vec<_>>() })
+            .flat_map(|users| futures::stream::iter(users.into_iter().map(Ok)))
+            .boxed()
+    }
+    fn stream_games(&self, finished_since: u64) -> BoxStream<'_, Result<PersistGame, ServiceResponse>> {
+        futures::stream::once(async move {
+            MOCKED_DB
+                .games
+                .read()
+                .await
+                .values()
+                .filter(|game| game.finished_at >= finished_since)
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .flat_map(|games| futures::stream::iter(games.into_iter().map(Ok)))
+        .boxed()
+    }
+    async fn append_game_event(
+        &self,
+        event: &PersistGameEvent,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        MOCKED_DB
+            .game_events
+            .write()
+            .await
+            .insert(event.id.clone(), event.clone());
+        Ok(ServiceResponse::new_generic_ok("saved"))
+    }
+    async fn find_game_events(&self, game_id: &str) -> Result<Vec<PersistGameEvent>, ServiceResponse> {
+        let mut events: Vec<PersistGameEvent> = MOCKED_DB
+            .game_events
+            .read()
+            .await
+            .values()
+            .filter(|event| event.game_id == game_id)
+            .cloned()
+            .collect();
+        events.sort_by_key(|event| event.game_index);
+        Ok(events)
+    }
+    async fn update_game_stats(
+        &self,
+        stats: &PersistGameStats,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        MOCKED_DB
+            .game_stats
+            .write()
+            .await
+            .insert(stats.id.clone(), stats.clone());
+        Ok(ServiceResponse::new_generic_ok("saved"))
+    }
+    async fn find_game_stats(&self, user_id: &str) -> Result<Vec<PersistGameStats>, ServiceResponse> {
+        let mut stats: Vec<PersistGameStats> = MOCKED_DB
+            .game_stats
+            .read()
+            .await
+            .values()
+            .filter(|stats| stats.user_id == user_id)
+            .cloned()
+            .collect();
+        stats.sort_by(|a, b| b.finished_at.cmp(&a.finished_at));
+        Ok(stats)
+    }
+    async fn add_achievement(
+        &self,
+        achievement: &PersistAchievement,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let mut achievements = MOCKED_DB.achievements.write().await;
+        if achievements.contains_key(&achievement.id) {
+            return Err(ServiceResponse::new(
+                "already unlocked",
+                StatusCode::CONFLICT,
+                ResponseType::NoData,
+                GameError::HttpError(StatusCode::CONFLICT),
+            ));
+        }
+        achievements.insert(achievement.id.clone(), achievement.clone());
+        Ok(ServiceResponse::new_generic_ok("saved"))
+    }
+    async fn find_achievements(&self, user_id: &str) -> Result<Vec<PersistAchievement>, ServiceResponse> {
+        let mut achievements: Vec<PersistAchievement> = MOCKED_DB
+            .achievements
+            .read()
+            .await
+            .values()
+            .filter(|achievement| achievement.user_id == user_id)
+            .cloned()
+            .collect();
+        achievements.sort_by_key(|achievement| achievement.unlocked_at);
+        Ok(achievements)
+    }
+    async fn add_to_outbox(
+        &self,
+        message: &PersistOutboxMessage,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        MOCKED_DB
+            .outbox
+            .write()
+            .await
+            .insert(message.id.clone(), message.clone());
+        Ok(ServiceResponse::new_generic_ok("saved"))
+    }
+    /// expired messages are removed here, the way Cosmos's ttl removes them
+    async fn find_outbox(&self, user_id: &str, now: u64) -> Result<Vec<PersistOutboxMessage>, ServiceResponse> {
+        let mut outbox = MOCKED_DB.outbox.write().await;
+        outbox.retain(|_, message| !message.expired(now));
+        let mut messages: Vec<PersistOutboxMessage> = outbox
+            .values()
+            .filter(|message| message.user_id == user_id)
+            .cloned()
+            .collect();
+        messages.sort_by_key(|message| (message.queued_at, message.message.user_sequence));
+        Ok(messages)
+    }
+    async fn delete_outbox_message(&self, id: &str) -> Result<(), ServiceResponse> {
+        match MOCKED_DB.outbox.write().await.remove(id) {
+            Some(_) => Ok(()),
+            None => new_not_found_error!(&format!("outbox message {}", id)),
+        }
+    }
+    async fn save_refresh_token(
+        &self,
+        token: &PersistRefreshToken,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        MOCKED_DB
+            .refresh_tokens
+            .write()
+            .await
+            .insert(token.id.clone(), token.clone());
+        Ok(ServiceResponse::new_generic_ok("saved"))
+    }
+    async fn find_refresh_token(&self, id: &str, now: u64) -> Result<PersistRefreshToken, ServiceResponse> {
+        match MOCKED_DB.refresh_tokens.read().await.get(id) {
+            Some(token) if token.expires_at > now => Ok(token.clone()),
+            _ => new_not_found_error!("not found"),
+        }
+    }
+    async fn use_refresh_token(&self, token: &PersistRefreshToken) -> Result<bool, ServiceResponse> {
+        match MOCKED_DB.refresh_tokens.write().await.get_mut(&token.id) {
+            Some(stored) if !stored.used => {
+                stored.used = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+    async fn delete_refresh_tokens(&self, family_id: &str) -> Result<(), ServiceResponse> {
+        MOCKED_DB
+            .refresh_tokens
+            .write()
+            .await
+            .retain(|_, token| token.family_id != family_id);
+        Ok(())
+    }
+    async fn delete_user_refresh_tokens(
+        &self,
+        user_id: &str,
+        session_id: Option<&str>,
+    ) -> Result<(), ServiceResponse> {
+        MOCKED_DB.refresh_tokens.write().await.retain(|_, token| {
+            token.partition_key != user_id || session_id.map_or(false, |session_id| token.session_id != session_id)
+        });
+        Ok(())
+    }
+    async fn health_check(&self) -> Result<(), ServiceResponse> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
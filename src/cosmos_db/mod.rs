@@ -1,3 +1,4 @@
-
+pub mod connection_manager;
 pub mod cosmosdb;
 pub mod mocked_db;
+pub mod schema;
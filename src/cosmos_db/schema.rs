@@ -0,0 +1,135 @@
+#![allow(dead_code)]
+/**
+ *  checks we run once at startup so that a misconfigured database fails the boot with a useful message, instead of
+ *  failing the first user request with a 500.
+ *
+ *  1. every collection exists and is partitioned on PARTITION_KEY_PATH
+ *  2. the documents we store serialize with the "id" and "partitionKey" fields Cosmos requires, and survive a round
+ *     trip through JSON unchanged
+ */
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    games_service::{
+        catan_games::games::regular::regular_game::RegularGame,
+        game_container::game_messages::CatanMessage,
+        long_poller::channels::{ChannelMessage, MessageChannel},
+    },
+    middleware::service_config::ServiceConfig,
+    shared::{
+        service_models::{PersistGame, PersistOutboxMessage, PersistUser},
+        shared_models::UserProfile,
+    },
+};
+
+use super::cosmosdb::{UserDb, PARTITION_KEY_PATH};
+
+/// make sure a sample document has the fields Cosmos needs and comes back out of JSON the same as it went in
+pub fn verify_document_contract<T>(name: &str, sample: &T) -> Result<(), String>
+where
+    T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let value = serde_json::to_value(sample)
+        .map_err(|e| format!("{} failed to serialize: {}", name, e))?;
+    let partition_key_field = PARTITION_KEY_PATH.trim_start_matches('/');
+    for field in ["id", partition_key_field] {
+        if value.get(field).is_none() {
+            return Err(format!(
+                "{} does not serialize an \"{}\" field, which Cosmos requires. check its serde renames",
+                name, field
+            ));
+        }
+    }
+
+    let round_trip: T = serde_json::from_value(value)
+        .map_err(|e| format!("{} failed to deserialize its own JSON: {}", name, e))?;
+    if round_trip != *sample {
+        return Err(format!(
+            "{} changed after a round trip through JSON. a field is probably skipped or defaulted",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// the document contracts for everything we store
+pub fn verify_document_contracts() -> Vec<String> {
+    let profile = UserProfile::new_test_user(None);
+    let user = PersistUser::from_user_profile(&profile, "hash".to_string());
+    let game = PersistGame::from_game(&RegularGame::new(&profile), profile.user_id.clone());
+    let message = ChannelMessage {
+        channel: MessageChannel::Lobby,
+        sequence: 1,
+        user_sequence: 1,
+        message: CatanMessage::Started(game.id.clone()),
+    };
+    let kept = PersistOutboxMessage::new("user", "boot", &message, 0, 60);
+
+    vec![
+        verify_document_contract("PersistUser", &user),
+        verify_document_contract("PersistGame", &game),
+        verify_document_contract("PersistOutboxMessage", &kept),
+    ]
+    .into_iter()
+    .filter_map(|result| result.err())
+    .collect()
+}
+
+/**
+ *  run all of the checks against the production database.  every problem is returned, not just the first one, so
+ *  that they can all be fixed in one go.
+ */
+pub async fn verify_schema(service_config: &'static ServiceConfig) -> Result<(), Vec<String>> {
+    let mut problems = verify_document_contracts();
+    problems.extend(UserDb::new(false, service_config).verify_collections().await);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct NoPartitionKey {
+        id: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct SkipsAField {
+        id: String,
+        #[serde(rename = "partitionKey")]
+        partition_key: u64,
+        #[serde(skip)]
+        lost: u32,
+    }
+
+    #[test]
+    fn test_document_contracts() {
+        assert_eq!(verify_document_contracts(), Vec::<String>::new());
+
+        let err = verify_document_contract(
+            "NoPartitionKey",
+            &NoPartitionKey {
+                id: "1".to_string(),
+            },
+        )
+        .expect_err("partitionKey is missing");
+        assert!(err.contains("partitionKey"));
+
+        let err = verify_document_contract(
+            "SkipsAField",
+            &SkipsAField {
+                id: "1".to_string(),
+                partition_key: 1,
+                lost: 5,
+            },
+        )
+        .expect_err("lost doesn't round trip");
+        assert!(err.contains("round trip"));
+    }
+}
@@ -0,0 +1,148 @@
+#![allow(dead_code)]
+/**
+ *  achievements: milestones a player unlocks once, ever -- their first win, winning without Longest Road, rolling
+ *  three 7s in one game...
+ *
+ *  every game that is pushed (see push_and_return_actions) is compared with the game before it, and an achievement is
+ *  earned at the moment its condition becomes true.  earning one doesn't mean it is new: the Achievement collection
+ *  has one document per user and achievement, and only the first write succeeds -- that player gets an
+ *  AchievementUnlocked message.  all of this happens in the background, after the action has been answered.
+ */
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+use crate::{
+    games_service::{
+        bots::bots::is_bot,
+        catan_games::games::regular::regular_game::RegularGame,
+        game_container::game_messages::CatanMessage,
+        long_poller::long_poller::LongPoller,
+        shared::game_enums::GameState,
+    },
+    middleware::request_context_mw::RequestContext,
+    shared::{error_reporting, service_models::PersistAchievement},
+};
+
+/// how many 7s a player has to roll in one game for SevensTrio
+pub const SEVENS_FOR_TRIO: u32 = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, JsonSchema)]
+pub enum Achievement {
+    FirstWin,              // win a game
+    WinWithoutLongestRoad, // win a game while somebody else (or nobody) holds Longest Road
+    SevensTrio,            // roll SEVENS_FOR_TRIO 7s in one game
+    LongestRoad,           // take Longest Road
+    LargestArmy,           // take Largest Army
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct AchievementUnlockedData {
+    pub user_id: String,
+    pub achievement: Achievement,
+    pub game_id: String,
+}
+
+/// the achievements that were earned between `previous` and `game`, by user id
+pub fn earned(previous: &RegularGame, game: &RegularGame) -> Vec<(String, Achievement)> {
+    let mut earned = Vec::new();
+    if previous.game_state != GameState::GameOver && game.game_state == GameState::GameOver {
+        if let Some(winner_id) = &game.winner_id {
+            earned.push((winner_id.clone(), Achievement::FirstWin));
+            if game.longest_road_holder.as_ref() != Some(winner_id) {
+                earned.push((winner_id.clone(), Achievement::WinWithoutLongestRoad));
+            }
+        }
+    }
+    for (user_id, player) in game.players.iter() {
+        let sevens_before = previous.players.get(user_id).map_or(0, |p| p.sevens_rolled);
+        if sevens_before < SEVENS_FOR_TRIO && player.sevens_rolled >= SEVENS_FOR_TRIO {
+            earned.push((user_id.clone(), Achievement::SevensTrio));
+        }
+    }
+    if let Some(holder) = &game.longest_road_holder {
+        if previous.longest_road_holder.as_ref() != Some(holder) {
+            earned.push((holder.clone(), Achievement::LongestRoad));
+        }
+    }
+    if let Some(holder) = &game.largest_army_holder {
+        if previous.largest_army_holder.as_ref() != Some(holder) {
+            earned.push((holder.clone(), Achievement::LargestArmy));
+        }
+    }
+    earned.retain(|(user_id, _)| !is_bot(user_id));
+    earned
+}
+
+/// record what was earned between the two games, and tell the players about the achievements that are new to them
+pub fn on_game_pushed(previous: &RegularGame, game: &RegularGame, request_context: &RequestContext) {
+    let earned = earned(previous, game);
+    if earned.is_empty() {
+        return;
+    }
+    let game_id = game.id.clone();
+    let request_context = request_context.clone();
+    actix_web::rt::spawn(async move {
+        let unlocked_at = request_context.environment.now();
+        for (user_id, achievement) in earned {
+            let persist = PersistAchievement::new(&user_id, achievement, &game_id, unlocked_at);
+            match request_context.database.add_achievement(&persist).await {
+                Ok(_) => {}
+                Err(e) if e.status == StatusCode::CONFLICT => continue, // they already have it
+                Err(e) => {
+                    let message = format!("failed to save {:?} for {}: {:#?}", achievement, user_id, e);
+                    tracing::error!("{}", message);
+                    error_reporting::report_background_failure("achievements", &message);
+                    continue;
+                }
+            }
+            let message = CatanMessage::AchievementUnlocked(AchievementUnlockedData {
+                user_id: user_id.clone(),
+                achievement,
+                game_id: game_id.clone(),
+            });
+            //  a player who isn't connected sees it with the rest of their achievements instead
+            let _ = LongPoller::send_message(vec![user_id], &message).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{games_service::catan_games::traits::game_trait::GameTrait, shared::shared_models::UserProfile};
+
+    #[test]
+    fn test_earned() {
+        let mut previous = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut previous, &UserProfile::new_test_user(Some("2".to_string())));
+        previous.game_state = GameState::BuyingAndTrading;
+        assert!(earned(&previous, &previous).is_empty());
+
+        let mut game = previous.clone();
+        game.players.get_mut("2").unwrap().sevens_rolled = SEVENS_FOR_TRIO;
+        game.longest_road_holder = Some("2".to_string());
+        assert_eq!(
+            earned(&previous, &game),
+            vec![
+                ("2".to_string(), Achievement::SevensTrio),
+                ("2".to_string(), Achievement::LongestRoad)
+            ]
+        );
+
+        // only when the condition becomes true: holding on to Longest Road doesn't earn it again
+        let mut over = game.clone();
+        over.game_state = GameState::GameOver;
+        over.winner_id = Some("1".to_string());
+        over.players.get_mut("2").unwrap().sevens_rolled = SEVENS_FOR_TRIO + 1;
+        assert_eq!(
+            earned(&game, &over),
+            vec![
+                ("1".to_string(), Achievement::FirstWin),
+                ("1".to_string(), Achievement::WinWithoutLongestRoad)
+            ]
+        );
+    }
+}
@@ -6,19 +6,33 @@ use reqwest::StatusCode;
 use crate::{
     games_service::{
         catan_games::traits::game_trait::GameTrait, game_container::game_container::GameContainer,
-        shared::game_enums::GameAction,
+        shared::{
+            game_enums::{DevCardType, GameAction, ResourceType},
+            game_models::{
+                BankTradeData, BestBankTradeData, BuildData, DevCardResolutionData, MoveBaronData,
+                ResourceCards, RollData, TradeOfferData,
+            },
+        },
     },
+    middleware::request_context_mw::RequestContext,
 
     user_service::user_handlers::create_http_response,
 };
 
+use super::local_seats::ActingPlayer;
+
 /**
  * this module takes the HTTP requests, calls the appropriate api in action.rs and then constructs the appropriate
- * HTTP response
+ * HTTP response.  the player an action is for is the caller, or the local user they name in X-Acting-As (see
+ * local_seats.rs)
  */
 
-pub async fn next(game_id: web::Path<String>) -> impl Responder {
-    super::actions::next(&game_id).await
+pub async fn next(
+    game_id: web::Path<String>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::actions::next(&game_id, Some(&player.player_id), &request_context).await
     .map(|sr| sr.to_http_response())
     .unwrap_or_else(|sr| sr.to_http_response())
 }
@@ -30,3 +44,237 @@ pub async fn valid_actions(game_id: web::Path<String>, _req: HttpRequest) -> imp
     .map(|sr| sr.to_http_response())
     .unwrap_or_else(|sr| sr.to_http_response())
 }
+/**
+ * explain why the caller can or can't take the action in the current state of the game
+ */
+pub async fn explain_action(
+    path: web::Path<(String, GameAction)>,
+    player: ActingPlayer,
+) -> impl Responder {
+    let (game_id, action) = path.into_inner();
+    super::actions::explain_action(&game_id, &action, &player.player_id).await
+    .map(|sr| sr.to_http_response())
+    .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+/**
+ * roll the dice for the current player.  the body is optional and is ignored unless this is a test request
+ */
+pub async fn roll(
+    game_id: web::Path<String>,
+    test_roll: Option<web::Json<RollData>>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    let test_roll = if request_context.is_test() {
+        test_roll.map(|json| json.into_inner())
+    } else {
+        None
+    };
+    super::actions::roll(&game_id, &player.player_id, test_roll, &request_context).await
+    .map(|sr| sr.to_http_response())
+    .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn discard(
+    game_id: web::Path<String>,
+    cards: web::Json<ResourceCards>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::actions::discard(&game_id, &player.player_id, &cards, &request_context).await
+    .map(|sr| sr.to_http_response())
+    .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn move_baron(
+    game_id: web::Path<String>,
+    move_baron_data: web::Json<MoveBaronData>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::actions::move_baron(
+        &game_id,
+        &player.player_id,
+        &move_baron_data,
+        &request_context,
+    )
+    .await
+    .map(|sr| sr.to_http_response())
+    .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn build(
+    game_id: web::Path<String>,
+    build_data: web::Json<BuildData>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::actions::build(&game_id, &player.player_id, &build_data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+/**
+ * the caller gives up the game they are playing
+ */
+pub async fn forfeit(
+    game_id: web::Path<String>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    if player.delegated {
+        return super::delegation::only_the_player("forfeit").to_http_response();
+    }
+    super::forfeit::forfeit(&game_id, &player.player_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn offer_trade(
+    game_id: web::Path<String>,
+    data: web::Json<TradeOfferData>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::trades::offer_trade(&game_id, &player.player_id, &data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn accept_trade(
+    path: web::Path<(String, String)>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    let (game_id, offer_id) = path.into_inner();
+    super::trades::accept_trade(&game_id, &offer_id, &player.player_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn reject_trade(
+    path: web::Path<(String, String)>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    let (game_id, offer_id) = path.into_inner();
+    super::trades::reject_trade(&game_id, &offer_id, &player.player_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn counter_trade(
+    path: web::Path<(String, String)>,
+    data: web::Json<TradeOfferData>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    let (game_id, offer_id) = path.into_inner();
+    super::trades::counter_trade(
+        &game_id,
+        &offer_id,
+        &player.player_id,
+        &data,
+        &request_context,
+    )
+    .await
+    .map(|sr| sr.to_http_response())
+    .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn bank_trade(
+    game_id: web::Path<String>,
+    data: web::Json<BankTradeData>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::trades::bank_trade(&game_id, &player.player_id, &data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn best_bank_trade(
+    game_id: web::Path<String>,
+    data: web::Json<BestBankTradeData>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::trades::best_bank_trade(&game_id, &player.player_id, &data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn play_dev_card(
+    game_id: web::Path<String>,
+    card: web::Json<DevCardType>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::dev_cards::play_dev_card(&game_id, &player.player_id, *card, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn resolve_dev_card(
+    game_id: web::Path<String>,
+    data: web::Json<DevCardResolutionData>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::dev_cards::resolve_dev_card(&game_id, &player.player_id, &data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn undo(game_id: web::Path<String>, player: ActingPlayer) -> impl Responder {
+    super::actions::undo(&game_id, &player.player_id)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn redo(game_id: web::Path<String>, player: ActingPlayer) -> impl Responder {
+    super::actions::redo(&game_id, &player.player_id)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn play_monopoly(
+    game_id: web::Path<String>,
+    resource: web::Json<ResourceType>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::dev_cards::play_monopoly(&game_id, &player.player_id, *resource, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn play_year_of_plenty(
+    game_id: web::Path<String>,
+    resources: web::Json<[ResourceType; 2]>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> impl Responder {
+    super::dev_cards::play_year_of_plenty(
+        &game_id,
+        &player.player_id,
+        *resources,
+        &request_context,
+    )
+    .await
+    .map(|sr| sr.to_http_response())
+    .unwrap_or_else(|sr| sr.to_http_response())
+}
@@ -1,18 +1,44 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
+use std::time::Duration;
+
 use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use reqwest::StatusCode;
 
 use crate::{
     games_service::{
-        catan_games::traits::game_trait::GameTrait, game_container::game_container::GameContainer,
-        shared::game_enums::GameAction,
+        achievements::achievements,
+        catan_games::games::regular::{baron::DISCARD_TIMEOUT_SECONDS, regular_game::RegularGame},
+        catan_games::traits::{game_info_trait::GameInfoTrait, game_trait::GameTrait},
+        game_container::{
+            event_log,
+            game_container::GameContainer,
+            game_messages::{CatanMessage, GameWonData, TurnSummary},
+            game_over::GameOverPipeline,
+        },
+        webhooks::{self, WebhookEvent},
+        shared::{
+            game_enums::{GameAction, GameState},
+            game_models::{
+                ActionExplanation, ActionPrecondition, BuildData, MoveBaronData, ResourceCards,
+                RollData,
+            },
+        },
     },
+    middleware::request_context_mw::RequestContext,
     shared::shared_models::{GameError, ResponseType, ServiceResponse},
-    user_service::user_handlers::create_http_response,
+    user_service::{
+        push_notifications::{self, PushNotification},
+        user_handlers::create_http_response,
+    },
 };
 
-pub async fn next(game_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+#[tracing::instrument(skip_all, fields(game = %game_id))]
+pub async fn next(
+    game_id: &str,
+    actor_id: Option<&str>,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
     let (game, can_redo) = match GameContainer::current_game(game_id).await {
         Ok(g) => g,
         Err(e) => {
@@ -24,6 +50,9 @@ pub async fn next(game_id: &str) -> Result<ServiceResponse, ServiceResponse> {
             ))
         }
     };
+    if let Some(actor_id) = actor_id {
+        verify_can_move_on(&game, actor_id)?;
+    }
     let actions = game.valid_actions(can_redo);
     if !actions.contains(&GameAction::Next) {
         return Err(ServiceResponse::new(
@@ -38,21 +67,88 @@ pub async fn next(game_id: &str) -> Result<ServiceResponse, ServiceResponse> {
     // so that the client can enable the next button based on the existence of the action...eg if the game doesn't
     // have enough players, we won't give them a "next" action. or if there are unspend entitlements, etc.
 
-    let game_clone = game.set_next_state().unwrap();
-    let _ = GameContainer::push_game(game_id, &game_clone).await;
+    let mut game_clone = game.set_next_state().unwrap();
+    //  the game has started: from now on, players who walk away are forfeited
+    if game.game_state == GameState::SettingPlayerOrder
+        && game_clone.game_state == GameState::AllocateResourceForward
+    {
+        super::forfeit::start_abandonment_monitor(game_id, request_context);
+        webhooks::fire(game_id, WebhookEvent::GameStarted, None, None, request_context);
+    }
+    //  the first roll: a timed game's clock starts now
+    if game.game_state == GameState::AllocateResourceReverse
+        && game_clone.game_state == GameState::WaitingForRoll
+    {
+        if let Some(ends_at) = game_clone.start_clock(request_context.environment.now()) {
+            start_game_clock(game_id, ends_at, request_context);
+        }
+    }
+    let response =
+        push_and_return_actions(game_id, &game_clone, "Next", actor_id, request_context).await?;
+
+    //  somebody's turn has started -- let them know if they aren't looking
+    if game_clone.game_state == GameState::WaitingForRoll && game.game_state != GameState::WaitingForRoll {
+        push_notifications::notify(
+            &[game_clone.current_player_id.clone()],
+            game_id,
+            |language| PushNotification::turn_started(game_id, language),
+            request_context,
+        );
+        webhooks::fire(
+            game_id,
+            WebhookEvent::TurnChanged,
+            Some(&game_clone.current_player_id),
+            None,
+            request_context,
+        );
+    }
+
+    //  the turn is over -- tell everybody what happened in it.  this has to come from the old game, since ending the
+    //  turn clears the ledger
+    if game.game_state == GameState::BuyingAndTrading {
+        let summary = TurnSummary::from_game(&game);
+        if let Err(e) =
+            GameContainer::broadcast_message(game_id, &CatanMessage::TurnSummary(summary)).await
+        {
+            tracing::warn!("failed to send TurnSummary for {}: {:#?}", game_id, e);
+        }
+    }
+    Ok(response)
+}
+/**
+ * look at the state of the game and answer the question "what are the valid actions"
+ */
+pub async fn valid_actions(game_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+
+
+    let (game, can_redo) = match GameContainer::current_game(game_id).await {
+        Ok(g) => g,
+        Err(e) => {
+            return Err(ServiceResponse::new(
+                &format!("invalid game id: {}", game_id),
+                StatusCode::NOT_FOUND,
+                ResponseType::ErrorInfo(format!("{:#?}", e)),
+                GameError::HttpError(StatusCode::NOT_FOUND),
+            ))
+        }
+    };
     Ok(ServiceResponse::new(
         "",
         StatusCode::OK,
-        ResponseType::ValidActions(game_clone.valid_actions(can_redo)),
+        ResponseType::ValidActions(game.valid_actions(can_redo)),
         GameError::NoError(String::default()),
     ))
 }
+
 /**
- * look at the state of the game and answer the question "what are the valid actions"
+ * answer the question "why can (or can't) I do this action right now?"  we break the answer down into the
+ * individual rules so that the client can show the user something better than a disabled button.
  */
-pub async fn valid_actions(game_id: &str) -> Result<ServiceResponse, ServiceResponse> {
-
-
+pub async fn explain_action(
+    game_id: &str,
+    action: &GameAction,
+    caller_id: &str,
+) -> Result<ServiceResponse, ServiceResponse> {
     let (game, can_redo) = match GameContainer::current_game(game_id).await {
         Ok(g) => g,
         Err(e) => {
@@ -64,6 +160,327 @@ pub async fn valid_actions(game_id: &str) -> Result<ServiceResponse, ServiceResp
             ))
         }
     };
+
+    let mut preconditions = vec![ActionPrecondition::new(
+        "caller is a player in this game",
+        game.players.contains_key(caller_id),
+    )];
+
+    match action {
+        //  discarding after a 7 is the one action that doesn't wait for your turn
+        GameAction::Discard => preconditions.push(ActionPrecondition::new(
+            "caller has cards to discard",
+            game.pending_discards.contains_key(caller_id),
+        )),
+        //  any player can offer the current player a trade
+        GameAction::Trade => {}
+        _ => preconditions.push(ActionPrecondition::new(
+            "it is the caller's turn",
+            game.current_player_id == caller_id,
+        )),
+    }
+
+    match action {
+        GameAction::AddPlayer => preconditions.push(ActionPrecondition::new(
+            &format!("game has fewer than {} players", game.max_players()),
+            game.players.len() < game.max_players(),
+        )),
+        GameAction::Next if game.game_state == GameState::AddingPlayers => {
+            preconditions.push(ActionPrecondition::new(
+                &format!("game has at least {} players", game.min_players()),
+                game.players.len() >= game.min_players(),
+            ))
+        }
+        GameAction::Redo => {
+            preconditions.push(ActionPrecondition::new("there is an action to redo", can_redo))
+        }
+        _ => {}
+    }
+
+    preconditions.push(ActionPrecondition::new(
+        &format!("{:?} is allowed in the {:?} state", action, game.game_state),
+        game.valid_actions(can_redo).contains(action),
+    ));
+
+    let explanation = ActionExplanation {
+        action: action.clone(),
+        game_state: game.game_state,
+        is_legal: preconditions.iter().all(|p| p.satisfied),
+        preconditions,
+    };
+
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::ActionExplanation(explanation),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub(super) async fn current_game_or_not_found(
+    game_id: &str,
+) -> Result<(RegularGame, bool), ServiceResponse> {
+    GameContainer::current_game(game_id).await.map_err(|e| {
+        ServiceResponse::new(
+            &format!("invalid game id: {}", game_id),
+            StatusCode::NOT_FOUND,
+            ResponseType::ErrorInfo(format!("{:#?}", e)),
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        )
+    })
+}
+
+pub(super) fn rejected_action(error: GameError) -> ServiceResponse {
+    ServiceResponse::new(
+        "action rejected",
+        StatusCode::BAD_REQUEST,
+        ResponseType::ErrorInfo(format!("{}", error)),
+        error,
+    )
+}
+
+fn verify_current_player(game: &RegularGame, caller_id: &str) -> Result<(), ServiceResponse> {
+    if game.current_player_id != caller_id {
+        return Err(rejected_action(GameError::ActionError(format!(
+            "it is {}'s turn, not {}'s",
+            game.current_player_id, caller_id
+        ))));
+    }
+    Ok(())
+}
+
+/// before the game starts only its creator can move it on (and so start it).  after that, only the current player can
+/// end their turn
+fn verify_can_move_on(game: &RegularGame, actor_id: &str) -> Result<(), ServiceResponse> {
+    match game.game_state {
+        GameState::AddingPlayers | GameState::ChoosingBoard | GameState::SettingPlayerOrder => {
+            if game.creator_id != actor_id {
+                return Err(rejected_action(GameError::ActionError(format!(
+                    "only the game's creator ({}) can start it, not {}",
+                    game.creator_id, actor_id
+                ))));
+            }
+            Ok(())
+        }
+        _ => verify_current_player(game, actor_id),
+    }
+}
+
+///
+/// score the new game, push it (which broadcasts it to the players) and tell the caller what they can do next.  if the
+/// action won the game, this is where GameWon is sent and the game over cleanup is started.  the action and who did
+/// it (None for the service's timers) go in the game's event log, which is written from here
+pub(super) async fn push_and_return_actions(
+    game_id: &str,
+    game: &RegularGame,
+    action: &str,
+    actor_id: Option<&str>,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    if let Some(actor_id) = actor_id {
+        super::local_seats::verify_capability(game, actor_id, action)?;
+    }
+    let mut game = game.clone();
+    game.update_scores();
+    game.end_if_time_is_up(request_context.environment.now());
+    let (previous, _) = current_game_or_not_found(game_id).await?;
+    //  somebody playing the seat for its player is in the event log too
+    let delegate = actor_id.and_then(|seat| super::delegation::acting_for(&previous, seat, request_context));
+    let pushed = match delegate {
+        Some(delegate) => {
+            GameContainer::push_game_for(game_id, &game, action, Some(delegate.as_str()), actor_id).await?
+        }
+        None => GameContainer::push_game(game_id, &game, action, actor_id).await?,
+    };
+    event_log::flush(game_id, request_context).await;
+    achievements::on_game_pushed(&previous, &pushed, request_context);
+    super::automation::after_push(game_id, &previous, &pushed, request_context);
+    if let Some(seconds) = game.auto_end_turn_after() {
+        start_auto_end_turn(game_id, pushed.game_index, seconds, request_context);
+    }
+    if game.game_state == GameState::GameOver {
+        if let Some(won) = GameWonData::from_game(&game) {
+            if let Err(e) =
+                GameContainer::broadcast_message(game_id, &CatanMessage::GameWon(won)).await
+            {
+                tracing::warn!("failed to send GameWon for {}: {:#?}", game_id, e);
+            }
+        }
+        GameOverPipeline::default().start(&game, game.winner_id.clone(), request_context);
+    }
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::ValidActions(game.valid_actions(false)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+/**
+ * the current player rolls the dice.  test games can pass in the roll so that the 7 flow can be tested.
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn roll(
+    game_id: &str,
+    caller_id: &str,
+    test_roll: Option<RollData>,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    verify_current_player(&game, caller_id)?;
+
+    //  a roll that was undone comes out the same when the turn rolls again
+    let roll = match (test_roll, GameContainer::rolled_this_turn(game_id).await?) {
+        (Some(data), _) => data.roll,
+        (None, Some(roll)) => roll,
+        (None, None) => request_context.environment.roll_dice(),
+    };
+
+    let mut new_game = game.roll(roll).map_err(rejected_action)?;
+    if new_game.game_state == GameState::MustDiscard {
+        let deadline = request_context.environment.now() + DISCARD_TIMEOUT_SECONDS;
+        new_game.discard_deadline = Some(deadline);
+        start_discard_timer(game_id, deadline, request_context);
+    }
+    push_and_return_actions(game_id, &new_game, "Roll", Some(caller_id), request_context).await
+}
+
+/// when a timed game's time is up, end it -- even if nobody is doing anything
+fn start_game_clock(game_id: &str, ends_at: u64, request_context: &RequestContext) {
+    let game_id = game_id.to_owned();
+    let request_context = request_context.clone();
+    actix_web::rt::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(ends_at.saturating_sub(request_context.environment.now()))).await;
+        let game = match GameContainer::current_game(&game_id).await {
+            Ok((game, _)) => game,
+            Err(_) => return, // the game is over
+        };
+        let mut new_game = game.clone();
+        if !new_game.end_if_time_is_up(request_context.environment.now()) {
+            return; // already over, or the clock was undone
+        }
+        let result =
+            push_and_return_actions(&game_id, &new_game, "TimeUp", None, &request_context).await;
+        if let Err(e) = result {
+            tracing::warn!("failed to end {} when its time was up: {:#?}", game_id, e);
+        }
+    });
+}
+
+/// end the turn for a player who has had nothing to do but end it for the grace period.  anything that happens in the
+/// game in the meantime (a trade offer, an undo, the player ending the turn themselves) pushes a new game_index,
+/// which cancels this.  the turn is ended by the service, not by whoever pushed the last action
+fn start_auto_end_turn(game_id: &str, game_index: u32, seconds: u32, request_context: &RequestContext) {
+    let game_id = game_id.to_owned();
+    let request_context = request_context.system_context();
+    actix_web::rt::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(seconds as u64)).await;
+        let game = match GameContainer::current_game(&game_id).await {
+            Ok((game, _)) => game,
+            Err(_) => return, // the game is over
+        };
+        if game.game_index != game_index || game.auto_end_turn_after().is_none() {
+            return;
+        }
+        if let Err(e) = next(&game_id, None, &request_context).await {
+            tracing::warn!("failed to end the turn in {} automatically: {:#?}", game_id, e);
+        }
+    });
+}
+
+/// once the deadline passes, discard for anybody who still owes cards so the game can go on
+fn start_discard_timer(game_id: &str, deadline: u64, request_context: &RequestContext) {
+    let game_id = game_id.to_owned();
+    let request_context = request_context.clone();
+    actix_web::rt::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(DISCARD_TIMEOUT_SECONDS)).await;
+        let game = match GameContainer::current_game(&game_id).await {
+            Ok((game, _)) => game,
+            Err(_) => return, // the game is over
+        };
+        //  everybody discarded in time, or the 7 was undone (and maybe rolled again, with its own timer)
+        if game.game_state != GameState::MustDiscard || game.discard_deadline != Some(deadline) {
+            return;
+        }
+        let result = match game.force_discards(request_context.environment.now()) {
+            Ok(new_game) => push_and_return_actions(
+                &game_id,
+                &new_game,
+                "ForcedDiscard",
+                None,
+                &request_context,
+            )
+            .await
+            .map(|_| ()),
+            Err(e) => Err(rejected_action(e)),
+        };
+        if let Err(e) = result {
+            tracing::warn!("failed to discard for the players in {}: {:#?}", game_id, e);
+        }
+    });
+}
+
+/**
+ * after a 7, each player holding too many cards picks which ones to give up.  any player that owes cards can call
+ * this - it does not need to be their turn - and they can send the cards in more than one call.  whatever is still
+ * owed after DISCARD_TIMEOUT_SECONDS is discarded for them.
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn discard(
+    game_id: &str,
+    caller_id: &str,
+    cards: &ResourceCards,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game.discard(caller_id, cards).map_err(rejected_action)?;
+    push_and_return_actions(game_id, &new_game, "Discard", Some(caller_id), request_context).await
+}
+
+/**
+ * the current player moves the baron and (if possible) steals a random card from a player on the new tile
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn move_baron(
+    game_id: &str,
+    caller_id: &str,
+    move_baron_data: &MoveBaronData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    verify_current_player(&game, caller_id)?;
+    let new_game = game
+        .move_baron(caller_id, move_baron_data)
+        .map_err(rejected_action)?;
+    push_and_return_actions(game_id, &new_game, "MoveBaron", Some(caller_id), request_context).await
+}
+
+/**
+ * the current player builds a settlement, city or road.  during setup these are the free starting pieces
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn build(
+    game_id: &str,
+    caller_id: &str,
+    build_data: &BuildData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    verify_current_player(&game, caller_id)?;
+    let new_game = game.build(caller_id, build_data).map_err(rejected_action)?;
+    push_and_return_actions(game_id, &new_game, "Build", Some(caller_id), request_context).await
+}
+
+/// undo and redo change the game for everybody, so only the current player or the player who created the game can
+/// use them
+/// a local user's owner can lock their seat out of undo (see local_seats.rs).  who took the action is checked by the
+/// container
+fn verify_undo_caller(game: &RegularGame, caller_id: &str) -> Result<(), ServiceResponse> {
+    super::local_seats::verify_capability(game, caller_id, "Undo")
+}
+
+async fn actions_after_undo_or_redo(game_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, can_redo) = current_game_or_not_found(game_id).await?;
     Ok(ServiceResponse::new(
         "",
         StatusCode::OK,
@@ -71,3 +488,61 @@ pub async fn valid_actions(game_id: &str) -> Result<ServiceResponse, ServiceResp
         GameError::NoError(String::default()),
     ))
 }
+
+/**
+ * take back the last action.  only the player who took it can.  every player gets the game as it was before the action
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn undo(game_id: &str, caller_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    verify_undo_caller(&game, caller_id)?;
+    GameContainer::undo(game_id, caller_id).await?;
+    actions_after_undo_or_redo(game_id).await
+}
+
+/**
+ * put back the last action that was undone, as it was -- a roll keeps its dice.  only the player who took it can.
+ * every player gets the game as it was after the action
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn redo(game_id: &str, caller_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    verify_undo_caller(&game, caller_id)?;
+    GameContainer::redo(game_id, caller_id).await?;
+    actions_after_undo_or_redo(game_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{games_service::catan_games::traits::game_trait::GameTrait, shared::shared_models::UserProfile};
+
+    #[tokio::test]
+    async fn test_next_is_the_current_players() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        let game_id = game.id.clone();
+        GameContainer::create_and_add_container(&game_id, &game)
+            .await
+            .expect("new game id");
+        let request_context = RequestContext::test_default(false);
+
+        // only the creator can start the game
+        let refused = next(&game_id, Some("2"), &request_context).await.expect_err("2 didn't create the game");
+        assert_eq!(refused.status, StatusCode::BAD_REQUEST);
+
+        // and only the current player can end their turn
+        game.set_player_order(vec!["1".to_string(), "2".to_string()]).unwrap();
+        game.current_player_id = "1".to_string();
+        game.game_state = GameState::BuyingAndTrading;
+        GameContainer::push_game(&game_id, &game, "Test", None).await.unwrap();
+        let refused = next(&game_id, Some("2"), &request_context).await.expect_err("it is 1's turn");
+        assert_eq!(refused.status, StatusCode::BAD_REQUEST);
+        let refused = next(&game_id, Some("nobody"), &request_context).await.expect_err("nobody isn't playing");
+        assert_eq!(refused.status, StatusCode::BAD_REQUEST);
+        let (current, _) = GameContainer::current_game(&game_id).await.unwrap();
+        assert_eq!(current.game_state, GameState::BuyingAndTrading);
+
+        GameContainer::remove_container(&game_id).await.unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+#![allow(dead_code)]
+/**
+ *  doing what players have asked the service to do for them (see automation_preferences.rs).  after every action is
+ *  pushed the game is looked at for something a player would otherwise be waited on for:
+ *
+ *  - a 7 was just rolled and they owe cards: their discard is made in the order of their DiscardPriority
+ *  - a new offer was made to them: it is accepted if it is from a player in their AcceptTradesFrom and they have the
+ *    cards, or turned down if they want that while they are away (see reconnect.rs) and they are
+ *
+ *  the preferences are looked up only when there is something to do, and each thing done is pushed as the player's own
+ *  Discard, AcceptTrade or RejectTrade -- so it runs through the same rules, is in the event log, and can be undone like
+ *  anything else.  resources a roll pays out are already the players' without anybody confirming them, so there is
+ *  nothing to do there.  bots, and seats handed to a bot, are played by the bot driver alone (see bots.rs).
+ */
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    games_service::{
+        bots::bots::{is_bot, is_driven},
+        catan_games::games::regular::regular_game::RegularGame,
+        long_poller::long_poller::LongPoller,
+        shared::{
+            game_enums::{GameState, ResourceType},
+            game_models::ResourceCards,
+        },
+    },
+    middleware::request_context_mw::RequestContext,
+    user_service::automation_preferences::AutomationPreferences,
+};
+
+use super::{actions::discard, reconnect::DISCONNECTED_AFTER, trades};
+
+/// what the service does for a player about an offer made to them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TradeResponse {
+    Accept { offer_id: String, user_id: String },
+    Decline { offer_id: String, user_id: String },
+}
+
+/**
+ *  the cards to give up: the resources in the priority first, in order, and then one at a time from whatever they
+ *  hold most of
+ */
+pub fn discard_by_priority(hand: &ResourceCards, owed: u32, priority: &[ResourceType]) -> ResourceCards {
+    let mut cards = ResourceCards::default();
+    let left = |cards: &ResourceCards, resource: ResourceType| hand.count(resource) - cards.count(resource);
+    let mut owed = owed.min(hand.total());
+    for resource in priority {
+        let count = left(&cards, *resource).min(owed);
+        cards.add(*resource, count);
+        owed -= count;
+    }
+    while owed > 0 {
+        let most = ResourceCards::RESOURCES
+            .iter()
+            .copied()
+            .max_by_key(|resource| left(&cards, *resource))
+            .expect("there are resources");
+        cards.add(most, 1);
+        owed -= 1;
+    }
+    cards
+}
+
+/// the answers to the offers that are new in the pushed game, from the preferences of the players they were made to
+pub fn trade_responses(
+    previous: &RegularGame,
+    pushed: &RegularGame,
+    preferences: &HashMap<String, AutomationPreferences>,
+    away: &HashSet<String>,
+) -> Vec<TradeResponse> {
+    let mut responses = Vec::new();
+    let mut offers: Vec<_> = pushed
+        .open_trades
+        .values()
+        .filter(|offer| !previous.open_trades.contains_key(&offer.offer_id))
+        .collect();
+    offers.sort_by(|a, b| a.offer_id.cmp(&b.offer_id));
+    for offer in offers {
+        let mut to: Vec<&String> = match &offer.to_id {
+            Some(to_id) => vec![to_id],
+            None => pushed
+                .players
+                .keys()
+                .filter(|id| **id != offer.from_id && !pushed.has_forfeited(id))
+                .collect(),
+        };
+        to.sort();
+        for user_id in to {
+            let wants = match preferences.get(user_id.as_str()) {
+                Some(wants) => wants,
+                None => continue,
+            };
+            let has_cards = pushed
+                .players
+                .get(user_id.as_str())
+                .map_or(false, |player| player.resources.contains(&offer.want));
+            let response = if wants.accepts_trades_from(&offer.from_id) && has_cards {
+                TradeResponse::Accept {
+                    offer_id: offer.offer_id.clone(),
+                    user_id: user_id.clone(),
+                }
+            } else if wants.decline_trades_when_away && away.contains(user_id.as_str()) {
+                TradeResponse::Decline {
+                    offer_id: offer.offer_id.clone(),
+                    user_id: user_id.clone(),
+                }
+            } else {
+                continue;
+            };
+            responses.push(response);
+        }
+    }
+    responses
+}
+
+/// the preferences of the players, for the ones who have some.  bots and players who can't be found have none
+async fn preferences_of(
+    user_ids: &[&String],
+    request_context: &RequestContext,
+) -> HashMap<String, AutomationPreferences> {
+    let mut preferences = HashMap::new();
+    for user_id in user_ids.iter().filter(|id| !is_bot(id)) {
+        if let Ok(user) = request_context.database.find_user_by_id(user_id).await {
+            if user.automation_preferences != AutomationPreferences::default() {
+                preferences.insert(user_id.to_string(), user.automation_preferences);
+            }
+        }
+    }
+    preferences
+}
+
+/// the player's connection is down
+async fn is_away(user_id: &str) -> bool {
+    !matches!(LongPoller::idle_for(user_id).await, Some(idle) if idle < DISCONNECTED_AFTER)
+}
+
+/// do what the players want done about the game that was just pushed.  nothing happens if there is nothing to do
+pub fn after_push(game_id: &str, previous: &RegularGame, pushed: &RegularGame, request_context: &RequestContext) {
+    let rolled_seven = pushed.game_state == GameState::MustDiscard && previous.game_state != GameState::MustDiscard;
+    let new_offers = pushed
+        .open_trades
+        .keys()
+        .any(|offer_id| !previous.open_trades.contains_key(offer_id));
+    if !rolled_seven && !new_offers {
+        return;
+    }
+    let game_id = game_id.to_owned();
+    let previous = previous.clone();
+    let pushed = pushed.clone();
+    let request_context = request_context.clone();
+    actix_web::rt::spawn(async move {
+        let players: Vec<&String> = pushed.players.keys().filter(|id| !is_driven(&pushed, id)).collect();
+        let preferences = preferences_of(&players, &request_context).await;
+        if preferences.is_empty() {
+            return;
+        }
+        if rolled_seven {
+            let mut owing: Vec<(&String, &u32)> = pushed.pending_discards.iter().collect();
+            owing.sort();
+            for (user_id, owed) in owing {
+                let priority = match preferences.get(user_id.as_str()) {
+                    Some(wants) if !wants.discard_priority.is_empty() => &wants.discard_priority,
+                    _ => continue,
+                };
+                let hand = match pushed.players.get(user_id.as_str()) {
+                    Some(player) => &player.resources,
+                    None => continue,
+                };
+                let cards = discard_by_priority(hand, *owed, priority);
+                if let Err(e) = discard(&game_id, user_id, &cards, &request_context).await {
+                    tracing::warn!("failed to discard for {} in {}: {:#?}", user_id, game_id, e);
+                }
+            }
+        }
+        if new_offers {
+            let mut away = HashSet::new();
+            for user_id in preferences.keys() {
+                if is_away(user_id).await {
+                    away.insert(user_id.clone());
+                }
+            }
+            for response in trade_responses(&previous, &pushed, &preferences, &away) {
+                //  an offer to everybody can only be taken once: the ones after the first are refused by the game
+                let result = match &response {
+                    TradeResponse::Accept { offer_id, user_id } => {
+                        trades::accept_trade(&game_id, offer_id, user_id, &request_context).await
+                    }
+                    TradeResponse::Decline { offer_id, user_id } => {
+                        trades::reject_trade(&game_id, offer_id, user_id, &request_context).await
+                    }
+                };
+                if let Err(e) = result {
+                    tracing::debug!("{:?} in {} wasn't made: {:#?}", response, game_id, e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{catan_games::traits::game_trait::GameTrait, shared::game_models::TradeOffer},
+        shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_discard_by_priority() {
+        // 2 wood, 1 brick, 3 sheep, 0 wheat, 4 ore
+        let hand = ResourceCards::new(2, 1, 3, 0, 4);
+        let cards = discard_by_priority(&hand, 5, &[ResourceType::Wheat, ResourceType::Wood, ResourceType::Brick]);
+        // wood and brick first, then the ore they hold most of
+        assert_eq!(cards, ResourceCards::new(2, 1, 0, 0, 2));
+        assert_eq!(discard_by_priority(&hand, 5, &[]).total(), 5);
+        assert_eq!(discard_by_priority(&hand, 2, &[ResourceType::Sheep]), ResourceCards::new(0, 0, 2, 0, 0));
+        assert!(hand.contains(&discard_by_priority(&hand, 20, &[ResourceType::Ore])));
+    }
+
+    #[test]
+    fn test_trade_responses() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("3".to_string())));
+        game.players.get_mut("2").unwrap().resources = ResourceCards::new(0, 0, 0, 1, 0);
+        let previous = game.clone();
+        let offer = TradeOffer {
+            offer_id: "offer".to_owned(),
+            from_id: "1".to_owned(),
+            to_id: None,
+            give: ResourceCards::new(1, 0, 0, 0, 0),
+            want: ResourceCards::new(0, 0, 0, 1, 0),
+            expires_at: u64::MAX,
+            counter_to: None,
+            rejected_by: Vec::new(),
+        };
+        game.open_trades.insert(offer.offer_id.clone(), offer);
+
+        let mut preferences = HashMap::new();
+        preferences.insert(
+            "2".to_owned(),
+            AutomationPreferences {
+                accept_trades_from: vec!["1".to_owned()],
+                ..AutomationPreferences::default()
+            },
+        );
+        preferences.insert(
+            "3".to_owned(),
+            AutomationPreferences {
+                accept_trades_from: vec!["1".to_owned()],
+                decline_trades_when_away: true,
+                ..AutomationPreferences::default()
+            },
+        );
+        let away: HashSet<String> = vec!["3".to_owned()].into_iter().collect();
+        // 2 has the wheat and takes it; 3 doesn't, and is away
+        assert_eq!(
+            trade_responses(&previous, &game, &preferences, &away),
+            vec![
+                TradeResponse::Accept {
+                    offer_id: "offer".to_owned(),
+                    user_id: "2".to_owned()
+                },
+                TradeResponse::Decline {
+                    offer_id: "offer".to_owned(),
+                    user_id: "3".to_owned()
+                },
+            ]
+        );
+        // only new offers are answered
+        assert!(trade_responses(&game, &game, &preferences, &away).is_empty());
+        assert_eq!(trade_responses(&previous, &game, &preferences, &HashSet::new()).len(), 1);
+    }
+}
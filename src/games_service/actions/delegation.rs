@@ -0,0 +1,157 @@
+#![allow(dead_code)]
+/**
+ *  handing your turns to somebody else while you step away.  a player in a game that is under way can hand their seat
+ *  to another player in the game, who plays it by sending actions with an X-Acting-As: {user_id} header (see
+ *  ActingPlayer), or to a bot, which the bot driver plays (see bots.rs).  the player can take the seat back whenever
+ *  they like, and so can the player they handed it to.  a player whose seat has been handed over isn't abandoning the
+ *  game, so they aren't forfeited for being away (see forfeit.rs).
+ *
+ *  handing over and taking back are actions like any other, so they are in the event log -- and so is everything the
+ *  delegate does: the event has the delegate as its actor and the seat's player as who it was done for.  a delegate
+ *  can't forfeit the seat or hand it on.
+ */
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+
+use crate::{
+    games_service::{
+        bots::bots, catan_games::games::regular::regular_game::RegularGame,
+        game_container::game_container::GameContainer, shared::game_models::Delegate,
+    },
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+use super::{
+    actions::{current_game_or_not_found, push_and_return_actions, rejected_action},
+    local_seats::ActingPlayer,
+};
+
+/// true if the player has handed their seat in the game to the caller
+pub async fn is_delegate(game_id: Option<&str>, user_id: &str, caller_id: &str) -> bool {
+    let game_id = match game_id {
+        Some(game_id) => game_id,
+        None => return false,
+    };
+    GameContainer::current_game(game_id)
+        .await
+        .map_or(false, |(game, _)| game.is_delegate_for(user_id, caller_id))
+}
+
+/// who is playing the seat, if it isn't the seat's own player: the caller, if the seat was handed to them, or the bot
+/// driver -- which plays without claims -- if it was handed to a bot
+pub fn acting_for(game: &RegularGame, user_id: &str, request_context: &RequestContext) -> Option<String> {
+    let caller_id = request_context.claims.as_ref().map(|claims| claims.id.as_str());
+    match game.delegate_of(user_id)? {
+        Delegate::Player(delegate_id) if caller_id == Some(delegate_id.as_str()) => Some(delegate_id.clone()),
+        delegate @ Delegate::Bot(_) if caller_id.is_none() => Some(delegate.actor_id()),
+        _ => None,
+    }
+}
+
+/// what a delegate is told when they try to do what only the seat's own player can
+pub fn only_the_player(what: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        &format!("only the seat's own player can {}", what),
+        StatusCode::FORBIDDEN,
+        ResponseType::NoData,
+        GameError::HttpError(StatusCode::FORBIDDEN),
+    )
+}
+
+/// the player hands their turns to the delegate
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %user_id))]
+pub async fn delegate(
+    game_id: &str,
+    user_id: &str,
+    delegate: &Delegate,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game.delegate(user_id, delegate).map_err(rejected_action)?;
+    let response =
+        push_and_return_actions(game_id, &new_game, "Delegate", Some(user_id), request_context).await?;
+    if let Delegate::Bot(_) = delegate {
+        bots::start_driver(game_id, request_context);
+    }
+    Ok(response)
+}
+
+/// the player takes their turns back
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %user_id))]
+pub async fn revoke(
+    game_id: &str,
+    user_id: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game.revoke_delegation(user_id).map_err(rejected_action)?;
+    push_and_return_actions(game_id, &new_game, "RevokeDelegation", Some(user_id), request_context).await
+}
+
+pub async fn delegate_handler(
+    game_id: web::Path<String>,
+    delegate: web::Json<Delegate>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> HttpResponse {
+    if player.delegated {
+        return only_the_player("hand the seat on").to_http_response();
+    }
+    self::delegate(&game_id, &player.player_id, &delegate, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn revoke_handler(
+    game_id: web::Path<String>,
+    player: ActingPlayer,
+    request_context: RequestContext,
+) -> HttpResponse {
+    revoke(&game_id, &player.player_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{
+            bots::engine::BotDifficulty, catan_games::traits::game_trait::GameTrait,
+            shared::game_enums::GameState,
+        },
+        shared::{
+            service_models::{Claims, Role},
+            shared_models::UserProfile,
+        },
+    };
+
+    #[test]
+    fn test_acting_for() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        game.player_order = vec!["1".to_string(), "2".to_string()];
+        game.game_state = GameState::WaitingForRoll;
+        let context_for = |caller_id: Option<&str>| {
+            let mut request_context = RequestContext::test_default(false);
+            if let Some(caller_id) = caller_id {
+                request_context.set_claims(&Claims::new(caller_id, "test@test.com", 60, &vec![Role::User], &None));
+            }
+            request_context
+        };
+
+        // nobody is playing for anybody
+        assert_eq!(acting_for(&game, "1", &context_for(Some("2"))), None);
+
+        let handed = game.delegate("1", &Delegate::Player("2".to_string())).unwrap();
+        assert_eq!(acting_for(&handed, "1", &context_for(Some("2"))), Some("2".to_string()));
+        assert_eq!(acting_for(&handed, "1", &context_for(Some("1"))), None); // the player is back and playing
+
+        let to_bot = game.delegate("1", &Delegate::Bot(BotDifficulty::Hard)).unwrap();
+        assert_eq!(acting_for(&to_bot, "1", &context_for(None)), Some("bot:Hard".to_string()));
+        assert_eq!(acting_for(&to_bot, "1", &context_for(Some("1"))), None);
+    }
+}
@@ -0,0 +1,127 @@
+#![allow(dead_code)]
+/**
+ *  Monopoly and Year of Plenty need a choice from the player after the card is played, so they are played in two
+ *  steps:
+ *
+ *  1. play: the card leaves the player's hand and the game waits for the choice.  everybody gets the GameUpdate (so
+ *     they can see what was played), and the player who played it also gets a ResolveDevCard prompt
+ *  2. resolve: the player sends the resource(s) they chose and the cards move through the game's ledger -- from the
+ *     other players for Monopoly, from the bank for Year of Plenty
+ *
+ *  nothing else can happen in the game between the two steps.  a client that asks for the resource(s) before the
+ *  card is played can use the monopoly and year-of-plenty apis instead, which do both steps in one call (and one
+ *  undo).
+ */
+use crate::{
+    games_service::{
+        game_container::game_messages::CatanMessage,
+        long_poller::{channels::MessageChannel, long_poller::LongPoller},
+        shared::{
+            game_enums::{DevCardType, ResourceType},
+            game_models::DevCardResolutionData,
+        },
+    },
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::ServiceResponse,
+};
+
+use super::actions::{current_game_or_not_found, push_and_return_actions, rejected_action};
+
+/**
+ * play a development card from the caller's hand, and prompt them to choose what it does
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn play_dev_card(
+    game_id: &str,
+    caller_id: &str,
+    card: DevCardType,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game.play_dev_card(caller_id, card).map_err(rejected_action)?;
+    let response = push_and_return_actions(
+        game_id,
+        &new_game,
+        "PlayDevCard",
+        Some(caller_id),
+        request_context,
+    )
+    .await?;
+    if let Some(pending) = new_game.pending_dev_card.clone() {
+        if let Err(e) = LongPoller::send_to_channel(
+            vec![caller_id.to_owned()],
+            &MessageChannel::Game(game_id.to_owned()),
+            &CatanMessage::ResolveDevCard(pending),
+        )
+        .await
+        {
+            tracing::warn!("failed to send the ResolveDevCard prompt: {:#?}", e);
+        }
+    }
+    Ok(response)
+}
+
+/**
+ * finish playing the card with the resource(s) the caller chose
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn resolve_dev_card(
+    game_id: &str,
+    caller_id: &str,
+    data: &DevCardResolutionData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game
+        .resolve_dev_card(caller_id, data)
+        .map_err(rejected_action)?;
+    push_and_return_actions(
+        game_id,
+        &new_game,
+        "ResolveDevCard",
+        Some(caller_id),
+        request_context,
+    )
+    .await
+}
+
+/**
+ * play a Monopoly card: every other player gives the caller all of their cards of the resource
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn play_monopoly(
+    game_id: &str,
+    caller_id: &str,
+    resource: ResourceType,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game
+        .play_monopoly(caller_id, resource)
+        .map_err(rejected_action)?;
+    push_and_return_actions(game_id, &new_game, "Monopoly", Some(caller_id), request_context).await
+}
+
+/**
+ * play a Year of Plenty card: the caller takes two cards (which can be the same resource) from the bank
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn play_year_of_plenty(
+    game_id: &str,
+    caller_id: &str,
+    resources: [ResourceType; 2],
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game
+        .play_year_of_plenty(caller_id, resources)
+        .map_err(rejected_action)?;
+    push_and_return_actions(
+        game_id,
+        &new_game,
+        "YearOfPlenty",
+        Some(caller_id),
+        request_context,
+    )
+    .await
+}
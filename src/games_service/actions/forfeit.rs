@@ -0,0 +1,124 @@
+#![allow(dead_code)]
+/**
+ *  leaving a game that has started.  a player can forfeit whenever they like, and a player who stops listening to the
+ *  long poller for ABANDONED_AFTER_MINUTES is taken to have walked away and is forfeited for them, so the rest of the
+ *  table isn't stuck waiting on them.  what happens to the forfeiting player's cards and pieces is up to the game (see
+ *  RegularGame::forfeit); both go through push_and_return_actions, so everybody sees it and it is in the event log.
+ *  if the player who forfeits is the host, the game gets a new host (see host.rs).  a player who has handed their turns
+ *  to somebody else (see delegation.rs) isn't away.  a player who has only just dropped has a grace period to come
+ *  back first (see reconnect.rs).
+ */
+use std::time::{Duration, Instant};
+
+use crate::{
+    games_service::{
+        bots::bots::is_bot, catan_games::traits::game_trait::GameTrait, game_container::game_container::GameContainer,
+        long_poller::long_poller::LongPoller,
+        shared::game_enums::{GameAction, GameState},
+    },
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::ServiceResponse,
+};
+
+use super::{
+    actions::{current_game_or_not_found, next, push_and_return_actions, rejected_action},
+    host::migrate_host,
+    reconnect::{self, Connections},
+};
+
+/// how long a player can be away from the long poller before they have abandoned the game
+pub const ABANDONED_AFTER_MINUTES: u64 = 5;
+/// how often each game looks for players who have walked away -- often enough to tell the table soon after a drop
+const ABANDONMENT_CHECK_SECONDS: u64 = 10;
+
+/**
+ *  the caller gives up.  it doesn't have to be their turn -- if it is, the turn moves on.  if only one player is left
+ *  they win the game
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn forfeit(
+    game_id: &str,
+    caller_id: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game.forfeit(caller_id).map_err(rejected_action)?;
+    let response =
+        push_and_return_actions(game_id, &new_game, "Forfeit", Some(caller_id), request_context).await?;
+    if caller_id == game.creator_id {
+        hand_on_host(game_id).await;
+    }
+    Ok(response)
+}
+
+/// the host forfeited: give the game to the next host now rather than at the next host check
+async fn hand_on_host(game_id: &str) {
+    if let Err(e) = migrate_host(game_id).await {
+        tracing::warn!("failed to move the host of {}: {:#?}", game_id, e);
+    }
+}
+
+/**
+ *  watch a game that has just started for players who have walked away, until it is over.  a player is away while
+ *  they have no wait open on the long poller -- whether they are still logged in or not -- and bots are never away.
+ *  the table hears about players whose connections drop and come back, and the turns of a player who is past their
+ *  grace period are ended for them (see reconnect.rs)
+ */
+pub fn start_abandonment_monitor(game_id: &str, request_context: &RequestContext) {
+    let game_id = game_id.to_owned();
+    let request_context = request_context.clone();
+    let grace = Duration::from_secs(request_context.config.reconnect_grace_seconds);
+    //  nobody is forfeited while they still have time to come back
+    let abandoned_after = Duration::from_secs(ABANDONED_AFTER_MINUTES * 60).max(grace);
+    actix_web::rt::spawn(async move {
+        let mut connections = Connections::default();
+        loop {
+            tokio::time::sleep(Duration::from_secs(ABANDONMENT_CHECK_SECONDS)).await;
+            let game = match GameContainer::current_game(&game_id).await {
+                Ok((game, _)) if game.game_state != GameState::GameOver => game,
+                _ => return, // the game is over
+            };
+            let now = Instant::now();
+            let mut abandoned = None;
+            //  a player who handed their turns to somebody else has stepped away, not walked away
+            let players = game.player_order.iter().filter(|id| !is_bot(id) && game.delegate_of(id).is_none());
+            for user_id in players {
+                let idle = LongPoller::idle_for(user_id).await;
+                if let Some(change) = connections.observe(user_id, idle, now) {
+                    reconnect::announce(&game_id, user_id, change, grace).await;
+                }
+                if connections.away_for(user_id, now) >= abandoned_after {
+                    abandoned = Some(user_id.clone());
+                    break;
+                }
+            }
+            //  one at a time: the next check sees the game after this player is gone
+            if let Some(user_id) = abandoned {
+                tracing::info!("{} abandoned {}", user_id, game_id);
+                let result = match game.forfeit(&user_id) {
+                    Ok(new_game) => {
+                        push_and_return_actions(&game_id, &new_game, "Abandoned", None, &request_context)
+                            .await
+                            .map(|_| ())
+                    }
+                    Err(e) => Err(rejected_action(e)),
+                };
+                match result {
+                    Ok(()) if user_id == game.creator_id => hand_on_host(&game_id).await,
+                    Ok(()) => {}
+                    Err(e) => tracing::warn!("failed to forfeit {} from {}: {:#?}", user_id, game_id, e),
+                }
+                continue;
+            }
+            //  the current player is gone and all they can do is end their turn: end it for them
+            if connections.past_grace(&game.current_player_id, grace, now)
+                && game.valid_actions(false).contains(&GameAction::Next)
+            {
+                tracing::info!("{} is away: ending their turn in {}", game.current_player_id, game_id);
+                if let Err(e) = next(&game_id, None, &request_context).await {
+                    tracing::warn!("failed to end the turn of {} in {}: {:#?}", game.current_player_id, game_id, e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,153 @@
+#![allow(dead_code)]
+/**
+ *  host migration.  the game's creator is its host: they are the one who can kick players, add bots, pick the options
+ *  and undo on anybody's turn.  a game whose host has gone would be stuck with nobody able to do those things, so when
+ *  the host forfeits, or has been away from the long poller for HOST_AWAY_SECONDS, creator_id moves to the next
+ *  connected player after them in seat order and everybody in the game gets HostChanged.
+ *
+ *  every game has a host monitor from when it is created until it is over or removed.  a forfeit by the host hands the
+ *  game on straight away (see forfeit.rs).  if nobody else is connected, the host stays where it is until somebody is.
+ */
+use std::time::Duration;
+
+use crate::{
+    games_service::{
+        bots::bots::is_bot,
+        catan_games::games::regular::regular_game::RegularGame,
+        game_container::{
+            game_container::GameContainer,
+            game_messages::{CatanMessage, HostChangedData},
+        },
+        long_poller::long_poller::LongPoller,
+        shared::game_enums::GameState,
+    },
+    shared::shared_models::ServiceResponse,
+};
+
+/// how long the host can be away from the long poller before the game moves on to a new host
+pub const HOST_AWAY_SECONDS: u64 = 60;
+/// how often each game checks on its host
+const HOST_CHECK_SECONDS: u64 = 15;
+
+/// true if the player still has the game: they haven't forfeited and are connected
+fn is_present(game: &RegularGame, user_id: &str, connected: &[String]) -> bool {
+    !game.has_forfeited(user_id) && connected.iter().any(|id| id == user_id)
+}
+
+/// the player who should host the game next: the first player after the host in seat order who is still in the game
+/// and connected.  bots never host.  None if there isn't one
+pub fn next_host(game: &RegularGame, connected: &[String]) -> Option<String> {
+    let mut seats: Vec<(usize, &String)> = game
+        .players
+        .iter()
+        .map(|(user_id, player)| (player.seat_index, user_id))
+        .collect();
+    seats.sort();
+    let host_seat = game
+        .players
+        .get(&game.creator_id)
+        .map_or(0, |player| player.seat_index);
+    let (before, after): (Vec<_>, Vec<_>) = seats.into_iter().partition(|(seat, _)| *seat <= host_seat);
+    after
+        .into_iter()
+        .chain(before)
+        .map(|(_, user_id)| user_id)
+        .find(|user_id| {
+            **user_id != game.creator_id && !is_bot(user_id) && is_present(game, user_id, connected)
+        })
+        .cloned()
+}
+
+/// the players in the game who have had a wait open on the long poller within HOST_AWAY_SECONDS
+async fn connected_players(game: &RegularGame) -> Vec<String> {
+    let mut connected = Vec::new();
+    for user_id in game.players.keys() {
+        if let Some(idle) = LongPoller::idle_for(user_id).await {
+            if idle < Duration::from_secs(HOST_AWAY_SECONDS) {
+                connected.push(user_id.clone());
+            }
+        }
+    }
+    connected
+}
+
+/**
+ *  if the host has gone, give the game to the next host and tell everybody.  returns the new host, or None if the
+ *  host is still here (or there is nobody to hand the game to)
+ */
+pub async fn migrate_host(game_id: &str) -> Result<Option<String>, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    let connected = connected_players(&game).await;
+    if is_present(&game, &game.creator_id, &connected) {
+        return Ok(None);
+    }
+    let new_host = match next_host(&game, &connected) {
+        Some(new_host) => new_host,
+        None => return Ok(None),
+    };
+    let mut new_game = game.clone();
+    new_game.creator_id = new_host.clone();
+    GameContainer::push_game(game_id, &new_game, "HostChanged", None).await?;
+    tracing::info!("{} is the host of {} now, in place of {}", new_host, game_id, game.creator_id);
+    let _ = GameContainer::broadcast_message(
+        game_id,
+        &CatanMessage::HostChanged(HostChangedData {
+            game_id: game_id.to_owned(),
+            from_id: game.creator_id.clone(),
+            to_id: new_host.clone(),
+            to_name: game
+                .players
+                .get(&new_host)
+                .map(|player| player.profile.display_name.clone())
+                .unwrap_or_default(),
+            text: String::default(),
+        }),
+    )
+    .await;
+    Ok(Some(new_host))
+}
+
+/// check on the game's host until the game is over or removed
+pub fn start_host_monitor(game_id: &str) {
+    let game_id = game_id.to_owned();
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(HOST_CHECK_SECONDS)).await;
+            match GameContainer::current_game(&game_id).await {
+                Ok((game, _)) if game.game_state != GameState::GameOver => {}
+                _ => return,
+            }
+            if let Err(e) = migrate_host(&game_id).await {
+                tracing::warn!("failed to check the host of {}: {:#?}", game_id, e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::catan_games::traits::game_trait::GameTrait, shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_next_host() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        for id in ["2", "3", "4"].iter() {
+            GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some(id.to_string())));
+        }
+        let everybody: Vec<String> = ["1", "2", "3", "4"].iter().map(|id| id.to_string()).collect();
+        assert_eq!(next_host(&game, &everybody), Some("2".to_string()));
+
+        // the next connected player, wrapping around the table
+        let connected = vec!["1".to_string(), "4".to_string()];
+        assert_eq!(next_host(&game, &connected), Some("4".to_string()));
+        game.creator_id = "4".to_string();
+        assert_eq!(next_host(&game, &connected), Some("1".to_string()));
+
+        // players who forfeited are gone
+        game.forfeited.push("1".to_string());
+        assert_eq!(next_host(&game, &connected), None);
+    }
+}
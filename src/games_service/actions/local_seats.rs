@@ -0,0 +1,243 @@
+#![allow(dead_code)]
+/**
+ *  local users at a shared screen.  a local user belongs to the connected user who made them, and that owner plays
+ *  their seat: an action sent with an X-Acting-As: {local_user_id} header is taken by the local user, as long as the
+ *  caller owns them (see ActingPlayer).
+ *
+ *  the owner can limit what each local seat may do in a game -- trade, undo, or nothing at all (spectator only) -- with
+ *  PUT /auth/api/v1/games/{game_id}/local/{local_user_id}/capabilities.  the limits are part of the game, so they
+ *  are checked where every action is pushed (see push_and_return_actions), whoever sends it.
+ *
+ *  X-Acting-As also names the seat of a player who has handed their turns to the caller (see delegation.rs).  a
+ *  seat token always plays its own local user (see seat_tokens.rs).
+ *
+ *  every action can carry the checksum of the game the caller's client is at, in x-game-checksum.  it is checked on
+ *  the side while the action goes ahead, and a client that has desynced is sent the game again (see checksum.rs).
+ */
+use actix_web::{dev::Payload, error::InternalError, web, FromRequest, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use reqwest::StatusCode;
+
+use crate::{
+    games_service::{
+        catan_games::games::regular::regular_game::RegularGame,
+        game_container::{checksum, game_container::GameContainer, game_messages::GameHeader},
+        shared::game_models::LocalCapabilities,
+    },
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse, UserType},
+};
+
+use super::{actions::rejected_action, delegation};
+
+/// the header that names the local user the caller is acting for
+pub const ACTING_AS_HEADER: &str = "X-Acting-As";
+
+/// the actions that need can_trade
+const TRADE_ACTIONS: [&str; 5] = ["OfferTrade", "AcceptTrade", "RejectTrade", "CounterTrade", "BankTrade"];
+/// the actions that need can_undo
+const UNDO_ACTIONS: [&str; 2] = ["Undo", "Redo"];
+
+/// the local user has to belong to the caller
+pub async fn verify_owner(
+    local_user_id: &str,
+    caller_id: &str,
+    request_context: &RequestContext,
+) -> Result<(), ServiceResponse> {
+    let local_user = request_context.database.find_user_by_id(local_user_id).await?;
+    if local_user.user_profile.user_type != UserType::Local
+        || local_user.connected_user_id.as_deref() != Some(caller_id)
+    {
+        return new_unauthorized_response!(&format!("{} is not one of your local users", local_user_id));
+    }
+    Ok(())
+}
+
+/// can the player's seat take the action?
+pub fn verify_capability(game: &RegularGame, player_id: &str, action: &str) -> Result<(), ServiceResponse> {
+    let capabilities = game.capabilities_of(player_id);
+    let refused = if capabilities.spectator_only {
+        Some("is a spectator")
+    } else if !capabilities.can_trade && TRADE_ACTIONS.contains(&action) {
+        Some("can't trade")
+    } else if !capabilities.can_undo && UNDO_ACTIONS.contains(&action) {
+        Some("can't undo or redo")
+    } else {
+        None
+    };
+    match refused {
+        Some(reason) => Err(rejected_action(GameError::ActionError(format!(
+            "{} {} in this game",
+            player_id, reason
+        )))),
+        None => Ok(()),
+    }
+}
+
+/**
+ *  the player an action is for: the caller, or the player named in X-Acting-As if the caller owns them (a local user)
+ *  or they have handed their turns in the game to the caller
+ */
+pub struct ActingPlayer {
+    pub caller_id: String,
+    pub player_id: String,
+    pub delegated: bool, // the caller is playing the seat for a player who handed it to them
+}
+
+impl ActingPlayer {
+    async fn resolve(
+        acting_as: Option<String>,
+        game_id: Option<String>,
+        request_context: RequestContext,
+    ) -> Result<Self, ServiceResponse> {
+        let claims = request_context
+            .claims
+            .as_ref()
+            .expect("auth_mw should have added this or rejected the call");
+        let caller_id = claims.id.clone();
+        // a seat token plays its local user, and nobody else (see seat_tokens.rs)
+        if let Some(seat) = claims.seat.as_ref() {
+            if acting_as.as_deref().map_or(false, |player_id| player_id != seat.local_user_id) {
+                return new_unauthorized_response!("a seat token can only play its seat");
+            }
+            verify_owner(&seat.local_user_id, &caller_id, &request_context).await?;
+            return Ok(Self {
+                caller_id,
+                player_id: seat.local_user_id.clone(),
+                delegated: false,
+            });
+        }
+        let (player_id, delegated) = match acting_as {
+            Some(player_id) if player_id != caller_id => {
+                if delegation::is_delegate(game_id.as_deref(), &player_id, &caller_id).await {
+                    (player_id, true)
+                } else {
+                    verify_owner(&player_id, &caller_id, &request_context).await?;
+                    (player_id, false)
+                }
+            }
+            _ => (caller_id.clone(), false),
+        };
+        Ok(Self {
+            caller_id,
+            player_id,
+            delegated,
+        })
+    }
+}
+
+impl FromRequest for ActingPlayer {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let acting_as = req
+            .headers()
+            .get(ACTING_AS_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_owned());
+        let checksum_echo = req
+            .headers()
+            .get(GameHeader::CHECKSUM)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_owned());
+        let game_id = req.match_info().get("game_id").map(|game_id| game_id.to_owned());
+        let request_context = RequestContext::from_request(req, payload);
+        Box::pin(async move {
+            let request_context = request_context.await?;
+            let acting = Self::resolve(acting_as, game_id.clone(), request_context.clone())
+                .await
+                .map_err(|sr| InternalError::from_response(sr.message.clone(), sr.to_http_response()))?;
+            if let (Some(game_id), Some(echo)) = (game_id, checksum_echo) {
+                let caller_id = acting.caller_id.clone();
+                actix_web::rt::spawn(async move {
+                    checksum::check_echo(&game_id, &caller_id, &echo, &request_context).await;
+                });
+            }
+            Ok(acting)
+        })
+    }
+}
+
+/// the owner of a local user in the game sets what their seat can do
+pub async fn set_capabilities(
+    game_id: &str,
+    local_user_id: &str,
+    capabilities: &LocalCapabilities,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let caller_id = &request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call")
+        .id;
+    verify_owner(local_user_id, caller_id, request_context).await?;
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    if !game.players.contains_key(local_user_id) {
+        return Err(ServiceResponse::new(
+            &format!("{} is not playing in {}", local_user_id, game_id),
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        ));
+    }
+    let mut new_game = game.clone();
+    new_game
+        .local_capabilities
+        .insert(local_user_id.to_owned(), *capabilities);
+    GameContainer::push_game(game_id, &new_game, "LocalCapabilities", Some(caller_id)).await?;
+    Ok(ServiceResponse::new(
+        "capabilities set",
+        StatusCode::OK,
+        ResponseType::Game(new_game.redacted_for(caller_id)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn set_capabilities_handler(
+    path: web::Path<(String, String)>,
+    capabilities: web::Json<LocalCapabilities>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let (game_id, local_user_id) = path.into_inner();
+    set_capabilities(&game_id, &local_user_id, &capabilities, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::catan_games::traits::game_trait::GameTrait, shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_local_capabilities() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("owner".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("local".to_string())));
+
+        // nothing set: the seat can do everything
+        for action in ["Build", "OfferTrade", "Undo"] {
+            assert!(verify_capability(&game, "local", action).is_ok());
+        }
+
+        game.local_capabilities.insert(
+            "local".to_string(),
+            LocalCapabilities {
+                can_trade: false,
+                can_undo: true,
+                spectator_only: false,
+            },
+        );
+        assert!(verify_capability(&game, "local", "Build").is_ok());
+        assert!(verify_capability(&game, "local", "BankTrade").is_err());
+        assert!(verify_capability(&game, "local", "Redo").is_ok());
+        assert!(verify_capability(&game, "owner", "BankTrade").is_ok());
+
+        game.local_capabilities.get_mut("local").unwrap().spectator_only = true;
+        assert!(verify_capability(&game, "local", "Roll").is_err());
+    }
+}
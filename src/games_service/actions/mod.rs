@@ -1,2 +1,11 @@
 pub mod actions;
-pub mod action_handlers;
\ No newline at end of file
+pub mod action_handlers;
+pub mod automation;
+pub mod delegation;
+pub mod dev_cards;
+pub mod forfeit;
+pub mod host;
+pub mod local_seats;
+pub mod reconnect;
+pub mod seat_tokens;
+pub mod trades;
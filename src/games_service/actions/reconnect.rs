@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+/**
+ *  a player whose connection drops in the middle of a game.  the abandonment monitor (see forfeit.rs) looks at every
+ *  player in the game every few seconds, and a player who has had no long poll, socket or event stream open for
+ *  DISCONNECTED_AFTER -- or who has logged out -- has dropped.  a client opens its next wait as soon as the last one
+ *  is answered (and heartbeats answer them before they go stale), so that long with nothing open isn't a slow client.
+ *
+ *  the rest of the table is told right away, with PlayerDisconnected on the game's channel, and the player gets
+ *  reconnect_grace_seconds (RECONNECT_GRACE_SECONDS, 90 unless it is set) to come back.  until the grace is up nothing
+ *  is done for them; after it, whenever it is their turn and all they can do is end it, it is ended for them.  if they
+ *  are still gone after ABANDONED_AFTER_MINUTES they are forfeited (see forfeit.rs).  a player who comes back gets a
+ *  PlayerReconnected, and is counted: each player's reconnects are in their GameStats when the game ends.  the counts
+ *  are kept in memory until the game is evicted, so a restart forgets them.
+ */
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::games_service::{
+    game_container::game_messages::CatanMessage,
+    long_poller::{
+        channels::MessageChannel,
+        long_poller::{LongPoller, MessageTarget},
+    },
+};
+
+/// how long a player can go without a wait open before their connection has dropped
+pub const DISCONNECTED_AFTER: Duration = Duration::from_secs(20);
+/// how long a player whose connection dropped has to come back unless RECONNECT_GRACE_SECONDS says otherwise
+pub const DEFAULT_RECONNECT_GRACE_SECONDS: u64 = 90;
+
+/// sent to the game's channel when a player's connection drops, and again when they come back
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConnectionData {
+    pub game_id: String,
+    pub user_id: String,
+    pub grace_seconds: u64, // PlayerDisconnected: how long until their turns are ended for them
+    pub away_seconds: u64,  // PlayerReconnected: how long they were gone
+}
+
+/// what happened to a player's connection since the last look
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionChange {
+    Disconnected,
+    Reconnected(Duration), // how long they were gone
+}
+
+/// the players in one game whose connections have dropped, and when
+#[derive(Debug, Default)]
+pub struct Connections {
+    dropped_at: HashMap<String, Instant>,
+}
+
+impl Connections {
+    /**
+     *  look at a player again.  idle is how long since they last had a wait open, None if they have logged out (the
+     *  long poller forgets users when they log out).  returns what changed, if anything
+     */
+    pub fn observe(&mut self, user_id: &str, idle: Option<Duration>, now: Instant) -> Option<ConnectionChange> {
+        let connected = matches!(idle, Some(idle) if idle < DISCONNECTED_AFTER);
+        match (connected, self.dropped_at.get(user_id).copied()) {
+            (true, Some(dropped_at)) => {
+                self.dropped_at.remove(user_id);
+                Some(ConnectionChange::Reconnected(now.saturating_duration_since(dropped_at)))
+            }
+            (false, None) => {
+                //  the connection dropped when they were last there, not when we noticed
+                let dropped_at = idle.and_then(|idle| now.checked_sub(idle)).unwrap_or(now);
+                self.dropped_at.insert(user_id.to_owned(), dropped_at);
+                Some(ConnectionChange::Disconnected)
+            }
+            _ => None,
+        }
+    }
+
+    /// how long the player has been gone.  zero if they are connected
+    pub fn away_for(&self, user_id: &str, now: Instant) -> Duration {
+        self.dropped_at
+            .get(user_id)
+            .map_or(Duration::ZERO, |dropped_at| now.saturating_duration_since(*dropped_at))
+    }
+
+    /// true if the player has been gone for longer than the grace period
+    pub fn past_grace(&self, user_id: &str, grace: Duration, now: Instant) -> bool {
+        self.dropped_at.contains_key(user_id) && self.away_for(user_id, now) >= grace
+    }
+}
+
+lazy_static::lazy_static! {
+    // game_id -> user_id -> how many times they have come back
+    static ref RECONNECTS: Mutex<HashMap<String, HashMap<String, u32>>> = Mutex::new(HashMap::new());
+}
+
+/// how many times the player came back to the game after their connection dropped
+pub fn reconnects(game_id: &str, user_id: &str) -> u32 {
+    RECONNECTS
+        .lock()
+        .expect("the reconnect lock shouldn't be poisoned")
+        .get(game_id)
+        .and_then(|players| players.get(user_id).copied())
+        .unwrap_or(0)
+}
+
+/// the game is gone -- so are its counts
+pub fn forget(game_id: &str) {
+    RECONNECTS
+        .lock()
+        .expect("the reconnect lock shouldn't be poisoned")
+        .remove(game_id);
+}
+
+/// tell the table about a player's connection, and count them if they came back
+pub async fn announce(game_id: &str, user_id: &str, change: ConnectionChange, grace: Duration) {
+    let data = |away: Duration| ConnectionData {
+        game_id: game_id.to_owned(),
+        user_id: user_id.to_owned(),
+        grace_seconds: grace.as_secs(),
+        away_seconds: away.as_secs(),
+    };
+    let message = match change {
+        ConnectionChange::Disconnected => {
+            tracing::info!("{} dropped from {}", user_id, game_id);
+            CatanMessage::PlayerDisconnected(data(Duration::ZERO))
+        }
+        ConnectionChange::Reconnected(away) => {
+            tracing::info!("{} came back to {} after {:?}", user_id, game_id, away);
+            *RECONNECTS
+                .lock()
+                .expect("the reconnect lock shouldn't be poisoned")
+                .entry(game_id.to_owned())
+                .or_default()
+                .entry(user_id.to_owned())
+                .or_default() += 1;
+            CatanMessage::PlayerReconnected(data(away))
+        }
+    };
+    let target = MessageTarget::Topic(MessageChannel::Game(game_id.to_owned()));
+    if let Err(e) = LongPoller::send_message(target, &message).await {
+        tracing::warn!("failed to tell {} about {}'s connection: {:#?}", game_id, user_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connections() {
+        let mut connections = Connections::default();
+        let start = Instant::now();
+        let grace = Duration::from_secs(90);
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+
+        // waiting, or between waits, is connected
+        assert_eq!(connections.observe("1", Some(Duration::ZERO), at(0)), None);
+        assert_eq!(connections.observe("1", Some(Duration::from_secs(5)), at(10)), None);
+
+        // the connection dropped when they were last there
+        assert_eq!(
+            connections.observe("1", Some(Duration::from_secs(30)), at(40)),
+            Some(ConnectionChange::Disconnected)
+        );
+        assert_eq!(connections.observe("1", Some(Duration::from_secs(40)), at(50)), None);
+        assert_eq!(connections.away_for("1", at(50)), Duration::from_secs(40));
+        assert!(!connections.past_grace("1", grace, at(99)));
+        assert!(connections.past_grace("1", grace, at(100)));
+
+        assert_eq!(
+            connections.observe("1", Some(Duration::ZERO), at(110)),
+            Some(ConnectionChange::Reconnected(Duration::from_secs(100)))
+        );
+        assert_eq!(connections.away_for("1", at(110)), Duration::ZERO);
+        assert!(!connections.past_grace("1", grace, at(500)));
+
+        // logging out drops the connection too
+        assert_eq!(connections.observe("2", None, at(0)), Some(ConnectionChange::Disconnected));
+        assert_eq!(connections.away_for("2", at(30)), Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+/**
+ *  tokens for a shared screen at the table -- a tablet that sits by the board -- that play one local user's seat in
+ *  one game and nothing else, so the screen doesn't have to carry its owner's login.  the owner of the local user asks
+ *  for one with POST /auth/api/v1/games/{game_id}/local/{local_user_id}/token and gets a token good for
+ *  SEAT_TOKEN_SECONDS.
+ *
+ *  a seat token is the owner's login limited by its SeatScope:
+ *
+ *  - the authentication middleware only lets it call the seat routes (the actions, the explanations of them, and the
+ *    state of the game -- not forfeiting), and only for its game
+ *  - every action it sends is taken by the local user.  X-Acting-As can't name anybody else (see ActingPlayer)
+ *  - its only role is User, whatever the owner's are, and it can't be used to get another one
+ *
+ *  the owner still has to own the local user every time it is used, and the seat's capabilities still apply (see
+ *  local_seats.rs) -- so a seat that is spectator only can't do anything with one either.
+ */
+use actix_web::{http::Method, web, HttpResponse};
+use reqwest::StatusCode;
+
+use crate::{
+    games_service::game_container::game_container::GameContainer,
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    routes::{self, ServedRoute},
+    shared::{
+        service_models::{Claims, SeatScope},
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+use super::local_seats::verify_owner;
+
+/// how long a seat token is good for: a long game night
+pub const SEAT_TOKEN_SECONDS: u64 = 12 * 60 * 60;
+
+/// the routes other than the actions a seat token can call
+const SEAT_READ_ROUTES: [&str; 2] = [
+    "/auth/api/v1/games/{game_id}/state",
+    "/auth/api/v1/games/{game_id}/actions/explain/{action}",
+];
+
+fn is_seat_route(route: &ServedRoute) -> bool {
+    let action = route.path.starts_with("/auth/api/v1/action/") && !route.path.contains("/forfeit/");
+    let read = route.method == Method::GET && SEAT_READ_ROUTES.contains(&route.path.as_str());
+    route.path.contains("{game_id}") && (action || read)
+}
+
+lazy_static::lazy_static! {
+    static ref SEAT_ROUTES: Vec<ServedRoute> = routes::manifest().into_iter().filter(is_seat_route).collect();
+}
+
+/// true if a seat token for the seat can make the call
+pub fn allows(seat: &SeatScope, method: &Method, path: &str) -> bool {
+    SEAT_ROUTES.iter().any(|route| {
+        route.method == *method
+            && routes::match_path(&route.path, path)
+                .map_or(false, |params| params.get("game_id") == Some(&seat.game_id))
+    })
+}
+
+/// the owner of a local user in the game gets a token that plays the local user's seat
+pub async fn mint(
+    game_id: &str,
+    local_user_id: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call");
+    if claims.seat.is_some() {
+        return new_unauthorized_response!("a seat token can't get another one");
+    }
+    verify_owner(local_user_id, &claims.id, request_context).await?;
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    if !game.players.contains_key(local_user_id) {
+        return Err(ServiceResponse::new(
+            &format!("{} is not playing in {}", local_user_id, game_id),
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        ));
+    }
+    let seat = SeatScope {
+        game_id: game_id.to_owned(),
+        local_user_id: local_user_id.to_owned(),
+    };
+    let token = request_context
+        .security_context
+        .login_keys
+        .sign_claims(&Claims::new_seat(claims, seat, SEAT_TOKEN_SECONDS))
+        .map_err(|e| {
+            ServiceResponse::new(
+                "failed to sign the seat token",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ResponseType::ErrorInfo(e.to_string()),
+                GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+            )
+        })?;
+    tracing::info!("{} got a seat token for {} in {}", claims.id, local_user_id, game_id);
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::Token(token),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn mint_handler(path: web::Path<(String, String)>, request_context: RequestContext) -> HttpResponse {
+    let (game_id, local_user_id) = path.into_inner();
+    mint(&game_id, &local_user_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{
+            actions::actions,
+            catan_games::{games::regular::regular_game::RegularGame, traits::game_trait::GameTrait},
+            shared::game_enums::GameState,
+        },
+        shared::{service_models::Role, shared_models::UserProfile},
+    };
+
+    #[test]
+    fn test_allows() {
+        let seat = SeatScope {
+            game_id: "game".to_owned(),
+            local_user_id: "local".to_owned(),
+        };
+        assert!(allows(&seat, &Method::POST, "/auth/api/v1/action/roll/game"));
+        assert!(allows(&seat, &Method::POST, "/auth/api/v1/action/trade/accept/game/offer"));
+        assert!(allows(&seat, &Method::GET, "/auth/api/v1/games/game/state"));
+
+        // another game, forfeiting, the wrong method, anything that isn't playing the seat
+        assert!(!allows(&seat, &Method::POST, "/auth/api/v1/action/roll/other"));
+        assert!(!allows(&seat, &Method::POST, "/auth/api/v1/action/trade/accept/other/game"));
+        assert!(!allows(&seat, &Method::POST, "/auth/api/v1/action/forfeit/game"));
+        assert!(!allows(&seat, &Method::GET, "/auth/api/v1/action/roll/game"));
+        assert!(!allows(&seat, &Method::POST, "/auth/api/v1/games/game/local/local/token"));
+        assert!(!allows(&seat, &Method::GET, "/auth/api/v1/users/local"));
+        assert!(!allows(&seat, &Method::GET, "/auth/api/v1/longpoll/0"));
+    }
+
+    #[tokio::test]
+    async fn test_seat_ends_only_its_turn() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("local".to_string())));
+        let game_id = game.id.clone();
+        game.set_player_order(vec!["1".to_string(), "local".to_string()]).unwrap();
+        game.current_player_id = "1".to_string();
+        game.game_state = GameState::BuyingAndTrading;
+        GameContainer::create_and_add_container(&game_id, &game)
+            .await
+            .expect("new game id");
+        let seat = SeatScope {
+            game_id: game_id.clone(),
+            local_user_id: "local".to_owned(),
+        };
+
+        // the token can call next for its game, but every action it sends is the local user's, and it isn't their turn
+        assert!(allows(&seat, &Method::POST, &format!("/auth/api/v1/action/next/{}", game_id)));
+        let refused = actions::next(&game_id, Some(&seat.local_user_id), &RequestContext::test_default(false))
+            .await
+            .expect_err("it is 1's turn");
+        assert_eq!(refused.status, StatusCode::BAD_REQUEST);
+        let (current, _) = GameContainer::current_game(&game_id).await.unwrap();
+        assert_eq!(current.current_player_id, "1");
+        assert_eq!(current.game_state, GameState::BuyingAndTrading);
+
+        GameContainer::remove_container(&game_id).await.unwrap();
+    }
+
+    #[test]
+    fn test_seat_claims() {
+        let mut owner = Claims::new("owner", "owner@example.com", 60, &vec![Role::User, Role::Admin], &None);
+        owner.session_id = Some("session".to_owned());
+        let seat = SeatScope {
+            game_id: "game".to_owned(),
+            local_user_id: "local".to_owned(),
+        };
+        let claims = Claims::new_seat(&owner, seat.clone(), SEAT_TOKEN_SECONDS);
+        // the owner's session, so ending it ends the seat token too -- but never the owner's other roles
+        assert_eq!(claims.session_id, owner.session_id);
+        assert_eq!(claims.roles, vec![Role::User]);
+        assert_eq!(claims.seat, Some(seat));
+    }
+}
@@ -0,0 +1,165 @@
+#![allow(dead_code)]
+/**
+ *  player to player trading.  the current player can make an offer to one player or to everybody, and any other
+ *  player can make an offer to the current player.  the player(s) an offer was made to can accept it, reject it, or
+ *  answer it with a counter offer.
+ *
+ *  open offers are part of the game, so every change here is pushed to the long poller as a GameUpdate and the other
+ *  players see (and can respond to) new offers as soon as they are made.  offers expire after a while and are all
+ *  closed at the end of the turn.
+ *
+ *  the current player can also trade with the bank at 4:1, or at a better rate at a harbor they have built on.  the
+ *  best-rate api picks the harbor for them, so the rules for harbors only have to live here.
+ */
+
+use reqwest::StatusCode;
+
+use crate::{
+    games_service::shared::game_models::{
+        BankTradeData, BestBankTradeData, TradeOffer, TradeOfferData,
+    },
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+use super::actions::{current_game_or_not_found, push_and_return_actions, rejected_action};
+
+fn trade_offer_response(offer: TradeOffer) -> ServiceResponse {
+    ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::TradeOffer(offer),
+        GameError::NoError(String::default()),
+    )
+}
+
+/**
+ * open a new offer.  returns the offer so the caller knows its id
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn offer_trade(
+    game_id: &str,
+    caller_id: &str,
+    data: &TradeOfferData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let (new_game, offer) = game
+        .offer_trade(caller_id, data, request_context.environment.now())
+        .map_err(rejected_action)?;
+    push_and_return_actions(
+        game_id,
+        &new_game,
+        "OfferTrade",
+        Some(caller_id),
+        request_context,
+    )
+    .await?;
+    Ok(trade_offer_response(offer))
+}
+
+/**
+ * take an offer.  the cards are swapped and the offer is closed
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn accept_trade(
+    game_id: &str,
+    offer_id: &str,
+    caller_id: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game
+        .accept_trade(caller_id, offer_id, request_context.environment.now())
+        .map_err(rejected_action)?;
+    push_and_return_actions(
+        game_id,
+        &new_game,
+        "AcceptTrade",
+        Some(caller_id),
+        request_context,
+    )
+    .await
+}
+
+/**
+ * turn an offer down, or withdraw it if the caller made it
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn reject_trade(
+    game_id: &str,
+    offer_id: &str,
+    caller_id: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game
+        .reject_trade(caller_id, offer_id, request_context.environment.now())
+        .map_err(rejected_action)?;
+    push_and_return_actions(
+        game_id,
+        &new_game,
+        "RejectTrade",
+        Some(caller_id),
+        request_context,
+    )
+    .await
+}
+
+/**
+ * answer an offer with a different one, made back to the player who made the original.  returns the counter offer
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn counter_trade(
+    game_id: &str,
+    offer_id: &str,
+    caller_id: &str,
+    data: &TradeOfferData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let (new_game, offer) = game
+        .counter_trade(caller_id, offer_id, data, request_context.environment.now())
+        .map_err(rejected_action)?;
+    push_and_return_actions(
+        game_id,
+        &new_game,
+        "CounterTrade",
+        Some(caller_id),
+        request_context,
+    )
+    .await?;
+    Ok(trade_offer_response(offer))
+}
+
+/**
+ * trade cards with the bank, at 4:1 or at the rate of a harbor the caller has built on
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn bank_trade(
+    game_id: &str,
+    caller_id: &str,
+    data: &BankTradeData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game.bank_trade(caller_id, data).map_err(rejected_action)?;
+    push_and_return_actions(game_id, &new_game, "BankTrade", Some(caller_id), request_context).await
+}
+
+/**
+ * trade cards with the bank at the best rate the caller gets -- the caller doesn't say which harbor to use
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn best_bank_trade(
+    game_id: &str,
+    caller_id: &str,
+    data: &BestBankTradeData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = current_game_or_not_found(game_id).await?;
+    let new_game = game
+        .best_bank_trade(caller_id, data)
+        .map_err(rejected_action)?;
+    push_and_return_actions(game_id, &new_game, "BankTrade", Some(caller_id), request_context).await
+}
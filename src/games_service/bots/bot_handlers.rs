@@ -0,0 +1,28 @@
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    get_header_value,
+    middleware::{header_extractor::HeadersExtractor, request_context_mw::RequestContext},
+};
+
+use super::engine::BotDifficulty;
+
+/**
+ * add a bot to the game in the x-game-id header.  the difficulty is Easy, Medium or Hard
+ */
+pub async fn add_bot(
+    difficulty: web::Path<BotDifficulty>,
+    headers: HeadersExtractor,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let game_id = get_header_value!(game_id, headers);
+    let caller_id = &request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs")
+        .id;
+    super::bots::add_bot(&game_id, caller_id, difficulty.into_inner(), &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
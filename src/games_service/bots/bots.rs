@@ -0,0 +1,257 @@
+#![allow(dead_code)]
+/**
+ *  bots fill the empty seats in a game.  the game's creator adds them while the game is AddingPlayers, and each one
+ *  is a local user owned by the creator, so it shows up in the game (and the game's stats) like anybody else.
+ *
+ *  when the first bot joins a game, a driver task is started for the game.  the driver looks at the game every
+ *  BOT_MOVE_DELAY and, if a bot has something to do, asks the engine (see engine.rs) for a move and makes it through
+ *  the same action apis the clients call -- so a bot's moves are checked, pushed, broadcast and undone exactly like a
+ *  person's.  a game has one driver, so a seat is only ever played by one.  the driver stops when the game ends or is
+ *  removed.  if a seat's moves keep getting rejected the driver gives up on that seat, not the game: a bot forfeits,
+ *  so the turn moves on, and a seat that was handed to a bot goes back to its player.
+ *
+ *  a player can also hand their seat to a bot while they step away (see delegation.rs).  the driver plays that seat
+ *  like a bot's until the player takes it back -- and starts for the game then, if it has no bots of its own.  the
+ *  driver's moves aren't made with anybody's claims, which is how the event log can tell them from the player's.
+ */
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+    time::Duration,
+};
+
+use reqwest::StatusCode;
+
+use crate::{
+    games_service::{
+        actions::{actions, delegation, forfeit},
+        catan_games::{games::regular::regular_game::RegularGame, traits::game_trait::GameTrait},
+        game_container::game_container::GameContainer,
+        shared::{game_enums::GameState, game_models::Delegate},
+    },
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        error_reporting,
+        service_models::PersistUser,
+        shared_models::{GameError, ResponseType, ServiceResponse, UserProfile, UserType},
+    },
+};
+
+use super::engine::{self, BotDifficulty, BotMove};
+
+/// how long the driver waits between moves, so that the people in the game can follow along
+pub const BOT_MOVE_DELAY: Duration = Duration::from_millis(750);
+
+/// how many moves in a row can be rejected before the driver gives up on the seat
+pub const MAX_REJECTED_MOVES: u32 = 5;
+
+lazy_static::lazy_static! {
+    // bot user id -> how well it plays
+    static ref BOTS: RwLock<HashMap<String, BotDifficulty>> = RwLock::new(HashMap::new());
+    // the games that have a driver
+    static ref DRIVEN: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+}
+
+/// true if the user is a bot
+pub fn is_bot(user_id: &str) -> bool {
+    BOTS.read()
+        .expect("the bot lock shouldn't be poisoned")
+        .contains_key(user_id)
+}
+
+/// true if the seat is played by the bot driver: it is a bot's, or its player handed it to a bot.  nothing else acts
+/// for these seats, so the driver is the only one playing them
+pub fn is_driven(game: &RegularGame, user_id: &str) -> bool {
+    is_bot(user_id) || matches!(game.delegate_of(user_id), Some(Delegate::Bot(_)))
+}
+
+pub fn difficulty_of(user_id: &str) -> Option<BotDifficulty> {
+    BOTS.read()
+        .expect("the bot lock shouldn't be poisoned")
+        .get(user_id)
+        .cloned()
+}
+
+/// the bot has left its game for good
+pub fn forget(bot_id: &str) {
+    BOTS.write()
+        .expect("the bot lock shouldn't be poisoned")
+        .remove(bot_id);
+}
+
+fn bad_request(message: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        message,
+        StatusCode::BAD_REQUEST,
+        ResponseType::ErrorInfo(message.to_owned()),
+        GameError::BadActionData(message.to_owned()),
+    )
+}
+
+/**
+ *  add a bot to the game.  only the game's creator can, and only while the game is still adding players.  returns the
+ *  bot's profile
+ */
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn add_bot(
+    game_id: &str,
+    caller_id: &str,
+    difficulty: BotDifficulty,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    if game.creator_id != caller_id {
+        return new_unauthorized_response!("only the game's creator can add bots");
+    }
+    if game.game_state != GameState::AddingPlayers {
+        return Err(bad_request("bots can only be added while the game is adding players"));
+    }
+    if game.players.len() >= game.max_players() {
+        return Err(bad_request("the game is full"));
+    }
+    let bots_in_game = game.players.keys().filter(|id| is_bot(id)).count();
+
+    let mut profile = UserProfile::default();
+    profile.user_id = Some(request_context.environment.new_id());
+    profile.user_type = UserType::Local;
+    profile.display_name = format!("{:?} Bot {}", difficulty, bots_in_game + 1);
+    profile.games_played = Some(0);
+    profile.games_won = Some(0);
+    let bot_id = profile.user_id.clone().unwrap();
+
+    request_context
+        .database
+        .update_or_create_user(&PersistUser::from_local_user(caller_id, &profile))
+        .await?;
+    BOTS.write()
+        .expect("the bot lock shouldn't be poisoned")
+        .insert(bot_id.clone(), difficulty);
+    if let Err(e) = GameContainer::add_player(game_id, &profile).await {
+        BOTS.write()
+            .expect("the bot lock shouldn't be poisoned")
+            .remove(&bot_id);
+        return Err(e);
+    }
+
+    if bots_in_game == 0 {
+        start_driver(game_id, request_context);
+    }
+    Ok(ServiceResponse::new(
+        "added",
+        StatusCode::OK,
+        ResponseType::Profile(profile),
+        GameError::NoError(String::default()),
+    ))
+}
+
+async fn play(
+    game_id: &str,
+    bot_id: &str,
+    bot_move: &BotMove,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    match bot_move {
+        BotMove::Build(data) => actions::build(game_id, bot_id, data, request_context).await,
+        BotMove::Roll => actions::roll(game_id, bot_id, None, request_context).await,
+        BotMove::Discard(cards) => actions::discard(game_id, bot_id, cards, request_context).await,
+        BotMove::MoveBaron(data) => {
+            actions::move_baron(game_id, bot_id, data, request_context).await
+        }
+        BotMove::Next => actions::next(game_id, Some(bot_id), request_context).await,
+    }
+}
+
+/// the driver can't play the seat: a bot forfeits, so its turn moves on, and a seat that was handed to a bot goes back
+/// to its player
+async fn give_up_seat(game_id: &str, seat_id: &str, request_context: &RequestContext) -> Result<(), ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    if game.delegate_of(seat_id).is_some() {
+        delegation::revoke(game_id, seat_id, request_context).await?;
+    } else {
+        forfeit::forfeit(game_id, seat_id, request_context).await?;
+        forget(seat_id);
+    }
+    Ok(())
+}
+
+/// play the bots -- and the seats handed to a bot -- in the game until it is over.  a game only ever has one driver:
+/// asking for another while it has one does nothing
+pub fn start_driver(game_id: &str, request_context: &RequestContext) {
+    if !DRIVEN
+        .write()
+        .expect("the bot lock shouldn't be poisoned")
+        .insert(game_id.to_owned())
+    {
+        return;
+    }
+    let game_id = game_id.to_owned();
+    let mut request_context = request_context.clone();
+    request_context.claims = None; // the bots' moves aren't anybody's
+    actix_web::rt::spawn(async move {
+        let mut bot_ids: Vec<String> = Vec::new();
+        let mut rejected = 0;
+        loop {
+            tokio::time::sleep(BOT_MOVE_DELAY).await;
+            let game = match GameContainer::current_game(&game_id).await {
+                Ok((game, _)) => game,
+                Err(_) => break, // the game is over and has been cleaned up
+            };
+            if game.game_state == GameState::GameOver {
+                break;
+            }
+            bot_ids = game.players.keys().filter(|id| is_bot(id)).cloned().collect();
+            let mut seats: Vec<(String, BotDifficulty)> = bot_ids
+                .iter()
+                .filter_map(|bot_id| difficulty_of(bot_id).map(|difficulty| (bot_id.clone(), difficulty)))
+                .collect();
+            seats.extend(game.delegations.iter().filter_map(|(user_id, delegate)| match delegate {
+                Delegate::Bot(difficulty) => Some((user_id.clone(), *difficulty)),
+                Delegate::Player(_) => None,
+            }));
+            seats.sort_by(|a, b| a.0.cmp(&b.0));
+
+            //  one move at a time, then look at the game again
+            let next_move = seats.iter().find_map(|(seat_id, difficulty)| {
+                engine::choose_move(&game, seat_id, *difficulty).map(|bot_move| (seat_id.clone(), bot_move))
+            });
+            let (bot_id, bot_move) = match next_move {
+                Some(next_move) => next_move,
+                None => continue,
+            };
+            match play(&game_id, &bot_id, &bot_move, &request_context).await {
+                Ok(_) => rejected = 0,
+                Err(e) => {
+                    rejected += 1;
+                    tracing::warn!("bot {} couldn't {:?} in {}: {:#?}", bot_id, bot_move, game_id, e);
+                    if rejected >= MAX_REJECTED_MOVES {
+                        rejected = 0;
+                        tracing::warn!(
+                            "giving up on {} in {} after {} rejected moves",
+                            bot_id,
+                            game_id,
+                            MAX_REJECTED_MOVES
+                        );
+                        if let Err(e) = give_up_seat(&game_id, &bot_id, &request_context).await {
+                            let message = format!(
+                                "the bots in {} stopped playing: {} was stuck and couldn't be taken out: {:#?}",
+                                game_id, bot_id, e
+                            );
+                            tracing::error!("{}", message);
+                            error_reporting::report_background_failure("bot_driver", &message);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        let mut bots = BOTS.write().expect("the bot lock shouldn't be poisoned");
+        for bot_id in &bot_ids {
+            bots.remove(bot_id);
+        }
+        DRIVEN
+            .write()
+            .expect("the bot lock shouldn't be poisoned")
+            .remove(&game_id);
+    });
+}
@@ -0,0 +1,405 @@
+#![allow(dead_code)]
+/**
+ *  the bot engine looks at a game and picks a bot's next move.  it never changes the game -- every move it picks has
+ *  already been tried on a clone of the game with the same methods the action apis use, so the only moves it picks
+ *  are ones a human could have made.  the bot driver (see bots.rs) then makes the move through the action apis.
+ *
+ *  the heuristics are simple:
+ *
+ *  1. every corner is worth the pips (the number of ways to roll its numbers) of the tiles around it
+ *  2. Easy picks at random from the legal moves, Medium picks the move worth the most pips, and Hard also counts
+ *     resources it doesn't have yet and puts the baron on the leader
+ *  3. in a turn a bot builds cities, then settlements, then roads, for as long as it can afford them
+ *
+ *  bots don't trade, play development cards or build ships.
+ */
+use std::collections::{HashMap, HashSet};
+
+use rand::seq::SliceRandom;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::games_service::{
+    buildings::{
+        building_enums::{BuildingPosition, BuildingState},
+        building_key::BuildingKey,
+    },
+    catan_games::games::regular::regular_game::RegularGame,
+    roads::{
+        longest_road::{building_corner, road_corners, Corner},
+        road_key::RoadKey,
+    },
+    shared::{
+        game_enums::{Direction, GameState, ResourceType},
+        game_models::{BuildData, MoveBaronData, ResourceCards},
+    },
+    tiles::tile_enums::TileResource,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, JsonSchema)]
+pub enum BotDifficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotMove {
+    Build(BuildData),
+    Roll,
+    Discard(ResourceCards),
+    MoveBaron(MoveBaronData),
+    Next,
+}
+
+/// the number of ways two dice can make the roll
+fn pips(roll: u32) -> u32 {
+    match roll {
+        2..=6 => roll - 1,
+        8..=12 => 13 - roll,
+        _ => 0,
+    }
+}
+
+/// what each corner on the board touches: the pips of its tiles and the resources they make
+fn corner_values(game: &RegularGame) -> HashMap<Corner, (u32, Vec<ResourceType>)> {
+    let mut values: HashMap<Corner, (u32, Vec<ResourceType>)> = HashMap::new();
+    for tile in game.tiles.values() {
+        for position in BuildingPosition::iter() {
+            let value = values
+                .entry(building_corner(&BuildingKey::new(position, tile.tile_key)))
+                .or_default();
+            value.0 += pips(tile.roll);
+            if let Some(resource) = tile.current_resource.produces() {
+                value.1.push(resource);
+            }
+        }
+    }
+    values
+}
+
+struct Scorer {
+    corners: HashMap<Corner, (u32, Vec<ResourceType>)>,
+    produced: HashSet<ResourceType>, // what the bot's buildings already make
+    difficulty: BotDifficulty,
+}
+
+impl Scorer {
+    fn new(game: &RegularGame, bot_id: &str, difficulty: BotDifficulty) -> Self {
+        let corners = corner_values(game);
+        let produced = game.players.get(bot_id).map_or(HashSet::new(), |player| {
+            player
+                .buildings
+                .iter()
+                .filter_map(|building| corners.get(&building_corner(&building.building_key)))
+                .flat_map(|(_, resources)| resources.iter().cloned())
+                .collect()
+        });
+        Self {
+            corners,
+            produced,
+            difficulty,
+        }
+    }
+
+    fn corner(&self, corner: &Corner) -> u32 {
+        let (pips, resources) = self.corners.get(corner).cloned().unwrap_or_default();
+        match self.difficulty {
+            BotDifficulty::Hard => {
+                let new: HashSet<&ResourceType> = resources
+                    .iter()
+                    .filter(|resource| !self.produced.contains(resource))
+                    .collect();
+                pips + 2 * new.len() as u32
+            }
+            _ => pips,
+        }
+    }
+
+    /// a road is worth the better of the corners it leads to
+    fn road(&self, road_key: &RoadKey) -> u32 {
+        road_corners(road_key)
+            .iter()
+            .map(|corner| self.corner(corner))
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn build(&self, data: &BuildData) -> u32 {
+        match data {
+            BuildData::Settlement(key) | BuildData::City(key) => {
+                self.corner(&building_corner(key))
+            }
+            BuildData::Road(key) | BuildData::Ship(key) => self.road(key),
+        }
+    }
+}
+
+/// one key for every corner on the board, in a stable order
+fn settlement_keys(game: &RegularGame) -> Vec<BuildingKey> {
+    let mut keys: Vec<(Corner, BuildingKey)> = Vec::new();
+    let mut seen = HashSet::new();
+    for tile in game.tiles.values() {
+        for position in BuildingPosition::iter() {
+            let key = BuildingKey::new(position, tile.tile_key);
+            if seen.insert(building_corner(&key)) {
+                keys.push((building_corner(&key), key));
+            }
+        }
+    }
+    keys.sort_by_key(|(corner, _)| *corner);
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+/// one key for every edge on the board, in a stable order
+fn road_keys(game: &RegularGame) -> Vec<RoadKey> {
+    let mut keys: Vec<([Corner; 2], RoadKey)> = Vec::new();
+    let mut seen = HashSet::new();
+    for tile in game.tiles.values() {
+        for direction in Direction::iter() {
+            let key = RoadKey::new(direction, tile.tile_key);
+            let mut corners = road_corners(&key);
+            corners.sort();
+            if seen.insert(corners) {
+                keys.push((corners, key));
+            }
+        }
+    }
+    keys.sort_by_key(|(corners, _)| *corners);
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+/// the legal builds, best first -- or shuffled for an Easy bot
+fn legal_builds(
+    game: &RegularGame,
+    bot_id: &str,
+    scorer: &Scorer,
+    candidates: Vec<BuildData>,
+) -> Vec<BuildData> {
+    let mut legal: Vec<BuildData> = candidates
+        .into_iter()
+        .filter(|data| game.build(bot_id, data).is_ok())
+        .collect();
+    match scorer.difficulty {
+        BotDifficulty::Easy => legal.shuffle(&mut rand::thread_rng()),
+        _ => legal.sort_by_key(|data| std::cmp::Reverse(scorer.build(data))),
+    }
+    legal
+}
+
+/// the first thing the bot can build, trying cities, then settlements, then roads
+fn best_build(game: &RegularGame, bot_id: &str, scorer: &Scorer) -> Option<BuildData> {
+    let cities = game.players.get(bot_id).map_or(vec![], |player| {
+        player
+            .buildings
+            .iter()
+            .filter(|building| building.state == BuildingState::Settlement)
+            .map(|building| BuildData::City(building.building_key))
+            .collect()
+    });
+    let settlements = settlement_keys(game)
+        .into_iter()
+        .map(BuildData::Settlement)
+        .collect();
+    let roads = road_keys(game).into_iter().map(BuildData::Road).collect();
+
+    for candidates in vec![cities, settlements, roads] {
+        if let Some(data) = legal_builds(game, bot_id, scorer, candidates).into_iter().next() {
+            return Some(data);
+        }
+    }
+    None
+}
+
+/// give up the cards the bot has the most of
+pub fn cards_to_discard(hand: &ResourceCards, owed: u32) -> ResourceCards {
+    let mut left: Vec<(ResourceType, u32)> = ResourceCards::RESOURCES
+        .iter()
+        .map(|resource| (*resource, hand.count(*resource)))
+        .collect();
+    let mut discard = ResourceCards::default();
+    for _ in 0..owed.min(hand.total()) {
+        if let Some(most) = left.iter_mut().max_by_key(|(_, count)| *count) {
+            most.1 -= 1;
+            discard.add(most.0, 1);
+        }
+    }
+    discard
+}
+
+/// where to put the baron: on the tile that hurts the other players the most and the bot the least
+fn baron_move(game: &RegularGame, bot_id: &str, difficulty: BotDifficulty) -> Option<MoveBaronData> {
+    let mut moves: Vec<(i64, MoveBaronData)> = game
+        .tiles
+        .values()
+        .filter(|tile| tile.tile_key != game.baron_tile && !tile.current_resource.is_sea())
+        .map(|tile| {
+            let mut score = 0i64;
+            for player in game.players.values() {
+                let touching = player
+                    .buildings
+                    .iter()
+                    .filter(|building| building.touches_tile(&tile.tile_key))
+                    .count() as i64;
+                let id = player.profile.user_id.clone().unwrap_or_default();
+                if id == bot_id {
+                    score -= 10 * touching * pips(tile.roll) as i64;
+                } else {
+                    //  Hard goes after whoever has built the most
+                    let weight = match difficulty {
+                        BotDifficulty::Hard => player.buildings.len() as i64,
+                        _ => 1,
+                    };
+                    score += weight * touching * pips(tile.roll) as i64;
+                }
+            }
+            //  steal from whoever has the most cards
+            let victim_id = game
+                .baron_victims(&tile.tile_key, bot_id)
+                .into_iter()
+                .max_by_key(|id| {
+                    game.players
+                        .get(id)
+                        .map_or(0, |player| player.resources.total())
+                });
+            (
+                score,
+                MoveBaronData {
+                    tile_key: tile.tile_key,
+                    victim_id,
+                },
+            )
+        })
+        .filter(|(_, data)| game.move_baron(bot_id, data).is_ok())
+        .collect();
+
+    match difficulty {
+        BotDifficulty::Easy => moves.choose(&mut rand::thread_rng()).map(|(_, data)| data.clone()),
+        _ => {
+            moves.sort_by_key(|(score, data)| (std::cmp::Reverse(*score), data.tile_key.to_string()));
+            moves.into_iter().next().map(|(_, data)| data)
+        }
+    }
+}
+
+/// Picks the bot's next move, or None if there is nothing for it to do right now -- it isn't its turn, or the game is
+/// waiting on a person (e.g. the creator choosing the board).
+pub fn choose_move(game: &RegularGame, bot_id: &str, difficulty: BotDifficulty) -> Option<BotMove> {
+    let player = game.players.get(bot_id)?;
+    if game.game_state == GameState::GameOver || game.pending_dev_card.is_some() {
+        return None;
+    }
+    //  a 7 makes everybody with too many cards discard, whoever's turn it is
+    if let Some(owed) = game.pending_discards.get(bot_id) {
+        return Some(BotMove::Discard(cards_to_discard(&player.resources, *owed)));
+    }
+    if game.current_player_id != bot_id {
+        return None;
+    }
+
+    let scorer = Scorer::new(game, bot_id, difficulty);
+    match game.game_state {
+        GameState::AllocateResourceForward | GameState::AllocateResourceReverse => {
+            if game.setup_turn_done() {
+                Some(BotMove::Next)
+            } else {
+                best_build(game, bot_id, &scorer).map(BotMove::Build)
+            }
+        }
+        GameState::WaitingForRoll => Some(BotMove::Roll),
+        GameState::MustMoveBaron => baron_move(game, bot_id, difficulty).map(BotMove::MoveBaron),
+        GameState::BuyingAndTrading => Some(
+            best_build(game, bot_id, &scorer)
+                .map(BotMove::Build)
+                .unwrap_or(BotMove::Next),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::catan_games::traits::{
+            game_state_machine_trait::StateMachineTrait, game_trait::GameTrait,
+        },
+        shared::shared_models::{GameError, UserProfile},
+    };
+
+    fn apply(game: &RegularGame, bot_id: &str, bot_move: &BotMove, roll: u32) -> Result<RegularGame, GameError> {
+        match bot_move {
+            BotMove::Build(data) => game.build(bot_id, data),
+            BotMove::Roll => game.roll(roll),
+            BotMove::Discard(cards) => game.discard(bot_id, cards),
+            BotMove::MoveBaron(data) => game.move_baron(bot_id, data),
+            BotMove::Next => game.set_next_state(),
+        }
+    }
+
+    #[test]
+    fn test_discard() {
+        let hand = ResourceCards::new(4, 1, 0, 3, 0);
+        let discard = cards_to_discard(&hand, 4);
+        assert_eq!(discard.total(), 4);
+        assert!(hand.contains(&discard));
+        assert_eq!(discard.count(ResourceType::Wood), 2);
+        assert_eq!(discard.count(ResourceType::Wheat), 2);
+    }
+
+    #[test]
+    fn test_bots_play() {
+        for difficulty in [BotDifficulty::Easy, BotDifficulty::Medium, BotDifficulty::Hard].iter() {
+            let creator = UserProfile::new_test_user(Some("1".to_string()));
+            let mut game = RegularGame::new(&creator);
+            GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+            GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("3".to_string())));
+            game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+                .unwrap();
+            game.game_state = GameState::AllocateResourceForward;
+            game.current_player_id = "1".to_string();
+
+            //  every player is a bot: set up the board, then play a few rounds
+            let rolls = [8, 7, 6, 5, 9, 4, 10, 7, 3, 11, 6, 8];
+            let mut turns = 0;
+            for step in 0..500 {
+                if turns == rolls.len() || game.game_state == GameState::GameOver {
+                    break;
+                }
+                let bot_id = game
+                    .pending_discards
+                    .keys()
+                    .next()
+                    .cloned()
+                    .unwrap_or_else(|| game.current_player_id.clone());
+                let bot_move = choose_move(&game, &bot_id, *difficulty)
+                    .unwrap_or_else(|| panic!("{:?} bot {} is stuck in {:?}", difficulty, bot_id, game.game_state));
+                if bot_move == BotMove::Roll {
+                    turns += 1;
+                }
+                game = apply(&game, &bot_id, &bot_move, rolls[turns.max(1) - 1])
+                    .unwrap_or_else(|e| panic!("step {}: {:?} is illegal: {:?}", step, bot_move, e));
+                game.update_scores();
+                assert!(game.invariant_violations().is_empty());
+            }
+            assert_eq!(turns, rolls.len(), "{:?} bots should get through every roll", difficulty);
+            for player in game.players.values() {
+                assert!(player.buildings.len() >= 2);
+                assert!(player.roads.len() >= 2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_waits_its_turn() {
+        let creator = UserProfile::new_test_user(Some("1".to_string()));
+        let mut game = RegularGame::new(&creator);
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        assert_eq!(choose_move(&game, "2", BotDifficulty::Medium), None); // still adding players
+        game.game_state = GameState::WaitingForRoll;
+        game.current_player_id = "1".to_string();
+        assert_eq!(choose_move(&game, "2", BotDifficulty::Medium), None);
+        assert_eq!(choose_move(&game, "1", BotDifficulty::Medium), Some(BotMove::Roll));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod bot_handlers;
+pub mod bots;
+pub mod engine;
@@ -3,8 +3,9 @@
 use super::{building_enums::BuildingState, building_key::BuildingKey};
 use crate::games_service::tiles::tile_key::TileKey;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Building {
     pub building_key: BuildingKey,
@@ -31,6 +32,11 @@ impl Building {
             state: BuildingState::Empty,
         }
     }
+    /// true if this building sits on a corner of the tile, regardless of which alias it was created from
+    pub fn touches_tile(&self, tile_key: &TileKey) -> bool {
+        self.building_key.tile_key == *tile_key
+            || self.aliases.iter().any(|alias| alias.tile_key == *tile_key)
+    }
     pub fn default(key: BuildingKey) -> Self {
         Building {
             building_key: key,
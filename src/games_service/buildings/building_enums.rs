@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use strum_macros::EnumIter;
 
 // Enum representing the position of a building on a board
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, EnumIter, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, EnumIter, Clone, Copy, JsonSchema)]
 pub enum BuildingPosition {
     Right,
     BottomRight,
@@ -12,7 +13,7 @@ pub enum BuildingPosition {
     TopRight,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 pub enum BuildingState {
     Empty,
     Settlement,
@@ -3,6 +3,7 @@ use crate::games_service::shared::game_enums::Direction;
 use crate::games_service::tiles::tile::Tile;
 use crate::games_service::tiles::tile_key::TileKey;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
@@ -10,7 +11,7 @@ use std::str::FromStr;
 use super::building_enums::BuildingPosition;
 
 // Struct representing a building alias containing position, coordinates and index of a building
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct BuildingKey {
     pub building_position: BuildingPosition,
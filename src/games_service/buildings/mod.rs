@@ -1,3 +1,4 @@
 pub mod building;
 pub mod building_enums;
 pub mod building_key;
+pub mod placement_validator;
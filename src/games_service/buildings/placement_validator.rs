@@ -0,0 +1,277 @@
+#![allow(dead_code)]
+/**
+ *  the board rules for where a settlement or road can go:
+ *
+ *  1. it has to be on the board
+ *  2. the spot can't already be built on
+ *  3. the distance rule: a settlement can't be one road away from another settlement or city
+ *  4. a road has to connect to the player's network -- one of its ends has to be the player's building, or the end
+ *     of another of the player's roads that isn't cut off by somebody else's building.  after setup, a settlement has
+ *     to be at the end of one of the player's roads
+ *
+ *  a failed check is returned as GameError::BadActionData holding a PlacementViolation as JSON, so that a client can
+ *  tell which rule failed (and where) without parsing the message.
+ */
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::roads::{
+        longest_road::{building_corner, edge_id, road_corners, Corner},
+        road::Road,
+        road_enums::RoadState,
+        road_key::RoadKey,
+    },
+    shared::shared_models::GameError,
+};
+
+use super::{building::Building, building_key::BuildingKey};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum PlacementRule {
+    OnBoard,
+    Unoccupied,
+    DistanceRule,
+    Connected,
+    Terrain, // roads and buildings go on land, ships go on the sea
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlacementViolation {
+    pub rule: PlacementRule,
+    pub location: String,
+    pub explanation: String,
+}
+
+impl PlacementViolation {
+    pub fn new(rule: PlacementRule, location: impl Display, explanation: &str) -> Self {
+        Self {
+            rule,
+            location: location.to_string(),
+            explanation: explanation.to_owned(),
+        }
+    }
+
+    /// the violation in an error returned by the validator, if that is what the error is
+    pub fn from_error(error: &GameError) -> Option<Self> {
+        match error {
+            GameError::BadActionData(json) => serde_json::from_str(json).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<PlacementViolation> for GameError {
+    fn from(violation: PlacementViolation) -> Self {
+        GameError::BadActionData(
+            serde_json::to_string(&violation).unwrap_or_else(|_| violation.explanation.clone()),
+        )
+    }
+}
+
+pub struct PlacementValidator<'a> {
+    buildings: &'a HashMap<BuildingKey, Building>,
+    roads: &'a HashMap<RoadKey, Road>,
+}
+
+impl<'a> PlacementValidator<'a> {
+    pub fn new(buildings: &'a HashMap<BuildingKey, Building>, roads: &'a HashMap<RoadKey, Road>) -> Self {
+        Self { buildings, roads }
+    }
+
+    /// a settlement has to be on an empty corner of the board with no building one road away
+    pub fn check_settlement(&self, building_key: &BuildingKey) -> Result<(), GameError> {
+        let corner = building_corner(building_key);
+        if !self
+            .buildings
+            .keys()
+            .any(|key| building_corner(key) == corner)
+        {
+            return Err(PlacementViolation::new(
+                PlacementRule::OnBoard,
+                building_key,
+                "the corner is not on the board",
+            )
+            .into());
+        }
+        if self.corner_owner(corner).is_some() {
+            return Err(PlacementViolation::new(
+                PlacementRule::Unoccupied,
+                building_key,
+                "the corner already has a building on it",
+            )
+            .into());
+        }
+        if self
+            .neighbor_corners(corner)
+            .into_iter()
+            .any(|neighbor| self.corner_owner(neighbor).is_some())
+        {
+            return Err(PlacementViolation::new(
+                PlacementRule::DistanceRule,
+                building_key,
+                "a settlement can't be one road away from another building",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// a road has to be on an unbuilt edge of the board and connect to the player's buildings or roads
+    pub fn check_road(&self, user_id: &str, road_key: &RoadKey) -> Result<(), GameError> {
+        let edge = edge_id(road_key);
+        let road = self
+            .roads
+            .values()
+            .find(|road| edge_id(road.primary_key()) == edge)
+            .ok_or_else(|| {
+                PlacementViolation::new(PlacementRule::OnBoard, road_key, "the road is not on the board")
+            })?;
+        if *road.state() != RoadState::Unbuilt {
+            return Err(PlacementViolation::new(
+                PlacementRule::Unoccupied,
+                road_key,
+                "the road has already been built",
+            )
+            .into());
+        }
+
+        let connected = road_corners(road_key)
+            .iter()
+            .any(|corner| match self.corner_owner(*corner) {
+                Some(owner) => owner == user_id,
+                None => self.player_road_ends(user_id).contains(corner),
+            });
+        if !connected {
+            return Err(PlacementViolation::new(
+                PlacementRule::Connected,
+                road_key,
+                "the road doesn't connect to any of the player's buildings or roads",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// a settlement bought during the game also has to be at the end of one of the player's roads
+    pub fn check_settlement_connected(
+        &self,
+        user_id: &str,
+        building_key: &BuildingKey,
+    ) -> Result<(), GameError> {
+        if !self
+            .player_road_ends(user_id)
+            .contains(&building_corner(building_key))
+        {
+            return Err(PlacementViolation::new(
+                PlacementRule::Connected,
+                building_key,
+                "the settlement isn't at the end of any of the player's roads",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// the corners one road away from this one
+    pub fn neighbor_corners(&self, corner: Corner) -> HashSet<Corner> {
+        self.roads
+            .values()
+            .filter_map(|road| match road_corners(road.primary_key()) {
+                [a, b] if a == corner => Some(b),
+                [a, b] if b == corner => Some(a),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the player who has a settlement or city on the corner
+    pub fn corner_owner(&self, corner: Corner) -> Option<&str> {
+        self.buildings
+            .values()
+            .find(|building| {
+                building.owner_id.is_some() && building_corner(&building.building_key) == corner
+            })
+            .and_then(|building| building.owner_id.as_deref())
+    }
+
+    fn player_road_ends(&self, user_id: &str) -> HashSet<Corner> {
+        self.roads
+            .values()
+            .filter(|road| road.is_owned_by(user_id))
+            .flat_map(|road| road_corners(road.primary_key()).to_vec())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games_service::{
+        buildings::building_enums::{BuildingPosition, BuildingState},
+        catan_games::games::regular::regular_game::RegularGame,
+        shared::game_enums::Direction,
+        tiles::tile_key::TileKey,
+    };
+    use crate::shared::shared_models::UserProfile;
+
+    fn violation(result: Result<(), GameError>) -> PlacementRule {
+        PlacementViolation::from_error(&result.expect_err("the placement should be rejected"))
+            .expect("the error should hold a PlacementViolation")
+            .rule
+    }
+
+    #[test]
+    fn test_placement_rules() {
+        let profile = UserProfile::new_test_user(None);
+        let user_id = profile.user_id.clone().unwrap();
+        let mut game = RegularGame::new(&profile);
+        let center = TileKey::new(0, 0, 0);
+        let top_right = BuildingKey::new(BuildingPosition::TopRight, center);
+        game.place_building(&user_id, &top_right, BuildingState::Settlement)
+            .expect("place_building should work");
+
+        let validator = PlacementValidator::new(&game.buildings, &game.roads);
+        assert_eq!(violation(validator.check_settlement(&top_right)), PlacementRule::Unoccupied);
+        assert_eq!(
+            violation(validator.check_settlement(&BuildingKey::new(BuildingPosition::TopLeft, center))),
+            PlacementRule::DistanceRule
+        );
+        assert_eq!(
+            violation(validator.check_settlement(&BuildingKey::new(
+                BuildingPosition::TopLeft,
+                TileKey::new(10, -10, 0)
+            ))),
+            PlacementRule::OnBoard
+        );
+        assert!(validator
+            .check_settlement(&BuildingKey::new(BuildingPosition::Left, center))
+            .is_ok());
+
+        // the North road touches the settlement, the South road touches nothing
+        let north = RoadKey::new(Direction::North, center);
+        assert!(validator.check_road(&user_id, &north).is_ok());
+        assert_eq!(
+            violation(validator.check_road("somebody else", &north)),
+            PlacementRule::Connected
+        );
+        assert_eq!(
+            violation(validator.check_road(&user_id, &RoadKey::new(Direction::South, center))),
+            PlacementRule::Connected
+        );
+
+        // once the North road is built it is taken, and a road can continue from its far end
+        game.place_road(&user_id, &north).expect("place_road should work");
+        let validator = PlacementValidator::new(&game.buildings, &game.roads);
+        assert_eq!(violation(validator.check_road(&user_id, &north)), PlacementRule::Unoccupied);
+        assert!(validator
+            .check_road(&user_id, &RoadKey::new(Direction::NorthWest, center))
+            .is_ok());
+    }
+}
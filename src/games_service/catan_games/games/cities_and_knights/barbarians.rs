@@ -0,0 +1,129 @@
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// how many times the ship face has to come up on the event die before the barbarians land
+pub const BARBARIAN_TRACK_LENGTH: u32 = 7;
+
+///
+/// the result of a barbarian attack.  the barbarians are as strong as the number of cities on the board, and the
+/// players defend with the total strength of their active knights
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum BarbarianAttackOutcome {
+    /// the players won.  the strongest defender (or defenders, on a tie) is rewarded
+    Defended(Vec<String>),
+    /// the barbarians won.  the weakest defenders that have a city each lose one
+    Pillaged(Vec<String>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct BarbarianAttack {
+    pub barbarian_strength: u32,
+    pub defense: u32,
+    pub outcome: BarbarianAttackOutcome,
+}
+
+///
+/// where the barbarian ship is, and how many times it has landed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct BarbarianTrack {
+    pub position: u32, // 0 is the start; the barbarians land when it reaches BARBARIAN_TRACK_LENGTH
+    pub attacks: u32,
+}
+
+impl BarbarianTrack {
+    /// Moves the ship one step when the event die shows the ship.  Returns true if the barbarians land -- the caller
+    /// resolves the attack with `attack`, and the ship goes back to the start.
+    pub fn advance(&mut self) -> bool {
+        self.position += 1;
+        if self.position < BARBARIAN_TRACK_LENGTH {
+            return false;
+        }
+        self.position = 0;
+        self.attacks += 1;
+        true
+    }
+
+    /// Resolves an attack.
+    ///
+    /// # Parameters
+    ///
+    /// * cities: user_id -> the number of cities the player has on the board
+    /// * knights: user_id -> the total strength of the player's active knights
+    pub fn attack(cities: &HashMap<String, u32>, knights: &HashMap<String, u32>) -> BarbarianAttack {
+        let barbarian_strength: u32 = cities.values().sum();
+        let defense: u32 = knights.values().sum();
+        let strength = |id: &String| knights.get(id).cloned().unwrap_or(0);
+
+        let outcome = if defense >= barbarian_strength {
+            let best = knights.values().max().cloned().unwrap_or(0);
+            let mut defenders: Vec<String> = knights
+                .keys()
+                .filter(|id| best > 0 && strength(id) == best)
+                .cloned()
+                .collect();
+            defenders.sort();
+            BarbarianAttackOutcome::Defended(defenders)
+        } else {
+            //  players without a city have nothing to lose, so they don't count as the weakest
+            let with_cities: Vec<&String> = cities
+                .iter()
+                .filter(|(_, count)| **count > 0)
+                .map(|(id, _)| id)
+                .collect();
+            let weakest = with_cities.iter().map(|id| strength(id)).min().unwrap_or(0);
+            let mut losers: Vec<String> = with_cities
+                .into_iter()
+                .filter(|id| strength(id) == weakest)
+                .cloned()
+                .collect();
+            losers.sort();
+            BarbarianAttackOutcome::Pillaged(losers)
+        };
+        BarbarianAttack {
+            barbarian_strength,
+            defense,
+            outcome,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(pairs: &[(&str, u32)]) -> HashMap<String, u32> {
+        pairs.iter().map(|(id, count)| (id.to_string(), *count)).collect()
+    }
+
+    #[test]
+    fn test_barbarians() {
+        let mut track = BarbarianTrack::default();
+        for _ in 1..BARBARIAN_TRACK_LENGTH {
+            assert!(!track.advance());
+        }
+        assert!(track.advance());
+        assert_eq!(track.position, 0);
+        assert_eq!(track.attacks, 1);
+
+        let cities = counts(&[("1", 2), ("2", 1), ("3", 0)]);
+        let attack = BarbarianTrack::attack(&cities, &counts(&[("1", 1), ("2", 2)]));
+        assert_eq!(attack.barbarian_strength, 3);
+        assert_eq!(attack.defense, 3);
+        assert_eq!(
+            attack.outcome,
+            BarbarianAttackOutcome::Defended(vec!["2".to_string()])
+        );
+
+        // "3" has no knights, but has no city to lose either
+        let attack = BarbarianTrack::attack(&cities, &counts(&[("1", 1)]));
+        assert_eq!(
+            attack.outcome,
+            BarbarianAttackOutcome::Pillaged(vec!["2".to_string()])
+        );
+    }
+}
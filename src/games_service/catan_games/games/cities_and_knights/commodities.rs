@@ -0,0 +1,81 @@
+#![allow(dead_code)]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::tiles::tile_enums::TileResource, shared::shared_models::GameError,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
+pub enum CommodityType {
+    Cloth,
+    Coin,
+    Paper,
+}
+
+impl CommodityType {
+    pub const ALL: [CommodityType; 3] = [CommodityType::Cloth, CommodityType::Coin, CommodityType::Paper];
+
+    /// the commodity a city on the tile gets instead of its second resource card.  hills and fields don't have one --
+    /// a city there gets two resource cards, as it does in the regular game
+    pub fn produced_by(resource: TileResource) -> Option<Self> {
+        match resource {
+            TileResource::Sheep => Some(CommodityType::Cloth),
+            TileResource::Ore => Some(CommodityType::Coin),
+            TileResource::Wood => Some(CommodityType::Paper),
+            _ => None,
+        }
+    }
+}
+
+///
+/// the commodity cards in a hand.  like ResourceCards, but for cloth, coin and paper
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct Commodities {
+    pub cloth: u32,
+    pub coin: u32,
+    pub paper: u32,
+}
+
+impl Commodities {
+    pub fn new(cloth: u32, coin: u32, paper: u32) -> Self {
+        Self { cloth, coin, paper }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.cloth + self.coin + self.paper
+    }
+
+    pub fn count(&self, commodity: CommodityType) -> u32 {
+        match commodity {
+            CommodityType::Cloth => self.cloth,
+            CommodityType::Coin => self.coin,
+            CommodityType::Paper => self.paper,
+        }
+    }
+
+    fn count_mut(&mut self, commodity: CommodityType) -> &mut u32 {
+        match commodity {
+            CommodityType::Cloth => &mut self.cloth,
+            CommodityType::Coin => &mut self.coin,
+            CommodityType::Paper => &mut self.paper,
+        }
+    }
+
+    pub fn add(&mut self, commodity: CommodityType, count: u32) {
+        *self.count_mut(commodity) += count;
+    }
+
+    /// removes count cards of the commodity.  nothing is removed if the hand doesn't have that many
+    pub fn remove(&mut self, commodity: CommodityType, count: u32) -> Result<(), GameError> {
+        let current = self.count_mut(commodity);
+        if *current < count {
+            return Err(GameError::BadActionData(format!(
+                "can't remove {} {:?} from {}",
+                count, commodity, current
+            )));
+        }
+        *current -= count;
+        Ok(())
+    }
+}
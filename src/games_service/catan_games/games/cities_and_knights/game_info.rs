@@ -0,0 +1,68 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::games_service::{
+    catan_games::{
+        games::regular::game_info::{RegularGameInfo, REGULAR_GAME_INFO},
+        traits::game_info_trait::GameInfoTrait,
+    },
+    harbors::harbor::Harbor,
+    tiles::tile_enums::TileResource,
+};
+
+use super::{barbarians::BARBARIAN_TRACK_LENGTH, improvements::MAX_IMPROVEMENT_LEVEL};
+
+///
+/// Cities & Knights is played on the regular board, with its own numbers on top
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct CitiesAndKnightsGameInfo {
+    pub name: String,
+    pub board: RegularGameInfo,
+    pub victory_points_to_win: u32,
+    pub barbarian_track_length: u32,
+    pub max_improvement_level: u32,
+}
+
+impl GameInfoTrait for CitiesAndKnightsGameInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tile_resources(&self) -> &[TileResource] {
+        &self.board.tile_resources
+    }
+
+    fn rolls(&self) -> &[u32] {
+        &self.board.rolls
+    }
+
+    fn rows_per_column(&self) -> &[u32] {
+        &self.board.rows_per_column
+    }
+
+    fn harbor_data(&self) -> &[Harbor] {
+        &self.board.harbor_data
+    }
+
+    fn min_players(&self) -> usize {
+        self.board.min_players
+    }
+
+    fn max_players(&self) -> usize {
+        self.board.max_players
+    }
+}
+
+fn create_cities_and_knights_game_info() -> CitiesAndKnightsGameInfo {
+    CitiesAndKnightsGameInfo {
+        name: "Cities & Knights".to_owned(),
+        board: REGULAR_GAME_INFO.clone(),
+        victory_points_to_win: 13,
+        barbarian_track_length: BARBARIAN_TRACK_LENGTH,
+        max_improvement_level: MAX_IMPROVEMENT_LEVEL,
+    }
+}
+
+pub static CITIES_AND_KNIGHTS_GAME_INFO: Lazy<CitiesAndKnightsGameInfo> =
+    Lazy::new(|| create_cities_and_knights_game_info());
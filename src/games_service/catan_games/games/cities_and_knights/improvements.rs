@@ -0,0 +1,126 @@
+#![allow(dead_code)]
+use serde::{Deserialize, Serialize};
+
+use crate::shared::shared_models::GameError;
+
+use super::commodities::{Commodities, CommodityType};
+
+/// the highest level on an improvement track
+pub const MAX_IMPROVEMENT_LEVEL: u32 = 5;
+/// the level a player needs on a track before they can hold its metropolis
+pub const METROPOLIS_LEVEL: u32 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
+pub enum ImprovementTrack {
+    Trade,    // paid for with cloth
+    Politics, // paid for with coin
+    Science,  // paid for with paper
+}
+
+impl ImprovementTrack {
+    pub fn commodity(&self) -> CommodityType {
+        match self {
+            ImprovementTrack::Trade => CommodityType::Cloth,
+            ImprovementTrack::Politics => CommodityType::Coin,
+            ImprovementTrack::Science => CommodityType::Paper,
+        }
+    }
+}
+
+///
+/// how far a player has improved their cities on each track
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct CityImprovements {
+    pub trade: u32,
+    pub politics: u32,
+    pub science: u32,
+}
+
+impl CityImprovements {
+    pub fn level(&self, track: ImprovementTrack) -> u32 {
+        match track {
+            ImprovementTrack::Trade => self.trade,
+            ImprovementTrack::Politics => self.politics,
+            ImprovementTrack::Science => self.science,
+        }
+    }
+
+    fn level_mut(&mut self, track: ImprovementTrack) -> &mut u32 {
+        match track {
+            ImprovementTrack::Trade => &mut self.trade,
+            ImprovementTrack::Politics => &mut self.politics,
+            ImprovementTrack::Science => &mut self.science,
+        }
+    }
+
+    /// the next level costs as many of the track's commodity as the level's number -- 1 for the first, 5 for the last
+    pub fn cost_of_next(&self, track: ImprovementTrack) -> Option<u32> {
+        let next = self.level(track) + 1;
+        if next > MAX_IMPROVEMENT_LEVEL {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    /// Moves up one level on the track, paying with the player's commodities.
+    ///
+    /// The player needs at least one city to improve, and nothing changes if they can't pay or are already at the
+    /// top of the track.
+    pub fn improve(
+        &mut self,
+        track: ImprovementTrack,
+        city_count: u32,
+        commodities: &mut Commodities,
+    ) -> Result<(), GameError> {
+        if city_count == 0 {
+            return Err(GameError::ActionError(
+                "a city is needed before cities can be improved".to_owned(),
+            ));
+        }
+        let cost = self.cost_of_next(track).ok_or_else(|| {
+            GameError::ActionError(format!("{:?} is already at level {}", track, MAX_IMPROVEMENT_LEVEL))
+        })?;
+        commodities.remove(track.commodity(), cost)?;
+        *self.level_mut(track) += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_improve() {
+        let mut improvements = CityImprovements::default();
+        let mut commodities = Commodities::new(20, 1, 0);
+
+        assert!(improvements
+            .improve(ImprovementTrack::Trade, 0, &mut commodities)
+            .is_err());
+        assert!(improvements
+            .improve(ImprovementTrack::Science, 1, &mut commodities)
+            .is_err());
+
+        for level in 1..=MAX_IMPROVEMENT_LEVEL {
+            improvements
+                .improve(ImprovementTrack::Trade, 1, &mut commodities)
+                .expect("there is enough cloth");
+            assert_eq!(improvements.level(ImprovementTrack::Trade), level);
+        }
+        // 1 + 2 + 3 + 4 + 5 cloth
+        assert_eq!(commodities.cloth, 5);
+        assert_eq!(improvements.cost_of_next(ImprovementTrack::Trade), None);
+        assert!(improvements
+            .improve(ImprovementTrack::Trade, 1, &mut commodities)
+            .is_err());
+        assert_eq!(commodities.cloth, 5);
+
+        improvements
+            .improve(ImprovementTrack::Politics, 1, &mut commodities)
+            .expect("one coin pays for the first level");
+        assert_eq!(commodities, Commodities::new(5, 0, 0));
+    }
+}
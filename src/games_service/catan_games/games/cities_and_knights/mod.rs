@@ -0,0 +1,36 @@
+/**
+ *  the pieces of Cities & Knights that the regular game doesn't have.  this is scaffolding: the types and rules are
+ *  here and tested, but the variant can't be created yet (it isn't in game::SUPPORTED_GAMES) until the actions that
+ *  use them are wired into the game.
+ *
+ *  1. commodities.rs: cloth, coin and paper, which cities on pasture, mountains and forest produce
+ *  2. improvements.rs: the three city improvement tracks, paid for with commodities
+ *  3. barbarians.rs: the barbarian ship, and the attack when it lands
+ *  4. game_info.rs: the board (the regular layout) and the Cities & Knights numbers, with their own GameInfoTrait
+ *
+ *  CitiesAndKnightsState is everything a game needs on top of RegularGame.
+ */
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use self::{
+    barbarians::BarbarianTrack, commodities::Commodities, improvements::CityImprovements,
+};
+
+pub mod barbarians;
+pub mod commodities;
+pub mod game_info;
+pub mod improvements;
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct CitiesAndKnightsState {
+    #[serde_as(as = "Vec<(_, _)>")]
+    pub commodities: HashMap<String, Commodities>, // user_id -> the commodity cards in their hand
+    #[serde_as(as = "Vec<(_, _)>")]
+    pub improvements: HashMap<String, CityImprovements>, // user_id -> how far they are on each track
+    pub barbarians: BarbarianTrack,
+}
@@ -1 +1,3 @@
+pub mod cities_and_knights;
 pub mod regular;
+pub mod seafarers;
@@ -0,0 +1,140 @@
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use crate::{
+    games_service::shared::game_models::{CardHolder, LedgerEntry, LedgerReason, ResourceCards},
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+/// the number of cards of each resource in the game.  every card is either in the bank or in a player's hand
+pub const RESOURCE_CARDS_PER_TYPE: u32 = 19;
+
+impl RegularGame {
+    /// the bank at the start of the game: every card
+    pub fn full_bank() -> ResourceCards {
+        ResourceCards::new(
+            RESOURCE_CARDS_PER_TYPE,
+            RESOURCE_CARDS_PER_TYPE,
+            RESOURCE_CARDS_PER_TYPE,
+            RESOURCE_CARDS_PER_TYPE,
+            RESOURCE_CARDS_PER_TYPE,
+        )
+    }
+
+    /// Moves resource cards from one holder to another and records the move in the ledger.
+    ///
+    /// Every card that changes hands goes through here, so the bank and the hands always add up to the whole supply.
+    /// Nothing moves (and nothing is recorded) if the holder doesn't have all of the cards.
+    pub fn transfer(
+        &mut self,
+        from: &CardHolder,
+        to: &CardHolder,
+        cards: &ResourceCards,
+        reason: LedgerReason,
+    ) -> Result<(), GameError> {
+        if let CardHolder::Player(to_id) = to {
+            if !self.players.contains_key(to_id) {
+                return Err(GameError::BadId(to_id.to_owned()));
+            }
+        }
+        match from {
+            CardHolder::Bank => {
+                if !self.bank.contains(cards) {
+                    return Err(GameError::ActionError(format!(
+                        "the bank can't pay {:?}, it only has {:?}",
+                        cards, self.bank
+                    )));
+                }
+                self.bank.subtract(cards)?;
+            }
+            CardHolder::Player(from_id) => self
+                .players
+                .get_mut(from_id)
+                .ok_or_else(|| GameError::BadId(from_id.to_owned()))?
+                .resources
+                .subtract(cards)?,
+        }
+        match to {
+            CardHolder::Bank => self.bank.add_cards(cards),
+            CardHolder::Player(to_id) => {
+                if let Some(player) = self.players.get_mut(to_id) {
+                    player.resources.add_cards(cards);
+                    //  cards the player collected, rather than traded for
+                    if matches!(
+                        reason,
+                        LedgerReason::StartingResources
+                            | LedgerReason::Roll
+                            | LedgerReason::Monopoly
+                            | LedgerReason::YearOfPlenty
+                    ) {
+                        player.resources_collected.add_cards(cards);
+                    }
+                }
+            }
+        }
+        self.ledger.push(LedgerEntry {
+            from: from.clone(),
+            to: to.clone(),
+            cards: cards.clone(),
+            reason,
+        });
+        Ok(())
+    }
+
+    /// moves cards from the bank to the player.  nothing moves if the bank doesn't have all of them
+    pub fn take_from_bank(
+        &mut self,
+        user_id: &str,
+        cards: &ResourceCards,
+        reason: LedgerReason,
+    ) -> Result<(), GameError> {
+        self.transfer(&CardHolder::Bank, &CardHolder::Player(user_id.to_owned()), cards, reason)
+    }
+
+    /// moves cards from the player back to the bank.  nothing moves if the player doesn't have all of them
+    pub fn return_to_bank(
+        &mut self,
+        user_id: &str,
+        cards: &ResourceCards,
+        reason: LedgerReason,
+    ) -> Result<(), GameError> {
+        self.transfer(&CardHolder::Player(user_id.to_owned()), &CardHolder::Bank, cards, reason)
+    }
+
+    /// Cuts what a roll pays out down to what the bank can cover.
+    ///
+    /// This is the official rule: if the bank doesn't have enough of a resource to pay everybody who is owed it, and
+    /// more than one player is owed it, nobody gets any.  If only one player is owed it, they get whatever is left.
+    /// Other resources are paid as normal.
+    pub fn limit_payouts_to_bank(
+        &self,
+        payouts: &HashMap<String, ResourceCards>,
+    ) -> HashMap<String, ResourceCards> {
+        let mut limited: HashMap<String, ResourceCards> = payouts
+            .keys()
+            .map(|id| (id.clone(), ResourceCards::default()))
+            .collect();
+        for resource in ResourceCards::RESOURCES {
+            let owed: Vec<(&String, u32)> = payouts
+                .iter()
+                .map(|(id, cards)| (id, cards.count(resource)))
+                .filter(|(_, count)| *count > 0)
+                .collect();
+            let total: u32 = owed.iter().map(|(_, count)| count).sum();
+            let in_bank = self.bank.count(resource);
+            match owed.as_slice() {
+                _ if total <= in_bank => {
+                    for (id, count) in owed.iter() {
+                        limited.entry((*id).clone()).or_default().add(resource, *count);
+                    }
+                }
+                [(id, _)] => limited.entry((*id).clone()).or_default().add(resource, in_bank),
+                _ => {}
+            }
+        }
+        limited.retain(|_, cards| cards.total() > 0);
+        limited
+    }
+}
@@ -0,0 +1,316 @@
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use crate::{
+    games_service::{
+        buildings::building_enums::BuildingState,
+        player::player_enums::{Target, Weapon},
+        shared::{
+            game_enums::{GameState, ResourceType},
+            game_models::{CardHolder, LedgerReason, MoveBaronData, ResourceCards},
+        },
+        tiles::tile_key::TileKey,
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+/// a player holding more than this many cards when a 7 is rolled has to discard half of them
+pub const MAX_SAFE_HAND_SIZE: u32 = 7;
+/// how long the players have to discard after a 7 before the service discards for them
+pub const DISCARD_TIMEOUT_SECONDS: u64 = 90;
+
+impl RegularGame {
+    /// Applies a dice roll for the current player.
+    ///
+    /// On anything but a 7, every tile with the rolled number (other than the one the baron is sitting on) pays out
+    /// to the buildings on its corners and the game moves on to BuyingAndTrading.  On a 7 nobody gets paid: players
+    /// holding more than 7 cards owe half their hand (rounded down) and the game moves to MustDiscard, or straight to
+    /// MustMoveBaron if nobody is over the limit.
+    ///
+    /// # Returns
+    ///
+    /// A clone of the game with the roll applied, or `GameError::BadActionData` if the game isn't waiting for a roll
+    /// or the roll isn't something two dice can produce.
+    pub fn roll(&self, roll: u32) -> Result<Self, GameError> {
+        self.verify_no_pending_dev_card()?;
+        if self.game_state != GameState::WaitingForRoll {
+            return Err(GameError::BadActionData(format!(
+                "can't roll in the {:?} state",
+                self.game_state
+            )));
+        }
+        if !(2..=12).contains(&roll) {
+            return Err(GameError::BadActionData(format!(
+                "{} is not a valid roll",
+                roll
+            )));
+        }
+
+        let mut clone = self.clone();
+        clone.last_roll = Some(roll);
+        if roll == 7 {
+            if let Some(player) = clone.players.get_mut(&self.current_player_id) {
+                player.sevens_rolled += 1;
+            }
+            clone.pending_discards = clone
+                .players
+                .iter()
+                .filter(|(_, player)| player.resources.total() > MAX_SAFE_HAND_SIZE)
+                .map(|(id, player)| (id.clone(), player.resources.total() / 2))
+                .collect();
+
+            clone.game_state = if clone.pending_discards.is_empty() {
+                GameState::MustMoveBaron
+            } else {
+                GameState::MustDiscard
+            };
+        } else {
+            clone.distribute_resources(roll);
+            clone.game_state = GameState::BuyingAndTrading;
+        }
+        Ok(clone)
+    }
+
+    /// pay every settlement (1) and city (2) on a corner of a tile with the rolled number, skipping the baron's tile.
+    /// the cards come out of the bank, so a resource the bank runs short of may not be paid (see limit_payouts_to_bank).
+    /// the pieces of players who have forfeited don't produce
+    fn distribute_resources(&mut self, roll: u32) {
+        let producing_tiles: HashMap<TileKey, ResourceType> = self
+            .tiles
+            .values()
+            .filter(|tile| tile.roll == roll && tile.tile_key != self.baron_tile)
+            .filter_map(|tile| {
+                tile.current_resource
+                    .produces()
+                    .map(|resource| (tile.tile_key, resource))
+            })
+            .collect();
+
+        let mut payouts: HashMap<String, ResourceCards> = HashMap::new();
+        for building in self.buildings.values() {
+            let count = match building.state {
+                BuildingState::Settlement => 1,
+                BuildingState::City => 2,
+                _ => continue,
+            };
+            let owner_id = match &building.owner_id {
+                Some(id) if !self.has_forfeited(id) => id,
+                _ => continue,
+            };
+            // every corner is in the map once per tile that touches it, so only count the entry keyed on the tile
+            for (tile_key, resource) in producing_tiles.iter() {
+                if building.building_key.tile_key == *tile_key {
+                    payouts
+                        .entry(owner_id.clone())
+                        .or_default()
+                        .add(*resource, count);
+                }
+            }
+        }
+
+        let payouts = self.limit_payouts_to_bank(&payouts);
+        for (id, cards) in payouts.iter() {
+            self.take_from_bank(id, cards, LedgerReason::Roll)
+                .expect("payouts are limited to what the bank holds");
+        }
+        for (id, player) in self.players.iter_mut() {
+            if payouts.contains_key(id) {
+                player.good_rolls += 1;
+            } else {
+                player.bad_rolls += 1;
+            }
+        }
+    }
+
+    /// Returns the cards a player chose to give up after a 7 to the bank.
+    ///
+    /// The player has to be one of the players that owes cards.  They can give up what they owe a few cards at a time
+    /// -- pending_discards holds what each player still owes -- but not more than they owe.  Once every player has
+    /// discarded, the game moves on to MustMoveBaron.
+    pub fn discard(&self, user_id: &str, cards: &ResourceCards) -> Result<Self, GameError> {
+        if self.game_state != GameState::MustDiscard {
+            return Err(GameError::BadActionData(format!(
+                "can't discard in the {:?} state",
+                self.game_state
+            )));
+        }
+        let owed = match self.pending_discards.get(user_id) {
+            Some(owed) => *owed,
+            None => {
+                return Err(GameError::BadActionData(format!(
+                    "{} does not need to discard",
+                    user_id
+                )))
+            }
+        };
+        // the cards come from the client, so their total can overflow
+        let discarded = match cards.checked_total() {
+            Some(total) if total > 0 && total <= owed => total,
+            _ => {
+                return Err(GameError::BadActionData(format!(
+                    "{} has {} cards left to discard, not {:?}",
+                    user_id, owed, cards
+                )))
+            }
+        };
+
+        let mut clone = self.clone();
+        clone.return_to_bank(user_id, cards, LedgerReason::Discard)?;
+
+        if owed == discarded {
+            clone.pending_discards.remove(user_id);
+        } else {
+            clone
+                .pending_discards
+                .insert(user_id.to_owned(), owed - discarded);
+        }
+        if clone.pending_discards.is_empty() {
+            clone.game_state = GameState::MustMoveBaron;
+            clone.discard_deadline = None;
+        }
+        Ok(clone)
+    }
+
+    /// Discards, at random, whatever the players still owe once the discard deadline has passed, so that one player
+    /// who has walked away can't hold up the game.  The game moves on to MustMoveBaron.
+    pub fn force_discards(&self, now: u64) -> Result<Self, GameError> {
+        if self.game_state != GameState::MustDiscard {
+            return Err(GameError::BadActionData(format!(
+                "can't discard in the {:?} state",
+                self.game_state
+            )));
+        }
+        match self.discard_deadline {
+            Some(deadline) if now >= deadline => {}
+            _ => {
+                return Err(GameError::ActionError(
+                    "the players still have time to discard".to_owned(),
+                ))
+            }
+        }
+
+        let mut clone = self.clone();
+        let mut late: Vec<(String, u32)> = self
+            .pending_discards
+            .iter()
+            .map(|(id, owed)| (id.clone(), *owed))
+            .collect();
+        late.sort();
+        for (user_id, owed) in late {
+            let mut hand = clone
+                .players
+                .get(&user_id)
+                .ok_or_else(|| GameError::BadId(user_id.clone()))?
+                .resources
+                .clone();
+            let mut cards = ResourceCards::default();
+            for _ in 0..owed {
+                if let Some(resource) = hand.take_random() {
+                    cards.add(resource, 1);
+                }
+            }
+            tracing::info!("{} didn't discard in time. discarding {:?}", user_id, cards);
+            clone.return_to_bank(&user_id, &cards, LedgerReason::Discard)?;
+        }
+        clone.pending_discards.clear();
+        clone.discard_deadline = None;
+        clone.game_state = GameState::MustMoveBaron;
+        Ok(clone)
+    }
+
+    /// the ids of the players, other than exclude_id, that have a building on a corner of the tile and at least one
+    /// card to steal.  a player who has forfeited can't be stolen from -- a hand they kept is out of play
+    pub fn baron_victims(&self, tile_key: &TileKey, exclude_id: &str) -> Vec<String> {
+        let mut victims: Vec<String> = self
+            .buildings
+            .values()
+            .filter(|building| building.touches_tile(tile_key))
+            .filter_map(|building| building.owner_id.clone())
+            .filter(|id| id != exclude_id && !self.has_forfeited(id))
+            .filter(|id| {
+                self.players
+                    .get(id)
+                    .map_or(false, |player| player.resources.total() > 0)
+            })
+            .collect();
+        victims.sort();
+        victims.dedup();
+        victims
+    }
+
+    /// Moves the baron and lets the current player steal a random card from a player on the new tile.
+    ///
+    /// The baron has to move to a different tile on the board.  If anybody other than the current player can be
+    /// stolen from, the request has to name one of them; if nobody can be, victim_id has to be None.
+    pub fn move_baron(&self, user_id: &str, data: &MoveBaronData) -> Result<Self, GameError> {
+        if self.game_state != GameState::MustMoveBaron {
+            return Err(GameError::BadActionData(format!(
+                "can't move the baron in the {:?} state",
+                self.game_state
+            )));
+        }
+        if !self.tiles.contains_key(&data.tile_key) {
+            return Err(GameError::BadActionData(format!(
+                "{:?} is not on the board",
+                data.tile_key
+            )));
+        }
+        if data.tile_key == self.baron_tile {
+            return Err(GameError::BadActionData(
+                "the baron has to move to a different tile".to_owned(),
+            ));
+        }
+        if self.tiles[&data.tile_key].current_resource.is_sea() {
+            return Err(GameError::BadActionData(format!(
+                "{:?} is sea -- the baron has to stay on land",
+                data.tile_key
+            )));
+        }
+
+        let victims = self.baron_victims(&data.tile_key, user_id);
+        let mut clone = self.clone();
+        clone.baron_tile = data.tile_key;
+
+        match &data.victim_id {
+            Some(victim_id) => {
+                if !victims.contains(victim_id) {
+                    return Err(GameError::BadActionData(format!(
+                        "{} can't be stolen from on {:?}. choose from {:?}",
+                        victim_id, data.tile_key, victims
+                    )));
+                }
+                let stolen = clone.players[victim_id]
+                    .resources
+                    .pick_random()
+                    .expect("victims always have at least one card");
+                let mut cards = ResourceCards::default();
+                cards.add(stolen, 1);
+                clone.transfer(
+                    &CardHolder::Player(victim_id.to_owned()),
+                    &CardHolder::Player(user_id.to_owned()),
+                    &cards,
+                    LedgerReason::Steal,
+                )?;
+                clone
+                    .players
+                    .get_mut(user_id)
+                    .ok_or_else(|| GameError::BadId(user_id.to_owned()))?
+                    .targets
+                    .push(Target::new(Weapon::RolledSeven, victim_id));
+            }
+            None => {
+                if !victims.is_empty() {
+                    return Err(GameError::BadActionData(format!(
+                        "a victim must be chosen from {:?}",
+                        victims
+                    )));
+                }
+            }
+        }
+
+        clone.game_state = GameState::BuyingAndTrading;
+        Ok(clone)
+    }
+}
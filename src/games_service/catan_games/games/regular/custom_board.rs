@@ -0,0 +1,151 @@
+#![allow(dead_code)]
+use std::collections::HashSet;
+
+use crate::{
+    games_service::{
+        shared::{game_enums::CatanGames, game_models::CustomBoardData},
+        tiles::tile_enums::TileResource,
+    },
+    shared::shared_models::{GameError, UserProfile},
+};
+
+use super::{game_info::RegularGameInfo, regular_game::RegularGame};
+
+/// the smallest and largest hexagon a custom board can be, counted in rings around the center tile
+pub const MIN_CUSTOM_BOARD_RADIUS: usize = 2;
+pub const MAX_CUSTOM_BOARD_RADIUS: usize = 5;
+
+impl RegularGame {
+    /// Creates a game on a board the creator laid out themselves.
+    ///
+    /// The board is used as it was sent -- it isn't shuffled, although the creator can still ask for a new board
+    /// while the game is ChoosingBoard, which shuffles the tiles and harbors they sent.  The board has to be a
+    /// hexagon with one desert (on the 7), numbers from 2 to 12 on the land, and harbors on the coast.
+    ///
+    /// # Returns
+    ///
+    /// The new game, or BadActionData listing everything that is wrong with the board.
+    pub fn new_custom(creator: &UserProfile, data: &CustomBoardData) -> Result<Self, GameError> {
+        let mut problems = custom_board_problems(data);
+        if problems.is_empty() {
+            let info = RegularGameInfo {
+                name: "Custom".to_owned(),
+                tile_resources: data.tile_resources.clone(),
+                rolls: data.rolls.clone(),
+                rows_per_column: data.rows_per_column.clone(),
+                harbor_data: data.harbors.clone(),
+                min_players: 3,
+                max_players: 4,
+            };
+            let mut game = Self::new_with_info(creator, CatanGames::Regular, &info);
+            if let Some(desert) = game
+                .tiles
+                .values()
+                .find(|tile| tile.current_resource == TileResource::Desert)
+            {
+                game.baron_tile = desert.tile_key;
+            }
+            game.custom_board = Some(info);
+
+            problems.extend(game.custom_harbor_problems());
+            if data.no_adjacent_six_eight && !game.validate_no_adjacent_six_eight() {
+                problems.push("a 6 or 8 is next to another 6 or 8".to_owned());
+            }
+            if problems.is_empty() {
+                return Ok(game);
+            }
+        }
+        Err(GameError::BadActionData(problems.join("; ")))
+    }
+
+    /// every harbor has to be on a land tile, on an edge that faces the sea or the edge of the board
+    fn custom_harbor_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut seen = HashSet::new();
+        for harbor in self.game_info().harbor_data.iter() {
+            let key = harbor.harbor_key;
+            if !seen.insert(key) {
+                problems.push(format!("there is more than one harbor at {:?}", key));
+                continue;
+            }
+            let on_land = self
+                .tiles
+                .get(&key.tile_key())
+                .map_or(false, |tile| !tile.current_resource.is_sea());
+            let facing_water = self
+                .tiles
+                .get(&key.tile_key().get_neighbor_key(key.position()))
+                .map_or(true, |tile| tile.current_resource.is_sea());
+            if !on_land || !facing_water {
+                problems.push(format!("the harbor at {:?} isn't on the coast", key));
+            }
+        }
+        problems
+    }
+}
+
+/// everything wrong with the board that can be found without building it
+fn custom_board_problems(data: &CustomBoardData) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    //  setup_tiles lays the columns out as a hexagon: [n+1, n+2, ... 2n+1 ... n+2, n+1]
+    let columns = data.rows_per_column.len();
+    let radius = columns / 2;
+    let hexagon: Vec<u32> = (0..columns)
+        .map(|column| (radius + 1 + column.min(columns - 1 - column)) as u32)
+        .collect();
+    if columns % 2 == 0
+        || radius < MIN_CUSTOM_BOARD_RADIUS
+        || radius > MAX_CUSTOM_BOARD_RADIUS
+        || data.rows_per_column != hexagon
+    {
+        problems.push(format!(
+            "rows_per_column {:?} isn't a hexagon with {} to {} rings, like [3, 4, 5, 4, 3]",
+            data.rows_per_column, MIN_CUSTOM_BOARD_RADIUS, MAX_CUSTOM_BOARD_RADIUS
+        ));
+        return problems;
+    }
+
+    let tile_count: u32 = data.rows_per_column.iter().sum();
+    if data.tile_resources.len() != tile_count as usize || data.rolls.len() != tile_count as usize {
+        problems.push(format!(
+            "the board has {} tiles, but there are {} resources and {} rolls",
+            tile_count,
+            data.tile_resources.len(),
+            data.rolls.len()
+        ));
+        return problems;
+    }
+
+    let deserts = data
+        .tile_resources
+        .iter()
+        .filter(|resource| **resource == TileResource::Desert)
+        .count();
+    if deserts != 1 {
+        problems.push(format!("there has to be one desert, not {}", deserts));
+    }
+    for (index, (resource, roll)) in data.tile_resources.iter().zip(data.rolls.iter()).enumerate() {
+        let problem = match resource {
+            TileResource::Back | TileResource::GoldMine => {
+                Some(format!("{:?} tiles can't be used on a custom board", resource))
+            }
+            TileResource::Desert if *roll != 7 => Some("the desert has to have the 7".to_owned()),
+            TileResource::Sea if *roll != 0 => Some("sea tiles can't have a number".to_owned()),
+            TileResource::Brick
+            | TileResource::Ore
+            | TileResource::Sheep
+            | TileResource::Wheat
+            | TileResource::Wood
+                if !(2..=12).contains(roll) || *roll == 7 =>
+            {
+                Some(format!("{} isn't a number a resource tile can have", roll))
+            }
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            problems.push(format!("tile {}: {}", index, problem));
+        }
+    }
+    problems
+}
@@ -0,0 +1,72 @@
+#![allow(dead_code)]
+use crate::{
+    games_service::{
+        bots::bots::is_bot,
+        shared::{game_enums::GameState, game_models::Delegate},
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// who plays the player's seat while they are away, if anybody
+    pub fn delegate_of(&self, user_id: &str) -> Option<&Delegate> {
+        self.delegations.get(user_id)
+    }
+
+    /// true if the player is playing the seat for the other player
+    pub fn is_delegate_for(&self, user_id: &str, delegate_id: &str) -> bool {
+        self.delegate_of(user_id) == Some(&Delegate::Player(delegate_id.to_owned()))
+    }
+
+    /// Hands the player's turns to another player in the game, or to a bot, until they take them back (see
+    /// revoke_delegation).  The game has to be under way and the player still in it; a player who is another's
+    /// delegate can't hand the seat on, and can't be given one if they have handed their own away.
+    pub fn delegate(&self, user_id: &str, delegate: &Delegate) -> Result<Self, GameError> {
+        if matches!(
+            self.game_state,
+            GameState::AddingPlayers
+                | GameState::ChoosingBoard
+                | GameState::SettingPlayerOrder
+                | GameState::GameOver
+        ) {
+            return Err(GameError::ActionError(
+                "turns can only be handed over while the game is under way".to_owned(),
+            ));
+        }
+        if !self.player_order.iter().any(|id| id == user_id) {
+            return Err(GameError::BadId(format!("{} isn't playing in this game", user_id)));
+        }
+        if let Delegate::Player(delegate_id) = delegate {
+            if delegate_id == user_id || is_bot(delegate_id) {
+                return Err(GameError::BadActionData(format!("{} can't play for {}", delegate_id, user_id)));
+            }
+            if !self.player_order.iter().any(|id| id == delegate_id) {
+                return Err(GameError::BadId(format!("{} isn't playing in this game", delegate_id)));
+            }
+            if self.delegations.contains_key(delegate_id) {
+                return Err(GameError::ActionError(format!(
+                    "{} has handed over their own turns",
+                    delegate_id
+                )));
+            }
+        }
+        if self.delegations.values().any(|d| *d == Delegate::Player(user_id.to_owned())) {
+            return Err(GameError::ActionError(format!("{} is playing for somebody else", user_id)));
+        }
+        let mut clone = self.clone();
+        clone.delegations.insert(user_id.to_owned(), delegate.clone());
+        Ok(clone)
+    }
+
+    /// The player plays their own turns again.
+    pub fn revoke_delegation(&self, user_id: &str) -> Result<Self, GameError> {
+        if !self.delegations.contains_key(user_id) {
+            return Err(GameError::ActionError(format!("{} hasn't handed over their turns", user_id)));
+        }
+        let mut clone = self.clone();
+        clone.delegations.remove(user_id);
+        Ok(clone)
+    }
+}
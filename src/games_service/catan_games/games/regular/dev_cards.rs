@@ -0,0 +1,200 @@
+#![allow(dead_code)]
+use crate::{
+    games_service::shared::{
+        game_enums::{DevCardType, GameState, ResourceType},
+        game_models::{
+            CardHolder, DevCardResolutionData, LedgerReason, PendingDevCard, ResourceCards,
+        },
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// true if the current player can play one of their development cards right now
+    pub fn can_play_dev_card(&self) -> bool {
+        self.pending_dev_card.is_none()
+            && self.dev_card_played.is_none()
+            && self.players.get(&self.current_player_id).map_or(false, |player| {
+                player
+                    .dev_cards
+                    .iter()
+                    .any(|card| matches!(card, DevCardType::Monopoly | DevCardType::YearOfPlenty))
+            })
+    }
+
+    /// Plays a Monopoly or Year of Plenty card from the current player's hand.
+    ///
+    /// This is the first half of playing the card: the card leaves the player's hand and the game waits, in
+    /// pending_dev_card, for the player to choose the resource(s) with resolve_dev_card.  Nothing else can happen in
+    /// the game until they do.  A card can be played before or after rolling, but only one a turn.
+    pub fn play_dev_card(&self, user_id: &str, card: DevCardType) -> Result<Self, GameError> {
+        self.verify_no_pending_dev_card()?;
+        if self.current_player_id != user_id {
+            return Err(GameError::ActionError(format!(
+                "it is {}'s turn, not {}'s",
+                self.current_player_id, user_id
+            )));
+        }
+        if !matches!(
+            self.game_state,
+            GameState::WaitingForRoll | GameState::BuyingAndTrading
+        ) {
+            return Err(GameError::BadActionData(format!(
+                "can't play a development card in the {:?} state",
+                self.game_state
+            )));
+        }
+        if self.dev_card_played.is_some() {
+            return Err(GameError::ActionError(
+                "only one development card can be played a turn".to_owned(),
+            ));
+        }
+        if !matches!(card, DevCardType::Monopoly | DevCardType::YearOfPlenty) {
+            return Err(GameError::BadActionData(format!(
+                "{:?} can't be played this way",
+                card
+            )));
+        }
+        let index = self.players[user_id]
+            .dev_cards
+            .iter()
+            .position(|held| *held == card)
+            .ok_or_else(|| {
+                GameError::BadActionData(format!("{} doesn't have a {:?} card", user_id, card))
+            })?;
+
+        let mut clone = self.clone();
+        if let Some(player) = clone.players.get_mut(user_id) {
+            player.dev_cards.remove(index);
+            player.dev_cards_played += 1;
+        }
+        clone.dev_card_played = Some(card);
+        clone.pending_dev_card = Some(PendingDevCard {
+            user_id: user_id.to_owned(),
+            card,
+        });
+        Ok(clone)
+    }
+
+    /// Finishes playing the pending Monopoly or Year of Plenty card with the player's choice.
+    ///
+    /// Monopoly takes every card of one resource from every other player.  Year of Plenty takes two cards from the
+    /// bank, and fails (leaving the card pending so the player can choose again) if the bank doesn't have them.  All of
+    /// the cards move through the ledger.
+    pub fn resolve_dev_card(
+        &self,
+        user_id: &str,
+        data: &DevCardResolutionData,
+    ) -> Result<Self, GameError> {
+        let pending = self.pending_dev_card.as_ref().ok_or_else(|| {
+            GameError::ActionError("no development card is waiting to be resolved".to_owned())
+        })?;
+        if pending.user_id != user_id {
+            return Err(GameError::ActionError(format!(
+                "{} played the {:?} card, not {}",
+                pending.user_id, pending.card, user_id
+            )));
+        }
+        if let Some(resource) = data
+            .resources
+            .iter()
+            .find(|resource| !ResourceCards::RESOURCES.contains(resource))
+        {
+            return Err(GameError::BadActionData(format!(
+                "{:?} is not a resource card",
+                resource
+            )));
+        }
+
+        let mut clone = self.clone();
+        match (pending.card, data.resources.as_slice()) {
+            (DevCardType::Monopoly, [resource]) => clone.take_monopoly(user_id, *resource)?,
+            (DevCardType::YearOfPlenty, [first, second]) => {
+                let mut cards = ResourceCards::default();
+                cards.add(*first, 1);
+                cards.add(*second, 1);
+                clone.take_from_bank(user_id, &cards, LedgerReason::YearOfPlenty)?;
+                if let Some(player) = clone.players.get_mut(user_id) {
+                    player.resource_count.record(cards.total(), 0);
+                }
+            }
+            (card, resources) => {
+                return Err(GameError::BadActionData(format!(
+                    "{:?} can't be resolved with {:?}",
+                    card, resources
+                )))
+            }
+        }
+        clone.pending_dev_card = None;
+        Ok(clone)
+    }
+
+    /// plays a Monopoly card and takes every card of the resource from the other players, in one step
+    pub fn play_monopoly(&self, user_id: &str, resource: ResourceType) -> Result<Self, GameError> {
+        self.play_dev_card(user_id, DevCardType::Monopoly)?.resolve_dev_card(
+            user_id,
+            &DevCardResolutionData {
+                resources: vec![resource],
+            },
+        )
+    }
+
+    /// plays a Year of Plenty card and takes the two resources from the bank, in one step.  if the bank can't pay,
+    /// the card stays in the player's hand
+    pub fn play_year_of_plenty(
+        &self,
+        user_id: &str,
+        resources: [ResourceType; 2],
+    ) -> Result<Self, GameError> {
+        self.play_dev_card(user_id, DevCardType::YearOfPlenty)?.resolve_dev_card(
+            user_id,
+            &DevCardResolutionData {
+                resources: resources.to_vec(),
+            },
+        )
+    }
+
+    /// everything else waits while a played card is waiting for the player's choice
+    pub fn verify_no_pending_dev_card(&self) -> Result<(), GameError> {
+        match &self.pending_dev_card {
+            Some(pending) => Err(GameError::ActionError(format!(
+                "{} has to finish playing their {:?} card first",
+                pending.user_id, pending.card
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn take_monopoly(&mut self, user_id: &str, resource: ResourceType) -> Result<(), GameError> {
+        let mut victims: Vec<String> = self
+            .players
+            .keys()
+            .filter(|id| *id != user_id && !self.has_forfeited(id))
+            .cloned()
+            .collect();
+        victims.sort();
+        for victim_id in victims {
+            let count = self.players[&victim_id].resources.count(resource);
+            if count == 0 {
+                continue;
+            }
+            let mut cards = ResourceCards::default();
+            cards.add(resource, count);
+            self.transfer(
+                &CardHolder::Player(victim_id.clone()),
+                &CardHolder::Player(user_id.to_owned()),
+                &cards,
+                LedgerReason::Monopoly,
+            )?;
+            if let Some(victim) = self.players.get_mut(&victim_id) {
+                victim.resource_count.record(0, count);
+            }
+            if let Some(player) = self.players.get_mut(user_id) {
+                player.resource_count.record(count, 0);
+            }
+        }
+        Ok(())
+    }
+}
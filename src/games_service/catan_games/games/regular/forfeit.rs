@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+use crate::{
+    games_service::shared::{
+        game_enums::GameState,
+        game_models::{CardHolder, Delegate, LedgerReason, RemovalPolicy, ResourceCards},
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// true if the player has forfeited (or abandoned) the game
+    pub fn has_forfeited(&self, user_id: &str) -> bool {
+        self.forfeited.iter().any(|id| id == user_id)
+    }
+
+    /// Takes a player out of a game that has started, because they gave up or walked away.
+    ///
+    /// The player's pieces stay on the board, frozen: they still block roads and settlements and still score, but they
+    /// don't produce and the player can't win.  What happens to their resource cards is up to the game's
+    /// removal_policy: they go back to the bank, are dealt out to the players left, or stay in the player's hand out
+    /// of play.  Their development cards are discarded (unless their hand is frozen), Longest Road and Largest Army
+    /// go to whoever has earned them among the players left, their trades are closed and anything they owe after a 7
+    /// is forgotten.  They leave the player order, so the turn passes them by -- and if it was their turn, it passes
+    /// to the next player now.  When only one player is left, they win.
+    pub fn forfeit(&self, user_id: &str) -> Result<Self, GameError> {
+        if matches!(
+            self.game_state,
+            GameState::AddingPlayers | GameState::ChoosingBoard | GameState::SettingPlayerOrder
+        ) {
+            return Err(GameError::ActionError(
+                "the game hasn't started -- leave it instead".to_owned(),
+            ));
+        }
+        if self.game_state == GameState::GameOver {
+            return Err(GameError::ActionError("the game is over".to_owned()));
+        }
+        let seat = self
+            .player_order
+            .iter()
+            .position(|id| id == user_id)
+            .ok_or_else(|| GameError::BadId(format!("{} isn't playing in this game", user_id)))?;
+
+        let mut clone = self.clone();
+        clone
+            .open_trades
+            .retain(|_, offer| offer.from_id != user_id && offer.to_id.as_deref() != Some(user_id));
+        clone.pending_discards.remove(user_id);
+        clone
+            .delegations
+            .retain(|id, delegate| id != user_id && *delegate != Delegate::Player(user_id.to_owned()));
+        clone.forfeited.push(user_id.to_owned());
+        clone.player_order.remove(seat);
+
+        let hand = clone.players[user_id].resources.clone();
+        match self.options.removal_policy {
+            RemovalPolicy::Freeze => {}
+            RemovalPolicy::ReturnToBank => {
+                if hand.total() > 0 {
+                    clone.return_to_bank(user_id, &hand, LedgerReason::Forfeit)?;
+                }
+            }
+            RemovalPolicy::Redistribute => clone.deal_out(user_id, &hand, seat)?,
+        }
+        if self.options.removal_policy != RemovalPolicy::Freeze {
+            if let Some(player) = clone.players.get_mut(user_id) {
+                player.dev_cards.clear();
+            }
+        }
+        //  the awards are worked out again without the player who left
+        clone.update_longest_road();
+        clone.update_largest_army();
+
+        if clone.player_order.len() == 1 {
+            clone.winner_id = clone.player_order.first().cloned();
+            clone.game_state = GameState::GameOver;
+            return Ok(clone);
+        }
+
+        if self.current_player_id == user_id {
+            clone.pass_turn_from_seat(seat);
+        } else if clone.game_state == GameState::MustDiscard && clone.pending_discards.is_empty() {
+            clone.game_state = GameState::MustMoveBaron;
+            clone.discard_deadline = None;
+        }
+        Ok(clone)
+    }
+
+    /// Deals the cards of the player who left one at a time round the players left, starting with the player who was
+    /// after them (now in `seat`), in the order the resources are listed in.
+    fn deal_out(&mut self, user_id: &str, hand: &ResourceCards, seat: usize) -> Result<(), GameError> {
+        let count = self.player_order.len();
+        if count == 0 {
+            return Ok(());
+        }
+        let mut shares: Vec<ResourceCards> = vec![ResourceCards::default(); count];
+        let mut next = seat % count;
+        for resource in ResourceCards::RESOURCES {
+            for _ in 0..hand.count(resource) {
+                shares[next].add(resource, 1);
+                next = (next + 1) % count;
+            }
+        }
+        for (index, share) in shares.iter().enumerate() {
+            if share.total() > 0 {
+                let to_id = self.player_order[index].clone();
+                self.transfer(
+                    &CardHolder::Player(user_id.to_owned()),
+                    &CardHolder::Player(to_id),
+                    share,
+                    LedgerReason::Forfeit,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// the player in `seat` has just left the player order on their turn -- give the turn to whoever is next
+    fn pass_turn_from_seat(&mut self, seat: usize) {
+        let count = self.player_order.len();
+        self.setup_settlement = None;
+        match self.game_state {
+            //  setup is snake order.  going forward the next player is now in the seat; if the last player left, the
+            //  new last player starts the way back
+            GameState::AllocateResourceForward if seat < count => {
+                self.current_player_id = self.player_order[seat].clone();
+            }
+            GameState::AllocateResourceForward => {
+                self.game_state = GameState::AllocateResourceReverse;
+                self.current_player_id = self.player_order[count - 1].clone();
+            }
+            GameState::AllocateResourceReverse if seat > 0 => {
+                self.current_player_id = self.player_order[seat - 1].clone();
+            }
+            _ => {
+                //  the end of setup, or the end of a turn: the 7 (if there was one) is over and the next player rolls
+                if self.game_state != GameState::AllocateResourceReverse {
+                    self.open_trades.clear();
+                    self.ledger.clear();
+                    self.dev_card_played = None;
+                    self.pending_dev_card = None;
+                    self.pending_discards.clear();
+                    self.discard_deadline = None;
+                }
+                let next = seat % count;
+                if next == 0 && self.game_state != GameState::AllocateResourceReverse {
+                    self.rounds_played += 1;
+                }
+                self.current_player_id = self.player_order[next].clone();
+                self.game_state = GameState::WaitingForRoll;
+                self.turn_start_scores = self
+                    .players
+                    .keys()
+                    .map(|id| (id.clone(), self.known_score(id)))
+                    .collect();
+            }
+        }
+    }
+}
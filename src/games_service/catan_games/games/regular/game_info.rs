@@ -1,5 +1,6 @@
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
 use crate::{
     games_service::{
@@ -13,7 +14,7 @@ use crate::{
 
 use super::regular_game::RegularGame;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct RegularGameInfo {
     pub name: String,
@@ -132,30 +133,30 @@ pub static REGULAR_GAME_INFO: Lazy<RegularGameInfo> = Lazy::new(|| create_regula
 
 impl GameInfoTrait for RegularGame {
     fn name(&self) -> &str {
-        &REGULAR_GAME_INFO.name
+        &self.game_info().name
     }
 
     fn tile_resources(&self) -> &[TileResource] {
-        &REGULAR_GAME_INFO.tile_resources
+        &self.game_info().tile_resources
     }
 
     fn rolls(&self) -> &[u32] {
-        &REGULAR_GAME_INFO.rolls
+        &self.game_info().rolls
     }
 
     fn rows_per_column(&self) -> &[u32] {
-        &REGULAR_GAME_INFO.rows_per_column
+        &self.game_info().rows_per_column
     }
 
     fn harbor_data(&self) -> &[Harbor] {
-        &REGULAR_GAME_INFO.harbor_data
+        &self.game_info().harbor_data
     }
 
     fn min_players(&self) -> usize {
-        3
+        self.game_info().min_players
     }
 
     fn max_players(&self) -> usize {
-       4
+        self.game_info().max_players
     }
 }
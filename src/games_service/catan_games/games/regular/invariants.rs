@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+use crate::games_service::{
+    buildings::building_enums::BuildingState,
+    roads::road_enums::RoadState,
+    shared::{
+        game_enums::GameState,
+        game_models::{BuildingSupply, RemovalPolicy, ResourceCards},
+    },
+};
+
+use super::bank::RESOURCE_CARDS_PER_TYPE;
+use super::regular_game::RegularGame;
+use super::victory_points::MIN_LARGEST_ARMY;
+
+/// the number of Knight cards in the development deck
+pub const KNIGHT_CARDS: usize = 14;
+/// the number of Victory Point cards in the development deck
+pub const VICTORY_POINT_CARDS: u32 = 5;
+
+impl RegularGame {
+    /// Checks the things that can never be true in a real game of Catan.
+    ///
+    /// None of these can happen if the actions are implemented correctly, so a violation means the game has been
+    /// corrupted by a bug.  GameContainer runs this on every game it is given and quarantines the game instead of
+    /// storing a bad state.
+    ///
+    /// # Returns
+    ///
+    /// One description for each broken rule -- an empty Vec means the game is fine.
+    pub fn invariant_violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        //  every card is either in the bank or in somebody's hand
+        let mut in_play = self.bank.clone();
+        for player in self.players.values() {
+            in_play.add_cards(&player.resources);
+        }
+        for resource in ResourceCards::RESOURCES {
+            let count = in_play.count(resource);
+            if count != RESOURCE_CARDS_PER_TYPE {
+                violations.push(format!(
+                    "the bank ({}) and the players' hands ({}) hold {} {:?} cards, not {}",
+                    self.bank.count(resource),
+                    count - self.bank.count(resource),
+                    count,
+                    resource,
+                    RESOURCE_CARDS_PER_TYPE
+                ));
+            }
+        }
+
+        for (user_id, player) in &self.players {
+            let built = |state: BuildingState| {
+                player
+                    .buildings
+                    .iter()
+                    .filter(|building| building.state == state)
+                    .count() as u32
+            };
+            let edges = |state: RoadState| {
+                player
+                    .roads
+                    .iter()
+                    .filter(|road| *road.state() == state)
+                    .count() as u32
+            };
+            let supply = &player.supply;
+            if supply.roads + edges(RoadState::Road) != BuildingSupply::ROADS
+                || supply.ships + edges(RoadState::Ship) != BuildingSupply::SHIPS
+                || supply.settlements + built(BuildingState::Settlement) != BuildingSupply::SETTLEMENTS
+                || supply.cities + built(BuildingState::City) != BuildingSupply::CITIES
+            {
+                violations.push(format!(
+                    "{}'s supply {:?} doesn't match the pieces on the board",
+                    user_id, supply
+                ));
+            }
+        }
+
+        for (user_id, owed) in &self.pending_discards {
+            match self.players.get(user_id) {
+                Some(player) if player.resources.total() >= *owed => {}
+                _ => violations.push(format!(
+                    "{} owes {} cards but doesn't hold that many",
+                    user_id, owed
+                )),
+            }
+        }
+        if !self.pending_discards.is_empty() && self.game_state != GameState::MustDiscard {
+            violations.push(format!(
+                "discards are pending in the {:?} state",
+                self.game_state
+            ));
+        }
+
+        let knights: usize = self.players.keys().map(|id| self.knights_played(id)).sum();
+        if knights > KNIGHT_CARDS {
+            violations.push(format!(
+                "{} knights have been played, but there are only {}",
+                knights, KNIGHT_CARDS
+            ));
+        }
+        let victory_point_cards: u32 = self
+            .players
+            .values()
+            .map(|player| player.victory_point_cards)
+            .sum();
+        if victory_point_cards > VICTORY_POINT_CARDS {
+            violations.push(format!(
+                "players hold {} victory point cards, but there are only {}",
+                victory_point_cards, VICTORY_POINT_CARDS
+            ));
+        }
+        if let Some(holder) = self.largest_army_holder.as_deref() {
+            if self.knights_played(holder) < MIN_LARGEST_ARMY {
+                violations.push(format!(
+                    "{} holds Largest Army with {} knights",
+                    holder,
+                    self.knights_played(holder)
+                ));
+            }
+        }
+
+        //  there is one baron, and it has to be on the board
+        if !self.tiles.contains_key(&self.baron_tile) {
+            violations.push(format!(
+                "the baron is on {:?}, which is not on the board",
+                self.baron_tile
+            ));
+        }
+
+        //  players who have left are out of the turn, hold no awards and, unless their hand is frozen, hold no cards
+        for user_id in self.forfeited.iter() {
+            if self.player_order.contains(user_id) {
+                violations.push(format!("{} forfeited but is still in the player order", user_id));
+            }
+            if self.longest_road_holder.as_ref() == Some(user_id)
+                || self.largest_army_holder.as_ref() == Some(user_id)
+            {
+                violations.push(format!("{} forfeited but still holds an award", user_id));
+            }
+            if self.options.removal_policy != RemovalPolicy::Freeze {
+                if let Some(player) = self.players.get(user_id) {
+                    if player.resources.total() > 0 || !player.dev_cards.is_empty() {
+                        violations.push(format!("{} forfeited but still holds cards", user_id));
+                    }
+                }
+            }
+        }
+
+        if !self.player_order.is_empty() && !self.player_order.contains(&self.current_player_id) {
+            violations.push(format!(
+                "the current player {} is not in the player order",
+                self.current_player_id
+            ));
+        }
+
+        violations
+    }
+}
@@ -0,0 +1,36 @@
+#![allow(dead_code)]
+use crate::shared::{i18n::Language, shared_models::GameError};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// the language the player reads the game in: their own if they have picked one, else the game's
+    pub fn language_for(&self, user_id: &str) -> Language {
+        self.language_overrides.get(user_id).copied().unwrap_or(self.language)
+    }
+
+    /// Sets the game's language.  Only the creator can, but they can at any time.
+    pub fn set_language(&self, user_id: &str, language: Language) -> Result<Self, GameError> {
+        if user_id != self.creator_id {
+            return Err(GameError::ActionError(
+                "only the game's creator can change its language".to_owned(),
+            ));
+        }
+        let mut clone = self.clone();
+        clone.language = language;
+        Ok(clone)
+    }
+
+    /// The player reads the game in this language instead of the game's -- or in the game's again, with None.
+    pub fn set_language_override(&self, user_id: &str, language: Option<Language>) -> Result<Self, GameError> {
+        if !self.players.contains_key(user_id) {
+            return Err(GameError::BadId(format!("{} isn't playing in this game", user_id)));
+        }
+        let mut clone = self.clone();
+        match language {
+            Some(language) => clone.language_overrides.insert(user_id.to_owned(), language),
+            None => clone.language_overrides.remove(user_id),
+        };
+        Ok(clone)
+    }
+}
@@ -0,0 +1,57 @@
+#![allow(dead_code)]
+use crate::{
+    games_service::{
+        bots::bots::is_bot,
+        shared::game_models::{GameMember, MemberRole},
+    },
+    shared::service_models::Claims,
+    user_service::profile_projection::{co_player_relationship, project},
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// Everybody in the game, in seat order.  Before the player order is set, nobody has a seat and the players are
+    /// sorted by name.
+    ///
+    /// Each member's profile is cut down to what the caller can see of a co-player (see profile_projection.rs).  The
+    /// game doesn't know who is connected, so connected is always false here, and nobody is the CoHost -- who the game
+    /// would go to depends on who is connected, so the members api fills both in from the long poller.
+    pub fn members(&self, claims: Option<&Claims>) -> Vec<GameMember> {
+        let mut members: Vec<GameMember> = self
+            .players
+            .iter()
+            .map(|(user_id, player)| GameMember {
+                user_id: user_id.clone(),
+                display_name: player.profile.display_name.clone(),
+                role: self.member_role(user_id),
+                user_type: player.profile.user_type.clone(),
+                seat: self.player_order.iter().position(|id| id == user_id),
+                is_current_player: *user_id == self.current_player_id,
+                connected: false,
+                profile: project(&player.profile, co_player_relationship(claims, user_id)),
+            })
+            .collect();
+        members.sort_by(|a, b| {
+            (a.seat.is_none(), a.seat, &a.display_name, &a.user_id).cmp(&(
+                b.seat.is_none(),
+                b.seat,
+                &b.display_name,
+                &b.user_id,
+            ))
+        });
+        members
+    }
+
+    fn member_role(&self, user_id: &str) -> MemberRole {
+        if user_id == self.creator_id {
+            MemberRole::Creator
+        } else if is_bot(user_id) {
+            MemberRole::Bot
+        } else if self.capabilities_of(user_id).spectator_only {
+            MemberRole::Spectator
+        } else {
+            MemberRole::Player
+        }
+    }
+}
@@ -1,3 +1,17 @@
+pub mod bank;
+pub mod baron;
+pub mod custom_board;
+pub mod delegation;
+pub mod dev_cards;
+pub mod forfeit;
 pub mod game_info;
+pub mod invariants;
+pub mod language;
+pub mod members;
+pub mod placement;
+pub mod purchases;
+pub mod redaction;
 pub mod regular_game;
-
+pub mod setup;
+pub mod trades;
+pub mod victory_points;
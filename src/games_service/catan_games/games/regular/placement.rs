@@ -0,0 +1,235 @@
+#![allow(dead_code)]
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    games_service::{
+        buildings::{
+            building_enums::BuildingState, building_key::BuildingKey,
+            placement_validator::PlacementValidator,
+        },
+        roads::{
+            longest_road::{
+                building_corner, edge_id, longest_road_length, Corner, MIN_LONGEST_ROAD,
+            },
+            road_enums::RoadState,
+            road_key::RoadKey,
+        },
+        shared::game_models::BuildData,
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// Builds a settlement, city, road or ship for the player.
+    ///
+    /// During setup this places the player's free starting pieces (see setup.rs).  After that the player pays for
+    /// them (see purchases.rs).
+    pub fn build(&self, user_id: &str, build_data: &BuildData) -> Result<Self, GameError> {
+        self.verify_no_pending_dev_card()?;
+        if !self.is_setup() {
+            return self.buy(user_id, build_data);
+        }
+        match build_data {
+            BuildData::Settlement(building_key) => self.build_setup_settlement(user_id, building_key),
+            BuildData::Road(road_key) => self.build_setup_road(user_id, road_key),
+            BuildData::Ship(road_key) => self.build_setup_ship(user_id, road_key),
+            BuildData::City(_) => Err(GameError::BadActionData(
+                "only settlements, roads and ships are placed during setup".to_owned(),
+            )),
+        }
+    }
+
+    /// checks settlements and roads against the board as it is now
+    pub fn placement_validator(&self) -> PlacementValidator {
+        PlacementValidator::new(&self.buildings, &self.roads)
+    }
+
+    /// Builds a road for the player and recalculates Longest Road.
+    ///
+    /// The key can name the road from either of the tiles it sits between.  This only checks that the road is on the
+    /// board, that it isn't already built, and that the player has a road left in their supply -- whether the player
+    /// is allowed to build there is up to the caller.
+    pub fn place_road(&mut self, user_id: &str, road_key: &RoadKey) -> Result<(), GameError> {
+        self.place_edge_piece(user_id, road_key, RoadState::Road)
+    }
+
+    /// Builds a ship for the player -- the same as place_road, but the piece comes out of the player's ships
+    pub fn place_ship(&mut self, user_id: &str, road_key: &RoadKey) -> Result<(), GameError> {
+        self.place_edge_piece(user_id, road_key, RoadState::Ship)
+    }
+
+    fn place_edge_piece(
+        &mut self,
+        user_id: &str,
+        road_key: &RoadKey,
+        state: RoadState,
+    ) -> Result<(), GameError> {
+        let profile = self
+            .players
+            .get(user_id)
+            .ok_or_else(|| GameError::BadId(user_id.to_owned()))?
+            .profile
+            .clone();
+
+        let edge = edge_id(road_key);
+        let road = self
+            .roads
+            .values_mut()
+            .find(|road| edge_id(road.primary_key()) == edge)
+            .ok_or_else(|| GameError::BadActionData(format!("{} is not on the board", road_key)))?;
+        if *road.state() != RoadState::Unbuilt {
+            return Err(GameError::BadActionData(format!(
+                "{} has already been built",
+                road_key
+            )));
+        }
+        let player = self
+            .players
+            .get_mut(user_id)
+            .ok_or_else(|| GameError::BadId(user_id.to_owned()))?;
+        match state {
+            RoadState::Ship => player.supply.take_ship()?,
+            _ => player.supply.take_road()?,
+        }
+        road.build(&profile, state);
+        player.roads.push(road.clone());
+        self.update_longest_road();
+        self.update_scores();
+        Ok(())
+    }
+
+    /// Builds (or upgrades) a settlement or city for the player and recalculates Longest Road, since a new building
+    /// can cut another player's road in two.
+    ///
+    /// A corner is in the buildings map once for every tile that touches it, so every one of those entries is updated.
+    /// The new piece comes out of the player's supply, and the piece it replaces (a settlement being upgraded to a
+    /// city) goes back.
+    pub fn place_building(
+        &mut self,
+        user_id: &str,
+        building_key: &BuildingKey,
+        state: BuildingState,
+    ) -> Result<(), GameError> {
+        if !self.players.contains_key(user_id) {
+            return Err(GameError::BadId(user_id.to_owned()));
+        }
+
+        let corner = building_corner(building_key);
+        let mut entries: Vec<_> = self
+            .buildings
+            .values_mut()
+            .filter(|building| building_corner(&building.building_key) == corner)
+            .collect();
+        if entries.is_empty() {
+            return Err(GameError::BadActionData(format!(
+                "{} is not on the board",
+                building_key
+            )));
+        }
+        if entries
+            .iter()
+            .any(|building| building.owner_id.is_some() && building.owner_id.as_deref() != Some(user_id))
+        {
+            return Err(GameError::BadActionData(format!(
+                "{} belongs to another player",
+                building_key
+            )));
+        }
+        let replaced = entries[0].state.clone();
+        if let Some(player) = self.players.get_mut(user_id) {
+            player.supply.take_building(&state)?;
+            player.supply.return_building(&replaced);
+        }
+        for building in entries.iter_mut() {
+            building.owner_id = Some(user_id.to_owned());
+            building.state = state.clone();
+        }
+        let placed = entries[0].clone();
+
+        if let Some(player) = self.players.get_mut(user_id) {
+            player
+                .buildings
+                .retain(|building| building_corner(&building.building_key) != corner);
+            player.buildings.push(placed);
+        }
+        self.update_longest_road();
+        self.update_scores();
+        Ok(())
+    }
+
+    /// Recalculates every player's longest road and who holds Longest Road.
+    ///
+    /// A player needs at least MIN_LONGEST_ROAD connected roads to hold it.  The current holder keeps it until
+    /// somebody has a strictly longer road; if the holder's road is cut and several players tie for the longest, the
+    /// card is set aside until one of them pulls ahead.  Players who have forfeited can't hold it.
+    pub fn update_longest_road(&mut self) {
+        if let Some(holder) = self.longest_road_holder.clone() {
+            if self.has_forfeited(&holder) {
+                self.longest_road_holder = None;
+            }
+        }
+        let lengths: HashMap<String, usize> = self
+            .players
+            .keys()
+            .map(|id| (id.clone(), self.longest_road_for(id)))
+            .collect();
+        let longest = lengths
+            .iter()
+            .filter(|(id, _)| !self.has_forfeited(id))
+            .map(|(_, length)| *length)
+            .max()
+            .unwrap_or(0);
+        let holder_length = self
+            .longest_road_holder
+            .as_ref()
+            .and_then(|id| lengths.get(id))
+            .cloned()
+            .unwrap_or(0);
+
+        self.longest_road_holder = if longest < MIN_LONGEST_ROAD {
+            None
+        } else if holder_length == longest {
+            self.longest_road_holder.clone()
+        } else {
+            let leaders: Vec<&String> = lengths
+                .iter()
+                .filter(|(id, length)| **length == longest && !self.has_forfeited(id))
+                .map(|(id, _)| id)
+                .collect();
+            match leaders.as_slice() {
+                [leader] => Some((*leader).clone()),
+                _ => None,
+            }
+        };
+
+        for (id, player) in self.players.iter_mut() {
+            let has_longest_road = self.longest_road_holder.as_deref() == Some(id.as_str());
+            player
+                .state
+                .set_longest_road(lengths[id] as i8, has_longest_road);
+        }
+    }
+
+    /// the longest road the player has, where other players' settlements and cities break the road
+    pub fn longest_road_for(&self, user_id: &str) -> usize {
+        let roads: Vec<RoadKey> = self
+            .roads
+            .values()
+            .filter(|road| road.is_owned_by(user_id))
+            .map(|road| road.primary_key().clone())
+            .collect();
+        let blocked: HashSet<Corner> = self
+            .buildings
+            .values()
+            .filter(|building| {
+                building.state != BuildingState::Empty
+                    && building.owner_id.is_some()
+                    && building.owner_id.as_deref() != Some(user_id)
+            })
+            .map(|building| building_corner(&building.building_key))
+            .collect();
+        longest_road_length(&roads, &blocked)
+    }
+}
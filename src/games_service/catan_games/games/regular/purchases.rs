@@ -0,0 +1,130 @@
+#![allow(dead_code)]
+use crate::{
+    games_service::{
+        buildings::building_enums::BuildingState,
+        roads::{longest_road::building_corner, road_enums::RoadState},
+        shared::{
+            game_enums::GameState,
+            game_models::{BuildData, LedgerReason, ResourceCards},
+        },
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// a road costs a wood and a brick
+    pub fn road_cost() -> ResourceCards {
+        ResourceCards::new(1, 1, 0, 0, 0)
+    }
+
+    /// a settlement costs a wood, a brick, a sheep and a wheat
+    pub fn settlement_cost() -> ResourceCards {
+        ResourceCards::new(1, 1, 1, 1, 0)
+    }
+
+    /// a city costs two wheat and three ore
+    pub fn city_cost() -> ResourceCards {
+        ResourceCards::new(0, 0, 0, 2, 3)
+    }
+
+    /// a ship costs a wood and a sheep
+    pub fn ship_cost() -> ResourceCards {
+        ResourceCards::new(1, 0, 1, 0, 0)
+    }
+
+    pub fn cost_of(build_data: &BuildData) -> ResourceCards {
+        match build_data {
+            BuildData::Road(_) => Self::road_cost(),
+            BuildData::Ship(_) => Self::ship_cost(),
+            BuildData::Settlement(_) => Self::settlement_cost(),
+            BuildData::City(_) => Self::city_cost(),
+        }
+    }
+
+    /// true if the current player has the cards and a piece left for a road, settlement or city -- or a ship, if
+    /// the board has any sea
+    pub fn can_buy_anything(&self) -> bool {
+        self.players
+            .get(&self.current_player_id)
+            .map_or(false, |player| {
+                let supply = &player.supply;
+                let affordable = |cost: ResourceCards| player.resources.contains(&cost);
+                (supply.roads > 0 && affordable(Self::road_cost()))
+                    || (supply.settlements > 0 && affordable(Self::settlement_cost()))
+                    || (supply.cities > 0 && affordable(Self::city_cost()))
+                    || (supply.ships > 0 && self.has_sea() && affordable(Self::ship_cost()))
+            })
+    }
+
+    /// Buys a road, ship, settlement or city for the current player after they have rolled.
+    ///
+    /// The piece has to follow the placement rules (see PlacementValidator) -- a settlement also has to be at the end
+    /// of one of the player's roads, and a city has to replace one of the player's settlements.  Roads and settlements
+    /// have to touch land and ships have to touch the sea (see seafarers/ships.rs).  The cards go back to
+    /// the bank through the ledger, and nothing changes if the player can't pay or has no piece left.
+    pub fn buy(&self, user_id: &str, build_data: &BuildData) -> Result<Self, GameError> {
+        if self.current_player_id != user_id {
+            return Err(GameError::ActionError(format!(
+                "it is {}'s turn, not {}'s",
+                self.current_player_id, user_id
+            )));
+        }
+        if self.game_state != GameState::BuyingAndTrading {
+            return Err(GameError::ActionError(format!(
+                "can't build in the {:?} state",
+                self.game_state
+            )));
+        }
+        let validator = self.placement_validator();
+        match build_data {
+            BuildData::Road(road_key) => {
+                validator.check_road(user_id, road_key)?;
+                self.check_edge_terrain(road_key, &RoadState::Road)?;
+            }
+            BuildData::Ship(road_key) => {
+                validator.check_road(user_id, road_key)?;
+                self.check_edge_terrain(road_key, &RoadState::Ship)?;
+            }
+            BuildData::Settlement(building_key) => {
+                validator.check_settlement(building_key)?;
+                validator.check_settlement_connected(user_id, building_key)?;
+                self.check_on_land(building_key)?;
+            }
+            BuildData::City(building_key) => {
+                let corner = building_corner(building_key);
+                let upgradable = self.players.get(user_id).map_or(false, |player| {
+                    player.buildings.iter().any(|building| {
+                        building.state == BuildingState::Settlement
+                            && building_corner(&building.building_key) == corner
+                    })
+                });
+                if !upgradable {
+                    return Err(GameError::BadActionData(format!(
+                        "{} doesn't have a settlement at {}",
+                        user_id, building_key
+                    )));
+                }
+            }
+        }
+
+        let cost = Self::cost_of(build_data);
+        let mut clone = self.clone();
+        clone.return_to_bank(user_id, &cost, LedgerReason::Purchase)?;
+        if let Some(player) = clone.players.get_mut(user_id) {
+            player.resource_count.record(0, cost.total());
+        }
+        match build_data {
+            BuildData::Road(road_key) => clone.place_road(user_id, road_key)?,
+            BuildData::Ship(road_key) => clone.place_ship(user_id, road_key)?,
+            BuildData::Settlement(building_key) => {
+                clone.place_building(user_id, building_key, BuildingState::Settlement)?
+            }
+            BuildData::City(building_key) => {
+                clone.place_building(user_id, building_key, BuildingState::City)?
+            }
+        }
+        Ok(clone)
+    }
+}
@@ -0,0 +1,30 @@
+#![allow(dead_code)]
+use crate::{
+    games_service::shared::game_enums::{DevCardType, GameState},
+    user_service::profile_projection::{project, Relationship},
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// The game as one player is allowed to see it.
+    ///
+    /// The development cards in the other players' hands are turned face down (the number of cards is public, what
+    /// they are isn't), and their victory point cards are hidden until the game is over.  Their profiles are cut down
+    /// to what a friend can see (see profile_projection.rs) -- no email or phone number.  Resource hands, the bank and
+    /// the ledger are public.  Every GameUpdate sent to a player goes through here.
+    pub fn redacted_for(&self, viewer_id: &str) -> Self {
+        let mut redacted = self.clone();
+        for (user_id, player) in redacted.players.iter_mut() {
+            if user_id == viewer_id {
+                continue;
+            }
+            player.dev_cards = vec![DevCardType::Back; player.dev_cards.len()];
+            player.profile = project(&player.profile, Relationship::Friend);
+            if self.game_state != GameState::GameOver {
+                player.victory_point_cards = 0;
+            }
+        }
+        redacted
+    }
+}
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
 #![macro_use]
+use crate::games_service::catan_games::games::seafarers::game_info::SEAFARERS_GAME_INFO;
 use crate::games_service::catan_games::traits::game_info_trait::shuffle_vector;
 use crate::games_service::catan_games::traits::game_state_machine_trait::{
     StateData, StateMachineTrait,
@@ -8,7 +9,10 @@ use crate::games_service::catan_games::traits::game_state_machine_trait::{
 use crate::games_service::harbors::harbor_enums::HarborType;
 use crate::games_service::player::calculated_state::{CalculatedState, ResourceCount};
 use crate::games_service::shared::game_enums::{
-    CatanGames, Direction, GameAction, GamePhase, GameState, GameType,
+    CatanGames, DevCardType, Direction, GameAction, GamePhase, GameState, GameType, GameVisibility,
+};
+use crate::games_service::shared::game_models::{
+    Delegate, GameOptions, LedgerEntry, LocalCapabilities, PendingDevCard, ResourceCards, TradeOffer,
 };
 use crate::games_service::{
     buildings::{building::Building, building_enums::BuildingPosition, building_key::BuildingKey},
@@ -20,13 +24,15 @@ use crate::games_service::{
     tiles::{self, tile::Tile, tile_enums::TileResource, tile_key::TileKey},
 };
 
-use crate::shared::shared_models::{UserProfile, GameError, ServiceResponse};
+use crate::shared::i18n::Language;
+use crate::shared::shared_models::{UserProfile, GameError, ResponseType, ServiceResponse};
 use crate::shared::service_models::PersistUser;
 
 use actix_web::Resource;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use schemars::JsonSchema;
 use serde_with::serde_as;
 use std::fs::File;
 use std::io::Write;
@@ -36,29 +42,81 @@ use strum::IntoEnumIterator;
 use super::game_info::{RegularGameInfo, REGULAR_GAME_INFO};
 
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct RegularGame {
     pub id: String,
     #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, Player)>")]
     pub players: HashMap<String, Player>,
     #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(TileKey, Tile)>")]
     pub tiles: HashMap<TileKey, Tile>,
     #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(HarborKey, Harbor)>")]
     pub harbors: HashMap<HarborKey, Harbor>,
     #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(RoadKey, Road)>")]
     pub roads: HashMap<RoadKey, Road>,
     #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(BuildingKey, Building)>")]
     pub buildings: HashMap<BuildingKey, Building>,
     pub current_player_id: String,
     pub player_order: Vec<String>,
     pub game_state: GameState,
     pub creator_id: String,
     pub baron_tile: TileKey,
-    pub can_undo: bool,
+    pub can_undo: bool, // what the undo policy in the options says about this state -- set when the game is pushed
     pub shuffle_count: u32,
     pub game_index: u32,
     pub game_type: CatanGames,
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, u32)>")]
+    pub pending_discards: HashMap<String, u32>, // user_id -> number of cards they still owe after a 7
+    pub discard_deadline: Option<u64>,          // when the service discards for anybody who still owes cards
+    pub longest_road_holder: Option<String>,    // user_id of the player holding Longest Road, if anybody does
+    pub largest_army_holder: Option<String>,    // user_id of the player holding Largest Army, if anybody does
+    pub winner_id: Option<String>,              // set when the game moves to GameOver
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, TradeOffer)>")]
+    pub open_trades: HashMap<String, TradeOffer>, // offer_id -> offer. cleared at the end of every turn
+    pub setup_settlement: Option<BuildingKey>,    // the settlement placed this setup turn. the road has to touch it
+    pub bank: ResourceCards,                      // every resource card that isn't in a player's hand
+    pub ledger: Vec<LedgerEntry>,                 // every card that has changed hands this turn
+    pub pending_dev_card: Option<PendingDevCard>, // a card that has been played and is waiting for the player's choice
+    pub dev_card_played: Option<DevCardType>,     // the card played this turn. only one can be played a turn
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, u32)>")]
+    pub turn_start_scores: HashMap<String, u32>, // user_id -> public score when this turn started
+    pub custom_board: Option<RegularGameInfo>, // the creator's own layout, if they sent one (see custom_board.rs)
+    pub options: GameOptions,                  // the victory point target and win condition (see victory_points.rs)
+    pub rounds_played: u32,                    // complete rounds since the first roll
+    pub ends_at: Option<u64>,                  // when a timed game ends. set at the first roll
+    #[serde(default)]
+    pub last_roll: Option<u32>, // the dice this turn rolled.  cleared when the turn ends
+    pub forfeited: Vec<String>, // players who gave up or walked away, in the order they left (see forfeit.rs)
+    #[serde(default)]
+    pub visibility: GameVisibility,
+    #[serde(default)]
+    pub join_code: Option<String>, // private games only: the code that joins the game (see join_codes.rs)
+    #[serde(default)]
+    pub banned: Vec<String>, // players the creator removed and won't let back in (see remove_player)
+    #[serde(default)]
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, LocalCapabilities)>")]
+    pub local_capabilities: HashMap<String, LocalCapabilities>, // local user_id -> what their seat can do
+    #[serde(default)]
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, Delegate)>")]
+    pub delegations: HashMap<String, Delegate>, // user_id -> who plays their seat while they are away
+    #[serde(default)]
+    pub language: Language, // what the service writes for the game is in this (see i18n.rs)
+    #[serde(default)]
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, Language)>")]
+    pub language_overrides: HashMap<String, Language>, // user_id -> the language they read the game in instead
+    #[serde(default)]
+    pub checksum: String, // of what every player can see -- set when the game is pushed (see checksum.rs)
 }
 
 impl RegularGame {
@@ -75,21 +133,22 @@ impl RegularGame {
     ///
     /// A new RegularGame instance.
     pub fn new(creator: &UserProfile) -> Self {
-        let player = {
-            let user = creator.clone();
-            Player {
-                profile: user.clone(),
-                roads: vec![],
-                buildings: vec![],
-                harbors: vec![],
-                targets: vec![],
-                resource_count: ResourceCount::default(),
-                good_rolls: 0,
-                bad_rolls: 0,
-                state: CalculatedState::default(),
-            }
-        };
-        let game_info = &*REGULAR_GAME_INFO;
+        Self::new_of_type(creator, CatanGames::Regular)
+    }
+
+    /// Creates a new game on the board for the game type.  Seafarers games get the multi-island board with sea tiles
+    /// (see seafarers/game_info.rs) and play by the same rules plus ships; every other type gets the regular board.
+    pub fn new_of_type(creator: &UserProfile, game_type: CatanGames) -> Self {
+        Self::new_with_info(creator, game_type, Self::game_info_for(game_type))
+    }
+
+    /// creates a new game on the board described by game_info, without checking that it is a sensible board
+    pub(super) fn new_with_info(
+        creator: &UserProfile,
+        game_type: CatanGames,
+        game_info: &RegularGameInfo,
+    ) -> Self {
+        let player = Player::new(creator, 0);
         let mut tiles = Self::setup_tiles(game_info);
         let roads = Self::setup_roads(&mut tiles);
         let buildings = Self::setup_buildings(&mut tiles);
@@ -119,10 +178,56 @@ impl RegularGame {
             can_undo: true,
             shuffle_count: 1,
             game_index: 1,
-            game_type: CatanGames::Regular,
+            game_type,
+            pending_discards: HashMap::new(),
+            discard_deadline: None,
+            longest_road_holder: None,
+            largest_army_holder: None,
+            winner_id: None,
+            open_trades: HashMap::new(),
+            setup_settlement: None,
+            bank: Self::full_bank(),
+            ledger: vec![],
+            pending_dev_card: None,
+            dev_card_played: None,
+            turn_start_scores: HashMap::new(),
+            custom_board: None,
+            options: GameOptions::default(),
+            rounds_played: 0,
+            ends_at: None,
+            last_roll: None,
+            forfeited: vec![],
+            visibility: GameVisibility::Public,
+            join_code: None,
+            banned: vec![],
+            local_capabilities: HashMap::new(),
+            delegations: HashMap::new(),
+            language: Language::default(),
+            language_overrides: HashMap::new(),
+            checksum: String::default(),
+        }
+    }
+
+    /// what the player's seat can do.  everything, unless they are a local user whose owner has locked it down
+    pub fn capabilities_of(&self, user_id: &str) -> LocalCapabilities {
+        self.local_capabilities.get(user_id).copied().unwrap_or_default()
+    }
+
+    /// the board layout, rolls and harbors for a game type
+    pub fn game_info_for(game_type: CatanGames) -> &'static RegularGameInfo {
+        match game_type {
+            CatanGames::Seafarers => &*SEAFARERS_GAME_INFO,
+            _ => &*REGULAR_GAME_INFO,
         }
     }
 
+    /// the board layout, rolls and harbors this game was created with
+    pub fn game_info(&self) -> &RegularGameInfo {
+        self.custom_board
+            .as_ref()
+            .unwrap_or_else(|| Self::game_info_for(self.game_type))
+    }
+
     /**
      *  clone the game, add the user, and return the clone.  presumably it will be added to the undo_stack
      *  so that the operation can be undone by simply going to the previous game struct
@@ -133,25 +238,75 @@ impl RegularGame {
         if self.players.contains_key(&user_id) {
             return Err(ServiceResponse::new_bad_id("user_id already exists", &user_id));
         }
+        if self.banned.contains(&user_id) {
+            return Err(ServiceResponse::new(
+                "banned from this game",
+                reqwest::StatusCode::FORBIDDEN,
+                ResponseType::NoData,
+                GameError::HttpError(reqwest::StatusCode::FORBIDDEN),
+            ));
+        }
+        //  nobody joins once the game has left the lobby -- a code or an invite that is still around doesn't get
+        //  anybody into a game that has started
+        if self.game_state != GameState::AddingPlayers {
+            return Err(ServiceResponse::new(
+                "the game has already started",
+                reqwest::StatusCode::CONFLICT,
+                ResponseType::NoData,
+                GameError::HttpError(reqwest::StatusCode::CONFLICT),
+            ));
+        }
+        //  this runs in the game's container, so two players taking the last seat can't both get it.  the one who
+        //  loses gets the players who are in the game now
+        if self.players.len() >= self.max_players() {
+            return Err(ServiceResponse::new(
+                "the game is full",
+                reqwest::StatusCode::CONFLICT,
+                ResponseType::NoData,
+                GameError::GameFull(self.players.keys().cloned().collect()),
+            ));
+        }
 
         let mut clone = self.clone();
-        let player = {
-            Player {
-                profile: profile.clone(),
-                roads: vec![],
-                buildings: vec![],
-                harbors: vec![],
-                targets: vec![],
-                resource_count: ResourceCount::default(),
-                good_rolls: 0,
-                bad_rolls: 0,
-                state: CalculatedState::default(),
-            }
-        };
+        let player = Player::new(profile, self.next_seat_index());
         clone.players.insert(user_id.clone(), player);
         Ok(clone)
     }
 
+    /**
+     *  the creator takes a player out of the game before it starts, and if ban is set they can't join it again.  the
+     *  creator can't remove themselves -- they can forfeit or just leave the game to be evicted.  nobody has cards or
+     *  pieces before the game starts, so there is nothing to hand back (a player who leaves later forfeits instead)
+     */
+    pub fn remove_player(&self, user_id: &str, ban: bool) -> Result<Self, GameError> {
+        if self.game_state != GameState::AddingPlayers {
+            return Err(GameError::ActionError(
+                "players can only be removed while the game is adding players".to_owned(),
+            ));
+        }
+        if user_id == self.creator_id {
+            return Err(GameError::BadActionData("the creator can't remove themselves".to_owned()));
+        }
+        if !self.players.contains_key(user_id) {
+            return Err(GameError::BadId(user_id.to_owned()));
+        }
+        let mut clone = self.clone();
+        clone.players.remove(user_id);
+        clone.player_order.retain(|id| id != user_id);
+        if ban && !clone.banned.iter().any(|id| id == user_id) {
+            clone.banned.push(user_id.to_owned());
+        }
+        Ok(clone)
+    }
+
+    /// the lowest seat index not already taken.  seats are never renumbered, so a player keeps their seat (and
+    /// their colors) for the whole game
+    pub fn next_seat_index(&self) -> usize {
+        (0..)
+            .find(|index| !self.players.values().any(|p| p.seat_index == *index))
+            .expect("there is always a free seat")
+    }
+
     /// Sets up the game tiles according to the provided game information.
     ///
     /// The setup_tiles function creates a HashMap of TileKey and Tile pairs, each representing a unique tile in the game.
@@ -300,6 +455,7 @@ impl RegularGame {
     /// This function takes all the `Tile` objects in the game, excluding the desert,
     /// shuffles their resources and roll numbers, and then assigns the shuffled values
     /// back to the tiles. It ensures that the desert tile always has a roll number of 7.
+    /// Sea tiles are part of the board's layout, so they (and their 0 roll) stay where they are.
     ///
     /// # Panics
     ///
@@ -316,7 +472,11 @@ impl RegularGame {
     /// game.shuffle_tiles();
     /// ```
     fn shuffle_tiles(&mut self) {
-        let mut tiles: Vec<Tile> = self.tiles.values().cloned().collect();
+        let (sea, mut tiles): (Vec<Tile>, Vec<Tile>) = self
+            .tiles
+            .values()
+            .cloned()
+            .partition(|tile| tile.original_resource.is_sea());
         let mut resources: Vec<TileResource> = tiles
             .iter()
             .map(|tile| tile.current_resource.clone())
@@ -348,7 +508,7 @@ impl RegularGame {
         self.baron_tile = tiles[desert_tile_index].tile_key.clone();
 
         self.tiles.clear();
-        for tile in tiles {
+        for tile in sea.into_iter().chain(tiles) {
             self.tiles.insert(tile.tile_key.clone(), tile);
         }
     }
@@ -382,7 +542,7 @@ impl RegularGame {
      * @param tiles - The array of TileProps objects to check.
      * @returns - A boolean value indicating whether the constraint is satisfied.
      */
-    fn validate_no_adjacent_six_eight(&self) -> bool {
+    pub fn validate_no_adjacent_six_eight(&self) -> bool {
         for (tile_key, tile_data) in self.tiles.iter() {
             if tile_data.roll == 6 || tile_data.roll == 8 {
                 let surrounding_tile_keys = tile_key.get_adjacent_keys();
@@ -399,6 +559,24 @@ impl RegularGame {
         }
         true
     }
+
+    /// How long to wait before ending the current player's turn for them, if the game ends turns automatically and
+    /// the player can't do anything but end it.  Trading doesn't count -- the player could always ask for a trade,
+    /// but a trade that is already on the table does.
+    pub fn auto_end_turn_after(&self) -> Option<u32> {
+        let seconds = self.options.auto_end_turn_seconds?;
+        let only_next = self.game_state == GameState::BuyingAndTrading
+            && self.open_trades.is_empty()
+            && self
+                .valid_actions(false)
+                .iter()
+                .all(|action| matches!(action, GameAction::Next | GameAction::Trade));
+        if only_next {
+            Some(seconds)
+        } else {
+            None
+        }
+    }
 }
 
 impl<'a> GameTrait<'a> for RegularGame {
@@ -411,10 +589,10 @@ impl<'a> GameTrait<'a> for RegularGame {
     // type Buildings = &'a HashMap<BuildingKey, Building>;
 
     fn get_game_info(&'a self) -> &'a Self::GameInfoType {
-        &*REGULAR_GAME_INFO
+        self.game_info()
     }
     fn get_game_info_ro(&self) -> &Self::GameInfoType {
-        &*REGULAR_GAME_INFO
+        self.game_info()
     }
 
     fn get_tiles(&mut self) -> &mut HashMap<TileKey, Tile> {
@@ -469,20 +647,9 @@ impl<'a> GameTrait<'a> for RegularGame {
     }
 
     fn add_user(&mut self, user: &UserProfile) {
-        self.players.insert(user.user_id.clone().unwrap(), {
-            let user = user;
-            Player {
-                profile: user.clone(),
-                roads: vec![],
-                buildings: vec![],
-                harbors: vec![],
-                targets: vec![],
-                resource_count: ResourceCount::default(),
-                good_rolls: 0,
-                bad_rolls: 0,
-                state: CalculatedState::default(),
-            }
-        });
+        let seat_index = self.next_seat_index();
+        self.players
+            .insert(user.user_id.clone().unwrap(), Player::new(user, seat_index));
     }
 
     fn shuffle(&mut self) {
@@ -555,6 +722,11 @@ impl<'a> GameTrait<'a> for RegularGame {
         if can_redo {
             actions.push(GameAction::Redo);
         }
+        //  a played Monopoly or Year of Plenty card has to be finished before anything else
+        if self.pending_dev_card.is_some() {
+            actions.push(GameAction::ResolveDevCard);
+            return actions;
+        }
         match self.game_state {
             GameState::AddingPlayers => {
                 let len = self.players.len();
@@ -575,16 +747,38 @@ impl<'a> GameTrait<'a> for RegularGame {
                 actions.push(GameAction::Next);
                 actions.push(GameAction::SetOrder);
             },
-            GameState::AllocateResourceForward => {
+            //  each player places a settlement and a road, and then can move on
+            GameState::AllocateResourceForward | GameState::AllocateResourceReverse => {
+                if self.setup_turn_done() {
+                    actions.push(GameAction::Next);
+                } else {
+                    actions.push(GameAction::Build);
+                }
+            }
+            GameState::WaitingForRoll => {
+                actions.push(GameAction::Roll);
+                if self.can_play_dev_card() {
+                    actions.push(GameAction::PlayDevCard);
+                }
+            }
+            GameState::MustDiscard => {
+                actions.push(GameAction::Discard);
+            }
+            GameState::MustMoveBaron => {
+                actions.push(GameAction::MoveBaron);
+            }
+            GameState::BuyingAndTrading => {
                 actions.push(GameAction::Next);
-                actions.push(GameAction::Build);
-            },
-            GameState::AllocateResourceReverse => todo!(),
-            GameState::WaitingForRoll => todo!(),
-            GameState::MustMoveBaron => todo!(),
-            GameState::BuyingAndTrading => todo!(),
+                actions.push(GameAction::Trade);
+                if self.can_buy_anything() {
+                    actions.push(GameAction::Build);
+                }
+                if self.can_play_dev_card() {
+                    actions.push(GameAction::PlayDevCard);
+                }
+            }
             GameState::Supplemental => todo!(),
-            GameState::GameOver => todo!(),
+            GameState::GameOver => {} // nothing left to do
         }
         actions
     }
@@ -616,16 +810,19 @@ impl<'a> GameTrait<'a> for RegularGame {
                             "We can't be in the allocation state with an empty list of players",
                         )
                     {
-                        GameState::AllocateResourceReverse
-                    } else {
                         GameState::WaitingForRoll
+                    } else {
+                        GameState::AllocateResourceReverse
                     }
                 }
-                GameState::WaitingForRoll => todo!(),
-                GameState::MustMoveBaron => todo!(),
-                GameState::BuyingAndTrading => todo!(),
+                //
+                //  these states are left by their own actions (roll, discard, move baron), not by next
+                GameState::WaitingForRoll | GameState::MustDiscard | GameState::MustMoveBaron => {
+                    self.game_state
+                }
+                GameState::BuyingAndTrading => GameState::WaitingForRoll,
                 GameState::Supplemental => todo!(),
-                GameState::GameOver => todo!(),
+                GameState::GameOver => GameState::GameOver,
             };
 
         state
@@ -635,8 +832,48 @@ impl<'a> GameTrait<'a> for RegularGame {
     /// return the clone.  We assume this is called from the next() handler, which has validated that next is the right
     /// state
     fn set_next_state(&self) -> Result<RegularGame, GameError> {
+        self.verify_no_pending_dev_card()?;
+        if self.is_setup() && !self.setup_turn_done() {
+            return Err(GameError::ActionError(format!(
+                "{} has to place a settlement and a road first",
+                self.current_player_id
+            )));
+        }
         let mut clone = self.clone();
         clone.game_state = self.get_next_state();
+        match (self.game_state, clone.game_state) {
+            (GameState::BuyingAndTrading, _) => {
+                // end of turn
+                clone.open_trades.clear();
+                clone.ledger.clear();
+                clone.dev_card_played = None;
+                clone.last_roll = None;
+                clone.get_next_player();
+                if clone.player_order.first() == Some(&clone.current_player_id) {
+                    clone.rounds_played += 1;
+                }
+            }
+            //  setup is snake order: 1, 2, 3, 3, 2, 1.  the last player goes twice in a row and the first player,
+            //  who placed last, rolls first
+            (GameState::AllocateResourceForward, GameState::AllocateResourceForward) => {
+                clone.get_next_player();
+            }
+            (GameState::AllocateResourceReverse, GameState::AllocateResourceReverse) => {
+                clone.set_previous_player();
+            }
+            _ => {}
+        }
+        if self.is_setup() {
+            clone.setup_settlement = None;
+        }
+        //  a new turn: remember the scores so the turn's TurnSummary can say what changed
+        if clone.game_state == GameState::WaitingForRoll {
+            clone.turn_start_scores = clone
+                .players
+                .keys()
+                .map(|id| (id.clone(), clone.known_score(id)))
+                .collect();
+        }
         Ok(clone)
     }
 }
@@ -0,0 +1,192 @@
+#![allow(dead_code)]
+use std::collections::HashMap;
+
+use crate::{
+    games_service::{
+        buildings::{
+            building_enums::BuildingState,
+            building_key::BuildingKey,
+            placement_validator::{PlacementRule, PlacementViolation},
+        },
+        roads::{
+            longest_road::{building_corner, road_corners},
+            road_enums::RoadState,
+            road_key::RoadKey,
+        },
+        shared::{
+            game_enums::GameState,
+            game_models::{LedgerReason, ResourceCards},
+        },
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+impl RegularGame {
+    /// true while the players are placing their starting settlements and roads
+    pub fn is_setup(&self) -> bool {
+        matches!(
+            self.game_state,
+            GameState::AllocateResourceForward | GameState::AllocateResourceReverse
+        )
+    }
+
+    /// how many settlements (and roads) the current player has once their setup turn is done: one after the forward
+    /// round, two after the reverse round
+    fn setup_target(&self) -> usize {
+        match self.game_state {
+            GameState::AllocateResourceReverse => 2,
+            _ => 1,
+        }
+    }
+
+    fn setup_counts(&self, user_id: &str) -> (usize, usize) {
+        self.players.get(user_id).map_or((0, 0), |player| {
+            (player.buildings.len(), player.roads.len())
+        })
+    }
+
+    /// true once the current player has placed this round's settlement and road
+    pub fn setup_turn_done(&self) -> bool {
+        let (settlements, roads) = self.setup_counts(&self.current_player_id);
+        settlements >= self.setup_target() && roads >= self.setup_target()
+    }
+
+    /// Places one of the current player's starting settlements.
+    ///
+    /// The settlement has to obey the distance rule (see PlacementValidator), but doesn't have to connect to anything.
+    /// The second settlement (placed in the reverse round) pays out one card for every resource tile it touches.
+    pub fn build_setup_settlement(
+        &self,
+        user_id: &str,
+        building_key: &BuildingKey,
+    ) -> Result<Self, GameError> {
+        self.verify_setup_turn(user_id)?;
+        let (settlements, _) = self.setup_counts(user_id);
+        if settlements >= self.setup_target() {
+            return Err(GameError::ActionError(format!(
+                "{} has already placed a settlement this round",
+                user_id
+            )));
+        }
+        self.placement_validator().check_settlement(building_key)?;
+        self.check_on_land(building_key)?;
+
+        let mut clone = self.clone();
+        clone.place_building(user_id, building_key, BuildingState::Settlement)?;
+        clone.setup_settlement = Some(*building_key);
+        if self.game_state == GameState::AllocateResourceReverse {
+            let owed = HashMap::from([(user_id.to_owned(), clone.starting_resources(building_key))]);
+            if let Some(cards) = clone.limit_payouts_to_bank(&owed).get(user_id) {
+                clone.take_from_bank(user_id, cards, LedgerReason::StartingResources)?;
+                if let Some(player) = clone.players.get_mut(user_id) {
+                    player.resource_count.record(cards.total(), 0);
+                }
+            }
+        }
+        Ok(clone)
+    }
+
+    /// Places one of the current player's starting roads.  The road has to touch the settlement placed this round.
+    pub fn build_setup_road(&self, user_id: &str, road_key: &RoadKey) -> Result<Self, GameError> {
+        self.build_setup_edge_piece(user_id, road_key, RoadState::Road)
+    }
+
+    /// Places a ship instead of one of the current player's starting roads (Seafarers only).  Like the road, it has to
+    /// touch the settlement placed this round.
+    pub fn build_setup_ship(&self, user_id: &str, road_key: &RoadKey) -> Result<Self, GameError> {
+        self.build_setup_edge_piece(user_id, road_key, RoadState::Ship)
+    }
+
+    fn build_setup_edge_piece(
+        &self,
+        user_id: &str,
+        road_key: &RoadKey,
+        state: RoadState,
+    ) -> Result<Self, GameError> {
+        self.verify_setup_turn(user_id)?;
+        let (settlements, roads) = self.setup_counts(user_id);
+        if settlements < self.setup_target() {
+            return Err(GameError::ActionError(
+                "place this round's settlement before its road".to_owned(),
+            ));
+        }
+        if roads >= self.setup_target() {
+            return Err(GameError::ActionError(format!(
+                "{} has already placed a road this round",
+                user_id
+            )));
+        }
+        let settlement = self
+            .setup_settlement
+            .as_ref()
+            .map(building_corner)
+            .ok_or_else(|| GameError::ActionError("no settlement was placed this round".to_owned()))?;
+        if !road_corners(road_key).contains(&settlement) {
+            return Err(PlacementViolation::new(
+                PlacementRule::Connected,
+                road_key,
+                "a starting road has to touch the settlement placed this round",
+            )
+            .into());
+        }
+        self.placement_validator().check_road(user_id, road_key)?;
+        self.check_edge_terrain(road_key, &state)?;
+
+        let mut clone = self.clone();
+        match state {
+            RoadState::Ship => clone.place_ship(user_id, road_key)?,
+            _ => clone.place_road(user_id, road_key)?,
+        }
+        Ok(clone)
+    }
+
+    /// one card for each resource tile touching the corner
+    fn starting_resources(&self, building_key: &BuildingKey) -> ResourceCards {
+        let corner = building_corner(building_key);
+        let mut cards = ResourceCards::default();
+        for building in self
+            .buildings
+            .values()
+            .filter(|building| building_corner(&building.building_key) == corner)
+        {
+            if let Some(resource) = self
+                .tiles
+                .get(&building.building_key.tile_key)
+                .and_then(|tile| tile.current_resource.produces())
+            {
+                cards.add(resource, 1);
+            }
+        }
+        cards
+    }
+
+    fn verify_setup_turn(&self, user_id: &str) -> Result<(), GameError> {
+        if !self.is_setup() {
+            return Err(GameError::ActionError(format!(
+                "can't place starting pieces in the {:?} state",
+                self.game_state
+            )));
+        }
+        if self.current_player_id != user_id {
+            return Err(GameError::ActionError(format!(
+                "it is {}'s turn, not {}'s",
+                self.current_player_id, user_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// move back one seat -- the reverse round goes in the opposite order
+    pub(super) fn set_previous_player(&mut self) {
+        if let Some(index) = self
+            .player_order
+            .iter()
+            .position(|id| *id == self.current_player_id)
+        {
+            let previous = (index + self.player_order.len() - 1) % self.player_order.len();
+            self.current_player_id = self.player_order[previous].clone();
+        }
+    }
+}
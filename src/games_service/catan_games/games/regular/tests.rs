@@ -3,16 +3,46 @@ mod tests {
 
     use crate::{
         games_service::{
+            bots::engine::BotDifficulty,
             catan_games::{
-                games::regular::regular_game::RegularGame,
+                games::regular::{
+                    bank::RESOURCE_CARDS_PER_TYPE,
+                    game_info::REGULAR_GAME_INFO,
+                    regular_game::RegularGame,
+                    trades::{BANK_TRADE_RATIO, MAX_TRADE_OFFER_SECONDS, RESOURCE_HARBOR_RATIO},
+                },
                 traits::{game_state_machine_trait::StateMachineTrait, game_trait::GameTrait},
             },
-            roads::road_key::RoadKey,
-            shared::game_enums::{Direction, GameAction, GamePhase, GameState},
+            game_container::game_messages::{GameWonData, TurnSummary},
+            buildings::{
+                building_enums::{BuildingPosition, BuildingState},
+                building_key::BuildingKey,
+                placement_validator::{PlacementRule, PlacementViolation},
+            },
+            harbors::{harbor::Harbor, harbor_key::HarborKey},
+            roads::{
+                longest_road::{building_corner, road_corners},
+                road_key::RoadKey,
+            },
+            shared::{
+                game_enums::{
+                    DevCardType, Direction, GameAction, GamePhase, GameState, ResourceType,
+                },
+                game_models::{
+                    BankTradeData, BestBankTradeData, BuildData, BuildingSupply, CardHolder,
+                    CustomBoardData, Delegate, DevCardResolutionData, GameOptions,
+                    LedgerEntry, LedgerReason, LocalCapabilities, MemberRole, MoveBaronData, ResourceCards,
+                    RemovalPolicy, TradeOfferData, UndoPolicy, WinCondition,
+                },
+            },
             tiles::{tile_enums::TileResource, tile_key::TileKey},
         },
         middleware::service_config::SERVICE_CONFIG,
-        shared::shared_models::{UserProfile, UserType},
+        shared::{
+            i18n::Language,
+            service_models::{Claims, Role},
+            shared_models::{GameError, UserProfile, UserType},
+        },
     };
     use std::io::Write;
     use std::{collections::HashMap, fs::File};
@@ -45,6 +75,1252 @@ mod tests {
         test_serialization(&game);
     }
 
+    #[test]
+    fn test_roll_seven_discard_and_move_baron() {
+        println!("test_roll_seven_discard_and_move_baron");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::WaitingForRoll;
+
+        let target_tile = game
+            .tiles
+            .values()
+            .find(|tile| tile.tile_key != game.baron_tile)
+            .expect("there should be more than one tile")
+            .tile_key;
+        let building_key = BuildingKey::new(BuildingPosition::TopRight, target_tile);
+        game.place_building("3", &building_key, BuildingState::Settlement)
+            .expect("place_building should work");
+
+        game.players.get_mut("2").unwrap().resources = ResourceCards::new(3, 3, 3, 0, 0);
+        game.players.get_mut("3").unwrap().resources = ResourceCards::new(0, 0, 0, 2, 0);
+
+        assert!(game.roll(13).is_err());
+        game = game.roll(7).expect("roll should work");
+        verify_state_and_actions(
+            &game,
+            "test_roll_seven",
+            GameState::MustDiscard,
+            vec![GameAction::Discard],
+        );
+        assert_eq!(game.pending_discards.len(), 1);
+        assert_eq!(*game.pending_discards.get("2").unwrap(), 4);
+        assert_eq!(game.players[&game.current_player_id].sevens_rolled, 1);
+
+        // "3" has two cards and doesn't owe anything, and "2" can give up 4 a few at a time, but no more
+        assert!(game.discard("3", &ResourceCards::new(0, 0, 0, 1, 0)).is_err());
+        assert!(game.discard("2", &ResourceCards::new(3, 2, 0, 0, 0)).is_err());
+        assert!(game.discard("2", &ResourceCards::new(0, 0, 0, 4, 0)).is_err());
+        assert!(game.discard("2", &ResourceCards::default()).is_err());
+        // counts that add up past u32::MAX are refused, not a panic (or, wrapped, a small discard)
+        let overflowing = ResourceCards::new(u32::MAX, 2, 0, 0, 0);
+        assert!(matches!(game.discard("2", &overflowing), Err(GameError::BadActionData(_))));
+        game = game
+            .discard("2", &ResourceCards::new(1, 1, 0, 0, 0))
+            .expect("a partial discard should work");
+        assert_eq!(game.pending_discards["2"], 2);
+        assert_eq!(game.game_state, GameState::MustDiscard);
+        game = game
+            .discard("2", &ResourceCards::new(1, 1, 0, 0, 0))
+            .expect("discard should work");
+        assert_eq!(game.players.get("2").unwrap().resources.total(), 5);
+        verify_state_and_actions(
+            &game,
+            "test_move_baron",
+            GameState::MustMoveBaron,
+            vec![GameAction::MoveBaron],
+        );
+
+        // "3" has a building on the tile, so a victim must be chosen
+        let mut data = MoveBaronData {
+            tile_key: target_tile,
+            victim_id: None,
+        };
+        assert!(game.move_baron("1", &data).is_err());
+        data.victim_id = Some("2".to_string());
+        assert!(game.move_baron("1", &data).is_err());
+        data.victim_id = Some("3".to_string());
+        game = game.move_baron("1", &data).expect("move_baron should work");
+
+        assert_eq!(game.baron_tile, target_tile);
+        assert_eq!(game.players.get("1").unwrap().resources.wheat, 1);
+        assert_eq!(game.players.get("3").unwrap().resources.wheat, 1);
+        assert_eq!(game.current_state(), GameState::BuyingAndTrading);
+
+        // the baron has to move somewhere else next time
+        game.game_state = GameState::MustMoveBaron;
+        data.victim_id = None;
+        assert!(game.move_baron("1", &data).is_err());
+    }
+
+    #[test]
+    fn test_roll_distributes_resources() {
+        println!("test_roll_distributes_resources");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::WaitingForRoll;
+
+        let tile = game
+            .tiles
+            .values()
+            .find(|tile| {
+                tile.tile_key != game.baron_tile
+                    && tile.roll != 7
+                    && tile.current_resource.produces().is_some()
+            })
+            .expect("there should be a producing tile")
+            .clone();
+        let building_key = BuildingKey::new(BuildingPosition::Left, tile.tile_key);
+        game.place_building("2", &building_key, BuildingState::City)
+            .expect("place_building should work");
+
+        game = game.roll(tile.roll).expect("roll should work");
+        let resource = tile.current_resource.produces().unwrap();
+        assert!(game.players.get("2").unwrap().resources.count(resource) >= 2);
+        assert_eq!(game.players.get("1").unwrap().resources.total(), 0);
+        assert_eq!(game.current_state(), GameState::BuyingAndTrading);
+
+        // end of turn goes to the next player
+        game = game.set_next_state().expect("set_next_state shouldn't fail");
+        assert_eq!(game.current_state(), GameState::WaitingForRoll);
+        assert_eq!(game.current_player_id, "2");
+    }
+
+    #[test]
+    fn test_longest_road() {
+        println!("test_longest_road");
+        let mut game = create_game();
+        test_add_players(&mut game);
+
+        // "2" runs 5 roads around the center tile, from its top left corner to its left corner
+        let center = TileKey::new(0, 0, 0);
+        for direction in [
+            Direction::North,
+            Direction::NorthEast,
+            Direction::SouthEast,
+            Direction::South,
+        ] {
+            game.place_road("2", &RoadKey::new(direction, center))
+                .expect("place_road should work");
+        }
+        assert_eq!(game.longest_road_holder, None);
+        game.place_road("2", &RoadKey::new(Direction::SouthWest, center))
+            .expect("place_road should work");
+        assert_eq!(game.longest_road_holder, Some("2".to_string()));
+        let player = game.players.get("2").unwrap();
+        assert_eq!(player.state.longest_road(), 5);
+        assert!(player.state.has_longest_road());
+        assert_eq!(player.state.known_score(), 2);
+
+        // a road can't be built twice, even when it is described from the tile on the other side
+        assert!(game
+            .place_road("3", &RoadKey::new(Direction::South, center.get_neighbor_key(Direction::North)))
+            .is_err());
+
+        // "3" ties with 5 roads on the bottom tile, which isn't enough to take it
+        let bottom = TileKey::new(0, 2, -2);
+        for direction in [
+            Direction::North,
+            Direction::NorthEast,
+            Direction::SouthEast,
+            Direction::South,
+            Direction::SouthWest,
+        ] {
+            game.place_road("3", &RoadKey::new(direction, bottom))
+                .expect("place_road should work");
+        }
+        assert_eq!(game.players.get("3").unwrap().state.longest_road(), 5);
+        assert_eq!(game.longest_road_holder, Some("2".to_string()));
+
+        // ...until "3" builds a settlement in the middle of the road "2" has
+        game.place_building(
+            "3",
+            &BuildingKey::new(BuildingPosition::Right, center),
+            BuildingState::Settlement,
+        )
+        .expect("place_building should work");
+        assert_eq!(game.players.get("2").unwrap().state.longest_road(), 3);
+        assert_eq!(game.longest_road_holder, Some("3".to_string()));
+        assert_eq!(game.players.get("2").unwrap().state.known_score(), 0);
+        // the settlement plus Longest Road
+        assert_eq!(game.players.get("3").unwrap().state.known_score(), 3);
+
+        // the holder goes out with every GameUpdate
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(json.contains("\"LongestRoadHolder\":\"3\""));
+    }
+
+    #[test]
+    fn test_victory_points_and_game_over() {
+        println!("test_victory_points_and_game_over");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+
+        // 3 cities and a settlement
+        let center = TileKey::new(0, 0, 0);
+        for position in [
+            BuildingPosition::TopRight,
+            BuildingPosition::BottomRight,
+            BuildingPosition::Left,
+        ] {
+            game.place_building("1", &BuildingKey::new(position, center), BuildingState::City)
+                .expect("place_building should work");
+        }
+        game.place_building(
+            "1",
+            &BuildingKey::new(BuildingPosition::TopLeft, TileKey::new(0, 2, -2)),
+            BuildingState::Settlement,
+        )
+        .expect("place_building should work");
+        assert_eq!(game.known_score("1"), 7);
+        assert_eq!(game.players.get("1").unwrap().state.known_score(), 7);
+
+        // only the current player can win
+        game.players.get_mut("2").unwrap().victory_point_cards = 10;
+        game.update_scores();
+        assert_eq!(game.current_state(), GameState::BuyingAndTrading);
+        assert_eq!(game.winner_id, None);
+        game.players.get_mut("2").unwrap().victory_point_cards = 0;
+
+        // a victory point card and Longest Road get "1" to 10
+        game.players.get_mut("1").unwrap().victory_point_cards = 1;
+        for direction in [
+            Direction::North,
+            Direction::NorthEast,
+            Direction::SouthEast,
+            Direction::South,
+            Direction::SouthWest,
+        ] {
+            assert_eq!(game.winner_id, None);
+            game.place_road("1", &RoadKey::new(direction, center))
+                .expect("place_road should work");
+        }
+        assert_eq!(game.current_state(), GameState::GameOver);
+        assert_eq!(game.winner_id, Some("1".to_string()));
+        assert!(game.valid_actions(false).is_empty());
+        // the card isn't part of the known score
+        assert_eq!(game.players.get("1").unwrap().state.known_score(), 9);
+
+        let won = GameWonData::from_game(&game).expect("the game has a winner");
+        assert_eq!(won.winner_id, "1");
+        assert_eq!(won.scores[0].user_id, "1");
+        assert_eq!(won.scores[0].victory_points, 10);
+        assert_eq!(won.scores.len(), 3);
+    }
+
+    #[test]
+    fn test_game_options() {
+        let mut game = create_game();
+        test_add_players(&mut game);
+
+        // only the creator, only valid options, only before the game starts
+        let options = GameOptions {
+            victory_points_to_win: 8,
+            win_condition: WinCondition::MostPointsAfterRounds(2),
+            auto_end_turn_seconds: None,
+            undo_policy: UndoPolicy::default(),
+            removal_policy: RemovalPolicy::default(),
+        };
+        assert!(game.set_options("2", &options).is_err());
+        let bad_target = GameOptions {
+            victory_points_to_win: 9,
+            ..options
+        };
+        assert!(game.set_options("1", &bad_target).is_err());
+        let bad_timer = GameOptions {
+            victory_points_to_win: 10,
+            win_condition: WinCondition::Timed(1),
+            auto_end_turn_seconds: None,
+            undo_policy: UndoPolicy::default(),
+            removal_policy: RemovalPolicy::default(),
+        };
+        assert!(game.set_options("1", &bad_timer).is_err());
+        game = game.set_options("1", &options).expect("the creator can set the options");
+        assert!(serde_json::to_string(&game).unwrap().contains("\"VictoryPointsToWin\":8"));
+
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        assert!(game.set_options("1", &GameOptions::default()).is_err());
+
+        // "2" leads, but never gets to the target
+        game.players.get_mut("2").unwrap().victory_point_cards = 7;
+
+        // two rounds of turns, then the leader wins
+        for _ in 0..5 {
+            game = game.set_next_state().unwrap();
+            game.update_scores();
+            assert_eq!(game.game_state, GameState::WaitingForRoll, "{} rounds", game.rounds_played);
+            game.game_state = GameState::BuyingAndTrading;
+        }
+        assert_eq!(game.rounds_played, 1);
+        game = game.set_next_state().unwrap();
+        game.update_scores();
+        assert_eq!(game.rounds_played, 2);
+        assert_eq!(game.game_state, GameState::GameOver);
+        assert_eq!(game.winner_id, Some("2".to_string()));
+
+        // a timed game ends when the clock runs out
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game = game
+            .set_options(
+                "1",
+                &GameOptions {
+                    victory_points_to_win: 12,
+                    win_condition: WinCondition::Timed(30),
+                    auto_end_turn_seconds: None,
+                    undo_policy: UndoPolicy::default(),
+                    removal_policy: RemovalPolicy::default(),
+                },
+            )
+            .unwrap();
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::WaitingForRoll;
+        assert_eq!(game.start_clock(1_000), Some(1_000 + 30 * 60));
+        assert!(!game.end_if_time_is_up(1_000 + 30 * 60 - 1));
+        assert!(game.end_if_time_is_up(1_000 + 30 * 60));
+        assert_eq!(game.game_state, GameState::GameOver);
+        assert_eq!(game.winner_id, Some("1".to_string())); // nobody has any points, so the tie goes to the first player
+    }
+
+    #[test]
+    fn test_auto_end_turn() {
+        let mut game = create_game();
+        test_add_players(&mut game);
+        let options = GameOptions {
+            auto_end_turn_seconds: Some(1),
+            ..GameOptions::default()
+        };
+        assert!(game.set_options("1", &options).is_err());
+        let options = GameOptions {
+            auto_end_turn_seconds: Some(10),
+            ..GameOptions::default()
+        };
+        game = game.set_options("1", &options).unwrap();
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.current_player_id = "1".to_string();
+
+        // not before the roll
+        game.game_state = GameState::WaitingForRoll;
+        assert_eq!(game.auto_end_turn_after(), None);
+
+        // not while the player can still build, or has a trade on the table
+        game.game_state = GameState::BuyingAndTrading;
+        game.players.get_mut("1").unwrap().resources = ResourceCards::new(1, 1, 0, 0, 0);
+        assert_eq!(game.auto_end_turn_after(), None);
+        let data = TradeOfferData {
+            to_id: None,
+            give: ResourceCards::new(1, 0, 0, 0, 0),
+            want: ResourceCards::new(0, 0, 0, 0, 1),
+            expires_in_seconds: Some(30),
+        };
+        game.players.get_mut("1").unwrap().resources = ResourceCards::new(1, 0, 0, 0, 0);
+        let (with_offer, _) = game.offer_trade("1", &data, 100).unwrap();
+        assert_eq!(with_offer.auto_end_turn_after(), None);
+
+        // nothing left to do but end the turn
+        assert_eq!(game.auto_end_turn_after(), Some(10));
+        game.options.auto_end_turn_seconds = None;
+        assert_eq!(game.auto_end_turn_after(), None);
+    }
+
+    #[test]
+    fn test_forfeit() {
+        println!("test_forfeit");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        assert!(game.forfeit("2").is_err()); // the game hasn't started
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        game.players.get_mut("2").unwrap().resources = ResourceCards::new(1, 2, 0, 0, 0);
+        game.players.get_mut("2").unwrap().dev_cards = vec![DevCardType::Knight];
+        game.bank.wood -= 1;
+        game.bank.brick -= 2;
+
+        // not their turn: their cards go back to the bank and the turn stays where it is
+        let forfeited = game.forfeit("2").unwrap();
+        assert!(forfeited.has_forfeited("2"));
+        assert_eq!(forfeited.player_order, vec!["1".to_string(), "3".to_string()]);
+        assert_eq!(forfeited.players["2"].resources, ResourceCards::default());
+        assert!(forfeited.players["2"].dev_cards.is_empty());
+        assert_eq!(forfeited.bank, RegularGame::full_bank());
+        assert_eq!(forfeited.current_player_id, "1");
+        assert_eq!(forfeited.game_state, GameState::BuyingAndTrading);
+        assert!(forfeited.forfeit("2").is_err());
+        assert!(forfeited.invariant_violations().is_empty());
+
+        // their cards can be dealt out instead, starting with the player after them
+        let mut dealt = game.clone();
+        dealt.options.removal_policy = RemovalPolicy::Redistribute;
+        let dealt = dealt.forfeit("2").unwrap();
+        assert_eq!(dealt.players["3"].resources, ResourceCards::new(1, 1, 0, 0, 0));
+        assert_eq!(dealt.players["1"].resources, ResourceCards::new(0, 1, 0, 0, 0));
+        assert_eq!(dealt.players["2"].resources, ResourceCards::default());
+        assert!(dealt.ledger.iter().all(|entry| entry.reason == LedgerReason::Forfeit));
+        assert!(dealt.invariant_violations().is_empty());
+
+        // or frozen: kept, but nobody can take them
+        let mut frozen = game.clone();
+        frozen.options.removal_policy = RemovalPolicy::Freeze;
+        let frozen = frozen.forfeit("2").unwrap();
+        assert_eq!(frozen.players["2"].resources, ResourceCards::new(1, 2, 0, 0, 0));
+        assert_eq!(frozen.players["2"].dev_cards, vec![DevCardType::Knight]);
+        assert!(frozen.invariant_violations().is_empty());
+        let mut thawed = frozen.clone();
+        thawed.options.removal_policy = RemovalPolicy::ReturnToBank;
+        assert_eq!(thawed.invariant_violations().len(), 1, "a forfeited player holding cards");
+
+        // an award held by the player who left goes to nobody unless somebody else has earned it
+        let mut holder = game.clone();
+        holder.largest_army_holder = Some("2".to_string());
+        let released = holder.forfeit("2").unwrap();
+        assert_eq!(released.largest_army_holder, None);
+
+        // their turn: it passes to the next player, who rolls
+        let passed = forfeited.forfeit("1").unwrap();
+        assert_eq!(passed.game_state, GameState::GameOver); // only 3 is left
+        assert_eq!(passed.winner_id, Some("3".to_string()));
+
+        let on_turn = game.forfeit("1").unwrap();
+        assert_eq!(on_turn.current_player_id, "2");
+        assert_eq!(on_turn.game_state, GameState::WaitingForRoll);
+        let mut last = game.clone();
+        last.current_player_id = "3".to_string();
+        let wrapped = last.forfeit("3").unwrap();
+        assert_eq!(wrapped.current_player_id, "1");
+        assert_eq!(wrapped.rounds_played, game.rounds_played + 1);
+
+        // the last player to discard after a 7 leaves: the baron moves
+        game.game_state = GameState::MustDiscard;
+        game.pending_discards = HashMap::from([("2".to_string(), 1)]);
+        let discarded = game.forfeit("2").unwrap();
+        assert_eq!(discarded.game_state, GameState::MustMoveBaron);
+        assert!(discarded.pending_discards.is_empty());
+    }
+
+    #[test]
+    fn test_trade_offers() {
+        println!("test_trade_offers");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        game.players.get_mut("1").unwrap().resources = ResourceCards::new(2, 0, 0, 0, 0);
+        game.players.get_mut("2").unwrap().resources = ResourceCards::new(0, 1, 1, 0, 0);
+        game.players.get_mut("3").unwrap().resources = ResourceCards::new(0, 0, 0, 1, 0);
+        assert!(game.valid_actions(false).contains(&GameAction::Trade));
+
+        // "1" offers everybody a wood for a brick
+        let data = TradeOfferData {
+            to_id: None,
+            give: ResourceCards::new(1, 0, 0, 0, 0),
+            want: ResourceCards::new(0, 1, 0, 0, 0),
+            expires_in_seconds: Some(30),
+        };
+        let too_much = TradeOfferData {
+            give: ResourceCards::new(3, 0, 0, 0, 0),
+            ..data.clone()
+        };
+        assert!(game.offer_trade("1", &too_much, 100).is_err());
+        let (with_offer, offer) = game.offer_trade("1", &data, 100).expect("offer should work");
+        assert_eq!(offer.expires_at, 130);
+        assert_eq!(with_offer.open_trade_offers(100).len(), 1);
+
+        // "3" doesn't have a brick, and the offer can't be taken once it has expired
+        assert!(with_offer.accept_trade("3", &offer.offer_id, 100).is_err());
+        assert!(with_offer.accept_trade("2", &offer.offer_id, 130).is_err());
+        assert!(with_offer.accept_trade("1", &offer.offer_id, 100).is_err());
+
+        let traded = with_offer
+            .accept_trade("2", &offer.offer_id, 100)
+            .expect("accept should work");
+        assert_eq!(traded.players["1"].resources, ResourceCards::new(1, 1, 0, 0, 0));
+        assert_eq!(traded.players["2"].resources, ResourceCards::new(1, 0, 1, 0, 0));
+        assert!(traded.open_trades.is_empty());
+
+        // the offer to everybody stays open until both of the other players reject it
+        let rejected = with_offer
+            .reject_trade("3", &offer.offer_id, 100)
+            .expect("reject should work");
+        assert_eq!(rejected.open_trades.len(), 1);
+        let rejected = rejected
+            .reject_trade("2", &offer.offer_id, 100)
+            .expect("reject should work");
+        assert!(rejected.open_trades.is_empty());
+
+        // "3" counters with wheat for wood.  the counter goes back to "1", and the original stays open for "2"
+        let counter_data = TradeOfferData {
+            to_id: None,
+            give: ResourceCards::new(0, 0, 0, 1, 0),
+            want: ResourceCards::new(1, 0, 0, 0, 0),
+            expires_in_seconds: None,
+        };
+        let (countered, counter) = with_offer
+            .counter_trade("3", &offer.offer_id, &counter_data, 100)
+            .expect("counter should work");
+        assert_eq!(counter.to_id, Some("1".to_string()));
+        assert_eq!(counter.counter_to, Some(offer.offer_id.clone()));
+        assert_eq!(countered.open_trades.len(), 2);
+        assert!(countered.accept_trade("2", &counter.offer_id, 100).is_err());
+        let traded = countered
+            .accept_trade("1", &counter.offer_id, 100)
+            .expect("accept should work");
+        assert_eq!(traded.players["1"].resources, ResourceCards::new(1, 0, 0, 1, 0));
+        assert_eq!(traded.players["3"].resources, ResourceCards::new(1, 0, 0, 0, 0));
+        assert!(traded.open_trades.is_empty());
+
+        // offers don't outlive the turn
+        let next_turn = with_offer.set_next_state().expect("set_next_state shouldn't fail");
+        assert!(next_turn.open_trades.is_empty());
+        assert!(next_turn.offer_trade("1", &data, 100).is_err());
+    }
+
+    #[test]
+    fn test_bank_and_harbor_trades() {
+        println!("test_bank_and_harbor_trades");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        game.players.get_mut("1").unwrap().resources = ResourceCards::new(0, 0, 3, 0, 6);
+
+        let mut data = BankTradeData {
+            give: ResourceType::Ore,
+            want: ResourceType::Wheat,
+            count: 1,
+            harbor_key: None,
+        };
+        assert!(game.bank_trade("2", &data).is_err());
+        let traded = game.bank_trade("1", &data).expect("a 4:1 trade should work");
+        assert_eq!(traded.players["1"].resources, ResourceCards::new(0, 0, 3, 1, 2));
+        assert_eq!(traded.players["1"].resource_count.acquired(), 1);
+        assert_eq!(traded.players["1"].resource_count.lost(), 4);
+        data.count = 2;
+        assert!(game.bank_trade("1", &data).is_err());
+        // a count that overflows is refused, not a panic
+        data.count = u32::MAX;
+        assert!(matches!(game.bank_trade("1", &data), Err(GameError::BadActionData(_))));
+        data.count = 2;
+
+        // the ore harbor only counts once "1" has built next to it
+        let ore_harbor = HarborKey::new(TileKey::new(2, -1, -1), Direction::SouthEast);
+        data.harbor_key = Some(ore_harbor);
+        assert!(game.bank_trade("1", &data).is_err());
+        let corners = road_corners(&RoadKey::new(Direction::SouthEast, TileKey::new(2, -1, -1)));
+        let on_harbor = game
+            .buildings
+            .values()
+            .find(|building| corners.contains(&building_corner(&building.building_key)))
+            .expect("the harbor should touch the board")
+            .building_key;
+        game.place_building("1", &on_harbor, BuildingState::Settlement)
+            .expect("place_building should work");
+        let traded = game.bank_trade("1", &data).expect("a 2:1 trade should work");
+        assert_eq!(traded.players["1"].resources, ResourceCards::new(0, 0, 3, 2, 2));
+
+        // a 2:1 harbor doesn't help with any other resource
+        data.give = ResourceType::Sheep;
+        data.count = 1;
+        assert!(game.bank_trade("1", &data).is_err());
+        data.give = ResourceType::Desert;
+        data.harbor_key = None;
+        assert!(game.bank_trade("1", &data).is_err());
+
+        // the best-rate trade finds the harbor on its own, and falls back to 4:1 for anything else
+        assert_eq!(
+            game.best_bank_trade_ratio("1", ResourceType::Ore),
+            (RESOURCE_HARBOR_RATIO, Some(ore_harbor))
+        );
+        assert_eq!(
+            game.best_bank_trade_ratio("1", ResourceType::Sheep),
+            (BANK_TRADE_RATIO, None)
+        );
+        let mut best = BestBankTradeData {
+            give: ResourceType::Ore,
+            want: ResourceType::Brick,
+            count: 3,
+        };
+        let traded = game.best_bank_trade("1", &best).expect("3 brick for 6 ore at 2:1");
+        assert_eq!(traded.players["1"].resources, ResourceCards::new(0, 3, 3, 0, 0));
+        best.count = 4;
+        let error = game.best_bank_trade("1", &best).expect_err("8 ore is too many");
+        assert!(format!("{:?}", error).contains("2:1"));
+        best.count = u32::MAX;
+        assert!(matches!(game.best_bank_trade("1", &best), Err(GameError::BadActionData(_))));
+    }
+
+    #[test]
+    fn test_trade_offer_overflow() {
+        println!("test_trade_offer_overflow");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        game.players.get_mut("1").unwrap().resources = ResourceCards::new(1, 0, 0, 0, 0);
+
+        // counts that add up past u32::MAX and lifetimes that are too long are refused, not a panic
+        let mut data = TradeOfferData {
+            to_id: None,
+            give: ResourceCards::new(1, 0, 0, 0, 0),
+            want: ResourceCards::new(0, 0, 0, u32::MAX, 1),
+            expires_in_seconds: None,
+        };
+        assert!(matches!(game.offer_trade("1", &data, 100), Err(GameError::BadActionData(_))));
+        data.want = ResourceCards::new(0, 0, 0, 0, 1);
+        data.expires_in_seconds = Some(u64::MAX);
+        assert!(matches!(game.offer_trade("1", &data, 100), Err(GameError::BadActionData(_))));
+        data.expires_in_seconds = Some(MAX_TRADE_OFFER_SECONDS + 1);
+        assert!(game.offer_trade("1", &data, 100).is_err());
+        data.expires_in_seconds = Some(MAX_TRADE_OFFER_SECONDS);
+        let (with_offer, offer) = game.offer_trade("1", &data, 100).expect("the longest offer should work");
+        assert_eq!(offer.expires_at, 100 + MAX_TRADE_OFFER_SECONDS);
+
+        // a counter offer is checked the same way
+        let counter_data = TradeOfferData {
+            to_id: None,
+            give: ResourceCards::new(0, 0, u32::MAX, 1, 0),
+            want: ResourceCards::new(1, 0, 0, 0, 0),
+            expires_in_seconds: None,
+        };
+        assert!(matches!(
+            with_offer.counter_trade("2", &offer.offer_id, &counter_data, 100),
+            Err(GameError::BadActionData(_))
+        ));
+    }
+
+    #[test]
+    fn test_setup_placement() {
+        println!("test_setup_placement");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::AllocateResourceForward;
+        let center = TileKey::new(0, 0, 0);
+        let top_right = BuildingKey::new(BuildingPosition::TopRight, center);
+
+        assert!(game.set_next_state().is_err());
+        assert!(game
+            .build("1", &BuildData::Road(RoadKey::new(Direction::North, center)))
+            .is_err());
+        assert!(game.build("2", &BuildData::Settlement(top_right)).is_err());
+        assert!(game.build("1", &BuildData::City(top_right)).is_err());
+        game = game
+            .build("1", &BuildData::Settlement(top_right))
+            .expect("the first settlement can go anywhere");
+        assert!(game
+            .build("1", &BuildData::Settlement(BuildingKey::new(BuildingPosition::Left, center)))
+            .is_err());
+        // the road has to touch the settlement
+        assert!(game
+            .build("1", &BuildData::Road(RoadKey::new(Direction::South, center)))
+            .is_err());
+        game = game
+            .build("1", &BuildData::Road(RoadKey::new(Direction::North, center)))
+            .expect("the road touches the settlement");
+        verify_state_and_actions(
+            &game,
+            "test_setup_placement",
+            GameState::AllocateResourceForward,
+            vec![GameAction::Next],
+        );
+        assert_eq!(game.players["1"].resources.total(), 0);
+
+        game = game.set_next_state().expect("set_next_state shouldn't fail");
+        assert_eq!(game.current_player_id, "2");
+        // the corner is taken, and TopLeft is one road away from it
+        let rule = |result: Result<RegularGame, GameError>| {
+            PlacementViolation::from_error(&result.expect_err("the settlement should be rejected"))
+                .map(|violation| violation.rule)
+        };
+        assert_eq!(
+            rule(game.build("2", &BuildData::Settlement(top_right))),
+            Some(PlacementRule::Unoccupied)
+        );
+        assert_eq!(
+            rule(game.build(
+                "2",
+                &BuildData::Settlement(BuildingKey::new(BuildingPosition::TopLeft, center))
+            )),
+            Some(PlacementRule::DistanceRule)
+        );
+        game = place_starting_pieces(&game, "2");
+        game = game.set_next_state().expect("set_next_state shouldn't fail");
+        game = place_starting_pieces(&game, "3");
+
+        // snake order: "3" goes again, and the second settlement pays out
+        game = game.set_next_state().expect("set_next_state shouldn't fail");
+        assert_eq!(game.current_state(), GameState::AllocateResourceReverse);
+        assert_eq!(game.current_player_id, "3");
+        game = place_starting_pieces(&game, "3");
+        let corner = building_corner(&game.setup_settlement.expect("a settlement was placed"));
+        let producing = game
+            .buildings
+            .values()
+            .filter(|building| building_corner(&building.building_key) == corner)
+            .filter(|building| {
+                game.tiles[&building.building_key.tile_key]
+                    .current_resource
+                    .produces()
+                    .is_some()
+            })
+            .count();
+        assert_eq!(game.players["3"].resources.total() as usize, producing);
+
+        for user_id in ["2", "1"] {
+            game = game.set_next_state().expect("set_next_state shouldn't fail");
+            assert_eq!(game.current_player_id, user_id);
+            game = place_starting_pieces(&game, user_id);
+        }
+        game = game.set_next_state().expect("set_next_state shouldn't fail");
+        assert_eq!(game.current_state(), GameState::WaitingForRoll);
+        assert_eq!(game.current_player_id, "1");
+        for user_id in ["1", "2", "3"] {
+            assert_eq!(game.players[user_id].buildings.len(), 2);
+            assert_eq!(game.players[user_id].roads.len(), 2);
+            assert_eq!(game.known_score(user_id), 2);
+        }
+    }
+
+    /// place a settlement on the first legal corner, and a road next to it
+    fn place_starting_pieces(game: &RegularGame, user_id: &str) -> RegularGame {
+        let mut keys: Vec<BuildingKey> = game.buildings.keys().cloned().collect();
+        keys.sort_by_key(|key| format!("{:?}", key));
+        let settlement = keys
+            .into_iter()
+            .find(|key| game.placement_validator().check_settlement(key).is_ok())
+            .expect("there should be room for another settlement");
+        let game = game
+            .build(user_id, &BuildData::Settlement(settlement))
+            .expect("the settlement obeys the distance rule");
+        let road = game
+            .roads
+            .values()
+            .map(|road| road.primary_key().clone())
+            .find(|key| road_corners(key).contains(&building_corner(&settlement)))
+            .expect("every corner has a road");
+        game.build(user_id, &BuildData::Road(road))
+            .expect("the road touches the settlement")
+    }
+
+    #[test]
+    fn test_buying() {
+        println!("test_buying");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        let center = TileKey::new(0, 0, 0);
+        let top_right = BuildingKey::new(BuildingPosition::TopRight, center);
+        game.place_building("1", &top_right, BuildingState::Settlement)
+            .expect("place_building should work");
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        assert!(!game.valid_actions(false).contains(&GameAction::Build));
+
+        let corner = building_corner(&top_right);
+        let road_key = game
+            .roads
+            .values()
+            .map(|road| road.primary_key().clone())
+            .find(|key| road_corners(key).contains(&corner))
+            .expect("the settlement should touch a road");
+        assert!(game.build("1", &BuildData::Road(road_key.clone())).is_err());
+
+        // the cards go back to the bank
+        game.take_from_bank("1", &ResourceCards::new(2, 1, 0, 2, 3), LedgerReason::Roll)
+            .unwrap();
+        assert!(game.valid_actions(false).contains(&GameAction::Build));
+        assert!(game.build("2", &BuildData::Road(road_key.clone())).is_err());
+        let bought = game
+            .build("1", &BuildData::Road(road_key))
+            .expect("a road next to the settlement should work");
+        assert_eq!(bought.players["1"].resources, ResourceCards::new(1, 0, 0, 2, 3));
+        assert_eq!(bought.players["1"].supply.roads, BuildingSupply::ROADS - 1);
+        assert_eq!(bought.bank.wood, RESOURCE_CARDS_PER_TYPE - 1);
+        assert_eq!(
+            bought.ledger.last().map(|entry| entry.reason),
+            Some(LedgerReason::Purchase)
+        );
+
+        // a settlement has to be at the end of a road, and a city has to replace a settlement
+        let left = BuildingKey::new(BuildingPosition::Left, center);
+        let violation = PlacementViolation::from_error(
+            &bought.build("1", &BuildData::Settlement(left)).unwrap_err(),
+        )
+        .expect("the error should be a PlacementViolation");
+        assert_eq!(violation.rule, PlacementRule::Connected);
+        assert!(bought.build("1", &BuildData::City(left)).is_err());
+        let bought = bought
+            .build("1", &BuildData::City(top_right))
+            .expect("upgrading the settlement should work");
+        assert_eq!(bought.players["1"].resources, ResourceCards::new(1, 0, 0, 0, 0));
+        assert_eq!(bought.players["1"].supply.cities, BuildingSupply::CITIES - 1);
+        assert_eq!(bought.players["1"].supply.settlements, BuildingSupply::SETTLEMENTS);
+        assert!(bought.invariant_violations().is_empty());
+    }
+
+    #[test]
+    fn test_turn_summary() {
+        println!("test_turn_summary");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        let top_right = BuildingKey::new(BuildingPosition::TopRight, TileKey::new(0, 0, 0));
+        game.place_building("2", &top_right, BuildingState::Settlement)
+            .expect("place_building should work");
+        game = game.set_next_state().expect("ending the turn should work");
+        assert_eq!(game.current_player_id, "2");
+        assert_eq!(game.turn_start_scores["2"], 1);
+
+        // "2" skips the roll, gets some cards, buys a road and a settlement, and plays Year of Plenty
+        game.game_state = GameState::BuyingAndTrading;
+        game.take_from_bank("2", &ResourceCards::new(2, 2, 1, 1, 0), LedgerReason::Roll)
+            .unwrap();
+        let corner = building_corner(&top_right);
+        let road_key = game
+            .roads
+            .values()
+            .map(|road| road.primary_key().clone())
+            .find(|key| road_corners(key).contains(&corner))
+            .expect("the settlement should touch a road");
+        game = game.build("2", &BuildData::Road(road_key.clone())).unwrap();
+        let far_end = road_corners(&road_key)
+            .iter()
+            .cloned()
+            .find(|end| *end != corner)
+            .unwrap();
+        let next_road = game
+            .roads
+            .values()
+            .map(|road| road.primary_key().clone())
+            .find(|key| {
+                road_corners(key).contains(&far_end) && !road_corners(key).contains(&corner)
+            })
+            .expect("there should be a road past the first one");
+        game.place_road("2", &next_road).unwrap();
+        let settlement = game
+            .buildings
+            .values()
+            .map(|building| building.building_key)
+            .find(|key| {
+                road_corners(&next_road).contains(&building_corner(key))
+                    && building_corner(key) != far_end
+            })
+            .expect("the road should end at a corner");
+        game = game.build("2", &BuildData::Settlement(settlement)).unwrap();
+        game.players.get_mut("2").unwrap().dev_cards = vec![DevCardType::YearOfPlenty];
+        game = game
+            .play_year_of_plenty("2", [ResourceType::Ore, ResourceType::Ore])
+            .unwrap();
+
+        let summary = TurnSummary::from_game(&game);
+        assert_eq!(summary.user_id, "2");
+        assert_eq!(summary.gained["2"], ResourceCards::new(2, 2, 1, 1, 2));
+        assert_eq!(summary.spent["2"], ResourceCards::new(2, 2, 1, 1, 0));
+        assert_eq!(summary.roads_built, 1);
+        assert_eq!(summary.settlements_built, 1);
+        assert_eq!(summary.cities_built, 0);
+        assert_eq!(summary.dev_card_played, Some(DevCardType::YearOfPlenty));
+        assert_eq!(summary.score_changes, HashMap::from([("2".to_string(), 1)]));
+
+        // the next turn starts with a clean slate
+        let next = game.set_next_state().expect("ending the turn should work");
+        let summary = TurnSummary::from_game(&next);
+        assert!(summary.gained.is_empty() && summary.score_changes.is_empty());
+        assert_eq!(summary.dev_card_played, None);
+    }
+
+    #[test]
+    fn test_discard_timeout() {
+        println!("test_discard_timeout");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::WaitingForRoll;
+        game.take_from_bank("2", &ResourceCards::new(3, 3, 3, 0, 0), LedgerReason::Roll)
+            .unwrap();
+        game.take_from_bank("3", &ResourceCards::new(0, 0, 2, 4, 4), LedgerReason::Roll)
+            .unwrap();
+        game = game.roll(7).expect("roll should work");
+        game.discard_deadline = Some(1000);
+        game = game
+            .discard("2", &ResourceCards::new(1, 0, 0, 0, 0))
+            .expect("a partial discard should work");
+
+        // nothing happens before the deadline, and after it the cards still owed go back to the bank
+        assert!(game.force_discards(999).is_err());
+        let forced = game.force_discards(1000).expect("the deadline has passed");
+        assert_eq!(forced.game_state, GameState::MustMoveBaron);
+        assert!(forced.pending_discards.is_empty());
+        assert_eq!(forced.discard_deadline, None);
+        assert_eq!(forced.players["2"].resources.total(), 5);
+        assert_eq!(forced.players["3"].resources.total(), 5);
+        assert!(forced.invariant_violations().is_empty());
+        assert!(forced.force_discards(1000).is_err());
+    }
+
+    #[test]
+    fn test_members() {
+        println!("test_members");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        let members = game.members(None);
+        assert_eq!(members.len(), 3);
+        assert!(members.iter().all(|member| member.seat.is_none() && !member.connected));
+        let creator: Vec<&str> = members
+            .iter()
+            .filter(|member| member.role == MemberRole::Creator)
+            .map(|member| member.user_id.as_str())
+            .collect();
+        assert_eq!(creator, vec!["1"]);
+
+        game.set_player_order(vec!["3".to_string(), "1".to_string(), "2".to_string()])
+            .unwrap();
+        game.current_player_id = "3".to_string();
+        let claims = Claims::new("1", "1@test.com", 60, &vec![Role::User], &None);
+        let members = game.members(Some(&claims));
+        let seats: Vec<(&str, Option<usize>)> = members
+            .iter()
+            .map(|member| (member.user_id.as_str(), member.seat))
+            .collect();
+        assert_eq!(seats, vec![("3", Some(0)), ("1", Some(1)), ("2", Some(2))]);
+        assert!(members[0].is_current_player && !members[1].is_current_player);
+        // the caller sees all of their own profile, and what a friend sees of the others
+        assert_ne!(members[1].profile.pii.as_ref().unwrap().email, "");
+        assert_eq!(members[0].profile.pii.as_ref().unwrap().email, "");
+
+        // a seat its owner has made spectator only is a spectator
+        game.local_capabilities.insert(
+            "2".to_string(),
+            LocalCapabilities {
+                spectator_only: true,
+                ..Default::default()
+            },
+        );
+        let roles: Vec<MemberRole> = game.members(None).iter().map(|member| member.role).collect();
+        assert_eq!(roles, vec![MemberRole::Player, MemberRole::Creator, MemberRole::Spectator]);
+    }
+
+    #[test]
+    fn test_bank_and_supply() {
+        println!("test_bank_and_supply");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        assert_eq!(game.bank, RegularGame::full_bank());
+
+        // the bank has 3 wood: one player owed 4 gets all 3, two players owed wood get nothing
+        game.bank.wood = 3;
+        let owed = |ids: &[&str]| -> HashMap<String, ResourceCards> {
+            ids.iter()
+                .map(|id| (id.to_string(), ResourceCards::new(2, 1, 0, 0, 0)))
+                .collect()
+        };
+        let alone = game.limit_payouts_to_bank(&HashMap::from([(
+            "1".to_string(),
+            ResourceCards::new(4, 1, 0, 0, 0),
+        )]));
+        assert_eq!(alone["1"], ResourceCards::new(3, 1, 0, 0, 0));
+        let shared = game.limit_payouts_to_bank(&owed(&["1", "2"]));
+        assert_eq!(shared["1"], ResourceCards::new(0, 1, 0, 0, 0));
+        assert_eq!(shared["2"], ResourceCards::new(0, 1, 0, 0, 0));
+        game.bank.brick = 0;
+        assert!(game.limit_payouts_to_bank(&owed(&["1", "2"])).is_empty());
+        game.bank = RegularGame::full_bank();
+
+        // a roll pays out of the bank
+        game.game_state = GameState::WaitingForRoll;
+        let tile = game
+            .tiles
+            .values()
+            .find(|tile| {
+                tile.tile_key != game.baron_tile
+                    && tile.roll != 7
+                    && tile.current_resource.produces().is_some()
+            })
+            .expect("there should be a producing tile")
+            .clone();
+        let resource = tile.current_resource.produces().unwrap();
+        game.place_building(
+            "2",
+            &BuildingKey::new(BuildingPosition::Left, tile.tile_key),
+            BuildingState::Settlement,
+        )
+        .expect("place_building should work");
+        game = game.roll(tile.roll).expect("roll should work");
+        let paid = game.players["2"].resources.count(resource);
+        assert!(paid >= 1);
+        assert_eq!(game.bank.count(resource) + paid, RESOURCE_CARDS_PER_TYPE);
+        assert!(game.invariant_violations().is_empty());
+
+        // upgrading to a city puts the settlement back in the supply
+        assert_eq!(game.players["2"].supply.settlements, BuildingSupply::SETTLEMENTS - 1);
+        game.place_building(
+            "2",
+            &BuildingKey::new(BuildingPosition::Left, tile.tile_key),
+            BuildingState::City,
+        )
+        .expect("place_building should work");
+        let supply = &game.players["2"].supply;
+        assert_eq!(supply.settlements, BuildingSupply::SETTLEMENTS);
+        assert_eq!(supply.cities, BuildingSupply::CITIES - 1);
+
+        // once the supply is empty, nothing more can be built
+        game.players.get_mut("3").unwrap().supply.roads = 0;
+        assert!(game
+            .place_road("3", &RoadKey::new(Direction::North, TileKey::new(0, 0, 0)))
+            .is_err());
+        game.players.get_mut("3").unwrap().supply.settlements = 0;
+        assert!(game
+            .place_building(
+                "3",
+                &BuildingKey::new(BuildingPosition::TopLeft, TileKey::new(0, 0, 0)),
+                BuildingState::Settlement
+            )
+            .is_err());
+
+        // the bank is part of every GameUpdate
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(json.contains("\"Bank\":{"));
+        assert!(json.contains("\"Supply\":{"));
+    }
+
+    #[test]
+    fn test_monopoly_and_year_of_plenty() {
+        println!("test_monopoly_and_year_of_plenty");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        for (id, ore) in [("1", 1), ("2", 3), ("3", 2)] {
+            game.take_from_bank(id, &ResourceCards::new(1, 0, 0, 0, ore), LedgerReason::Roll)
+                .expect("the bank has the cards");
+        }
+        game.players.get_mut("1").unwrap().dev_cards =
+            vec![DevCardType::Monopoly, DevCardType::YearOfPlenty];
+        assert!(game.valid_actions(false).contains(&GameAction::PlayDevCard));
+
+        assert!(game.play_dev_card("2", DevCardType::Monopoly).is_err(), "not 2's turn");
+        assert!(game.play_dev_card("1", DevCardType::Knight).is_err());
+        game = game
+            .play_dev_card("1", DevCardType::Monopoly)
+            .expect("1 has a Monopoly card");
+        assert_eq!(game.players["1"].dev_cards, vec![DevCardType::YearOfPlenty]);
+        // nothing else happens until the card is resolved, and only by the player who played it
+        assert_eq!(game.valid_actions(false), vec![GameAction::ResolveDevCard]);
+        assert!(game.set_next_state().is_err());
+        let ore = DevCardResolutionData {
+            resources: vec![ResourceType::Ore],
+        };
+        assert!(game.resolve_dev_card("2", &ore).is_err());
+        assert!(game
+            .resolve_dev_card(
+                "1",
+                &DevCardResolutionData {
+                    resources: vec![ResourceType::Desert]
+                }
+            )
+            .is_err());
+
+        let ledger_len = game.ledger.len();
+        game = game.resolve_dev_card("1", &ore).expect("resolve should work");
+        assert_eq!(game.pending_dev_card, None);
+        assert_eq!(game.players["1"].resources, ResourceCards::new(1, 0, 0, 0, 6));
+        assert_eq!(game.players["2"].resources, ResourceCards::new(1, 0, 0, 0, 0));
+        assert_eq!(game.players["3"].resources, ResourceCards::new(1, 0, 0, 0, 0));
+        let taken: Vec<&LedgerEntry> = game.ledger[ledger_len..].iter().collect();
+        assert_eq!(taken.len(), 2);
+        assert!(taken
+            .iter()
+            .all(|entry| entry.reason == LedgerReason::Monopoly
+                && entry.to == CardHolder::Player("1".to_owned())));
+        assert!(game.invariant_violations().is_empty());
+
+        // one card a turn
+        assert!(!game.valid_actions(false).contains(&GameAction::PlayDevCard));
+        assert!(game.play_dev_card("1", DevCardType::YearOfPlenty).is_err());
+        game = game.set_next_state().unwrap();
+        game = game.roll(2).unwrap().set_next_state().unwrap();
+        game = game.roll(2).unwrap().set_next_state().unwrap();
+        assert_eq!(game.current_player_id, "1");
+        assert!(game.ledger.iter().all(|entry| entry.reason == LedgerReason::Roll));
+
+        // year of plenty comes from the bank, and the bank has to have the cards
+        game = game
+            .play_dev_card("1", DevCardType::YearOfPlenty)
+            .expect("1 has a Year of Plenty card");
+        game.bank.brick = 1;
+        let two_brick = DevCardResolutionData {
+            resources: vec![ResourceType::Brick, ResourceType::Brick],
+        };
+        assert!(game.resolve_dev_card("1", &two_brick).is_err());
+        assert!(game.pending_dev_card.is_some(), "the player can choose again");
+        game.bank.brick = RESOURCE_CARDS_PER_TYPE;
+        let before = game.players["1"].resources.clone();
+        game = game.resolve_dev_card("1", &two_brick).expect("resolve should work");
+        assert_eq!(game.players["1"].resources.brick, before.brick + 2);
+        assert_eq!(game.bank.brick, RESOURCE_CARDS_PER_TYPE - 2);
+    }
+
+    #[test]
+    fn test_one_step_dev_cards() {
+        println!("test_one_step_dev_cards");
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::WaitingForRoll;
+        game.take_from_bank("2", &ResourceCards::new(0, 0, 2, 0, 0), LedgerReason::Roll)
+            .unwrap();
+        game.players.get_mut("1").unwrap().dev_cards =
+            vec![DevCardType::YearOfPlenty, DevCardType::Monopoly];
+
+        // the bank is out of wheat, so Year of Plenty can't take any and the card isn't used up
+        game.bank.wheat = 0;
+        assert!(game
+            .play_year_of_plenty("1", [ResourceType::Wheat, ResourceType::Ore])
+            .is_err());
+        assert_eq!(game.players["1"].dev_cards.len(), 2);
+
+        let sheep = game
+            .play_monopoly("1", ResourceType::Sheep)
+            .expect("monopoly should work");
+        assert_eq!(sheep.pending_dev_card, None);
+        assert_eq!(sheep.players["1"].resources.sheep, 2);
+        assert_eq!(sheep.players["2"].resources.sheep, 0);
+        assert_eq!(sheep.players["1"].dev_cards, vec![DevCardType::YearOfPlenty]);
+
+        game.bank.wheat = RESOURCE_CARDS_PER_TYPE;
+        let plenty = game
+            .play_year_of_plenty("1", [ResourceType::Wheat, ResourceType::Wheat])
+            .expect("year of plenty should work");
+        assert_eq!(plenty.players["1"].resources.wheat, 2);
+        assert_eq!(plenty.bank.wheat, RESOURCE_CARDS_PER_TYPE - 2);
+    }
+
+    #[test]
+    fn test_redaction() {
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.players.get_mut("1").unwrap().dev_cards = vec![DevCardType::Monopoly];
+        game.players.get_mut("1").unwrap().victory_point_cards = 1;
+
+        let mine = game.redacted_for("1");
+        assert_eq!(mine.players["1"].dev_cards, vec![DevCardType::Monopoly]);
+        assert_eq!(mine.players["1"].victory_point_cards, 1);
+
+        let theirs = game.redacted_for("2");
+        assert_eq!(theirs.players["1"].dev_cards, vec![DevCardType::Back]);
+        assert_eq!(theirs.players["1"].victory_point_cards, 0);
+        // the other players' email and phone number aren't sent either
+        assert_eq!(theirs.players["1"].profile.pii.as_ref().unwrap().email, "");
+        assert_ne!(mine.players["1"].profile.pii.as_ref().unwrap().email, "");
+
+        game.game_state = GameState::GameOver;
+        assert_eq!(game.redacted_for("2").players["1"].victory_point_cards, 1);
+    }
+
+    #[test]
+    fn test_invariant_violations() {
+        let mut game = create_game();
+        test_add_players(&mut game);
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::BuyingAndTrading;
+        game.players.get_mut("1").unwrap().resources = ResourceCards::new(10, 0, 0, 0, 0);
+        game.players.get_mut("2").unwrap().resources = ResourceCards::new(9, 0, 0, 0, 0);
+        game.bank.wood = 0;
+        assert!(game.invariant_violations().is_empty(), "19 wood is the whole supply");
+
+        let mut broken = game.clone();
+        broken.players.get_mut("3").unwrap().resources = ResourceCards::new(1, 0, 0, 0, 0);
+        assert_eq!(broken.invariant_violations().len(), 1, "a 20th wood card");
+
+        let mut broken = game.clone();
+        broken.baron_tile = TileKey::new(10, -10, 0);
+        broken.pending_discards.insert("3".to_owned(), 4);
+        broken.largest_army_holder = Some("2".to_owned());
+        broken.current_player_id = "somebody else".to_owned();
+        // the discard is owed by a player with no cards, and in the wrong state
+        assert_eq!(broken.invariant_violations().len(), 5);
+    }
+
+    #[test]
+    fn test_seats_with_identical_display_names() {
+        println!("test_seats_with_identical_display_names");
+        let mut game = create_game();
+        let mut first = UserProfile::new_test_user(Some("2".to_string()));
+        let mut second = UserProfile::new_test_user(Some("3".to_string()));
+        first.display_name = "Joe".to_string();
+        second.display_name = "Joe".to_string();
+        game.players.get_mut("1").unwrap().profile.display_name = "Joe".to_string();
+
+        game = game.add_user(&first).expect("add_user should work");
+        game = game.add_user(&second).expect("add_user should work");
+
+        let seats: Vec<usize> = ["1", "2", "3"]
+            .iter()
+            .map(|id| game.players.get(*id).unwrap().seat_index)
+            .collect();
+        assert_eq!(seats, vec![0, 1, 2]);
+
+        let colors: Vec<String> = game
+            .players
+            .values()
+            .map(|p| p.presentation.background_color.clone())
+            .collect();
+        assert!(colors.iter().all(|c| colors.iter().filter(|o| *o == c).count() == 1));
+
+        // the seat is stable: setting the order or round tripping through json doesn't move anybody
+        game.set_player_order(vec!["3".to_string(), "1".to_string(), "2".to_string()])
+            .unwrap();
+        let json = serde_json::to_string(&game).unwrap();
+        let de_game: RegularGame = serde_json::from_str(&json).unwrap();
+        for (id, player) in de_game.players.iter() {
+            assert_eq!(player.seat_index, game.players.get(id).unwrap().seat_index);
+            assert_eq!(player.presentation, game.players.get(id).unwrap().presentation);
+        }
+
+        // a new player gets the first open seat
+        game.players.remove("2");
+        let fourth = UserProfile::new_test_user(Some("4".to_string()));
+        game = game.add_user(&fourth).expect("add_user should work");
+        assert_eq!(game.players.get("4").unwrap().seat_index, 1);
+
+        // and once the game has started nobody else can join
+        game.game_state = GameState::WaitingForRoll;
+        let late = UserProfile::new_test_user(Some("5".to_string()));
+        let refused = game.add_user(&late).expect_err("the game has started");
+        assert_eq!(refused.status, reqwest::StatusCode::CONFLICT);
+    }
+
     fn verify_state_and_actions(
         game: &RegularGame,
         name: &str,
@@ -62,6 +1338,51 @@ mod tests {
             "Not all expected actions are present"
         );
     }
+
+    #[test]
+    fn test_custom_board() {
+        let creator = UserProfile::new_test_user(Some("1".to_string()));
+        let regular_board = || CustomBoardData {
+            tile_resources: REGULAR_GAME_INFO.tile_resources.clone(),
+            rolls: REGULAR_GAME_INFO.rolls.clone(),
+            rows_per_column: REGULAR_GAME_INFO.rows_per_column.clone(),
+            harbors: REGULAR_GAME_INFO.harbor_data.clone(),
+            no_adjacent_six_eight: false,
+        };
+
+        let game = RegularGame::new_custom(&creator, &regular_board())
+            .expect("the regular board is a valid custom board");
+        assert_eq!(game.tiles.len(), 19);
+        assert_eq!(game.game_info().name, "Custom");
+        assert_eq!(game.tiles[&game.baron_tile].current_resource, TileResource::Desert);
+        test_desert(&game);
+
+        let is_rejected = |data: &CustomBoardData| match RegularGame::new_custom(&creator, data) {
+            Err(GameError::BadActionData(problems)) => !problems.is_empty(),
+            _ => false,
+        };
+
+        let mut data = regular_board();
+        data.rows_per_column = vec![3, 4, 5, 4];
+        assert!(is_rejected(&data), "not a hexagon");
+
+        let mut data = regular_board();
+        data.tile_resources[1] = TileResource::Desert;
+        assert!(is_rejected(&data), "two deserts");
+
+        let mut data = regular_board();
+        data.harbors[0] = Harbor::new(
+            HarborKey::new(TileKey::new(0, 0, 0), Direction::North),
+            data.harbors[0].harbor_type.clone(),
+        );
+        assert!(is_rejected(&data), "a harbor in the middle of the board");
+
+        // the unshuffled rolls put the two 6s next to each other in the middle column
+        let mut data = regular_board();
+        data.no_adjacent_six_eight = true;
+        assert!(is_rejected(&data), "adjacent 6s");
+    }
+
     #[test]
     fn test_tile_key_serialization() {
         println!("test_tile_key_serialization");
@@ -187,6 +1508,84 @@ mod tests {
         assert_eq!(game.current_player_id, "3");
     }
 
+    #[test]
+    fn test_remove_player() {
+        let game = create_game();
+        let kicked = UserProfile::new_test_user(Some("2".to_string()));
+        let game = RegularGame::add_user(&game, &kicked).unwrap();
+
+        let error = game.remove_player("1", false).expect_err("the creator stays");
+        assert!(matches!(error, GameError::BadActionData(_)));
+        assert!(matches!(game.remove_player("9", false), Err(GameError::BadId(_))));
+
+        // without a ban they can come back
+        let removed = game.remove_player("2", false).unwrap();
+        assert!(!removed.players.contains_key("2"));
+        assert!(RegularGame::add_user(&removed, &kicked).is_ok());
+
+        let banned = game.remove_player("2", true).unwrap();
+        assert_eq!(banned.banned, vec!["2".to_string()]);
+        let sr = RegularGame::add_user(&banned, &kicked).expect_err("banned");
+        assert_eq!(sr.status, reqwest::StatusCode::FORBIDDEN);
+
+        let mut started = game.clone();
+        started.game_state = GameState::WaitingForRoll;
+        assert!(matches!(started.remove_player("2", false), Err(GameError::ActionError(_))));
+    }
+
+    #[test]
+    fn test_delegation() {
+        let mut game = create_game();
+        test_add_players(&mut game);
+        let to_3 = Delegate::Player("3".to_string());
+        assert!(game.delegate("2", &to_3).is_err()); // the game hasn't started
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::WaitingForRoll;
+
+        let delegated = game.delegate("2", &to_3).unwrap();
+        assert!(delegated.is_delegate_for("2", "3"));
+        assert!(!delegated.is_delegate_for("2", "1"));
+        let to_self = delegated.delegate("2", &Delegate::Player("2".to_string()));
+        assert!(matches!(to_self, Err(GameError::BadActionData(_))));
+        let to_stranger = delegated.delegate("1", &Delegate::Player("9".to_string()));
+        assert!(matches!(to_stranger, Err(GameError::BadId(_))));
+        // no chains: the delegate can't hand over their own turns, and nobody can hand theirs to a player who has
+        assert!(delegated.delegate("3", &Delegate::Player("1".to_string())).is_err());
+        assert!(delegated.delegate("1", &Delegate::Player("2".to_string())).is_err());
+
+        // a bot can play the seat instead, and the player can take it back at any time
+        let to_bot = delegated.delegate("2", &Delegate::Bot(BotDifficulty::Easy)).unwrap();
+        assert_eq!(to_bot.delegate_of("2"), Some(&Delegate::Bot(BotDifficulty::Easy)));
+        let revoked = to_bot.revoke_delegation("2").unwrap();
+        assert_eq!(revoked.delegate_of("2"), None);
+        assert!(revoked.revoke_delegation("2").is_err());
+
+        // a delegate who forfeits gives the seat back
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        let delegated = game.delegate("2", &to_3).unwrap();
+        assert!(delegated.forfeit("3").unwrap().delegations.is_empty());
+    }
+
+    #[test]
+    fn test_language() {
+        let mut game = create_game();
+        test_add_players(&mut game);
+        assert_eq!(game.language_for("2"), Language::English);
+        assert!(game.set_language("2", Language::Spanish).is_err()); // only the creator
+        let game = game.set_language("1", Language::Spanish).unwrap();
+        assert_eq!(game.language_for("2"), Language::Spanish);
+
+        // a player can read it in their own language, and go back to the game's
+        let game = game.set_language_override("2", Some(Language::German)).unwrap();
+        assert_eq!(game.language_for("2"), Language::German);
+        assert_eq!(game.language_for("3"), Language::Spanish);
+        assert!(game.set_language_override("9", Some(Language::French)).is_err());
+        let game = game.set_language_override("2", None).unwrap();
+        assert_eq!(game.language_for("2"), Language::Spanish);
+    }
+
     fn create_game() -> RegularGame {
         println!("create_game");
         let user = UserProfile::new_test_user(Some("1".to_string()));
@@ -223,7 +1622,8 @@ mod tests {
         test_rolls_and_resources(game);
     }
     fn test_allocate_resources(game: &mut RegularGame) {
-        let expected_actions = vec![GameAction::Next, GameAction::Build];
+        // nobody can move on until they've placed their settlement and road
+        let expected_actions = vec![GameAction::Build];
         verify_state_and_actions(
             game,
             "test_allocate_resources",
@@ -0,0 +1,397 @@
+#![allow(dead_code)]
+use crate::{
+    games_service::{
+        buildings::building_enums::BuildingState,
+        harbors::harbor_key::HarborKey,
+        roads::{
+            longest_road::{building_corner, road_corners},
+            road_key::RoadKey,
+        },
+        shared::{
+            game_enums::{GameState, ResourceType},
+            game_models::{
+                BankTradeData, BestBankTradeData, CardHolder, LedgerReason, ResourceCards,
+                TradeOffer, TradeOfferData,
+            },
+        },
+    },
+    shared::{service_models::PersistUser, shared_models::GameError},
+};
+
+use super::regular_game::RegularGame;
+
+/// how long an offer stays open if the player making it doesn't say
+pub const DEFAULT_TRADE_OFFER_SECONDS: u64 = 60;
+/// the longest an offer can stay open
+pub const MAX_TRADE_OFFER_SECONDS: u64 = 10 * 60;
+/// cards given to the bank for each card taken, without a harbor
+pub const BANK_TRADE_RATIO: u32 = 4;
+/// cards given for each card taken at a 3:1 harbor
+pub const GENERIC_HARBOR_RATIO: u32 = 3;
+/// cards given for each card taken at a harbor for the resource being given
+pub const RESOURCE_HARBOR_RATIO: u32 = 2;
+
+impl RegularGame {
+    /// Opens a trade offer from a player.
+    ///
+    /// Every trade has to involve the current player, so an offer from anybody else goes to the current player.  The
+    /// player making the offer has to hold the cards they are giving, but the cards aren't set aside -- they are
+    /// checked again when the offer is accepted.  Offers that have expired are dropped.
+    ///
+    /// # Returns
+    ///
+    /// A clone of the game with the offer added, and the offer, or `GameError::BadActionData` if the offer can't be
+    /// made.
+    pub fn offer_trade(
+        &self,
+        from_id: &str,
+        data: &TradeOfferData,
+        now: u64,
+    ) -> Result<(Self, TradeOffer), GameError> {
+        self.verify_trading(from_id)?;
+        let (given, wanted) = match (data.give.checked_total(), data.want.checked_total()) {
+            (Some(given), Some(wanted)) => (given, wanted),
+            _ => {
+                return Err(GameError::BadActionData(
+                    "there aren't that many cards to trade".to_owned(),
+                ))
+            }
+        };
+        if given == 0 || wanted == 0 {
+            return Err(GameError::BadActionData(
+                "a trade has to give and get at least one card".to_owned(),
+            ));
+        }
+        let to_id = if from_id == self.current_player_id {
+            data.to_id.clone()
+        } else {
+            Some(self.current_player_id.clone())
+        };
+        if let Some(to_id) = to_id.as_deref() {
+            if to_id == from_id || !self.players.contains_key(to_id) || self.has_forfeited(to_id) {
+                return Err(GameError::BadId(to_id.to_owned()));
+            }
+        }
+        if data.to_id.is_some() && data.to_id != to_id {
+            return Err(GameError::BadActionData(format!(
+                "trades have to include the current player ({})",
+                self.current_player_id
+            )));
+        }
+        let lifetime = data.expires_in_seconds.unwrap_or(DEFAULT_TRADE_OFFER_SECONDS);
+        let expires_at = now
+            .checked_add(lifetime)
+            .filter(|_| lifetime <= MAX_TRADE_OFFER_SECONDS)
+            .ok_or_else(|| {
+                GameError::BadActionData(format!(
+                    "an offer can't be open for more than {} seconds",
+                    MAX_TRADE_OFFER_SECONDS
+                ))
+            })?;
+        if !self.players[from_id].resources.contains(&data.give) {
+            return Err(GameError::BadActionData(format!(
+                "{} doesn't have {:?} to trade",
+                from_id, data.give
+            )));
+        }
+
+        let offer = TradeOffer {
+            offer_id: PersistUser::new_id(),
+            from_id: from_id.to_owned(),
+            to_id,
+            give: data.give.clone(),
+            want: data.want.clone(),
+            expires_at,
+            counter_to: None,
+            rejected_by: vec![],
+        };
+        let mut clone = self.clone();
+        clone.expire_trades(now);
+        clone
+            .open_trades
+            .insert(offer.offer_id.clone(), offer.clone());
+        Ok((clone, offer))
+    }
+
+    /// Accepts an offer: both players swap the cards and the offer (and the offer it was a counter to) is closed.
+    pub fn accept_trade(&self, user_id: &str, offer_id: &str, now: u64) -> Result<Self, GameError> {
+        let offer = self.offer_for_responder(user_id, offer_id, now)?;
+        if offer.from_id != self.current_player_id && user_id != self.current_player_id {
+            return Err(GameError::BadActionData(format!(
+                "trades have to include the current player ({})",
+                self.current_player_id
+            )));
+        }
+
+        let mut clone = self.clone();
+        clone.expire_trades(now);
+        clone.move_cards(&offer.from_id, &offer.give, user_id)?;
+        clone.move_cards(user_id, &offer.want, &offer.from_id)?;
+        clone.open_trades.remove(offer_id);
+        if let Some(countered) = offer.counter_to.as_deref() {
+            clone.open_trades.remove(countered);
+        }
+        Ok(clone)
+    }
+
+    /// Turns an offer down.  An offer made to one player is closed when they reject it, and an offer made to everybody
+    /// is closed once every other player has rejected it.  The player who made an offer can reject it to withdraw it.
+    pub fn reject_trade(&self, user_id: &str, offer_id: &str, now: u64) -> Result<Self, GameError> {
+        let mut clone = self.clone();
+        clone.expire_trades(now);
+        let offer = clone
+            .open_trades
+            .get(offer_id)
+            .cloned()
+            .ok_or_else(|| GameError::BadId(offer_id.to_owned()))?;
+        if offer.from_id == user_id {
+            clone.open_trades.remove(offer_id);
+            return Ok(clone);
+        }
+        self.offer_for_responder(user_id, offer_id, now)?;
+        clone.close_for(user_id, &offer);
+        Ok(clone)
+    }
+
+    /// Answers an offer with a different one.  The counter offer goes back to the player who made the original offer,
+    /// and counts as the responder rejecting the original.
+    pub fn counter_trade(
+        &self,
+        user_id: &str,
+        offer_id: &str,
+        data: &TradeOfferData,
+        now: u64,
+    ) -> Result<(Self, TradeOffer), GameError> {
+        let original = self.offer_for_responder(user_id, offer_id, now)?;
+        let data = TradeOfferData {
+            to_id: Some(original.from_id.clone()),
+            ..data.clone()
+        };
+        let (mut clone, mut counter) = self.offer_trade(user_id, &data, now)?;
+        counter.counter_to = Some(offer_id.to_owned());
+        clone
+            .open_trades
+            .insert(counter.offer_id.clone(), counter.clone());
+        clone.close_for(user_id, &original);
+        Ok((clone, counter))
+    }
+
+    /// Trades cards with the bank for the current player.
+    ///
+    /// The player gives `ratio * count` cards of one resource and gets `count` cards of another, where the ratio comes
+    /// from bank_trade_ratio.  The trade fails if the bank doesn't have the cards.  The player's ResourceCount is
+    /// updated with the cards that changed hands.
+    pub fn bank_trade(&self, user_id: &str, data: &BankTradeData) -> Result<Self, GameError> {
+        self.verify_trading(user_id)?;
+        if self.current_player_id != user_id {
+            return Err(GameError::ActionError(format!(
+                "only the current player ({}) can trade with the bank",
+                self.current_player_id
+            )));
+        }
+        if data.count == 0 || data.give == data.want {
+            return Err(GameError::BadActionData(format!(
+                "can't trade {:?} for {} {:?}",
+                data.give, data.count, data.want
+            )));
+        }
+        let ratio = self.bank_trade_ratio(user_id, data.give, data.harbor_key.as_ref())?;
+        let given = Self::bank_trade_cost(ratio, data.count)?;
+
+        let mut give = ResourceCards::default();
+        give.add(data.give, given);
+        let mut want = ResourceCards::default();
+        want.add(data.want, data.count);
+        // ResourceCards ignores anything that isn't one of the five resource cards
+        if give.total() == 0 || want.total() == 0 {
+            return Err(GameError::BadActionData(format!(
+                "only resource cards can be traded with the bank, not {:?} for {:?}",
+                data.give, data.want
+            )));
+        }
+
+        let mut clone = self.clone();
+        clone.return_to_bank(user_id, &give, LedgerReason::BankTrade)?;
+        clone.take_from_bank(user_id, &want, LedgerReason::BankTrade)?;
+        if let Some(player) = clone.players.get_mut(user_id) {
+            player.resource_count.record(want.total(), give.total());
+        }
+        Ok(clone)
+    }
+
+    /// ratio * count, or BadActionData if a count that big overflows
+    fn bank_trade_cost(ratio: u32, count: u32) -> Result<u32, GameError> {
+        ratio
+            .checked_mul(count)
+            .ok_or_else(|| GameError::BadActionData(format!("can't trade for {} cards", count)))
+    }
+
+    /// Works out how many cards of give the player has to hand over for each card they get from the bank.
+    ///
+    /// Without a harbor it is BANK_TRADE_RATIO.  A harbor only counts if the player has a settlement or city on one of
+    /// the two corners it touches, and a 2:1 harbor only counts for its own resource.
+    pub fn bank_trade_ratio(
+        &self,
+        user_id: &str,
+        give: ResourceType,
+        harbor_key: Option<&HarborKey>,
+    ) -> Result<u32, GameError> {
+        let harbor_key = match harbor_key {
+            Some(key) => key,
+            None => return Ok(BANK_TRADE_RATIO),
+        };
+        let harbor = self
+            .harbors
+            .get(harbor_key)
+            .ok_or_else(|| GameError::BadActionData(format!("{:?} is not a harbor", harbor_key)))?;
+        let corners = road_corners(&RoadKey::new(harbor_key.position(), harbor_key.tile_key()));
+        let on_harbor = self.buildings.values().any(|building| {
+            building.owner_id.as_deref() == Some(user_id)
+                && (building.state == BuildingState::Settlement || building.state == BuildingState::City)
+                && corners.contains(&building_corner(&building.building_key))
+        });
+        if !on_harbor {
+            return Err(GameError::BadActionData(format!(
+                "{} doesn't have a settlement or city on the {:?} harbor",
+                user_id, harbor.harbor_type
+            )));
+        }
+        match harbor.harbor_type.resource() {
+            None => Ok(GENERIC_HARBOR_RATIO),
+            Some(resource) if resource == give => Ok(RESOURCE_HARBOR_RATIO),
+            Some(resource) => Err(GameError::BadActionData(format!(
+                "the {:?} harbor only takes {:?}",
+                harbor.harbor_type, resource
+            ))),
+        }
+    }
+
+    /// The best rate the player can trade give at: the lowest ratio from bank_trade_ratio over every harbor they have
+    /// built on, along with the harbor that gives it, or BANK_TRADE_RATIO and None if no harbor helps.
+    pub fn best_bank_trade_ratio(
+        &self,
+        user_id: &str,
+        give: ResourceType,
+    ) -> (u32, Option<HarborKey>) {
+        self.harbors
+            .keys()
+            .filter_map(|key| {
+                self.bank_trade_ratio(user_id, give, Some(key))
+                    .ok()
+                    .map(|ratio| (ratio, Some(*key)))
+            })
+            .chain(std::iter::once((BANK_TRADE_RATIO, None)))
+            .min_by_key(|(ratio, _)| *ratio)
+            .expect("the bank is always there")
+    }
+
+    /// Trades with the bank at the best rate the player gets (see best_bank_trade_ratio), so that the client doesn't
+    /// have to know the rules for harbors.  Fails, saying what the trade would cost, if the player can't afford it.
+    pub fn best_bank_trade(&self, user_id: &str, data: &BestBankTradeData) -> Result<Self, GameError> {
+        let (ratio, harbor_key) = self.best_bank_trade_ratio(user_id, data.give);
+        let needed = Self::bank_trade_cost(ratio, data.count)?;
+        let held = self
+            .players
+            .get(user_id)
+            .map_or(0, |player| player.resources.count(data.give));
+        if held < needed {
+            return Err(GameError::BadActionData(format!(
+                "{} {:?} costs {} {:?} at {}:1, but {} only has {}",
+                data.count, data.want, needed, data.give, ratio, user_id, held
+            )));
+        }
+        self.bank_trade(
+            user_id,
+            &BankTradeData {
+                give: data.give,
+                want: data.want,
+                count: data.count,
+                harbor_key,
+            },
+        )
+    }
+
+    /// the offers that haven't expired, oldest first
+    pub fn open_trade_offers(&self, now: u64) -> Vec<TradeOffer> {
+        let mut offers: Vec<TradeOffer> = self
+            .open_trades
+            .values()
+            .filter(|offer| offer.expires_at > now)
+            .cloned()
+            .collect();
+        offers.sort_by(|a, b| a.expires_at.cmp(&b.expires_at));
+        offers
+    }
+
+    pub fn expire_trades(&mut self, now: u64) {
+        self.open_trades.retain(|_, offer| offer.expires_at > now);
+    }
+
+    fn verify_trading(&self, user_id: &str) -> Result<(), GameError> {
+        self.verify_no_pending_dev_card()?;
+        if self.game_state != GameState::BuyingAndTrading {
+            return Err(GameError::BadActionData(format!(
+                "can't trade in the {:?} state",
+                self.game_state
+            )));
+        }
+        if !self.players.contains_key(user_id) {
+            return Err(GameError::BadId(user_id.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// the offer, if it is still open and user_id is somebody it was made to
+    fn offer_for_responder(
+        &self,
+        user_id: &str,
+        offer_id: &str,
+        now: u64,
+    ) -> Result<TradeOffer, GameError> {
+        self.verify_trading(user_id)?;
+        let offer = self
+            .open_trades
+            .get(offer_id)
+            .filter(|offer| offer.expires_at > now)
+            .ok_or_else(|| GameError::BadId(offer_id.to_owned()))?;
+        if offer.from_id == user_id {
+            return Err(GameError::BadActionData(
+                "you can't respond to your own offer".to_owned(),
+            ));
+        }
+        if offer.to_id.as_deref().map_or(false, |to_id| to_id != user_id) {
+            return Err(GameError::BadActionData(format!(
+                "offer {} wasn't made to {}",
+                offer_id, user_id
+            )));
+        }
+        Ok(offer.clone())
+    }
+
+    /// record that user_id has turned the offer down, and close it if nobody is left to take it
+    fn close_for(&mut self, user_id: &str, offer: &TradeOffer) {
+        let player_count = self.players.len();
+        if let Some(open) = self.open_trades.get_mut(&offer.offer_id) {
+            if !open.rejected_by.iter().any(|id| id == user_id) {
+                open.rejected_by.push(user_id.to_owned());
+            }
+            if open.to_id.is_some() || open.rejected_by.len() + 1 >= player_count {
+                self.open_trades.remove(&offer.offer_id);
+            }
+        }
+    }
+
+    fn move_cards(
+        &mut self,
+        from_id: &str,
+        cards: &ResourceCards,
+        to_id: &str,
+    ) -> Result<(), GameError> {
+        self.transfer(
+            &CardHolder::Player(from_id.to_owned()),
+            &CardHolder::Player(to_id.to_owned()),
+            cards,
+            LedgerReason::PlayerTrade,
+        )
+    }
+}
@@ -0,0 +1,210 @@
+#![allow(dead_code)]
+use std::collections::HashSet;
+
+use crate::{
+    games_service::{
+        buildings::building_enums::BuildingState,
+        player::player_enums::Weapon,
+        roads::longest_road::{building_corner, Corner, LONGEST_ROAD_POINTS},
+        shared::{
+            game_enums::GameState,
+            game_models::{GameOptions, WinCondition},
+        },
+    },
+    shared::shared_models::GameError,
+};
+
+use super::regular_game::RegularGame;
+
+/// the first player to reach this many victory points on their turn wins, unless the game's options say otherwise
+pub const WINNING_SCORE: u32 = 10;
+/// the fewest knights a player has to play before they can hold Largest Army
+pub const MIN_LARGEST_ARMY: usize = 3;
+/// the victory points that go with holding Largest Army
+pub const LARGEST_ARMY_POINTS: u32 = 2;
+
+impl RegularGame {
+    /// the number of knights the player has played
+    pub fn knights_played(&self, user_id: &str) -> usize {
+        self.players.get(user_id).map_or(0, |player| {
+            player
+                .targets
+                .iter()
+                .filter(|target| *target.weapon() == Weapon::Knight)
+                .count()
+        })
+    }
+
+    /// Recalculates who holds Largest Army, using the same rules as Longest Road: the holder keeps it until somebody
+    /// has played strictly more knights.  Players who have forfeited can't hold it.
+    pub fn update_largest_army(&mut self) {
+        if let Some(holder) = self.largest_army_holder.clone() {
+            if self.has_forfeited(&holder) {
+                self.largest_army_holder = None;
+            }
+        }
+        let knights: Vec<(String, usize)> = self
+            .players
+            .keys()
+            .map(|id| (id.clone(), self.knights_played(id)))
+            .collect();
+        let most = knights
+            .iter()
+            .filter(|(id, _)| !self.has_forfeited(id))
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or(0);
+        let holder_count = self
+            .largest_army_holder
+            .as_ref()
+            .map_or(0, |id| self.knights_played(id));
+
+        if most < MIN_LARGEST_ARMY {
+            self.largest_army_holder = None;
+        } else if holder_count < most {
+            let leaders: Vec<&String> = knights
+                .iter()
+                .filter(|(id, count)| *count == most && !self.has_forfeited(id))
+                .map(|(id, _)| id)
+                .collect();
+            if let [leader] = leaders.as_slice() {
+                self.largest_army_holder = Some((*leader).clone());
+            }
+        }
+
+        for (id, count) in knights {
+            let has_largest_army = self.largest_army_holder.as_deref() == Some(id.as_str());
+            if let Some(player) = self.players.get_mut(&id) {
+                player.state.set_largest_army(count as i8, has_largest_army);
+            }
+        }
+    }
+
+    /// the points everybody can see: settlements, cities, Longest Road and Largest Army
+    pub fn known_score(&self, user_id: &str) -> u32 {
+        // a building is in the map once for each tile touching its corner, so count corners, not entries
+        let mut counted: HashSet<Corner> = HashSet::new();
+        let mut score = 0;
+        for building in self.buildings.values() {
+            if building.owner_id.as_deref() != Some(user_id)
+                || !counted.insert(building_corner(&building.building_key))
+            {
+                continue;
+            }
+            score += match building.state {
+                BuildingState::Settlement => 1,
+                BuildingState::City => 2,
+                _ => 0,
+            };
+        }
+        if self.longest_road_holder.as_deref() == Some(user_id) {
+            score += LONGEST_ROAD_POINTS;
+        }
+        if self.largest_army_holder.as_deref() == Some(user_id) {
+            score += LARGEST_ARMY_POINTS;
+        }
+        score
+    }
+
+    /// the player's full score, including the victory point cards only they can see
+    pub fn victory_points(&self, user_id: &str) -> u32 {
+        self.known_score(user_id)
+            + self
+                .players
+                .get(user_id)
+                .map_or(0, |player| player.victory_point_cards)
+    }
+
+    /// Sets the victory point target and win condition.  Only the creator can, and only before the board is set up.
+    pub fn set_options(&self, user_id: &str, options: &GameOptions) -> Result<Self, GameError> {
+        if user_id != self.creator_id {
+            return Err(GameError::ActionError(
+                "only the game's creator can change its options".to_owned(),
+            ));
+        }
+        if !matches!(
+            self.game_state,
+            GameState::AddingPlayers | GameState::ChoosingBoard | GameState::SettingPlayerOrder
+        ) {
+            return Err(GameError::ActionError(
+                "the options can't be changed once the game has started".to_owned(),
+            ));
+        }
+        if let Some(problem) = options.problem() {
+            return Err(GameError::BadActionData(problem));
+        }
+        let mut clone = self.clone();
+        clone.options = *options;
+        Ok(clone)
+    }
+
+    /// Starts the clock of a timed game at the first roll.  Returns when the game ends, if it is timed.
+    pub fn start_clock(&mut self, now: u64) -> Option<u64> {
+        if let WinCondition::Timed(minutes) = self.options.win_condition {
+            if self.ends_at.is_none() {
+                self.ends_at = Some(now + minutes as u64 * 60);
+            }
+        }
+        self.ends_at
+    }
+
+    /// the player with the most victory points.  a tie goes to whoever comes first in the player order
+    pub fn leader(&self) -> Option<String> {
+        let mut leader: Option<(&String, u32)> = None;
+        for id in self.player_order.iter() {
+            let points = self.victory_points(id);
+            if leader.map_or(true, |(_, most)| points > most) {
+                leader = Some((id, points));
+            }
+        }
+        leader.map(|(id, _)| id.clone())
+    }
+
+    fn end_with_leader(&mut self) {
+        self.winner_id = self.leader();
+        self.game_state = GameState::GameOver;
+    }
+
+    /// Ends a timed game whose time is up, giving it to the leader.  The game is unchanged if it isn't timed, isn't
+    /// over yet or has already ended.
+    pub fn end_if_time_is_up(&mut self, now: u64) -> bool {
+        let time_is_up = self.ends_at.map_or(false, |ends_at| now >= ends_at);
+        if time_is_up && self.game_state != GameState::GameOver {
+            self.end_with_leader();
+            return true;
+        }
+        false
+    }
+
+    /// Recalculates Largest Army and every player's known score, then checks for a winner.
+    ///
+    /// This is run after every action that changes the game.  Only the current player can win by reaching the target,
+    /// so if a player other than the current player gets there (say, because Longest Road moved to them) they win when
+    /// their turn comes around.  When the current player has won, winner_id is set and the game moves to GameOver.  A
+    /// game that is MostPointsAfterRounds ends once its last round is played, and goes to the leader.  (Timed games
+    /// are ended by the service's clock -- see end_if_time_is_up.)
+    pub fn update_scores(&mut self) {
+        self.update_largest_army();
+        let ids: Vec<String> = self.players.keys().cloned().collect();
+        for id in ids.iter() {
+            let score = self.known_score(id);
+            if let Some(player) = self.players.get_mut(id) {
+                player.state.set_known_score(score as i8);
+            }
+        }
+
+        if self.game_state == GameState::GameOver || !self.players.contains_key(&self.current_player_id) {
+            return;
+        }
+        if self.victory_points(&self.current_player_id) >= self.options.victory_points_to_win {
+            self.winner_id = Some(self.current_player_id.clone());
+            self.game_state = GameState::GameOver;
+            return;
+        }
+        if let WinCondition::MostPointsAfterRounds(rounds) = self.options.win_condition {
+            if self.rounds_played >= rounds {
+                self.end_with_leader();
+            }
+        }
+    }
+}
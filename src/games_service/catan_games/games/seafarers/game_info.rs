@@ -0,0 +1,91 @@
+use once_cell::sync::Lazy;
+
+use crate::{
+    games_service::{
+        catan_games::games::regular::game_info::RegularGameInfo,
+        harbors::{harbor::Harbor, harbor_enums::HarborType, harbor_key::HarborKey},
+        shared::game_enums::Direction,
+        tiles::{tile_enums::TileResource, tile_key::TileKey},
+    },
+    harbor_data,
+};
+
+use TileResource::{Brick, Desert, Ore, Sea, Sheep, Wheat, Wood};
+
+/**
+ *  the Seafarers board is one ring bigger than the regular board.  the outer ring is all sea, and a channel of sea
+ *  down the middle column splits the land into two islands of seven tiles each:
+ *
+ *      column      -3   -2   -1    0    1    2    3
+ *      rows         4    5    6    7    6    5    4
+ *      land         -    3    4    -    4    3    -
+ *
+ *  the sea tiles never move and have no number.  the land tiles (and their numbers) are shuffled among the land
+ *  positions, and every harbor is on the coast of one of the islands.
+ */
+fn create_seafarers_game_info() -> RegularGameInfo {
+    RegularGameInfo {
+        name: "Seafarers".to_owned(),
+        tile_resources: vec![
+            Sea, Sea, Sea, Sea, //
+            Sea, Desert, Brick, Brick, Sea, //
+            Sea, Ore, Ore, Sheep, Sheep, Sea, //
+            Sea, Sea, Sea, Sea, Sea, Sea, Sea, //
+            Sea, Sheep, Wheat, Wheat, Wheat, Sea, //
+            Sea, Wood, Wood, Wood, Sea, //
+            Sea, Sea, Sea, Sea, //
+        ],
+        rolls: vec![
+            0, 0, 0, 0, //
+            0, 7, 2, 3, 0, //
+            0, 4, 4, 5, 6, 0, //
+            0, 0, 0, 0, 0, 0, 0, //
+            0, 8, 9, 9, 10, 0, //
+            0, 10, 11, 12, 0, //
+            0, 0, 0, 0, //
+        ],
+        rows_per_column: vec![4, 5, 6, 7, 6, 5, 4],
+        harbor_data: vec![
+            //  the west island
+            harbor_data!(
+                TileKey::new(-2, 0, 2),
+                Direction::NorthWest,
+                HarborType::Wheat
+            ),
+            harbor_data!(
+                TileKey::new(-2, 2, 0),
+                Direction::SouthWest,
+                HarborType::ThreeForOne
+            ),
+            harbor_data!(TileKey::new(-1, -1, 2), Direction::North, HarborType::Brick),
+            harbor_data!(
+                TileKey::new(-1, 0, 1),
+                Direction::NorthEast,
+                HarborType::ThreeForOne
+            ),
+            //  the east island
+            harbor_data!(TileKey::new(1, -2, 1), Direction::North, HarborType::Sheep),
+            harbor_data!(
+                TileKey::new(2, -2, 0),
+                Direction::NorthEast,
+                HarborType::ThreeForOne
+            ),
+            harbor_data!(TileKey::new(2, 0, -2), Direction::SouthEast, HarborType::Ore),
+            harbor_data!(
+                TileKey::new(1, 1, -2),
+                Direction::South,
+                HarborType::ThreeForOne
+            ),
+            harbor_data!(
+                TileKey::new(1, 0, -1),
+                Direction::SouthWest,
+                HarborType::Wood
+            ),
+        ],
+        min_players: 3,
+        max_players: 4,
+    }
+}
+
+pub static SEAFARERS_GAME_INFO: Lazy<RegularGameInfo> =
+    Lazy::new(|| create_seafarers_game_info());
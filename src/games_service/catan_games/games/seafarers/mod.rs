@@ -0,0 +1,17 @@
+/**
+ *  Seafarers is played by RegularGame -- the rules are the regular rules plus a few that only matter when the board
+ *  has sea on it, so a regular game plays exactly as it always has:
+ *
+ *  1. the board (game_info.rs) is a bigger board with sea tiles and more than one island.  sea tiles don't produce
+ *     anything, never move when the board is shuffled, and the baron can't be moved onto them
+ *  2. ships are a second kind of edge piece.  they cost a wood and a sheep, come out of their own supply, and have to
+ *     be built on an edge that touches the sea.  roads have to touch land
+ *  3. settlements have to be built on a corner that touches land
+ *
+ *  ships and roads are one network: a ship can be built off the end of a road and vice versa, and both count toward
+ *  Longest Road.
+ *
+ *  a Seafarers game is created with POST /games/Seafarers.
+ */
+pub mod game_info;
+pub mod ships;
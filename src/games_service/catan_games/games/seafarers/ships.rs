@@ -0,0 +1,227 @@
+#![allow(dead_code)]
+use strum::IntoEnumIterator;
+
+use crate::{
+    games_service::{
+        buildings::{
+            building_enums::BuildingPosition,
+            building_key::BuildingKey,
+            placement_validator::{PlacementRule, PlacementViolation},
+        },
+        catan_games::games::regular::regular_game::RegularGame,
+        roads::{longest_road::building_corner, road_enums::RoadState, road_key::RoadKey},
+        tiles::tile::Tile,
+    },
+    shared::shared_models::GameError,
+};
+
+impl RegularGame {
+    /// true if the board has any sea tiles on it -- only Seafarers boards do
+    pub fn has_sea(&self) -> bool {
+        self.tiles
+            .values()
+            .any(|tile| tile.current_resource.is_sea())
+    }
+
+    /// the tiles on either side of an edge, leaving out the side that is off the board (if there is one)
+    fn edge_tiles(&self, road_key: &RoadKey) -> Vec<&Tile> {
+        let tile_key = road_key.tile_key();
+        [tile_key, tile_key.get_neighbor_key(road_key.direction())]
+            .iter()
+            .filter_map(|key| self.tiles.get(key))
+            .collect()
+    }
+
+    /// Checks that an edge piece is on the right terrain: a road has to have land on at least one side, and a ship
+    /// has to have sea on at least one side.  On a board without any sea every road passes and every ship fails.
+    pub fn check_edge_terrain(&self, road_key: &RoadKey, state: &RoadState) -> Result<(), GameError> {
+        let tiles = self.edge_tiles(road_key);
+        match state {
+            RoadState::Ship if !tiles.iter().any(|tile| tile.current_resource.is_sea()) => {
+                Err(PlacementViolation::new(
+                    PlacementRule::Terrain,
+                    road_key,
+                    "a ship has to be built next to the sea",
+                )
+                .into())
+            }
+            RoadState::Road if tiles.iter().all(|tile| tile.current_resource.is_sea()) => {
+                Err(PlacementViolation::new(
+                    PlacementRule::Terrain,
+                    road_key,
+                    "a road has to be built next to land -- build a ship instead",
+                )
+                .into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// a settlement has to be on a corner that touches at least one land tile
+    pub fn check_on_land(&self, building_key: &BuildingKey) -> Result<(), GameError> {
+        let corner = building_corner(building_key);
+        let on_land = self
+            .tiles
+            .values()
+            .filter(|tile| !tile.current_resource.is_sea())
+            .any(|tile| {
+                BuildingPosition::iter().any(|position| {
+                    building_corner(&BuildingKey::new(position, tile.tile_key)) == corner
+                })
+            });
+        if on_land {
+            Ok(())
+        } else {
+            Err(PlacementViolation::new(
+                PlacementRule::Terrain,
+                building_key,
+                "a settlement has to be built next to land",
+            )
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{
+            buildings::building_enums::BuildingState,
+            catan_games::{
+                games::seafarers::game_info::SEAFARERS_GAME_INFO, traits::game_trait::GameTrait,
+            },
+            game_container::game_messages::TurnSummary,
+            roads::longest_road::road_corners,
+            shared::{
+                game_enums::{CatanGames, Direction, GameState},
+                game_models::{BuildData, BuildingSupply, LedgerReason, ResourceCards},
+            },
+            tiles::{tile_enums::TileResource, tile_key::TileKey},
+        },
+        shared::shared_models::UserProfile,
+    };
+
+    fn create_seafarers_game() -> RegularGame {
+        let user = UserProfile::new_test_user(Some("1".to_string()));
+        let mut game = RegularGame::new_of_type(&user, CatanGames::Seafarers);
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("3".to_string())));
+        game
+    }
+
+    fn violated_rule(result: Result<(), GameError>) -> Option<PlacementRule> {
+        result
+            .err()
+            .and_then(|error| PlacementViolation::from_error(&error))
+            .map(|violation| violation.rule)
+    }
+
+    #[test]
+    fn test_seafarers_board() {
+        let mut game = create_seafarers_game();
+        assert_eq!(game.game_type, CatanGames::Seafarers);
+        assert_eq!(game.tiles.len(), 37);
+        assert!(game.has_sea());
+        let sea_keys = |game: &RegularGame| {
+            let mut keys: Vec<String> = game
+                .tiles
+                .values()
+                .filter(|tile| tile.current_resource.is_sea())
+                .map(|tile| tile.tile_key.to_string())
+                .collect();
+            keys.sort();
+            keys
+        };
+        let before = sea_keys(&game);
+        assert_eq!(before.len(), 23);
+
+        for _ in 0..5 {
+            game.shuffle();
+            // the sea stays put and never gets a number
+            assert_eq!(sea_keys(&game), before);
+            for tile in game.tiles.values() {
+                assert_eq!(tile.current_resource.is_sea(), tile.roll == 0);
+            }
+            assert_eq!(game.tiles[&game.baron_tile].current_resource, TileResource::Desert);
+            assert_eq!(game.tiles[&game.baron_tile].roll, 7);
+        }
+
+        // every harbor is on land, facing the sea
+        assert_eq!(game.harbors.len(), SEAFARERS_GAME_INFO.harbor_data.len());
+        for harbor in game.harbors.keys() {
+            let road_key = RoadKey::new(harbor.position(), harbor.tile_key());
+            let tiles = game.edge_tiles(&road_key);
+            assert_eq!(tiles.len(), 2, "{:?} should be between two tiles", harbor);
+            assert!(!tiles[0].current_resource.is_sea());
+            assert!(tiles[1].current_resource.is_sea());
+        }
+
+        // the middle column is sea, so the land is at least two islands
+        assert!(game.tiles[&TileKey::new(0, 0, 0)].current_resource.is_sea());
+        assert!(!game.tiles[&TileKey::new(-1, 0, 1)].current_resource.is_sea());
+        assert!(!game.tiles[&TileKey::new(1, 0, -1)].current_resource.is_sea());
+    }
+
+    #[test]
+    fn test_terrain() {
+        let game = create_seafarers_game();
+        let coast = RoadKey::new(Direction::NorthEast, TileKey::new(-1, 0, 1));
+        let open_sea = RoadKey::new(Direction::North, TileKey::new(0, -1, 1));
+        let inland = RoadKey::new(Direction::South, TileKey::new(-1, 0, 1));
+
+        assert!(game.check_edge_terrain(&coast, &RoadState::Road).is_ok());
+        assert!(game.check_edge_terrain(&coast, &RoadState::Ship).is_ok());
+        assert!(game.check_edge_terrain(&open_sea, &RoadState::Ship).is_ok());
+        assert_eq!(
+            violated_rule(game.check_edge_terrain(&open_sea, &RoadState::Road)),
+            Some(PlacementRule::Terrain)
+        );
+        assert!(game.check_edge_terrain(&inland, &RoadState::Road).is_ok());
+        assert_eq!(
+            violated_rule(game.check_edge_terrain(&inland, &RoadState::Ship)),
+            Some(PlacementRule::Terrain)
+        );
+
+        let at_sea = BuildingKey::new(BuildingPosition::TopLeft, TileKey::new(0, -2, 2));
+        assert_eq!(violated_rule(game.check_on_land(&at_sea)), Some(PlacementRule::Terrain));
+        let on_coast = BuildingKey::new(BuildingPosition::TopRight, TileKey::new(-1, 0, 1));
+        assert!(game.check_on_land(&on_coast).is_ok());
+
+        // a regular board has no sea, so there is nowhere to put a ship
+        let regular = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        assert!(!regular.has_sea());
+        assert!(regular.check_edge_terrain(&inland, &RoadState::Road).is_ok());
+        assert!(regular.check_edge_terrain(&coast, &RoadState::Ship).is_err());
+    }
+
+    #[test]
+    fn test_buying_a_ship() {
+        let mut game = create_seafarers_game();
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        let coast = RoadKey::new(Direction::NorthEast, TileKey::new(-1, 0, 1));
+        let settlement = BuildingPosition::iter()
+            .map(|position| BuildingKey::new(position, TileKey::new(-1, 0, 1)))
+            .find(|key| road_corners(&coast).contains(&building_corner(key)))
+            .expect("the edge has a corner on the tile");
+        game.place_building("1", &settlement, BuildingState::Settlement)
+            .expect("place_building should work");
+        game.game_state = GameState::BuyingAndTrading;
+        game.current_player_id = "1".to_string();
+        game.take_from_bank("1", &RegularGame::ship_cost(), LedgerReason::Roll)
+            .unwrap();
+
+        let inland = RoadKey::new(Direction::South, TileKey::new(-1, 0, 1));
+        assert!(game.build("1", &BuildData::Ship(inland)).is_err());
+        let bought = game
+            .build("1", &BuildData::Ship(coast.clone()))
+            .expect("a ship on the coast next to the settlement should work");
+        assert_eq!(bought.players["1"].resources, ResourceCards::default());
+        assert_eq!(bought.players["1"].supply.ships, BuildingSupply::SHIPS - 1);
+        assert_eq!(bought.players["1"].supply.roads, BuildingSupply::ROADS);
+        assert_eq!(*bought.players["1"].roads[0].state(), RoadState::Ship);
+        assert_eq!(TurnSummary::from_game(&bought).ships_built, 1);
+        assert!(bought.invariant_violations().is_empty());
+    }
+}
@@ -56,6 +56,9 @@ impl StateData {
             GameState::AllocateResourceForward => actions.push(GameAction::Build),
             GameState::AllocateResourceReverse => actions.push(GameAction::Build),
             GameState::WaitingForRoll => actions.push( GameAction::Roll),
+            GameState::MustDiscard => {
+               actions = vec![GameAction::Discard];
+            },
             GameState::MustMoveBaron => {
                actions = vec![GameAction::MoveBaron, GameAction::Undo, GameAction::Redo];
             },
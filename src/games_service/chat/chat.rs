@@ -0,0 +1,164 @@
+#![allow(dead_code)]
+/**
+ *  players in the same game talking to each other.  a chat message goes to every player in the game on the game's
+ *  channel of the long poller, so it is in the recent messages a rejoining player gets back (see rejoin_state).
+ *  nothing is stored: once the game is evicted its chat is gone.
+ *
+ *  messages are trimmed and have to be 1 to MAX_CHAT_LENGTH characters.  when CHAT_PROFANITY_FILTER is set, the words
+ *  in PROFANITY are starred out before anybody sees them.
+ */
+
+use actix_web::{
+    web::{self, Path},
+    HttpResponse,
+};
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::game_container::{game_container::GameContainer, game_messages::CatanMessage},
+    middleware::{request_context_mw::RequestContext, service_config::SERVICE_CONFIG},
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+/// the longest message a player can send, in characters
+pub const MAX_CHAT_LENGTH: usize = 500;
+
+/// starred out when the filter is on.  whole words only, so "hello" and "scrapbook" get through
+const PROFANITY: [&str; 8] = [
+    "arse", "bastard", "bollocks", "crap", "damn", "hell", "piss", "shit",
+];
+
+///
+/// the body of the chat api
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChatData {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChatMessage {
+    pub game_id: String,
+    pub from_id: String,
+    pub from_name: String, // the sender's display name, so clients don't have to look it up
+    pub text: String,      // trimmed, and filtered if the filter is on
+    pub sent_at: u64,      // seconds since the UNIX epoch
+}
+
+/// the text with every word in PROFANITY replaced by as many '*'s, ignoring case
+pub fn filter_profanity(text: &str) -> String {
+    fn flush(word: &mut String, filtered: &mut String) {
+        if PROFANITY.contains(&word.to_lowercase().as_str()) {
+            filtered.extend(word.chars().map(|_| '*'));
+        } else {
+            filtered.push_str(word);
+        }
+        word.clear();
+    }
+    let mut filtered = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut filtered);
+            filtered.push(c);
+        }
+    }
+    flush(&mut word, &mut filtered);
+    filtered
+}
+
+/// the text as it will be sent, or what is wrong with it
+pub fn clean_text(text: &str, filter: bool) -> Result<String, GameError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(GameError::BadActionData("a chat message can't be empty".to_owned()));
+    }
+    if text.chars().count() > MAX_CHAT_LENGTH {
+        return Err(GameError::BadActionData(format!(
+            "a chat message can be at most {} characters",
+            MAX_CHAT_LENGTH
+        )));
+    }
+    Ok(if filter {
+        filter_profanity(text)
+    } else {
+        text.to_owned()
+    })
+}
+
+/// send a message from the caller to everybody in the game.  only players in the game can chat in it
+#[tracing::instrument(skip_all, fields(game = %game_id, user = %caller_id))]
+pub async fn send_chat(
+    game_id: &str,
+    caller_id: &str,
+    data: &ChatData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    let from_name = match game.players.get(caller_id) {
+        Some(player) => player.profile.display_name.clone(),
+        None => {
+            return Err(ServiceResponse::new(
+                &format!("{} isn't playing in {}", caller_id, game_id),
+                StatusCode::FORBIDDEN,
+                ResponseType::NoData,
+                GameError::HttpError(StatusCode::FORBIDDEN),
+            ))
+        }
+    };
+    let text = clean_text(&data.text, SERVICE_CONFIG.chat_profanity_filter).map_err(|e| {
+        ServiceResponse::new(
+            "bad chat message",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(format!("{:?}", e)),
+            e,
+        )
+    })?;
+    let message = CatanMessage::Chat(ChatMessage {
+        game_id: game_id.to_owned(),
+        from_id: caller_id.to_owned(),
+        from_name,
+        text,
+        sent_at: request_context.environment.now(),
+    });
+    GameContainer::broadcast_message(game_id, &message).await
+}
+
+pub async fn chat_handler(
+    game_id: Path<String>,
+    data: web::Json<ChatData>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    send_chat(&game_id, &claims.id, &data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_text() {
+        assert_eq!(clean_text("  good game  ", false).unwrap(), "good game");
+        assert!(clean_text("   ", false).is_err());
+        assert!(clean_text(&"a".repeat(MAX_CHAT_LENGTH), false).is_ok());
+        assert!(clean_text(&"a".repeat(MAX_CHAT_LENGTH + 1), false).is_err());
+        // characters, not bytes
+        assert!(clean_text(&"é".repeat(MAX_CHAT_LENGTH), false).is_ok());
+
+        assert_eq!(clean_text("Damn, that baron!", false).unwrap(), "Damn, that baron!");
+        assert_eq!(clean_text("Damn, that baron!", true).unwrap(), "****, that baron!");
+        assert_eq!(filter_profanity("hello shell CRAP"), "hello shell ****");
+    }
+}
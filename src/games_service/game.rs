@@ -1,37 +1,52 @@
 use crate::{
     games_service::{
-        game_container::game_messages::{CatanMessage, GameCreatedData},
-        long_poller::long_poller::LongPoller,
+        game_container::game_messages::{CatanMessage, GameCreatedData, RejoinState},
+        long_poller::{channels::MessageChannel, long_poller::LongPoller},
     },
     middleware::request_context_mw::RequestContext,
-    shared::shared_models::{UserProfile, GameError, ResponseType, ServiceResponse},
+    shared::{
+        i18n::Language,
+        service_models::{Claims, Role},
+        shared_models::{UserProfile, GameError, ResponseType, ServiceResponse},
+    },
 };
 
 use reqwest::StatusCode;
 
-use crate::games_service::shared::game_enums::CatanGames;
+use crate::games_service::shared::{
+    game_enums::{CatanGames, GameVisibility},
+    game_models::{CustomBoardData, GameOptions, MemberRole},
+};
 
 use super::{
+    actions::host,
     catan_games::{games::regular::regular_game::RegularGame, traits::game_trait::GameTrait},
-    game_container::game_container::GameContainer,
+    game_container::{checksum, game_container::GameContainer},
+    lobby::join_codes,
+    webhooks,
 };
 
+/// the game types POST /games/{game_type} can create.  Seafarers is played by RegularGame on a board with sea
+/// (see catan_games/games/seafarers)
+pub const SUPPORTED_GAMES: [CatanGames; 2] = [CatanGames::Regular, CatanGames::Seafarers];
+
 ///
 /// check the state to make sure the request is valid
 /// randomize the board and the harbors
 /// post the response to websocket
-pub async fn shuffle_game(game_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+/// return the game as the caller is allowed to see it (see RegularGame::redacted_for)
+pub async fn shuffle_game(game_id: &str, caller_id: &str) -> Result<ServiceResponse, ServiceResponse> {
     let (game, _) = GameContainer::current_game(&game_id.to_owned()).await?;
 
     let mut new_game = game.clone();
     new_game.shuffle_count = new_game.shuffle_count + 1;
     new_game.shuffle();
-    let result = GameContainer::push_game(&game_id.to_owned(), &new_game).await;
+    let result = GameContainer::push_game(&game_id.to_owned(), &new_game, "NewBoard", None).await;
     match result {
         Ok(_) => Ok(ServiceResponse::new(
             "shuffled",
             StatusCode::OK,
-            ResponseType::Game(new_game),
+            ResponseType::Game(new_game.redacted_for(caller_id)),
             GameError::NoError(String::default()),
         )),
         Err(e) => {
@@ -46,6 +61,82 @@ pub async fn shuffle_game(game_id: &str) -> Result<ServiceResponse, ServiceRespo
     }
 }
 
+///
+/// set the victory point target and win condition.  only the creator can, before the game starts (see
+/// RegularGame::set_options)
+pub async fn set_game_options(
+    game_id: &str,
+    user_id: &str,
+    options: &GameOptions,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    let new_game = game.set_options(user_id, options).map_err(|e| {
+        ServiceResponse::new(
+            "bad game options",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(format!("{}", e)),
+            e,
+        )
+    })?;
+    let pushed = GameContainer::push_game(game_id, &new_game, "SetOptions", Some(user_id)).await?;
+    Ok(ServiceResponse::new(
+        "options set",
+        StatusCode::OK,
+        ResponseType::Game(pushed.redacted_for(user_id)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+///
+/// set the language the game's system messages, turn summaries and notifications are written in.  only the creator can
+pub async fn set_game_language(
+    game_id: &str,
+    user_id: &str,
+    language: Language,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    let new_game = game.set_language(user_id, language).map_err(|e| {
+        ServiceResponse::new(
+            "can't set the language",
+            StatusCode::FORBIDDEN,
+            ResponseType::ErrorInfo(format!("{}", e)),
+            e,
+        )
+    })?;
+    let pushed = GameContainer::push_game(game_id, &new_game, "SetLanguage", Some(user_id)).await?;
+    Ok(ServiceResponse::new(
+        "language set",
+        StatusCode::OK,
+        ResponseType::Game(pushed.redacted_for(user_id)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+///
+/// the player reads the game in their own language -- or, with None, in the game's again
+pub async fn set_language_override(
+    game_id: &str,
+    user_id: &str,
+    language: Option<Language>,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    let new_game = game.set_language_override(user_id, language).map_err(|e| {
+        ServiceResponse::new(
+            "can't set the language",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(format!("{}", e)),
+            e,
+        )
+    })?;
+    let pushed = GameContainer::push_game(game_id, &new_game, "SetLanguageOverride", Some(user_id)).await?;
+    Ok(ServiceResponse::new(
+        "language set",
+        StatusCode::OK,
+        ResponseType::Game(pushed.redacted_for(user_id)),
+        GameError::NoError(String::default()),
+    ))
+}
+
 ///
 /// creates a new game and returns a gamedId that is used for all subsequent game* apis.
 /// the user header is filled in by the auth middleware.  a JWT token from login must be
@@ -53,12 +144,14 @@ pub async fn shuffle_game(game_id: &str) -> Result<ServiceResponse, ServiceRespo
 /// cames can be run at the same time.
 pub async fn new_game(
     game_type: CatanGames,
+    visibility: GameVisibility,
+    webhook: Option<&str>,
     user_id: &str,
     is_test: bool,
     test_game: Option<RegularGame>,
     request_context: &RequestContext,
 ) -> Result<ServiceResponse, ServiceResponse> {
-    if game_type != CatanGames::Regular {
+    if !SUPPORTED_GAMES.contains(&game_type) {
         return Err(ServiceResponse::new(
             &format!("Game not supported: {:#?}", game_type),
             StatusCode::BAD_REQUEST,
@@ -66,6 +159,11 @@ pub async fn new_game(
             GameError::MissingData(String::default()),
         ));
     }
+    //  check the webhook before there is a game to clean up
+    let webhook = match webhook {
+        Some(url) => Some(webhooks::check_url(url).await?),
+        None => None,
+    };
     let user = request_context
         .database
         .find_user_by_id(user_id)
@@ -73,21 +171,63 @@ pub async fn new_game(
 
     //
     //  "if it is a test game and the game has been passed in, use it.  otherwise create a new game and shuffle"
-    let game = if is_test {
+    let mut game = if is_test {
         match test_game {
             Some(g) => g.clone(),
             None => {
-                let mut game = RegularGame::new(&UserProfile::from_persist_user(&user));
+                let mut game =
+                    RegularGame::new_of_type(&UserProfile::from_persist_user(&user), game_type);
+                game.id = request_context.environment.new_id();
                 game.shuffle();
                 game
             }
         }
     } else {
-        let mut game = RegularGame::new(&UserProfile::from_persist_user(&user));
+        let mut game = RegularGame::new_of_type(&UserProfile::from_persist_user(&user), game_type);
+        game.id = request_context.environment.new_id();
         game.shuffle();
         game
     };
+    if visibility == GameVisibility::Private {
+        game.visibility = GameVisibility::Private;
+        game.join_code = Some(join_codes::create(&game.id, request_context.environment.as_ref()));
+    }
+    if let Some(target) = webhook {
+        webhooks::register(&game.id, target);
+    }
+    add_new_game(game, user_id, "shuffled").await
+}
+
+///
+/// creates a new game on the board the creator sent, instead of a shuffled one.  the board is checked first (see
+/// custom_board.rs) and a 400 lists everything that is wrong with it
+pub async fn new_custom_game(
+    user_id: &str,
+    data: &CustomBoardData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let user = request_context
+        .database
+        .find_user_by_id(user_id)
+        .await?;
+    let game = RegularGame::new_custom(&UserProfile::from_persist_user(&user), data).map_err(|e| {
+        ServiceResponse::new(
+            "bad custom board",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(format!("{:?}", e)),
+            e,
+        )
+    })?;
+    add_new_game(game, user_id, "custom").await
+}
 
+pub(crate) async fn add_new_game(
+    mut game: RegularGame,
+    user_id: &str,
+    message: &str,
+) -> Result<ServiceResponse, ServiceResponse> {
+    //  the container sets the checksum too, but the creator gets this copy
+    game.checksum = checksum::checksum_of(&game);
     //
     //  the sequence is
     //  1. create_and_add_container
@@ -106,6 +246,8 @@ pub async fn new_game(
         ));
     }
 
+    host::start_host_monitor(&game.id);
+
     //
     //  send a message to the user that the game was created
 
@@ -124,18 +266,88 @@ pub async fn new_game(
     //  and then get the update from the long_polller, which will do the same thing.  we might just ignore the return
     //  value on the client, in which case we are wasting bytes on the wire.
     Ok(ServiceResponse::new(
-        "shuffled",
+        message,
         StatusCode::OK,
         ResponseType::Game(game),
         GameError::NoError(String::default()),
     ))
 }
 
+///
+/// everybody in the game, in seat order, with their role and whether they are connected right now.  only the players
+/// in the game, and admins, can ask
+pub async fn game_members(game_id: &str, claims: Option<&Claims>) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    let allowed = claims.map_or(false, |claims| {
+        game.players.contains_key(&claims.id) || claims.roles.contains(&Role::Admin)
+    });
+    if !allowed {
+        return Err(ServiceResponse::new(
+            &format!("only the players in {} can see who is in it", game_id),
+            StatusCode::FORBIDDEN,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::FORBIDDEN),
+        ));
+    }
+    let mut members = game.members(claims);
+    for member in members.iter_mut() {
+        member.connected = LongPoller::is_connected(&member.user_id).await;
+    }
+    let connected: Vec<String> = members
+        .iter()
+        .filter(|member| member.connected)
+        .map(|member| member.user_id.clone())
+        .collect();
+    if let Some(co_host) = host::next_host(&game, &connected) {
+        if let Some(member) = members.iter_mut().find(|member| member.user_id == co_host) {
+            member.role = MemberRole::CoHost;
+        }
+    }
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::GameMembers(members),
+        GameError::NoError(String::default()),
+    ))
+}
+
+///
+/// for a player coming back after their connection died: the game as it is now, redacted for them, and the messages
+/// on the game's channel after `since` (the last sequence number they saw -- 0, or nothing, for everything we kept)
+pub async fn rejoin_state(
+    game_id: &str,
+    caller_id: &str,
+    since: Option<u64>,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    if !game.players.contains_key(caller_id) {
+        return Err(ServiceResponse::new(
+            &format!("{} isn't playing in {}", caller_id, game_id),
+            StatusCode::FORBIDDEN,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::FORBIDDEN),
+        ));
+    }
+    let channel = MessageChannel::Game(game_id.to_owned());
+    let (last_sequence, missed) =
+        LongPoller::missed_messages(caller_id, &channel, since.unwrap_or(0)).await;
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::RejoinState(RejoinState {
+            game: game.redacted_for(caller_id),
+            last_sequence,
+            missed,
+        }),
+        GameError::NoError(String::default()),
+    ))
+}
+
 pub async fn supported_games() -> Result<ServiceResponse, ServiceResponse> {
     Ok(ServiceResponse::new(
         "shuffled",
         StatusCode::OK,
-        ResponseType::SupportedGames(vec![CatanGames::Regular]),
+        ResponseType::SupportedGames(SUPPORTED_GAMES.to_vec()),
         GameError::NoError(String::default()),
     ))
 }
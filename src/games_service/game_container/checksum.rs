@@ -0,0 +1,228 @@
+#![allow(dead_code)]
+/**
+ *  every game the service pushes gets a Checksum, so a client can tell right away when the game it has worked out
+ *  isn't the game the service has.  the checksum goes out with every GameUpdate, and a client sends back the one it
+ *  computed for the game it is at on its next action, in the x-game-checksum header: "{game_index}:{checksum}".
+ *
+ *  the checksum only covers what every player can see, so each player's redacted copy has the same one.  it is the
+ *  64 bit FNV-1a hash of this JSON (no whitespace, fields in this order), written as 16 lowercase hex digits:
+ *
+ *      {"GameIndex":42,"GameState":"WaitingForRoll","CurrentPlayerId":"...","BaronTile":{...},"Bank":{...},
+ *       "Players":[{"UserId":"...","Resources":{...},"DevCards":2,"Supply":{...},"Roads":[...],"Buildings":[...]}]}
+ *
+ *  players are sorted by user id.  DevCards is how many the player holds, not what they are.  Roads and Buildings are
+ *  "{state json}:{key json}" strings, sorted.
+ *
+ *  when the echo doesn't match the service's checksum for that game_index, the client has desynced: it is sent the
+ *  current game (its own redacted copy) on the long poller, and the mismatch is recorded with the client error
+ *  reports (see client_telemetry.rs) so somebody can diff the snapshots and find out why.  the action itself goes
+ *  ahead -- the service's game is the one that counts.
+ */
+use serde::Serialize;
+
+use crate::{
+    games_service::{
+        buildings::building_enums::BuildingState,
+        catan_games::games::regular::regular_game::RegularGame,
+        game_container::{game_container::GameContainer, game_messages::CatanMessage},
+        long_poller::long_poller::LongPoller,
+        player::player::Player,
+        roads::road_enums::RoadState,
+        shared::{
+            game_enums::GameState,
+            game_models::{BuildingSupply, ResourceCards},
+        },
+        tiles::tile_key::TileKey,
+    },
+    middleware::request_context_mw::RequestContext,
+    shared::client_telemetry,
+};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct CanonicalPlayer<'a> {
+    user_id: &'a str,
+    resources: &'a ResourceCards,
+    dev_cards: usize,
+    supply: &'a BuildingSupply,
+    roads: Vec<String>,
+    buildings: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct CanonicalGame<'a> {
+    game_index: u32,
+    game_state: &'a GameState,
+    current_player_id: &'a str,
+    baron_tile: &'a TileKey,
+    bank: &'a ResourceCards,
+    players: Vec<CanonicalPlayer<'a>>,
+}
+
+fn state_and_key<S: Serialize, K: Serialize>(state: &S, key: &K) -> String {
+    format!(
+        "{}:{}",
+        serde_json::to_string(state).unwrap_or_default(),
+        serde_json::to_string(key).unwrap_or_default()
+    )
+}
+
+fn canonical_player<'a>(user_id: &'a str, player: &'a Player) -> CanonicalPlayer<'a> {
+    let mut roads: Vec<String> = player
+        .roads
+        .iter()
+        .map(|road| state_and_key::<RoadState, _>(road.state(), road.primary_key()))
+        .collect();
+    roads.sort();
+    let mut buildings: Vec<String> = player
+        .buildings
+        .iter()
+        .map(|building| state_and_key::<BuildingState, _>(&building.state, &building.building_key))
+        .collect();
+    buildings.sort();
+    CanonicalPlayer {
+        user_id,
+        resources: &player.resources,
+        dev_cards: player.dev_cards.len(),
+        supply: &player.supply,
+        roads,
+        buildings,
+    }
+}
+
+/// the game, as the JSON the checksum is computed over
+pub fn canonical_json(game: &RegularGame) -> String {
+    let mut players: Vec<CanonicalPlayer> = game
+        .players
+        .iter()
+        .map(|(user_id, player)| canonical_player(user_id, player))
+        .collect();
+    players.sort_by(|a, b| a.user_id.cmp(b.user_id));
+    let canonical = CanonicalGame {
+        game_index: game.game_index,
+        game_state: &game.game_state,
+        current_player_id: &game.current_player_id,
+        baron_tile: &game.baron_tile,
+        bank: &game.bank,
+        players,
+    };
+    serde_json::to_string(&canonical).expect("the canonical game is plain data")
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// the game's checksum -- set on every game the container pushes
+pub fn checksum_of(game: &RegularGame) -> String {
+    format!("{:016x}", fnv1a(canonical_json(game).as_bytes()))
+}
+
+/// "{game_index}:{checksum}" from the header
+pub fn parse_echo(echo: &str) -> Option<(u32, String)> {
+    let (game_index, checksum) = echo.trim().split_once(':')?;
+    let checksum = checksum.trim().to_ascii_lowercase();
+    if checksum.len() != 16 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((game_index.trim().parse().ok()?, checksum))
+}
+
+/**
+ *  check the checksum the player echoed.  a game_index the container no longer has can't be checked and is let go --
+ *  the client will get the next GameUpdate anyway.  one the service never made is a desync, like a wrong checksum.
+ *  returns true if the client was resynced
+ */
+pub async fn check_echo(game_id: &str, user_id: &str, echo: &str, request_context: &RequestContext) -> bool {
+    let (game_index, echoed) = match parse_echo(echo) {
+        Some(echo) => echo,
+        None => {
+            tracing::debug!("{} sent a checksum we can't read: {}", user_id, echo);
+            return false;
+        }
+    };
+    let (current, _) = match GameContainer::current_game(game_id).await {
+        Ok(current) => current,
+        Err(_) => return false,
+    };
+    if !current.players.contains_key(user_id) {
+        return false;
+    }
+    let expected = match GameContainer::snapshot(game_id, game_index).await {
+        Ok(snapshot) => snapshot.checksum,
+        Err(_) if game_index > current.game_index => String::default(),
+        Err(_) => return false,
+    };
+    if expected == echoed {
+        return false;
+    }
+    tracing::warn!(
+        "{} is out of sync in {} at {}: they have {}, we have {:?}.  resyncing them at {}",
+        user_id,
+        game_id,
+        game_index,
+        echoed,
+        expected,
+        current.game_index
+    );
+    client_telemetry::record_checksum_mismatch(
+        user_id,
+        game_id,
+        game_index,
+        current.game_index,
+        &format!("checksum {} expected {:?}", echoed, expected),
+        request_context,
+    );
+    let update = CatanMessage::GameUpdate(current.redacted_for(user_id));
+    if let Err(e) = LongPoller::send_message(vec![user_id.to_owned()], &update).await {
+        tracing::warn!("failed to resync {} in {}: {:#?}", user_id, game_id, e);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{catan_games::traits::game_trait::GameTrait, shared::game_enums::DevCardType},
+        shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_checksum() {
+        // the FNV-1a test vectors, so a client's hash can be checked against ours
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        game.players.get_mut("2").unwrap().dev_cards = vec![DevCardType::Knight];
+        game.players.get_mut("2").unwrap().victory_point_cards = 1;
+        let checksum = checksum_of(&game);
+        assert_eq!(checksum.len(), 16);
+
+        // every player's copy has the same checksum, and so does the same game built again
+        assert_eq!(checksum_of(&game.redacted_for("1")), checksum);
+        assert_eq!(checksum_of(&game.redacted_for("2")), checksum);
+        assert_eq!(checksum_of(&game.clone()), checksum);
+
+        let mut changed = game.clone();
+        changed.players.get_mut("1").unwrap().resources.wood += 1;
+        assert_ne!(checksum_of(&changed), checksum);
+        let mut changed = game.clone();
+        changed.game_index += 1;
+        assert_ne!(checksum_of(&changed), checksum);
+
+        assert_eq!(parse_echo(&format!("12:{}", checksum)), Some((12, checksum.clone())));
+        assert_eq!(parse_echo(&format!(" 12 : {} ", checksum.to_uppercase())), Some((12, checksum)));
+        assert_eq!(parse_echo("12"), None);
+        assert_eq!(parse_echo("twelve:0123456789abcdef"), None);
+        assert_eq!(parse_echo("12:xyz"), None);
+    }
+}
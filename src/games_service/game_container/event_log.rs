@@ -0,0 +1,367 @@
+#![allow(dead_code)]
+/**
+ *  an append-only log of everything that happened to a game.  instead of storing a whole RegularGame for every step,
+ *  each step is stored as a PersistGameEvent: the action, who did it, and a json diff from the game before it.  the
+ *  first event (Created) has the whole game, and rebuild() replays the diffs to get the game at any game_index.
+ *
+ *  the container builds the events as games are pushed (see GameContainer::push_game) and keeps them until they are
+ *  flushed to the database, after every action and when the game ends.  an action that is undone keeps its event
+ *  until the next action gets its game_index, which replaces it -- so the log always describes the game as it was
+ *  actually played.
+ *
+ *  flushed events go in their game's queue, and each game has one writer at a time, so a game's events are written in
+ *  the order they happened and an undone event is never written over the one that replaced it.  an event that
+ *  replaces queued ones (same or later game_index -- they were undone) takes their place.  a write that fails is
+ *  retried, WRITE_ATTEMPTS times with a doubling delay; after that the events stay queued, in front of anything newer,
+ *  for the game's next flush.  the events are partitioned by game.
+ */
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    games_service::catan_games::games::regular::regular_game::RegularGame,
+    middleware::request_context_mw::RequestContext,
+    shared::{error_reporting, service_models::PersistGameEvent, shared_models::GameError},
+};
+
+use super::game_container::GameContainer;
+
+/// the key in a diff of an array that holds the changed items, by index
+const ARRAY_ITEMS: &str = "$items";
+
+/// how many times a game's writer tries its events before it leaves them for the next flush
+pub const WRITE_ATTEMPTS: u32 = 3;
+
+/// how long the writer waits after the first failure.  doubled after every one after that
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default)]
+struct PendingEvents {
+    events: Vec<PersistGameEvent>, // oldest first
+    writing: bool,                 // the game's writer is running
+}
+
+lazy_static::lazy_static! {
+    // game id -> the events that have been flushed but not written yet
+    static ref PENDING: Mutex<HashMap<String, PendingEvents>> = Mutex::new(HashMap::new());
+}
+
+/**
+ *  the changes that turn `from` into `to`.  an object diff has only the keys that changed; an array of the same
+ *  length is diffed item by item ({"$items": {"3": diff}}); anything else is replaced.  a key that is missing from
+ *  `to` is set to null -- everything we diff is a struct, where that only happens to an Option that became None
+ */
+pub fn json_diff(from: &Value, to: &Value) -> Option<Value> {
+    if from == to {
+        return None;
+    }
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            let mut diff = Map::new();
+            for (key, value) in to.iter() {
+                match from.get(key) {
+                    Some(old) => {
+                        if let Some(changed) = json_diff(old, value) {
+                            diff.insert(key.clone(), changed);
+                        }
+                    }
+                    None => {
+                        diff.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            for key in from.keys().filter(|key| !to.contains_key(*key)) {
+                diff.insert(key.clone(), Value::Null);
+            }
+            Some(Value::Object(diff))
+        }
+        (Value::Array(from), Value::Array(to)) if from.len() == to.len() => {
+            let items: Map<String, Value> = from
+                .iter()
+                .zip(to.iter())
+                .enumerate()
+                .filter_map(|(index, (old, new))| json_diff(old, new).map(|d| (index.to_string(), d)))
+                .collect();
+            let mut diff = Map::new();
+            diff.insert(ARRAY_ITEMS.to_owned(), Value::Object(items));
+            Some(Value::Object(diff))
+        }
+        _ => Some(to.clone()),
+    }
+}
+
+/// the other half of json_diff: change `target` the way the diff says
+pub fn apply_diff(target: &mut Value, diff: &Value) {
+    match (target, diff) {
+        (Value::Array(items), Value::Object(diff)) if diff.contains_key(ARRAY_ITEMS) => {
+            if let Some(Value::Object(changes)) = diff.get(ARRAY_ITEMS) {
+                for (index, change) in changes.iter() {
+                    if let Some(item) = index.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                        apply_diff(item, change);
+                    }
+                }
+            }
+        }
+        (Value::Object(fields), Value::Object(diff)) => {
+            for (key, change) in diff.iter() {
+                match fields.get_mut(key) {
+                    Some(field) => apply_diff(field, change),
+                    None => {
+                        fields.insert(key.clone(), change.clone());
+                    }
+                }
+            }
+        }
+        (target, diff) => *target = diff.clone(),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// the event for `game`, which came from `previous` (None for a new game)
+pub fn new_event(
+    previous: Option<&RegularGame>,
+    game: &RegularGame,
+    action: &str,
+    actor_id: Option<&str>,
+) -> PersistGameEvent {
+    let to = serde_json::to_value(game).expect("a game should always serialize");
+    let diff = match previous {
+        Some(previous) => {
+            let from = serde_json::to_value(previous).expect("a game should always serialize");
+            json_diff(&from, &to).unwrap_or_else(|| Value::Object(Map::new()))
+        }
+        None => to,
+    };
+    PersistGameEvent {
+        id: format!("{}:{}", game.id, game.game_index),
+        partition_key: game.id.clone(),
+        game_id: game.id.clone(),
+        game_index: game.game_index,
+        action: action.to_owned(),
+        actor_id: actor_id.map(|id| id.to_owned()),
+        on_behalf_of: None,
+        at: now(),
+        diff,
+        note: None,
+    }
+}
+
+/**
+ *  the game as it was at game_index, from its events.  the events can be in any order, but they have to start with the
+ *  game being created and can't have any gaps
+ */
+pub fn rebuild(events: &[PersistGameEvent], game_index: u32) -> Result<RegularGame, GameError> {
+    let mut events: Vec<&PersistGameEvent> = events
+        .iter()
+        .filter(|event| event.game_index <= game_index)
+        .collect();
+    events.sort_by_key(|event| event.game_index);
+    let first = events
+        .first()
+        .ok_or_else(|| GameError::BadId(format!("there are no events up to {}", game_index)))?;
+    let mut game = first.diff.clone();
+    let mut expected = first.game_index;
+    for event in events.iter().skip(1) {
+        expected += 1;
+        if event.game_index != expected {
+            return Err(GameError::BadActionData(format!(
+                "event {} is missing",
+                expected
+            )));
+        }
+        apply_diff(&mut game, &event.diff);
+    }
+    if expected != game_index {
+        return Err(GameError::BadId(format!("there is no event {}", game_index)));
+    }
+    serde_json::from_value(game).map_err(|e| GameError::BadActionData(e.to_string()))
+}
+
+/// put the events at the back of the queue.  each replaces any queued event with the same or a later game_index --
+/// those were undone
+fn enqueue(queue: &mut Vec<PersistGameEvent>, events: Vec<PersistGameEvent>) {
+    for event in events {
+        queue.retain(|queued| queued.game_index < event.game_index);
+        queue.push(event);
+    }
+}
+
+/// write the game's new events to the database, without making the caller wait
+pub async fn flush(game_id: &str, request_context: &RequestContext) {
+    //  the events are queued in the actor, so two flushes at once queue them in the order they happened
+    let start_writer = GameContainer::take_events_into(game_id, {
+        let game_id = game_id.to_owned();
+        move |events| {
+            if events.is_empty() {
+                return false;
+            }
+            let mut pending = PENDING.lock().expect("the event lock shouldn't be poisoned");
+            let queue = pending.entry(game_id).or_default();
+            enqueue(&mut queue.events, events);
+            !std::mem::replace(&mut queue.writing, true)
+        }
+    })
+    .await
+    .unwrap_or(false);
+    if start_writer {
+        actix_web::rt::spawn(write_pending(game_id.to_owned(), request_context.clone()));
+    }
+}
+
+/// the game's writer: write its queued events, in order, until there aren't any
+async fn write_pending(game_id: String, request_context: RequestContext) {
+    let mut attempts = 0;
+    let mut delay = RETRY_DELAY;
+    loop {
+        let batch = {
+            let mut pending = PENDING.lock().expect("the event lock shouldn't be poisoned");
+            match pending.get_mut(&game_id) {
+                Some(queue) if !queue.events.is_empty() => std::mem::take(&mut queue.events),
+                _ => {
+                    pending.remove(&game_id);
+                    return;
+                }
+            }
+        };
+        let mut written = 0;
+        let mut failure = None;
+        for event in batch.iter() {
+            match request_context.database.append_game_event(event).await {
+                Ok(_) => written += 1,
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+        let e = match failure {
+            Some(e) => e,
+            None => {
+                attempts = 0;
+                delay = RETRY_DELAY;
+                continue;
+            }
+        };
+
+        //  what wasn't written goes back in front of anything queued since, which replaces it if it is newer
+        let event = &batch[written];
+        let message = format!("failed to write event {} of {}: {:#?}", event.game_index, game_id, e);
+        attempts += 1;
+        let give_up = attempts >= WRITE_ATTEMPTS;
+        {
+            let mut pending = PENDING.lock().expect("the event lock shouldn't be poisoned");
+            let queue = pending.entry(game_id.clone()).or_default();
+            let newer = std::mem::take(&mut queue.events);
+            queue.events = batch[written..].to_vec();
+            enqueue(&mut queue.events, newer);
+            queue.writing = !give_up;
+        }
+        if give_up {
+            //  they stay queued for the game's next flush
+            tracing::error!("{}", message);
+            error_reporting::report_background_failure("event_log", &message);
+            return;
+        }
+        tracing::warn!("{}.  retrying in {:?}", message, delay);
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{
+            catan_games::traits::game_trait::GameTrait,
+            shared::{game_enums::GameState, game_models::{LedgerReason, ResourceCards}},
+        },
+        shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_rebuild_from_events() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        let mut events = vec![new_event(None, &game, "Created", Some("1"))];
+        let mut snapshots = vec![game.clone()];
+
+        let mut next = game.clone();
+        GameTrait::add_user(&mut next, &UserProfile::new_test_user(Some("2".to_string())));
+        next.game_index += 1;
+        events.push(new_event(Some(&game), &next, "AddPlayer", Some("2")));
+        snapshots.push(next.clone());
+        game = next;
+
+        let mut next = game.clone();
+        next.game_state = GameState::WaitingForRoll;
+        next.take_from_bank("2", &ResourceCards::new(1, 0, 0, 2, 0), LedgerReason::Roll)
+            .unwrap();
+        next.game_index += 1;
+        let event = new_event(Some(&game), &next, "Roll", None);
+        // only what changed is stored
+        assert!(event.diff.to_string().len() < events[0].diff.to_string().len() / 4);
+        events.push(event);
+        snapshots.push(next.clone());
+
+        events.reverse();
+        for snapshot in snapshots.iter() {
+            assert_eq!(&rebuild(&events, snapshot.game_index).unwrap(), snapshot);
+        }
+        assert!(rebuild(&events, next.game_index + 1).is_err());
+        events.remove(1); // the AddPlayer event
+        assert!(rebuild(&events, next.game_index).is_err());
+    }
+
+    #[test]
+    fn test_enqueue() {
+        let game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        let event = |game_index: u32, action: &str| {
+            let mut next = game.clone();
+            next.game_index = game_index;
+            new_event(Some(&game), &next, action, None)
+        };
+        assert_eq!(event(1, "Roll").partition_key, game.id);
+
+        let mut queue = Vec::new();
+        enqueue(&mut queue, vec![event(1, "Roll"), event(2, "Build"), event(3, "Next")]);
+        // 2 and 3 were undone, and another action took 2's place
+        enqueue(&mut queue, vec![event(2, "Trade")]);
+        let queued: Vec<(u32, &str)> = queue.iter().map(|e| (e.game_index, e.action.as_str())).collect();
+        assert_eq!(queued, vec![(1, "Roll"), (2, "Trade")]);
+
+        // a failed batch goes back in front of what was queued since, which wins where they overlap
+        let failed = vec![event(2, "Build"), event(3, "Next")];
+        let newer = vec![event(3, "Buy"), event(4, "Next")];
+        let mut queue = failed;
+        enqueue(&mut queue, newer);
+        let queued: Vec<(u32, &str)> = queue.iter().map(|e| (e.game_index, e.action.as_str())).collect();
+        assert_eq!(queued, vec![(2, "Build"), (3, "Buy"), (4, "Next")]);
+    }
+
+    #[test]
+    fn test_json_diff() {
+        let from: Value = serde_json::json!({"A": 1, "B": [1, 2, 3], "C": {"D": "x"}, "E": "gone"});
+        let to: Value = serde_json::json!({"A": 1, "B": [1, 5, 3], "C": {"D": "y"}, "F": [1]});
+        let diff = json_diff(&from, &to).unwrap();
+        assert_eq!(
+            diff,
+            serde_json::json!({"B": {"$items": {"1": 5}}, "C": {"D": "y"}, "E": null, "F": [1]})
+        );
+        let mut patched = from.clone();
+        apply_diff(&mut patched, &diff);
+        assert_eq!(patched["B"], to["B"]);
+        assert_eq!(patched["E"], Value::Null);
+        assert_eq!(json_diff(&to, &to), None);
+    }
+}
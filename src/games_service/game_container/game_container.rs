@@ -1,28 +1,76 @@
 #![allow(dead_code)]
 
-use super::game_messages::CatanMessage;
+use super::{
+    checksum, event_log,
+    game_map::{ShardedMap, SHARD_COUNT},
+    game_messages::{CatanMessage, ErrorData},
+    surgery::{self, SurgeryRequest},
+};
 use crate::{
     games_service::{
+        bots::bots,
         catan_games::games::regular::regular_game::RegularGame,
-        long_poller::long_poller::LongPoller,
+        long_poller::{
+            channels::MessageChannel,
+            long_poller::{LongPoller, MessageTarget},
+        },
+    },
+    shared::{
+        error_reporting, i18n,
+        profiling::{self, HotPath},
+        service_models::PersistGameEvent,
+        shared_models::{UserProfile, GameError, ResponseType, ServiceResponse},
     },
-    shared::shared_models::{UserProfile, GameError, ResponseType, ServiceResponse},
-
 };
 
 
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, oneshot};
+
+/**
+ *  every game in memory is owned by its own tokio task -- an actor.  the actor holds the GameContainer and does the
+ *  jobs sent to its mailbox one at a time, in the order they were sent, so nothing else ever touches the container and
+ *  there are no locks to take (or to remember to drop before broadcasting).  GAME_MAP only holds the mailboxes;
+ *  removing a game's mailbox from it ends the actor once the jobs already sent are done.
+ *
+ *  the jobs are short and never wait on anything: broadcasting and the database are left to the caller, after the
+ *  actor has answered.  before it answers, the actor brings the game's topic up to date with its players (see
+ *  sync_topic), so a broadcast straight after a player joins or leaves goes to the right people.
+ */
+type Job = Box<dyn FnOnce(&mut GameContainer) + Send>;
+type Mailbox = mpsc::Sender<Job>;
+
+/// how many jobs can wait for a game's actor before callers wait to send theirs
+const MAILBOX_SIZE: usize = 64;
 
 lazy_static::lazy_static! {
-    static ref GAME_MAP: Arc<RwLock<HashMap<String, Arc<RwLock<GameContainer>>>>> = Arc::new(RwLock::new(HashMap::new()));
+    static ref GAME_MAP: ShardedMap<Mailbox> = ShardedMap::new(SHARD_COUNT);
 }
 
 pub struct GameContainer {
     game_id: String,
     undo_stack: Vec<RegularGame>,
     redo_stack: Vec<RegularGame>,
+    quarantine: Option<Vec<String>>, // the broken invariants, once the game has been quarantined
+    events: Vec<PersistGameEvent>,   // the events that haven't been written to the database yet (see event_log.rs)
+    members: Vec<String>,            // who was in the game's topic when it was last synced
+    actors: HashMap<u32, String>,    // game_index -> the player whose action made it.  only they can undo it
+    turn_roll: Option<TurnRoll>,     // the dice the current turn rolled, even if the roll was undone
+}
+
+/// a turn's roll.  rolling again after the roll is undone gets the same dice (see rolled_this_turn)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TurnRoll {
+    player_id: String,
+    round: u32,
+    roll: u32,
+}
+
+/// why the actor didn't push a game
+enum PushRefused {
+    Stale(u32),               // the game wasn't made from the current one, which is at this game_index
+    Quarantined(Vec<String>), // the game was already quarantined
+    BrokeInvariants(Vec<String>), // this game broke them, and the game is quarantined now
 }
 
 impl GameContainer {
@@ -30,15 +78,23 @@ impl GameContainer {
         game_id: &str,
         game: &RegularGame,
     ) -> Result<ServiceResponse, ServiceResponse> {
-        let mut game_map = GAME_MAP.write().await; // Acquire write lock
-        if game_map.contains_key(game_id) {
-            return Err(ServiceResponse::new_bad_id("GameId", game_id));
-        }
-
+        let mut game = game.clone();
+        game.checksum = checksum::checksum_of(&game);
         let mut game_container = GameContainer::new(game_id);
         game_container.undo_stack.push(game.clone());
-        game_map.insert(game_id.to_owned(), Arc::new(RwLock::new(game_container)));
+        game_container.sync_topic();
+        game_container.events.push(event_log::new_event(
+            None,
+            &game,
+            "Created",
+            Some(&game.creator_id),
+        ));
 
+        let (mailbox, jobs) = mpsc::channel(MAILBOX_SIZE);
+        if !GAME_MAP.insert_new(game_id, mailbox).await {
+            return Err(ServiceResponse::new_bad_id("GameId", game_id));
+        }
+        tokio::spawn(Self::run(game_container, jobs));
         Ok(ServiceResponse::new_generic_ok("added"))
     }
 
@@ -48,112 +104,688 @@ impl GameContainer {
 
             undo_stack: vec![],
             redo_stack: vec![],
+            quarantine: None,
+            events: vec![],
+            members: vec![],
+            actors: HashMap::new(),
+            turn_roll: None,
         }
     }
 
-    pub async fn get_locked_container(
-        game_id: &str,
-    ) -> Result<Arc<RwLock<GameContainer>>, ServiceResponse> {
-        let game_map = GAME_MAP.read().await; // Acquire read lock
-        match game_map.get(game_id) {
-            Some(container) => Ok(container.clone()),
+    /// put the current players -- but not the bots, who look at the game instead of getting messages -- in the game's
+    /// topic, if they have changed.  the game's languages are kept up to date the same way
+    fn sync_topic(&mut self) {
+        let mut members: Vec<String> = self
+            .current()
+            .players
+            .values()
+            .filter_map(|player| player.profile.user_id.clone())
+            .filter(|user_id| !bots::is_bot(user_id))
+            .collect();
+        members.sort();
+        if members != self.members {
+            LongPoller::set_topic_members(&MessageChannel::Game(self.game_id.clone()), members.clone());
+            self.members = members;
+        }
+        let game = self.current();
+        i18n::sync_game(&self.game_id, game.language, &game.language_overrides);
+    }
+
+    /// the actor: do each job as it comes, until the game is removed from GAME_MAP
+    async fn run(mut game_container: GameContainer, mut jobs: mpsc::Receiver<Job>) {
+        while let Some(job) = jobs.recv().await {
+            job(&mut game_container);
+        }
+        tracing::debug!("actor for {} stopped", game_container.game_id);
+    }
+
+    /// send the job to the game's actor and wait for its answer.  waiting is one of the hot paths we time (see
+    /// profiling.rs)
+    async fn call<T, F>(game_id: &str, job: F) -> Result<T, ServiceResponse>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut GameContainer) -> T + Send + 'static,
+    {
+        let mailbox = GAME_MAP
+            .get(game_id)
+            .await
+            .ok_or_else(|| ServiceResponse::new_bad_id("GameId", game_id))?;
+        let _timer = profiling::timer(HotPath::ContainerLock);
+        let (reply, answer) = oneshot::channel();
+        let job: Job = Box::new(move |game_container| {
+            let answer = job(game_container);
+            game_container.sync_topic();
+            let _ = reply.send(answer);
+        });
+        //  either fails only if the game was removed after we found its mailbox
+        mailbox
+            .send(job)
+            .await
+            .map_err(|_| ServiceResponse::new_bad_id("GameId", game_id))?;
+        answer
+            .await
+            .map_err(|_| ServiceResponse::new_bad_id("GameId", game_id))
+    }
+
+    fn current(&self) -> &RegularGame {
+        self.undo_stack
+            .last()
+            .expect("you cannot have an empty undo stack *and a valid game_id")
+    }
+
+    fn quarantined_response(game_id: &str, violations: &[String]) -> ServiceResponse {
+        ServiceResponse::new(
+            &format!("game {} is quarantined", game_id),
+            reqwest::StatusCode::CONFLICT,
+            ResponseType::ErrorInfo(violations.join("; ")),
+            GameError::ActionError(format!("game {} is quarantined", game_id)),
+        )
+    }
+
+    /// the invariants the game broke, if it has been quarantined
+    pub async fn quarantine(game_id: &str) -> Result<Option<Vec<String>>, ServiceResponse> {
+        Self::call(game_id, |game_container| game_container.quarantine.clone()).await
+    }
+
+    /// the ids of every game in memory
+    pub async fn game_ids() -> Vec<String> {
+        GAME_MAP.keys().await
+    }
+
+    /**
+     *  drop the game from memory.  after this the game_id is no longer valid for any of the game apis.
+     */
+    pub async fn remove_container(game_id: &str) -> Result<(), ServiceResponse> {
+        match GAME_MAP.remove(game_id).await {
+            Some(_) => {
+                LongPoller::drop_topic(&MessageChannel::Game(game_id.to_owned()));
+                i18n::forget_game(game_id);
+                Ok(())
+            }
             None => Err(ServiceResponse::new_bad_id("GameId", game_id)),
         }
     }
 
     /**
-     *  add a player to a game.  takes a write lock and writes a PlayerAdded message to the long poller.  while we have a
-     *  game we could return (which has the players), at this point, I think the UI would be a "Create Game" UI where invites
-     *  have been sent out and the UI reflects updates based on Accept/Reject
+     *  add a player to a game.  while we have a game we could return (which has the players), at this point, I think
+     *  the UI would be a "Create Game" UI where invites have been sent out and the UI reflects updates based on
+     *  Accept/Reject
      */
     pub async fn add_player(
         game_id: &str,
         client_user: &UserProfile,
     ) -> Result<ServiceResponse, ServiceResponse> {
-
-
-        let game_container = Self::get_locked_container(&game_id).await?;
-        let mut game_container = game_container.write().await; // drop locked container
-
-        let game = game_container.undo_stack.last().clone().unwrap(); // you cannot have an empty undo stack *and a valid game_id
-        let clone = game.add_user(client_user)?;
-        game_container.undo_stack.push(clone.clone());
-        Ok(ServiceResponse::new_generic_ok("added"))
+        let client_user = client_user.clone();
+        Self::call(game_id, move |game_container| {
+            let game = game_container.current().clone();
+            let mut clone = game.add_user(&client_user)?;
+            clone.game_index = game.game_index + 1;
+            clone.checksum = checksum::checksum_of(&clone);
+            let event = event_log::new_event(Some(&game), &clone, "AddPlayer", client_user.user_id.as_deref());
+            game_container.events.push(event);
+            if let Some(user_id) = &client_user.user_id {
+                game_container.actors.insert(clone.game_index, user_id.clone());
+            }
+            game_container.undo_stack.push(clone);
+            Ok(ServiceResponse::new_generic_ok("added"))
+        })
+        .await?
     }
     /**
-     *  send the message to all players in game_id
+     *  send the message to the game's topic -- all players in game_id.  bots don't get messages -- they look at the game
+     *  instead
      */
     pub async fn broadcast_message(
         game_id: &str,
         message: &CatanMessage,
     ) -> Result<ServiceResponse, ServiceResponse> {
-        let ids = GameContainer::get_game_players(game_id).await?;
-        LongPoller::send_message(ids, message).await
+        if GAME_MAP.get(game_id).await.is_none() {
+            return Err(ServiceResponse::new_bad_id("GameId", game_id));
+        }
+        LongPoller::send_message(MessageTarget::Topic(MessageChannel::Game(game_id.to_owned())), message).await
     }
 
     pub async fn get_game_players(game_id: &str) -> Result<Vec<String>, ServiceResponse> {
         let mut players = Vec::new();
         let (game, _) = GameContainer::current_game(game_id).await?;
         for p in game.players.values() {
-            players.push(p.profile.user_id.clone().unwrap());
+            let id = p.profile.user_id.clone().unwrap();
+            if !bots::is_bot(&id) {
+                players.push(id);
+            }
         }
 
         Ok(players)
     }
 
-    pub async fn undo(game_id: &String) -> Result<ServiceResponse, ServiceResponse> {
-        let game_container = Self::get_locked_container(game_id).await?;
-        let mut game_container = game_container.write().await;
-        let len = game_container.undo_stack.len();
-        if len < 2 {
-            return Err(ServiceResponse::new(
-                "",
-                reqwest::StatusCode::BAD_REQUEST,
-                ResponseType::NoData,
-                GameError::ActionError(format!("cannot undo first game. undo_stack len {} ", len)),
-            ));
-        }
-        if !game_container.undo_stack.last().unwrap().can_undo {
+    /// the player whose action made the game at game_index
+    fn verify_actor(&self, game_index: u32, caller_id: &str) -> Result<(), ServiceResponse> {
+        if self.actors.get(&game_index).map(String::as_str) != Some(caller_id) {
             return Err(ServiceResponse::new(
-                "",
-                reqwest::StatusCode::BAD_REQUEST,
+                "only the player who took the action can undo or redo it",
+                reqwest::StatusCode::FORBIDDEN,
                 ResponseType::NoData,
-                GameError::ActionError("item in undo stack cannot be undone".to_string()),
+                GameError::HttpError(reqwest::StatusCode::FORBIDDEN),
             ));
         }
-        let game = game_container.undo_stack.pop().unwrap();
+        Ok(())
+    }
 
-        game_container.redo_stack.push(game.clone());
-        let _ = Self::broadcast_message(game_id, &CatanMessage::GameUpdate(game)).await;
+    /**
+     *  go back to the game before the last action.  only the player who took it can -- not another player, and not
+     *  anybody for an action the service took.  the undone game goes on the redo stack and everybody gets the game
+     *  they are back to
+     */
+    pub async fn undo(game_id: &str, caller_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+        let game_id_owned = game_id.to_owned();
+        let caller_id = caller_id.to_owned();
+        let current = Self::call(game_id, move |game_container| {
+            if let Some(violations) = &game_container.quarantine {
+                return Err(Self::quarantined_response(&game_id_owned, violations));
+            }
+            let len = game_container.undo_stack.len();
+            if len < 2 {
+                return Err(ServiceResponse::new(
+                    "",
+                    reqwest::StatusCode::BAD_REQUEST,
+                    ResponseType::NoData,
+                    GameError::ActionError(format!("cannot undo first game. undo_stack len {} ", len)),
+                ));
+            }
+            if !game_container.current().can_undo {
+                return Err(ServiceResponse::new(
+                    "",
+                    reqwest::StatusCode::BAD_REQUEST,
+                    ResponseType::NoData,
+                    GameError::ActionError(format!(
+                        "the game's undo policy doesn't allow undo in {:?}",
+                        game_container.current().game_state
+                    )),
+                ));
+            }
+            game_container.verify_actor(game_container.current().game_index, &caller_id)?;
+            let game = game_container.undo_stack.pop().unwrap();
+            game_container.redo_stack.push(game);
+            Ok(game_container.current().clone())
+        })
+        .await??;
+        Self::broadcast_game(&current).await;
         Ok(ServiceResponse::new_generic_ok(""))
     }
 
-    pub async fn current_game(game_id: &str) -> Result<(RegularGame, bool), ServiceResponse> {
-        match Self::get_locked_container(game_id).await {
-            Ok(game_container) => {
-                let ro_container = game_container.read().await;
-                Ok((
-                    ro_container.undo_stack.last().unwrap().clone(),
-                    ro_container.redo_stack.len() > 0,
-                ))
+    /**
+     *  put back the last game that was undone -- the game as it was, so a roll that is redone has the same dice.  only
+     *  the player who took the action can.  any new action clears the redo stack (see push_game)
+     */
+    pub async fn redo(game_id: &str, caller_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+        let game_id_owned = game_id.to_owned();
+        let caller_id = caller_id.to_owned();
+        let game = Self::call(game_id, move |game_container| {
+            if let Some(violations) = &game_container.quarantine {
+                return Err(Self::quarantined_response(&game_id_owned, violations));
             }
-            Err(_) => Err(ServiceResponse::new(
+            let game_index = game_container.redo_stack.last().map(|game| game.game_index).ok_or_else(|| {
+                ServiceResponse::new(
+                    "",
+                    reqwest::StatusCode::BAD_REQUEST,
+                    ResponseType::NoData,
+                    GameError::ActionError("there is nothing to redo".to_string()),
+                )
+            })?;
+            game_container.verify_actor(game_index, &caller_id)?;
+            let game = game_container.redo_stack.pop().expect("the redo stack was just looked at");
+            game_container.undo_stack.push(game.clone());
+            Ok(game)
+        })
+        .await??;
+        Self::broadcast_game(&game).await;
+        Ok(ServiceResponse::new_generic_ok(""))
+    }
+
+    /**
+     *  the snapshot of the game with this game_index, if the container still has it -- either on the undo stack or
+     *  undone and waiting to be redone
+     */
+    pub async fn snapshot(game_id: &str, game_index: u32) -> Result<RegularGame, ServiceResponse> {
+        Self::call(game_id, move |game_container| {
+            game_container
+                .undo_stack
+                .iter()
+                .chain(game_container.redo_stack.iter())
+                .find(|game| game.game_index == game_index)
+                .cloned()
+        })
+        .await?
+        .ok_or_else(|| {
+            ServiceResponse::new(
+                &format!("game {} has no snapshot {}", game_id, game_index),
+                reqwest::StatusCode::NOT_FOUND,
+                ResponseType::NoData,
+                GameError::BadId(format!("{}:{}", game_id, game_index)),
+            )
+        })
+    }
+
+    /// every snapshot on the undo stack, oldest first -- what the game went through to get where it is
+    pub async fn history(game_id: &str) -> Result<Vec<RegularGame>, ServiceResponse> {
+        Self::call(game_id, |game_container| game_container.undo_stack.clone()).await
+    }
+
+    /// the events that haven't been written yet.  whoever takes them writes them (see event_log::flush)
+    pub async fn take_events(game_id: &str) -> Result<Vec<PersistGameEvent>, ServiceResponse> {
+        Self::take_events_into(game_id, |events| events).await
+    }
+
+    /// take_events, handing them to `into` in the actor -- so events two callers take at once are handed over in the
+    /// order they were taken
+    pub async fn take_events_into<T, F>(game_id: &str, into: F) -> Result<T, ServiceResponse>
+    where
+        T: Send + 'static,
+        F: FnOnce(Vec<PersistGameEvent>) -> T + Send + 'static,
+    {
+        Self::call(game_id, move |game_container| into(std::mem::take(&mut game_container.events))).await
+    }
+
+    /**
+     *  the dice the current turn already rolled, if it did -- even if the roll has since been undone, and whatever has
+     *  happened since.  a roll can be taken back, but not rolled again for better numbers
+     */
+    pub async fn rolled_this_turn(game_id: &str) -> Result<Option<u32>, ServiceResponse> {
+        Self::call(game_id, |game_container| {
+            let current = game_container.current();
+            game_container
+                .turn_roll
+                .as_ref()
+                .filter(|turn| turn.player_id == current.current_player_id && turn.round == current.rounds_played)
+                .map(|turn| turn.roll)
+        })
+        .await
+    }
+
+    pub async fn current_game(game_id: &str) -> Result<(RegularGame, bool), ServiceResponse> {
+        Self::call(game_id, |game_container| {
+            (
+                game_container.current().clone(),
+                !game_container.redo_stack.is_empty(),
+            )
+        })
+        .await
+        .map_err(|_| {
+            ServiceResponse::new(
                 "",
                 reqwest::StatusCode::NOT_FOUND,
                 ResponseType::NoData,
                 GameError::BadId(format!("{} not found", game_id)),
+            )
+        })
+    }
+
+    /// the actor's half of push_game
+    fn push(
+        &mut self,
+        game: RegularGame,
+        action: &str,
+        actor_id: Option<&str>,
+        on_behalf_of: Option<&str>,
+    ) -> Result<RegularGame, PushRefused> {
+        if let Some(violations) = &self.quarantine {
+            return Err(PushRefused::Quarantined(violations.clone()));
+        }
+        //  the game was made from the one at its game_index.  if another push got in first, this one would undo it
+        let current_index = self.current().game_index;
+        if game.game_index != current_index {
+            return Err(PushRefused::Stale(current_index));
+        }
+        let violations = game.invariant_violations();
+        if !violations.is_empty() {
+            self.quarantine = Some(violations.clone());
+            return Err(PushRefused::BrokeInvariants(violations));
+        }
+        let mut game = game;
+        game.can_undo = game.options.undo_policy.allows(game.game_state);
+        let game = self.append(game, action, actor_id, |event| {
+            event.on_behalf_of = on_behalf_of.map(|id| id.to_owned())
+        });
+        //  a move made for somebody's seat is theirs to undo
+        if let Some(seat) = on_behalf_of {
+            self.actors.insert(game.game_index, seat.to_owned());
+        }
+        Ok(game)
+    }
+
+    /// put the game on top of the undo stack, with the next game_index, and log its event
+    fn append(
+        &mut self,
+        mut game: RegularGame,
+        action: &str,
+        actor_id: Option<&str>,
+        annotate: impl FnOnce(&mut PersistGameEvent),
+    ) -> RegularGame {
+        //  every snapshot gets the next index, so support can refer to (and diff) them.  see snapshot_diff.rs
+        let previous = self.undo_stack.last();
+        game.game_index = previous.map_or(1, |last| last.game_index + 1);
+        game.checksum = checksum::checksum_of(&game);
+        let mut event = event_log::new_event(previous, &game, action, actor_id);
+        annotate(&mut event);
+        self.events.push(event);
+        match actor_id {
+            Some(actor_id) => self.actors.insert(game.game_index, actor_id.to_owned()),
+            None => self.actors.remove(&game.game_index),
+        };
+        if let Some(roll) = game.last_roll {
+            self.turn_roll = Some(TurnRoll {
+                player_id: game.current_player_id.clone(),
+                round: game.rounds_played,
+                roll,
+            });
+        }
+        self.undo_stack.push(game.clone());
+        self.redo_stack.clear();
+        game
+    }
+
+    /**
+     *  an admin's repair of a game (see surgery.rs).  the ops are applied to the current game, which has to be the one
+     *  the admin was looking at, and the repaired game has to pass the invariants.  it goes in even if the game is
+     *  quarantined -- that is what it is for -- and ends the quarantine.  the repair can't be undone, and its event has
+     *  the admin and their reason.  with dry_run nothing changes; the game it would be is returned either way
+     */
+    pub async fn operate(
+        game_id: &str,
+        request: &SurgeryRequest,
+        admin_id: &str,
+        dry_run: bool,
+    ) -> Result<RegularGame, ServiceResponse> {
+        let request = request.clone();
+        let admin_id = admin_id.to_owned();
+        Self::call(game_id, move |game_container| {
+            let current = game_container.current();
+            if current.game_index != request.base_game_index {
+                return Err(ServiceResponse::new(
+                    &format!(
+                        "the game is at {}, not {}.  look at it again",
+                        current.game_index, request.base_game_index
+                    ),
+                    reqwest::StatusCode::CONFLICT,
+                    ResponseType::NoData,
+                    GameError::ActionError("the game has moved on".to_owned()),
+                ));
+            }
+            let mut game = surgery::apply(current, &request.ops).map_err(|e| {
+                ServiceResponse::new(
+                    "the patch can't be applied",
+                    reqwest::StatusCode::BAD_REQUEST,
+                    ResponseType::ErrorInfo(format!("{:?}", e)),
+                    e,
+                )
+            })?;
+            let violations = game.invariant_violations();
+            if !violations.is_empty() {
+                return Err(ServiceResponse::new(
+                    "the patched game breaks the invariants",
+                    reqwest::StatusCode::BAD_REQUEST,
+                    ResponseType::ErrorInfo(violations.join("; ")),
+                    GameError::BadActionData(violations.join("; ")),
+                ));
+            }
+            if dry_run {
+                return Ok(game);
+            }
+            game.can_undo = false;
+            game_container.quarantine = None;
+            let note = request.reason.clone();
+            Ok(game_container.append(game, "Surgery", Some(&admin_id), |event| event.note = Some(note)))
+        })
+        .await?
+    }
+
+    /**
+     *  every action ends here, so this is where the game invariants are checked.  a game that breaks one has been
+     *  corrupted by a bug: rather than store the bad state and let it spread, the game is quarantined -- the last good
+     *  state is kept, every later push (and undo) is refused, the players are told, and the admins are notified
+     *  through the logs and error reporting.
+     *
+     *  the game has to have been made from the current one -- its game_index is the one it was made from.  if another
+     *  push got in since, it is refused with a 409, as surgery is, and the caller looks at the game again.
+     *
+     *  action and actor_id (None when the service did it) go in the game's event log.  returns the game that was
+     *  pushed, with its game_index
+     */
+    pub async fn push_game(
+        game_id: &str,
+        game: &RegularGame,
+        action: &str,
+        actor_id: Option<&str>,
+    ) -> Result<RegularGame, ServiceResponse> {
+        Self::push_game_for(game_id, game, action, actor_id, None).await
+    }
+
+    /**
+     *  push_game, for an action actor_id took for another player -- whose turns were handed to them (see
+     *  delegation.rs).  the event log has both
+     */
+    pub async fn push_game_for(
+        game_id: &str,
+        game: &RegularGame,
+        action: &str,
+        actor_id: Option<&str>,
+        on_behalf_of: Option<&str>,
+    ) -> Result<RegularGame, ServiceResponse> {
+        let game = game.clone();
+        let game_index = game.game_index;
+        let action = action.to_owned();
+        let actor_id = actor_id.map(|id| id.to_owned());
+        let on_behalf_of = on_behalf_of.map(|id| id.to_owned());
+        let pushed = Self::call(game_id, move |game_container| {
+            game_container.push(game, &action, actor_id.as_deref(), on_behalf_of.as_deref())
+        })
+        .await?;
+        match pushed {
+            Ok(game) => {
+                Self::broadcast_game(&game).await;
+                Ok(game)
+            }
+            Err(PushRefused::Stale(current_index)) => Err(ServiceResponse::new(
+                &format!("the game is at {}, not {}.  look at it again", current_index, game_index),
+                reqwest::StatusCode::CONFLICT,
+                ResponseType::NoData,
+                GameError::ActionError("the game has moved on".to_owned()),
             )),
+            Err(PushRefused::Quarantined(violations)) => {
+                Err(Self::quarantined_response(game_id, &violations))
+            }
+            Err(PushRefused::BrokeInvariants(violations)) => {
+                tracing::error!(game = %game_id, ?violations, "game quarantined");
+                error_reporting::report_quarantined_game(game_id, &violations);
+                let _ = Self::broadcast_message(
+                    game_id,
+                    &CatanMessage::Error(ErrorData {
+                        status_code: reqwest::StatusCode::CONFLICT.as_u16() as i32,
+                        message: format!("game {} has been stopped because of a server error", game_id),
+                    }),
+                )
+                .await;
+                Err(Self::quarantined_response(game_id, &violations))
+            }
         }
     }
 
-    pub async fn push_game(game_id: &str, game: &RegularGame) -> Result<(), ServiceResponse> {
-        let game_container = Self::get_locked_container(game_id).await?;
-        let mut rw_game_container = game_container.write().await;
-        let game_clone = game.clone();
-        rw_game_container.undo_stack.push(game_clone);
-        rw_game_container.redo_stack.clear();
-        drop(rw_game_container);
-        let _ = Self::broadcast_message(game_id, &CatanMessage::GameUpdate(game.clone())).await;
-        Ok(())
+    /**
+     *  send the game to every player in it.  each player gets their own copy, with what they aren't allowed to see
+     *  about the other players taken out (see RegularGame::redacted_for)
+     */
+    pub async fn broadcast_game(game: &RegularGame) {
+        let _timer = profiling::timer(HotPath::Broadcast);
+        for user_id in game.players.keys().filter(|id| !bots::is_bot(id)) {
+            let _ = LongPoller::send_to_channel(
+                vec![user_id.clone()],
+                &MessageChannel::Game(game.id.clone()),
+                &CatanMessage::GameUpdate(game.redacted_for(user_id)),
+            )
+            .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games_service::shared::{game_enums::GameState, game_models::UndoPolicy};
+
+    #[tokio::test]
+    async fn test_actor_orders_pushes() {
+        let game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        let game_id = game.id.clone();
+        GameContainer::create_and_add_container(&game_id, &game)
+            .await
+            .expect("new game id");
+        assert!(GameContainer::create_and_add_container(&game_id, &game).await.is_err());
+
+        //  pushes from many requests at once are done one at a time.  they were all made from the same game, so the
+        //  first one in gets the next game_index and the rest are told the game has moved on
+        let pushes: Vec<_> = (0..10)
+            .map(|_| {
+                let game = game.clone();
+                tokio::spawn(async move { GameContainer::push_game(&game.id, &game, "Test", None).await })
+            })
+            .collect();
+        let mut indexes = Vec::new();
+        let mut conflicts = 0;
+        for push in pushes {
+            match push.await.unwrap() {
+                Ok(pushed) => indexes.push(pushed.game_index),
+                Err(sr) => {
+                    assert_eq!(sr.status, reqwest::StatusCode::CONFLICT);
+                    conflicts += 1;
+                }
+            }
+        }
+        assert_eq!(indexes, vec![game.game_index + 1]);
+        assert_eq!(conflicts, 9);
+        assert_eq!(GameContainer::history(&game_id).await.unwrap().len(), 2);
+        assert_eq!(GameContainer::take_events(&game_id).await.unwrap().len(), 2);
+
+        //  made from the game that is there now, the next one goes in
+        let (current, _) = GameContainer::current_game(&game_id).await.unwrap();
+        let pushed = GameContainer::push_game(&game_id, &current, "Test", None).await.unwrap();
+        assert_eq!(pushed.game_index, game.game_index + 2);
+
+        GameContainer::remove_container(&game_id).await.unwrap();
+        assert!(GameContainer::current_game(&game_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_undo_policy() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        game.options.undo_policy = UndoPolicy {
+            setup: true,
+            before_roll: true,
+            after_roll: false,
+        };
+        let game_id = game.id.clone();
+        GameContainer::create_and_add_container(&game_id, &game)
+            .await
+            .expect("new game id");
+
+        game.game_state = GameState::WaitingForRoll;
+        let mut pushed = GameContainer::push_game(&game_id, &game, "Test", Some("1")).await.unwrap();
+        assert!(pushed.can_undo);
+        pushed.game_state = GameState::BuyingAndTrading;
+        let mut pushed = GameContainer::push_game(&game_id, &pushed, "Roll", Some("1")).await.unwrap();
+        assert!(!pushed.can_undo);
+        assert!(GameContainer::undo(&game_id, "1").await.is_err());
+
+        //  the next turn can be undone again
+        pushed.game_state = GameState::WaitingForRoll;
+        GameContainer::push_game(&game_id, &pushed, "Next", Some("1")).await.unwrap();
+        assert!(GameContainer::undo(&game_id, "1").await.is_ok());
+        let (current, _) = GameContainer::current_game(&game_id).await.unwrap();
+        assert_eq!(current.game_state, GameState::BuyingAndTrading);
+
+        GameContainer::remove_container(&game_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undo_is_the_actors() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        game.options.undo_policy = UndoPolicy {
+            setup: true,
+            before_roll: true,
+            after_roll: true,
+        };
+        let game_id = game.id.clone();
+        GameContainer::create_and_add_container(&game_id, &game)
+            .await
+            .expect("new game id");
+
+        //  nobody can undo what the service did
+        game.game_state = GameState::WaitingForRoll;
+        let mut pushed = GameContainer::push_game(&game_id, &game, "Test", None).await.unwrap();
+        assert_eq!(
+            GameContainer::undo(&game_id, "1").await.unwrap_err().status,
+            reqwest::StatusCode::FORBIDDEN
+        );
+
+        //  only the player who rolled can take the roll back, or put it back
+        pushed.game_state = GameState::BuyingAndTrading;
+        pushed.last_roll = Some(8);
+        GameContainer::push_game(&game_id, &pushed, "Roll", Some("1")).await.unwrap();
+        assert_eq!(
+            GameContainer::undo(&game_id, "2").await.unwrap_err().status,
+            reqwest::StatusCode::FORBIDDEN
+        );
+        assert!(GameContainer::undo(&game_id, "1").await.is_ok());
+        assert_eq!(
+            GameContainer::redo(&game_id, "2").await.unwrap_err().status,
+            reqwest::StatusCode::FORBIDDEN
+        );
+
+        //  the turn rolled 8, and still has -- rolling again, or redoing the roll, gets the same dice
+        assert_eq!(GameContainer::rolled_this_turn(&game_id).await.unwrap(), Some(8));
+        assert!(GameContainer::redo(&game_id, "1").await.is_ok());
+        let (current, _) = GameContainer::current_game(&game_id).await.unwrap();
+        assert_eq!(current.last_roll, Some(8));
+
+        GameContainer::remove_container(&game_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_last_seat_race() {
+        let game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        let game_id = game.id.clone();
+        GameContainer::create_and_add_container(&game_id, &game)
+            .await
+            .expect("new game id");
+        for id in ["2", "3"].iter() {
+            GameContainer::add_player(&game_id, &UserProfile::new_test_user(Some(id.to_string())))
+                .await
+                .unwrap();
+        }
+
+        //  two players go for the last seat at once: one gets it, the other is told who is in the game
+        let joins: Vec<_> = ["4", "5"]
+            .iter()
+            .map(|id| {
+                let game_id = game_id.clone();
+                let profile = UserProfile::new_test_user(Some(id.to_string()));
+                tokio::spawn(async move { GameContainer::add_player(&game_id, &profile).await })
+            })
+            .collect();
+        let mut losers = Vec::new();
+        for join in joins {
+            if let Err(sr) = join.await.unwrap() {
+                losers.push(sr);
+            }
+        }
+        assert_eq!(losers.len(), 1);
+        assert_eq!(losers[0].status, reqwest::StatusCode::CONFLICT);
+        let (current, _) = GameContainer::current_game(&game_id).await.unwrap();
+        assert_eq!(current.players.len(), 4);
+        match &losers[0].game_error {
+            GameError::GameFull(players) => assert_eq!(players.len(), 4),
+            other => panic!("expected GameFull, got {:?}", other),
+        }
+
+        GameContainer::remove_container(&game_id).await.unwrap();
     }
 }
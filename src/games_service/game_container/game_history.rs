@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+/**
+ *  the replay api: every action pushes a snapshot onto the game's undo stack (see GameContainer::push_game), so the
+ *  history of a game is the list of changes between one snapshot and the next.  clients page through the changes and
+ *  play them over the board to replay (or scrub through) a game.
+ *
+ *  while the game is in memory the history comes from its undo stack.  when the game ends the history is stored with
+ *  the final game (see CleanupStep::PersistFinalState), so it can still be replayed after the container is evicted.
+ *  undone actions aren't part of the history.
+ */
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::catan_games::games::regular::regular_game::RegularGame,
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+use super::{
+    game_container::GameContainer,
+    snapshot_diff::{diff_games, GameSnapshotDiff},
+};
+
+/// how many changes are returned when the caller doesn't say, and the most they can ask for
+pub const DEFAULT_HISTORY_PAGE: usize = 50;
+pub const MAX_HISTORY_PAGE: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub offset: Option<usize>,
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct GameHistory {
+    pub game_id: String,
+    pub total: usize,  // how many changes the game has
+    pub offset: usize, // the first change in this page
+    pub changes: Vec<GameSnapshotDiff>,
+    pub latest: Option<RegularGame>, // the game as it is now (or ended), for the board.  only on the first page
+}
+
+/// the changes from each snapshot to the next
+pub fn history_of(snapshots: &[RegularGame]) -> Vec<GameSnapshotDiff> {
+    snapshots
+        .windows(2)
+        .map(|pair| diff_games(&pair[0], &pair[1]))
+        .collect()
+}
+
+fn page(
+    game_id: &str,
+    changes: Vec<GameSnapshotDiff>,
+    latest: RegularGame,
+    query: &HistoryQuery,
+) -> GameHistory {
+    let total = changes.len();
+    let offset = query.offset.unwrap_or(0).min(total);
+    let count = query
+        .count
+        .unwrap_or(DEFAULT_HISTORY_PAGE)
+        .min(MAX_HISTORY_PAGE);
+    GameHistory {
+        game_id: game_id.to_owned(),
+        total,
+        offset,
+        changes: changes.into_iter().skip(offset).take(count).collect(),
+        latest: if offset == 0 { Some(latest) } else { None },
+    }
+}
+
+/**
+ *  a page of the game's history.  only the players in the game (and admins) can see it.  the game comes from memory
+ *  if it is still there, and from the database once it has ended and been evicted
+ */
+pub async fn game_history(
+    game_id: &str,
+    query: &HistoryQuery,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let caller_id = match &request_context.claims {
+        Some(claims) => claims.id.clone(),
+        None => return new_unauthorized_response!(""),
+    };
+    let (changes, latest) = match GameContainer::history(game_id).await {
+        Ok(snapshots) => {
+            let latest = snapshots.last().cloned().expect("the undo stack is never empty");
+            (history_of(&snapshots), latest)
+        }
+        Err(_) => {
+            let persisted = request_context.database.find_game_by_id(game_id).await?;
+            (persisted.history, persisted.game)
+        }
+    };
+    if !latest.players.contains_key(&caller_id) && !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("only the players in a game can see its history");
+    }
+    let latest = latest.redacted_for(&caller_id);
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::GameHistory(page(game_id, changes, latest, query)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn game_history_handler(
+    game_id: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    game_history(&game_id, &query, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{catan_games::traits::game_trait::GameTrait, shared::game_enums::GameState},
+        shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_history_pages() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        let mut snapshots = vec![game.clone()];
+        for id in ["2", "3"].iter() {
+            GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some(id.to_string())));
+            game.game_index += 1;
+            snapshots.push(game.clone());
+        }
+        game.game_state = GameState::ChoosingBoard;
+        game.game_index += 1;
+        snapshots.push(game.clone());
+
+        let changes = history_of(&snapshots);
+        assert_eq!(changes.len(), 3);
+        assert!(changes[0].players[0].joined);
+        assert_eq!(changes[2].game_state.as_ref().unwrap().to, "ChoosingBoard");
+
+        let first = page("game", changes.clone(), game.clone(), &HistoryQuery { offset: None, count: Some(2) });
+        assert_eq!((first.total, first.offset, first.changes.len()), (3, 0, 2));
+        assert!(first.latest.is_some());
+        let second = page("game", changes.clone(), game.clone(), &HistoryQuery { offset: Some(2), count: Some(2) });
+        assert_eq!(second.changes, changes[2..].to_vec());
+        assert!(second.latest.is_none());
+        let past_the_end = page("game", changes, game, &HistoryQuery { offset: Some(10), count: None });
+        assert_eq!((past_the_end.offset, past_the_end.changes.len()), (3, 0));
+    }
+}
@@ -0,0 +1,154 @@
+#![allow(dead_code)]
+/**
+ *  the map from game_id to its GameContainer.  every game api looks its container up here, so a single
+ *  RwLock<HashMap> made every call in the service wait on every game being created or evicted.  instead the map is
+ *  split into SHARD_COUNT shards by a hash of the game_id, each with its own lock: a write only blocks the games that
+ *  hash to the same shard, and lookups in different shards never wait on each other.
+ *
+ *  the shard lock is only held long enough to clone what is stored -- the games themselves are looked after by their
+ *  own actors (see GameContainer::call).
+ */
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use tokio::sync::RwLock;
+
+/// enough that a handful of busy games rarely share a shard, few enough that walking every shard is cheap
+pub const SHARD_COUNT: usize = 32;
+
+pub struct ShardedMap<V> {
+    shards: Vec<RwLock<HashMap<String, Arc<V>>>>,
+}
+
+impl<V> ShardedMap<V> {
+    pub fn new(shard_count: usize) -> Self {
+        Self {
+            shards: (0..shard_count.max(1))
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    fn shard(&self, key: &str) -> &RwLock<HashMap<String, Arc<V>>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[(hasher.finish() as usize) % self.shards.len()]
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Arc<V>> {
+        self.shard(key).read().await.get(key).cloned()
+    }
+
+    /// adds the value unless the key is already there.  returns false (and drops the value) if it was
+    pub async fn insert_new(&self, key: &str, value: V) -> bool {
+        let mut shard = self.shard(key).write().await;
+        if shard.contains_key(key) {
+            return false;
+        }
+        shard.insert(key.to_owned(), Arc::new(value));
+        true
+    }
+
+    pub async fn remove(&self, key: &str) -> Option<Arc<V>> {
+        self.shard(key).write().await.remove(key)
+    }
+
+    /// every key, shard by shard.  keys added or removed while this runs may or may not be in it
+    pub async fn keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        for shard in self.shards.iter() {
+            keys.extend(shard.read().await.keys().cloned());
+        }
+        keys
+    }
+
+    pub async fn len(&self) -> usize {
+        let mut len = 0;
+        for shard in self.shards.iter() {
+            len += shard.read().await.len();
+        }
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_sharded_map() {
+        let map: ShardedMap<u32> = ShardedMap::new(4);
+        assert!(map.insert_new("a", 1).await);
+        assert!(!map.insert_new("a", 2).await);
+        assert!(map.insert_new("b", 3).await);
+        assert_eq!(*map.get("a").await.unwrap(), 1);
+        assert_eq!(map.len().await, 2);
+        let mut keys = map.keys().await;
+        keys.sort();
+        assert_eq!(keys, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(*map.remove("a").await.unwrap(), 1);
+        assert!(map.get("a").await.is_none());
+        assert!(map.remove("a").await.is_none());
+    }
+
+    /// readers of many games while one writer keeps creating and evicting games -- the way the service uses the map.
+    /// the writer holds the lock for a while each time, the way a slow create or reload does
+    async fn contended_lookups(map: Arc<ShardedMap<u32>>) -> Duration {
+        const GAMES: usize = 64;
+        const READERS: usize = 16;
+        const LOOKUPS: usize = 2_000;
+        for game in 0..GAMES {
+            map.insert_new(&format!("game-{}", game), game as u32).await;
+        }
+        let start = Instant::now();
+        let writer_map = map.clone();
+        let writer = tokio::spawn(async move {
+            for n in 0..200 {
+                let key = format!("new-{}", n);
+                let shard = writer_map.shard(&key).write().await;
+                tokio::time::sleep(Duration::from_micros(200)).await;
+                drop(shard);
+                writer_map.insert_new(&key, 0).await;
+                writer_map.remove(&key).await;
+            }
+        });
+        let readers: Vec<_> = (0..READERS)
+            .map(|reader| {
+                let map = map.clone();
+                tokio::spawn(async move {
+                    for n in 0..LOOKUPS {
+                        let key = format!("game-{}", (reader * 7 + n) % GAMES);
+                        assert!(map.get(&key).await.is_some());
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        writer.await.unwrap();
+        elapsed
+    }
+
+    /// a benchmark, not a test: `cargo test bench_sharded_lookups --release -- --ignored --nocapture`.  one shard is the
+    /// old single RwLock<HashMap>
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    #[ignore]
+    async fn bench_sharded_lookups() {
+        let single = contended_lookups(Arc::new(ShardedMap::new(1))).await;
+        let sharded = contended_lookups(Arc::new(ShardedMap::new(SHARD_COUNT))).await;
+        println!(
+            "single lock: {:?}  {} shards: {:?}  ({:.1}x)",
+            single,
+            SHARD_COUNT,
+            sharded,
+            single.as_secs_f64() / sharded.as_secs_f64()
+        );
+        assert!(sharded < single);
+    }
+}
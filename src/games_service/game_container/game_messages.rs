@@ -1,12 +1,26 @@
 #![allow(dead_code)]
 
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde_with::serde_as;
 
-use crate::games_service::catan_games::games::regular::regular_game::RegularGame;
+use crate::games_service::{
+    achievements::achievements::AchievementUnlockedData,
+    chat::chat::ChatMessage,
+    catan_games::games::regular::regular_game::RegularGame,
+    long_poller::channels::ChannelMessage,
+    shared::{
+        game_enums::DevCardType,
+        game_models::{CardHolder, LedgerReason, PendingDevCard, ResourceCards},
+    },
+};
+use crate::games_service::actions::reconnect::ConnectionData;
+use crate::games_service::long_poller::presence::PresenceData;
+use crate::user_service::{direct_messages::DirectMessage, sessions::SessionEndedData};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Invitation {
     pub from_id: String,
@@ -16,6 +30,8 @@ pub struct Invitation {
     pub message: String,
     pub from_picture: String,
     pub game_id: String,
+    #[serde(default)]
+    pub token: String, // signed by the service when the invite is sent (see invitation_token.rs) -- leave it empty
 }
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(rename_all = "PascalCase")]
@@ -29,9 +45,11 @@ impl GameHeader {
     pub const EMAIL: &'static str = "x-email";
     pub const ROLES: &'static str = "x-roles";
     pub const CLAIMS: &'static str= "x-claims";
+    pub const CORRELATION_ID: &'static str = "x-correlation-id";
+    pub const CHECKSUM: &'static str = "x-game-checksum"; // "{game_index}:{checksum}" (see checksum.rs)
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct InvitationResponseData {
     pub from_id: String,
@@ -40,6 +58,8 @@ pub struct InvitationResponseData {
     pub to_name: String,
     pub game_id: String,
     pub accepted: bool,
+    #[serde(default)]
+    pub token: String, // the token from the Invitation being answered
 }
 impl InvitationResponseData {
     pub fn new(
@@ -49,9 +69,11 @@ impl InvitationResponseData {
         to_name: &str,
         accepted: bool,
         game_id: &str,
+        token: &str,
     ) -> Self {
         Self {
             accepted,
+            token: token.into(),
             to_id: to.into(),
             from_id: from.into(),
             game_id: game_id.into(),
@@ -67,25 +89,197 @@ impl InvitationResponseData {
             accepted: accepted,
             from_name: invite.to_name.clone(),
             to_name: invite.from_name.clone(),
+            token: invite.token.clone(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct GameCreatedData {
     pub user_id: String,
     pub game_id: String,
 }
 
+///
+/// a player the creator took out of the game before it started.  banned players can't join it again
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayerRemovedData {
+    pub game_id: String,
+    pub user_id: String,
+    pub banned: bool,
+    #[serde(default)]
+    pub user_name: String,
+    #[serde(default)]
+    pub text: String, // in the reader's language for the game (see i18n.rs)
+}
+
+///
+/// the game has a new host (see host.rs): the creator left and to_id has their privileges now
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct HostChangedData {
+    pub game_id: String,
+    pub from_id: String,
+    pub to_id: String,
+    #[serde(default)]
+    pub to_name: String,
+    #[serde(default)]
+    pub text: String, // in the reader's language for the game (see i18n.rs)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayerScore {
+    pub user_id: String,
+    pub victory_points: u32, // includes victory point cards -- once the game is over there is nothing to hide
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct GameWonData {
+    pub game_id: String,
+    pub winner_id: String,
+    pub winner_name: String,
+    pub scores: Vec<PlayerScore>, // highest score first
+    #[serde(default)]
+    pub text: String, // in the reader's language for the game (see i18n.rs)
+}
+
+impl GameWonData {
+    /// None if the game doesn't have a winner
+    pub fn from_game(game: &RegularGame) -> Option<Self> {
+        let winner_id = game.winner_id.clone()?;
+        let winner_name = game
+            .players
+            .get(&winner_id)
+            .map(|player| player.profile.display_name.clone())
+            .unwrap_or_default();
+        let mut scores: Vec<PlayerScore> = game
+            .players
+            .keys()
+            .map(|id| PlayerScore {
+                user_id: id.clone(),
+                victory_points: game.victory_points(id),
+            })
+            .collect();
+        scores.sort_by(|a, b| b.victory_points.cmp(&a.victory_points));
+        Some(Self {
+            game_id: game.id.clone(),
+            winner_id,
+            winner_name,
+            scores,
+            text: String::default(),
+        })
+    }
+}
+
+///
+/// what happened in a turn, sent to everybody in the game when the turn ends.  the cards and the builds come from the
+/// game's ledger, so they add up to what actually moved
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct TurnSummary {
+    pub game_id: String,
+    pub user_id: String, // the player whose turn it was
+    #[serde(default)]
+    pub user_name: String,
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, ResourceCards)>")]
+    pub gained: HashMap<String, ResourceCards>, // user_id -> the cards they got this turn
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, ResourceCards)>")]
+    pub spent: HashMap<String, ResourceCards>, // user_id -> the cards they gave up this turn
+    pub roads_built: u32,
+    pub settlements_built: u32,
+    pub cities_built: u32,
+    pub ships_built: u32,
+    pub dev_card_played: Option<DevCardType>,
+    #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(String, i32)>")]
+    pub score_changes: HashMap<String, i32>, // user_id -> public victory points won (or lost) this turn
+    #[serde(default)]
+    pub text: String, // in the reader's language for the game (see i18n.rs)
+}
+
+impl TurnSummary {
+    /// the summary of the current turn -- call it before the turn ends, since ending the turn clears the ledger
+    pub fn from_game(game: &RegularGame) -> Self {
+        let mut gained: HashMap<String, ResourceCards> = HashMap::new();
+        let mut spent: HashMap<String, ResourceCards> = HashMap::new();
+        let (mut roads_built, mut settlements_built, mut cities_built, mut ships_built) =
+            (0, 0, 0, 0);
+        for entry in &game.ledger {
+            if let CardHolder::Player(id) = &entry.from {
+                spent.entry(id.clone()).or_default().add_cards(&entry.cards);
+            }
+            if let CardHolder::Player(id) = &entry.to {
+                gained.entry(id.clone()).or_default().add_cards(&entry.cards);
+            }
+            //  every piece has a different cost, so the payment says what was bought
+            if entry.reason == LedgerReason::Purchase {
+                if entry.cards == RegularGame::road_cost() {
+                    roads_built += 1;
+                } else if entry.cards == RegularGame::settlement_cost() {
+                    settlements_built += 1;
+                } else if entry.cards == RegularGame::city_cost() {
+                    cities_built += 1;
+                } else if entry.cards == RegularGame::ship_cost() {
+                    ships_built += 1;
+                }
+            }
+        }
+        let score_changes = game
+            .players
+            .keys()
+            .map(|id| {
+                let start = game.turn_start_scores.get(id).cloned().unwrap_or(0);
+                (id.clone(), game.known_score(id) as i32 - start as i32)
+            })
+            .filter(|(_, change)| *change != 0)
+            .collect();
+        Self {
+            game_id: game.id.clone(),
+            user_id: game.current_player_id.clone(),
+            user_name: game
+                .players
+                .get(&game.current_player_id)
+                .map(|player| player.profile.display_name.clone())
+                .unwrap_or_default(),
+            gained,
+            spent,
+            roads_built,
+            settlements_built,
+            cities_built,
+            ships_built,
+            dev_card_played: game.dev_card_played,
+            score_changes,
+            text: String::default(),
+        }
+    }
+}
+
+///
+/// everything a player needs to pick a game back up after losing their connection: the game as it is now, and the
+/// messages on the game's channel they may not have seen
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
+pub struct RejoinState {
+    pub game: RegularGame,            // redacted for the player
+    pub last_sequence: u64,           // the sequence of the last message sent on the game's channel
+    pub missed: Vec<ChannelMessage>,  // the messages after the sequence the client asked from, oldest first
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
 pub struct ErrorData {
     pub status_code: i32,
     pub message: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub enum CatanMessage {
     GameUpdate(RegularGame),
@@ -93,8 +287,21 @@ pub enum CatanMessage {
     InvitationResponse(InvitationResponseData),
     GameCreated(GameCreatedData),
     PlayerAdded(Vec<String>),
+    PlayerRemoved(PlayerRemovedData), // sent to the players left in the game and to the player that was removed
+    HostChanged(HostChangedData),
     Started(String),
     Ended(String),
+    GameWon(GameWonData),
+    ResolveDevCard(PendingDevCard), // sent only to the player who has to choose the resource(s) for the card
+    TurnSummary(TurnSummary),
+    AchievementUnlocked(AchievementUnlockedData), // sent only to the player who unlocked it
+    Chat(ChatMessage),
+    DirectMessage(DirectMessage), // sent only to the user it is for, on the sender's direct channel
+    SessionEnded(SessionEndedData), // sent to the user whose session was ended by a newer login
+    PresenceChanged(PresenceData), // sent to everybody connected when a user comes online or goes offline
+    PlayerDisconnected(ConnectionData), // sent to the game when a player's connection drops (see reconnect.rs)
+    PlayerReconnected(ConnectionData),  // sent to the game when they come back
+    Heartbeat(u64), // answers an open wait that has had nothing else for a while.  the service's time, in seconds
     Error(ErrorData),
 }
 impl fmt::Debug for CatanMessage {
@@ -113,8 +320,55 @@ impl fmt::Debug for CatanMessage {
             }
             CatanMessage::GameCreated(data) => write!(f, "GameCreated: {:?}", data),
             CatanMessage::PlayerAdded(players) => write!(f, "PlayerAdded: {:?}", players),
+            CatanMessage::PlayerRemoved(removed) => write!(f, "PlayerRemoved: {:?}", removed),
+            CatanMessage::HostChanged(changed) => write!(f, "HostChanged: {:?}", changed),
             CatanMessage::Started(started) => write!(f, "Started: {}", started),
             CatanMessage::Ended(ended) => write!(f, "Ended: {}", ended),
+            CatanMessage::GameWon(won) => write!(f, "GameWon: [id={}] [winner={}]", won.game_id, won.winner_id),
+            CatanMessage::ResolveDevCard(pending) => write!(f, "ResolveDevCard: {:?}", pending),
+            CatanMessage::TurnSummary(summary) => {
+                write!(f, "TurnSummary: [id={}] [user={}]", summary.game_id, summary.user_id)
+            }
+            CatanMessage::AchievementUnlocked(data) => write!(
+                f,
+                "AchievementUnlocked: [user={}] [achievement={:?}]",
+                data.user_id, data.achievement
+            ),
+            CatanMessage::Chat(chat) => write!(
+                f,
+                "Chat: [game={}] [from={}] [length={}]",
+                chat.game_id,
+                chat.from_id,
+                chat.text.len()
+            ),
+            CatanMessage::DirectMessage(dm) => write!(
+                f,
+                "DirectMessage: [from={}] [to={}] [length={}]",
+                dm.from_id,
+                dm.to_id,
+                dm.text.len()
+            ),
+            CatanMessage::SessionEnded(data) => write!(
+                f,
+                "SessionEnded: [user={}] [session={}]",
+                data.user_id, data.session_id
+            ),
+            CatanMessage::PresenceChanged(presence) => write!(
+                f,
+                "PresenceChanged: [user={}] [online={}]",
+                presence.user_id, presence.online
+            ),
+            CatanMessage::PlayerDisconnected(data) => write!(
+                f,
+                "PlayerDisconnected: [game={}] [user={}]",
+                data.game_id, data.user_id
+            ),
+            CatanMessage::PlayerReconnected(data) => write!(
+                f,
+                "PlayerReconnected: [game={}] [user={}] [away={}]",
+                data.game_id, data.user_id, data.away_seconds
+            ),
+            CatanMessage::Heartbeat(now) => write!(f, "Heartbeat: {}", now),
             CatanMessage::Error(error) => write!(f, "Error: {:?}", error),
         }
     }
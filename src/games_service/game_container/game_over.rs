@@ -0,0 +1,340 @@
+#![allow(dead_code)]
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use crate::{
+    azure_setup::azure_wrapper::send_email,
+    games_service::{
+        actions::reconnect,
+        catan_games::games::regular::regular_game::RegularGame,
+        lobby::join_codes,
+        long_poller::{channels::MessageChannel, long_poller::LongPoller},
+        webhooks::{self, WebhookEvent},
+    },
+    middleware::{request_context_mw::RequestContext, service_config::SERVICE_CONFIG},
+    shared::{
+        error_reporting,
+        service_models::{PersistGame, PersistGameStats},
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+    user_service::push_notifications::{self, PushNotification},
+};
+
+use super::{game_container::GameContainer, game_history::history_of, game_messages::CatanMessage};
+
+/**
+ *  the steps we run, in order, when a game ends.  each step is retried on its own so that (say) a Cosmos hiccup while
+ *  updating stats doesn't keep the container from being evicted.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupStep {
+    PersistFinalState, // the final game, players and winner -- this doubles as the match history
+    UpdateStats,       // games played/won in each player's profile
+    NotifyPlayers,     // Ended over the long poller, or an email for players that aren't logged in
+    EvictContainer,    // after the grace period, drop the game from GAME_MAP
+}
+
+/// how many of a player's last games are remembered as counted -- far more than can end while one is being retried
+pub const COUNTED_GAMES_KEPT: usize = 20;
+
+pub const CLEANUP_STEPS: [CleanupStep; 4] = [
+    CleanupStep::PersistFinalState,
+    CleanupStep::UpdateStats,
+    CleanupStep::NotifyPlayers,
+    CleanupStep::EvictContainer,
+];
+
+#[derive(Debug, Clone)]
+pub struct CleanupStepResult {
+    pub step: CleanupStep,
+    pub attempts: u32,
+    pub result: Result<(), ServiceResponse>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GameOverPipeline {
+    pub max_attempts: u32,
+    pub retry_delay: Duration,  // doubled after every failed attempt
+    pub eviction_grace: Duration, // how long clients have to fetch the final game before it is evicted
+}
+
+impl Default for GameOverPipeline {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(500),
+            eviction_grace: Duration::from_secs(60),
+        }
+    }
+}
+
+impl GameOverPipeline {
+    /**
+     *  kick off the pipeline without making the caller wait for it.  the RequestContext is cloned so that the pipeline
+     *  uses the same database (test or production) as the request that ended the game.
+     */
+    pub fn start(&self, game: &RegularGame, winner_id: Option<String>, request_context: &RequestContext) {
+        let pipeline = self.clone();
+        let game = game.clone();
+        let request_context = request_context.clone();
+        actix_web::rt::spawn(async move {
+            let results = pipeline.run(&game, winner_id, &request_context).await;
+            for step in results.iter().filter(|r| r.result.is_err()) {
+                let message = format!(
+                    "game over cleanup for {} failed at {:?} after {} attempts: {:#?}",
+                    game.id, step.step, step.attempts, step.result
+                );
+                tracing::error!("{}", message);
+                error_reporting::report_background_failure("game_over", &message);
+            }
+        });
+    }
+
+    /**
+     *  run every step, in order, and report how each one went.  a step that runs out of attempts is reported as
+     *  failed and the pipeline moves on to the next one.
+     */
+    pub async fn run(
+        &self,
+        game: &RegularGame,
+        winner_id: Option<String>,
+        request_context: &RequestContext,
+    ) -> Vec<CleanupStepResult> {
+        let mut results = Vec::new();
+        for step in CLEANUP_STEPS.iter() {
+            let mut attempts = 0;
+            let mut delay = self.retry_delay;
+            let result = loop {
+                attempts += 1;
+                let result = self
+                    .run_step(*step, game, &winner_id, request_context)
+                    .await;
+                if result.is_ok() || attempts >= self.max_attempts {
+                    break result;
+                }
+                tracing::warn!(
+                    "{:?} failed for game {} (attempt {} of {}). retrying in {:?}",
+                    step,
+                    game.id,
+                    attempts,
+                    self.max_attempts,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            };
+            results.push(CleanupStepResult {
+                step: *step,
+                attempts,
+                result,
+            });
+        }
+        results
+    }
+
+    async fn run_step(
+        &self,
+        step: CleanupStep,
+        game: &RegularGame,
+        winner_id: &Option<String>,
+        request_context: &RequestContext,
+    ) -> Result<(), ServiceResponse> {
+        match step {
+            CleanupStep::PersistFinalState => {
+                let mut persist_game = PersistGame::from_game(game, winner_id.clone());
+                //  the container is still here -- it isn't evicted until the last step
+                if let Ok(snapshots) = GameContainer::history(&game.id).await {
+                    persist_game.history = history_of(&snapshots);
+                }
+                request_context
+                    .database
+                    .update_or_create_game(&persist_game)
+                    .await?;
+            }
+            CleanupStep::UpdateStats => {
+                for player_id in game.players.keys() {
+                    let mut persist_user =
+                        match request_context.database.find_user_by_id(player_id).await {
+                            Ok(user) => user,
+                            // players that aren't in the database (eg. deleted) don't have stats to update
+                            Err(e) if e.status == StatusCode::NOT_FOUND => continue,
+                            Err(e) => return Err(e),
+                        };
+                    //  the stats are upserted by game and player, so writing them again is harmless
+                    if let Some(stats) =
+                        PersistGameStats::from_game(game, player_id, winner_id.as_deref())
+                    {
+                        request_context.database.update_game_stats(&stats).await?;
+                    }
+                    //  the counts go up with the game's id, in one write: a retry after that write finds the id and
+                    //  doesn't count the game twice
+                    if persist_user.counted_games.contains(&game.id) {
+                        continue;
+                    }
+                    let profile = &mut persist_user.user_profile;
+                    profile.games_played = Some(profile.games_played.unwrap_or(0) + 1);
+                    if winner_id.as_deref() == Some(player_id.as_str()) {
+                        profile.games_won = Some(profile.games_won.unwrap_or(0) + 1);
+                    }
+                    persist_user.counted_games.push(game.id.clone());
+                    let extra = persist_user.counted_games.len().saturating_sub(COUNTED_GAMES_KEPT);
+                    persist_user.counted_games.drain(..extra);
+                    request_context
+                        .database
+                        .update_or_create_user(&persist_user)
+                        .await?;
+                }
+            }
+            CleanupStep::NotifyPlayers => {
+                let player_ids: Vec<String> = game.players.keys().cloned().collect();
+                let offline_ids = match LongPoller::send_message(
+                    player_ids.clone(),
+                    &CatanMessage::Ended(game.id.clone()),
+                )
+                .await
+                {
+                    Ok(_) => vec![],
+                    Err(sr) => match &sr.response_type {
+                        ResponseType::SendMessageError(errors) => {
+                            errors.iter().map(|(id, _)| id.clone()).collect()
+                        }
+                        _ => return Err(sr),
+                    },
+                };
+                push_notifications::notify(
+                    &player_ids,
+                    &game.id,
+                    |language| PushNotification::game_over(&game.id, language),
+                    request_context,
+                );
+                webhooks::fire(&game.id, WebhookEvent::GameOver, None, winner_id.as_deref(), request_context);
+                self.email_offline_players(game, &offline_ids);
+            }
+            CleanupStep::EvictContainer => {
+                tokio::time::sleep(self.eviction_grace).await;
+                let player_ids: Vec<String> = game.players.keys().cloned().collect();
+                LongPoller::forget_channel(&player_ids, &MessageChannel::Game(game.id.clone())).await;
+                join_codes::forget(&game.id);
+                webhooks::forget(&game.id);
+                reconnect::forget(&game.id);
+                match GameContainer::remove_container(&game.id).await {
+                    Ok(_) => {}
+                    // somebody else already evicted it, which is what we wanted anyway
+                    Err(e) if matches!(e.game_error, GameError::BadId(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// players that aren't logged in don't have a long poller, so we send them an email if they have validated one.
+    /// this is best effort -- an email failure is logged, but doesn't fail the step (a retry would re-send the Ended
+    /// message to everybody else)
+    fn email_offline_players(&self, game: &RegularGame, offline_ids: &[String]) {
+        for id in offline_ids {
+            let profile = match game.players.get(id) {
+                Some(player) => &player.profile,
+                None => continue,
+            };
+            let email = match &profile.pii {
+                Some(pii) if profile.validated_email => pii.email.clone(),
+                _ => continue,
+            };
+            let (subject, msg) = SERVICE_CONFIG.branding.game_ended_email(&game.id);
+            if let Err(e) = send_email(&email, &SERVICE_CONFIG.service_email, &subject, &msg) {
+                tracing::warn!("failed to email {} that game {} ended: {}", id, game.id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::{service_models::PersistUser, shared_models::UserProfile};
+
+    #[tokio::test]
+    async fn test_game_over_pipeline() {
+        let request_context = RequestContext::test_default(false);
+        let creator = UserProfile::new_test_user(None);
+        let other = UserProfile::new_test_user(None);
+        let mut game = RegularGame::new(&creator)
+            .add_user(&other)
+            .expect("add_user should work");
+        game.game_state = crate::games_service::shared::game_enums::GameState::GameOver;
+
+        for profile in [&creator, &other] {
+            let persist_user = PersistUser::from_user_profile(profile, "hash".to_string());
+            request_context
+                .database
+                .update_or_create_user(&persist_user)
+                .await
+                .expect("adding to the mocked db should work");
+        }
+        GameContainer::create_and_add_container(&game.id, &game)
+            .await
+            .expect("new game id");
+
+        let pipeline = GameOverPipeline {
+            max_attempts: 2,
+            retry_delay: Duration::from_millis(0),
+            eviction_grace: Duration::from_millis(0),
+        };
+        let winner_id = creator.user_id.clone();
+        let results = pipeline.run(&game, winner_id.clone(), &request_context).await;
+
+        assert_eq!(results.len(), CLEANUP_STEPS.len());
+        for result in results.iter() {
+            assert!(result.result.is_ok(), "{:?} failed: {:#?}", result.step, result);
+            assert_eq!(result.attempts, 1);
+        }
+
+        let persist_game = request_context
+            .database
+            .find_game_by_id(&game.id)
+            .await
+            .expect("final state should be saved");
+        assert_eq!(persist_game.winner_id, winner_id);
+        assert_eq!(persist_game.player_ids.len(), 2);
+
+        let winner = request_context
+            .database
+            .find_user_by_id(creator.user_id.as_ref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(winner.user_profile.games_played, Some(1));
+        assert_eq!(winner.user_profile.games_won, Some(1));
+        let loser = request_context
+            .database
+            .find_user_by_id(other.user_id.as_ref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(loser.user_profile.games_played, Some(1));
+        assert_eq!(loser.user_profile.games_won, None);
+        let stats = request_context
+            .database
+            .find_game_stats(other.user_id.as_ref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].game_id.as_str(), stats[0].won), (game.id.as_str(), false));
+
+        assert!(GameContainer::current_game(&game.id).await.is_err());
+
+        // running it again is harmless -- the game isn't counted twice, and evicting a game that is already gone is
+        // not an error
+        let results = pipeline.run(&game, None, &request_context).await;
+        assert!(results.iter().all(|r| r.result.is_ok()));
+        let winner = request_context
+            .database
+            .find_user_by_id(creator.user_id.as_ref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(winner.user_profile.games_played, Some(1));
+        assert_eq!(winner.user_profile.games_won, Some(1));
+        assert_eq!(winner.counted_games, vec![game.id.clone()]);
+    }
+}
@@ -1,2 +1,9 @@
+pub mod checksum;
+pub mod event_log;
 pub mod game_container;
+pub mod game_history;
+pub mod game_map;
 pub mod game_messages;
+pub mod game_over;
+pub mod snapshot_diff;
+pub mod surgery;
@@ -0,0 +1,245 @@
+#![allow(dead_code)]
+/**
+ *  a support tool: what changed in a game between two of its snapshots?  every push to the game container gets the
+ *  next game_index, so "the state jumped between 41 and 42" can be answered with a short list of changes instead of
+ *  by reading two copies of the game side by side.
+ *
+ *  only the snapshots the container still has can be compared: the ones on the undo stack and the ones that were
+ *  undone and can still be redone.
+ */
+use std::collections::HashSet;
+
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::{
+        catan_games::games::regular::regular_game::RegularGame, player::player::Player,
+        shared::game_models::ResourceCards,
+    },
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+use super::game_container::GameContainer;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ValueChange {
+    pub from: String,
+    pub to: String,
+}
+
+impl ValueChange {
+    /// None if the value didn't change
+    fn of<T: std::fmt::Debug + PartialEq>(from: &T, to: &T) -> Option<Self> {
+        if from == to {
+            None
+        } else {
+            Some(Self {
+                from: format!("{:?}", from),
+                to: format!("{:?}", to),
+            })
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayerSnapshotDiff {
+    pub user_id: String,
+    pub joined: bool,
+    pub left: bool,
+    pub resources_before: ResourceCards,
+    pub resources_after: ResourceCards,
+    pub buildings_added: Vec<String>, // eg. "City BuildingKey { ... }".  an upgrade is a Settlement removed and a City added
+    pub buildings_removed: Vec<String>,
+    pub roads_added: Vec<String>,
+    pub roads_removed: Vec<String>,
+    pub dev_cards: Option<ValueChange>,
+    pub score: Option<ValueChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct GameSnapshotDiff {
+    pub game_id: String,
+    pub from_index: u32,
+    pub to_index: u32,
+    pub game_state: Option<ValueChange>,
+    pub current_player: Option<ValueChange>,
+    pub baron_tile: Option<ValueChange>,
+    pub pending_discards: Option<ValueChange>,
+    pub players: Vec<PlayerSnapshotDiff>, // only the players that changed, by user id
+}
+
+fn buildings(player: Option<&Player>) -> HashSet<String> {
+    player.map_or(HashSet::new(), |player| {
+        player
+            .buildings
+            .iter()
+            .map(|building| format!("{:?} {:?}", building.state, building.building_key))
+            .collect()
+    })
+}
+
+fn roads(player: Option<&Player>) -> HashSet<String> {
+    player.map_or(HashSet::new(), |player| {
+        player
+            .roads
+            .iter()
+            .map(|road| format!("{:?} {:?}", road.state(), road.primary_key()))
+            .collect()
+    })
+}
+
+fn sorted_difference(a: &HashSet<String>, b: &HashSet<String>) -> Vec<String> {
+    let mut difference: Vec<String> = a.difference(b).cloned().collect();
+    difference.sort();
+    difference
+}
+
+fn diff_player(user_id: &str, from: &RegularGame, to: &RegularGame) -> Option<PlayerSnapshotDiff> {
+    let before = from.players.get(user_id);
+    let after = to.players.get(user_id);
+    let resources = |player: Option<&Player>| player.map_or(ResourceCards::default(), |p| p.resources.clone());
+    let dev_cards = |player: Option<&Player>| player.map_or(0, |p| p.dev_cards.len());
+    let score = |game: &RegularGame, player: Option<&Player>| player.map_or(0, |_| game.known_score(user_id));
+
+    let diff = PlayerSnapshotDiff {
+        user_id: user_id.to_owned(),
+        joined: before.is_none() && after.is_some(),
+        left: before.is_some() && after.is_none(),
+        resources_before: resources(before),
+        resources_after: resources(after),
+        buildings_added: sorted_difference(&buildings(after), &buildings(before)),
+        buildings_removed: sorted_difference(&buildings(before), &buildings(after)),
+        roads_added: sorted_difference(&roads(after), &roads(before)),
+        roads_removed: sorted_difference(&roads(before), &roads(after)),
+        dev_cards: ValueChange::of(&dev_cards(before), &dev_cards(after)),
+        score: ValueChange::of(&score(from, before), &score(to, after)),
+    };
+    let unchanged = PlayerSnapshotDiff {
+        user_id: diff.user_id.clone(),
+        resources_before: diff.resources_before.clone(),
+        resources_after: diff.resources_before.clone(),
+        ..Default::default()
+    };
+    if diff == unchanged {
+        None
+    } else {
+        Some(diff)
+    }
+}
+
+/// what changed between two snapshots of the same game
+pub fn diff_games(from: &RegularGame, to: &RegularGame) -> GameSnapshotDiff {
+    let mut user_ids: Vec<&String> = from.players.keys().chain(to.players.keys()).collect();
+    user_ids.sort();
+    user_ids.dedup();
+    let mut discards_before: Vec<(&String, &u32)> = from.pending_discards.iter().collect();
+    let mut discards_after: Vec<(&String, &u32)> = to.pending_discards.iter().collect();
+    discards_before.sort();
+    discards_after.sort();
+
+    GameSnapshotDiff {
+        game_id: to.id.clone(),
+        from_index: from.game_index,
+        to_index: to.game_index,
+        game_state: ValueChange::of(&from.game_state, &to.game_state),
+        current_player: ValueChange::of(&from.current_player_id, &to.current_player_id),
+        baron_tile: ValueChange::of(&from.baron_tile, &to.baron_tile),
+        pending_discards: ValueChange::of(&discards_before, &discards_after),
+        players: user_ids
+            .into_iter()
+            .filter_map(|user_id| diff_player(user_id, from, to))
+            .collect(),
+    }
+}
+
+/// diff two snapshots of a game by their game_index -- admin only
+pub async fn snapshot_diff(
+    game_id: &str,
+    from_index: u32,
+    to_index: u32,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    let from = GameContainer::snapshot(game_id, from_index).await?;
+    let to = GameContainer::snapshot(game_id, to_index).await?;
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::SnapshotDiff(diff_games(&from, &to)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn snapshot_diff_handler(
+    path: web::Path<(String, u32, u32)>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let (game_id, from_index, to_index) = path.into_inner();
+    snapshot_diff(&game_id, from_index, to_index, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{
+            buildings::{
+                building_enums::{BuildingPosition, BuildingState},
+                building_key::BuildingKey,
+            },
+            catan_games::traits::game_trait::GameTrait,
+            shared::{game_enums::GameState, game_models::LedgerReason},
+            tiles::tile_key::TileKey,
+        },
+        shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_diff_games() {
+        let mut from = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut from, &UserProfile::new_test_user(Some("2".to_string())));
+        assert_eq!(diff_games(&from, &from).players, vec![]);
+
+        let mut to = from.clone();
+        to.game_index = from.game_index + 1;
+        to.game_state = GameState::WaitingForRoll;
+        to.take_from_bank("1", &ResourceCards::new(1, 0, 0, 2, 0), LedgerReason::Roll)
+            .unwrap();
+        let key = BuildingKey::new(BuildingPosition::TopRight, TileKey::new(0, 0, 0));
+        to.place_building("2", &key, BuildingState::Settlement).unwrap();
+        GameTrait::add_user(&mut to, &UserProfile::new_test_user(Some("3".to_string())));
+
+        let diff = diff_games(&from, &to);
+        assert_eq!(diff.to_index, from.game_index + 1);
+        assert_eq!(
+            diff.game_state,
+            Some(ValueChange {
+                from: "AddingPlayers".to_owned(),
+                to: "WaitingForRoll".to_owned()
+            })
+        );
+        assert_eq!(diff.baron_tile, None);
+        let ids: Vec<&str> = diff.players.iter().map(|p| p.user_id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        assert_eq!(diff.players[0].resources_after, ResourceCards::new(1, 0, 0, 2, 0));
+        assert!(diff.players[0].buildings_added.is_empty());
+        assert_eq!(diff.players[1].buildings_added.len(), 1);
+        assert!(diff.players[1].buildings_added[0].starts_with("Settlement"));
+        assert!(diff.players[2].joined);
+    }
+}
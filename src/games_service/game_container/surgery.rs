@@ -0,0 +1,319 @@
+#![allow(dead_code)]
+/**
+ *  for the bugs a game can't recover from by itself -- a card that went missing, a baron on the wrong tile, a turn
+ *  that is stuck -- an admin can repair the live game by hand:
+ *
+ *      POST /auth/api/v1/admin/games/{game_id}/surgery?dry_run={true|false}
+ *      {"BaseGameIndex": 42, "Reason": "the roll at 41 paid 2 wheat to the bank", "Ops": [{"GiveResources": {...}}]}
+ *
+ *  the ops are applied, in order, to the game as it is at BaseGameIndex -- if it has moved on since the admin looked at
+ *  it the repair is refused, so nobody patches a game they haven't seen.  every op is checked (the player is in the
+ *  game, the tile is on the board, the bank has the cards, no count is more than the game has, the state is one a
+ *  regular game can be in and the current player still takes turns), and then so is the whole game, with the same
+ *  invariants every action has to pass (see invariants.rs).  nothing is changed unless all of that passes.
+ *
+ *  the repaired game is pushed like any other -- the players get it right away -- even if the game is quarantined,
+ *  and ends the quarantine.  it can't be undone.  the repair goes in the game's event log with the admin's id, their
+ *  Reason and the diff of what changed, and is logged as a warning.  with ?dry_run=true the repaired game is returned
+ *  and nothing is changed.
+ */
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::{
+        catan_games::games::regular::{bank::RESOURCE_CARDS_PER_TYPE, regular_game::RegularGame},
+        game_container::{event_log, game_container::GameContainer},
+        shared::{
+            game_enums::GameState,
+            game_models::{LedgerReason, ResourceCards},
+        },
+        tiles::tile_key::TileKey,
+    },
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+/// a reason has to say something
+pub const MIN_REASON_LENGTH: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum SurgeryOp {
+    #[serde(rename_all = "PascalCase")]
+    GiveResources { user_id: String, cards: ResourceCards }, // from the bank
+    #[serde(rename_all = "PascalCase")]
+    TakeResources { user_id: String, cards: ResourceCards }, // back to the bank
+    #[serde(rename_all = "PascalCase")]
+    SetResources { user_id: String, cards: ResourceCards }, // the bank isn't touched: for cards that were lost or made up
+    #[serde(rename_all = "PascalCase")]
+    SetBank { cards: ResourceCards },
+    #[serde(rename_all = "PascalCase")]
+    MoveBaron { tile: TileKey },
+    #[serde(rename_all = "PascalCase")]
+    SetGameState { game_state: GameState },
+    #[serde(rename_all = "PascalCase")]
+    SetCurrentPlayer { user_id: String },
+    ClearPendingDiscards,
+    ClearOpenTrades,
+    ClearPendingDevCard,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SurgeryRequest {
+    pub base_game_index: u32, // the game the admin looked at
+    pub reason: String,       // goes in the event log
+    pub ops: Vec<SurgeryOp>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SurgeryQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn check_player(game: &RegularGame, user_id: &str) -> Result<(), GameError> {
+    if game.players.contains_key(user_id) {
+        Ok(())
+    } else {
+        Err(GameError::BadId(format!("{} isn't playing in {}", user_id, game.id)))
+    }
+}
+
+/// no count can be more than there are cards of that resource in the game -- a made up count can't overflow the
+/// invariant check's sums
+fn check_counts(cards: &ResourceCards) -> Result<(), GameError> {
+    match ResourceCards::RESOURCES
+        .iter()
+        .find(|resource| cards.count(**resource) > RESOURCE_CARDS_PER_TYPE)
+    {
+        Some(resource) => Err(GameError::BadActionData(format!(
+            "there are only {} {:?} cards, not {}",
+            RESOURCE_CARDS_PER_TYPE,
+            resource,
+            cards.count(*resource)
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// a state a live regular game can be in.  Supplemental isn't played yet, and a game can only be over with a winner
+fn check_game_state(game: &RegularGame, game_state: GameState) -> Result<(), GameError> {
+    match game_state {
+        GameState::Supplemental => Err(GameError::BadActionData(format!(
+            "a regular game can't be in the {:?} state",
+            game_state
+        ))),
+        GameState::GameOver if game.winner_id.is_none() => Err(GameError::BadActionData(
+            "a game can't be over without a winner".to_owned(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// the game with the ops applied, or what is wrong with the first one that can't be
+pub fn apply(game: &RegularGame, ops: &[SurgeryOp]) -> Result<RegularGame, GameError> {
+    if ops.is_empty() {
+        return Err(GameError::MissingData("there is nothing to do".to_owned()));
+    }
+    let mut game = game.clone();
+    for op in ops {
+        match op {
+            SurgeryOp::GiveResources { user_id, cards } => {
+                check_player(&game, user_id)?;
+                game.take_from_bank(user_id, cards, LedgerReason::Surgery)?;
+            }
+            SurgeryOp::TakeResources { user_id, cards } => {
+                check_player(&game, user_id)?;
+                game.return_to_bank(user_id, cards, LedgerReason::Surgery)?;
+            }
+            SurgeryOp::SetResources { user_id, cards } => {
+                check_player(&game, user_id)?;
+                check_counts(cards)?;
+                if let Some(player) = game.players.get_mut(user_id) {
+                    player.resources = cards.clone();
+                }
+            }
+            SurgeryOp::SetBank { cards } => {
+                check_counts(cards)?;
+                game.bank = cards.clone();
+            }
+            SurgeryOp::MoveBaron { tile } => {
+                if !game.tiles.contains_key(tile) {
+                    return Err(GameError::BadId(format!("{:?} isn't on the board", tile)));
+                }
+                game.baron_tile = *tile;
+            }
+            SurgeryOp::SetGameState { game_state } => {
+                check_game_state(&game, *game_state)?;
+                game.game_state = *game_state;
+            }
+            SurgeryOp::SetCurrentPlayer { user_id } => {
+                check_player(&game, user_id)?;
+                if !game.player_order.contains(user_id) || game.forfeited.contains(user_id) {
+                    return Err(GameError::BadId(format!("{} doesn't take turns in {}", user_id, game.id)));
+                }
+                game.current_player_id = user_id.clone();
+            }
+            SurgeryOp::ClearPendingDiscards => {
+                game.pending_discards.clear();
+                game.discard_deadline = None;
+            }
+            SurgeryOp::ClearOpenTrades => game.open_trades.clear(),
+            SurgeryOp::ClearPendingDevCard => game.pending_dev_card = None,
+        }
+    }
+    Ok(game)
+}
+
+/// repair the game -- admins only
+pub async fn operate(
+    game_id: &str,
+    request: &SurgeryRequest,
+    dry_run: bool,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    if request.reason.trim().len() < MIN_REASON_LENGTH {
+        return Err(ServiceResponse::new(
+            "say why the game is being changed",
+            StatusCode::BAD_REQUEST,
+            ResponseType::NoData,
+            GameError::MissingData("Reason".to_owned()),
+        ));
+    }
+    let admin_id = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call")
+        .id
+        .clone();
+    let game = GameContainer::operate(game_id, request, &admin_id, dry_run).await?;
+    if !dry_run {
+        tracing::warn!(
+            game = %game_id,
+            admin = %admin_id,
+            reason = %request.reason,
+            ops = ?request.ops,
+            "game repaired by hand at {}",
+            game.game_index
+        );
+        GameContainer::broadcast_game(&game).await;
+        event_log::flush(game_id, request_context).await;
+    }
+    Ok(ServiceResponse::new(
+        if dry_run { "dry run" } else { "repaired" },
+        StatusCode::OK,
+        ResponseType::Game(game),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn surgery_handler(
+    game_id: web::Path<String>,
+    query: web::Query<SurgeryQuery>,
+    request: web::Json<SurgeryRequest>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    operate(&game_id, &request, query.dry_run, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{games_service::catan_games::traits::game_trait::GameTrait, shared::shared_models::UserProfile};
+
+    fn request(base_game_index: u32, ops: Vec<SurgeryOp>) -> SurgeryRequest {
+        SurgeryRequest {
+            base_game_index,
+            reason: "testing the surgery api".to_owned(),
+            ops,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_surgery() {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        let game_id = game.id.clone();
+        GameContainer::create_and_add_container(&game_id, &game)
+            .await
+            .expect("new game id");
+        let wheat = ResourceCards::new(0, 0, 0, 2, 0);
+
+        // ops that don't make sense are refused, and so are ones that leave a game that couldn't happen
+        let bad_player = vec![SurgeryOp::GiveResources {
+            user_id: "nobody".to_owned(),
+            cards: wheat.clone(),
+        }];
+        assert!(apply(&game, &bad_player).is_err());
+        assert!(apply(&game, &[]).is_err());
+        let set_state = |game_state| vec![SurgeryOp::SetGameState { game_state }];
+        assert!(apply(&game, &set_state(GameState::Supplemental)).is_err());
+        assert!(apply(&game, &set_state(GameState::GameOver)).is_err());
+        assert!(apply(&game, &set_state(GameState::ChoosingBoard)).is_ok());
+        let mut won = game.clone();
+        won.winner_id = Some("1".to_owned());
+        assert!(apply(&won, &set_state(GameState::GameOver)).is_ok());
+        let overflowing = ResourceCards::new(u32::MAX, 0, 0, 0, 0);
+        let too_many = vec![SurgeryOp::SetResources {
+            user_id: "2".to_owned(),
+            cards: overflowing.clone(),
+        }];
+        assert!(matches!(apply(&game, &too_many), Err(GameError::BadActionData(_))));
+        assert!(apply(&game, &[SurgeryOp::SetBank { cards: overflowing }]).is_err());
+        let set_player = |user_id: &str| vec![SurgeryOp::SetCurrentPlayer { user_id: user_id.to_owned() }];
+        assert!(apply(&game, &set_player("2")).is_err()); // no player order yet
+        let mut ordered = game.clone();
+        ordered.player_order = vec!["1".to_owned(), "2".to_owned()];
+        assert!(apply(&ordered, &set_player("2")).is_ok());
+        ordered.forfeited.push("2".to_owned());
+        assert!(apply(&ordered, &set_player("2")).is_err());
+        let made_up = request(
+            game.game_index,
+            vec![SurgeryOp::SetResources {
+                user_id: "2".to_owned(),
+                cards: wheat.clone(),
+            }],
+        );
+        assert!(GameContainer::operate(&game_id, &made_up, "admin", false).await.is_err());
+
+        // a repair of a game that has moved on is refused
+        let give = vec![SurgeryOp::GiveResources {
+            user_id: "2".to_owned(),
+            cards: wheat.clone(),
+        }];
+        assert!(GameContainer::operate(&game_id, &request(game.game_index + 1, give.clone()), "admin", false)
+            .await
+            .is_err());
+
+        // a dry run changes nothing
+        let base = request(game.game_index, give);
+        let dry = GameContainer::operate(&game_id, &base, "admin", true).await.unwrap();
+        assert_eq!(dry.players["2"].resources, wheat);
+        let (current, _) = GameContainer::current_game(&game_id).await.unwrap();
+        assert_eq!(current.game_index, game.game_index);
+
+        let repaired = GameContainer::operate(&game_id, &base, "admin", false).await.unwrap();
+        assert_eq!(repaired.game_index, game.game_index + 1);
+        assert_eq!(repaired.players["2"].resources, wheat);
+        assert!(!repaired.can_undo);
+        let events = GameContainer::take_events(&game_id).await.unwrap();
+        let event = events.last().unwrap();
+        assert_eq!(event.action, "Surgery");
+        assert_eq!(event.actor_id.as_deref(), Some("admin"));
+        assert_eq!(event.note.as_deref(), Some("testing the surgery api"));
+
+        GameContainer::remove_container(&game_id).await.unwrap();
+    }
+}
@@ -1,11 +1,17 @@
-use crate::
-   middleware::{request_context_mw::RequestContext, header_extractor::HeadersExtractor};
+use crate::{
+    middleware::{header_extractor::HeadersExtractor, request_context_mw::RequestContext},
+    shared::i18n::Language,
+};
 use actix_web::{
     web::{self, Path},
     HttpResponse,
 };
+use serde::Deserialize;
 
-use crate::games_service::shared::game_enums::CatanGames;
+use crate::games_service::shared::{
+    game_enums::{CatanGames, GameVisibility},
+    game_models::{CustomBoardData, GameOptions},
+};
 
 use super::catan_games::games::regular::regular_game::RegularGame;
 
@@ -13,13 +19,23 @@ use super::catan_games::games::regular::regular_game::RegularGame;
 /// check the state to make sure the request is valid
 /// randomize the board and the harbors
 /// post the response to websocket
-pub async fn shuffle_game(game_id: web::Path<String>) -> HttpResponse {
-    super::game::shuffle_game(&game_id)
+pub async fn shuffle_game(game_id: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    super::game::shuffle_game(&game_id, &claims.id)
         .await
         .map(|sr| sr.to_http_response())
         .unwrap_or_else(|sr| sr.to_http_response())
 }
 
+#[derive(Debug, Deserialize)]
+pub struct NewGameQuery {
+    pub visibility: Option<GameVisibility>, // ?visibility=private for a game that can be joined with a code
+    pub webhook: Option<String>,            // ?webhook={https url} to be told about the game's events (see webhooks.rs)
+}
+
 ///
 /// creates a new game and returns a gamedId that is used for all subsequent game* apis.
 /// the user header is filled in by the auth middleware.  a JWT token from login must be
@@ -27,6 +43,7 @@ pub async fn shuffle_game(game_id: web::Path<String>) -> HttpResponse {
 /// cames can be run at the same time.
 pub async fn new_game(
     game_type: Path<CatanGames>,
+    query: web::Query<NewGameQuery>,
     headers: HeadersExtractor,
     test_game: Option<web::Json<RegularGame>>,
     request_context: RequestContext
@@ -35,7 +52,114 @@ pub async fn new_game(
     let claims = request_context.claims.as_ref().expect("if claims can't unwrap, the call should fail in the auth middleware");
    
     let test_game: Option<RegularGame> = test_game.map(|json_game| json_game.into_inner());
-    super::game::new_game(game_type, &claims.id, headers.is_test, test_game, &request_context)
+    let visibility = query.visibility.unwrap_or_default();
+    super::game::new_game(
+        game_type,
+        visibility,
+        query.webhook.as_deref(),
+        &claims.id,
+        headers.is_test,
+        test_game,
+        &request_context,
+    )
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+///
+/// sets the victory point target and win condition of the game.  see GameOptions
+pub async fn set_game_options(
+    game_id: Path<String>,
+    options: web::Json<GameOptions>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    super::game::set_game_options(&game_id, &claims.id, &options)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+///
+/// sets the language the game is written in.  the body is a Language
+pub async fn set_game_language(
+    game_id: Path<String>,
+    language: web::Json<Language>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    super::game::set_game_language(&game_id, &claims.id, language.into_inner())
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+///
+/// sets the language the caller reads the game in.  the body is a Language, or null for the game's
+pub async fn set_language_override(
+    game_id: Path<String>,
+    language: web::Json<Option<Language>>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    super::game::set_language_override(&game_id, &claims.id, language.into_inner())
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+///
+/// creates a new game on the board in the body.  see CustomBoardData for the layout
+pub async fn new_custom_game(
+    data: web::Json<CustomBoardData>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    super::game::new_custom_game(&claims.id, &data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn game_members(game_id: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    super::game::game_members(&game_id, request_context.claims.as_ref())
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RejoinQuery {
+    pub since: Option<u64>,
+}
+
+///
+/// the current game and the messages the caller missed, for picking a game back up after a lost connection
+pub async fn rejoin_state(
+    game_id: Path<String>,
+    query: web::Query<RejoinQuery>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    // a seat token sees the game as its seat does (see seat_tokens.rs)
+    let user_id = claims.seat.as_ref().map_or(&claims.id, |seat| &seat.local_user_id);
+    super::game::rejoin_state(&game_id, user_id, query.since)
         .await
         .map(|sr| sr.to_http_response())
         .unwrap_or_else(|sr| sr.to_http_response())
@@ -1,11 +1,12 @@
 #![allow(dead_code)]
 
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
 use super::{harbor_enums::HarborType, harbor_key::HarborKey};
 
 // Defining HarborInfo struct to be analogous to TypeScript's class
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Harbor {
     pub harbor_key: HarborKey,
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+use crate::games_service::shared::game_enums::ResourceType;
 
 // Defining HarborType enum with variants that map to TypeScript variant strings
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub enum HarborType {
     Wheat,
@@ -11,3 +14,17 @@ pub enum HarborType {
     Brick,
     ThreeForOne,
 }
+
+impl HarborType {
+    /// the resource a 2:1 harbor takes, or None for a 3:1 harbor
+    pub fn resource(&self) -> Option<ResourceType> {
+        match self {
+            HarborType::Wheat => Some(ResourceType::Wheat),
+            HarborType::Wood => Some(ResourceType::Wood),
+            HarborType::Ore => Some(ResourceType::Ore),
+            HarborType::Sheep => Some(ResourceType::Sheep),
+            HarborType::Brick => Some(ResourceType::Brick),
+            HarborType::ThreeForOne => None,
+        }
+    }
+}
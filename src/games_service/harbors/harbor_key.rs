@@ -1,8 +1,9 @@
 use crate::games_service::shared::game_enums::Direction;
 use crate::games_service::tiles::tile_key::TileKey;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Copy)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Copy, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct HarborKey {
     tile_key: TileKey,
@@ -16,6 +17,14 @@ impl HarborKey {
             position: pos,
         }
     }
+
+    pub fn tile_key(&self) -> TileKey {
+        self.tile_key
+    }
+
+    pub fn position(&self) -> Direction {
+        self.position
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,298 @@
+#![allow(dead_code)]
+/**
+ *  the game browser: the public games that are still taking players, so a player can find a game to join instead of
+ *  waiting for an invite.  private games (see join_codes.rs) are never listed.
+ *
+ *  the games come from memory, and only the ones the caller could join are listed: a game that has started, is full,
+ *  is quarantined, that the caller is already in or that they were banned from is left out.  the filters are all
+ *  optional and a game has to pass every one that is given:
+ *      game_type       the kind of game (Regular, Seafarers...)
+ *      min_players     at least this many players are already in it
+ *      max_players     no more than this many players are in it
+ *      victory_points  the victory point target in its options
+ *      win_condition   FirstToTarget, MostPointsAfterRounds or Timed -- the name, whatever the number of rounds/minutes
+ *      min_rating      the creator has won at least this percentage of the games they've played
+ *  and the house rules the creator picked:
+ *      auto_end_turn   true for games that end a turn on their own once there's nothing left to do, false for games
+ *                      that don't
+ *      undo_after_roll whether a player can take back what they do after they roll
+ *      removal_policy  ReturnToBank, Freeze or Redistribute -- what happens to the cards of a player who leaves
+ *  the games are sorted by id, so paging through them with offset and count is stable while the list changes.
+ */
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::{
+        catan_games::games::regular::regular_game::RegularGame,
+        game_container::game_container::GameContainer,
+        shared::{
+            game_enums::{CatanGames, GameState, GameVisibility},
+            game_models::{GameOptions, RemovalPolicy, WinCondition},
+        },
+    },
+    shared::shared_models::{GameError, ResponseType, ServiceResponse, UserProfile},
+};
+
+/// how many games are returned when the caller doesn't say, and the most they can ask for
+pub const DEFAULT_BROWSER_PAGE: usize = 25;
+pub const MAX_BROWSER_PAGE: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GameBrowserQuery {
+    pub game_type: Option<CatanGames>,
+    pub min_players: Option<usize>,
+    pub max_players: Option<usize>,
+    pub victory_points: Option<u32>,
+    pub win_condition: Option<String>,
+    pub min_rating: Option<u32>,
+    pub auto_end_turn: Option<bool>,
+    pub undo_after_roll: Option<bool>,
+    pub removal_policy: Option<RemovalPolicy>,
+    pub offset: Option<usize>,
+    pub count: Option<usize>,
+}
+
+///
+/// what the browser shows about a game -- enough to pick one, without the board
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct OpenGame {
+    pub game_id: String,
+    pub game_type: CatanGames,
+    pub creator_id: String,
+    pub creator_name: String,
+    pub creator_rating: u32, // the percentage of their games the creator has won
+    pub player_count: usize,
+    pub max_players: usize,
+    pub options: GameOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct GameBrowserPage {
+    pub total: usize,  // how many games passed the filters
+    pub offset: usize, // the first game in this page
+    pub games: Vec<OpenGame>,
+}
+
+/// the percentage of their games the player has won.  0 if they haven't played
+pub fn rating(profile: &UserProfile) -> u32 {
+    match (profile.games_played, profile.games_won) {
+        (Some(played), Some(won)) if played > 0 => (won as u32 * 100) / played as u32,
+        _ => 0,
+    }
+}
+
+/// the creator's rating, from the profile they joined the game with
+pub fn creator_rating(game: &RegularGame) -> u32 {
+    game.players
+        .get(&game.creator_id)
+        .map_or(0, |player| rating(&player.profile))
+}
+
+fn win_condition_name(condition: &WinCondition) -> &'static str {
+    match condition {
+        WinCondition::FirstToTarget => "FirstToTarget",
+        WinCondition::MostPointsAfterRounds(_) => "MostPointsAfterRounds",
+        WinCondition::Timed(_) => "Timed",
+    }
+}
+
+/// true if the caller could join the game: it is public, hasn't started, has a free seat, and they are neither in it
+/// nor banned from it
+pub fn is_joinable(game: &RegularGame, caller_id: &str) -> bool {
+    game.visibility == GameVisibility::Public
+        && game.game_state == GameState::AddingPlayers
+        && game.players.len() < game.game_info().max_players
+        && !game.players.contains_key(caller_id)
+        && !game.banned.iter().any(|id| id == caller_id)
+}
+
+/// true if the game has the house rules the query asks for
+fn has_house_rules(options: &GameOptions, query: &GameBrowserQuery) -> bool {
+    query
+        .auto_end_turn
+        .map_or(true, |auto_end_turn| options.auto_end_turn_seconds.is_some() == auto_end_turn)
+        && query
+            .undo_after_roll
+            .map_or(true, |after_roll| options.undo_policy.after_roll == after_roll)
+        && query
+            .removal_policy
+            .map_or(true, |policy| options.removal_policy == policy)
+}
+
+/// true if the caller could join the game and it passes every filter in the query
+pub fn is_listed(game: &RegularGame, caller_id: &str, query: &GameBrowserQuery) -> bool {
+    let player_count = game.players.len();
+    is_joinable(game, caller_id)
+        && query.game_type.map_or(true, |game_type| game.game_type == game_type)
+        && query.min_players.map_or(true, |min| player_count >= min)
+        && query.max_players.map_or(true, |max| player_count <= max)
+        && query
+            .victory_points
+            .map_or(true, |points| game.options.victory_points_to_win == points)
+        && query.win_condition.as_ref().map_or(true, |name| {
+            win_condition_name(&game.options.win_condition).eq_ignore_ascii_case(name)
+        })
+        && query.min_rating.map_or(true, |rating| creator_rating(game) >= rating)
+        && has_house_rules(&game.options, query)
+}
+
+fn open_game(game: &RegularGame) -> OpenGame {
+    OpenGame {
+        game_id: game.id.clone(),
+        game_type: game.game_type,
+        creator_id: game.creator_id.clone(),
+        creator_name: game
+            .players
+            .get(&game.creator_id)
+            .map(|player| player.profile.display_name.clone())
+            .unwrap_or_default(),
+        creator_rating: creator_rating(game),
+        player_count: game.players.len(),
+        max_players: game.game_info().max_players,
+        options: game.options,
+    }
+}
+
+/// the page of the listed games the query asks for
+pub fn page(games: &[RegularGame], caller_id: &str, query: &GameBrowserQuery) -> GameBrowserPage {
+    let listed: Vec<&RegularGame> = games
+        .iter()
+        .filter(|game| is_listed(game, caller_id, query))
+        .collect();
+    let total = listed.len();
+    let offset = query.offset.unwrap_or(0).min(total);
+    let count = query
+        .count
+        .unwrap_or(DEFAULT_BROWSER_PAGE)
+        .min(MAX_BROWSER_PAGE);
+    GameBrowserPage {
+        total,
+        offset,
+        games: listed
+            .into_iter()
+            .skip(offset)
+            .take(count)
+            .map(open_game)
+            .collect(),
+    }
+}
+
+pub async fn open_games(caller_id: &str, query: &GameBrowserQuery) -> Result<ServiceResponse, ServiceResponse> {
+    let mut games = Vec::new();
+    for game_id in GameContainer::game_ids().await {
+        // a game evicted while we look is just not listed, and nobody can join a quarantined one
+        if !matches!(GameContainer::quarantine(&game_id).await, Ok(None)) {
+            continue;
+        }
+        if let Ok((game, _)) = GameContainer::current_game(&game_id).await {
+            games.push(game);
+        }
+    }
+    games.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::GameBrowser(page(&games, caller_id, query)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games_service::catan_games::traits::game_trait::GameTrait;
+
+    fn game(id: &str, creator_played: u16, creator_won: u16) -> RegularGame {
+        let mut creator = UserProfile::new_test_user(Some(format!("{}-creator", id)));
+        creator.games_played = Some(creator_played);
+        creator.games_won = Some(creator_won);
+        let mut game = RegularGame::new(&creator);
+        game.id = id.to_owned();
+        game
+    }
+
+    #[test]
+    fn test_game_browser_filters() {
+        let open = game("a", 10, 5);
+        let mut private = game("b", 10, 5);
+        private.visibility = GameVisibility::Private;
+        let mut started = game("c", 10, 5);
+        started.game_state = GameState::WaitingForRoll;
+        let mut full = game("d", 0, 0);
+        for n in 1..full.game_info().max_players {
+            GameTrait::add_user(&mut full, &UserProfile::new_test_user(Some(format!("d-{}", n))));
+        }
+        let mut twelve = game("e", 4, 1);
+        twelve.options.victory_points_to_win = 12;
+        twelve.options.win_condition = WinCondition::Timed(60);
+        twelve.options.auto_end_turn_seconds = Some(30);
+        twelve.options.removal_policy = RemovalPolicy::Freeze;
+        let mut kicked = game("f", 0, 0);
+        kicked.banned.push("caller".to_owned());
+        let mut joined = game("g", 0, 0);
+        GameTrait::add_user(&mut joined, &UserProfile::new_test_user(Some("caller".to_owned())));
+        let games = vec![open, private, started, full, twelve, kicked, joined];
+
+        let all = page(&games, "caller", &GameBrowserQuery::default());
+        let ids: Vec<&str> = all.games.iter().map(|game| game.game_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "e"]);
+        assert_eq!(all.games[0].creator_rating, 50);
+
+        let query = GameBrowserQuery {
+            victory_points: Some(12),
+            win_condition: Some("timed".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(page(&games, "caller", &query).games[0].game_id, "e");
+
+        let query = GameBrowserQuery {
+            min_rating: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(page(&games, "caller", &query).total, 1);
+
+        let query = GameBrowserQuery {
+            min_players: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(page(&games, "caller", &query).total, 0);
+
+        //  the house rules
+        let query = GameBrowserQuery {
+            auto_end_turn: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(page(&games, "caller", &query).games[0].game_id, "e");
+        let query = GameBrowserQuery {
+            auto_end_turn: Some(false),
+            removal_policy: Some(RemovalPolicy::ReturnToBank),
+            undo_after_roll: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(page(&games, "caller", &query).games[0].game_id, "a");
+        let query = GameBrowserQuery {
+            undo_after_roll: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(page(&games, "caller", &query).total, 0);
+
+        //  another player could join the games the caller is banned from or already in
+        let ids: Vec<String> = page(&games, "someone else", &GameBrowserQuery::default())
+            .games
+            .into_iter()
+            .map(|game| game.game_id)
+            .collect();
+        assert_eq!(ids, vec!["a", "e", "f", "g"]);
+
+        let query = GameBrowserQuery {
+            offset: Some(1),
+            count: Some(5),
+            ..Default::default()
+        };
+        let second = page(&games, "caller", &query);
+        assert_eq!((second.total, second.offset, second.games.len()), (2, 1, 1));
+    }
+}
@@ -0,0 +1,191 @@
+#![allow(dead_code)]
+/**
+ *  invitations are signed by the service when they are sent, so that an answer can be checked before anybody is added
+ *  to a game.  the token in the Invitation carries the game, who sent the invite, who it was sent to, when it expires,
+ *  and a nonce.  an answer is only accepted if
+ *
+ *  1. the token was signed by the service (with the validation keys) and hasn't expired
+ *  2. the game and the players in the answer are the ones in the token, and the caller is the player that was invited
+ *  3. the token hasn't been used before -- every invitation can be answered once
+ *
+ *  the used nonces are kept in memory until their token expires, so a restart forgets them.  that leaves at most
+ *  INVITATION_LIFETIME_SECS for a replay to an instance that was just restarted
+ */
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    games_service::game_container::game_messages::{Invitation, InvitationResponseData},
+    middleware::security_context::SecurityContext,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+/// how long an invitation can be answered for
+pub const INVITATION_LIFETIME_SECS: u64 = 15 * 60;
+
+lazy_static::lazy_static! {
+    // nonce -> when its token expires
+    static ref USED_NONCES: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+}
+
+// no PascalCase -- jsonwebtoken looks for "exp" (see Claims)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct InvitationClaims {
+    pub game_id: String,
+    pub from_id: String,
+    pub to_id: String,
+    pub exp: usize,
+    pub nonce: String,
+}
+
+fn now() -> usize {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default() as usize
+}
+
+fn rejected(message: &str, status: StatusCode) -> ServiceResponse {
+    ServiceResponse::new(
+        message,
+        status,
+        ResponseType::ErrorInfo("answer the invitation that was sent".to_owned()),
+        GameError::HttpError(status),
+    )
+}
+
+/// Signs the invite as it is about to be sent.  Everything but the token comes from the invite.
+pub fn sign_invitation(
+    invite: &Invitation,
+    security_context: &SecurityContext,
+) -> Result<String, ServiceResponse> {
+    let claims = InvitationClaims {
+        game_id: invite.game_id.clone(),
+        from_id: invite.from_id.clone(),
+        to_id: invite.to_id.clone(),
+        exp: now() + INVITATION_LIFETIME_SECS as usize,
+        nonce: Uuid::new_v4().to_string(),
+    };
+    security_context
+        .validation_keys
+        .sign_payload(&claims)
+        .map_err(|e| {
+            ServiceResponse::new(
+                "failed to sign the invitation",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ResponseType::ErrorInfo(e.to_string()),
+                GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+            )
+        })
+}
+
+/// Checks an answer to an invitation, and uses up its token.  caller_id is the id in the caller's claims.
+pub fn verify_response(
+    response: &InvitationResponseData,
+    caller_id: &str,
+    security_context: &SecurityContext,
+) -> Result<InvitationClaims, ServiceResponse> {
+    let claims: InvitationClaims = security_context
+        .validation_keys
+        .validate_payload(&response.token)
+        .ok_or_else(|| {
+            rejected(
+                "the invitation is not valid or has expired",
+                StatusCode::UNAUTHORIZED,
+            )
+        })?;
+
+    // the answer goes from the invitee back to whoever sent the invite
+    if claims.game_id != response.game_id
+        || claims.to_id != response.from_id
+        || claims.from_id != response.to_id
+        || caller_id != claims.to_id
+    {
+        return Err(rejected(
+            "the answer doesn't match the invitation",
+            StatusCode::UNAUTHORIZED,
+        ));
+    }
+
+    forget_expired(now() as u64);
+    let mut used = USED_NONCES.lock().expect("the nonce lock shouldn't be poisoned");
+    if used.contains_key(&claims.nonce) {
+        return Err(rejected(
+            "the invitation has already been answered",
+            StatusCode::CONFLICT,
+        ));
+    }
+    used.insert(claims.nonce.clone(), claims.exp);
+    Ok(claims)
+}
+
+/// forget the nonces of invitations that can't be answered any more by now.  returns how many were forgotten
+pub fn forget_expired(now: u64) -> usize {
+    let mut used = USED_NONCES.lock().expect("the nonce lock shouldn't be poisoned");
+    let before = used.len();
+    used.retain(|_, exp| *exp as u64 >= now);
+    before - used.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite() -> Invitation {
+        Invitation {
+            from_id: "1".to_owned(),
+            to_id: "2".to_owned(),
+            from_name: "one".to_owned(),
+            to_name: "two".to_owned(),
+            message: "Join my game!".to_owned(),
+            from_picture: String::default(),
+            game_id: "game".to_owned(),
+            token: String::default(),
+        }
+    }
+
+    #[test]
+    fn test_invitation_tokens() {
+        let security_context = SecurityContext::cached_secrets();
+        let mut invite = invite();
+        invite.token = sign_invitation(&invite, &security_context).expect("signing should work");
+        let response = InvitationResponseData::from_invitation(true, &invite);
+
+        // tampered with, or answered by somebody else
+        let mut tampered = response.clone();
+        tampered.game_id = "another game".to_owned();
+        let sr = verify_response(&tampered, "2", &security_context).expect_err("wrong game");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+        let sr = verify_response(&response, "3", &security_context).expect_err("wrong caller");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+        let mut forged = response.clone();
+        forged.token = "not a token".to_owned();
+        assert!(verify_response(&forged, "2", &security_context).is_err());
+
+        // good once
+        let claims = verify_response(&response, "2", &security_context).expect("the answer is valid");
+        assert_eq!(claims.game_id, "game");
+        let sr = verify_response(&response, "2", &security_context).expect_err("replayed");
+        assert_eq!(sr.status, StatusCode::CONFLICT);
+
+        // expired
+        let expired = InvitationClaims {
+            game_id: "game".to_owned(),
+            from_id: "1".to_owned(),
+            to_id: "2".to_owned(),
+            exp: now() - 10 * 60,
+            nonce: Uuid::new_v4().to_string(),
+        };
+        let mut late = response.clone();
+        late.token = security_context.validation_keys.sign_payload(&expired).unwrap();
+        let sr = verify_response(&late, "2", &security_context).expect_err("expired");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+    }
+}
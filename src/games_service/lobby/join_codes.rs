@@ -0,0 +1,73 @@
+#![allow(dead_code)]
+/**
+ *  private games.  instead of inviting each player, the creator of a private game gets a short code to share however
+ *  they like, and anybody with the code can join the game while it is still taking players (see join_by_code).
+ *
+ *  codes are JOIN_CODE_LENGTH characters from an alphabet without the easily confused 0/O and 1/I/L, and aren't case
+ *  sensitive.  they are kept in memory for as long as the game is, and forgotten when the game is evicted
+ */
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::shared::environment::Environment;
+
+pub const JOIN_CODE_LENGTH: usize = 6;
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+
+lazy_static::lazy_static! {
+    // code -> game_id
+    static ref JOIN_CODES: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+}
+
+fn random_code(environment: &dyn Environment) -> String {
+    (0..JOIN_CODE_LENGTH)
+        .map(|_| JOIN_CODE_ALPHABET[environment.random_below(JOIN_CODE_ALPHABET.len() as u32) as usize] as char)
+        .collect()
+}
+
+/// a new code for the game
+pub fn create(game_id: &str, environment: &dyn Environment) -> String {
+    let mut codes = JOIN_CODES.lock().expect("the join code lock shouldn't be poisoned");
+    loop {
+        let code = random_code(environment);
+        if !codes.contains_key(&code) {
+            codes.insert(code.clone(), game_id.to_owned());
+            return code;
+        }
+    }
+}
+
+/// the game the code is for, if it is one of ours
+pub fn game_for(code: &str) -> Option<String> {
+    JOIN_CODES
+        .lock()
+        .expect("the join code lock shouldn't be poisoned")
+        .get(&code.trim().to_uppercase())
+        .cloned()
+}
+
+/// the game is gone -- its codes don't join anything any more
+pub fn forget(game_id: &str) {
+    JOIN_CODES
+        .lock()
+        .expect("the join code lock shouldn't be poisoned")
+        .retain(|_, id| id != game_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::environment::ProductionEnvironment;
+
+    #[test]
+    fn test_join_codes() {
+        let code = create("private-game", &ProductionEnvironment);
+        assert_eq!(code.len(), JOIN_CODE_LENGTH);
+        assert!(code.bytes().all(|c| JOIN_CODE_ALPHABET.contains(&c)));
+        assert_ne!(create("private-game", &ProductionEnvironment), code);
+
+        assert_eq!(game_for(&code), Some("private-game".to_string()));
+        assert_eq!(game_for(&format!(" {} ", code.to_lowercase())), Some("private-game".to_string()));
+        forget("private-game");
+        assert_eq!(game_for(&code), None);
+    }
+}
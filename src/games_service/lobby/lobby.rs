@@ -1,16 +1,20 @@
 #![allow(unused_variables)]
 use reqwest::StatusCode;
 
+use super::{invitation_token, join_codes};
 use crate::{
     games_service::{
+        bots::bots,
         game_container::{
             game_container::GameContainer,
-            game_messages::{CatanMessage, Invitation, InvitationResponseData},
+            game_messages::{CatanMessage, Invitation, InvitationResponseData, PlayerRemovedData},
         },
-        long_poller::long_poller::LongPoller,
+        long_poller::{channels::MessageChannel, long_poller::LongPoller},
     },
     middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
     shared::shared_models::{UserProfile, GameError, ResponseType, ServiceResponse},
+    user_service::push_notifications::{self, PushNotification},
 };
 
 pub async fn get_lobby() -> Result<ServiceResponse, ServiceResponse> {
@@ -21,15 +25,35 @@ pub async fn get_lobby() -> Result<ServiceResponse, ServiceResponse> {
         GameError::NoError(String::default()),
     ));
 }
+/**
+ * send the invite to the player, signed so that their answer can be checked (see invitation_token.rs).  the invite is
+ * always from the caller, whatever from_id it was posted with, and the caller has to be in the game (or have made it)
+ * -- nobody can invite people into somebody else's game
+ */
 pub async fn post_invite(
     from_id: &str,
     invite: &Invitation,
+    request_context: &RequestContext,
 ) -> Result<ServiceResponse, ServiceResponse> {
-    LongPoller::send_message(
-        vec![invite.to_id.clone()],
-        &CatanMessage::Invite(invite.clone()),
-    )
-    .await
+    let (game, _) = GameContainer::current_game(&invite.game_id).await?;
+    if !game.players.contains_key(from_id) && game.creator_id != from_id {
+        return Err(ServiceResponse::new(
+            &format!("{} isn't playing in {}", from_id, invite.game_id),
+            StatusCode::FORBIDDEN,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::FORBIDDEN),
+        ));
+    }
+    let mut invite = invite.clone();
+    invite.from_id = from_id.to_owned();
+    invite.token = invitation_token::sign_invitation(&invite, &request_context.security_context)?;
+    push_notifications::notify(
+        &[invite.to_id.clone()],
+        &invite.game_id,
+        |language| PushNotification::invitation(&invite.game_id, &invite.from_name, language),
+        request_context,
+    );
+    LongPoller::send_message(vec![invite.to_id.clone()], &CatanMessage::Invite(invite)).await
 }
 /**
  * pass this on to the client.  the long_poll will pass it to the client, which will update the UI to indicate this
@@ -38,12 +62,24 @@ pub async fn post_invite(
  *  2. notify the originator of the answer
  *  3. notify the sender (e.g. the reciever of the original invite) that a response has occured so that it will
  *     loop and end up waiting on the right thing
+ *
+ *  the answer has to carry the token from the invite, and come from the player that was invited
  */
 pub async fn respond_to_invite(
     is_test: bool,
     invite_response: &InvitationResponseData,
     request_context: &RequestContext,
 ) -> Result<ServiceResponse, ServiceResponse> {
+    let caller_id = request_context
+        .claims
+        .as_ref()
+        .map(|claims| claims.id.clone())
+        .unwrap_or_default();
+    invitation_token::verify_response(
+        invite_response,
+        &caller_id,
+        &request_context.security_context,
+    )?;
     if invite_response.accepted {
         // add the user to the Container -- now they are in both the lobby and the game
         // this will release any threads waiting for updates on the game
@@ -55,8 +91,7 @@ pub async fn respond_to_invite(
             &invite_response.game_id,
             &UserProfile::from_persist_user(&persist_user),
         )
-        .await
-        .expect("add_player shouldn't fail.  TODO: handle failure");
+        .await?;
     }
 
     // tell the reciever the result of the invitation
@@ -67,3 +102,88 @@ pub async fn respond_to_invite(
     )
     .await
 }
+
+/**
+ *  join a private game with its code, without being invited.  the game has to still be taking players: once it has
+ *  left the lobby the join is refused with a 409, even though the code still finds it.  returns the game, and
+ *  everybody already in it gets it with the new player
+ */
+pub async fn join_by_code(
+    code: &str,
+    caller_id: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let game_id = match join_codes::game_for(code) {
+        Some(game_id) => game_id,
+        None => {
+            return Err(ServiceResponse::new(
+                "there is no game with that code",
+                StatusCode::NOT_FOUND,
+                ResponseType::NoData,
+                GameError::BadId(code.to_owned()),
+            ))
+        }
+    };
+    let persist_user = request_context.database.find_user_by_id(caller_id).await?;
+    GameContainer::add_player(&game_id, &UserProfile::from_persist_user(&persist_user)).await?;
+    let (game, _) = GameContainer::current_game(&game_id).await?;
+    GameContainer::broadcast_game(&game).await;
+    Ok(ServiceResponse::new(
+        "joined",
+        StatusCode::OK,
+        ResponseType::Game(game.redacted_for(caller_id)),
+        GameError::NoError(String::default()),
+    ))
+}
+
+/**
+ *  the creator takes a player out of the game before it starts, and with ban set keeps them from joining it again.
+ *  everybody still in the game gets the game without them, and they and the player that was removed get
+ *  PlayerRemoved
+ */
+pub async fn kick(
+    game_id: &str,
+    caller_id: &str,
+    user_id: &str,
+    ban: bool,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    if game.creator_id != caller_id {
+        return new_unauthorized_response!("only the game's creator can remove players");
+    }
+    let new_game = game.remove_player(user_id, ban).map_err(|e| {
+        ServiceResponse::new(
+            "the player can't be removed",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(format!("{:?}", e)),
+            e,
+        )
+    })?;
+    let pushed = GameContainer::push_game(game_id, &new_game, "Kick", Some(caller_id)).await?;
+
+    let mut to_users = GameContainer::get_game_players(game_id).await?;
+    if bots::is_bot(user_id) {
+        bots::forget(user_id);
+    } else {
+        to_users.push(user_id.to_owned());
+    }
+    let message = CatanMessage::PlayerRemoved(PlayerRemovedData {
+        game_id: game_id.to_owned(),
+        user_id: user_id.to_owned(),
+        banned: ban,
+        user_name: game
+            .players
+            .get(user_id)
+            .map(|player| player.profile.display_name.clone())
+            .unwrap_or_default(),
+        text: String::default(),
+    });
+    let _ = LongPoller::send_to_channel(to_users, &MessageChannel::Game(game_id.to_owned()), &message).await;
+    Ok(ServiceResponse::new(
+        "removed",
+        StatusCode::OK,
+        ResponseType::Game(pushed.redacted_for(caller_id)),
+        GameError::NoError(String::default()),
+    ))
+}
@@ -1,7 +1,11 @@
 #![allow(unused_variables)]
 use actix_web::{web, HttpRequest, HttpResponse};
 
+use serde::Deserialize;
+
+use super::{game_browser::GameBrowserQuery, matchmaking::MatchPreferences};
 use crate::{
+    get_header_value,
     games_service::game_container::game_messages::{Invitation, InvitationResponseData},
     middleware::{request_context_mw::RequestContext, header_extractor::HeadersExtractor}
 };
@@ -24,7 +28,7 @@ pub async fn post_invite(
         .id;
     let invite: &Invitation = &invite;
 
-    super::lobby::post_invite(&from_id, invite)
+    super::lobby::post_invite(&from_id, invite, &request_context)
         .await
         .map(|sr| sr.to_http_response())
         .unwrap_or_else(|sr| sr.to_http_response())
@@ -49,3 +53,78 @@ pub async fn respond_to_invite(
         .map(|sr| sr.to_http_response())
         .unwrap_or_else(|sr| sr.to_http_response())
 }
+
+pub async fn join_by_code(code: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    let caller_id = &request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs")
+        .id;
+    super::lobby::join_by_code(&code, caller_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn get_games(query: web::Query<GameBrowserQuery>, request_context: RequestContext) -> HttpResponse {
+    let caller_id = &request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs")
+        .id;
+    super::game_browser::open_games(caller_id, &query)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn matchmake(preferences: web::Json<MatchPreferences>, request_context: RequestContext) -> HttpResponse {
+    let caller_id = &request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs")
+        .id;
+    super::matchmaking::matchmake(caller_id, &preferences, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn leave_matchmaking(request_context: RequestContext) -> HttpResponse {
+    let caller_id = &request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs")
+        .id;
+    super::matchmaking::leave_matchmaking(caller_id)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickQuery {
+    #[serde(default)]
+    pub ban: bool,
+}
+
+/**
+ * remove the player from the game in the x-game-id header.  with ?ban=true they can't join it again
+ */
+pub async fn kick(
+    user_id: web::Path<String>,
+    query: web::Query<KickQuery>,
+    headers: HeadersExtractor,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let game_id = get_header_value!(game_id, headers);
+    let caller_id = &request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs")
+        .id;
+    super::lobby::kick(&game_id, caller_id, &user_id, query.ban, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
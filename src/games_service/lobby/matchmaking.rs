@@ -0,0 +1,354 @@
+#![allow(dead_code)]
+/**
+ *  matchmaking: instead of inviting people or browsing for a game (see game_browser.rs), a player asks to be matched
+ *  with POST /lobby/matchmake and waits on the long poller.  every MATCH_INTERVAL the matchmaker groups the players
+ *  that are waiting by game type, table size and rating band, oldest first, and makes a game for each full group.
+ *  the oldest player in the group creates it, the others are added, and everybody gets GameCreated and then the game.
+ *
+ *  a player is in the queue once -- asking again replaces what they asked for -- and leaves it when they are matched,
+ *  when they DELETE /lobby/matchmake, or when they have waited MATCH_TTL without a match (they get an Error with a 408
+ *  on the long poller, and can ask again).  if the game for a group can't be made, it is dropped and the players go
+ *  back in the queue where they were.  the queue is in memory, so a restart empties it.
+ */
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::game_browser::rating;
+use crate::{
+    games_service::{
+        catan_games::{games::regular::regular_game::RegularGame, traits::game_trait::GameTrait},
+        game::{add_new_game, SUPPORTED_GAMES},
+        game_container::{
+            game_container::GameContainer,
+            game_messages::{CatanMessage, ErrorData, GameCreatedData},
+        },
+        long_poller::long_poller::LongPoller,
+        shared::game_enums::CatanGames,
+    },
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse, UserProfile},
+};
+
+/// how often the matchmaker looks for groups
+pub const MATCH_INTERVAL: Duration = Duration::from_secs(5);
+/// how long a player waits in the queue before they are taken out of it
+pub const MATCH_TTL: Duration = Duration::from_secs(10 * 60);
+/// players are matched with players whose rating (the percentage of games they've won) is in the same band
+pub const RATING_BAND_WIDTH: u32 = 25;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct MatchPreferences {
+    pub game_type: CatanGames,
+    #[serde(default)]
+    pub players: Option<usize>, // how many players the game should have.  the fewest the game type allows if not given
+}
+
+#[derive(Debug, Clone)]
+struct QueuedPlayer {
+    profile: UserProfile,
+    game_type: CatanGames,
+    players: usize,
+    band: u32,
+    queued_at: Instant,
+}
+
+impl QueuedPlayer {
+    fn user_id(&self) -> String {
+        self.profile.user_id.clone().unwrap_or_default()
+    }
+
+    fn matches(&self, other: &QueuedPlayer) -> bool {
+        self.game_type == other.game_type && self.players == other.players && self.band == other.band
+    }
+}
+
+lazy_static::lazy_static! {
+    // the players waiting to be matched, oldest first
+    static ref QUEUE: Mutex<Vec<QueuedPlayer>> = Mutex::new(Vec::new());
+}
+
+/// the rating band the player is matched in
+pub fn band_for(profile: &UserProfile) -> u32 {
+    rating(profile) / RATING_BAND_WIDTH
+}
+
+/// put the player in the queue, in place of anything they asked for before
+fn enqueue(profile: &UserProfile, preferences: &MatchPreferences) -> Result<(), ServiceResponse> {
+    if !SUPPORTED_GAMES.contains(&preferences.game_type) {
+        return Err(ServiceResponse::new(
+            &format!("Game not supported: {:#?}", preferences.game_type),
+            StatusCode::BAD_REQUEST,
+            ResponseType::NoData,
+            GameError::MissingData(String::default()),
+        ));
+    }
+    let info = RegularGame::game_info_for(preferences.game_type);
+    let players = preferences.players.unwrap_or(info.min_players);
+    if players < info.min_players || players > info.max_players {
+        let message = format!(
+            "a {:?} game has {} to {} players",
+            preferences.game_type, info.min_players, info.max_players
+        );
+        return Err(ServiceResponse::new(
+            &message,
+            StatusCode::BAD_REQUEST,
+            ResponseType::NoData,
+            GameError::BadActionData(message.clone()),
+        ));
+    }
+    let player = QueuedPlayer {
+        profile: profile.clone(),
+        game_type: preferences.game_type,
+        players,
+        band: band_for(profile),
+        queued_at: Instant::now(),
+    };
+    let mut queue = QUEUE.lock().expect("the matchmaking lock shouldn't be poisoned");
+    queue.retain(|queued| queued.user_id() != player.user_id());
+    queue.push(player);
+    Ok(())
+}
+
+/// take the player out of the queue.  returns false if they weren't in it
+pub fn leave(user_id: &str) -> bool {
+    let mut queue = QUEUE.lock().expect("the matchmaking lock shouldn't be poisoned");
+    let before = queue.len();
+    queue.retain(|queued| queued.user_id() != user_id);
+    queue.len() != before
+}
+
+/// take the players who have waited longer than the ttl out of the queue
+fn take_expired(queue: &mut Vec<QueuedPlayer>, ttl: Duration) -> Vec<QueuedPlayer> {
+    let (expired, waiting): (Vec<QueuedPlayer>, Vec<QueuedPlayer>) =
+        queue.drain(..).partition(|queued| queued.queued_at.elapsed() > ttl);
+    *queue = waiting;
+    expired
+}
+
+/// put a group that couldn't be matched back in the queue, in the places they had.  a player who asked again in the
+/// meantime keeps what they asked for since
+fn requeue(queue: &mut Vec<QueuedPlayer>, group: Vec<QueuedPlayer>) {
+    for player in group {
+        if !queue.iter().any(|queued| queued.user_id() == player.user_id()) {
+            queue.push(player);
+        }
+    }
+    queue.sort_by_key(|queued| queued.queued_at);
+}
+
+/// take every full group out of the queue, oldest players first.  whoever is left waits for the next look
+fn take_groups(queue: &mut Vec<QueuedPlayer>) -> Vec<Vec<QueuedPlayer>> {
+    let mut groups = Vec::new();
+    let mut index = 0;
+    while index < queue.len() {
+        let first = &queue[index];
+        let members: Vec<usize> = (index..queue.len())
+            .filter(|n| queue[*n].matches(first))
+            .take(first.players)
+            .collect();
+        if members.len() < first.players {
+            index += 1;
+            continue;
+        }
+        // remove from the back so the indices stay good, then put the group back in queue order
+        let mut group: Vec<QueuedPlayer> = members.iter().rev().map(|n| queue.remove(*n)).collect();
+        group.reverse();
+        groups.push(group);
+    }
+    groups
+}
+
+/// make the game for a group: the oldest player creates it and the rest are added.  nobody hears about the game until
+/// everybody is in it, and if anybody can't be added the game is dropped
+async fn create_match(group: &[QueuedPlayer]) -> Result<RegularGame, ServiceResponse> {
+    let creator = &group[0];
+    let mut game = RegularGame::new_of_type(&creator.profile, creator.game_type);
+    game.shuffle();
+    add_new_game(game.clone(), &creator.user_id(), "matched").await?;
+    for player in group.iter().skip(1) {
+        if let Err(e) = GameContainer::add_player(&game.id, &player.profile).await {
+            let _ = GameContainer::remove_container(&game.id).await;
+            return Err(e);
+        }
+    }
+    for player in group.iter().skip(1) {
+        let _ = LongPoller::send_message(
+            vec![player.user_id()],
+            &CatanMessage::GameCreated(GameCreatedData {
+                user_id: player.user_id(),
+                game_id: game.id.clone(),
+            }),
+        )
+        .await;
+    }
+    let (game, _) = GameContainer::current_game(&game.id).await?;
+    GameContainer::broadcast_game(&game).await;
+    Ok(game)
+}
+
+/// one look at the queue: drop the players who have waited too long, and make a game for every full group.  returns
+/// the ids of the games that were made
+pub async fn match_players() -> Vec<String> {
+    let (expired, groups) = {
+        let mut queue = QUEUE.lock().expect("the matchmaking lock shouldn't be poisoned");
+        (take_expired(&mut queue, MATCH_TTL), take_groups(&mut queue))
+    };
+    for player in expired {
+        let _ = LongPoller::send_message(
+            vec![player.user_id()],
+            &CatanMessage::Error(ErrorData {
+                status_code: StatusCode::REQUEST_TIMEOUT.as_u16() as i32,
+                message: format!("nobody was matched with you in {:?}", MATCH_TTL),
+            }),
+        )
+        .await;
+    }
+    let mut games = Vec::new();
+    for group in groups {
+        match create_match(&group).await {
+            Ok(game) => {
+                tracing::info!(
+                    "matched {} players into {} after {:?}",
+                    group.len(),
+                    game.id,
+                    group[0].queued_at.elapsed()
+                );
+                games.push(game.id);
+            }
+            Err(e) => {
+                tracing::error!("matchmaking couldn't make a game: {:#?}", e);
+                let mut queue = QUEUE.lock().expect("the matchmaking lock shouldn't be poisoned");
+                requeue(&mut queue, group);
+            }
+        }
+    }
+    games
+}
+
+/// run the matchmaker every interval, for the life of the service
+pub fn start_matchmaker(interval: Duration) -> tokio::task::JoinHandle<()> {
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            match_players().await;
+        }
+    })
+}
+
+/// the caller asks to be matched.  they hear about their game through the long poller
+pub async fn matchmake(
+    caller_id: &str,
+    preferences: &MatchPreferences,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let persist_user = request_context.database.find_user_by_id(caller_id).await?;
+    enqueue(&UserProfile::from_persist_user(&persist_user), preferences)?;
+    Ok(ServiceResponse::new(
+        "queued",
+        StatusCode::ACCEPTED,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    ))
+}
+
+/// the caller doesn't want to be matched any more
+pub async fn leave_matchmaking(caller_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+    if leave(caller_id) {
+        Ok(ServiceResponse::new_generic_ok("left the matchmaking queue"))
+    } else {
+        Err(ServiceResponse::new(
+            "not in the matchmaking queue",
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::BadId(caller_id.to_owned()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(id: &str, game_type: CatanGames, players: usize, played: u16, won: u16) -> QueuedPlayer {
+        let mut profile = UserProfile::new_test_user(Some(id.to_owned()));
+        profile.games_played = Some(played);
+        profile.games_won = Some(won);
+        QueuedPlayer {
+            band: band_for(&profile),
+            profile,
+            game_type,
+            players,
+            queued_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_take_groups() {
+        let mut queue = vec![
+            queued("a", CatanGames::Regular, 3, 10, 1),
+            queued("strong", CatanGames::Regular, 3, 10, 9),
+            queued("b", CatanGames::Regular, 3, 4, 0),
+            queued("sea", CatanGames::Seafarers, 3, 0, 0),
+            queued("c", CatanGames::Regular, 3, 0, 0),
+            queued("d", CatanGames::Regular, 3, 10, 2),
+        ];
+        let groups = take_groups(&mut queue);
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<String> = groups[0].iter().map(|player| player.user_id()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+
+        // nobody else has a group yet, and they keep their place
+        let left: Vec<String> = queue.iter().map(|player| player.user_id()).collect();
+        assert_eq!(left, vec!["strong", "sea", "d"]);
+        assert!(take_groups(&mut queue).is_empty());
+    }
+
+    #[test]
+    fn test_expire_and_requeue() {
+        let mut old = queued("old", CatanGames::Regular, 3, 0, 0);
+        old.queued_at = Instant::now() - MATCH_TTL - Duration::from_secs(1);
+        let mut queue = vec![
+            old,
+            queued("a", CatanGames::Regular, 3, 0, 0),
+            queued("b", CatanGames::Regular, 3, 0, 0),
+            queued("c", CatanGames::Regular, 3, 0, 0),
+            queued("d", CatanGames::Regular, 3, 0, 0),
+        ];
+        let expired = take_expired(&mut queue, MATCH_TTL);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].user_id(), "old");
+
+        // the match fails: the group goes back where it was, except for a player who asked again meanwhile
+        let mut groups = take_groups(&mut queue);
+        let group = groups.remove(0);
+        queue.push(queued("b", CatanGames::Seafarers, 3, 0, 0));
+        requeue(&mut queue, group);
+        let ids: Vec<String> = queue.iter().map(|player| player.user_id()).collect();
+        assert_eq!(ids, vec!["a", "c", "d", "b"]);
+        assert_eq!(queue[3].game_type, CatanGames::Seafarers);
+    }
+
+    #[test]
+    fn test_enqueue() {
+        let profile = UserProfile::new_test_user(Some("matchmaking-enqueue".to_owned()));
+        let too_many = MatchPreferences {
+            game_type: CatanGames::Regular,
+            players: Some(12),
+        };
+        assert!(enqueue(&profile, &too_many).is_err());
+        let preferences = MatchPreferences {
+            game_type: CatanGames::Regular,
+            players: None,
+        };
+        enqueue(&profile, &preferences).unwrap();
+        enqueue(&profile, &preferences).unwrap();
+        assert!(leave("matchmaking-enqueue"));
+        assert!(!leave("matchmaking-enqueue"));
+    }
+}
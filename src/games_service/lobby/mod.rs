@@ -1,3 +1,7 @@
 
 pub mod lobby_handlers;
-pub mod lobby;
\ No newline at end of file
+pub mod lobby;
+pub mod invitation_token;
+pub mod join_codes;
+pub mod game_browser;
+pub mod matchmaking;
@@ -0,0 +1,146 @@
+#![allow(dead_code)]
+/**
+ *  a user can be in the lobby and in more than one game at the same time, and all of their messages come through the
+ *  one long poller.  every message is tagged with the channel it belongs to -- the lobby, a game, or a direct message
+ *  from another user -- and a sequence number that counts up per user and channel, so a client can route messages to
+ *  the right game and can tell when it has missed one.
+ *
+ *  the longpoll api takes an optional channel filter ("lobby", "game:{game_id}" or "dm:{user_id}").  a filtered call
+ *  only returns messages on that channel; the others are held for a later call that wants them.
+ *
+ *  every message also gets a user_sequence, which counts every message sent to the user on any channel.  a client
+ *  passes the last one it saw as ?since= on its next long poll, which acknowledges everything up to it: nothing at or
+ *  before it is returned again, and anything after it that the client missed -- because its connection died, or it
+ *  logged in again -- is replayed from the last MAX_REPLAY_MESSAGES kept for the user.
+ *
+ *  a channel is also a topic that messages can be sent to (see LongPoller::send_message and MessageTarget): everybody
+ *  logged in is in the lobby, and a game's players are in its topic -- the game's actor keeps that up to date as
+ *  players come and go -- so a user in more than one game only gets the messages for the games they are in.
+ *
+ *  heartbeats (see presence.rs) are the exception: they have sequence numbers of 0, are never kept or replayed, and
+ *  every filter wants them, so that any open wait can be answered with one.
+ */
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+use crate::games_service::game_container::game_messages::CatanMessage;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub enum MessageChannel {
+    Lobby,
+    Game(String),   // game_id
+    Direct(String), // the user_id of the user that sent it
+}
+
+impl MessageChannel {
+    /// the channel named by a longpoll filter, or None if the filter doesn't name one
+    pub fn parse(filter: &str) -> Option<Self> {
+        match filter.split_once(':') {
+            None if filter.eq_ignore_ascii_case("lobby") => Some(MessageChannel::Lobby),
+            Some(("game", id)) if !id.is_empty() => Some(MessageChannel::Game(id.to_owned())),
+            Some(("dm", id)) if !id.is_empty() => Some(MessageChannel::Direct(id.to_owned())),
+            _ => None,
+        }
+    }
+
+    /// The channel a message belongs on when the sender doesn't say.
+    ///
+    /// Anything that names its game goes on the game's channel, and invitations and direct messages go on the sender's
+    /// direct channel.  Everything else goes to the lobby -- senders that know better (eg.
+    /// GameContainer::broadcast_message) pick the channel themselves.
+    pub fn for_message(message: &CatanMessage) -> Self {
+        match message {
+            CatanMessage::GameUpdate(game) => MessageChannel::Game(game.id.clone()),
+            CatanMessage::GameCreated(created) => MessageChannel::Game(created.game_id.clone()),
+            CatanMessage::GameWon(won) => MessageChannel::Game(won.game_id.clone()),
+            CatanMessage::TurnSummary(summary) => MessageChannel::Game(summary.game_id.clone()),
+            CatanMessage::Ended(game_id) => MessageChannel::Game(game_id.clone()),
+            CatanMessage::Chat(chat) => MessageChannel::Game(chat.game_id.clone()),
+            CatanMessage::PlayerRemoved(removed) => MessageChannel::Game(removed.game_id.clone()),
+            CatanMessage::HostChanged(changed) => MessageChannel::Game(changed.game_id.clone()),
+            CatanMessage::Invite(invite) => MessageChannel::Direct(invite.from_id.clone()),
+            CatanMessage::DirectMessage(dm) => MessageChannel::Direct(dm.from_id.clone()),
+            CatanMessage::InvitationResponse(response) => {
+                MessageChannel::Direct(response.from_id.clone())
+            }
+            _ => MessageChannel::Lobby,
+        }
+    }
+}
+
+impl fmt::Display for MessageChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageChannel::Lobby => write!(f, "lobby"),
+            MessageChannel::Game(game_id) => write!(f, "game:{}", game_id),
+            MessageChannel::Direct(user_id) => write!(f, "dm:{}", user_id),
+        }
+    }
+}
+
+///
+/// what the long poller returns: the message, the channel it is on, and its place in that channel
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChannelMessage {
+    pub channel: MessageChannel,
+    pub sequence: u64, // starts at 1 for each user and channel, and goes up by one for every message
+    #[serde(default)]
+    pub user_sequence: u64, // starts at 1 for each user, and goes up by one for every message on any channel
+    pub message: CatanMessage,
+}
+
+impl ChannelMessage {
+    /// true if a wait with this filter should return the message
+    pub fn matches(&self, filter: Option<&MessageChannel>) -> bool {
+        self.is_heartbeat() || filter.map_or(true, |channel| *channel == self.channel)
+    }
+
+    /// a heartbeat for an open wait.  it isn't numbered, so it doesn't use up a sequence number or get acknowledged
+    pub fn heartbeat(now: u64) -> Self {
+        Self {
+            channel: MessageChannel::Lobby,
+            sequence: 0,
+            user_sequence: 0,
+            message: CatanMessage::Heartbeat(now),
+        }
+    }
+
+    pub fn is_heartbeat(&self) -> bool {
+        matches!(self.message, CatanMessage::Heartbeat(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_channel() {
+        assert_eq!(MessageChannel::parse("lobby"), Some(MessageChannel::Lobby));
+        assert_eq!(
+            MessageChannel::parse("game:abc"),
+            Some(MessageChannel::Game("abc".to_owned()))
+        );
+        assert_eq!(
+            MessageChannel::parse("dm:user-1"),
+            Some(MessageChannel::Direct("user-1".to_owned()))
+        );
+        assert_eq!(MessageChannel::parse("game:"), None);
+        assert_eq!(MessageChannel::parse("games"), None);
+        for channel in vec![
+            MessageChannel::Lobby,
+            MessageChannel::Game("abc".to_owned()),
+            MessageChannel::Direct("user-1".to_owned()),
+        ] {
+            assert_eq!(MessageChannel::parse(&channel.to_string()), Some(channel));
+        }
+        assert_eq!(
+            MessageChannel::for_message(&CatanMessage::Ended("abc".to_owned())),
+            MessageChannel::Game("abc".to_owned())
+        );
+    }
+}
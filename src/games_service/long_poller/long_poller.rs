@@ -1,30 +1,157 @@
 #![allow(dead_code)]
 use reqwest::StatusCode;
 use scopeguard::defer;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, Mutex, RwLock};
 
 use crate::{
     games_service::game_container::game_messages::{CatanMessage, GameStatus},
     log_thread_info,
-    shared::shared_models::{UserProfile, GameError, ResponseType, ServiceResponse},
+    shared::{
+        i18n,
+        shared_models::{UserProfile, GameError, ResponseType, ServiceResponse},
+    },
 };
+
+use super::channels::{ChannelMessage, MessageChannel};
+use super::outbox;
+
+/// the most messages held for a user while they long poll with a filter that doesn't want them.  past this the oldest
+/// are dropped
+pub const MAX_HELD_MESSAGES: usize = 100;
+/// the most recent messages kept for each user and channel, for a user who comes back after losing their connection
+pub const MAX_RECENT_MESSAGES: usize = 200;
+/// the most recent messages kept for each user, on any channel, to replay to a long poll that says what it has seen
+pub const MAX_REPLAY_MESSAGES: usize = 500;
 //
 //  this is a map of "waiters" - holding all the state necessary for a Long Poller to wait on a thread
 //  and other threads to find and call send on the tx
 //
 lazy_static::lazy_static! {
     static ref ALL_USERS_MAP: Arc<RwLock<HashMap<String, Arc<RwLock<LongPoller>>>>> = Arc::new(RwLock::new(HashMap::new()));
+    static ref MESSAGE_HISTORY: RwLock<HashMap<String, MessageHistory>> = RwLock::new(HashMap::new());
+    /// who is in each game's topic.  a std lock, so that a game's actor can keep it up to date (see GameContainer::call)
+    static ref TOPICS: std::sync::RwLock<HashMap<MessageChannel, Vec<String>>> = std::sync::RwLock::new(HashMap::new());
+}
+
+/// who a message goes to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageTarget {
+    Users(Vec<String>),    // these users, on the channel the message belongs on (see MessageChannel::for_message)
+    Topic(MessageChannel), // everybody in the topic: all logged in users for the lobby, the players for a game
+    Direct { from_id: String, to_id: String }, // one user, on the direct channel from another
+}
+
+impl From<Vec<String>> for MessageTarget {
+    fn from(user_ids: Vec<String>) -> Self {
+        MessageTarget::Users(user_ids)
+    }
+}
+
+/// what a user has been sent.  unlike the LongPoller this outlives a login, so the sequence numbers keep counting up
+/// and a user whose connection died can get what they missed (see LongPoller::missed_messages)
+#[derive(Debug, Default)]
+struct MessageHistory {
+    sequences: HashMap<MessageChannel, u64>, // the last sequence number sent on each channel
+    recent: HashMap<MessageChannel, VecDeque<ChannelMessage>>, // the last MAX_RECENT_MESSAGES on each channel
+    user_sequence: u64,                      // the last user_sequence sent on any channel
+    replay: VecDeque<ChannelMessage>,        // the last MAX_REPLAY_MESSAGES on any channel, oldest first
+}
+
+impl MessageHistory {
+    /// give the message the channel's and the user's next sequence numbers and remember it
+    fn record(&mut self, channel: &MessageChannel, message: &CatanMessage) -> ChannelMessage {
+        let sequence = self.sequences.entry(channel.clone()).or_insert(0);
+        *sequence += 1;
+        self.user_sequence += 1;
+        let channel_message = ChannelMessage {
+            channel: channel.clone(),
+            sequence: *sequence,
+            user_sequence: self.user_sequence,
+            message: message.clone(),
+        };
+        let recent = self.recent.entry(channel.clone()).or_default();
+        if recent.len() >= MAX_RECENT_MESSAGES {
+            recent.pop_front();
+        }
+        recent.push_back(channel_message.clone());
+        if self.replay.len() >= MAX_REPLAY_MESSAGES {
+            self.replay.pop_front();
+        }
+        self.replay.push_back(channel_message.clone());
+        channel_message
+    }
+
+    /// the oldest message after `since` that the filter wants
+    fn replay_after(&self, since: u64, filter: Option<&MessageChannel>) -> Option<ChannelMessage> {
+        self.replay
+            .iter()
+            .find(|message| message.user_sequence > since && message.matches(filter))
+            .cloned()
+    }
+}
+
+/// whether the user has a wait open, and when they last did.  it is behind a std Mutex so that a wait that is dropped
+/// because the client went away can still record it (see WaitGuard)
+#[derive(Debug)]
+struct Presence {
+    waiting: usize,
+    last_seen: Instant,
+}
+
+impl Presence {
+    /// zero while a wait is open, otherwise how long since the last one closed
+    fn idle(&self) -> Duration {
+        if self.waiting > 0 {
+            Duration::ZERO
+        } else {
+            self.last_seen.elapsed()
+        }
+    }
+}
+
+/// who is logged in and how long since each of them last had a wait open (see presence.rs)
+#[derive(Debug, Clone)]
+pub struct UserActivity {
+    pub user_id: String,
+    pub profile: UserProfile,
+    pub idle: Duration,
+    pub waiting: bool,
+}
+
+/// counts a wait as open until it is dropped, however it ends
+struct WaitGuard(Arc<std::sync::Mutex<Presence>>);
+
+impl WaitGuard {
+    fn start(presence: Arc<std::sync::Mutex<Presence>>) -> Self {
+        if let Ok(mut p) = presence.lock() {
+            p.waiting += 1;
+            p.last_seen = Instant::now();
+        }
+        Self(presence)
+    }
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        if let Ok(mut p) = self.0.lock() {
+            p.waiting = p.waiting.saturating_sub(1);
+            p.last_seen = Instant::now();
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct LongPoller {
     user_id: String, // can be any kind of id
     user_profile: UserProfile,
-    pub tx: mpsc::Sender<ServiceResponse>,
-    pub rx: Arc<Mutex<mpsc::Receiver<ServiceResponse>>>,
+    pub tx: mpsc::Sender<ChannelMessage>,
+    pub rx: Arc<Mutex<mpsc::Receiver<ChannelMessage>>>,
     pub status: GameStatus,
+    held: VecDeque<ChannelMessage>,          // messages a filtered wait skipped, oldest first
+    presence: Arc<std::sync::Mutex<Presence>>,
 }
 
 impl LongPoller {
@@ -36,7 +163,45 @@ impl LongPoller {
             rx: Arc::new(Mutex::new(rx)),
             status: GameStatus::Available,
             user_profile: profile.clone(),
+            held: VecDeque::new(),
+            presence: Arc::new(std::sync::Mutex::new(Presence {
+                waiting: 0,
+                last_seen: Instant::now(),
+            })),
+        }
+    }
+
+    /// keep a message that the current wait doesn't want for a later wait that does
+    fn hold(&mut self, message: ChannelMessage) {
+        if self.held.len() >= MAX_HELD_MESSAGES {
+            if let Some(dropped) = self.held.pop_front() {
+                tracing::warn!(
+                    "dropping a held message for {} [channel={}] [sequence={}]",
+                    self.user_id,
+                    dropped.channel,
+                    dropped.sequence
+                );
+            }
         }
+        self.held.push_back(message);
+    }
+
+    /// the oldest held message the filter wants.  held messages the client has acknowledged are dropped
+    fn take_held(&mut self, filter: Option<&MessageChannel>, since: Option<u64>) -> Option<ChannelMessage> {
+        if let Some(since) = since {
+            self.held.retain(|message| message.user_sequence > since);
+        }
+        let position = self.held.iter().position(|message| message.matches(filter))?;
+        self.held.remove(position)
+    }
+
+    fn message_response(message: ChannelMessage) -> ServiceResponse {
+        ServiceResponse::new(
+            "",
+            StatusCode::OK,
+            ResponseType::ChannelMessage(message),
+            GameError::NoError(String::default()),
+        )
     }
     /// Add the user to the hashmap by putting them in a LongPoller struct.
     ///
@@ -76,11 +241,12 @@ impl LongPoller {
             None => Err(GameError::BadId(format!("{} does not exist", user_id))),
         }
     }
-    /// Sends a message to a list of users.
+    /// Sends a message to a list of users, on the channel the message belongs on (see MessageChannel::for_message), or
+    /// to a topic, so that only the users in it get the message.
     ///
     /// # Arguments
     ///
-    /// * `to_users` - A vector of user IDs (represented as Strings) to whom the message will be sent.
+    /// * `to` - A vector of user IDs (represented as Strings) to whom the message will be sent, or a MessageTarget.
     ///
     /// * `message` - The message to send, of type `CatanMessage`.
     ///
@@ -91,36 +257,97 @@ impl LongPoller {
     /// whom the message could not be sent.
 
     pub async fn send_message(
+        to: impl Into<MessageTarget>,
+        message: &CatanMessage,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let (to_users, channel) = match to.into() {
+            MessageTarget::Users(user_ids) => (user_ids, MessageChannel::for_message(message)),
+            MessageTarget::Topic(channel) => (Self::topic_members(&channel).await, channel),
+            MessageTarget::Direct { from_id, to_id } => (vec![to_id], MessageChannel::Direct(from_id)),
+        };
+        Self::send_to_channel(to_users, &channel, message).await
+    }
+
+    /// the users in a topic.  everybody logged in is in the lobby; a game's players are in its topic from when it is
+    /// created until it is removed, whether they are logged in or not
+    pub async fn topic_members(channel: &MessageChannel) -> Vec<String> {
+        match channel {
+            MessageChannel::Lobby => {
+                let mut user_ids: Vec<String> = ALL_USERS_MAP.read().await.keys().cloned().collect();
+                user_ids.sort();
+                user_ids
+            }
+            _ => TOPICS.read().unwrap().get(channel).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// set who is in the topic
+    pub fn set_topic_members(channel: &MessageChannel, user_ids: Vec<String>) {
+        TOPICS.write().unwrap().insert(channel.clone(), user_ids);
+    }
+
+    /// forget the topic -- eg. when its game is removed
+    pub fn drop_topic(channel: &MessageChannel) {
+        TOPICS.write().unwrap().remove(channel);
+    }
+
+    /// the topics the user is in, other than the lobby
+    pub fn topics_of(user_id: &str) -> Vec<MessageChannel> {
+        TOPICS
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, members)| members.iter().any(|member| member == user_id))
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    /// Sends a message to a list of users on the given channel.  Each user gets the next sequence number for the
+    /// channel, and the message is kept for them (see missed_messages) even if they aren't connected -- and in the
+    /// outbox, so that it outlives a restart (see outbox.rs).  Fails the same way as send_message.
+    pub async fn send_to_channel(
         to_users: Vec<String>,
+        channel: &MessageChannel,
         message: &CatanMessage,
     ) -> Result<ServiceResponse, ServiceResponse> {
         log_thread_info!(
             "send_message",
-            "enter [to:{:#?}] [message={:?}]",
+            "enter [to:{:#?}] [channel={}] [message={:?}]",
             to_users,
+            channel,
             message
         );
         defer! {log_thread_info!("send_message","leave [to:{:#?}] [message={:?}]", to_users, message )};
 
-        let users_map = ALL_USERS_MAP.read().await; // Acquire read lock
+        //  each user gets the message's text in their language for the game
+        let channel_messages: Vec<ChannelMessage> = {
+            let mut history = MESSAGE_HISTORY.write().await;
+            to_users
+                .iter()
+                .map(|to| {
+                    let localized = i18n::localized_for(to, message);
+                    history
+                        .entry(to.clone())
+                        .or_default()
+                        .record(channel, localized.as_ref().unwrap_or(message))
+                })
+                .collect()
+        };
 
-        let service_response = ServiceResponse::new(
-            "",
-            StatusCode::OK,
-            ResponseType::ServiceMessage(message.clone()),
-            GameError::NoError(String::default()),
-        );
+        let users_map = ALL_USERS_MAP.read().await; // Acquire read lock
 
         // Collect the senders and check for missing users
         let mut senders = Vec::new();
+        let mut offline = Vec::new();
         let mut errors = Vec::new();
-        for to in &to_users {
+        for (to, channel_message) in to_users.iter().zip(channel_messages.into_iter()) {
             match users_map.get(to) {
                 Some(user) => {
                     let lp = user.read().await;
-                    senders.push(lp.tx.clone());
+                    senders.push((lp.tx.clone(), to, channel_message));
                 }
                 None => {
+                    offline.push((to, channel_message));
                     errors.push((
                         to.clone(),
                         GameError::BadId(format!("id {} not in list", to)),
@@ -130,9 +357,13 @@ impl LongPoller {
         }
         drop(users_map); // Explicitly drop the read lock
 
+        for (to, channel_message) in offline.iter() {
+            outbox::save(to, channel_message).await;
+        }
+
         // Send the messages
-        for (tx, to) in senders.into_iter().zip(to_users.iter()) {
-            if tx.send(service_response.clone()).await.is_err() {
+        for (tx, to, channel_message) in senders.into_iter() {
+            if tx.send(channel_message).await.is_err() {
                 errors.push((
                     to.clone(),
                     GameError::ChannelError(format!("error in tx.send for {}", to)),
@@ -157,11 +388,35 @@ impl LongPoller {
         }
     }
 
+    /// Puts a message that was kept for the user (see outbox.rs) in their mailbox.  `renumber` gives it the next
+    /// sequence numbers, for a message numbered by an earlier run of the service.
+    pub async fn requeue(user_id: &str, message: &ChannelMessage, renumber: bool) -> Result<(), GameError> {
+        let tx = match ALL_USERS_MAP.read().await.get(user_id) {
+            Some(user) => user.read().await.tx.clone(),
+            None => return Err(GameError::BadId(format!("id {} not in list", user_id))),
+        };
+        let message = if renumber {
+            MESSAGE_HISTORY
+                .write()
+                .await
+                .entry(user_id.to_owned())
+                .or_default()
+                .record(&message.channel, &message.message)
+        } else {
+            message.clone()
+        };
+        tx.send(message)
+            .await
+            .map_err(|_| GameError::ChannelError(format!("error in tx.send for {}", user_id)))
+    }
+
     /// Waits for a message for the specified user ID.
     ///
     /// # Arguments
     ///
     /// * `user_id` - The user ID for which to wait for a message.
+    /// * `filter` - Only return a message on this channel.  Messages on other channels are held (up to
+    ///   MAX_HELD_MESSAGES) and returned by a later wait that wants them.
     ///
     /// # Returns
     ///
@@ -173,29 +428,100 @@ impl LongPoller {
     /// It is designed to be used with a model that allows only one reader at a time for the
     /// specified user ID.
 
-    pub async fn wait(user_id: &str) -> Result<ServiceResponse, ServiceResponse> {
-        let user_rx = {
+    pub async fn wait(
+        user_id: &str,
+        filter: Option<&MessageChannel>,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        Self::wait_since(user_id, filter, None).await
+    }
+
+    /// Waits for a message like wait, for a client that says the last user_sequence it saw.  Everything up to `since`
+    /// is acknowledged and never returned again; if the user was sent anything after it that is still in the replay
+    /// buffer, the oldest of those is returned straight away.
+    pub async fn wait_since(
+        user_id: &str,
+        filter: Option<&MessageChannel>,
+        since: Option<u64>,
+    ) -> Result<ServiceResponse, ServiceResponse> {
+        let user = {
             let users_map = ALL_USERS_MAP.read().await;
             match users_map.get(user_id) {
-                Some(lp) => lp.read().await.rx.clone(),
+                Some(lp) => lp.clone(),
                 None => return Err(ServiceResponse::new_bad_id("in long poller", user_id)),
             }
         };
+        let (user_rx, presence) = {
+            let lp = user.read().await;
+            (lp.rx.clone(), lp.presence.clone())
+        };
+        let _waiting = WaitGuard::start(presence);
 
         // Access the rx by taking a write lock -- this'd be bad if there were multipler readers, but our MEP says
         // we can only have one at a time, *and* so does our mpsc channel.
         //
         let mut rx = user_rx.lock().await;
-        match rx.recv().await {
-            Some(msg) => Ok(msg),
-            None => Err(ServiceResponse::new(
-                &format!("error writing channel. [user_id={}]", user_id),
-                reqwest::StatusCode::INTERNAL_SERVER_ERROR,
-                ResponseType::NoData,
-                GameError::ChannelError(String::default()),
-            )),
+        if let Some(message) = user.write().await.take_held(filter, since) {
+            return Ok(Self::message_response(message));
+        }
+        if let Some(since) = since {
+            let missed = MESSAGE_HISTORY
+                .read()
+                .await
+                .get(user_id)
+                .and_then(|history| history.replay_after(since, filter));
+            if let Some(message) = missed {
+                return Ok(Self::message_response(message));
+            }
+        }
+        let acknowledged = |message: &ChannelMessage| {
+            !message.is_heartbeat() && since.map_or(false, |since| message.user_sequence <= since)
+        };
+        loop {
+            match rx.recv().await {
+                Some(message) if acknowledged(&message) => {}
+                Some(message) if message.matches(filter) => {
+                    return Ok(Self::message_response(message))
+                }
+                Some(message) => user.write().await.hold(message),
+                None => {
+                    return Err(ServiceResponse::new(
+                        &format!("error writing channel. [user_id={}]", user_id),
+                        reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+                        ResponseType::NoData,
+                        GameError::ChannelError(String::default()),
+                    ))
+                }
+            }
         }
     }
+
+    /// wait_since, giving up after `limit`.  Ok(None) means nothing came in time -- with a limit of zero, that nothing
+    /// was waiting -- and None waits as long as it takes, like wait_since.
+    pub async fn wait_for(
+        user_id: &str,
+        filter: Option<&MessageChannel>,
+        since: Option<u64>,
+        limit: Option<Duration>,
+    ) -> Result<Option<ServiceResponse>, ServiceResponse> {
+        let wait = Self::wait_since(user_id, filter, since);
+        match limit {
+            //  the wait looks at what is already there before it waits, so even a zero limit returns it
+            Some(limit) => match tokio::time::timeout(limit, wait).await {
+                Ok(message) => message.map(Some),
+                Err(_) => Ok(None),
+            },
+            None => wait.await.map(Some),
+        }
+    }
+
+    /// drop every user's mailbox, so nothing more is queued for anybody.  returns how many users were connected
+    pub async fn remove_all() -> usize {
+        let mut users_map = ALL_USERS_MAP.write().await;
+        let count = users_map.len();
+        users_map.clear();
+        count
+    }
+
     /// returns all logged in users marked as "Available"
     ///
     /// # Arguments
@@ -214,6 +540,99 @@ impl LongPoller {
         available
     }
 
+    /// true if the user has a long poller -- ie. they are logged in and listening for messages
+    pub async fn is_connected(user_id: &str) -> bool {
+        ALL_USERS_MAP.read().await.contains_key(user_id)
+    }
+
+    /// how long it has been since the user last had a wait open: zero while they are waiting, None if they don't have
+    /// a long poller at all
+    pub async fn idle_for(user_id: &str) -> Option<Duration> {
+        let user = ALL_USERS_MAP.read().await.get(user_id)?.clone();
+        let presence = user.read().await.presence.clone();
+        let idle = presence.lock().ok()?.idle();
+        Some(idle)
+    }
+
+    /// every logged in user and how long they have been idle
+    pub async fn activity() -> Vec<UserActivity> {
+        let users: Vec<Arc<RwLock<LongPoller>>> = ALL_USERS_MAP.read().await.values().cloned().collect();
+        let mut activity = Vec::with_capacity(users.len());
+        for user in users {
+            let lp = user.read().await;
+            let (idle, waiting) = match lp.presence.lock() {
+                Ok(presence) => (presence.idle(), presence.waiting > 0),
+                Err(_) => continue,
+            };
+            activity.push(UserActivity {
+                user_id: lp.user_id.clone(),
+                profile: lp.user_profile.clone(),
+                idle,
+                waiting,
+            });
+        }
+        activity
+    }
+
+    /// answer every open wait with a heartbeat.  users who aren't waiting don't get one, and a full mailbox is skipped,
+    /// so heartbeats never pile up.  returns how many were sent
+    pub async fn send_heartbeats(now: u64) -> usize {
+        let user_ids: Vec<String> = ALL_USERS_MAP.read().await.keys().cloned().collect();
+        Self::send_heartbeats_to(&user_ids, now).await
+    }
+
+    /// send_heartbeats, for just these users
+    pub async fn send_heartbeats_to(user_ids: &[String], now: u64) -> usize {
+        let users: Vec<Arc<RwLock<LongPoller>>> = {
+            let users_map = ALL_USERS_MAP.read().await;
+            user_ids.iter().filter_map(|user_id| users_map.get(user_id).cloned()).collect()
+        };
+        let mut sent = 0;
+        for user in users {
+            let lp = user.read().await;
+            let waiting = lp.presence.lock().map(|p| p.waiting > 0).unwrap_or(false);
+            if waiting && lp.tx.try_send(ChannelMessage::heartbeat(now)).is_ok() {
+                sent += 1;
+            }
+        }
+        sent
+    }
+
+    /// The messages sent to the user on the channel after `since`, oldest first, and the channel's last sequence
+    /// number.  Only the last MAX_RECENT_MESSAGES are kept, so a client that sees a gap between `since` and the first
+    /// message has missed more than that and should rely on the current state instead.
+    pub async fn missed_messages(
+        user_id: &str,
+        channel: &MessageChannel,
+        since: u64,
+    ) -> (u64, Vec<ChannelMessage>) {
+        let history = MESSAGE_HISTORY.read().await;
+        let history = match history.get(user_id) {
+            Some(history) => history,
+            None => return (0, vec![]),
+        };
+        let last = history.sequences.get(channel).copied().unwrap_or(0);
+        let missed = history.recent.get(channel).map_or(vec![], |recent| {
+            recent
+                .iter()
+                .filter(|message| message.sequence > since)
+                .cloned()
+                .collect()
+        });
+        (last, missed)
+    }
+
+    /// Forget what the users were sent on the channel -- eg. when a game is over and has been evicted.
+    pub async fn forget_channel(user_ids: &[String], channel: &MessageChannel) {
+        let mut history = MESSAGE_HISTORY.write().await;
+        for user_id in user_ids {
+            if let Some(history) = history.get_mut(user_id) {
+                history.sequences.remove(channel);
+                history.recent.remove(channel);
+            }
+        }
+    }
+
     pub async fn set_status(user_id: &str, status: GameStatus) -> Result<(), GameError> {
         let users_map = ALL_USERS_MAP.write().await; // Acquire write lock
 
@@ -273,9 +692,208 @@ mod tests {
                 .unwrap(); // Use the cloned message
         });
 
-        assert_eq!(LongPoller::wait("user5").await.unwrap().get_service_message().unwrap(), message);
-        assert!(LongPoller::wait("user6").await.is_err());
+        assert_eq!(LongPoller::wait("user5", None).await.unwrap().get_service_message().unwrap(), message);
+        assert!(LongPoller::wait("user6", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channel_filter() {
+        assert_eq!(
+            LongPoller::add_user("user7", &UserProfile::default()).await,
+            Ok(())
+        );
+        let game = MessageChannel::Game("game-7".to_owned());
+        let to = || vec!["user7".to_string()];
+        let lobby = MessageChannel::Lobby;
+        LongPoller::send_to_channel(to(), &lobby, &CatanMessage::Started("1".into()))
+            .await
+            .unwrap();
+        LongPoller::send_to_channel(to(), &game, &CatanMessage::Started("2".into()))
+            .await
+            .unwrap();
+        LongPoller::send_to_channel(to(), &game, &CatanMessage::Started("3".into()))
+            .await
+            .unwrap();
+
+        // the game's messages come first, in order, and the lobby's is held until it is asked for
+        let channel_message = |sr: ServiceResponse| match sr.response_type {
+            ResponseType::ChannelMessage(message) => message,
+            other => panic!("expected a ChannelMessage, got {:?}", other),
+        };
+        let first = channel_message(LongPoller::wait("user7", Some(&game)).await.unwrap());
+        assert_eq!((first.channel.clone(), first.sequence), (game.clone(), 1));
+        assert_eq!(first.message, CatanMessage::Started("2".into()));
+        let second = channel_message(LongPoller::wait("user7", Some(&game)).await.unwrap());
+        assert_eq!(second.sequence, 2);
+        let lobby = channel_message(LongPoller::wait("user7", None).await.unwrap());
+        assert_eq!((lobby.channel, lobby.sequence), (MessageChannel::Lobby, 1));
+        assert_eq!(lobby.message, CatanMessage::Started("1".into()));
+    }
+    #[tokio::test]
+    async fn test_missed_messages() {
+        let game = MessageChannel::Game("game-9".to_owned());
+        let to = || vec!["user9".to_string()];
+        assert_eq!(
+            LongPoller::add_user("user9", &UserProfile::default()).await,
+            Ok(())
+        );
+        LongPoller::send_to_channel(to(), &game, &CatanMessage::Started("1".into()))
+            .await
+            .unwrap();
+        LongPoller::wait("user9", None).await.unwrap();
+
+        // the connection dies: the next message can't be delivered, but it is kept
+        LongPoller::remove_user("user9").await.unwrap();
+        assert!(LongPoller::send_to_channel(to(), &game, &CatanMessage::Started("2".into()))
+            .await
+            .is_err());
+        assert_eq!(
+            LongPoller::add_user("user9", &UserProfile::default()).await,
+            Ok(())
+        );
+        let (last, missed) = LongPoller::missed_messages("user9", &game, 1).await;
+        assert_eq!(last, 2);
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].message, CatanMessage::Started("2".into()));
+
+        // sequence numbers keep counting across logins
+        LongPoller::send_to_channel(to(), &game, &CatanMessage::Started("3".into()))
+            .await
+            .unwrap();
+        assert_eq!(LongPoller::missed_messages("user9", &game, 0).await.1.len(), 3);
+        LongPoller::forget_channel(&to(), &game).await;
+        assert_eq!(LongPoller::missed_messages("user9", &game, 0).await, (0, vec![]));
+    }
+
+    #[tokio::test]
+    async fn test_wait_since() {
+        let to = || vec!["user10".to_string()];
+        assert_eq!(
+            LongPoller::add_user("user10", &UserProfile::default()).await,
+            Ok(())
+        );
+        let user_sequence = |sr: ServiceResponse| match sr.response_type {
+            ResponseType::ChannelMessage(message) => message.user_sequence,
+            other => panic!("expected a ChannelMessage, got {:?}", other),
+        };
+        let lobby = MessageChannel::Lobby;
+        let game = MessageChannel::Game("game-10".to_owned());
+        LongPoller::send_to_channel(to(), &lobby, &CatanMessage::Started("1".into()))
+            .await
+            .unwrap();
+        LongPoller::send_to_channel(to(), &game, &CatanMessage::Started("2".into()))
+            .await
+            .unwrap();
+        assert_eq!(user_sequence(LongPoller::wait_since("user10", None, Some(0)).await.unwrap()), 1);
+
+        // the client missed the answer: asking again with the same since gets the same message again
+        assert_eq!(user_sequence(LongPoller::wait_since("user10", None, Some(0)).await.unwrap()), 1);
+
+        // after a new login the mailbox is new, but what was missed is replayed, and acknowledged messages aren't
+        LongPoller::remove_user("user10").await.unwrap();
+        LongPoller::add_user("user10", &UserProfile::default()).await.unwrap();
+        assert_eq!(user_sequence(LongPoller::wait_since("user10", None, Some(1)).await.unwrap()), 2);
+        LongPoller::send_to_channel(to(), &lobby, &CatanMessage::Started("3".into()))
+            .await
+            .unwrap();
+        assert_eq!(user_sequence(LongPoller::wait_since("user10", None, Some(2)).await.unwrap()), 3);
+    }
+
+    #[tokio::test]
+    async fn test_idle_for() {
+        assert_eq!(LongPoller::idle_for("user8").await, None);
+        assert_eq!(
+            LongPoller::add_user("user8", &UserProfile::default()).await,
+            Ok(())
+        );
+        let waiting = tokio::spawn(async { LongPoller::wait("user8", None).await });
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(LongPoller::idle_for("user8").await, Some(Duration::ZERO));
+
+        LongPoller::send_message(vec!["user8".to_string()], &CatanMessage::Started("8".into()))
+            .await
+            .unwrap();
+        waiting.await.unwrap().unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        assert!(LongPoller::idle_for("user8").await.unwrap() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_topics() {
+        let game = MessageChannel::Game("topic game".to_owned());
+        LongPoller::add_user("user12", &UserProfile::default()).await.unwrap();
+        LongPoller::add_user("user13", &UserProfile::default()).await.unwrap();
+        LongPoller::set_topic_members(&game, vec!["user12".to_string()]);
+        assert_eq!(LongPoller::topics_of("user12"), vec![game.clone()]);
+        assert!(LongPoller::topics_of("user13").is_empty());
+
+        // only the game's players get a message sent to its topic
+        LongPoller::send_message(MessageTarget::Topic(game.clone()), &CatanMessage::Started("12".into()))
+            .await
+            .unwrap();
+        assert_eq!(LongPoller::missed_messages("user12", &game, 0).await.1.len(), 1);
+        assert!(LongPoller::missed_messages("user13", &game, 0).await.1.is_empty());
+
+        // everybody logged in is in the lobby
+        let lobby = LongPoller::topic_members(&MessageChannel::Lobby).await;
+        assert!(lobby.contains(&"user12".to_string()) && lobby.contains(&"user13".to_string()));
+
+        // a direct message is on the sender's channel
+        let direct = MessageTarget::Direct {
+            from_id: "user12".to_owned(),
+            to_id: "user13".to_owned(),
+        };
+        LongPoller::send_message(direct, &CatanMessage::Started("13".into())).await.unwrap();
+        let from_user12 = MessageChannel::Direct("user12".to_owned());
+        assert_eq!(LongPoller::missed_messages("user13", &from_user12, 0).await.1.len(), 1);
+
+        LongPoller::drop_topic(&game);
+        assert!(LongPoller::topic_members(&game).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for() {
+        LongPoller::add_user("user14", &UserProfile::default()).await.unwrap();
+        let nothing = LongPoller::wait_for("user14", None, None, Some(Duration::ZERO)).await;
+        assert!(matches!(nothing, Ok(None)));
+        let nothing = LongPoller::wait_for("user14", None, None, Some(Duration::from_millis(20))).await;
+        assert!(matches!(nothing, Ok(None)));
+
+        // a message that is already waiting comes back straight away, even without waiting at all
+        LongPoller::send_message(vec!["user14".to_string()], &CatanMessage::Started("14".into()))
+            .await
+            .unwrap();
+        let message = LongPoller::wait_for("user14", None, None, Some(Duration::ZERO)).await;
+        assert!(matches!(message, Ok(Some(_))));
+        assert!(LongPoller::wait_for("nobody", None, None, Some(Duration::ZERO)).await.is_err());
     }
+
+    #[tokio::test]
+    async fn test_heartbeats() {
+        let user = vec!["user11".to_string()];
+        LongPoller::add_user("user11", &UserProfile::default()).await.unwrap();
+        LongPoller::send_message(user.clone(), &CatanMessage::Started("11".into()))
+            .await
+            .unwrap();
+        LongPoller::wait("user11", None).await.unwrap();
+
+        // nobody is waiting, so nothing is sent
+        assert_eq!(LongPoller::send_heartbeats_to(&user, 42).await, 0);
+
+        // a heartbeat answers any wait, whatever its filter, and doesn't count as acknowledged
+        let game = MessageChannel::Game("some game".to_owned());
+        let waiting = tokio::spawn(async move { LongPoller::wait_since("user11", Some(&game), Some(1)).await });
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(LongPoller::send_heartbeats_to(&user, 42).await, 1);
+        match waiting.await.unwrap().unwrap().response_type {
+            ResponseType::ChannelMessage(message) => {
+                assert_eq!(message, ChannelMessage::heartbeat(42));
+                assert_eq!(message.user_sequence, 0);
+            }
+            other => panic!("expected a ChannelMessage, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_get_available_and_set_status() {
         // Add users
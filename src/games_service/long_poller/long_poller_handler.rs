@@ -1,26 +1,81 @@
-use actix_web::HttpResponse;
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::Deserialize;
 
 use crate::{
-    games_service::long_poller::long_poller::LongPoller, middleware::request_context_mw::RequestContext,
+    games_service::long_poller::{channels::MessageChannel, long_poller::LongPoller},
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
 };
 
+/// the longest a client can ask a long poll to wait
+pub const MAX_LONG_POLL_SECONDS: u64 = 300;
+
+#[derive(Debug, Deserialize)]
+pub struct LongPollQuery {
+    pub channel: Option<String>, // "lobby", "game:{game_id}" or "dm:{user_id}".  missing means every channel
+    pub since: Option<u64>,      // the last user_sequence the client saw.  acknowledges it and everything before it
+    pub timeout: Option<u64>,    // seconds to wait, at most MAX_LONG_POLL_SECONDS.  missing waits until a message comes
+    #[serde(default)]
+    pub immediate: bool, // don't wait at all: a short poll
+}
+
+impl LongPollQuery {
+    /// how long the call can wait for a message.  None waits until one comes
+    pub fn limit(&self) -> Option<Duration> {
+        if self.immediate {
+            return Some(Duration::ZERO);
+        }
+        self.timeout
+            .map(|seconds| Duration::from_secs(seconds.min(MAX_LONG_POLL_SECONDS)))
+    }
+}
+
 /**
  *  a GET that is a long polling get.  the call waits here until the game changes and then the service will signal
- *  and the call will complete, returning a CatanMessage.  the if GAME_HEADER is missing or "", then we longpoll
- *  for the LOBBY, otherwise send them for game updates.
+ *  and the call will complete, returning a ChannelMessage.  pass ?channel= to only get messages for the lobby, one
+ *  game, or one user's direct messages (see channels.rs).  pass ?since= with the last user_sequence the client saw
+ *  to get anything it missed after that, instead of only what is sent from now on
+ *
+ *  clients that care more about battery than latency can bound the wait with ?timeout={seconds}, or not wait at all
+ *  with ?immediate=true to get a message that is already waiting.  either way, if there is nothing to return the call
+ *  answers 204 No Content
  */
-pub async fn long_poll_handler(request_context: RequestContext) -> HttpResponse {
+pub async fn long_poll_handler(
+    query: web::Query<LongPollQuery>,
+    request_context: RequestContext,
+) -> HttpResponse {
     let user_id = &request_context
         .claims
         .as_ref()
         .expect("auth_mw should set this for all authenticated APIs")
         .id;
-    let message = LongPoller::wait(&user_id).await;
+    let filter = match query.channel.as_deref() {
+        Some(filter) => match MessageChannel::parse(filter) {
+            Some(channel) => Some(channel),
+            None => {
+                return ServiceResponse::new(
+                    &format!("{} is not a channel", filter),
+                    StatusCode::BAD_REQUEST,
+                    ResponseType::ErrorInfo(
+                        "use lobby, game:{game_id} or dm:{user_id}".to_owned(),
+                    ),
+                    GameError::HttpError(StatusCode::BAD_REQUEST),
+                )
+                .to_http_response()
+            }
+        },
+        None => None,
+    };
+    let message = LongPoller::wait_for(&user_id, filter.as_ref(), query.since, query.limit()).await;
 
     match message {
-        Ok(message) => HttpResponse::Ok()
+        Ok(Some(message)) => HttpResponse::Ok()
             .content_type("application/json")
             .json(message),
+        Ok(None) => HttpResponse::NoContent().finish(),
         Err(service_response) => service_response.to_http_response(),
     }
 }
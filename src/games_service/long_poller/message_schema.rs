@@ -0,0 +1,74 @@
+#![allow(dead_code)]
+/**
+ *  the JSON Schema of what the long poller returns: a ChannelMessage, with every CatanMessage variant and every game
+ *  payload they carry in its definitions.  it is generated from the Rust types with schemars, so it changes when they
+ *  do -- client authors can generate their bindings from it and check the traffic they get against it.
+ *
+ *  the schema is returned as it is, not wrapped in a ServiceResponse, so tools can use the url directly.
+ */
+use actix_web::HttpResponse;
+use schemars::{schema::RootSchema, schema_for};
+
+use super::channels::ChannelMessage;
+
+pub fn message_schema() -> RootSchema {
+    schema_for!(ChannelMessage)
+}
+
+pub async fn get_message_schema() -> HttpResponse {
+    HttpResponse::Ok().json(message_schema())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_schema() {
+        let schema = serde_json::to_value(message_schema()).unwrap();
+        assert_eq!(schema["title"], "ChannelMessage");
+        let definitions = schema["definitions"].as_object().expect("the payloads are definitions");
+        for name in [
+            "CatanMessage",
+            "MessageChannel",
+            "RegularGame",
+            "Player",
+            "TurnSummary",
+            "AchievementUnlockedData",
+        ]
+        .iter()
+        {
+            assert!(definitions.contains_key(*name), "{} is missing", name);
+        }
+        // the maps go over the wire as lists of pairs, and the schema has to say so
+        assert_eq!(definitions["RegularGame"]["properties"]["Players"]["type"], "array");
+
+        let variants = definitions["CatanMessage"].to_string();
+        for variant in [
+            "GameUpdate",
+            "Invite",
+            "InvitationResponse",
+            "GameCreated",
+            "PlayerAdded",
+            "PlayerRemoved",
+            "HostChanged",
+            "Started",
+            "Ended",
+            "GameWon",
+            "ResolveDevCard",
+            "TurnSummary",
+            "AchievementUnlocked",
+            "Chat",
+            "SessionEnded",
+            "PresenceChanged",
+            "PlayerDisconnected",
+            "PlayerReconnected",
+            "Heartbeat",
+            "Error",
+        ]
+        .iter()
+        {
+            assert!(variants.contains(&format!("\"{}\"", variant)), "{} is missing", variant);
+        }
+    }
+}
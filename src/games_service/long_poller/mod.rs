@@ -1,2 +1,8 @@
+pub mod channels;
 pub mod long_poller;
-pub mod long_poller_handler;
\ No newline at end of file
+pub mod long_poller_handler;
+pub mod message_schema;
+pub mod outbox;
+pub mod presence;
+pub mod sse;
+pub mod websocket;
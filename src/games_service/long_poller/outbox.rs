@@ -0,0 +1,160 @@
+#![allow(dead_code)]
+/**
+ *  the outbox: messages for users who aren't connected are written to the Outbox collection, so they outlive a restart
+ *  of the service, and are put in the user's mailbox when they next log in.  a message nobody collects is deleted by
+ *  Cosmos OUTBOX_TTL_SECONDS after it was sent (see PersistOutboxMessage).
+ *
+ *  the outbox is opened by the long poller's startup hook (see main.rs) -- until then, and in tests, messages for
+ *  users who aren't connected are only kept in memory (see LongPoller::missed_messages).  bots are never connected,
+ *  and never log in, so nothing is kept for them.
+ *
+ *  a message keeps the sequence numbers it was sent with as long as the service that numbered it is still running, so
+ *  a client that has already had it through missed_messages or ?since= doesn't get it again.  after a restart the
+ *  numbering starts again, so a message from before the restart is given the next numbers when it is delivered.
+ */
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    cosmos_db::cosmosdb::UserDbTrait,
+    games_service::bots::bots::is_bot,
+    shared::{
+        environment::{Environment, ProductionEnvironment},
+        service_models::PersistOutboxMessage,
+    },
+};
+
+use super::{channels::ChannelMessage, long_poller::LongPoller};
+
+/// how long a message waits for its user before it is thrown away
+pub const OUTBOX_TTL_SECONDS: u64 = 7 * 24 * 60 * 60;
+
+pub type OutboxDb = Arc<dyn UserDbTrait + Send + Sync>;
+
+lazy_static::lazy_static! {
+    static ref OUTBOX: RwLock<Option<OutboxDb>> = RwLock::new(None);
+    /// tells this run of the service from the ones before it
+    static ref BOOT_ID: String = uuid::Uuid::new_v4().to_string();
+}
+
+/// keep messages for users who aren't connected in this database from now on
+pub fn open(database: OutboxDb) {
+    *OUTBOX.write().unwrap() = Some(database);
+}
+
+/// stop keeping messages
+pub fn close() {
+    *OUTBOX.write().unwrap() = None;
+}
+
+fn outbox() -> Option<OutboxDb> {
+    OUTBOX.read().unwrap().clone()
+}
+
+/// keep the message for a user who isn't connected, if the outbox is open.  messages are sent from outside any
+/// request, so they are stamped with the production clock
+pub async fn save(user_id: &str, message: &ChannelMessage) {
+    if let Some(database) = outbox() {
+        save_to(database.as_ref(), user_id, message, ProductionEnvironment.now()).await;
+    }
+}
+
+pub async fn save_to(
+    database: &(dyn UserDbTrait + Send + Sync),
+    user_id: &str,
+    message: &ChannelMessage,
+    now: u64,
+) {
+    if is_bot(user_id) {
+        return;
+    }
+    let stored = PersistOutboxMessage::new(user_id, &BOOT_ID, message, now, OUTBOX_TTL_SECONDS);
+    if let Err(e) = database.add_to_outbox(&stored).await {
+        tracing::error!("couldn't keep a message for {}: {:#?}", user_id, e);
+    }
+}
+
+/// put the user's kept messages in their mailbox, in the background so that logging in doesn't wait for it.  now is
+/// the time of the login
+pub fn start_delivery(user_id: &str, now: u64) {
+    if let Some(database) = outbox() {
+        let user_id = user_id.to_owned();
+        actix_web::rt::spawn(async move {
+            deliver_from(database.as_ref(), &user_id, now).await;
+        });
+    }
+}
+
+/// put the user's kept messages in their mailbox, oldest first.  a message is deleted once it is in the mailbox, so
+/// one that couldn't be delivered (the user logged out again) waits for the next login.  returns how many went
+pub async fn deliver_from(database: &(dyn UserDbTrait + Send + Sync), user_id: &str, now: u64) -> usize {
+    let kept = match database.find_outbox(user_id, now).await {
+        Ok(kept) => kept,
+        Err(e) => {
+            tracing::error!("couldn't read the outbox for {}: {:#?}", user_id, e);
+            return 0;
+        }
+    };
+    let mut delivered = 0;
+    for stored in kept.iter() {
+        let renumber = stored.boot_id != *BOOT_ID;
+        if LongPoller::requeue(user_id, &stored.message, renumber).await.is_err() {
+            break;
+        }
+        if let Err(e) = database.delete_outbox_message(&stored.id).await {
+            tracing::warn!("couldn't delete a delivered message for {}: {:#?}", user_id, e);
+        }
+        delivered += 1;
+    }
+    if delivered > 0 {
+        tracing::info!("delivered {} kept messages to {}", delivered, user_id);
+    }
+    delivered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cosmos_db::mocked_db::TestDb,
+        games_service::{
+            game_container::game_messages::CatanMessage, long_poller::channels::MessageChannel,
+        },
+        shared::shared_models::{ResponseType, UserProfile},
+    };
+
+    #[tokio::test]
+    async fn test_outbox() {
+        let database = TestDb::new();
+        let kept = |user_sequence: u64| ChannelMessage {
+            channel: MessageChannel::Lobby,
+            sequence: user_sequence,
+            user_sequence,
+            message: CatanMessage::Started(format!("{}", user_sequence)),
+        };
+        let now = ProductionEnvironment.now();
+        save_to(&database, "outbox-user", &kept(1), now).await;
+        save_to(&database, "outbox-user", &kept(2), now).await;
+
+        // not logged in: nothing can be delivered, so it is all still there
+        assert_eq!(deliver_from(&database, "outbox-user", now).await, 0);
+        assert_eq!(database.find_outbox("outbox-user", now).await.unwrap().len(), 2);
+
+        LongPoller::add_user("outbox-user", &UserProfile::default()).await.unwrap();
+        assert_eq!(deliver_from(&database, "outbox-user", now).await, 2);
+        assert!(database.find_outbox("outbox-user", now).await.unwrap().is_empty());
+        for expected in ["1", "2"] {
+            match LongPoller::wait("outbox-user", None).await.unwrap().response_type {
+                ResponseType::ChannelMessage(message) => {
+                    assert_eq!(message.message, CatanMessage::Started(expected.to_owned()))
+                }
+                other => panic!("expected a ChannelMessage, got {:?}", other),
+            }
+        }
+
+        // expired messages aren't delivered
+        let mut stale = PersistOutboxMessage::new("outbox-user", &BOOT_ID, &kept(3), 0, OUTBOX_TTL_SECONDS);
+        stale.id = "stale".to_owned();
+        database.add_to_outbox(&stale).await.unwrap();
+        assert!(database.find_outbox("outbox-user", now).await.unwrap().is_empty());
+    }
+}
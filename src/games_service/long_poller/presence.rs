@@ -0,0 +1,156 @@
+#![allow(dead_code)]
+/**
+ *  who is actually there.  being logged in only means a user has a mailbox; a user is online while they are reading
+ *  it -- they have a long poll, socket or event stream open, or had one within ONLINE_WINDOW (see LongPoller::idle_for).
+ *
+ *  every HEARTBEAT_INTERVAL each open wait is answered with a Heartbeat, so that a long poll doesn't sit idle long
+ *  enough for a proxy to drop it and a client can tell a quiet service from a dead connection.  heartbeats aren't
+ *  numbered or kept (see ChannelMessage::heartbeat) and only go to users who are waiting, so they never fill a mailbox.
+ *
+ *  the presence monitor looks at everybody every PRESENCE_CHECK and sends PresenceChanged to everybody connected when
+ *  a user comes online or goes offline, so lobbies can show who is there.  GET /auth/api/v1/users/online lists the
+ *  users who are online now.
+ */
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::HttpResponse;
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::game_container::game_messages::CatanMessage,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+use super::{
+    channels::MessageChannel,
+    long_poller::{LongPoller, MessageTarget, UserActivity},
+};
+
+/// how long a user can go without a wait open before they are offline
+pub const ONLINE_WINDOW: Duration = Duration::from_secs(60);
+/// how often open waits are answered with a heartbeat
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// how often the presence monitor looks for users coming and going
+pub const PRESENCE_CHECK: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct PresenceData {
+    pub user_id: String,
+    pub display_name: String,
+    pub online: bool,
+    pub last_active: u64, // seconds since the UNIX epoch.  now, for a user who is waiting
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn to_presence(activity: &UserActivity, now: u64) -> PresenceData {
+    PresenceData {
+        user_id: activity.user_id.clone(),
+        display_name: activity.profile.display_name.clone(),
+        online: activity.idle < ONLINE_WINDOW,
+        last_active: now.saturating_sub(activity.idle.as_secs()),
+    }
+}
+
+/// the users who are online now
+pub async fn online_users() -> Vec<PresenceData> {
+    let now = now();
+    LongPoller::activity()
+        .await
+        .iter()
+        .map(|activity| to_presence(activity, now))
+        .filter(|presence| presence.online)
+        .collect()
+}
+
+/// true if the user has had a wait open within ONLINE_WINDOW
+pub async fn is_online(user_id: &str) -> bool {
+    matches!(LongPoller::idle_for(user_id).await, Some(idle) if idle < ONLINE_WINDOW)
+}
+
+/// what changed between two looks at who is online: the users who came online and the ones who went
+pub fn presence_changes(before: &[PresenceData], after: &[PresenceData]) -> Vec<PresenceData> {
+    let was_online = |user_id: &str| before.iter().any(|p| p.user_id == user_id && p.online);
+    let is_online = |user_id: &str| after.iter().any(|p| p.user_id == user_id && p.online);
+    let mut changes: Vec<PresenceData> = after
+        .iter()
+        .filter(|p| p.online && !was_online(&p.user_id))
+        .cloned()
+        .collect();
+    changes.extend(before.iter().filter(|p| p.online && !is_online(&p.user_id)).map(|p| {
+        let mut gone = after
+            .iter()
+            .find(|now| now.user_id == p.user_id)
+            .cloned()
+            .unwrap_or_else(|| p.clone()); // logged out: they were last active when we last looked
+        gone.online = false;
+        gone
+    }));
+    changes
+}
+
+/// send heartbeats and presence changes until the service stops
+pub fn start_presence_monitor() -> tokio::task::JoinHandle<()> {
+    actix_web::rt::spawn(async move {
+        let mut last_heartbeat = std::time::Instant::now();
+        let mut before: Vec<PresenceData> = Vec::new();
+        loop {
+            tokio::time::sleep(PRESENCE_CHECK).await;
+            if last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+                LongPoller::send_heartbeats(now()).await;
+                last_heartbeat = std::time::Instant::now();
+            }
+            let after = online_users().await;
+            let changes = presence_changes(&before, &after);
+            before = after;
+            if changes.is_empty() {
+                continue;
+            }
+            for change in changes {
+                let _ = LongPoller::send_message(
+                    MessageTarget::Topic(MessageChannel::Lobby),
+                    &CatanMessage::PresenceChanged(change),
+                )
+                .await;
+            }
+        }
+    })
+}
+
+pub async fn online_users_handler() -> HttpResponse {
+    ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::Presence(online_users().await),
+        GameError::NoError(String::default()),
+    )
+    .to_http_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence_changes() {
+        let presence = |user_id: &str, online: bool, last_active: u64| PresenceData {
+            user_id: user_id.to_owned(),
+            display_name: user_id.to_owned(),
+            online,
+            last_active,
+        };
+        let before = vec![presence("stays", true, 10), presence("leaves", true, 10)];
+        let after = vec![presence("stays", true, 20), presence("arrives", true, 20)];
+        let changes = presence_changes(&before, &after);
+        assert_eq!(changes, vec![presence("arrives", true, 20), presence("leaves", false, 10)]);
+        assert!(presence_changes(&after, &after).is_empty());
+    }
+}
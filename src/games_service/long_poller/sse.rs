@@ -0,0 +1,186 @@
+#![allow(dead_code)]
+/**
+ *  Server-Sent Events, for clients that can't open a WebSocket: GET /auth/api/v1/events streams the caller's messages
+ *  as text/event-stream.  like the socket (see websocket.rs) it reads the user's mailbox in the LongPoller, so it gets
+ *  the same ChannelMessages as the long poller, and only one transport reads the mailbox at a time.
+ *
+ *  each event is
+ *      id: lobby=3,game:{game_id}=12
+ *      event: GameUpdate
+ *      data: {the ChannelMessage as JSON}
+ *
+ *  sequence numbers count per channel, so the id is the last sequence number the stream has sent on every channel it
+ *  has sent on.  a client that reconnects sends the id of the last event it got as Last-Event-ID (browsers do this
+ *  themselves), and the stream starts with what it missed on those channels (see LongPoller::missed_messages) and
+ *  never sends a message twice.  a comment goes out every KEEP_ALIVE so that proxies don't close a quiet stream.
+ */
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+use actix_web::{http::header::HeaderMap, web::Bytes, HttpRequest, HttpResponse};
+use futures::stream;
+
+use crate::{
+    games_service::game_container::game_messages::CatanMessage,
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::ResponseType,
+};
+
+use super::{
+    channels::{ChannelMessage, MessageChannel},
+    long_poller::LongPoller,
+};
+
+/// how long the stream can be quiet before it sends a keep alive comment
+pub const KEEP_ALIVE: Duration = Duration::from_secs(15);
+pub const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+/// the last sequence number sent on each channel.  a BTreeMap so that the same channels always make the same id
+pub type SeenSequences = BTreeMap<String, u64>;
+
+/// the event id for what has been sent so far
+pub fn event_id(seen: &SeenSequences) -> String {
+    seen.iter()
+        .map(|(channel, sequence)| format!("{}={}", channel, sequence))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// what a Last-Event-ID says was sent.  anything that doesn't parse is left out -- the client just doesn't get that
+/// channel's missed messages
+pub fn parse_event_id(id: &str) -> SeenSequences {
+    id.split(',')
+        .filter_map(|part| {
+            let (channel, sequence) = part.rsplit_once('=')?;
+            MessageChannel::parse(channel)?;
+            Some((channel.to_owned(), sequence.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// the name of the message's variant -- the tag serde gives it -- so clients can listen for the events they want
+fn variant_name(message: &CatanMessage) -> String {
+    match serde_json::to_value(message) {
+        Ok(serde_json::Value::Object(object)) => object.keys().next().cloned().unwrap_or_default(),
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::default(),
+    }
+}
+
+/// Records the message as seen and returns the event for it -- or None if the client has already had it.
+pub fn to_event(message: &ChannelMessage, seen: &mut SeenSequences) -> Option<String> {
+    let channel = message.channel.to_string();
+    if seen.get(&channel).map_or(false, |last| message.sequence <= *last) {
+        return None;
+    }
+    seen.insert(channel, message.sequence);
+    let data = serde_json::to_string(message).ok()?;
+    Some(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event_id(seen),
+        variant_name(&message.message),
+        data
+    ))
+}
+
+struct EventStream {
+    user_id: String,
+    replay: VecDeque<ChannelMessage>,
+    seen: SeenSequences,
+}
+
+impl EventStream {
+    /// the next chunk to send: a missed message, the next message in the mailbox, or a keep alive.  None ends the
+    /// stream -- the user logged out
+    async fn next_chunk(&mut self) -> Option<String> {
+        loop {
+            let message = match self.replay.pop_front() {
+                Some(message) => message,
+                None => match tokio::time::timeout(KEEP_ALIVE, LongPoller::wait(&self.user_id, None)).await {
+                    Err(_) => return Some(": keep-alive\n\n".to_owned()),
+                    Ok(Err(_)) => return None,
+                    Ok(Ok(service_response)) => match service_response.response_type {
+                        // the stream has its own keep alive, and a heartbeat has no sequence to put in the event id
+                        ResponseType::ChannelMessage(message) if message.is_heartbeat() => {
+                            return Some(": keep-alive\n\n".to_owned())
+                        }
+                        ResponseType::ChannelMessage(message) => message,
+                        _ => continue,
+                    },
+                },
+            };
+            if let Some(event) = to_event(&message, &mut self.seen) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+async fn last_event_id(user_id: &str, headers: &HeaderMap) -> (SeenSequences, VecDeque<ChannelMessage>) {
+    let seen = headers
+        .get(LAST_EVENT_ID)
+        .and_then(|id| id.to_str().ok())
+        .map(parse_event_id)
+        .unwrap_or_default();
+    let mut replay = VecDeque::new();
+    for (channel, since) in seen.iter() {
+        if let Some(channel) = MessageChannel::parse(channel) {
+            let (_, missed) = LongPoller::missed_messages(user_id, &channel, *since).await;
+            replay.extend(missed);
+        }
+    }
+    (seen, replay)
+}
+
+/// stream the caller's messages as Server-Sent Events
+pub async fn events_handler(req: HttpRequest, request_context: RequestContext) -> HttpResponse {
+    let user_id = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs")
+        .id
+        .clone();
+    let (seen, replay) = last_event_id(&user_id, req.headers()).await;
+    let events = EventStream {
+        user_id,
+        replay,
+        seen,
+    };
+    let body = stream::unfold(events, |mut events| async move {
+        let chunk = events.next_chunk().await?;
+        Some((Ok::<Bytes, actix_web::Error>(Bytes::from(chunk)), events))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_ids() {
+        let mut seen = SeenSequences::new();
+        let message = |channel: MessageChannel, sequence: u64| ChannelMessage {
+            channel,
+            sequence,
+            user_sequence: sequence,
+            message: CatanMessage::Started("abc".to_owned()),
+        };
+        let event = to_event(&message(MessageChannel::Lobby, 3), &mut seen).unwrap();
+        assert!(event.starts_with("id: lobby=3\nevent: Started\ndata: {"));
+        let game = MessageChannel::Game("abc".to_owned());
+        let event = to_event(&message(game.clone(), 12), &mut seen).unwrap();
+        assert!(event.starts_with("id: game:abc=12,lobby=3\n"));
+
+        // a message the client has had isn't sent again
+        assert!(to_event(&message(game.clone(), 12), &mut seen).is_none());
+        assert!(to_event(&message(MessageChannel::Lobby, 2), &mut seen).is_none());
+
+        // the id the client sends back is what was seen
+        assert_eq!(parse_event_id(&event_id(&seen)), seen);
+        assert_eq!(parse_event_id("lobby=x,nothing=4,dm:u=7").len(), 1);
+    }
+}
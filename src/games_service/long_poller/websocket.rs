@@ -0,0 +1,186 @@
+#![allow(dead_code)]
+/**
+ *  the WebSocket transport: GET /auth/api/v1/ws upgrades to a socket that pushes the same ChannelMessages the long
+ *  poller returns, one JSON text frame each, without the client having to ask again after every message.
+ *
+ *  there is one dispatch layer for both transports: everything that is sent to a user (GameContainer::broadcast_message,
+ *  LongPoller::send_message...) goes into the user's mailbox in the LongPoller, with its channel and sequence number,
+ *  and whichever transport the user is connected with takes it out.  a socket is a long poll that never ends -- it
+ *  waits on the mailbox, sends what it gets and waits again -- so presence, held messages and missed_messages work
+ *  the same for both.  a user reads their mailbox with one transport at a time: while a socket is open a long poll
+ *  waits behind it.
+ *
+ *  the connection is authenticated when it is opened, like any other /auth call, and then checked again with every
+ *  heartbeat: the socket is closed when the token expires or the login's session is ended (see sessions.rs).  the
+ *  service pings every HEARTBEAT_INTERVAL and closes a socket that hasn't answered (or sent anything) for
+ *  CLIENT_TIMEOUT.  the client doesn't need to send anything but pongs; text frames from the client are ignored.
+ */
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use actix::{Actor, ActorContext, AsyncContext, Handler, Message, StreamHandler};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    shared::{service_models::Claims, shared_models::ResponseType},
+    user_service::sessions,
+};
+
+use super::long_poller::LongPoller;
+
+/// how often the service pings the client (and checks the login is still good)
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// how long the client can go without answering before the socket is closed
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// a message from the user's mailbox, ready to go out as a text frame
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Outgoing(String);
+
+/// the mailbox has gone -- the user logged out
+#[derive(Message)]
+#[rtype(result = "()")]
+struct MailboxClosed;
+
+pub struct WebSocketSession {
+    claims: Claims,
+    last_heard: Instant,
+}
+
+impl WebSocketSession {
+    pub fn new(claims: &Claims) -> Self {
+        Self {
+            claims: claims.clone(),
+            last_heard: Instant::now(),
+        }
+    }
+
+    /// true while the token the socket was opened with is still good
+    fn still_authorized(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as usize)
+            .unwrap_or_default();
+        let session_active = self
+            .claims
+            .session_id
+            .as_ref()
+            .map_or(true, |session_id| sessions::is_active(&self.claims.id, session_id));
+        self.claims.exp > now && session_active
+    }
+
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |session, ctx| {
+            if session.last_heard.elapsed() > CLIENT_TIMEOUT {
+                tracing::info!("closing the socket for {}: no answer to pings", session.claims.id);
+                ctx.stop();
+                return;
+            }
+            if !session.still_authorized() {
+                tracing::info!("closing the socket for {}: the login has ended", session.claims.id);
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Policy,
+                    description: Some("the login has ended".to_owned()),
+                }));
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    /// take messages out of the user's mailbox for as long as the socket is open.  the loop is owned by the actor, so
+    /// it is dropped with the socket -- and a wait that is dropped doesn't lose the message it was waiting for
+    fn forward_mailbox(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let user_id = self.claims.id.clone();
+        let address = ctx.address();
+        let forward = async move {
+            loop {
+                match LongPoller::wait(&user_id, None).await {
+                    Ok(service_response) => {
+                        let frame = match &service_response.response_type {
+                            ResponseType::ChannelMessage(message) => serde_json::to_string(message),
+                            _ => serde_json::to_string(&service_response),
+                        };
+                        match frame {
+                            Ok(frame) => address.do_send(Outgoing(frame)),
+                            Err(e) => tracing::error!("couldn't serialize a message for {}: {:#?}", user_id, e),
+                        }
+                    }
+                    Err(_) => {
+                        address.do_send(MailboxClosed);
+                        return;
+                    }
+                }
+            }
+        };
+        ctx.spawn(actix::fut::wrap_future(forward));
+    }
+}
+
+impl Actor for WebSocketSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        tracing::info!("{} opened a socket", self.claims.id);
+        self.heartbeat(ctx);
+        self.forward_mailbox(ctx);
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        tracing::info!("{} closed their socket", self.claims.id);
+    }
+}
+
+impl Handler<Outgoing> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, message: Outgoing, ctx: &mut Self::Context) {
+        ctx.text(message.0);
+    }
+}
+
+impl Handler<MailboxClosed> for WebSocketSession {
+    type Result = ();
+
+    fn handle(&mut self, _: MailboxClosed, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Normal,
+            description: Some("logged out".to_owned()),
+        }));
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketSession {
+    fn handle(&mut self, message: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        self.last_heard = Instant::now();
+        match message {
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!("socket error for {}: {:#?}", self.claims.id, e);
+                ctx.stop();
+            }
+        }
+    }
+}
+
+/// upgrade the request to a socket carrying the caller's messages
+pub async fn websocket_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    request_context: RequestContext,
+) -> Result<HttpResponse, actix_web::Error> {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should set this for all authenticated APIs");
+    ws::start(WebSocketSession::new(claims), &req, stream)
+}
@@ -1,10 +1,14 @@
+pub mod achievements;
+pub mod bots;
+pub mod chat;
 pub mod buildings;
 pub mod catan_games;
 pub mod game_handlers;
 
-mod game;
+pub mod game;
 pub mod harbors;
 pub mod player;
+pub mod public_results;
 pub mod roads;
 pub mod shared;
 pub mod tiles;
@@ -12,4 +16,5 @@ pub mod tiles;
 pub mod game_container;
 pub mod lobby;
 pub mod long_poller;
-pub mod actions;
\ No newline at end of file
+pub mod actions;
+pub mod webhooks;
\ No newline at end of file
@@ -1,7 +1,8 @@
 #![allow(dead_code)]
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct CalculatedState {
     knights_played: i8,
@@ -43,7 +44,36 @@ impl Default for CalculatedState {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+impl CalculatedState {
+    pub fn longest_road(&self) -> i8 {
+        self.longest_road
+    }
+    pub fn has_longest_road(&self) -> bool {
+        self.has_longest_road
+    }
+    pub fn known_score(&self) -> i8 {
+        self.known_score
+    }
+    pub fn knights_played(&self) -> i8 {
+        self.knights_played
+    }
+    pub fn has_largest_army(&self) -> bool {
+        self.has_largest_army
+    }
+    pub fn set_longest_road(&mut self, length: i8, has_longest_road: bool) {
+        self.longest_road = length;
+        self.has_longest_road = has_longest_road;
+    }
+    pub fn set_largest_army(&mut self, knights_played: i8, has_largest_army: bool) {
+        self.knights_played = knights_played;
+        self.has_largest_army = has_largest_army;
+    }
+    pub fn set_known_score(&mut self, known_score: i8) {
+        self.known_score = known_score;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 pub struct WonResources {
     sheep: i8,
     wood: i8,
@@ -77,7 +107,7 @@ impl Default for WonResources {
         }
     }
 }
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 pub struct ResourceCount {
     acquired: i32,
     lost: i32,
@@ -104,6 +134,12 @@ impl ResourceCount {
     pub fn lost(&self) -> i32 {
         self.lost
     }
+
+    /// add to the running totals after cards change hands
+    pub fn record(&mut self, acquired: u32, lost: u32) {
+        self.acquired += acquired as i32;
+        self.lost += lost as i32;
+    }
 }
 
 impl std::fmt::Display for ResourceCount {
@@ -1,11 +1,16 @@
 // Allow dead code in this module
 #![allow(dead_code)]
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
 use crate::shared::shared_models::UserProfile;
 
 use crate::games_service::{
     buildings::building::Building, harbors::harbor::Harbor, roads::road::Road,
+    shared::{
+        game_enums::DevCardType,
+        game_models::{BuildingSupply, ResourceCards},
+    },
 };
 
 use super::calculated_state::{CalculatedState, ResourceCount};
@@ -14,7 +19,7 @@ use super::player_enums::Target;
 //
 //  this contains all the "concrete" data the result from a players actions.  we separetely define the calculated
 //  data.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Player {
     pub profile: UserProfile,
@@ -23,9 +28,77 @@ pub struct Player {
     pub harbors: Vec<Harbor>,
     pub targets: Vec<Target>, // from this you can derive number of times 7 is rolled, how many knights played
     pub resource_count: ResourceCount, // total number of resources won and/or lost
+    pub resources: ResourceCards,      // the resource cards currently in the player's hand
+    pub victory_point_cards: u32,      // hidden from the other players until the game ends
+    pub dev_cards: Vec<DevCardType>,   // development cards bought but not played yet. hidden from the other players
+    pub supply: BuildingSupply,        // the roads, settlements and cities the player has left to build
     pub good_rolls: i8,       // the number of rolls the resulted in resources
     pub bad_rolls: i8,        // the number of rolls the resulted in no resources
     pub state: CalculatedState,
+    pub seat_index: usize, // stable for the life of the game, assigned in the order players join
+    pub presentation: SeatPresentation,
+    #[serde(default)]
+    pub resources_collected: ResourceCards, // every card the player got from the bank or a Monopoly, for GameStats
+    #[serde(default)]
+    pub dev_cards_played: u32,
+    #[serde(default)]
+    pub sevens_rolled: u32,
 }
 
-impl Player {}
+//
+//  the colors a seat is drawn with.  these are assigned by the service from the seat index so that every client
+//  agrees on who is who, no matter what the players picked in their profiles (or how many of them are named "Joe")
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct SeatPresentation {
+    pub background_color: String,
+    pub foreground_color: String,
+}
+
+// background/foreground pairs, indexed by seat
+const SEAT_COLORS: [(&str, &str); 6] = [
+    ("#C62828", "#FFFFFF"), // red
+    ("#1565C0", "#FFFFFF"), // blue
+    ("#FAFAFA", "#000000"), // white
+    ("#EF6C00", "#000000"), // orange
+    ("#2E7D32", "#FFFFFF"), // green
+    ("#6D4C41", "#FFFFFF"), // brown
+];
+
+impl SeatPresentation {
+    pub fn for_seat(seat_index: usize) -> Self {
+        let (background, foreground) = SEAT_COLORS[seat_index % SEAT_COLORS.len()];
+        Self {
+            background_color: background.to_owned(),
+            foreground_color: foreground.to_owned(),
+        }
+    }
+}
+
+impl Player {
+    pub fn new(profile: &UserProfile, seat_index: usize) -> Self {
+        // everybody in the game sees the game -- but who the player has blocked is nobody else's business
+        let mut profile = profile.clone();
+        profile.blocked_user_ids = Vec::new();
+        Self {
+            profile,
+            roads: vec![],
+            buildings: vec![],
+            harbors: vec![],
+            targets: vec![],
+            resource_count: ResourceCount::default(),
+            resources: ResourceCards::default(),
+            victory_point_cards: 0,
+            dev_cards: vec![],
+            supply: BuildingSupply::full(),
+            good_rolls: 0,
+            bad_rolls: 0,
+            state: CalculatedState::default(),
+            seat_index,
+            presentation: SeatPresentation::for_seat(seat_index),
+            resources_collected: ResourceCards::default(),
+            dev_cards_played: 0,
+            sevens_rolled: 0,
+        }
+    }
+}
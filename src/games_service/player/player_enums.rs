@@ -1,12 +1,28 @@
 use serde::{Deserialize, Serialize};
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+use schemars::JsonSchema;
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 pub enum Weapon {
     Knight,
     RolledSeven,
     PirateShip,
 }
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, JsonSchema)]
 pub struct Target {
     weapon: Weapon,
     target: String, // the user ID of the target
 }
+
+impl Target {
+    pub fn new(weapon: Weapon, target: &str) -> Self {
+        Self {
+            weapon,
+            target: target.to_owned(),
+        }
+    }
+    pub fn weapon(&self) -> &Weapon {
+        &self.weapon
+    }
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
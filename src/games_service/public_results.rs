@@ -0,0 +1,463 @@
+#![allow(dead_code)]
+/**
+ *  a read-only api for the results of finished games, for community sites to embed without credentials:
+ *
+ *      GET /api/v1/public/games                recent finished games, newest first (?count=, at most MAX_GAMES)
+ *      GET /api/v1/public/games/{game_id}      one finished game
+ *      GET /api/v1/public/leaderboard          wins and games played over the last ?days= (at most MAX_DAYS)
+ *
+ *  nobody is named unless they opted in (PUT /auth/api/v1/users/public-results), and even then only by an alias made
+ *  from their user id -- the same in every game, so a leaderboard means something, but not their name or id.  the alias
+ *  is an HMAC of the id keyed with PUBLIC_ALIAS_SECRET, so it can't be worked back to the id by hashing ids until one
+ *  matches.  players who haven't opted in are "Anonymous" in the games they played, and aren't on the leaderboard.
+ *
+ *  the documents are the contract: CONTRACT_VERSION goes up if a field is ever removed or changes meaning, and fields
+ *  are only ever added.  they aren't wrapped in a ServiceResponse, since the wrapper isn't part of the contract.
+ *
+ *  each document is cached here for CACHE_SECONDS, keyed by what was asked for (not the url, so made up query
+ *  parameters can't fill the cache), and sent with a Cache-Control and an ETag, so a site can answer its own visitors
+ *  from its cache.  callers are limited to REQUESTS_PER_MINUTE by the address that connected (see
+ *  request_context_mw.rs); over that they get a 429 with Retry-After.
+ */
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    shared::{
+        service_models::PersistGame,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+pub const CONTRACT_VERSION: u32 = 1;
+
+/// how long a document is cached, here and by whoever reads it
+pub const CACHE_SECONDS: u64 = 60;
+
+/// how many requests an address can make each minute
+pub const REQUESTS_PER_MINUTE: u32 = 60;
+
+pub const DEFAULT_GAMES: usize = 20;
+pub const MAX_GAMES: usize = 100;
+pub const DEFAULT_DAYS: u64 = 30;
+pub const MAX_DAYS: u64 = 365;
+
+const DAY_SECONDS: u64 = 24 * 60 * 60;
+const ANONYMOUS: &str = "Anonymous";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublicPlayerResult {
+    pub alias: String, // "Anonymous" for players who haven't opted in
+    pub victory_points: u32,
+    pub won: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublicGameSummary {
+    pub game_id: String,
+    pub game_type: String,
+    pub finished_at: u64, // seconds since the epoch
+    pub players: Vec<PublicPlayerResult>, // highest score first
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct LeaderboardEntry {
+    pub rank: usize, // players with the same wins and games share a rank
+    pub alias: String,
+    pub wins: u32,
+    pub games_played: u32,
+}
+
+/// what every document is wrapped in
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublicDocument<T> {
+    pub contract_version: u32,
+    pub generated_at: u64, // seconds since the epoch
+    pub data: T,
+}
+
+#[derive(Debug, Clone)]
+struct CachedDocument {
+    expires_at: u64,
+    etag: String,
+    body: String,
+}
+
+lazy_static::lazy_static! {
+    // what was asked for -> the document
+    static ref CACHE: Mutex<HashMap<String, CachedDocument>> = Mutex::new(HashMap::new());
+    // address -> (the minute, requests in it)
+    static ref REQUESTS: Mutex<HashMap<String, (u64, u32)>> = Mutex::new(HashMap::new());
+}
+
+/// the name the user has on public pages
+pub fn alias(user_id: &str, secret: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes a key of any length");
+    mac.update(user_id.as_bytes());
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .take(4)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("Player-{}", hex)
+}
+
+/// Ok, or how many seconds the address has to wait.  the window is the minute on the clock, not a sliding one
+pub fn check_rate(address: &str, now: u64) -> Result<(), u64> {
+    let minute = now / 60;
+    let mut requests = REQUESTS.lock().expect("the rate lock shouldn't be poisoned");
+    requests.retain(|_, (their_minute, _)| *their_minute == minute);
+    let (_, count) = requests.entry(address.to_owned()).or_insert((minute, 0));
+    if *count >= REQUESTS_PER_MINUTE {
+        return Err((minute + 1) * 60 - now);
+    }
+    *count += 1;
+    Ok(())
+}
+
+/// the game as the public sees it: only opted in players are named
+pub fn summarize(persist_game: &PersistGame, opted_in: &HashSet<String>, secret: &str) -> PublicGameSummary {
+    let game = &persist_game.game;
+    let mut players: Vec<PublicPlayerResult> = game
+        .players
+        .keys()
+        .map(|id| PublicPlayerResult {
+            alias: if opted_in.contains(id) { alias(id, secret) } else { ANONYMOUS.to_owned() },
+            victory_points: game.victory_points(id),
+            won: persist_game.winner_id.as_deref() == Some(id.as_str()),
+        })
+        .collect();
+    players.sort_by(|a, b| b.victory_points.cmp(&a.victory_points).then(b.won.cmp(&a.won)));
+    PublicGameSummary {
+        game_id: persist_game.id.clone(),
+        game_type: format!("{:?}", game.game_type),
+        finished_at: persist_game.finished_at,
+        players,
+    }
+}
+
+/// the opted in players' wins and games, best first
+pub fn leaderboard(games: &[PersistGame], opted_in: &HashSet<String>, secret: &str) -> Vec<LeaderboardEntry> {
+    let mut totals: HashMap<&str, (u32, u32)> = HashMap::new();
+    for game in games {
+        for id in game.player_ids.iter().filter(|id| opted_in.contains(*id)) {
+            let (wins, played) = totals.entry(id.as_str()).or_default();
+            *played += 1;
+            if game.winner_id.as_deref() == Some(id.as_str()) {
+                *wins += 1;
+            }
+        }
+    }
+    let mut entries: Vec<LeaderboardEntry> = totals
+        .into_iter()
+        .map(|(id, (wins, games_played))| LeaderboardEntry {
+            rank: 0,
+            alias: alias(id, secret),
+            wins,
+            games_played,
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.wins
+            .cmp(&a.wins)
+            .then(a.games_played.cmp(&b.games_played))
+            .then(a.alias.cmp(&b.alias))
+    });
+    for index in 0..entries.len() {
+        entries[index].rank = match index {
+            0 => 1,
+            _ if entries[index].wins == entries[index - 1].wins
+                && entries[index].games_played == entries[index - 1].games_played =>
+            {
+                entries[index - 1].rank
+            }
+            _ => index + 1,
+        };
+    }
+    entries
+}
+
+/// the players in the games who opted in, and haven't since been deleted
+async fn opted_in(
+    games: &[PersistGame],
+    request_context: &RequestContext,
+) -> Result<HashSet<String>, ServiceResponse> {
+    let mut opted_in = HashSet::new();
+    let mut checked = HashSet::new();
+    for game in games {
+        for id in game.player_ids.iter().filter(|id| !game.deleted_player_ids.contains(*id)) {
+            if !checked.insert(id.clone()) {
+                continue;
+            }
+            match request_context.database.find_user_by_id(id).await {
+                Ok(user) if user.public_results => {
+                    opted_in.insert(id.clone());
+                }
+                Ok(_) => {}
+                Err(e) if e.status == StatusCode::NOT_FOUND => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(opted_in)
+}
+
+fn document<T: Serialize>(data: T, request_context: &RequestContext) -> Result<String, ServiceResponse> {
+    Ok(serde_json::to_string(&PublicDocument {
+        contract_version: CONTRACT_VERSION,
+        generated_at: request_context.environment.now(),
+        data,
+    })?)
+}
+
+async fn recent_games_document(count: usize, request_context: &RequestContext) -> Result<String, ServiceResponse> {
+    let games = request_context.database.list_recent_games(count).await?;
+    let opted_in = opted_in(&games, request_context).await?;
+    let secret = &request_context.config.public_alias_secret;
+    document(
+        games.iter().map(|game| summarize(game, &opted_in, secret)).collect::<Vec<_>>(),
+        request_context,
+    )
+}
+
+async fn game_document(game_id: &str, request_context: &RequestContext) -> Result<String, ServiceResponse> {
+    let game = request_context.database.find_game_by_id(game_id).await.map_err(|_| {
+        ServiceResponse::new(
+            "no finished game with that id",
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::BadId(game_id.to_owned()),
+        )
+    })?;
+    let opted_in = opted_in(std::slice::from_ref(&game), request_context).await?;
+    document(
+        summarize(&game, &opted_in, &request_context.config.public_alias_secret),
+        request_context,
+    )
+}
+
+async fn leaderboard_document(days: u64, request_context: &RequestContext) -> Result<String, ServiceResponse> {
+    let games = request_context
+        .database
+        .list_games(request_context.environment.now().saturating_sub(days * DAY_SECONDS))
+        .await?;
+    let opted_in = opted_in(&games, request_context).await?;
+    document(
+        leaderboard(&games, &opted_in, &request_context.config.public_alias_secret),
+        request_context,
+    )
+}
+
+/**
+ *  answer from the cache if the document under key is fresh, else make it.  a caller that already has it
+ *  (If-None-Match) gets a 304.  the rate limit comes first, so a cached document doesn't let anybody around it
+ */
+async fn respond<F, FF>(request: &HttpRequest, key: String, request_context: &RequestContext, make: F) -> HttpResponse
+where
+    F: FnOnce() -> FF,
+    FF: std::future::Future<Output = Result<String, ServiceResponse>>,
+{
+    let now = request_context.environment.now();
+    let address = request_context.client_address.as_deref().unwrap_or("unknown");
+    if let Err(retry_after) = check_rate(address, now) {
+        let mut response = ServiceResponse::new(
+            "too many requests",
+            StatusCode::TOO_MANY_REQUESTS,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::TOO_MANY_REQUESTS),
+        )
+        .to_http_response();
+        if let Ok(value) = retry_after.to_string().parse() {
+            response
+                .headers_mut()
+                .insert(actix_web::http::header::RETRY_AFTER, value);
+        }
+        return response;
+    }
+
+    let cached = CACHE
+        .lock()
+        .expect("the cache lock shouldn't be poisoned")
+        .get(&key)
+        .filter(|cached| cached.expires_at > now)
+        .cloned();
+    let cached = match cached {
+        Some(cached) => cached,
+        None => match make().await {
+            Ok(body) => {
+                let etag: String = Sha256::digest(body.as_bytes())
+                    .iter()
+                    .take(16)
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                let cached = CachedDocument {
+                    expires_at: now + CACHE_SECONDS,
+                    etag: format!("\"{}\"", etag),
+                    body,
+                };
+                let mut cache = CACHE.lock().expect("the cache lock shouldn't be poisoned");
+                cache.retain(|_, cached| cached.expires_at > now);
+                cache.insert(key, cached.clone());
+                cached
+            }
+            Err(sr) => return sr.to_http_response(),
+        },
+    };
+
+    let max_age = cached.expires_at.saturating_sub(now);
+    let cache_control = format!("public, max-age={}", max_age);
+    let not_modified = request
+        .headers()
+        .get(actix_web::http::header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.split(',').any(|tag| tag.trim() == cached.etag));
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header(("Cache-Control", cache_control))
+            .insert_header(("ETag", cached.etag))
+            .finish();
+    }
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(("Cache-Control", cache_control))
+        .insert_header(("ETag", cached.etag))
+        .insert_header(("Access-Control-Allow-Origin", "*"))
+        .body(cached.body)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecentGamesQuery {
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    pub days: Option<u64>,
+}
+
+pub async fn recent_games_handler(
+    request: HttpRequest,
+    query: web::Query<RecentGamesQuery>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let count = query.count.unwrap_or(DEFAULT_GAMES).clamp(1, MAX_GAMES);
+    let key = format!("games?count={}", count);
+    respond(&request, key, &request_context, || recent_games_document(count, &request_context)).await
+}
+
+pub async fn game_handler(
+    request: HttpRequest,
+    game_id: web::Path<String>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let key = format!("games/{}", game_id);
+    respond(&request, key, &request_context, || game_document(&game_id, &request_context)).await
+}
+
+pub async fn leaderboard_handler(
+    request: HttpRequest,
+    query: web::Query<LeaderboardQuery>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let days = query.days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS);
+    let key = format!("leaderboard?days={}", days);
+    respond(&request, key, &request_context, || leaderboard_document(days, &request_context)).await
+}
+
+/// put the caller on the public pages, under their alias -- or take them off
+pub async fn set_public_results(
+    enabled: bool,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call");
+    let mut user = request_context.database.find_user_by_id(&claims.id).await?;
+    user.public_results = enabled;
+    request_context.database.update_or_create_user(&user).await?;
+    let message = if enabled {
+        alias(&claims.id, &request_context.config.public_alias_secret)
+    } else {
+        "opted out".to_owned()
+    };
+    Ok(ServiceResponse::new(
+        &message,
+        StatusCode::OK,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn set_public_results_handler(enabled: web::Json<bool>, request_context: RequestContext) -> HttpResponse {
+    set_public_results(enabled.into_inner(), &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::catan_games::games::regular::regular_game::RegularGame, shared::shared_models::UserProfile,
+    };
+
+    fn finished(id: &str, players: &[&str], winner: Option<&str>) -> PersistGame {
+        let game = RegularGame::new(&UserProfile::new_test_user(Some(players[0].to_string())));
+        let mut persist_game = PersistGame::from_game(&game, winner.map(|w| w.to_owned()));
+        persist_game.id = id.to_owned();
+        persist_game.player_ids = players.iter().map(|p| p.to_string()).collect();
+        persist_game
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        let secret = "a server secret";
+        assert_eq!(alias("someone", secret), alias("someone", secret));
+        assert_ne!(alias("someone", secret), alias("somebody else", secret));
+        assert_ne!(alias("someone", secret), alias("someone", "another secret"));
+        assert!(!alias("someone", secret).contains("someone"));
+
+        let games = vec![
+            finished("1", &["a", "b", "c"], Some("a")),
+            finished("2", &["a", "b"], Some("b")),
+            finished("3", &["b", "c"], Some("c")),
+            finished("4", &["a", "c"], None),
+        ];
+        let opted_in: HashSet<String> = ["a", "c"].iter().map(|id| id.to_string()).collect();
+        let board = leaderboard(&games, &opted_in, secret);
+        // b never opted in, so isn't there
+        assert_eq!(board.len(), 2);
+        assert!(board.iter().all(|entry| entry.alias != alias("b", secret)));
+        // a and c both won 1 of 3
+        assert_eq!((board[0].rank, board[0].wins, board[0].games_played), (1, 1, 3));
+        assert_eq!((board[1].rank, board[1].wins, board[1].games_played), (1, 1, 3));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let minute = 1_000 * 60;
+        for _ in 0..REQUESTS_PER_MINUTE {
+            assert_eq!(check_rate("10.0.0.1", minute), Ok(()));
+        }
+        assert_eq!(check_rate("10.0.0.1", minute + 20), Err(40));
+        assert_eq!(check_rate("10.0.0.2", minute + 20), Ok(()));
+        assert_eq!(check_rate("10.0.0.1", minute + 60), Ok(()));
+    }
+}
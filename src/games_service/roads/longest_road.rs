@@ -0,0 +1,208 @@
+#![allow(dead_code)]
+//
+//  Longest Road.
+//
+//  a road can be described from either of the two tiles it sits between, and a building from any of the (up to) three
+//  tiles that share its corner, so the keys can't be compared directly.  instead we identify corners and edges by the
+//  sum of the cube coordinates of the tiles that meet there: a corner is the sum of its three tiles and an edge is the
+//  sum of its two tiles.  those sums are unique and come out the same no matter which tile the key was built from --
+//  and the math works for tiles that are off the board too, so roads along the coast are handled like any other.
+use std::collections::HashSet;
+
+use crate::games_service::{
+    buildings::{building_enums::BuildingPosition, building_key::BuildingKey},
+    shared::game_enums::Direction,
+    tiles::tile_key::TileKey,
+};
+
+use super::{
+    road::{ADJACENT_INTERNAL_ROADS, DIRECTION_TO_BUILDING_POSITION_MAP},
+    road_key::RoadKey,
+};
+
+/// the fewest connected roads a player needs before they can hold Longest Road
+pub const MIN_LONGEST_ROAD: usize = 5;
+/// the victory points that go with holding Longest Road
+pub const LONGEST_ROAD_POINTS: u32 = 2;
+
+pub type Corner = (i32, i32, i32);
+pub type Edge = (i32, i32, i32);
+
+fn sum(keys: &[TileKey]) -> (i32, i32, i32) {
+    keys.iter()
+        .fold((0, 0, 0), |acc, key| (acc.0 + key.q, acc.1 + key.r, acc.2 + key.s))
+}
+
+/// the corner of tile_key between the sides facing first and second
+fn corner_between(tile_key: TileKey, first: Direction, second: Direction) -> Corner {
+    sum(&[
+        tile_key,
+        tile_key.get_neighbor_key(first),
+        tile_key.get_neighbor_key(second),
+    ])
+}
+
+pub fn edge_id(road_key: &RoadKey) -> Edge {
+    let tile_key = road_key.tile_key();
+    sum(&[tile_key, tile_key.get_neighbor_key(road_key.direction())])
+}
+
+/// the two corners at the ends of a road
+pub fn road_corners(road_key: &RoadKey) -> [Corner; 2] {
+    let tile_key = road_key.tile_key();
+    let direction = road_key.direction();
+    let adjacent = &ADJACENT_INTERNAL_ROADS[&direction];
+    [
+        corner_between(tile_key, direction, adjacent[0]),
+        corner_between(tile_key, direction, adjacent[1]),
+    ]
+}
+
+pub fn building_corner(building_key: &BuildingKey) -> Corner {
+    let (first, second) = DIRECTION_TO_BUILDING_POSITION_MAP
+        .iter()
+        .find(|(_, position)| **position == building_key.building_position)
+        .map(|(directions, _)| *directions)
+        .expect("every building position is between two sides");
+    corner_between(building_key.tile_key, first, second)
+}
+
+/// Calculates the length of the longest road that can be traced through a player's roads without using any road
+/// twice.
+///
+/// # Parameters
+///
+/// * roads - the keys of the roads the player has built
+/// * blocked - corners holding another player's settlement or city. a road can end at one of these, but can't continue
+///   through it
+pub fn longest_road_length(roads: &[RoadKey], blocked: &HashSet<Corner>) -> usize {
+    // the same road can show up under both of its keys
+    let mut seen = HashSet::new();
+    let edges: Vec<[Corner; 2]> = roads
+        .iter()
+        .filter(|road_key| seen.insert(edge_id(road_key)))
+        .map(road_corners)
+        .collect();
+    let mut used = vec![false; edges.len()];
+    let starts: HashSet<Corner> = edges.iter().flatten().cloned().collect();
+    starts
+        .iter()
+        .map(|corner| longest_from(*corner, &edges, &mut used, blocked))
+        .max()
+        .unwrap_or(0)
+}
+
+fn longest_from(
+    corner: Corner,
+    edges: &[[Corner; 2]],
+    used: &mut [bool],
+    blocked: &HashSet<Corner>,
+) -> usize {
+    let mut longest = 0;
+    for (index, [a, b]) in edges.iter().enumerate() {
+        if used[index] {
+            continue;
+        }
+        let next = if *a == corner {
+            *b
+        } else if *b == corner {
+            *a
+        } else {
+            continue;
+        };
+
+        used[index] = true;
+        let length = if blocked.contains(&next) {
+            1
+        } else {
+            1 + longest_from(next, edges, used, blocked)
+        };
+        used[index] = false;
+        longest = longest.max(length);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //  a 6 road ring around the center tile with a 2 road tail running out of its top right corner (between the North
+    //  and NorthEast tiles).  the longest single trace comes in along the tail and goes all the way around the ring
+    fn ring_with_tail() -> Vec<RoadKey> {
+        let center = TileKey::new(0, 0, 0);
+        let north_east = center.get_neighbor_key(Direction::NorthEast);
+        let mut roads: Vec<RoadKey> = vec![
+            Direction::North,
+            Direction::NorthEast,
+            Direction::SouthEast,
+            Direction::South,
+            Direction::SouthWest,
+            Direction::NorthWest,
+        ]
+        .into_iter()
+        .map(|direction| RoadKey::new(direction, center))
+        .collect();
+        roads.push(RoadKey::new(Direction::NorthWest, north_east));
+        roads.push(RoadKey::new(Direction::North, north_east));
+        roads
+    }
+
+    #[test]
+    fn test_keys_from_either_side_match() {
+        let center = TileKey::new(0, 0, 0);
+        let north = center.get_neighbor_key(Direction::North);
+        assert_eq!(
+            edge_id(&RoadKey::new(Direction::North, center)),
+            edge_id(&RoadKey::new(Direction::South, north))
+        );
+
+        let top_right = building_corner(&BuildingKey::new(BuildingPosition::TopRight, center));
+        let from_north = building_corner(&BuildingKey::new(BuildingPosition::BottomRight, north));
+        let from_north_east = building_corner(&BuildingKey::new(
+            BuildingPosition::Left,
+            center.get_neighbor_key(Direction::NorthEast),
+        ));
+        assert_eq!(top_right, from_north);
+        assert_eq!(top_right, from_north_east);
+        assert!(road_corners(&RoadKey::new(Direction::North, center)).contains(&top_right));
+    }
+
+    #[test]
+    fn test_longest_road_length() {
+        let roads = ring_with_tail();
+        let blocked = HashSet::new();
+        assert_eq!(longest_road_length(&roads, &blocked), 8);
+        assert_eq!(longest_road_length(&roads[..3], &blocked), 3);
+        assert_eq!(longest_road_length(&[], &blocked), 0);
+
+        // a road described from the other side of the tile is the same road
+        let center = TileKey::new(0, 0, 0);
+        let same_road = RoadKey::new(Direction::South, center.get_neighbor_key(Direction::North));
+        assert_eq!(
+            longest_road_length(&[RoadKey::new(Direction::North, center), same_road], &blocked),
+            1
+        );
+    }
+
+    #[test]
+    fn test_opponent_building_breaks_road() {
+        let center = TileKey::new(0, 0, 0);
+        let roads = ring_with_tail();
+        // an opponent settlement where the tail meets the ring: the ring can still be walked all the way around, but
+        // the tail can't be walked into it
+        let mut blocked = HashSet::new();
+        blocked.insert(building_corner(&BuildingKey::new(
+            BuildingPosition::TopRight,
+            center,
+        )));
+        assert_eq!(longest_road_length(&roads, &blocked), 6);
+
+        // a second one on the bottom of the ring leaves the 4 roads between the two settlements
+        blocked.insert(building_corner(&BuildingKey::new(
+            BuildingPosition::BottomRight,
+            center,
+        )));
+        assert_eq!(longest_road_length(&roads, &blocked), 4);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod longest_road;
 pub mod road;
 pub mod road_enums;
 pub mod road_key;
@@ -6,13 +6,14 @@ use crate::{
 };
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
 use std::collections::HashMap;
 
 use super::{road_enums::RoadState, road_key::RoadKey};
 
 // RoadProps struct
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Road {
     primary_key: RoadKey,      // ids[0]
@@ -31,6 +32,23 @@ impl Road {
             state: RoadState::Unbuilt,
         }
     }
+    pub fn primary_key(&self) -> &RoadKey {
+        &self.primary_key
+    }
+    pub fn state(&self) -> &RoadState {
+        &self.state
+    }
+    pub fn owner_id(&self) -> Option<String> {
+        self.owner.as_ref().and_then(|owner| owner.user_id.clone())
+    }
+    /// true if the road (or ship) has been built by the player with this id
+    pub fn is_owned_by(&self, user_id: &str) -> bool {
+        self.state != RoadState::Unbuilt && self.owner_id().as_deref() == Some(user_id)
+    }
+    pub fn build(&mut self, owner: &UserProfile, state: RoadState) {
+        self.owner = Some(owner.clone());
+        self.state = state;
+    }
 }
 
 pub static ADJACENT_INTERNAL_ROADS: Lazy<HashMap<Direction, Vec<Direction>>> = Lazy::new(|| {
@@ -2,10 +2,11 @@
 use crate::games_service::shared::game_enums::Direction;
 use crate::games_service::tiles::tile_key::TileKey;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use std::fmt;
 
 // RoadKey struct
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct RoadKey {
     tile_key: TileKey,    // the tile coordinates that Direction is relative to
@@ -19,6 +20,12 @@ impl RoadKey {
             tile_key: tile,
         }
     }
+    pub fn tile_key(&self) -> TileKey {
+        self.tile_key
+    }
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
 }
 
 impl fmt::Display for RoadKey {
@@ -2,6 +2,7 @@
 
 use crate::shared::shared_models::UserProfile;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use std::error::Error;
 use std::{fmt, str::FromStr};
 use strum_macros::EnumIter;
@@ -18,12 +19,30 @@ pub struct SupportedGames {
     pub catan_games: Vec<CatanGames>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Copy, Eq)]
+///
+/// who can join a game.  anybody can be invited to a public game; a private game can also be joined by anybody with
+/// its code (see join_codes.rs).  the lowercase names are for the ?visibility= query
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub enum GameVisibility {
+    #[serde(alias = "public")]
+    Public,
+    #[serde(alias = "private")]
+    Private,
+}
+
+impl Default for GameVisibility {
+    fn default() -> Self {
+        GameVisibility::Public
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Copy, Eq, JsonSchema)]
 pub enum CatanGames {
     Regular,
     Expansion,
     Seafarers,
     Seafarers4Player,
+    CitiesAndKnights,
 }
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Copy)]
 pub enum GameType {
@@ -56,7 +75,10 @@ pub enum GameAction {
     Build,
     Roll,
     MoveBaron,
+    Discard,
     Trade,
+    PlayDevCard,
+    ResolveDevCard,
     Next,
     Undo,
     Redo,
@@ -64,7 +86,7 @@ pub enum GameAction {
 
 //
 //  answers the question "what are we doing now?"
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Copy)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Copy, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub enum GameState {
     AddingPlayers,
@@ -73,6 +95,7 @@ pub enum GameState {
     AllocateResourceForward,
     AllocateResourceReverse,
     WaitingForRoll,
+    MustDiscard,
     MustMoveBaron,
     BuyingAndTrading,
     Supplemental,
@@ -86,7 +109,7 @@ pub enum GamePhase {
     Playing,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
 pub enum ResourceType {
     Sheep,
     Wood,
@@ -100,7 +123,7 @@ pub enum ResourceType {
     Sea,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub enum DevCardType {
     Knight,
     VictoryPoint,
@@ -134,7 +157,7 @@ impl fmt::Display for DirectionError {
     }
 }
 impl Error for DirectionError {}
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy, EnumIter)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy, EnumIter, JsonSchema)]
 pub enum Direction {
     North,
     NorthEast,
@@ -1,6 +1,22 @@
 use ::serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use serde_with::serde_as;
 
+use rand::Rng;
+
+use crate::{
+    games_service::{
+        bots::engine::BotDifficulty,
+        buildings::{building_enums::BuildingState, building_key::BuildingKey},
+        harbors::{harbor::Harbor, harbor_key::HarborKey},
+        roads::road_key::RoadKey,
+        tiles::{tile_enums::TileResource, tile_key::TileKey},
+    },
+    shared::shared_models::{GameError, UserProfile, UserType},
+};
+
+use super::game_enums::{DevCardType, GameAction, GameState, ResourceType};
+
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
@@ -15,3 +31,588 @@ pub struct AllocateResourceReverseData {
     pub user_id: String,
     pub is_first: bool,
 }
+
+///
+/// one line in the answer to "why can't I do this?" - a description of the rule and whether the current game
+/// satisfies it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActionPrecondition {
+    pub description: String,
+    pub satisfied: bool,
+}
+
+impl ActionPrecondition {
+    pub fn new(description: &str, satisfied: bool) -> Self {
+        Self {
+            description: description.to_owned(),
+            satisfied,
+        }
+    }
+}
+
+///
+/// returned by the explain api.  the action is legal only if every precondition is satisfied.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActionExplanation {
+    pub action: GameAction,
+    pub game_state: GameState,
+    pub is_legal: bool,
+    pub preconditions: Vec<ActionPrecondition>,
+}
+
+///
+/// the resource cards in a hand or in the bank.  only the five tradeable resources are tracked - any other
+/// ResourceType is treated as having a count of 0
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct ResourceCards {
+    pub wood: u32,
+    pub brick: u32,
+    pub sheep: u32,
+    pub wheat: u32,
+    pub ore: u32,
+}
+
+impl ResourceCards {
+    /// the five resources that have cards
+    pub const RESOURCES: [ResourceType; 5] = [
+        ResourceType::Wood,
+        ResourceType::Brick,
+        ResourceType::Sheep,
+        ResourceType::Wheat,
+        ResourceType::Ore,
+    ];
+
+    pub fn new(wood: u32, brick: u32, sheep: u32, wheat: u32, ore: u32) -> Self {
+        Self {
+            wood,
+            brick,
+            sheep,
+            wheat,
+            ore,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.wood + self.brick + self.sheep + self.wheat + self.ore
+    }
+
+    /// the total, or None if it doesn't fit in a u32 -- for cards a client sent, which can be anything
+    pub fn checked_total(&self) -> Option<u32> {
+        [self.brick, self.sheep, self.wheat, self.ore]
+            .iter()
+            .try_fold(self.wood, |total, count| total.checked_add(*count))
+    }
+
+    pub fn count(&self, resource: ResourceType) -> u32 {
+        match resource {
+            ResourceType::Wood => self.wood,
+            ResourceType::Brick => self.brick,
+            ResourceType::Sheep => self.sheep,
+            ResourceType::Wheat => self.wheat,
+            ResourceType::Ore => self.ore,
+            _ => 0,
+        }
+    }
+
+    fn count_mut(&mut self, resource: ResourceType) -> Option<&mut u32> {
+        match resource {
+            ResourceType::Wood => Some(&mut self.wood),
+            ResourceType::Brick => Some(&mut self.brick),
+            ResourceType::Sheep => Some(&mut self.sheep),
+            ResourceType::Wheat => Some(&mut self.wheat),
+            ResourceType::Ore => Some(&mut self.ore),
+            _ => None,
+        }
+    }
+
+    pub fn add(&mut self, resource: ResourceType, count: u32) {
+        if let Some(current) = self.count_mut(resource) {
+            *current += count;
+        }
+    }
+
+    pub fn add_cards(&mut self, other: &ResourceCards) {
+        self.wood += other.wood;
+        self.brick += other.brick;
+        self.sheep += other.sheep;
+        self.wheat += other.wheat;
+        self.ore += other.ore;
+    }
+
+    /// true if every count in other is covered by this hand
+    pub fn contains(&self, other: &ResourceCards) -> bool {
+        self.wood >= other.wood
+            && self.brick >= other.brick
+            && self.sheep >= other.sheep
+            && self.wheat >= other.wheat
+            && self.ore >= other.ore
+    }
+
+    /// removes other from this hand.  nothing is removed if the hand doesn't hold all of the cards.
+    pub fn subtract(&mut self, other: &ResourceCards) -> Result<(), GameError> {
+        if !self.contains(other) {
+            return Err(GameError::BadActionData(format!(
+                "can't remove {:?} from {:?}",
+                other, self
+            )));
+        }
+        self.wood -= other.wood;
+        self.brick -= other.brick;
+        self.sheep -= other.sheep;
+        self.wheat -= other.wheat;
+        self.ore -= other.ore;
+        Ok(())
+    }
+
+    /// chooses one card at random, weighted by how many of each resource are in the hand
+    pub fn pick_random(&self) -> Option<ResourceType> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rand::thread_rng().gen_range(0..total);
+        for resource in Self::RESOURCES {
+            let count = self.count(resource);
+            if pick < count {
+                return Some(resource);
+            }
+            pick -= count;
+        }
+        None
+    }
+
+    /// removes one card chosen at random, weighted by how many of each resource are in the hand
+    pub fn take_random(&mut self) -> Option<ResourceType> {
+        let resource = self.pick_random()?;
+        *self.count_mut(resource).expect("only card resources are picked") -= 1;
+        Some(resource)
+    }
+}
+
+///
+/// where resource cards come from and go to
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub enum CardHolder {
+    Bank,
+    Player(String), // user_id
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub enum LedgerReason {
+    StartingResources,
+    Roll,
+    Discard,
+    Steal,
+    PlayerTrade,
+    BankTrade,
+    Monopoly,
+    YearOfPlenty,
+    Purchase,
+    Forfeit, // a player who forfeited gives their hand back
+    Surgery, // an admin put a player's hand right (see surgery.rs)
+}
+
+///
+/// one movement of resource cards.  every card that changes hands is recorded in the game's ledger, so the bank and
+/// the hands always add up and the turn can be explained afterwards
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct LedgerEntry {
+    pub from: CardHolder,
+    pub to: CardHolder,
+    pub cards: ResourceCards,
+    pub reason: LedgerReason,
+}
+
+///
+/// a Monopoly or Year of Plenty card that has been played, but is waiting for the player to choose the resource(s)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct PendingDevCard {
+    pub user_id: String,
+    pub card: DevCardType,
+}
+
+///
+/// the body of the resolve api: the one resource to take from the other players with Monopoly, or the two resources
+/// (which can be the same) to take from the bank with Year of Plenty
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct DevCardResolutionData {
+    pub resources: Vec<ResourceType>,
+}
+
+///
+/// the pieces a player has left to build with.  every player starts with the same set, a piece comes out of the
+/// supply when it is built, and a settlement goes back when it is upgraded to a city
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct BuildingSupply {
+    pub roads: u32,
+    pub settlements: u32,
+    pub cities: u32,
+    pub ships: u32, // only used in Seafarers games
+}
+
+impl BuildingSupply {
+    pub const ROADS: u32 = 15;
+    pub const SETTLEMENTS: u32 = 5;
+    pub const CITIES: u32 = 4;
+    pub const SHIPS: u32 = 15;
+
+    /// the pieces a player starts the game with
+    pub fn full() -> Self {
+        Self {
+            roads: Self::ROADS,
+            settlements: Self::SETTLEMENTS,
+            cities: Self::CITIES,
+            ships: Self::SHIPS,
+        }
+    }
+
+    fn count_mut(&mut self, state: &BuildingState) -> Option<&mut u32> {
+        match state {
+            BuildingState::Settlement => Some(&mut self.settlements),
+            BuildingState::City => Some(&mut self.cities),
+            _ => None,
+        }
+    }
+
+    /// takes a settlement or city out of the supply.  anything else doesn't use a piece
+    pub fn take_building(&mut self, state: &BuildingState) -> Result<(), GameError> {
+        match self.count_mut(state) {
+            Some(0) => Err(GameError::ActionError(format!(
+                "there are no {:?} pieces left",
+                state
+            ))),
+            Some(count) => {
+                *count -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// puts a settlement or city back, e.g. when a settlement is upgraded
+    pub fn return_building(&mut self, state: &BuildingState) {
+        if let Some(count) = self.count_mut(state) {
+            *count += 1;
+        }
+    }
+
+    pub fn take_road(&mut self) -> Result<(), GameError> {
+        if self.roads == 0 {
+            return Err(GameError::ActionError("there are no roads left".to_owned()));
+        }
+        self.roads -= 1;
+        Ok(())
+    }
+
+    pub fn take_ship(&mut self) -> Result<(), GameError> {
+        if self.ships == 0 {
+            return Err(GameError::ActionError("there are no ships left".to_owned()));
+        }
+        self.ships -= 1;
+        Ok(())
+    }
+}
+
+///
+/// the body of the roll api.  the service rolls the dice -- this is only honored for test games so that the tests
+/// can force a particular number
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct RollData {
+    pub roll: u32,
+}
+
+///
+/// the body of the move baron api.  victim_id is required if any player other than the caller has a building on
+/// the target tile and cards in their hand
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct MoveBaronData {
+    pub tile_key: TileKey,
+    pub victim_id: Option<String>,
+}
+
+///
+/// the body of the trade offer and counter offer apis.  to_id is None for an offer any player can take.  the offer
+/// lasts for expires_in_seconds, or DEFAULT_TRADE_OFFER_SECONDS if that isn't set, and no longer than
+/// MAX_TRADE_OFFER_SECONDS
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct TradeOfferData {
+    pub to_id: Option<String>,
+    pub give: ResourceCards,
+    pub want: ResourceCards,
+    pub expires_in_seconds: Option<u64>,
+}
+
+///
+/// an open offer from one player to another (or to everybody).  the cards aren't held while the offer is open -- they
+/// are checked again when it is accepted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct TradeOffer {
+    pub offer_id: String,
+    pub from_id: String,
+    pub to_id: Option<String>,
+    pub give: ResourceCards,
+    pub want: ResourceCards,
+    pub expires_at: u64, // seconds since the epoch
+    pub counter_to: Option<String>, // the offer this one is a counter to
+    pub rejected_by: Vec<String>,
+}
+
+///
+/// the body of the bank trade api: trade give for count cards of want.  with no harbor the rate is 4:1.  naming a
+/// harbor the player has a settlement or city on gets 3:1 (or 2:1 if it is the harbor for give).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct BankTradeData {
+    pub give: ResourceType,
+    pub want: ResourceType,
+    pub count: u32,
+    pub harbor_key: Option<HarborKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum MemberRole {
+    Creator,   // the host: the player who created the game, or who it was handed to (see host.rs)
+    CoHost,    // the player the game goes to if the host leaves
+    Player,
+    Bot,       // played by the bot driver (see bots.rs)
+    Spectator, // a local seat its owner has made spectator only (see local_seats.rs)
+}
+
+///
+/// one person in a game, as returned by the members api
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct GameMember {
+    pub user_id: String,
+    pub display_name: String,
+    pub role: MemberRole,
+    pub user_type: UserType,
+    pub seat: Option<usize>, // where they are in the player order, once it has been set.  0 goes first
+    pub is_current_player: bool,
+    pub connected: bool,      // has a long poller open
+    pub profile: UserProfile, // what the caller can see of them (see profile_projection.rs)
+}
+
+///
+/// the body of the best-rate bank trade api: trade give for count cards of want, at the best rate the player gets
+/// from the bank or any harbor they have built on.  the service works out the rate
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct BestBankTradeData {
+    pub give: ResourceType,
+    pub want: ResourceType,
+    pub count: u32,
+}
+
+///
+/// how the game ends.  whatever the condition, a player who reaches the victory point target on their turn wins
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum WinCondition {
+    FirstToTarget,
+    MostPointsAfterRounds(u32), // the player with the most victory points after this many rounds wins
+    Timed(u32),                 // the player with the most victory points when this many minutes of play are up wins
+}
+
+///
+/// when a player can take back what they just did.  an undo goes back one action, and it is the state that action left
+/// the game in that decides: with after_roll false, the roll and every action after it in the turn is final, but ending
+/// the turn leaves the game waiting for the next roll, so it can be undone -- back to the end of the turn before.
+/// nothing can be undone once the game is over
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct UndoPolicy {
+    pub setup: bool,       // choosing the board and the player order, and placing the first settlements and roads
+    pub before_roll: bool, // the start of a turn, before the dice are rolled
+    pub after_roll: bool,  // the rest of the turn, including a 7
+}
+
+impl Default for UndoPolicy {
+    fn default() -> Self {
+        Self {
+            setup: true,
+            before_roll: true,
+            after_roll: true,
+        }
+    }
+}
+
+///
+/// what happens to the resource cards of a player who leaves a game that has started (see RegularGame::forfeit)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub enum RemovalPolicy {
+    ReturnToBank, // the cards go back to the bank
+    Freeze,       // the player keeps their hand, but it is out of play -- nobody can steal, take or trade for it
+    Redistribute, // the cards are dealt out one at a time to the players left, starting with the next player
+}
+
+impl Default for RemovalPolicy {
+    fn default() -> Self {
+        RemovalPolicy::ReturnToBank
+    }
+}
+
+///
+/// what the owner of a local user lets whoever is at that seat do (see local_seats.rs).  a local user without any set
+/// can do everything
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct LocalCapabilities {
+    pub can_trade: bool,      // offer, answer and bank trades
+    pub can_undo: bool,       // undo and redo
+    pub spectator_only: bool, // watch, but take no action at all
+}
+
+/// who plays a seat while its player has stepped away (see RegularGame::delegate)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub enum Delegate {
+    Player(String),     // another player in the game, who acts for the seat with X-Acting-As
+    Bot(BotDifficulty), // the bot driver plays the seat
+}
+
+impl Delegate {
+    /// who the event log says took an action for the seat
+    pub fn actor_id(&self) -> String {
+        match self {
+            Delegate::Player(user_id) => user_id.clone(),
+            Delegate::Bot(difficulty) => format!("bot:{:?}", difficulty),
+        }
+    }
+}
+
+impl Default for LocalCapabilities {
+    fn default() -> Self {
+        Self {
+            can_trade: true,
+            can_undo: true,
+            spectator_only: false,
+        }
+    }
+}
+
+impl UndoPolicy {
+    pub fn allows(&self, game_state: GameState) -> bool {
+        match game_state {
+            GameState::AddingPlayers
+            | GameState::ChoosingBoard
+            | GameState::SettingPlayerOrder
+            | GameState::AllocateResourceForward
+            | GameState::AllocateResourceReverse => self.setup,
+            GameState::WaitingForRoll => self.before_roll,
+            GameState::MustDiscard
+            | GameState::MustMoveBaron
+            | GameState::BuyingAndTrading
+            | GameState::Supplemental => self.after_roll,
+            GameState::GameOver => false,
+        }
+    }
+}
+
+///
+/// the options the creator picks before the game starts.  they are part of every GameUpdate, so clients can show the
+/// target, how far the game is through its rounds or time, and when undo is allowed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct GameOptions {
+    pub victory_points_to_win: u32,
+    pub win_condition: WinCondition,
+    #[serde(default)]
+    pub auto_end_turn_seconds: Option<u32>, // end the turn for a player with nothing left to do but end it, after this long
+    #[serde(default)]
+    pub undo_policy: UndoPolicy,
+    #[serde(default)]
+    pub removal_policy: RemovalPolicy,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        Self {
+            victory_points_to_win: 10,
+            win_condition: WinCondition::FirstToTarget,
+            auto_end_turn_seconds: None,
+            undo_policy: UndoPolicy::default(),
+            removal_policy: RemovalPolicy::default(),
+        }
+    }
+}
+
+impl GameOptions {
+    pub const VICTORY_POINT_TARGETS: [u32; 3] = [8, 10, 12];
+    pub const MAX_ROUNDS: u32 = 50;
+    pub const MIN_MINUTES: u32 = 15;
+    pub const MAX_MINUTES: u32 = 240;
+    pub const MIN_AUTO_END_TURN_SECONDS: u32 = 5;
+    pub const MAX_AUTO_END_TURN_SECONDS: u32 = 120;
+
+    /// what is wrong with the options, if anything
+    pub fn problem(&self) -> Option<String> {
+        if !Self::VICTORY_POINT_TARGETS.contains(&self.victory_points_to_win) {
+            return Some(format!(
+                "the victory point target has to be one of {:?}",
+                Self::VICTORY_POINT_TARGETS
+            ));
+        }
+        if let Some(seconds) = self.auto_end_turn_seconds {
+            if seconds < Self::MIN_AUTO_END_TURN_SECONDS || seconds > Self::MAX_AUTO_END_TURN_SECONDS {
+                return Some(format!(
+                    "turns can be ended automatically after {} to {} seconds",
+                    Self::MIN_AUTO_END_TURN_SECONDS,
+                    Self::MAX_AUTO_END_TURN_SECONDS
+                ));
+            }
+        }
+        match self.win_condition {
+            WinCondition::MostPointsAfterRounds(rounds) if rounds == 0 || rounds > Self::MAX_ROUNDS => Some(format!(
+                "a game can be 1 to {} rounds long",
+                Self::MAX_ROUNDS
+            )),
+            WinCondition::Timed(minutes) if minutes < Self::MIN_MINUTES || minutes > Self::MAX_MINUTES => {
+                Some(format!(
+                    "a timed game can be {} to {} minutes long",
+                    Self::MIN_MINUTES,
+                    Self::MAX_MINUTES
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+///
+/// the body of the custom game api: the creator's own board.  tiles are listed column by column, top to bottom, the
+/// same way RegularGameInfo lists them
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct CustomBoardData {
+    pub tile_resources: Vec<TileResource>,
+    pub rolls: Vec<u32>,             // one for each tile: 7 for the desert, 0 for sea
+    pub rows_per_column: Vec<u32>,   // eg. [3, 4, 5, 4, 3] for the regular board
+    pub harbors: Vec<Harbor>,
+    pub no_adjacent_six_eight: bool, // reject the board if a 6 or 8 is next to another 6 or 8
+}
+
+///
+/// the body of the build api: what to build, and where
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum BuildData {
+    Settlement(BuildingKey),
+    City(BuildingKey),
+    Road(RoadKey),
+    Ship(RoadKey), // Seafarers only: a ship goes on an edge next to the sea
+}
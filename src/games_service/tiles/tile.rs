@@ -6,11 +6,12 @@ use crate::games_service::shared::game_enums::Direction;
 use super::tile_enums::TileResource;
 use super::tile_key::TileKey;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use serde_with::serde_as;
 use std::collections::HashMap;
 
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Tile {
     pub current_resource: TileResource, // the resource (including a temp resource) that the tile currently holds
@@ -19,9 +20,11 @@ pub struct Tile {
     pub tile_key: TileKey,              // the position of the tile on the board
 
     #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(Direction, Road)>")]
     pub roads: HashMap<Direction, Road>, // all the roads around the tile
 
     #[serde_as(as = "Vec<(_, _)>")]
+    #[schemars(with = "Vec<(BuildingPosition, Building)>")]
     pub owned_buildings: HashMap<BuildingPosition, Building>, // the owned buildings that get resources for this tile
 }
 impl Tile {
@@ -1,19 +1,45 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use strum_macros::Display;
 
-//  these are not the same as ResourceType because they have Desert and GoldMine
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Copy, Display)]
+use crate::games_service::shared::game_enums::ResourceType;
+
+//  these are not the same as ResourceType because they have Desert, GoldMine and Sea
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Hash, Copy, Display, JsonSchema)]
 pub enum TileResource {
     Back,
     Brick,
     Desert,
     GoldMine,
     Ore,
+    Sea,
     Sheep,
     Wheat,
     Wood,
 }
 
+impl TileResource {
+    /// the resource card a tile produces when its number is rolled, if it produces one at all
+    pub fn produces(&self) -> Option<ResourceType> {
+        match self {
+            TileResource::Brick => Some(ResourceType::Brick),
+            TileResource::Ore => Some(ResourceType::Ore),
+            TileResource::Sheep => Some(ResourceType::Sheep),
+            TileResource::Wheat => Some(ResourceType::Wheat),
+            TileResource::Wood => Some(ResourceType::Wood),
+            TileResource::Back
+            | TileResource::Desert
+            | TileResource::GoldMine
+            | TileResource::Sea => None,
+        }
+    }
+
+    /// true for the tiles ships sail on.  everything else is land, which is where roads and buildings go
+    pub fn is_sea(&self) -> bool {
+        *self == TileResource::Sea
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum TileOrientation {
     FaceUp,
@@ -4,6 +4,7 @@ use crate::games_service::shared::game_enums::Direction;
 use once_cell::sync::Lazy;
 
 use ::serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
 use std::collections::HashMap;
 use strum::IntoEnumIterator;
@@ -19,7 +20,7 @@ static DIRECTIONS: Lazy<HashMap<Direction, TileKey>> = Lazy::new(|| {
     directions.insert(Direction::NorthWest, TileKey::new(-1, 0, 1));
     directions
 });
-#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Copy, Clone, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct TileKey {
     pub q: i32,
@@ -0,0 +1,448 @@
+#![allow(dead_code)]
+/**
+ *  webhooks for a game's events.  the creator of a game can pass ?webhook={https url} to POST /games/{game_type}, and
+ *  the service POSTs a WebhookPayload to the url when
+ *
+ *  1. the game starts
+ *  2. a turn starts
+ *  3. the game is over
+ *
+ *  each payload is signed with a secret the service makes for the game, which only the creator can get (GET
+ *  /games/{game_id}/webhook).  the X-Catan-Signature header is "sha256=" and the hex HMAC-SHA256 of
+ *  "{X-Catan-Timestamp}.{body}", so the receiver can check that the call came from us and isn't a replay of an old one.
+ *
+ *  the url's host is looked up when the webhook is registered, and refused if any address it has isn't on the public
+ *  internet -- loopback, private, link-local, the clouds' metadata endpoints -- so a webhook can't be pointed at the
+ *  service's own network.  deliveries connect to the address that was checked, without looking the host up again, and
+ *  don't follow redirects, so neither a DNS change nor the receiver can send them somewhere else.
+ *
+ *  the deliveries are made by the dispatcher (started by the lifecycle) off the request.  a delivery that fails -- an
+ *  error, or anything but a 2xx -- is retried MAX_ATTEMPTS times, waiting twice as long each time, and then dropped.
+ *  the webhooks are kept in memory for as long as the game is, and forgotten when the game is evicted
+ */
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_web::{web, HttpResponse};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::{
+    games_service::game_container::game_container::GameContainer,
+    middleware::request_context_mw::RequestContext,
+    shared::{
+        environment::Environment,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+/// how many times a delivery is tried before it is dropped
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// how long to wait before the first retry.  each one after that waits twice as long as the last
+pub const FIRST_RETRY: Duration = Duration::from_secs(2);
+
+/// how long the receiver has to answer
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const SIGNATURE_HEADER: &str = "X-Catan-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Catan-Timestamp";
+pub const EVENT_HEADER: &str = "X-Catan-Event";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    GameStarted,
+    TurnChanged,
+    GameOver,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub game_id: String,
+    pub occurred_at: u64, // seconds since the epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_player_id: Option<String>, // TurnChanged: whose turn it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winner_id: Option<String>, // GameOver: who won, if anybody did
+}
+
+/// what the creator gets from GET /games/{game_id}/webhook
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct GameWebhook {
+    pub url: String,
+    pub secret: String,
+}
+
+/// a url check_url has passed, and the address its host was found at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedUrl {
+    pub url: String,
+    host: String,
+    address: SocketAddr,
+}
+
+#[derive(Debug, Clone)]
+struct Registered {
+    webhook: GameWebhook,
+    target: CheckedUrl,
+}
+
+#[derive(Clone)]
+struct Delivery {
+    registered: Registered,
+    payload: WebhookPayload,
+    environment: Arc<dyn Environment>, // the clock of the request that fired it
+}
+
+lazy_static::lazy_static! {
+    // game_id -> its webhook
+    static ref WEBHOOKS: Mutex<HashMap<String, Registered>> = Mutex::new(HashMap::new());
+    // where fire() queues deliveries.  None until the dispatcher starts, and then nothing is delivered
+    static ref DISPATCHER: Mutex<Option<mpsc::UnboundedSender<Delivery>>> = Mutex::new(None);
+}
+
+fn bad_webhook(message: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        "bad webhook url",
+        StatusCode::BAD_REQUEST,
+        ResponseType::ErrorInfo(message.to_owned()),
+        GameError::HttpError(StatusCode::BAD_REQUEST),
+    )
+}
+
+/// true if the address is on the public internet.  anything that could reach the service's own network isn't
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local() // and 169.254.169.254, where most clouds keep their metadata
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)) // carrier-grade NAT, and Alibaba's metadata
+                || (a == 192 && b == 0 && v4.octets()[2] == 0) // IETF protocol assignments
+                || a >= 240 // reserved
+                || v4 == Ipv4Addr::new(168, 63, 129, 16)) // Azure's host endpoint
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (first & 0xfe00) == 0xfc00 // unique local, and AWS's metadata
+                || (first & 0xffc0) == 0xfe80 // link-local
+                || (first == 0x2001 && v6.segments()[1] == 0x0db8)) // documentation
+        }
+    }
+}
+
+/// the url's host and port, if it is an absolute https url
+fn parse_url(url: &str) -> Result<(url::Url, String, u16), ServiceResponse> {
+    let parsed = url::Url::parse(url.trim()).map_err(|e| bad_webhook(&format!("{} isn't a url: {}", url, e)))?;
+    if parsed.scheme() != "https" {
+        return Err(bad_webhook("webhooks have to be https"));
+    }
+    let host = match parsed.host_str() {
+        Some(host) if !host.is_empty() => host.trim_start_matches('[').trim_end_matches(']').to_owned(),
+        _ => return Err(bad_webhook("the webhook url needs a host")),
+    };
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    Ok((parsed, host, port))
+}
+
+/// the url, if it is an absolute https url whose host is only on the public internet
+pub async fn check_url(url: &str) -> Result<CheckedUrl, ServiceResponse> {
+    let (parsed, host, port) = parse_url(url)?;
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|e| bad_webhook(&format!("{} can't be found: {}", host, e)))?
+        .collect();
+    //  one bad address is enough: which one a connection gets isn't up to us
+    if let Some(address) = addresses.iter().find(|address| !is_public(address.ip())) {
+        return Err(bad_webhook(&format!("{} is at {}, which isn't on the internet", host, address.ip())));
+    }
+    match addresses.first() {
+        Some(address) => Ok(CheckedUrl {
+            url: parsed.to_string(),
+            host,
+            address: *address,
+        }),
+        None => Err(bad_webhook(&format!("{} doesn't have an address", host))),
+    }
+}
+
+/// give the game a webhook, with a new secret
+pub fn register(game_id: &str, target: CheckedUrl) -> GameWebhook {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let webhook = GameWebhook {
+        url: target.url.clone(),
+        secret: base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, secret),
+    };
+    WEBHOOKS.lock().expect("the webhook lock shouldn't be poisoned").insert(
+        game_id.to_owned(),
+        Registered {
+            webhook: webhook.clone(),
+            target,
+        },
+    );
+    webhook
+}
+
+fn registered_for(game_id: &str) -> Option<Registered> {
+    WEBHOOKS
+        .lock()
+        .expect("the webhook lock shouldn't be poisoned")
+        .get(game_id)
+        .cloned()
+}
+
+pub fn webhook_for(game_id: &str) -> Option<GameWebhook> {
+    registered_for(game_id).map(|registered| registered.webhook)
+}
+
+/// the game is gone -- nothing more is delivered for it
+pub fn forget(game_id: &str) {
+    WEBHOOKS
+        .lock()
+        .expect("the webhook lock shouldn't be poisoned")
+        .remove(game_id);
+}
+
+/// the X-Catan-Signature of the body sent at the timestamp
+pub fn signature(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes a key of any length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// queue the event for the game's webhook, if it has one
+pub fn fire(
+    game_id: &str,
+    event: WebhookEvent,
+    current_player_id: Option<&str>,
+    winner_id: Option<&str>,
+    request_context: &RequestContext,
+) {
+    let registered = match registered_for(game_id) {
+        Some(registered) => registered,
+        None => return,
+    };
+    let delivery = Delivery {
+        registered,
+        payload: WebhookPayload {
+            event,
+            game_id: game_id.to_owned(),
+            occurred_at: request_context.environment.now(),
+            current_player_id: current_player_id.map(|id| id.to_owned()),
+            winner_id: winner_id.map(|id| id.to_owned()),
+        },
+        environment: request_context.environment.clone(),
+    };
+    let dispatcher = DISPATCHER.lock().expect("the webhook lock shouldn't be poisoned");
+    match dispatcher.as_ref() {
+        Some(sender) if sender.send(delivery).is_ok() => {}
+        _ => tracing::warn!("the webhook dispatcher isn't running: dropped {:?} for {}", event, game_id),
+    }
+}
+
+/// one try at a delivery.  Err says why it didn't work
+async fn deliver(client: &reqwest::Client, delivery: &Delivery) -> Result<(), String> {
+    let body = serde_json::to_vec(&delivery.payload).map_err(|e| e.to_string())?;
+    let timestamp = delivery.environment.now();
+    let webhook = &delivery.registered.webhook;
+    let response = client
+        .post(&webhook.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header(EVENT_HEADER, format!("{:?}", delivery.payload.event))
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, signature(&webhook.secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(format!("the webhook answered {}", status)),
+    }
+}
+
+/// a client that only connects to the address the webhook's host was checked at, and doesn't follow redirects
+fn client_for(target: &CheckedUrl) -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .resolve(&target.host, target.address)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// try the delivery until it works or runs out of attempts, backing off between them
+async fn deliver_with_retries(delivery: Delivery) {
+    let client = match client_for(&delivery.registered.target) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("can't deliver webhooks for {}: {}", delivery.payload.game_id, e);
+            return;
+        }
+    };
+    let mut wait = FIRST_RETRY;
+    for attempt in 1..=MAX_ATTEMPTS {
+        match deliver(&client, &delivery).await {
+            Ok(()) => return,
+            Err(e) if attempt < MAX_ATTEMPTS => {
+                tracing::info!(
+                    "webhook {:?} for {} failed (attempt {}), retrying in {:?}: {}",
+                    delivery.payload.event,
+                    delivery.payload.game_id,
+                    attempt,
+                    wait,
+                    e
+                );
+                tokio::time::sleep(wait).await;
+                wait *= 2;
+            }
+            Err(e) => tracing::warn!(
+                "gave up on webhook {:?} for {} after {} attempts: {}",
+                delivery.payload.event,
+                delivery.payload.game_id,
+                MAX_ATTEMPTS,
+                e
+            ),
+        }
+    }
+}
+
+/// start delivering what fire() queues.  each delivery retries on its own, so a slow receiver doesn't hold up others
+pub fn start_dispatcher() -> JoinHandle<()> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Delivery>();
+    *DISPATCHER.lock().expect("the webhook lock shouldn't be poisoned") = Some(sender);
+    tokio::spawn(async move {
+        while let Some(delivery) = receiver.recv().await {
+            tokio::spawn(deliver_with_retries(delivery));
+        }
+    })
+}
+
+/// stop taking deliveries.  the ones already being retried are dropped with the runtime
+pub fn stop_dispatcher() {
+    DISPATCHER.lock().expect("the webhook lock shouldn't be poisoned").take();
+}
+
+/// the game's webhook and its secret.  only the creator can see them
+pub async fn get_webhook(game_id: &str, user_id: &str) -> Result<ServiceResponse, ServiceResponse> {
+    let (game, _) = GameContainer::current_game(game_id).await?;
+    if game.creator_id != user_id {
+        return Err(ServiceResponse::new(
+            "only the game's creator can see its webhook",
+            StatusCode::FORBIDDEN,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::FORBIDDEN),
+        ));
+    }
+    match webhook_for(game_id) {
+        Some(webhook) => Ok(ServiceResponse::new(
+            "webhook",
+            StatusCode::OK,
+            ResponseType::Webhook(webhook),
+            GameError::NoError(String::default()),
+        )),
+        None => Err(ServiceResponse::new(
+            "the game doesn't have a webhook",
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        )),
+    }
+}
+
+pub async fn get_webhook_handler(game_id: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("if claims can't unwrap, the call should fail in the auth middleware");
+    get_webhook(&game_id, &claims.id)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_webhooks() {
+        let request_context = RequestContext::test_default(false);
+        assert!(check_url("http://example.com/hook").await.is_err());
+        assert!(check_url("not a url").await.is_err());
+        let checked = check_url(" https://8.8.8.8/hook ").await.expect("https to a public address is fine");
+        assert_eq!(checked.url, "https://8.8.8.8/hook");
+        assert_eq!(checked.address, "8.8.8.8:443".parse().unwrap());
+
+        // nothing on the service's own network
+        for url in &[
+            "https://127.0.0.1/hook",
+            "https://localhost/hook",
+            "https://10.0.0.5/hook",
+            "https://192.168.1.1:8443/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://168.63.129.16/hook",
+            "https://[::1]/hook",
+            "https://[::ffff:127.0.0.1]/hook",
+            "https://[fd00:ec2::254]/hook",
+        ] {
+            assert!(check_url(url).await.is_err(), "{} was allowed", url);
+        }
+        assert!(is_public("2606:4700:4700::1111".parse().unwrap()));
+        assert!(!is_public("fe80::1".parse().unwrap()));
+        assert!(!is_public("100.100.100.200".parse().unwrap()));
+
+        let webhook = register("webhook-game", checked.clone());
+        assert_eq!(webhook_for("webhook-game"), Some(webhook.clone()));
+        let other = register("webhook-game-2", checked.clone());
+        assert_ne!(webhook.secret, other.secret);
+        assert!(client_for(&checked).is_ok());
+
+        // the receiver can check what we sent, and nothing else
+        let body = br#"{"Event":"GameStarted"}"#;
+        let signed = signature(&webhook.secret, 1700000000, body);
+        assert!(signed.starts_with("sha256="));
+        assert_eq!(signed.len(), "sha256=".len() + 64);
+        assert_eq!(signed, signature(&webhook.secret, 1700000000, body));
+        assert_ne!(signed, signature(&webhook.secret, 1700000001, body));
+        assert_ne!(signed, signature(&other.secret, 1700000000, body));
+
+        // without a dispatcher, firing is a no-op
+        fire("webhook-game", WebhookEvent::GameStarted, None, None, &request_context);
+
+        forget("webhook-game");
+        forget("webhook-game-2");
+        assert_eq!(webhook_for("webhook-game"), None);
+    }
+}
@@ -37,7 +37,7 @@ macro_rules! deserialize_from_array {
 #[macro_export]
 macro_rules! log_return_err {
     ( $e:expr ) => {{
-        log::error!("\t{}\n {:#?}", $e, $e);
+        tracing::error!("\t{}\n {:#?}", $e, $e);
         return Err($e);
     }};
 }
@@ -45,7 +45,7 @@ macro_rules! log_return_err {
 #[macro_export]
 macro_rules! new_unexpected_server_error {
     ( $e:expr, $msg:expr ) => {{
-        log::error!("\t{}\n {:#?}", $msg, $e);
+        tracing::error!("\t{}\n {:#?}", $msg, $e);
         Err(ServiceResponse::new(
             $msg,
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -93,7 +93,7 @@ macro_rules! new_unauthorized_response {
 #[macro_export]
 macro_rules! log_return_not_found {
     ( $e:expr, $msg:expr ) => {{
-        log::error!("\t{}\n {:#?}", $e, $e);
+        tracing::error!("\t{}\n {:#?}", $e, $e);
         return Err(ServiceResponse::new(
             $msg,
             StatusCode::NOT_FOUND,
@@ -119,7 +119,7 @@ macro_rules! new_not_found_error {
 macro_rules! log_return_bad_id {
     ( $id:expr,$msg:expr ) => {{
         use reqwest::StatusCode;
-        log::error!("badid in {}", $msg);
+        tracing::error!("badid in {}", $msg);
         return Err(ServiceResponse::new(
             $msg,
             StatusCode::NOT_FOUND,
@@ -132,7 +132,7 @@ macro_rules! log_return_bad_id {
 #[macro_export]
 macro_rules! log_return_bad_request {
     ( $e:expr, $msg:expr ) => {{
-        log::error!("\t{}\n {:#?}", $e, $e);
+        tracing::error!("\t{}\n {:#?}", $e, $e);
         return Err(ServiceResponse::new(
             $msg,
             StatusCode::BAD_REQUEST,
@@ -159,7 +159,7 @@ macro_rules! bad_request_from_string {
 macro_rules! log_and_return_azure_core_error {
     ( $e:expr, $msg:expr ) => {{
         use crate::macros::convert_status_code;
-        log::error!("\t{}\n {:#?}", $msg, $e);
+        tracing::error!("\t{}\n {:#?}", $msg, $e);
 
         let status_code = match $e.as_http_error() {
             Some(http_err) => convert_status_code(http_err.status()),
@@ -191,7 +191,7 @@ macro_rules! az_error_to_service_response {
         use crate::shared::shared_models::GameError;
 
         let msg = format!("command: {}\n Error: {:#?}", $cmd, $stderr);
-        log::error!("{}", &msg);
+        tracing::error!("{}", &msg);
 
         Err(ServiceResponse {
             message: String::default(),
@@ -210,7 +210,7 @@ macro_rules! log_return_serde_error {
         use crate::shared::models::GameError;
 
         let msg = format!("serde_json error: {} Message: {:#?}", $e, $hint);
-        log::error!("{}", &msg);
+        tracing::error!("{}", &msg);
 
         return Err(ServiceResponse {
             message: String::default(),
@@ -263,33 +263,20 @@ macro_rules! setup_test {
 #[macro_export]
 macro_rules! create_service {
     () => {{
-        use crate::create_unauthenticated_service;
+        use crate::middleware::request_context_mw::RequestContextMiddleware;
+        use crate::routes;
         use crate::AuthenticationMiddlewareFactory;
         use actix_cors::Cors;
         use actix_web::{web, App};
 
-        use crate::{
-            action_service, game_service, lobby_service, longpoll_service, profile_service,
-            user_service,
-        };
-
-        use crate::middleware::request_context_mw::RequestContextMiddleware;
-
+        //  the routes are all in routes.rs, so the service and the tests are built from the same list
         App::new()
           //  .wrap(Logger::default())
             .wrap(RequestContextMiddleware)
             .wrap(Cors::permissive())
-            .service(create_unauthenticated_service()) // Make sure this function is in scope
-            .service(
-                web::scope("auth/api/v1")
-                    .wrap(AuthenticationMiddlewareFactory)
-                    .service(user_service())
-                    .service(lobby_service())
-                    .service(game_service())
-                    .service(longpoll_service())
-                    .service(profile_service())
-                    .service(action_service()),
-            )
+            .service(routes::unauthenticated_service())
+            .service(routes::authenticated_service().wrap(AuthenticationMiddlewareFactory))
+            .default_service(web::to(routes::no_route))
     }};
 }
 
@@ -300,7 +287,7 @@ macro_rules! create_test_service {
         use crate::init_env_logger;
         use actix_web::test;
 
-        init_env_logger(log::LevelFilter::Trace, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::TRACE, crate::LevelFilter::ERROR).await;
 
         let app = test::init_service(create_service!()).await;
         app
@@ -310,21 +297,21 @@ macro_rules! create_test_service {
 #[macro_export]
 macro_rules! full_info {
     ($($arg:tt)*) => {
-        log::info!(target: &format!("{}:{}:", file!(), line!()), $($arg)*)
+        tracing::info!(file = file!(), line = line!(), $($arg)*)
     };
 }
 
 #[macro_export]
 macro_rules! log_thread_info {
     ($from:expr, $($arg:tt)*) => {
-        log::info!("[{}]:{},[{}:{}]", $from, { format!($($arg)*).replace("\n", "").replace("  ", "") }, file!(), line!())
+        tracing::info!("[{}]:{},[{}:{}]", $from, { format!($($arg)*).replace("\n", "").replace("  ", "") }, file!(), line!())
     };
 }
 
 #[macro_export]
 macro_rules! trace_thread_info {
     ($from:expr, $($arg:tt)*) => {{
-        //  log::trace!("{}:{},{},{}", file!(), line!(), $from, format!($($arg)*))
+        //  tracing::trace!("{}:{},{},{}", file!(), line!(), $from, format!($($arg)*))
     }};
 }
 #[macro_export]
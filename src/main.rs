@@ -6,35 +6,53 @@ mod cosmos_db;
 mod games_service;
 mod macros;
 mod middleware;
+mod routes;
 mod shared;
 mod test;
 mod user_service;
 
-use actix_web::{web, HttpResponse, HttpServer, Scope};
-
-use cosmos_db::cosmosdb::COLLECTION_NAME_VALUES;
-use games_service::actions::action_handlers;
-use games_service::long_poller::long_poller_handler::long_poll_handler;
+use actix_web::{HttpResponse, HttpServer};
+
+use cosmos_db::connection_manager::ConnectionManager;
+use cosmos_db::cosmosdb::{UserDb, COLLECTION_NAME_VALUES};
+use cosmos_db::schema::verify_schema;
+use games_service::long_poller::long_poller::LongPoller;
+use games_service::long_poller::outbox;
+use games_service::long_poller::presence;
+use shared::error_reporting::init_error_reporting;
+use shared::analytics_export;
+use shared::integrity;
+use shared::lifecycle::{Lifecycle, DEFAULT_HOOK_TIMEOUT};
+use shared::log_filter::{init_logging, LogFormat};
+use shared::service_info;
+use shared::smoke_test;
+use shared::status_page;
 use shared::shared_models::ServiceResponse;
 
+use std::cell::RefCell;
 use std::env;
 use std::net::ToSocketAddrs;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::azure_setup::azure_wrapper::verify_or_create_account;
 use crate::azure_setup::azure_wrapper::verify_or_create_collection;
 use crate::azure_setup::azure_wrapper::verify_or_create_database;
-use crate::games_service::lobby::lobby_handlers;
-use games_service::game_handlers;
+use crate::games_service::lobby::matchmaking;
+use games_service::webhooks;
 use lazy_static::lazy_static;
-use log::{error, LevelFilter};
+use tracing::error;
+pub use tracing::level_filters::LevelFilter;
 use middleware::authn_mw::AuthenticationMiddlewareFactory;
+use middleware::kc_proxy::{self, KeyCloakProxy};
+use middleware::request_context_mw::RequestContext;
 use middleware::service_config::SERVICE_CONFIG;
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 use std::sync::atomic::{AtomicBool, Ordering};
-use user_service::user_handlers;
 
-pub use log::info;
-pub use log::trace;
+pub use tracing::info;
+pub use tracing::trace;
 
 fn get_host_ip_and_port() -> (String, String) {
     let host_name = std::env::var("HOST_NAME").expect("HOST_NAME must be set");
@@ -61,17 +79,40 @@ fn get_host_ip_and_port() -> (String, String) {
  */
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    //
+    //  the smoke test runs against a deployed service and needs none of this one's config, so it goes first
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "--smoke-test" {
+        let host = args.get(2).expect("usage: --smoke-test <url> [report.xml]");
+        let report = args
+            .get(3)
+            .map(String::as_str)
+            .unwrap_or(smoke_test::DEFAULT_REPORT);
+        init_logging("info", LogFormat::from_env(), None);
+        let passed = smoke_test::run_and_report(host, report).await;
+        println!("smoke test {} -- report in {}", if passed { "passed" } else { "failed" }, report);
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+
     // Access CATAN_SECRETS to force initialization and potentially panic.
-    print!("env_logger set with {:#?}\n", SERVICE_CONFIG.rust_log);
+    print!("log filter set with {:#?}\n", SERVICE_CONFIG.rust_log);
     print!("ssl key file {:#?}\n", SERVICE_CONFIG.ssl_key_location);
     print!("ssl cert file {:#?}\n", SERVICE_CONFIG.ssl_cert_location);
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-    let args: Vec<String> = env::args().collect();
+    init_logging(&SERVICE_CONFIG.rust_log, LogFormat::from_env(), None);
+    // held until main returns so that queued reports are flushed on shutdown
+    let _error_reporting = init_error_reporting(SERVICE_CONFIG.sentry_dsn.as_deref());
+    info!("{}", service_info::banner(&service_info::service_info()));
 
     if args.len() > 1 && args[1] == "--setup" {
         setup_cosmos().expect("Setup failed and the app cannot continue.");
     }
 
+    status_page::start_clock();
+    let mut lifecycle = service_lifecycle();
+    if let Err(e) = lifecycle.start().await {
+        panic!("the service can't start: {}", e);
+    }
+
     let (ip_address, port) = get_host_ip_and_port();
 
     println!("Binding to IP: {}:{}", ip_address, port);
@@ -89,10 +130,157 @@ async fn main() -> std::io::Result<()> {
     //
     // set up the HttpServer - pass in the broker service as part of App data
     // we use the create_app! macro so that we always create the same shape of app in our tests
-    HttpServer::new(move || create_service!())
+    let served = HttpServer::new(move || create_service!())
         .bind_openssl(format!("{}:{}", ip_address, port), builder)?
         .run()
-        .await
+        .await;
+    lifecycle.stop().await;
+    served
+}
+
+/**
+ *  the subsystems the service starts before it takes requests, in order, and stops after it has finished serving
+ *  (see lifecycle.rs).  the background loops are aborted when they stop
+ */
+fn service_lifecycle() -> Lifecycle {
+    let monitor = Rc::new(RefCell::new(None));
+    let jobs = Rc::new(RefCell::new(Vec::new()));
+    let presence_monitor = Rc::new(RefCell::new(None));
+    let dispatcher = Rc::new(RefCell::new(None));
+    let key_refresh = Rc::new(RefCell::new(None));
+    let mut lifecycle = Lifecycle::new();
+    lifecycle
+        .require("database", 10, DEFAULT_HOOK_TIMEOUT, {
+            let monitor = monitor.clone();
+            move || {
+                let monitor = monitor.clone();
+                async move {
+                    //
+                    //  fail fast if the database isn't what the code expects, rather than on the first request
+                    //  that touches it
+                    if let Err(problems) = verify_schema(&SERVICE_CONFIG).await {
+                        for problem in problems.iter() {
+                            error!("{}", problem);
+                        }
+                        return Err(format!(
+                            "the database does not match what the service expects:\n\t{}",
+                            problems.join("\n\t")
+                        ));
+                    }
+                    *monitor.borrow_mut() = Some(ConnectionManager::start_monitor(Duration::from_secs(60)));
+                    Ok(())
+                }
+            }
+        })
+        .on_shutdown(move || {
+            let monitor = monitor.clone();
+            async move {
+                if let Some(monitor) = monitor.borrow_mut().take() {
+                    monitor.abort();
+                }
+                Ok(())
+            }
+        })
+        // in KeyCloak mode the realm's keys have to be there before the first token is checked (see kc_proxy.rs)
+        .require("keycloak", 15, DEFAULT_HOOK_TIMEOUT, {
+            let key_refresh = key_refresh.clone();
+            move || {
+                let key_refresh = key_refresh.clone();
+                async move {
+                    let config = SERVICE_CONFIG.keycloak.clone();
+                    if !config.enabled() {
+                        return Ok(());
+                    }
+                    config.check()?;
+                    KeyCloakProxy::new(&config).refresh_keys().await?;
+                    *key_refresh.borrow_mut() = Some(kc_proxy::start_key_refresh(config));
+                    Ok(())
+                }
+            }
+        })
+        .on_shutdown(move || {
+            let key_refresh = key_refresh.clone();
+            async move {
+                if let Some(key_refresh) = key_refresh.borrow_mut().take() {
+                    key_refresh.abort();
+                }
+                Ok(())
+            }
+        })
+        // mailboxes are made as users log in.  messages for users who aren't are kept in the outbox, and the presence
+        // monitor sends heartbeats and tells everybody who comes and goes
+        .require("long poller", 20, DEFAULT_HOOK_TIMEOUT, {
+            let presence_monitor = presence_monitor.clone();
+            move || {
+                let presence_monitor = presence_monitor.clone();
+                async move {
+                    outbox::open(Arc::new(UserDb::new(false, &SERVICE_CONFIG)));
+                    *presence_monitor.borrow_mut() = Some(presence::start_presence_monitor());
+                    Ok(())
+                }
+            }
+        })
+        .on_shutdown(move || {
+            let presence_monitor = presence_monitor.clone();
+            async move {
+                if let Some(presence_monitor) = presence_monitor.borrow_mut().take() {
+                    presence_monitor.abort();
+                }
+                outbox::close();
+                let count = LongPoller::remove_all().await;
+                info!("closed the mailboxes of {} users", count);
+                Ok(())
+            }
+        })
+        .optional("job scheduler", 30, DEFAULT_HOOK_TIMEOUT, {
+            let jobs = jobs.clone();
+            move || {
+                let jobs = jobs.clone();
+                async move {
+                    let mut jobs = jobs.borrow_mut();
+                    jobs.push(matchmaking::start_matchmaker(matchmaking::MATCH_INTERVAL));
+                    jobs.push(integrity::start_schedule(integrity::INTEGRITY_INTERVAL));
+                    if let Some(export_dir) = SERVICE_CONFIG.analytics_export_dir.clone() {
+                        jobs.push(analytics_export::start_schedule(
+                            export_dir,
+                            analytics_export::EXPORT_INTERVAL,
+                        ));
+                    }
+                    Ok(())
+                }
+            }
+        })
+        .on_shutdown(move || {
+            let jobs = jobs.clone();
+            async move {
+                for job in jobs.borrow_mut().drain(..) {
+                    job.abort();
+                }
+                Ok(())
+            }
+        })
+        // webhook deliveries are queued as games fire their events, and retried here
+        .optional("webhook dispatcher", 40, DEFAULT_HOOK_TIMEOUT, {
+            let dispatcher = dispatcher.clone();
+            move || {
+                let dispatcher = dispatcher.clone();
+                async move {
+                    *dispatcher.borrow_mut() = Some(webhooks::start_dispatcher());
+                    Ok(())
+                }
+            }
+        })
+        .on_shutdown(move || {
+            let dispatcher = dispatcher.clone();
+            async move {
+                webhooks::stop_dispatcher();
+                if let Some(dispatcher) = dispatcher.borrow_mut().take() {
+                    dispatcher.abort();
+                }
+                Ok(())
+            }
+        });
+    lifecycle
 }
 
 fn setup_cosmos() -> Result<(), ServiceResponse> {
@@ -143,199 +331,22 @@ async fn get_version() -> HttpResponse {
 }
 
 /**
- * Creates a set of unauthenticated services under the "/api/v1" path.
- * These endpoints are accessible without any user authentication and are mainly used for:
- *
- * - Version Information:
- *   - Retrieves the version information of the application.
- *   - URL: `https://localhost:8080/api/v1/version`
- *   - Method: `GET`
- *
- * - User Registration:
- *   - Registers a new user with the provided information.
- *   - URL: `https://localhost:8080/api/v1/users/register`
- *   - Method: `POST`
- *
- * - User Login:
- *   - Authenticates a user and returns a token or session information.
- *   - URL: `https://localhost:8080/api/v1/users/login`
- *   - Method: `POST`
- *
- * - Test Setup:
- *   - A special endpoint used only for testing purposes to set up test data.
- *   - URL: `https://localhost:8080/api/v1/test/verify-service`
- *   - Method: `POST`
+ * readiness probe: OK if the last database health check passed, SERVICE_UNAVAILABLE if it didn't (or hasn't run yet).
+ * test requests check their own database, since the monitor only watches the production one.
  */
-fn create_unauthenticated_service() -> Scope {
-    web::scope("/api").service(
-        web::scope("/v1")
-            .route("/version", web::get().to(get_version))
-            .route(
-                "/users/register",
-                web::post().to(user_handlers::register_handler),
-            )
-            .route("/users/login", web::post().to(user_handlers::login_handler))
-            .route(
-                "/test/verify-service",
-                web::post().to(user_handlers::verify_handler),
-            ) /* TEST ONLY */
-            .route(
-                "/users/validate-email/{token}",
-                web::get().to(user_handlers::validate_email),
-            ),
-    )
-}
-
-/**
- * Creates a set of user-related services under the "/users" path.
- * These endpoints allow for user management and are typically restricted to authenticated users:
- *
- * - List Users:
- *   - Retrieves a list of all users in the system.
- *   - URL: `https://localhost:8080/auth/api/v1/users`
- *   - Method: `GET`
- *
- * - Delete User:
- *   - Deletes a user with the given ID.
- *   - URL: `https://localhost:8080/auth/api/v1/users/{id}` (replace `{id}` with the user's ID)
- *   - Method: `DELETE`
- *
- * - Find User by ID:
- *   - Retrieves details of a specific user by their ID.
- *   - URL: `https://localhost:8080/auth/api/v1/users/{id}` (replace `{id}` with the user's ID)
- *   - Method: `GET`
- */
-fn user_service() -> Scope {
-    web::scope("/users")
-        .route("", web::get().to(user_handlers::list_users_handler))
-        .route(
-            "/local",
-            web::post().to(user_handlers::create_local_user_handler),
-        )
-        .route(
-            "/local/{id}",
-            web::get().to(user_handlers::get_local_users_handler),
-        )
-        .route(
-            "/local/{id}",
-            web::delete().to(user_handlers::delete_local_user_handler),
-        )
-        .route(
-            "/local",
-            web::put().to(user_handlers::update_local_user_handler),
-        )
-        .route("/{id}", web::delete().to(user_handlers::delete_handler))
-        .route(
-            "/{id}",
-            web::get().to(user_handlers::find_user_by_id_handler),
-        )
-        .route(
-            "/{id}",
-            web::put().to(user_handlers::update_profile_handler),
-        )
-        .route(
-            "/phone/validate/{code}",
-            web::post().to(user_handlers::validate_phone_handler),
-        )
-        .route(
-            "/phone/send-code",
-            web::post().to(user_handlers::send_phone_code_handler),
-        )
-        .route(
-            "/email/send-validation-email",
-            web::post().to(user_handlers::send_validation_email),
-        )
-        .route(
-            "/register-test-user",
-            web::post().to(user_handlers::register_test_user_handler),
-        )
-        .route(
-            "/rotate-login-keys",
-            web::post().to(user_handlers::rotate_login_keys_handler),
-        )
-}
-// fn local_user_service() -> Scope {
-
-// }
-/**
- * Creates a set of lobby-related services under the "/lobby" path.
- * These endpoints allow for handling lobby operations within the game, such as inviting and joining games.
- * They are typically restricted to authenticated users:
- *
- * - Get Lobby:
- *   - Retrieves information about the current lobby.
- *   - URL: `https://localhost:8080/auth/api/v1/lobby`
- *   - Method: `GET`
- *
- * - Invite to Lobby:
- *   - Sends an invite to another user to join the current lobby.
- *   - URL: `https://localhost:8080/auth/api/v1/lobby/invite`
- *   - Method: `POST`
- *
- * - Join Game:
- *   - Allows a user to join a game via the lobby.
- *   - URL: `https://localhost:8080/auth/api/v1/lobby/joingame`
- *   - Method: `POST`
- */
-fn lobby_service() -> Scope {
-    web::scope("/lobby")
-        .route("", web::get().to(lobby_handlers::get_lobby))
-        .route("/invite", web::post().to(lobby_handlers::post_invite))
-        .route(
-            "/acceptinvite",
-            web::post().to(lobby_handlers::respond_to_invite),
-        )
-}
-
-/**
- * Creates a set of game-related services under the "/games" path.
- * These endpoints enable various game operations, such as fetching supported games, creating a new game, and shuffling an existing game.
- * They are typically restricted to authenticated users:
- *
- * - Supported Games:
- *   - Retrieves information about the supported game types.
- *   - URL: `https://localhost:8080/auth/api/v1/games/`
- *   - Method: `GET`
- *
- * - New Game:
- *   - Creates a new game of the specified type.
- *   - URL: `https://localhost:8080/auth/api/v1/games/{game_type}`
- *   - Method: `POST`
- *
- * - Shuffle Game:
- *   - Initiates the shuffling of the specified game.
- *   - URL: `https://localhost:8080/auth/api/v1/games/shuffle/{game_id}`
- *   - Method: `POST`
- */
-fn game_service() -> Scope {
-    web::scope("/games")
-        .route("/", web::get().to(game_handlers::supported_games))
-        .route("/{game_type}", web::post().to(game_handlers::new_game))
-        .route(
-            "/shuffle/{game_id}",
-            web::post().to(game_handlers::shuffle_game),
-        )
-}
-
-fn action_service() -> Scope {
-    web::scope("/action")
-        .route("/start/{game_id}", web::post().to(action_handlers::next))
-        .route(
-            "/actions/{game_id}",
-            web::get().to(action_handlers::valid_actions),
-        )
-        .route("/next/{game_id}", web::post().to(action_handlers::next))
-}
-
-fn longpoll_service() -> Scope {
-    web::scope("/longpoll/{index}").route("", web::get().to(long_poll_handler))
-}
-
-fn profile_service() -> Scope {
-    web::scope("profile").route(
-        "/{email}",
-        web::get().to(user_handlers::get_profile_handler),
-    )
+async fn get_ready(request_context: RequestContext) -> HttpResponse {
+    let health = if request_context.is_test() {
+        let mut health = ConnectionManager::health();
+        let result = request_context.database.health_check().await;
+        health.healthy = result.is_ok();
+        health.last_error = result.err().map(|sr| sr.message);
+        health
+    } else {
+        ConnectionManager::health()
+    };
+    ConnectionManager::readiness(health)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
 }
 
 lazy_static! {
@@ -354,23 +365,21 @@ pub async fn init_env_logger(min_level: LevelFilter, cosmos_log_level: LevelFilt
         return;
     }
 
-    // Start by setting the global filter level to `min_level`
-    let mut builder =
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
-    builder.filter(None, min_level);
-
-    // Then, set the module-specific filter level
-    builder.filter(Some("catan_service::cosmos_db::cosmosdb"), cosmos_log_level);
-
-    match builder.try_init() {
-        Ok(()) => {
-            full_info!(
-                "logger initialized [min_level: {:#?}] [cosmos_min_level: {:#?}]",
-                min_level,
-                cosmos_log_level
-            );
-        }
-        Err(e) => error!("logger failed to init -- already inited?: {:#?}", e),
+    // the global level is `min_level`, with its own level for cosmos.  both can be changed later via the admin api
+    let directives = format!(
+        "{},catan_service::cosmos_db::cosmosdb={}",
+        min_level.to_string().to_lowercase(),
+        cosmos_log_level.to_string().to_lowercase()
+    );
+
+    if init_logging(&directives, LogFormat::from_env(), None) {
+        full_info!(
+            "logger initialized [min_level: {:#?}] [cosmos_min_level: {:#?}]",
+            min_level,
+            cosmos_log_level
+        );
+    } else {
+        error!("logger failed to init -- already inited?");
     }
     LOGGER_INIT.store(true, Ordering::Relaxed);
 }
@@ -379,10 +388,11 @@ pub async fn init_env_logger(min_level: LevelFilter, cosmos_log_level: LevelFilt
 mod tests {
     use crate::{
         create_service, create_test_service,
-        games_service::game_container::game_messages::GameHeader,
+        games_service::{game::SUPPORTED_GAMES, game_container::game_messages::GameHeader},
         init_env_logger,
         middleware::{request_context_mw::TestContext, service_config::SERVICE_CONFIG},
         setup_cosmos, setup_test,
+        shared::shared_models::ResponseType,
         test::{
             test_helpers::test::{delete_all_test_users, register_test_users},
             test_proxy::TestProxy,
@@ -394,8 +404,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_version_and_log_intialized() {
-        init_env_logger(log::LevelFilter::Trace, log::LevelFilter::Error).await;
-        init_env_logger(log::LevelFilter::Trace, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::TRACE, crate::LevelFilter::ERROR).await;
+        init_env_logger(crate::LevelFilter::TRACE, crate::LevelFilter::ERROR).await;
         let mut app = create_test_service!();
         let req = test::TestRequest::get().uri("/api/v1/version").to_request();
 
@@ -407,9 +417,48 @@ mod tests {
         assert_eq!(body, "version 1.0");
     }
 
+    #[tokio::test]
+    async fn test_info() {
+        let app = create_test_service!();
+        let proxy = TestProxy::new(&app, None);
+        let service_response = proxy.get("/api/v1/info", None).await;
+        assert_eq!(service_response.status, StatusCode::OK);
+        match service_response.response_type {
+            ResponseType::ServiceInfo(info) => {
+                assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+                assert!(!info.git_commit.is_empty());
+                assert_eq!(info.supported_games, SUPPORTED_GAMES.to_vec());
+            }
+            other => panic!("expected ServiceInfo, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_message_schema() {
+        let mut app = create_test_service!();
+        let req = test::TestRequest::get().uri("/api/v1/schema/messages").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), 200);
+        let schema: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(schema["title"], "ChannelMessage");
+        assert!(schema["definitions"]["CatanMessage"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_ready() {
+        let app = create_test_service!();
+        let proxy = TestProxy::new(&app, Some(TestContext::new(false, None)));
+        let service_response = proxy.get("/api/v1/ready", None).await;
+        assert_eq!(service_response.status, StatusCode::OK);
+        match service_response.response_type {
+            ResponseType::DbHealth(health) => assert!(health.healthy),
+            _ => panic!("expected DbHealth, got {:#?}", service_response.response_type),
+        }
+    }
+
     #[tokio::test]
     async fn create_user_login_check_profile() {
-        init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
 
         let app = create_test_service!();
         setup_test!(&app, false);
@@ -460,7 +509,7 @@ mod tests {
     #[tokio::test]
 
     async fn test_validate_phone_and_email() {
-        init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
         let app = create_test_service!();
         let code = 569342;
         let mut proxy = TestProxy::new(&app, Some(TestContext::new(true, Some(code))));
@@ -482,13 +531,34 @@ mod tests {
         assert!(auth_token.len() > 0);
         proxy.set_auth_token(&Some(auth_token));
         //
-        //  set the phone number and email
+        //  set the phone number.  the email can't be changed with the profile
+        let old_email = profile.pii.as_ref().unwrap().email.clone();
         profile.pii.as_mut().unwrap().phone_number = SERVICE_CONFIG.test_phone_number.clone();
         profile.pii.as_mut().unwrap().email = SERVICE_CONFIG.test_email.clone();
-        // update the profile
+        let service_response = proxy.update_profile(&profile).await;
+        assert_eq!(service_response.status, StatusCode::BAD_REQUEST);
+        profile.pii.as_mut().unwrap().email = old_email.clone();
         let service_response = proxy.update_profile(&profile).await;
         assert!(service_response.status.is_success());
 
+        //
+        //  change the email: the old one works until the link mailed to the new one is opened
+        let service_response = proxy.request_email_change(&SERVICE_CONFIG.test_email).await;
+        assert!(service_response.status.is_success());
+        let url_str = service_response
+            .get_url()
+            .expect("should be the confirmation url");
+        let encoded_token = url_str.rsplitn(2, '/').next().unwrap().to_owned();
+        assert!(proxy.login(&old_email, "password").await.status.is_success());
+        let service_response = proxy.confirm_email_change(&encoded_token).await;
+        assert!(service_response.status.is_success());
+        // the link only works once
+        let service_response = proxy.confirm_email_change(&encoded_token).await;
+        assert_eq!(service_response.status, StatusCode::UNAUTHORIZED);
+        assert!(!proxy.login(&old_email, "password").await.status.is_success());
+        profile.pii.as_mut().unwrap().email = SERVICE_CONFIG.test_email.clone();
+        profile.validated_email = true;
+
         //
         // we've change the email, which is encoded in the token and used as truth by the service -
         // so login again and get a new token
@@ -556,7 +626,7 @@ mod tests {
     }
     #[tokio::test]
     async fn test_setup() {
-        init_env_logger(log::LevelFilter::Trace, log::LevelFilter::Trace).await;
+        init_env_logger(crate::LevelFilter::TRACE, crate::LevelFilter::TRACE).await;
         setup_cosmos().expect("can't continue if setup fails!");
     }
 }
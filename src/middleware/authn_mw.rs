@@ -1,9 +1,9 @@
-use std::pin::Pin;
+use std::{pin::Pin, rc::Rc};
 
 use actix::fut::err;
 use actix_service::{Service, Transform};
 use actix_web::{
-    dev::ServiceRequest, dev::ServiceResponse, error::ErrorUnauthorized, Error, HttpMessage,
+    dev::ServiceRequest, dev::ServiceResponse, error::{ErrorForbidden, ErrorUnauthorized}, Error, HttpMessage,
 };
 
 use futures::{
@@ -11,7 +11,8 @@ use futures::{
     Future,
 };
 
-use super::request_context_mw::RequestContext;
+use super::{kc_proxy, request_context_mw::RequestContext};
+use crate::{games_service::actions::seat_tokens, shared::service_models::Claims};
 
 // AuthenticationMiddlewareFactory serves as a factory to create instances of AuthenticationMiddleware
 // which is the actual middleware component. It implements the Transform trait required by
@@ -37,14 +38,29 @@ where
     // a Future that resolves to either a new Transform (the actual middleware component)
     // or an error.
     fn new_transform(&self, service: S) -> Self::Future {
-        ok(AuthenticateMiddleware { service })
+        ok(AuthenticateMiddleware {
+            service: Rc::new(service),
+        })
     }
 }
 
 // AuthenticateMiddleware is the actual middleware component.
 // It has a service field that represents the next service in the middleware chain.
 pub struct AuthenticateMiddleware<S> {
-    service: S,
+    service: Rc<S>, // shared with the calls that finish after a database lookup
+}
+
+/// the caller is who the claims say: pass them on to the handlers in the RequestContext
+fn admit(req: &ServiceRequest, mut request_context: RequestContext, claims: &Claims) {
+    tracing::Span::current().record("user", claims.id.as_str());
+    crate::shared::error_reporting::set_user(&claims.id);
+
+    // a service isn't a user, so it doesn't count toward usage or daily active users
+    if !claims.is_service() {
+        super::usage_tracker::record(&claims.id);
+    }
+    request_context.set_claims(claims);
+    req.extensions_mut().insert(request_context);
 }
 
 impl<S, B> Service<ServiceRequest> for AuthenticateMiddleware<S>
@@ -72,7 +88,7 @@ where
 
         match auth_header {
             Some(header_value) => {
-                let mut request_context = req
+                let request_context = req
                     .extensions_mut()
                     .get_mut::<RequestContext>()
                     .expect(
@@ -104,6 +120,28 @@ where
                         .validate_token(&token_str);
                 }
 
+                // internal components (background workers, the webhook dispatcher...) call with service tokens
+                if claims.is_none() {
+                    claims = request_context
+                        .security_context
+                        .validate_service_token(&token_str);
+                }
+
+                // in KeyCloak mode, logins can come from the realm too (see kc_proxy.rs).  who the realm's user is
+                // has to be looked up, so the call carries on once it has been
+                if claims.is_none() && request_context.config.keycloak.enabled() {
+                    if let Some(realm_token) = kc_proxy::validate_token(&request_context.config.keycloak, &token_str) {
+                        let service = self.service.clone();
+                        return Box::pin(async move {
+                            let claims = kc_proxy::to_claims(realm_token, request_context.database.as_ref())
+                                .await
+                                .ok_or_else(|| ErrorUnauthorized("No such user"))?;
+                            admit(&req, request_context, &claims);
+                            service.call(req).await
+                        });
+                    }
+                }
+
                 if claims.is_none() {
                     let fut = err::<ServiceResponse<B>, _>(
                         ErrorUnauthorized("No Authorization Header").into(),
@@ -113,8 +151,23 @@ where
 
                 let claims = claims.unwrap();
 
-                request_context.set_claims(&claims);
-                req.extensions_mut().insert(request_context);
+                // a login whose session was ended by a newer one (see sessions.rs) is logged out
+                if let Some(session_id) = claims.session_id.as_ref() {
+                    if !crate::user_service::sessions::is_active(&claims.id, session_id) {
+                        let fut = err::<ServiceResponse<B>, _>(ErrorUnauthorized("Session ended").into());
+                        return Box::pin(fut);
+                    }
+                }
+                // a seat token can only play its seat (see seat_tokens.rs)
+                if let Some(seat) = claims.seat.as_ref() {
+                    if !seat_tokens::allows(seat, req.method(), req.path()) {
+                        let fut = err::<ServiceResponse<B>, _>(
+                            ErrorForbidden("a seat token can only play its seat").into(),
+                        );
+                        return Box::pin(fut);
+                    }
+                }
+                admit(&req, request_context, &claims);
             }
             None => {
                 let fut = err::<ServiceResponse<B>, _>(
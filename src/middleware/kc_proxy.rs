@@ -0,0 +1,753 @@
+#![allow(dead_code)]
+/**
+ *  KeyCloak as a second place logins come from.  when KEYCLOAK_URL and KEYCLOAK_REALM are set the service is in
+ *  KeyCloak mode: the authentication middleware still takes the service's own tokens, and it also takes access tokens
+ *  the realm issued.
+ *
+ *  the realm's public keys are fetched from its certs endpoint when the service starts and every KEY_REFRESH_INTERVAL
+ *  after that, and again when a token signed with a key we don't have shows up (the realm rotated its keys) -- at most
+ *  once every KEY_REFETCH_MIN.  tokens are checked against the cached keys, so no request waits on KeyCloak.
+ *
+ *  KEYCLOAK_CLIENT_ID has to be set in KeyCloak mode: the service won't start without it.  a token has to be signed with
+ *  one of the realm's keys (never a shared secret), come from the realm, not be expired, and be for that client -- its
+ *  azp, or one of its aud, is the client.  its realm roles and its roles for the client are mapped onto Role: admin ->
+ *  Admin, user -> User, test-user -> TestUser.  nothing maps onto the roles only the service hands out (Service,
+ *  Validation, PasswordReset), and a token without any role that maps is refused.
+ *
+ *  the id in the claims is the id of the user the realm's user was made for: the token's subject is looked up in the
+ *  users' keycloak_id.  nothing else in the token says who the user is -- attributes can be changed in the realm -- so
+ *  a realm user the service didn't make can't log in.
+ *
+ *  users who register with the service are made in the realm too (see users.rs), through the client's service account:
+ *  with the same email and password, DEFAULT_CLIENT_ROLE, and the catan_user_id attribute set.  a user is kept in both
+ *  or in neither -- if either one can't be made, the other is undone -- and the realm's id for them is kept on the
+ *  PersistUser.  after that the realm is kept up with the service: a reset password is set there too, a changed
+ *  email is changed there, and a deleted user is deleted there.
+ */
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{JwkSet, PublicKeyUse},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cosmos_db::cosmosdb::UserDbTrait,
+    shared::{
+        error_reporting,
+        service_models::{Claims, Role},
+        shared_models::PersonalInformation,
+    },
+};
+
+/// how often the realm's keys are fetched again
+pub const KEY_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// the least time between fetches caused by tokens signed with keys we don't have
+pub const KEY_REFETCH_MIN: Duration = Duration::from_secs(30);
+
+/// the client role every user registered through the service is given in the realm
+pub const DEFAULT_CLIENT_ROLE: &str = "user";
+
+/// the algorithms the realm signs with.  no HS*: the realm's keys are public, so they can't be used as a secret
+const SIGNING_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct KeyCloakConfig {
+    pub url: String,           // https://keycloak.example.com -- without /realms
+    pub realm: String,
+    pub client_id: String,     // the service's client.  tokens have to be issued to it, and its client roles are mapped
+    pub client_secret: String, // the client's secret, for its service account
+}
+
+impl KeyCloakConfig {
+    pub fn from_env() -> Self {
+        let set = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_owned())
+                .unwrap_or_default()
+        };
+        Self {
+            url: set("KEYCLOAK_URL").trim_end_matches('/').to_owned(),
+            realm: set("KEYCLOAK_REALM"),
+            client_id: set("KEYCLOAK_CLIENT_ID"),
+            client_secret: set("KEYCLOAK_CLIENT_SECRET"),
+        }
+    }
+
+    /// true if the service is in KeyCloak mode
+    pub fn enabled(&self) -> bool {
+        !self.url.is_empty() && !self.realm.is_empty()
+    }
+
+    /// what is missing for KeyCloak mode to work, if anything
+    pub fn check(&self) -> Result<(), String> {
+        if self.enabled() && self.client_id.is_empty() {
+            return Err("KEYCLOAK_CLIENT_ID has to be set when KEYCLOAK_URL and KEYCLOAK_REALM are".to_owned());
+        }
+        Ok(())
+    }
+
+    /// the iss of the realm's tokens
+    pub fn issuer(&self) -> String {
+        format!("{}/realms/{}", self.url, self.realm)
+    }
+
+    pub fn certs_url(&self) -> String {
+        format!("{}/protocol/openid-connect/certs", self.issuer())
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct RoleList {
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// the parts of a KeyCloak access token we use
+#[derive(Debug, Deserialize)]
+struct KeyCloakClaims {
+    sub: String,
+    exp: usize,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    azp: Option<String>, // the client the token was issued to
+    #[serde(default)]
+    aud: serde_json::Value, // who the token is for: one of them, or a list
+    #[serde(default)]
+    realm_access: RoleList,
+    #[serde(default)]
+    resource_access: HashMap<String, RoleList>, // client id -> the roles for that client
+}
+
+#[derive(Default)]
+struct RealmKeys {
+    keys: HashMap<String, DecodingKey>, // kid -> key
+    fetched_at: Option<Instant>,
+    refetch_requested_at: Option<Instant>,
+}
+
+lazy_static::lazy_static! {
+    static ref REALM_KEYS: RwLock<RealmKeys> = RwLock::new(RealmKeys::default());
+}
+
+/// the Role a KeyCloak role is, if it is one
+pub fn map_role(name: &str) -> Option<Role> {
+    match name.to_ascii_lowercase().as_str() {
+        "admin" => Some(Role::Admin),
+        "user" => Some(Role::User),
+        "test-user" | "testuser" => Some(Role::TestUser),
+        _ => None,
+    }
+}
+
+/// the Roles of a token with these realm and client roles
+pub fn map_roles(realm_roles: &[String], client_roles: &[String]) -> Vec<Role> {
+    let mut roles = Vec::new();
+    for role in realm_roles.iter().chain(client_roles).filter_map(|name| map_role(name)) {
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+    roles
+}
+
+/// a good token from the realm.  who it is for still has to be looked up (see to_claims)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RealmToken {
+    pub subject: String, // the realm's id for the user
+    pub email: Option<String>,
+    pub exp: usize,
+    pub roles: Vec<Role>,
+}
+
+/// true if the token was issued to the client, or is for it
+fn is_for_client(token: &KeyCloakClaims, client_id: &str) -> bool {
+    let in_aud = match &token.aud {
+        serde_json::Value::String(aud) => aud == client_id,
+        serde_json::Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    !client_id.is_empty() && (token.azp.as_deref() == Some(client_id) || in_aud)
+}
+
+fn to_realm_token(config: &KeyCloakConfig, token: KeyCloakClaims) -> Option<RealmToken> {
+    if !is_for_client(&token, &config.client_id) {
+        return None;
+    }
+    let client_roles = token
+        .resource_access
+        .get(&config.client_id)
+        .map_or(&[][..], |access| &access.roles[..]);
+    let roles = map_roles(&token.realm_access.roles, client_roles);
+    if roles.is_empty() {
+        return None;
+    }
+    Some(RealmToken {
+        subject: token.sub,
+        email: token.email,
+        exp: token.exp,
+        roles,
+    })
+}
+
+/// the claims of the user the token's realm user was made for.  None if the service doesn't have one
+pub async fn to_claims(token: RealmToken, database: &dyn UserDbTrait) -> Option<Claims> {
+    let user = database.find_user_by_keycloak_id(&token.subject).await.ok()?;
+    let email = user.user_profile.pii.as_ref().map(|pii| pii.email.clone());
+    Some(Claims {
+        id: user.id,
+        sub: email.or(token.email).unwrap_or(token.subject),
+        exp: token.exp,
+        roles: token.roles,
+        test_context: None,
+        aud: None,
+        session_id: None,
+        seat: None,
+    })
+}
+
+/// the token, if the realm issued it and it is good
+pub fn validate_token(config: &KeyCloakConfig, token: &str) -> Option<RealmToken> {
+    let header = decode_header(token).ok()?;
+    if !SIGNING_ALGORITHMS.contains(&header.alg) {
+        return None;
+    }
+    let kid = header.kid?;
+    let key = REALM_KEYS
+        .read()
+        .expect("the realm keys lock shouldn't be poisoned")
+        .keys
+        .get(&kid)
+        .cloned();
+    let key = match key {
+        Some(key) => key,
+        None => {
+            request_refetch(config);
+            return None;
+        }
+    };
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[config.issuer()]);
+    validation.validate_aud = false; // azp or aud, checked in to_realm_token
+
+    let token = decode::<KeyCloakClaims>(token, &key, &validation).ok()?;
+    to_realm_token(config, token.claims)
+}
+
+/// the keys in the set we can check signatures with
+fn signing_keys(set: &JwkSet) -> HashMap<String, DecodingKey> {
+    set.keys
+        .iter()
+        .filter(|jwk| !matches!(jwk.common.public_key_use, Some(PublicKeyUse::Encryption)))
+        .filter_map(|jwk| Some((jwk.common.key_id.clone()?, DecodingKey::from_jwk(jwk).ok()?)))
+        .collect()
+}
+
+fn cache_keys(keys: HashMap<String, DecodingKey>) {
+    let mut realm_keys = REALM_KEYS.write().expect("the realm keys lock shouldn't be poisoned");
+    realm_keys.keys = keys;
+    realm_keys.fetched_at = Some(Instant::now());
+}
+
+/// fetch the keys again in the background, unless that was just done
+fn request_refetch(config: &KeyCloakConfig) {
+    let now = Instant::now();
+    {
+        let mut realm_keys = REALM_KEYS.write().expect("the realm keys lock shouldn't be poisoned");
+        if matches!(realm_keys.refetch_requested_at, Some(at) if now.duration_since(at) < KEY_REFETCH_MIN) {
+            return;
+        }
+        realm_keys.refetch_requested_at = Some(now);
+    }
+    let proxy = KeyCloakProxy::new(config);
+    actix_web::rt::spawn(async move {
+        if let Err(e) = proxy.refresh_keys().await {
+            tracing::warn!("{}", e);
+        }
+    });
+}
+
+/// fetches the realm's keys every KEY_REFRESH_INTERVAL
+pub fn start_key_refresh(config: KeyCloakConfig) -> tokio::task::JoinHandle<()> {
+    actix_web::rt::spawn(async move {
+        let proxy = KeyCloakProxy::new(&config);
+        loop {
+            tokio::time::sleep(KEY_REFRESH_INTERVAL).await;
+            if let Err(e) = proxy.refresh_keys().await {
+                tracing::error!("{}", e);
+                error_reporting::report_background_failure("keycloak keys", &e);
+            }
+        }
+    })
+}
+
+/// talks to the KeyCloak realm
+#[derive(Debug, Clone)]
+pub struct KeyCloakProxy {
+    config: KeyCloakConfig,
+    client: reqwest::Client,
+}
+
+impl KeyCloakProxy {
+    pub fn new(config: &KeyCloakConfig) -> Self {
+        Self {
+            config: config.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// fetch the realm's public keys and cache them.  returns how many there are
+    pub async fn refresh_keys(&self) -> Result<usize, String> {
+        let url = self.config.certs_url();
+        let set = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("couldn't fetch the KeyCloak keys from {}: {}", url, e))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| format!("the KeyCloak keys from {} aren't a key set: {}", url, e))?;
+        let keys = signing_keys(&set);
+        if keys.is_empty() {
+            return Err(format!("{} has no keys that can check a signature", url));
+        }
+        let count = keys.len();
+        cache_keys(keys);
+        tracing::info!("cached {} KeyCloak keys from {}", count, url);
+        Ok(count)
+    }
+
+    fn admin_url(&self, path: &str) -> String {
+        format!("{}/admin/realms/{}{}", self.config.url, self.config.realm, path)
+    }
+
+    /// a token for the client's service account -- which needs realm-management's manage-users role
+    async fn admin_token(&self) -> Result<String, String> {
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            access_token: String,
+        }
+        let url = format!("{}/protocol/openid-connect/token", self.config.issuer());
+        self.client
+            .post(&url)
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("couldn't get a KeyCloak admin token: {}", e))?
+            .json::<TokenResponse>()
+            .await
+            .map(|response| response.access_token)
+            .map_err(|e| format!("the KeyCloak admin token didn't parse: {}", e))
+    }
+
+    /**
+     *  makes the user in the realm: with the password they registered with, the DEFAULT_CLIENT_ROLE, and their id in
+     *  the catan_user_id attribute.  if the password or the role can't be set the user is deleted again, so the realm
+     *  is never left with half a user.  returns the KeyCloak id of the user
+     */
+    pub async fn provision_user(
+        &self,
+        user_id: &str,
+        pii: &PersonalInformation,
+        password: &str,
+    ) -> Result<String, String> {
+        let token = self.admin_token().await?;
+        let keycloak_id = self.create_user(&token, user_id, pii).await?;
+        let provisioned = match self.set_password(&token, &keycloak_id, password).await {
+            Ok(()) => self.assign_client_role(&token, &keycloak_id, DEFAULT_CLIENT_ROLE).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = provisioned {
+            if let Err(delete_error) = self.delete_user(&keycloak_id).await {
+                tracing::error!("{} is half made in KeyCloak: {}", pii.email, delete_error);
+            }
+            return Err(e);
+        }
+        tracing::info!("made {} in KeyCloak as {}", pii.email, keycloak_id);
+        Ok(keycloak_id)
+    }
+
+    async fn create_user(&self, token: &str, user_id: &str, pii: &PersonalInformation) -> Result<String, String> {
+        let response = self
+            .client
+            .post(&self.admin_url("/users"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "username": pii.email,
+                "email": pii.email,
+                "firstName": pii.first_name,
+                "lastName": pii.last_name,
+                "enabled": true,
+                "attributes": { "catan_user_id": [user_id] },
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("couldn't make {} in KeyCloak: {}", pii.email, e))?;
+        response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(user_id_from_location)
+            .ok_or_else(|| format!("KeyCloak made {} but didn't say where", pii.email))
+    }
+
+    async fn set_password(&self, token: &str, keycloak_id: &str, password: &str) -> Result<(), String> {
+        self.client
+            .put(&self.admin_url(&format!("/users/{}/reset-password", keycloak_id)))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "type": "password", "value": password, "temporary": false }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("couldn't set the KeyCloak password of {}: {}", keycloak_id, e))
+    }
+
+    async fn assign_client_role(&self, token: &str, keycloak_id: &str, role: &str) -> Result<(), String> {
+        #[derive(Deserialize)]
+        struct Client {
+            id: String,
+        }
+        let failed = |e: reqwest::Error| format!("couldn't give {} the {} role: {}", keycloak_id, role, e);
+        let clients = self
+            .client
+            .get(&self.admin_url("/clients"))
+            .query(&[("clientId", self.config.client_id.as_str())])
+            .bearer_auth(token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json::<Vec<Client>>()
+            .await
+            .map_err(failed)?;
+        let client = clients
+            .first()
+            .ok_or_else(|| format!("there is no {} client in KeyCloak", self.config.client_id))?;
+        let role = self
+            .client
+            .get(&self.admin_url(&format!("/clients/{}/roles/{}", client.id, role)))
+            .bearer_auth(token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json::<serde_json::Value>()
+            .await
+            .map_err(failed)?;
+        self.client
+            .post(&self.admin_url(&format!("/users/{}/role-mappings/clients/{}", keycloak_id, client.id)))
+            .bearer_auth(token)
+            .json(&vec![role])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(failed)
+    }
+
+    /// sets the password of a user the realm has
+    pub async fn update_password(&self, keycloak_id: &str, password: &str) -> Result<(), String> {
+        let token = self.admin_token().await?;
+        self.set_password(&token, keycloak_id, password).await
+    }
+
+    /// changes the email of a user the realm has.  it is their username too, and it has been checked
+    pub async fn update_email(&self, keycloak_id: &str, email: &str) -> Result<(), String> {
+        let token = self.admin_token().await?;
+        self.client
+            .put(&self.admin_url(&format!("/users/{}", keycloak_id)))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "username": email, "email": email, "emailVerified": true }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("couldn't change the KeyCloak email of {}: {}", keycloak_id, e))
+    }
+
+    /// removes the user from the realm
+    pub async fn delete_user(&self, keycloak_id: &str) -> Result<(), String> {
+        let token = self.admin_token().await?;
+        self.client
+            .delete(&self.admin_url(&format!("/users/{}", keycloak_id)))
+            .bearer_auth(token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("couldn't delete {} from KeyCloak: {}", keycloak_id, e))
+    }
+}
+
+/// the id at the end of the Location KeyCloak answers a new user with: .../admin/realms/{realm}/users/{id}
+fn user_id_from_location(location: &str) -> Option<String> {
+    let (users, id) = location.trim_end_matches('/').rsplit_once('/')?;
+    if !users.ends_with("/users") || id.is_empty() {
+        return None;
+    }
+    Some(id.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use base64::Engine;
+    use std::sync::{Arc, Mutex};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use openssl::rsa::Rsa;
+    use serde_json::json;
+
+    fn config() -> KeyCloakConfig {
+        KeyCloakConfig {
+            url: "https://keycloak.test".to_owned(),
+            realm: "catan".to_owned(),
+            client_id: "catan-service".to_owned(),
+            client_secret: String::default(),
+        }
+    }
+
+    /// a realm that answers the admin calls the proxy makes, and says which ones it got.  it can refuse to set passwords
+    async fn fake_realm(refuse_passwords: bool) -> (KeyCloakConfig, Arc<Mutex<Vec<String>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let realm_calls = calls.clone();
+        let server = HttpServer::new(move || {
+            let calls = realm_calls.clone();
+            App::new().default_service(web::to(move |req: HttpRequest| {
+                let calls = calls.clone();
+                async move {
+                    let call = format!("{} {}", req.method(), req.path());
+                    calls.lock().unwrap().push(call.clone());
+                    match call.as_str() {
+                        "POST /realms/catan/protocol/openid-connect/token" => {
+                            HttpResponse::Ok().json(json!({ "access_token": "admin-token" }))
+                        }
+                        "POST /admin/realms/catan/users" => HttpResponse::Created()
+                            .append_header(("Location", "/admin/realms/catan/users/kc-1"))
+                            .finish(),
+                        "PUT /admin/realms/catan/users/kc-1/reset-password" if refuse_passwords => {
+                            HttpResponse::InternalServerError().finish()
+                        }
+                        "GET /admin/realms/catan/clients" => HttpResponse::Ok().json(json!([{ "id": "client-1" }])),
+                        "GET /admin/realms/catan/clients/client-1/roles/user" => {
+                            HttpResponse::Ok().json(json!({ "id": "role-1", "name": "user" }))
+                        }
+                        _ if call.starts_with("GET") => HttpResponse::NotFound().finish(),
+                        _ => HttpResponse::NoContent().finish(),
+                    }
+                }
+            }))
+        })
+        .bind("127.0.0.1:0")
+        .expect("a free port");
+        let port = server.addrs()[0].port();
+        tokio::spawn(server.run());
+        let config = KeyCloakConfig {
+            url: format!("http://127.0.0.1:{}", port),
+            ..config()
+        };
+        (config, calls)
+    }
+
+    #[actix_rt::test]
+    async fn test_provision_user() {
+        let pii = PersonalInformation {
+            phone_number: String::default(),
+            email: "player@example.com".to_owned(),
+            first_name: "Play".to_owned(),
+            last_name: "Er".to_owned(),
+        };
+        let (config, calls) = fake_realm(false).await;
+        let proxy = KeyCloakProxy::new(&config);
+        assert_eq!(
+            proxy.provision_user("catan-id", &pii, "a new password").await,
+            Ok("kc-1".to_owned())
+        );
+        {
+            let calls = calls.lock().unwrap();
+            assert!(calls.contains(&"PUT /admin/realms/catan/users/kc-1/reset-password".to_owned()));
+            assert!(calls.contains(&"POST /admin/realms/catan/users/kc-1/role-mappings/clients/client-1".to_owned()));
+            assert!(!calls.iter().any(|call| call.starts_with("DELETE")));
+        }
+        proxy.update_email("kc-1", "moved@example.com").await.expect("the realm takes it");
+        proxy.update_password("kc-1", "another password").await.expect("the realm takes it");
+        proxy.delete_user("kc-1").await.expect("the realm deletes it");
+        assert_eq!(
+            calls.lock().unwrap().last().map(String::as_str),
+            Some("DELETE /admin/realms/catan/users/kc-1")
+        );
+
+        // the password can't be set: the user that was made is deleted again, so the realm doesn't keep half of it
+        let (config, calls) = fake_realm(true).await;
+        assert!(KeyCloakProxy::new(&config)
+            .provision_user("catan-id", &pii, "a new password")
+            .await
+            .is_err());
+        {
+            let calls = calls.lock().unwrap();
+            assert!(!calls.iter().any(|call| call.contains("role-mappings")));
+            assert_eq!(calls.last().map(String::as_str), Some("DELETE /admin/realms/catan/users/kc-1"));
+        }
+        assert!(KeyCloakProxy::new(&config).update_password("kc-1", "a password").await.is_err());
+    }
+
+    #[test]
+    fn test_user_id_from_location() {
+        assert_eq!(
+            user_id_from_location("https://keycloak.test/admin/realms/catan/users/4f1c-99").as_deref(),
+            Some("4f1c-99")
+        );
+        assert_eq!(user_id_from_location("https://keycloak.test/admin/realms/catan/users/"), None);
+        assert_eq!(user_id_from_location("https://keycloak.test/admin/realms/catan/clients/4f1c"), None);
+    }
+
+    #[test]
+    fn test_map_roles() {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            map_roles(&names(&["offline_access", "Admin"]), &names(&["user", "admin"])),
+            vec![Role::Admin, Role::User]
+        );
+        assert_eq!(map_roles(&names(&["test-user"]), &[]), vec![Role::TestUser]);
+        // the realm can't hand out the service's own roles
+        assert!(map_roles(&names(&["service", "validation", "passwordreset"]), &[]).is_empty());
+    }
+
+    #[test]
+    fn test_validate_token() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let encode_part = |bytes: Vec<u8>| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+        let key = DecodingKey::from_rsa_components(&encode_part(rsa.n().to_vec()), &encode_part(rsa.e().to_vec()))
+            .unwrap();
+        let mut keys = HashMap::new();
+        keys.insert("kc-test-key".to_owned(), key);
+        cache_keys(keys);
+
+        let signing_key = EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap();
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("kc-test-key".to_owned());
+        let config = config();
+        let sign = |claims: serde_json::Value| encode(&header, &claims, &signing_key).unwrap();
+        let exp = chrono::Utc::now().timestamp() + 60;
+        let token = |azp: &str, roles: &[&str]| {
+            sign(json!({
+                "iss": config.issuer(),
+                "sub": "kc-subject",
+                "exp": exp,
+                "email": "player@example.com",
+                "azp": azp,
+                "aud": "account",
+                "catan_user_id": "catan-id",
+                "realm_access": { "roles": ["offline_access"] },
+                "resource_access": { "catan-service": { "roles": roles } },
+            }))
+        };
+
+        let realm_token = validate_token(&config, &token("catan-service", &["user"])).expect("the token is good");
+        assert_eq!(realm_token.subject, "kc-subject");
+        assert_eq!(realm_token.email.as_deref(), Some("player@example.com"));
+        assert_eq!(realm_token.roles, vec![Role::User]);
+
+        // a token another client got for this one
+        let for_us = sign(json!({
+            "iss": config.issuer(),
+            "sub": "kc-subject",
+            "exp": exp,
+            "azp": "another-client",
+            "aud": ["account", "catan-service"],
+            "resource_access": { "catan-service": { "roles": ["user"] } },
+        }));
+        assert!(validate_token(&config, &for_us).is_some());
+
+        // without a client, nothing is for us
+        let no_client = KeyCloakConfig {
+            client_id: String::default(),
+            ..config.clone()
+        };
+        assert!(no_client.check().is_err());
+        assert!(config.check().is_ok());
+        assert!(validate_token(&no_client, &token("", &["user"])).is_none());
+
+        // issued to another client, no role we know, another realm, expired
+        assert!(validate_token(&config, &token("another-client", &["user"])).is_none());
+        assert!(validate_token(&config, &token("catan-service", &["player"])).is_none());
+        let other_realm = KeyCloakConfig {
+            realm: "other".to_owned(),
+            ..config.clone()
+        };
+        assert!(validate_token(&other_realm, &token("catan-service", &["user"])).is_none());
+        let expired = sign(json!({
+            "iss": config.issuer(),
+            "sub": "kc-subject",
+            "exp": exp - 3600,
+            "azp": "catan-service",
+            "resource_access": { "catan-service": { "roles": ["user"] } },
+        }));
+        assert!(validate_token(&config, &expired).is_none());
+
+        // the service's own tokens aren't the realm's
+        let hs_token = encode(
+            &Header::new(Algorithm::HS512),
+            &json!({ "iss": config.issuer(), "sub": "x", "exp": exp }),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert!(validate_token(&config, &hs_token).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_to_claims() {
+        let request_context = crate::middleware::request_context_mw::RequestContext::test_default(false);
+        let database = request_context.database.as_ref();
+        let realm_token = |subject: &str| RealmToken {
+            subject: subject.to_owned(),
+            email: Some("realm@example.com".to_owned()),
+            exp: 0,
+            roles: vec![Role::User],
+        };
+
+        // a realm user the service didn't make isn't anybody
+        assert!(to_claims(realm_token("kc-stranger"), database).await.is_none());
+
+        let mut user = crate::shared::service_models::PersistUser::new();
+        user.keycloak_id = Some("kc-made-here".to_owned());
+        user.user_profile.pii = Some(PersonalInformation {
+            phone_number: String::default(),
+            email: "made-here@example.com".to_owned(),
+            first_name: "Made".to_owned(),
+            last_name: "Here".to_owned(),
+        });
+        database.update_or_create_user(&user).await.expect("the mocked db takes anything");
+        let claims = to_claims(realm_token("kc-made-here"), database).await.expect("the service made them");
+        assert_eq!(claims.id, user.id);
+        assert_eq!(claims.sub, "made-here@example.com");
+        assert_eq!(claims.roles, vec![Role::User]);
+        assert!(!claims.is_service());
+    }
+}
@@ -0,0 +1,301 @@
+#![allow(dead_code)]
+/**
+ *  brute force protection for login.  every failed login is counted against the account (the email it was for) and
+ *  against the address it came from (request_context_mw puts the caller's address in the RequestContext):
+ *
+ *  - an account that fails LOGIN_MAX_FAILURES times (5) within LOGIN_FAILURE_WINDOW_SECONDS (15 minutes) is locked
+ *    for LOGIN_LOCKOUT_SECONDS (15 minutes).  while it is locked even the right password is refused, with a 423 and
+ *    GameError::AccountLocked, so a client can tell the user to wait instead of asking for their password again.  the
+ *    owner is emailed when it is locked -- if it wasn't them, somebody is guessing their password.
+ *  - an address that fails LOGIN_MAX_IP_FAILURES times (50) in the window is refused with a 429 until the window has
+ *    passed, whichever accounts it tries.  that is one machine guessing at many accounts.
+ *
+ *  a good login clears the account's failures.  the counts are kept in memory, so a restart forgets them.
+ *
+ *  LoginGuard is the middleware on the login route that refuses a login that is locked out before the handler runs, so
+ *  a refused login never gets as far as the database or the password hash.  the address is the one that connected
+ *  unless it is a trusted proxy (see request_context_mw.rs), so a caller can't dodge the limit with X-Forwarded-For.
+ */
+use std::{collections::HashMap, pin::Pin, sync::Mutex};
+
+use actix::fut::err;
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{ServiceRequest, ServiceResponse as ActixResponse},
+    error::InternalError,
+    Error, HttpMessage,
+};
+use futures::{
+    future::{ok, Ready},
+    Future,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::request_context_mw::RequestContext;
+use crate::{
+    games_service::game_container::game_messages::GameHeader,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct LockoutPolicy {
+    pub max_failures: u32,    // per account, in the window
+    pub max_ip_failures: u32, // per address, in the window
+    pub window_seconds: u64,
+    pub lockout_seconds: u64,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            max_ip_failures: 50,
+            window_seconds: 15 * 60,
+            lockout_seconds: 15 * 60,
+        }
+    }
+}
+
+impl LockoutPolicy {
+    pub fn from_env() -> Self {
+        fn set<T: std::str::FromStr + PartialOrd + Default>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse::<T>().ok())
+                .filter(|value| *value > T::default())
+                .unwrap_or(default)
+        }
+        let default = Self::default();
+        Self {
+            max_failures: set("LOGIN_MAX_FAILURES", default.max_failures),
+            max_ip_failures: set("LOGIN_MAX_IP_FAILURES", default.max_ip_failures),
+            window_seconds: set("LOGIN_FAILURE_WINDOW_SECONDS", default.window_seconds),
+            lockout_seconds: set("LOGIN_LOCKOUT_SECONDS", default.lockout_seconds),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Failures {
+    at: Vec<u64>,               // when each failure in the window happened
+    locked_until: Option<u64>, // accounts only
+}
+
+impl Failures {
+    fn forget_old(&mut self, window_seconds: u64, now: u64) {
+        self.at.retain(|at| *at + window_seconds > now);
+    }
+}
+
+lazy_static::lazy_static! {
+    // lowercased email -> its failures
+    static ref ACCOUNTS: Mutex<HashMap<String, Failures>> = Mutex::new(HashMap::new());
+    // address -> its failures
+    static ref ADDRESSES: Mutex<HashMap<String, Failures>> = Mutex::new(HashMap::new());
+}
+
+fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// why the login is refused before the password is even looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refused {
+    AccountLocked(u64), // seconds until it unlocks
+    TooManyFromAddress(u64),
+}
+
+impl Refused {
+    pub fn to_response(self) -> ServiceResponse {
+        match self {
+            Refused::AccountLocked(seconds) => ServiceResponse::new(
+                &format!(
+                    "too many failed logins: the account is locked for {} more minutes",
+                    (seconds + 59) / 60
+                ),
+                StatusCode::LOCKED,
+                ResponseType::NoData,
+                GameError::AccountLocked(seconds),
+            ),
+            Refused::TooManyFromAddress(seconds) => ServiceResponse::new(
+                &format!("too many failed logins -- try again in {} minutes", (seconds + 59) / 60),
+                StatusCode::TOO_MANY_REQUESTS,
+                ResponseType::NoData,
+                GameError::HttpError(StatusCode::TOO_MANY_REQUESTS),
+            ),
+        }
+    }
+}
+
+/// can a login for the email, from the address, be tried now?
+pub fn check(email: &str, address: Option<&str>, policy: &LockoutPolicy, now: u64) -> Result<(), Refused> {
+    if let Some(failures) = ACCOUNTS
+        .lock()
+        .expect("the login lock shouldn't be poisoned")
+        .get(&account_key(email))
+    {
+        if let Some(locked_until) = failures.locked_until.filter(|until| *until > now) {
+            return Err(Refused::AccountLocked(locked_until - now));
+        }
+    }
+    if let Some(address) = address {
+        let mut addresses = ADDRESSES.lock().expect("the login lock shouldn't be poisoned");
+        if let Some(failures) = addresses.get_mut(address) {
+            failures.forget_old(policy.window_seconds, now);
+            if failures.at.len() as u32 >= policy.max_ip_failures {
+                let oldest = failures.at.first().copied().unwrap_or(now);
+                return Err(Refused::TooManyFromAddress(oldest + policy.window_seconds - now));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// count a failed login.  returns true if this failure locked the account -- the owner should be told
+pub fn record_failure(email: &str, address: Option<&str>, policy: &LockoutPolicy, now: u64) -> bool {
+    if let Some(address) = address {
+        let mut addresses = ADDRESSES.lock().expect("the login lock shouldn't be poisoned");
+        //  nothing older than the window matters any more
+        addresses.retain(|_, failures| failures.at.last().map_or(false, |at| *at + policy.window_seconds > now));
+        addresses.entry(address.to_owned()).or_default().at.push(now);
+    }
+    let mut accounts = ACCOUNTS.lock().expect("the login lock shouldn't be poisoned");
+    accounts.retain(|_, failures| {
+        failures.locked_until.map_or(false, |until| until > now)
+            || failures.at.last().map_or(false, |at| *at + policy.window_seconds > now)
+    });
+    let failures = accounts.entry(account_key(email)).or_default();
+    failures.forget_old(policy.window_seconds, now);
+    failures.at.push(now);
+    if failures.at.len() as u32 >= policy.max_failures {
+        failures.at.clear();
+        failures.locked_until = Some(now + policy.lockout_seconds);
+        return true;
+    }
+    false
+}
+
+/// the login worked: the account starts again from no failures
+pub fn record_success(email: &str) {
+    ACCOUNTS
+        .lock()
+        .expect("the login lock shouldn't be poisoned")
+        .remove(&account_key(email));
+}
+
+/// wrap the login route in this: it refuses the login if the account is locked or the address has failed too often
+pub struct LoginGuard;
+
+impl<S: 'static, B> Transform<S, ServiceRequest> for LoginGuard
+where
+    S: Service<ServiceRequest, Response = ActixResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ActixResponse<B>;
+    type Error = Error;
+    type Transform = LoginGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(LoginGuardMiddleware { service })
+    }
+}
+
+pub struct LoginGuardMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for LoginGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ActixResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    actix_service::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        //  without an email there is nothing to check -- the handler turns the login down with a 400
+        let refused = {
+            let extensions = req.extensions();
+            let request_context = extensions
+                .get::<RequestContext>()
+                .expect("request_context_mw should have added the RequestContext");
+            req.headers()
+                .get(GameHeader::EMAIL)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|email| {
+                    check(
+                        email,
+                        request_context.client_address.as_deref(),
+                        &request_context.config.lockout_policy,
+                        request_context.environment.now(),
+                    )
+                    .err()
+                })
+        };
+        match refused {
+            Some(refused) => {
+                let response = refused.to_response();
+                let error = InternalError::from_response(response.message.clone(), response.to_http_response());
+                Box::pin(err::<ActixResponse<B>, Error>(error.into()))
+            }
+            None => Box::pin(self.service.call(req)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout() {
+        let policy = LockoutPolicy {
+            max_failures: 3,
+            max_ip_failures: 5,
+            window_seconds: 100,
+            lockout_seconds: 1000,
+        };
+        let now = 10_000;
+        let email = "Lockout@Example.com";
+
+        // failures that are too far apart don't add up
+        assert!(!record_failure(email, None, &policy, now));
+        assert!(!record_failure(email, None, &policy, now + 50));
+        assert!(!record_failure(email, None, &policy, now + 200));
+        assert!(check(email, None, &policy, now + 200).is_ok());
+
+        // a good login starts the count again
+        assert!(!record_failure(email, None, &policy, now + 210));
+        record_success(email);
+        assert!(!record_failure(email, None, &policy, now + 220));
+        assert!(!record_failure(email, None, &policy, now + 230));
+        assert!(record_failure("lockout@example.com", None, &policy, now + 240));
+        assert_eq!(
+            check(email, None, &policy, now + 250),
+            Err(Refused::AccountLocked(990))
+        );
+        assert!(check(email, None, &policy, now + 1240).is_ok());
+
+        // one address guessing at many accounts
+        let address = Some("192.0.2.7");
+        for n in 0..5 {
+            assert!(check(&format!("guess{}@example.com", n), address, &policy, now).is_ok());
+            record_failure(&format!("guess{}@example.com", n), address, &policy, now + n);
+        }
+        assert_eq!(
+            check("another@example.com", address, &policy, now + 10),
+            Err(Refused::TooManyFromAddress(90))
+        );
+        assert!(check("another@example.com", Some("192.0.2.8"), &policy, now + 10).is_ok());
+        assert!(check("another@example.com", address, &policy, now + 100).is_ok());
+    }
+}
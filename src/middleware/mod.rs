@@ -2,4 +2,7 @@ pub mod authn_mw;
 pub mod request_context_mw;
 pub mod service_config;
 pub mod header_extractor;
-pub mod security_context;
\ No newline at end of file
+pub mod kc_proxy;
+pub mod login_guard;
+pub mod security_context;
+pub mod usage_tracker;
\ No newline at end of file
@@ -3,7 +3,9 @@ use crate::cosmos_db::cosmosdb::{UserDb, UserDbTrait};
 use crate::cosmos_db::mocked_db::TestDb;
 use crate::games_service::game_container::game_messages::GameHeader;
 use crate::middleware::service_config::{ServiceConfig, SERVICE_CONFIG};
-use crate::shared::service_models::{Claims, Role};
+use crate::shared::environment::{Environment, ProductionEnvironment, TestEnvironment};
+use crate::shared::error_reporting;
+use crate::shared::service_models::{Claims, PersistUser, Role};
 /**
  *  this file contains the middleware that injects ServiceContext into the Request.  The data in RequestContext is the
  *  configuration data necessary for the Service to run -- the secrets loaded from the environment, hard coded strings,
@@ -16,7 +18,11 @@ use actix_web::{dev::ServiceRequest, dev::ServiceResponse, Error};
 use actix_web::{FromRequest, HttpMessage, HttpRequest};
 use futures::future::{ok, Ready};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use sentry::{Hub, SentryFuture, SentryFutureExt};
+use tracing::{instrument::Instrumented, Instrument};
 
 use super::security_context::SecurityContext;
 
@@ -24,12 +30,18 @@ use super::security_context::SecurityContext;
 #[serde(rename_all = "PascalCase")]
 pub struct TestContext {
     pub use_cosmos_db: bool,
-    pub phone_code: Option<i32>
+    pub phone_code: Option<i32>,
+    #[serde(default)]
+    pub seed: Option<u64>, // if set, the request gets a TestEnvironment with this seed (see environment.rs)
 }
 
 impl TestContext {
     pub fn new(use_cosmos_db: bool, phone_code: Option<i32>) -> Self {
-        Self { use_cosmos_db, phone_code: phone_code }
+        Self { use_cosmos_db, phone_code: phone_code, seed: None }
+    }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
     }
     pub fn as_json(use_cosmos: bool) -> String {
         let tc = TestContext::new(use_cosmos, None);
@@ -46,17 +58,23 @@ pub struct RequestContext {
     pub database: Box<dyn UserDbTrait>,
     pub claims: Option<Claims>,
     pub security_context: SecurityContext,
+    pub environment: Arc<dyn Environment>,
+    pub client_address: Option<String>, // where the request came from, for the login guard (see login_guard.rs)
 }
 
 impl Clone for RequestContext {
     fn clone(&self) -> Self {
-        log::trace!("Cloning Request Context");
-        RequestContext::new(
+        tracing::trace!("Cloning Request Context");
+        let mut clone = RequestContext::new(
             &self.claims,
             &self.test_context,
             &SERVICE_CONFIG,
             &self.security_context,
-        )
+        );
+        // the clone shares the environment, so a test environment's clock and dice carry on where they were
+        clone.environment = self.environment.clone();
+        clone.client_address = self.client_address.clone();
+        clone
     }
 }
 
@@ -77,14 +95,27 @@ impl RequestContext {
             }
             None => Box::new(UserDb::new(false, service_config)),
         };
+        let environment: Arc<dyn Environment> = match test_context.as_ref().and_then(|context| context.seed) {
+            Some(seed) => Arc::new(TestEnvironment::new(seed)),
+            None => Arc::new(ProductionEnvironment),
+        };
         RequestContext {
             config: service_config.clone(), // Clone the read-only environment data
             test_context: test_context.clone(),
             database,
             claims: claims.clone(),
             security_context: security_context.clone(),
+            environment,
+            client_address: None,
         }
     }
+    /// a context for work the service does on its own later (a timer), not for whoever's request started it: no
+    /// claims and no client address, but the same test context and environment so tests keep their db and clock
+    pub fn system_context(&self) -> Self {
+        let mut context = RequestContext::new(&None, &self.test_context, &SERVICE_CONFIG, &self.security_context);
+        context.environment = self.environment.clone();
+        context
+    }
     pub fn set_claims(&mut self, claims: &Claims) {
         self.claims = Some(claims.clone());
     }
@@ -128,12 +159,21 @@ impl RequestContext {
             None => false,
         }
     }
+
+    /// true if the caller is an internal component with a service token, not a person
+    pub fn is_service_call(&self) -> bool {
+        self.claims.as_ref().map_or(false, |claims| claims.is_service())
+    }
 }
 impl FromRequest for RequestContext {
     type Error = Error;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // routing has happened by the time a handler asks for its RequestContext, so the game id is known now
+        if let Some(game_id) = req.match_info().get("game_id") {
+            tracing::Span::current().record("game", game_id);
+        }
         // Fetch the RequestContext from request extensions
         if let Some(request_context) = req.extensions().get::<RequestContext>() {
             ok(request_context.clone()) // Clone the RequestContext
@@ -145,6 +185,8 @@ impl FromRequest for RequestContext {
                 database: Box::new(UserDb::new(false, &SERVICE_CONFIG)),
                 claims: None,
                 security_context: SecurityContext::cached_secrets(),
+                environment: Arc::new(ProductionEnvironment),
+                client_address: None,
             })
         }
     }
@@ -169,6 +211,26 @@ where
     }
 }
 
+/**
+ *  where the request came from, for the login guard (see login_guard.rs).  that is the address that connected, unless
+ *  it is one of the TRUSTED_PROXIES -- then it is the last address in X-Forwarded-For that isn't a trusted proxy, since
+ *  anything before that was written by the caller and can say whatever it likes.
+ */
+pub fn client_address(peer: Option<IpAddr>, forwarded_for: Option<&str>, trusted_proxies: &[String]) -> Option<String> {
+    let peer = peer?.to_string();
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+    forwarded_for
+        .into_iter()
+        .flat_map(|value| value.rsplit(','))
+        .map(|address| address.trim())
+        .find(|address| !trusted_proxies.iter().any(|proxy| proxy == address))
+        .and_then(|address| address.parse::<IpAddr>().ok())
+        .map(|address| address.to_string())
+        .or(Some(peer))
+}
+
 pub struct RequestContextInjector<S> {
     service: S,
 }
@@ -181,7 +243,7 @@ where
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = S::Future;
+    type Future = Instrumented<SentryFuture<S::Future>>;
 
     fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.service.poll_ready(cx)
@@ -197,18 +259,78 @@ where
         });
 
         // Create RequestContext  - RequestContext runs *before* auth_mw, so claims are always None here
-        let request_context = RequestContext::new(
+        let mut request_context = RequestContext::new(
             &None,
             &test_context,
             &SERVICE_CONFIG,
             &SecurityContext::cached_secrets(),
         );
+        request_context.client_address = client_address(
+            req.peer_addr().map(|peer| peer.ip()),
+            req.headers()
+                .get("X-Forwarded-For")
+                .and_then(|value| value.to_str().ok()),
+            &SERVICE_CONFIG.trusted_proxies,
+        );
 
         // now we know what database to talk to!
 
         // Attach the RequestContext to the request's extensions
         req.extensions_mut().insert(request_context);
 
-        self.service.call(req)
+        // everything logged while handling the request is in this span.  user is filled in by auth_mw and game when
+        // the handler extracts its RequestContext.  callers can pass a correlation id to tie our logs to theirs
+        let correlation_id = req
+            .headers()
+            .get(GameHeader::CORRELATION_ID)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_owned())
+            .unwrap_or_else(PersistUser::new_id);
+        let span = tracing::info_span!(
+            "request",
+            method = %req.method(),
+            path = %req.path(),
+            correlation_id = %correlation_id,
+            user = tracing::field::Empty,
+            game = tracing::field::Empty,
+        );
+        // and anything reported to Sentry is tagged with the route and correlation id (see error_reporting.rs)
+        let hub = error_reporting::request_hub(req.path(), &correlation_id);
+        let future = {
+            let _entered = span.enter();
+            Hub::run(hub.clone(), || self.service.call(req))
+        };
+        future.bind_hub(hub).instrument(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_address() {
+        let caller: IpAddr = "198.51.100.4".parse().unwrap();
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let trusted = vec!["10.0.0.2".to_owned()];
+
+        // X-Forwarded-For from anybody but a trusted proxy is ignored
+        assert_eq!(
+            client_address(Some(caller), Some("203.0.113.9"), &trusted),
+            Some("198.51.100.4".to_owned())
+        );
+        assert_eq!(client_address(Some(proxy), Some("203.0.113.9"), &[]), Some("10.0.0.2".to_owned()));
+
+        // behind a trusted proxy it is the address the proxy saw, not what the caller put in front of it
+        assert_eq!(
+            client_address(Some(proxy), Some("203.0.113.9, 198.51.100.4"), &trusted),
+            Some("198.51.100.4".to_owned())
+        );
+        assert_eq!(
+            client_address(Some(proxy), Some("198.51.100.4, 10.0.0.2"), &trusted),
+            Some("198.51.100.4".to_owned())
+        );
+        assert_eq!(client_address(Some(proxy), None, &trusted), Some("10.0.0.2".to_owned()));
+        assert_eq!(client_address(None, Some("203.0.113.9"), &trusted), None);
     }
 }
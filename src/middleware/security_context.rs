@@ -1,11 +1,11 @@
 #![allow(dead_code)]
 use crate::{
     azure_setup::azure_wrapper::{key_vault_get_secret, key_vault_save_secret},
-    shared::service_models::Claims,
+    shared::service_models::{Claims, Role},
 };
 
 use rand::RngCore;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     fs::File,
     io::{Read, Write},
@@ -30,6 +30,8 @@ impl KeyKind {
     pub const TEST_SECONDARY_KEY: &'static str = "test-secondary-login-key";
     pub const VALIDATATION_PRIMARY_KEY: &'static str = "validation-primary-key";
     pub const VALIDATATION_SECONDARY_KEY: &'static str = "validation-secondary-key";
+    pub const SERVICE_PRIMARY_KEY: &'static str = "service-primary-key";
+    pub const SERVICE_SECONDARY_KEY: &'static str = "service-secondary-key";
 }
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
 pub struct KeySet {
@@ -69,6 +71,26 @@ impl KeySet {
         token_result.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
     }
 
+    /// sign something other than Claims -- it needs an "exp" field, like Claims has
+    pub fn sign_payload<T: Serialize>(&self, payload: &T) -> Result<String, Box<dyn std::error::Error>> {
+        encode(
+            &Header::new(Algorithm::HS512),
+            payload,
+            &EncodingKey::from_secret(self.primary_key.as_ref()),
+        )
+        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+    }
+
+    /// the payload of a token made with sign_payload, if either key signed it and it hasn't expired
+    pub fn validate_payload<T: DeserializeOwned>(&self, token: &str) -> Option<T> {
+        let validation = Validation::new(Algorithm::HS512);
+        [&self.primary_key, &self.secondary_key].iter().find_map(|key| {
+            decode::<T>(token, &DecodingKey::from_secret(key.as_ref()), &validation)
+                .ok()
+                .map(|data| data.claims)
+        })
+    }
+
     pub fn validate_token(&self, token: &str) -> Option<Claims> {
         // Try to validate with primary key first.
         let claims = match self.validate_jwt_token_with_key(&token, &self.primary_key) {
@@ -108,10 +130,38 @@ pub struct SecurityContext {
     pub login_keys: KeySet,
     pub validation_keys: KeySet,
     pub test_keys: KeySet,
+    //  service tokens are signed with their own keys, so a user token can never be passed off as one (and the
+    //  other way around).  contexts saved before there were service tokens get new keys the first time they load
+    #[serde(default = "SecurityContext::new_service_keys")]
+    pub service_keys: KeySet,
 }
 
 impl SecurityContext {
     const SECURITY_CONTEXT_SECRET_NAME: &'static str = "security-context-secrets";
+    /// how long a service token is good for.  components mint a new one when it runs out -- there is no refresh
+    pub const SERVICE_TOKEN_DURATION_SECS: u64 = 60 * 60;
+
+    fn new_service_keys() -> KeySet {
+        KeySet::new(KeyKind::SERVICE_PRIMARY_KEY, KeyKind::SERVICE_SECONDARY_KEY)
+    }
+
+    /// Mints a client-credentials style token for an internal component, e.g. "webhook-dispatcher".  The token has
+    /// the Service role and the service audience, and is signed with the service keys.
+    pub fn mint_service_token(&self, service_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+        self.service_keys.sign_claims(&Claims::new_service(
+            service_name,
+            Self::SERVICE_TOKEN_DURATION_SECS,
+        ))
+    }
+
+    /// the claims in a service token, if it is one.  a token signed with the service keys that isn't for the service
+    /// audience, or has any role but Service, is rejected
+    pub fn validate_service_token(&self, token: &str) -> Option<Claims> {
+        self.service_keys
+            .validate_token(token)
+            .filter(|claims| claims.is_service() && claims.roles == vec![Role::Service])
+    }
+
     pub fn cached_secrets() -> SecurityContext {
         let secrets = SECRETS_CACHE
             .read()
@@ -135,7 +185,7 @@ impl SecurityContext {
                     let _ = std::fs::remove_file(&cred_cache);
                 } else if let Ok(sc) = serde_json::from_str::<SecurityContext>(&json) {
                     // Successfully deserialized SecurityContext
-                    log::info!("loading keys from cache. this should *not* be production!");
+                    tracing::info!("loading keys from cache. this should *not* be production!");
                     return sc;
                 }
             }
@@ -143,14 +193,20 @@ impl SecurityContext {
 
         match key_vault_get_secret(&SERVICE_CONFIG.kv_name, Self::SECURITY_CONTEXT_SECRET_NAME) {
             Ok(json) => match serde_json::from_str::<SecurityContext>(&json) {
-                Ok(sc) => sc,
+                Ok(sc) => {
+                    if !json.contains("ServiceKeys") {
+                        // save the service keys that were just made, so every instance of the service uses them
+                        Self::save_security_context(&sc);
+                    }
+                    sc
+                }
                 Err(e) => {
-                    log::error!("Failed to deserialize the security context: {}", e);
+                    tracing::error!("Failed to deserialize the security context: {}", e);
                     Self::create_and_save_security_context()
                 }
             },
             Err(e) => {
-                log::error!("Failed to retrieve secret from key vault: {}", e);
+                tracing::error!("Failed to retrieve secret from key vault: {}", e);
                 Self::create_and_save_security_context()
             }
         }
@@ -166,16 +222,21 @@ impl SecurityContext {
                 KeyKind::VALIDATATION_SECONDARY_KEY,
             ),
             test_keys: KeySet::new(KeyKind::TEST_PRIMARY_KEY, KeyKind::TEST_SECONDARY_KEY),
+            service_keys: Self::new_service_keys(),
         };
+        Self::save_security_context(&security_context);
+        security_context
+    }
 
-        match serde_json::to_string(&security_context) {
+    fn save_security_context(security_context: &SecurityContext) {
+        match serde_json::to_string(security_context) {
             Ok(secrets) => {
                 if let Err(e) = key_vault_save_secret(
                     &SERVICE_CONFIG.kv_name,
                     Self::SECURITY_CONTEXT_SECRET_NAME,
                     &secrets,
                 ) {
-                    log::error!("Failed to save secret in key vault: {}", e);
+                    tracing::error!("Failed to save secret in key vault: {}", e);
                 }
 
                 if let Some(cred_cache) = SecurityContext::get_cache_file() {
@@ -184,10 +245,8 @@ impl SecurityContext {
                     }
                 }
             }
-            Err(e) => log::error!("Failed to serialize the security context: {}", e),
+            Err(e) => tracing::error!("Failed to serialize the security context: {}", e),
         }
-
-        security_context
     }
 
     pub fn refresh_cache() {
@@ -198,6 +257,7 @@ impl SecurityContext {
                 KeyKind::VALIDATATION_SECONDARY_KEY,
             ),
             test_keys: KeySet::new(KeyKind::TEST_PRIMARY_KEY, KeyKind::TEST_SECONDARY_KEY),
+            service_keys: Self::new_service_keys(),
         };
 
         // Acquire the write lock and update the cache
@@ -213,3 +273,35 @@ impl SecurityContext {
         openssl::base64::encode_block(&key)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_tokens() {
+        let security_context = SecurityContext::cached_secrets();
+        let token = security_context
+            .mint_service_token("webhook-dispatcher")
+            .expect("minting a service token should work");
+
+        let claims = security_context
+            .validate_service_token(&token)
+            .expect("a freshly minted token is valid");
+        assert_eq!(claims.id, "service:webhook-dispatcher");
+        assert_eq!(claims.roles, vec![Role::Service]);
+        assert!(claims.is_service());
+
+        // a service token isn't a login, and a login isn't a service token
+        assert!(security_context.login_keys.validate_token(&token).is_none());
+        let user_claims = Claims::new("1", "1@example.com", 60, &vec![Role::User], &None);
+        let user_token = security_context.login_keys.sign_claims(&user_claims).unwrap();
+        assert!(security_context.validate_service_token(&user_token).is_none());
+
+        // the service keys won't vouch for a person, even with the service audience
+        let mut impersonation = Claims::new_service("webhook-dispatcher", 60);
+        impersonation.roles.push(Role::Admin);
+        let token = security_context.service_keys.sign_claims(&impersonation).unwrap();
+        assert!(security_context.validate_service_token(&token).is_none());
+    }
+}
@@ -12,6 +12,19 @@ use std::{collections::HashMap, env};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    games_service::actions::reconnect::DEFAULT_RECONNECT_GRACE_SECONDS,
+    shared::branding::Branding,
+    user_service::{
+        oauth::OAuthConfig, password_policy::PasswordPolicy, push_notifications::PushProvider,
+        sessions::SessionPolicy,
+    },
+};
+
+use super::{kc_proxy::KeyCloakConfig, login_guard::LockoutPolicy};
+
+/// how long a login's access token is good for unless ACCESS_TOKEN_SECONDS says otherwise
+pub const DEFAULT_ACCESS_TOKEN_SECONDS: u64 = 15 * 60;
 
 // load the environment variables once and only once the first time they are accessed (which is in main() in this case)
 lazy_static! {
@@ -39,9 +52,24 @@ pub struct ServiceConfig {
     pub ssl_key_location: String,
     pub ssl_cert_location: String,
     pub login_secret_key: String,
+    pub public_alias_secret: String, // keys the aliases on the public pages (see public_results.rs)
     pub validation_secret_key: String,
 
     pub rust_log: String,
+    pub sentry_dsn: Option<String>, // error reporting is off unless this is set
+    pub analytics_export_dir: Option<String>, // the scheduled analytics export is off unless this is set
+    pub chat_profanity_filter: bool,          // star out profanity in chat messages (see chat.rs)
+    pub branding: Branding,                   // the name, support email, logo and terms the service presents
+    pub session_policy: SessionPolicy,        // how many logins a user can have at once (see sessions.rs)
+    pub push_provider: PushProvider,          // where push notifications go, if anywhere (see push_notifications.rs)
+    pub client_error_sample_percent: u32,     // how many of the crashes clients report are kept (see client_telemetry.rs)
+    pub access_token_seconds: u64,            // how long a login's access token is good for (see refresh_tokens.rs)
+    pub lockout_policy: LockoutPolicy,        // how many failed logins lock an account, and for how long (see login_guard.rs)
+    pub trusted_proxies: Vec<String>,         // the proxies whose X-Forwarded-For is believed (see request_context_mw.rs)
+    pub password_policy: PasswordPolicy,      // what a password a user picks has to be (see password_policy.rs)
+    pub oauth: OAuthConfig,                   // the Google/GitHub logins that are offered (see oauth.rs)
+    pub keycloak: KeyCloakConfig,             // the realm logins can also come from, if any (see kc_proxy.rs)
+    pub reconnect_grace_seconds: u64,         // how long a dropped player has before their turns are ended (see reconnect.rs)
 
     pub test_phone_number: String,
     pub service_phone_number: String,
@@ -71,6 +99,7 @@ impl ServiceConfig {
         let ssl_key_location = insert_env_to_map(&mut name_map, "SSL_KEY_FILE")?;
         let ssl_cert_location = insert_env_to_map(&mut name_map, "SSL_CERT_FILE")?;
         let login_secret_key = insert_env_to_map(&mut name_map, "LOGIN_SECRET_KEY")?;
+        let public_alias_secret = env::var("PUBLIC_ALIAS_SECRET").unwrap_or_else(|_| login_secret_key.clone());
         let validation_secret_key = insert_env_to_map(&mut name_map, "VALIDATION_SECRET_KEY")?;
         let rust_log = insert_env_to_map(&mut name_map, "RUST_LOG")?;
         let test_phone_number = insert_env_to_map(&mut name_map, "TEST_PHONE_NUMBER")?;
@@ -79,6 +108,40 @@ impl ServiceConfig {
         let service_email = insert_env_to_map(&mut name_map, "SERVICE_FROM_EMAIL")?;
         let location = insert_env_to_map(&mut name_map, "AZURE_LOCATION")?;
         let admin_email = insert_env_to_map(&mut name_map, "ADMIN_EMAIL")?;
+        let sentry_dsn = env::var("SENTRY_DSN").ok();
+        let analytics_export_dir = env::var("ANALYTICS_EXPORT_DIR").ok();
+        let chat_profanity_filter = env::var("CHAT_PROFANITY_FILTER")
+            .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let branding = Branding::from_env();
+        let session_policy = SessionPolicy::from_env();
+        let push_provider = PushProvider::from_env();
+        let client_error_sample_percent = env::var("CLIENT_ERROR_SAMPLE_PERCENT")
+            .ok()
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .map_or(100, |percent| percent.min(100));
+        let access_token_seconds = env::var("ACCESS_TOKEN_SECONDS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(DEFAULT_ACCESS_TOKEN_SECONDS);
+        let lockout_policy = LockoutPolicy::from_env();
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|proxy| proxy.trim().to_owned())
+                    .filter(|proxy| !proxy.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let password_policy = PasswordPolicy::from_env();
+        let oauth = OAuthConfig::from_env();
+        let keycloak = KeyCloakConfig::from_env();
+        let reconnect_grace_seconds = env::var("RECONNECT_GRACE_SECONDS")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_RECONNECT_GRACE_SECONDS);
         Ok(Self {
             resource_group,
             kv_name,
@@ -90,9 +153,24 @@ impl ServiceConfig {
             ssl_key_location,
             ssl_cert_location,
             login_secret_key,
+            public_alias_secret,
             validation_secret_key,
             cosmos_database_name: cosmos_database,
             rust_log,
+            sentry_dsn,
+            analytics_export_dir,
+            chat_profanity_filter,
+            branding,
+            session_policy,
+            push_provider,
+            client_error_sample_percent,
+            access_token_seconds,
+            lockout_policy,
+            trusted_proxies,
+            password_policy,
+            oauth,
+            keycloak,
+            reconnect_grace_seconds,
             test_email,
             service_email,
             name_value_map: name_map.clone(),
@@ -101,19 +179,19 @@ impl ServiceConfig {
     }
 
     pub fn dump_values(&self) {
-        log::info!("cosmos_token: {}", self.cosmos_token);
-        log::info!("cosmos_account: {}", self.cosmos_account);
-        log::info!("ssl_key_location: {}", self.ssl_key_location);
-        log::info!("ssl_cert_location: {}", self.ssl_cert_location);
-        log::info!("login_secret_key: {}", self.login_secret_key);
-        log::info!("validation_secret_key: {}", self.validation_secret_key);
-        log::info!("database_name: {}", self.cosmos_database_name);
-        log::info!("rust_log: {}", self.rust_log);
-        log::info!("kv_name: {}", self.kv_name);
-        log::info!("test_phone_number: {}", self.test_phone_number);
-        log::info!("test_email: {}", self.test_email);
-        log::info!("service_mail: {}", self.service_email);
-        log::info!("admin_email: {}", self.admin_email)
+        tracing::info!("cosmos_token: {}", self.cosmos_token);
+        tracing::info!("cosmos_account: {}", self.cosmos_account);
+        tracing::info!("ssl_key_location: {}", self.ssl_key_location);
+        tracing::info!("ssl_cert_location: {}", self.ssl_cert_location);
+        tracing::info!("login_secret_key: {}", self.login_secret_key);
+        tracing::info!("validation_secret_key: {}", self.validation_secret_key);
+        tracing::info!("database_name: {}", self.cosmos_database_name);
+        tracing::info!("rust_log: {}", self.rust_log);
+        tracing::info!("kv_name: {}", self.kv_name);
+        tracing::info!("test_phone_number: {}", self.test_phone_number);
+        tracing::info!("test_email: {}", self.test_email);
+        tracing::info!("service_mail: {}", self.service_email);
+        tracing::info!("admin_email: {}", self.admin_email)
     }
 }
 impl Default for ServiceConfig {
@@ -124,9 +202,24 @@ impl Default for ServiceConfig {
             ssl_key_location: String::default(),
             ssl_cert_location: String::default(),
             login_secret_key: String::default(),
+            public_alias_secret: String::default(),
             validation_secret_key: String::default(),
             cosmos_database_name: "Users-Database".to_owned(),
             rust_log: "actix_web=trace,actix_server=trace,rust=trace".to_owned(),
+            sentry_dsn: None,
+            analytics_export_dir: None,
+            chat_profanity_filter: false,
+            branding: Branding::default(),
+            session_policy: SessionPolicy::default(),
+            push_provider: PushProvider::default(),
+            client_error_sample_percent: 100,
+            access_token_seconds: DEFAULT_ACCESS_TOKEN_SECONDS,
+            lockout_policy: LockoutPolicy::default(),
+            trusted_proxies: Vec::new(),
+            password_policy: PasswordPolicy::default(),
+            oauth: OAuthConfig::default(),
+            keycloak: KeyCloakConfig::default(),
+            reconnect_grace_seconds: DEFAULT_RECONNECT_GRACE_SECONDS,
             kv_name: String::default(),
             test_phone_number: String::default(),
             resource_group: "catan-rg".to_owned(),
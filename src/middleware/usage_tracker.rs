@@ -0,0 +1,218 @@
+#![allow(dead_code)]
+/**
+ *  counts the authenticated requests each user makes, and when they last made one.  the authentication middleware
+ *  records every request it lets through, so the counts cover the whole /auth/api/v1 surface.
+ *
+ *  this is for quota decisions and for finding a broken client that is hammering the service -- it is kept in memory,
+ *  so it starts over when the service restarts.  a user can see their own usage; an admin can see everybody's.
+ *
+ *  it also remembers which users were active on each (UTC) day, for the daily active users in the analytics export.
+ */
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::HttpResponse;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    new_unauthorized_response,
+    shared::{
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+use super::request_context_mw::RequestContext;
+
+lazy_static::lazy_static! {
+static ref USAGE: Arc<RwLock<HashMap<String, UserUsage>>> = Arc::new(RwLock::new(HashMap::new()));
+static ref DAILY_ACTIVE: Arc<RwLock<HashMap<u64, HashSet<String>>>> = Arc::new(RwLock::new(HashMap::new()));}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserUsage {
+    pub user_id: String,
+    pub request_count: u64,
+    pub first_request: u64, // seconds since the epoch
+    pub last_activity: u64, // seconds since the epoch
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct UsageSummary {
+    pub user_count: usize,
+    pub request_count: u64,
+    pub users: Vec<UserUsage>, // busiest first
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct DailyActiveUsers {
+    pub day: u64, // days since the epoch, in UTC
+    pub user_count: usize,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// count one request for the user.  called by the authentication middleware once the token has been validated
+pub fn record(user_id: &str) {
+    let now = now();
+    let mut usage = match USAGE.write() {
+        Ok(usage) => usage,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let entry = usage.entry(user_id.to_owned()).or_insert_with(|| UserUsage {
+        user_id: user_id.to_owned(),
+        request_count: 0,
+        first_request: now,
+        last_activity: now,
+    });
+    entry.request_count += 1;
+    entry.last_activity = now;
+    drop(usage);
+
+    let mut daily = match DAILY_ACTIVE.write() {
+        Ok(daily) => daily,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    daily
+        .entry(now / SECONDS_PER_DAY)
+        .or_default()
+        .insert(user_id.to_owned());
+}
+
+/// how many users made a request on each day since the service started, oldest day first
+pub fn daily_active_users() -> Vec<DailyActiveUsers> {
+    let mut days: Vec<DailyActiveUsers> = DAILY_ACTIVE
+        .read()
+        .map(|daily| {
+            daily
+                .iter()
+                .map(|(day, users)| DailyActiveUsers {
+                    day: *day,
+                    user_count: users.len(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    days.sort_by_key(|day| day.day);
+    days
+}
+
+/// the usage for one user, or None if they haven't made an authenticated request since the service started
+pub fn usage_for(user_id: &str) -> Option<UserUsage> {
+    USAGE
+        .read()
+        .ok()
+        .and_then(|usage| usage.get(user_id).cloned())
+}
+
+/// everybody's usage, busiest first
+pub fn summary() -> UsageSummary {
+    let mut users: Vec<UserUsage> = USAGE
+        .read()
+        .map(|usage| usage.values().cloned().collect())
+        .unwrap_or_default();
+    users.sort_by(|a, b| {
+        b.request_count
+            .cmp(&a.request_count)
+            .then_with(|| a.user_id.cmp(&b.user_id))
+    });
+    UsageSummary {
+        user_count: users.len(),
+        request_count: users.iter().map(|user| user.request_count).sum(),
+        users,
+    }
+}
+
+/// the caller's own usage
+pub fn get_my_usage(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let user_id = match request_context.claims.as_ref() {
+        Some(claims) => claims.id.clone(),
+        None => return new_unauthorized_response!(""),
+    };
+    //  the request asking for the usage has already been counted, so there is always an entry
+    let usage = usage_for(&user_id).unwrap_or(UserUsage {
+        user_id,
+        request_count: 0,
+        first_request: 0,
+        last_activity: 0,
+    });
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::Usage(usage),
+        GameError::NoError(String::default()),
+    ))
+}
+
+/// every user's usage -- admin only
+pub fn get_all_usage(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::UsageSummary(summary()),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn get_my_usage_handler(request_context: RequestContext) -> HttpResponse {
+    get_my_usage(&request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn get_all_usage_handler(request_context: RequestContext) -> HttpResponse {
+    get_all_usage(&request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage() {
+        // other tests run through the middleware too, so only look at users nobody else uses
+        record("usage-test-1");
+        record("usage-test-1");
+        record("usage-test-2");
+        let first = usage_for("usage-test-1").expect("usage-test-1 made requests");
+        assert_eq!(first.request_count, 2);
+        assert!(first.last_activity >= first.first_request);
+        assert_eq!(usage_for("usage-test-2").unwrap().request_count, 1);
+        assert!(usage_for("usage-test-nobody").is_none());
+
+        let summary = summary();
+        assert!(summary.request_count >= 3);
+        let position = |id: &str| summary.users.iter().position(|user| user.user_id == id);
+        assert!(position("usage-test-1") < position("usage-test-2"));
+
+        let today = daily_active_users()
+            .last()
+            .cloned()
+            .expect("somebody was active today");
+        assert_eq!(today.day, now() / SECONDS_PER_DAY);
+        assert!(today.user_count >= 2);
+
+        // only admins can see everybody
+        let request_context = RequestContext::test_default(false);
+        let sr = get_all_usage(&request_context).expect_err("not an admin");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,964 @@
+/**
+ *  every route the service serves, and who can call it.  each scope is built with Routes, which registers a handler
+ *  and adds it to the manifest in the same call, so there is no way to serve a route that isn't in the manifest or to
+ *  list one that isn't served.  create_service! (which the tests use too) builds the App from unauthenticated_service
+ *  and authenticated_service, and the test at the bottom calls every route in the manifest on that App.
+ *
+ *  the role in the manifest is what a caller needs: None for the routes under /api/v1, User for anybody logged in,
+ *  Admin for the ones only admins can call.  the handlers check it -- the manifest just says what they check.  a few
+ *  admin routes also take service tokens (see the docs on admin_service).
+ */
+use actix_web::{
+    dev::HttpServiceFactory, http::Method, web, FromRequest, Handler, HttpRequest, HttpResponse, Resource, Responder,
+    Scope,
+};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+
+use crate::{
+    games_service::{
+        actions::{action_handlers, delegation, local_seats, seat_tokens},
+        bots::bot_handlers,
+        chat::chat,
+        game_container::{game_history, snapshot_diff, surgery},
+        game_handlers,
+        lobby::lobby_handlers,
+        long_poller::{long_poller_handler::long_poll_handler, message_schema, presence, sse, websocket},
+        public_results, webhooks,
+    },
+    get_ready, get_version,
+    middleware::{login_guard, usage_tracker},
+    shared::{
+        analytics_export, client_telemetry, integrity, log_filter, profiling, service_info,
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+        status_page,
+    },
+    user_service::{
+        automation_preferences, direct_messages, notification_preferences, oauth, push_notifications,
+        refresh_tokens, user_handlers, user_import, user_stats,
+    },
+};
+
+/// the message no_route answers with
+pub const NO_ROUTE: &str = "no such route";
+
+/// one route in the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServedRoute {
+    pub method: Method,
+    pub path: String,       // the whole path, with its {parameters}: /auth/api/v1/users/{id}
+    pub role: Option<Role>, // what the caller needs.  None: anybody, without logging in
+}
+
+/// a scope and the manifest of the routes in it, built together
+struct Routes {
+    path: String,
+    role: Option<Role>, // what the routes in the scope need, unless they are added with admin_route
+    scope: Scope,
+    manifest: Vec<ServedRoute>,
+}
+
+impl Routes {
+    fn new(path: &str, role: Option<Role>) -> Self {
+        Routes {
+            path: path.to_owned(),
+            role,
+            scope: web::scope(path),
+            manifest: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, method: Method, path: &str, role: Option<Role>) {
+        self.manifest.push(ServedRoute {
+            method,
+            path: format!("{}{}", self.path, path),
+            role,
+        });
+    }
+
+    fn route_as<F, Args>(mut self, role: Option<Role>, method: Method, path: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.add(method.clone(), path, role);
+        self.scope = self.scope.route(path, web::method(method).to(handler));
+        self
+    }
+
+    fn route<F, Args>(self, method: Method, path: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        let role = self.role.clone();
+        self.route_as(role, method, path, handler)
+    }
+
+    /// a route only admins can call, in a scope anybody logged in can
+    fn admin_route<F, Args>(self, method: Method, path: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.route_as(Some(Role::Admin), method, path, handler)
+    }
+
+    /// a route that needs its own resource settings (a bigger payload limit, a middleware of its own...)
+    fn resource<F, Args, R>(
+        mut self,
+        method: Method,
+        path: &str,
+        configure: impl FnOnce(Resource) -> R,
+        handler: F,
+    ) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+        R: HttpServiceFactory + 'static,
+    {
+        let role = self.role.clone();
+        self.add(method.clone(), path, role);
+        self.scope = self
+            .scope
+            .service(configure(web::resource(path).route(web::method(method).to(handler))));
+        self
+    }
+
+    /// a scope inside this one
+    fn service(mut self, routes: Routes) -> Self {
+        for route in routes.manifest {
+            self.manifest.push(ServedRoute {
+                path: format!("{}{}", self.path, route.path),
+                ..route
+            });
+        }
+        self.scope = self.scope.service(routes.scope);
+        self
+    }
+}
+
+/// the routes anybody can call
+pub fn unauthenticated_service() -> Scope {
+    unauthenticated().scope
+}
+
+/// the routes that need a login.  create_service! puts the authentication middleware in front of them
+pub fn authenticated_service() -> Scope {
+    authenticated().scope
+}
+
+/// every route the service serves
+pub fn manifest() -> Vec<ServedRoute> {
+    let mut manifest = unauthenticated().manifest;
+    manifest.extend(authenticated().manifest);
+    manifest
+}
+
+/**
+ *  the parameters in the path, if it is one the pattern (a path in the manifest) matches.  every parameter is one
+ *  whole segment, the way the routes here use them
+ */
+pub fn match_path(pattern: &str, path: &str) -> Option<HashMap<String, String>> {
+    let patterns: Vec<&str> = pattern.split('/').collect();
+    let segments: Vec<&str> = path.split('/').collect();
+    if patterns.len() != segments.len() {
+        return None;
+    }
+    let mut params = HashMap::new();
+    for (pattern, segment) in patterns.iter().zip(segments) {
+        if pattern.starts_with('{') && pattern.ends_with('}') {
+            if segment.is_empty() {
+                return None;
+            }
+            params.insert(pattern[1..pattern.len() - 1].to_owned(), segment.to_owned());
+        } else if *pattern != segment {
+            return None;
+        }
+    }
+    Some(params)
+}
+
+/// what anything that isn't in the manifest gets
+pub async fn no_route(request: HttpRequest) -> HttpResponse {
+    ServiceResponse::new(
+        NO_ROUTE,
+        StatusCode::NOT_FOUND,
+        ResponseType::ErrorInfo(format!("{} {}", request.method(), request.path())),
+        GameError::HttpError(StatusCode::NOT_FOUND),
+    )
+    .to_http_response()
+}
+
+fn authenticated() -> Routes {
+    Routes::new("/auth/api/v1", Some(Role::User))
+        .service(user_service())
+        .service(lobby_service())
+        .service(game_service())
+        .service(longpoll_service())
+        .service(websocket_service())
+        .service(events_service())
+        .service(profile_service())
+        .service(action_service())
+        .service(telemetry_service())
+        .service(admin_service())
+}
+
+/**
+ * Creates a set of unauthenticated services under the "/api/v1" path.
+ * These endpoints are accessible without any user authentication and are mainly used for:
+ *
+ * - Version Information:
+ *   - Retrieves the version information of the application.
+ *   - URL: `https://localhost:8080/api/v1/version`
+ *   - Method: `GET`
+ *
+ * - Service Information:
+ *   - The semantic version, git commit, build time, enabled features and flags, and supported game types.
+ *   - URL: `https://localhost:8080/api/v1/info`
+ *   - Method: `GET`
+ *
+ * - Message Schema:
+ *   - The JSON Schema of every message the long poller can return, for clients that generate their bindings.
+ *   - URL: `https://localhost:8080/api/v1/schema/messages`
+ *   - Method: `GET`
+ *
+ * - Readiness:
+ *   - Reports whether the service can reach its database.
+ *   - URL: `https://localhost:8080/api/v1/ready`
+ *   - Method: `GET`
+ *
+ * - Status:
+ *   - For a public status page: how each component is doing, the current incident and maintenance announcements,
+ *     and the database's uptime over the last day and week.  No internal details.
+ *   - URL: `https://localhost:8080/api/v1/status`
+ *   - Method: `GET`
+ *
+ * - User Registration:
+ *   - Registers a new user with the provided information.  In KeyCloak mode the user is made in the realm too, with
+ *     the same password; if that fails nothing is registered (see kc_proxy.rs).  A password that is too guessable,
+ *     common or breached is refused with a 400 listing the PasswordProblems (see password_policy.rs).
+ *   - URL: `https://localhost:8080/api/v1/users/register`
+ *   - Method: `POST`
+ *
+ * - User Login:
+ *   - Authenticates a user and returns a short lived access token and a refresh token.  Too many failed logins lock
+ *     the account for a while (423, GameError::AccountLocked) and mail the owner; too many from one address are
+ *     refused with a 429.  The address is the one that connected, or what a TRUSTED_PROXIES proxy says it forwarded
+ *     for (see login_guard.rs).
+ *   - URL: `https://localhost:8080/api/v1/users/login`
+ *   - Method: `POST`
+ *
+ * - Refresh Login:
+ *   - Swaps a refresh token (in the body) for a new access token and refresh token.  Each refresh token works once:
+ *     using one again revokes every token from that login, and so does resetting the password or changing the
+ *     email (see refresh_tokens.rs).
+ *   - URL: `https://localhost:8080/api/v1/users/refresh`
+ *   - Method: `POST`
+ *
+ * - Google/GitHub Login:
+ *   - Start sets a cookie on the browser and redirects it to the provider; the provider sends it back to the
+ *     callback, which starts a session and sends the browser on to the client's page with a one time code.  The
+ *     client POSTs the code (in the body) to exchange, which answers like login.  The first login with an account
+ *     makes a new user; an account with an existing user's email has to be linked by them (see oauth.rs).
+ *   - URL: `https://localhost:8080/api/v1/users/oauth/{google|github}/start`
+ *   - URL: `https://localhost:8080/api/v1/users/oauth/{google|github}/callback?code={code}&state={state}`
+ *   - Method: `GET`
+ *   - URL: `https://localhost:8080/api/v1/users/oauth/exchange`
+ *   - Method: `POST`
+ *
+ * - Reset Password:
+ *   - Sets the password of an account that has to have one set (an imported user), with the token from the link they
+ *     were mailed when they first tried to log in.  The password is in the password header, and is checked like
+ *     the one a user registers with.
+ *   - URL: `https://localhost:8080/api/v1/users/reset-password/{token}`
+ *   - Method: `POST`
+ *
+ * - Confirm Email Change:
+ *   - The link mailed to a new address by Change Email.  Makes it the user's email, validated, and tells the old
+ *     address.  Like the validation link, a browser gets a page.
+ *   - URL: `https://localhost:8080/api/v1/users/confirm-email/{token}`
+ *   - Method: `GET`
+ *
+ * - Public Results:
+ *   - Finished games, newest first, and a leaderboard of wins over the last days, for community sites.  Players are
+ *     only named -- by an alias -- if they opted in.  Rate limited and cacheable (see public_results.rs).
+ *   - URL: `https://localhost:8080/api/v1/public/games?count={count}`
+ *   - URL: `https://localhost:8080/api/v1/public/games/{game_id}`
+ *   - URL: `https://localhost:8080/api/v1/public/leaderboard?days={days}`
+ *   - Method: `GET`
+ *
+ * - Test Setup:
+ *   - A special endpoint used only for testing purposes to set up test data.
+ *   - URL: `https://localhost:8080/api/v1/test/verify-service`
+ *   - Method: `POST`
+ */
+fn unauthenticated() -> Routes {
+    Routes::new("/api/v1", None)
+        .route(Method::GET, "/version", get_version)
+        .route(Method::GET, "/info", service_info::get_info)
+        .route(Method::GET, "/schema/messages", message_schema::get_message_schema)
+        .route(Method::GET, "/ready", get_ready)
+        .route(Method::GET, "/status", status_page::get_status)
+        .route(Method::POST, "/users/register", user_handlers::register_handler)
+        .resource(
+            Method::POST,
+            "/users/login",
+            |resource| resource.wrap(login_guard::LoginGuard),
+            user_handlers::login_handler,
+        )
+        .route(Method::POST, "/users/refresh", refresh_tokens::refresh_handler)
+        .route(Method::GET, "/users/oauth/{provider}/start", oauth::start_handler)
+        .route(Method::GET, "/users/oauth/{provider}/callback", oauth::callback_handler)
+        .route(Method::POST, "/users/oauth/exchange", oauth::exchange_handler)
+        .route(Method::POST, "/test/verify-service", user_handlers::verify_handler) /* TEST ONLY */
+        .route(Method::GET, "/users/validate-email/{token}", user_handlers::validate_email)
+        .route(Method::POST, "/users/reset-password/{token}", user_handlers::reset_password_handler)
+        .route(Method::GET, "/users/confirm-email/{token}", user_handlers::confirm_email_change_handler)
+        .route(Method::GET, "/public/games", public_results::recent_games_handler)
+        .route(Method::GET, "/public/games/{game_id}", public_results::game_handler)
+        .route(Method::GET, "/public/leaderboard", public_results::leaderboard_handler)
+}
+
+/**
+ * Creates a set of user-related services under the "/users" path.
+ * These endpoints allow for user management and are typically restricted to authenticated users:
+ *
+ * - List Users:
+ *   - Retrieves a list of all users in the system.
+ *   - URL: `https://localhost:8080/auth/api/v1/users`
+ *   - Method: `GET`
+ *
+ * - Delete User:
+ *   - Deletes a user with the given ID.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/{id}` (replace `{id}` with the user's ID)
+ *   - Method: `DELETE`
+ *
+ * - Online Users:
+ *   - The users who have a long poll, socket or event stream open, or had one in the last minute, and when each was
+ *     last active.  Changes are also sent to everybody connected as PresenceChanged messages.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/online`
+ *   - Method: `GET`
+ *
+ * - Push Tokens:
+ *   - Registers one of the caller's devices for push notifications (turn started, invitation, game over), sent when
+ *     they aren't connected.  The body is a PushToken: the device's token and its platform.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/push-token`
+ *   - Method: `POST`
+ *   - Stops pushing to the device.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/push-token`
+ *   - Method: `DELETE`
+ *
+ * - Notification Preferences:
+ *   - How often the caller is pushed: at most one push for a game every GamePushMinutes, and nothing during their
+ *     QuietHours.  Held pushes are sent together later, as a digest if there are several games (see
+ *     notification_preferences.rs).
+ *   - URL: `https://localhost:8080/auth/api/v1/users/notification-preferences`
+ *   - Method: `GET`, `PUT`
+ *
+ * - Automation Preferences:
+ *   - What the service does for the caller in their games: discard for them in the order of their DiscardPriority
+ *     when a 7 is rolled, accept offers from the players in AcceptTradesFrom, and turn offers down while they are
+ *     away if DeclineTradesWhenAway is set.  All off by default (see automation_preferences.rs).
+ *   - URL: `https://localhost:8080/auth/api/v1/users/automation-preferences`
+ *   - Method: `GET`, `PUT`
+ *
+ * - Public Results:
+ *   - Puts the caller on the public game results and leaderboards (true), under an alias, or takes them off (false).
+ *   - URL: `https://localhost:8080/auth/api/v1/users/public-results`
+ *   - Method: `PUT`
+ *
+ * - Change Email:
+ *   - Mails a link to the new address (in the email header) that makes it the caller's email.  Until it is opened the
+ *     caller keeps the old one -- to log in with and to be mailed at.  Updating the profile can't change the email.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/email/change`
+ *   - Method: `POST`
+ *
+ * - Link Google/GitHub:
+ *   - Returns the url of the provider for the client to open.  Once the user has said yes there, the account is
+ *     linked to the caller, who can log in with it from then on.  The caller's email has to be validated.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/oauth/{google|github}/link`
+ *   - Method: `GET`
+ *
+ * - Logout:
+ *   - Ends the caller's session and revokes its refresh tokens.  Their other logins carry on.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/logout`
+ *   - Method: `POST`
+ *
+ * - Block:
+ *   - Drops the direct messages the user sends the caller from now on.  The sender isn't told.  DELETE unblocks them.
+ *     The caller's block list is in their profile.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/blocked/{id}`
+ *   - Method: `PUT`, `DELETE`
+ *
+ * - Direct Message:
+ *   - Sends the user a message (a ChatData) through the long poller, on the caller's direct channel (dm:{caller_id}).
+ *   - URL: `https://localhost:8080/auth/api/v1/users/{id}/message`
+ *   - Method: `POST`
+ *
+ * - Find User by ID:
+ *   - Retrieves details of a specific user by their ID.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/{id}` (replace `{id}` with the user's ID)
+ *   - Method: `GET`
+ *
+ * - Usage:
+ *   - How many requests the caller has made since the service started, and when they last made one.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/self/usage`
+ *   - Method: `GET`
+ *
+ * - Stats:
+ *   - Games played and won, and how the user did in each game (resources collected, dev cards played, longest
+ *     road...), newest first.  Users can see their own; admins can see anybody's.
+ *   - URL: `https://localhost:8080/auth/api/v1/users/{id}/stats`
+ *   - Method: `GET`
+ */
+fn user_service() -> Routes {
+    Routes::new("/users", Some(Role::User))
+        .route(Method::GET, "", user_handlers::list_users_handler)
+        .route(Method::POST, "/local", user_handlers::create_local_user_handler)
+        .route(Method::GET, "/local/{id}", user_handlers::get_local_users_handler)
+        .route(Method::DELETE, "/local/{id}", user_handlers::delete_local_user_handler)
+        .route(Method::PUT, "/local", user_handlers::update_local_user_handler)
+        .route(Method::GET, "/online", presence::online_users_handler)
+        // before /{id}, which would otherwise take the DELETE
+        .route(Method::POST, "/push-token", push_notifications::register_token_handler)
+        .route(Method::DELETE, "/push-token", push_notifications::unregister_token_handler)
+        .route(Method::PUT, "/public-results", public_results::set_public_results_handler)
+        .route(
+            Method::GET,
+            "/notification-preferences",
+            notification_preferences::get_preferences_handler,
+        )
+        .route(
+            Method::PUT,
+            "/notification-preferences",
+            notification_preferences::set_preferences_handler,
+        )
+        .route(
+            Method::GET,
+            "/automation-preferences",
+            automation_preferences::get_preferences_handler,
+        )
+        .route(
+            Method::PUT,
+            "/automation-preferences",
+            automation_preferences::set_preferences_handler,
+        )
+        .route(Method::PUT, "/blocked/{id}", direct_messages::block_user_handler)
+        .route(Method::DELETE, "/blocked/{id}", direct_messages::unblock_user_handler)
+        .route(Method::POST, "/{id}/message", direct_messages::send_direct_message_handler)
+        .route(Method::DELETE, "/{id}", user_handlers::delete_handler)
+        .route(Method::GET, "/{id}", user_handlers::find_user_by_id_handler)
+        .route(Method::PUT, "/{id}", user_handlers::update_profile_handler)
+        .route(Method::POST, "/phone/validate/{code}", user_handlers::validate_phone_handler)
+        .route(Method::POST, "/phone/send-code", user_handlers::send_phone_code_handler)
+        .route(Method::POST, "/email/send-validation-email", user_handlers::send_validation_email)
+        .route(Method::POST, "/email/change", user_handlers::request_email_change_handler)
+        .route(Method::POST, "/logout", refresh_tokens::logout_handler)
+        .route(Method::GET, "/oauth/{provider}/link", oauth::link_handler)
+        .admin_route(Method::POST, "/register-test-user", user_handlers::register_test_user_handler)
+        .admin_route(Method::POST, "/rotate-login-keys", user_handlers::rotate_login_keys_handler)
+        .route(Method::GET, "/self/usage", usage_tracker::get_my_usage_handler)
+        .route(Method::GET, "/{id}/stats", user_stats::user_stats_handler)
+}
+/**
+ * Creates a set of lobby-related services under the "/lobby" path.
+ * These endpoints allow for handling lobby operations within the game, such as inviting and joining games.
+ * They are typically restricted to authenticated users:
+ *
+ * - Get Lobby:
+ *   - Retrieves information about the current lobby.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby`
+ *   - Method: `GET`
+ *
+ * - Invite to Lobby:
+ *   - Sends an invite to another user to join the current lobby.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/invite`
+ *   - Method: `POST`
+ *
+ * - Join Game:
+ *   - Allows a user to join a game via the lobby.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/joingame`
+ *   - Method: `POST`
+ *
+ * - Add Bot:
+ *   - Fills an empty seat in the game in the x-game-id header with a bot: `Easy`, `Medium` or `Hard`.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/add-bot/{difficulty}`
+ *   - Method: `POST`
+ *
+ * - Join By Code:
+ *   - Joins the private game with the code, without an invitation, while it is still taking players.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/join-by-code/{code}`
+ *   - Method: `POST`
+ *
+ * - Kick:
+ *   - The creator removes a player from the game in the x-game-id header before it starts.  with `?ban=true` the
+ *     player can't join that game again.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/kick/{user_id}`
+ *   - Method: `POST`
+ *
+ * - Browse Games:
+ *   - Lists the public games the caller could join, filtered by the optional game_type, min_players, max_players,
+ *     victory_points, win_condition and min_rating query parameters and the auto_end_turn, undo_after_roll and
+ *     removal_policy house rules, and paged with offset and count.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/games`
+ *   - Method: `GET`
+ *
+ * - Matchmake:
+ *   - Queues the caller to be matched with players of the same rating band for a game of the type (and size) in the
+ *     body.  the game is announced through the long poller once the matchmaker has a full table.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/matchmake`
+ *   - Method: `POST`
+ *
+ * - Leave Matchmaking:
+ *   - Takes the caller out of the matchmaking queue.
+ *   - URL: `https://localhost:8080/auth/api/v1/lobby/matchmake`
+ *   - Method: `DELETE`
+ */
+fn lobby_service() -> Routes {
+    Routes::new("/lobby", Some(Role::User))
+        .route(Method::GET, "", lobby_handlers::get_lobby)
+        .route(Method::POST, "/invite", lobby_handlers::post_invite)
+        .route(Method::POST, "/acceptinvite", lobby_handlers::respond_to_invite)
+        .route(Method::POST, "/add-bot/{difficulty}", bot_handlers::add_bot)
+        .route(Method::POST, "/join-by-code/{code}", lobby_handlers::join_by_code)
+        .route(Method::POST, "/kick/{user_id}", lobby_handlers::kick)
+        .route(Method::GET, "/games", lobby_handlers::get_games)
+        .route(Method::POST, "/matchmake", lobby_handlers::matchmake)
+        .route(Method::DELETE, "/matchmake", lobby_handlers::leave_matchmaking)
+}
+
+/**
+ * Creates a set of game-related services under the "/games" path.
+ * These endpoints enable various game operations, such as fetching supported games, creating a new game, and shuffling an existing game.
+ * They are typically restricted to authenticated users:
+ *
+ * - Supported Games:
+ *   - Retrieves information about the supported game types.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/`
+ *   - Method: `GET`
+ *
+ * - New Game:
+ *   - Creates a new game of the specified type: `Regular`, or `Seafarers` for the multi-island board with ships.
+ *     `?visibility=private` makes a private game, with a join code in the returned game.  `?webhook={https url}`
+ *     has the service POST the game's events (started, turn changed, over) there, signed.  A url whose host isn't
+ *     on the public internet is refused.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_type}?visibility={public|private}&webhook={url}`
+ *   - Method: `POST`
+ *
+ * - New Custom Game:
+ *   - Creates a new game on the board in the body (a CustomBoardData) instead of a shuffled one.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/custom`
+ *   - Method: `POST`
+ *
+ * - Shuffle Game:
+ *   - Initiates the shuffling of the specified game.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/shuffle/{game_id}`
+ *   - Method: `POST`
+ *
+ * - Explain Action:
+ *   - Explains why an action is or isn't currently legal for the caller.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/actions/explain/{action}`
+ *   - Method: `GET`
+ *
+ * - Members:
+ *   - Everybody in the game, with their role (creator, co-host, player, bot or spectator), seat and whether they are
+ *     connected.  Only the players in the game can ask.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/members`
+ *   - Method: `GET`
+ *
+ * - Options:
+ *   - Sets the victory point target (8, 10 or 12), the win condition and whether turns end automatically (a
+ *     GameOptions) before the game starts.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/options`
+ *   - Method: `POST`
+ *
+ * - Language:
+ *   - The creator sets the language (a Language) the game's system messages, turn summaries and push notifications
+ *     are written in.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/language`
+ *   - Method: `PUT`
+ *
+ * - Own Language:
+ *   - A player reads the game in their own language (a Language), or in the game's again with null.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/language/self`
+ *   - Method: `PUT`
+ *
+ * - Webhook:
+ *   - The creator gets the url passed as ?webhook= when the game was created, and the secret the service signs each
+ *     event it POSTs there with (see webhooks.rs).
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/webhook`
+ *   - Method: `GET`
+ *
+ * - History:
+ *   - A page of the changes the game went through, oldest first, for replaying it.  Works for finished games too.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/history?offset={offset}&count={count}`
+ *   - Method: `GET`
+ *
+ * - State:
+ *   - For a player rejoining after their long poll connection died: the current game, and the messages on the game's
+ *     channel after the last sequence number they saw.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/state?since={sequence}`
+ *   - Method: `GET`
+ *
+ * - Chat:
+ *   - Sends a message (a ChatData) to everybody in the game, through the long poller.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/chat`
+ *   - Method: `POST`
+ *
+ * - Local Capabilities:
+ *   - The owner of a local user in the game sets what their seat can do (a LocalCapabilities: trade, undo, or
+ *     spectate only).  The owner plays the seat by sending actions with an X-Acting-As: {local_user_id} header.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/local/{local_user_id}/capabilities`
+ *   - Method: `PUT`
+ *
+ * - Seat Token:
+ *   - The owner of a local user in the game gets a token (a Token) for a shared screen at the table.  It can only
+ *     take the local user's actions in the game and see the game as they do, for 12 hours (see seat_tokens.rs).
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/local/{local_user_id}/token`
+ *   - Method: `POST`
+ *
+ * - Delegate:
+ *   - Hands the caller's turns to another player in the game or to a bot (a Delegate) while they step away.  Another
+ *     player plays the seat by sending actions with an X-Acting-As: {user_id} header.  DELETE takes the turns back,
+ *     and either of them can.
+ *   - URL: `https://localhost:8080/auth/api/v1/games/{game_id}/delegate`
+ *   - Method: `POST`, `DELETE`
+ */
+fn game_service() -> Routes {
+    Routes::new("/games", Some(Role::User))
+        .route(Method::GET, "/", game_handlers::supported_games)
+        // before /{game_type}, which would otherwise match "custom"
+        .route(Method::POST, "/custom", game_handlers::new_custom_game)
+        .route(Method::POST, "/{game_type}", game_handlers::new_game)
+        .route(Method::POST, "/shuffle/{game_id}", game_handlers::shuffle_game)
+        .route(Method::GET, "/{game_id}/actions/explain/{action}", action_handlers::explain_action)
+        .route(Method::GET, "/{game_id}/members", game_handlers::game_members)
+        .route(Method::POST, "/{game_id}/options", game_handlers::set_game_options)
+        .route(Method::PUT, "/{game_id}/language", game_handlers::set_game_language)
+        .route(Method::PUT, "/{game_id}/language/self", game_handlers::set_language_override)
+        .route(Method::GET, "/{game_id}/webhook", webhooks::get_webhook_handler)
+        .route(Method::GET, "/{game_id}/history", game_history::game_history_handler)
+        .route(Method::GET, "/{game_id}/state", game_handlers::rejoin_state)
+        .route(Method::POST, "/{game_id}/chat", chat::chat_handler)
+        .route(
+            Method::PUT,
+            "/{game_id}/local/{local_user_id}/capabilities",
+            local_seats::set_capabilities_handler,
+        )
+        .route(Method::POST, "/{game_id}/local/{local_user_id}/token", seat_tokens::mint_handler)
+        .route(Method::POST, "/{game_id}/delegate", delegation::delegate_handler)
+        .route(Method::DELETE, "/{game_id}/delegate", delegation::revoke_handler)
+}
+
+/**
+ *  every action can send the checksum of the game the client is at, in an x-game-checksum: {game_index}:{checksum}
+ *  header.  a client whose checksum doesn't match the game's is sent the current game on the long poller, and the
+ *  mismatch shows up in the admin client error summary (see checksum.rs)
+ */
+fn action_service() -> Routes {
+    Routes::new("/action", Some(Role::User))
+        .route(Method::POST, "/start/{game_id}", action_handlers::next)
+        .route(Method::GET, "/actions/{game_id}", action_handlers::valid_actions)
+        .route(Method::POST, "/next/{game_id}", action_handlers::next)
+        .route(Method::POST, "/roll/{game_id}", action_handlers::roll)
+        .route(Method::POST, "/discard/{game_id}", action_handlers::discard)
+        .route(Method::POST, "/move-baron/{game_id}", action_handlers::move_baron)
+        .route(Method::POST, "/build/{game_id}", action_handlers::build)
+        .route(Method::POST, "/undo/{game_id}", action_handlers::undo)
+        .route(Method::POST, "/redo/{game_id}", action_handlers::redo)
+        .route(Method::POST, "/forfeit/{game_id}", action_handlers::forfeit)
+        .route(Method::POST, "/trade/offer/{game_id}", action_handlers::offer_trade)
+        .route(Method::POST, "/trade/accept/{game_id}/{offer_id}", action_handlers::accept_trade)
+        .route(Method::POST, "/trade/reject/{game_id}/{offer_id}", action_handlers::reject_trade)
+        .route(Method::POST, "/trade/counter/{game_id}/{offer_id}", action_handlers::counter_trade)
+        .route(Method::POST, "/trade/bank/{game_id}", action_handlers::bank_trade)
+        .route(Method::POST, "/trade/bank/best/{game_id}", action_handlers::best_bank_trade)
+        .route(Method::POST, "/dev-card/play/{game_id}", action_handlers::play_dev_card)
+        .route(Method::POST, "/dev-card/resolve/{game_id}", action_handlers::resolve_dev_card)
+        .route(Method::POST, "/dev-card/monopoly/{game_id}", action_handlers::play_monopoly)
+        .route(
+            Method::POST,
+            "/dev-card/year-of-plenty/{game_id}",
+            action_handlers::play_year_of_plenty,
+        )
+}
+
+/**
+ * Creates a set of admin-only services under the "/admin" path:
+ *
+ * - Log Filter:
+ *   - Gets or replaces the log filter (RUST_LOG syntax, eg. "info,catan_service::cosmos_db=trace") while the service
+ *     is running.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/log-filter`
+ *   - Method: `GET`, `PUT`
+ *
+ * - Usage:
+ *   - Every user's request count and last activity, busiest first.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/usage`
+ *   - Method: `GET`
+ *
+ * - Analytics Export:
+ *   - Writes the match history, user aggregate and daily active user CSVs to ANALYTICS_EXPORT_DIR now, instead of
+ *     waiting for the scheduled export.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/analytics/export`
+ *   - Method: `POST`
+ *
+ * - Integrity:
+ *   - The report from the last integrity check: orphaned local users deleted, games archived because their players
+ *     were deleted, and expired invitations forgotten.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/integrity`
+ *   - Method: `GET`
+ *   - Runs the check now.  With ?dry_run=true it only reports what it would repair.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/integrity?dry_run={true|false}`
+ *   - Method: `POST`
+ *
+ * - User Import:
+ *   - Creates accounts from a CSV (Content-Type: text/csv) or JSON file of users -- email, display name and optionally
+ *     games played and won.  The users set a password the first time they log in.  Returns a report for each row;
+ *     files with more than 100 rows are imported in the background and the call returns 202 with the job's id.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/users/import`
+ *   - Method: `POST`
+ *   - The report of an import job, as far as it has got.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/users/import/{job_id}`
+ *   - Method: `GET`
+ *
+ * - Snapshot Diff:
+ *   - What changed in a game between two of its snapshots (by game_index): state, current player, baron, and each
+ *     player's resources, buildings and roads.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/games/{game_id}/diff/{from_index}/{to_index}`
+ *   - Method: `GET`
+ *
+ * - Game Surgery:
+ *   - Repairs a live game a bug has broken: applies the ops (give or take resources, set the bank, move the baron, set
+ *     the state or current player, clear stuck discards, trades or dev cards) to the game at BaseGameIndex, checks the
+ *     invariants, pushes it to the players and records it, with the Reason, in the game's event log.  It also ends a
+ *     quarantine.  With ?dry_run=true it returns the repaired game without changing anything.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/games/{game_id}/surgery?dry_run={true|false}`
+ *   - Method: `POST`
+ *
+ * - Status Announcements:
+ *   - Every incident and maintenance announcement, including the ones that are over.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/status/announcements`
+ *   - Method: `GET`
+ *   - Adds an announcement, or replaces the one with the same id.  Set EndsAt to end it.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/status/announcements`
+ *   - Method: `POST`
+ *   - Deletes an announcement.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/status/announcements/{id}`
+ *   - Method: `DELETE`
+ *
+ * - Profiling (only when built with `--features profiling`):
+ *   - Timing histograms for the GameContainer locks, response serialization and game broadcasts.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/profiling/timings`
+ *   - Method: `GET`
+ *   - Profiles the service for ?seconds= (10 by default, at most 60) and returns a flamegraph svg.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/profiling/flamegraph?seconds={seconds}`
+ *   - Method: `GET`
+ *
+ * - Client Errors:
+ *   - The crashes and desyncs clients have reported: counts by kind and client version, and the newest reports, with
+ *     how each desync compared with the service's game.
+ *   - URL: `https://localhost:8080/auth/api/v1/admin/telemetry/client-errors`
+ *   - Method: `GET`
+ */
+fn admin_service() -> Routes {
+    Routes::new("/admin", Some(Role::Admin))
+        .route(Method::GET, "/log-filter", log_filter::get_log_filter_handler)
+        .route(Method::PUT, "/log-filter", log_filter::set_log_filter_handler)
+        .route(Method::GET, "/usage", usage_tracker::get_all_usage_handler)
+        .route(Method::POST, "/analytics/export", analytics_export::run_export_handler)
+        .route(Method::GET, "/integrity", integrity::last_report_handler)
+        .route(Method::POST, "/integrity", integrity::run_check_handler)
+        .resource(
+            Method::POST,
+            "/users/import",
+            |resource| resource.app_data(web::PayloadConfig::new(user_import::MAX_IMPORT_BYTES)),
+            user_import::import_users_handler,
+        )
+        .route(Method::GET, "/users/import/{job_id}", user_import::import_job_handler)
+        .route(
+            Method::GET,
+            "/games/{game_id}/diff/{from_index}/{to_index}",
+            snapshot_diff::snapshot_diff_handler,
+        )
+        .route(Method::POST, "/games/{game_id}/surgery", surgery::surgery_handler)
+        .route(Method::GET, "/status/announcements", status_page::list_announcements_handler)
+        .route(Method::POST, "/status/announcements", status_page::post_announcement_handler)
+        .route(
+            Method::DELETE,
+            "/status/announcements/{id}",
+            status_page::delete_announcement_handler,
+        )
+        .route(Method::GET, "/profiling/timings", profiling::get_timings_handler)
+        .route(Method::GET, "/profiling/flamegraph", profiling::flamegraph_handler)
+        .route(
+            Method::GET,
+            "/telemetry/client-errors",
+            client_telemetry::client_error_summary_handler,
+        )
+}
+
+/**
+ * Creates the services clients report to under the "/telemetry" path:
+ *
+ * - Client Errors:
+ *   - Reports a crash or a desync (a ClientErrorReport).  A desync names the game and the GameIndex the client is at,
+ *     and a client that is behind is sent the current game.  Crashes are sampled (see client_telemetry.rs).
+ *   - URL: `https://localhost:8080/auth/api/v1/telemetry/client-errors`
+ *   - Method: `POST`
+ */
+fn telemetry_service() -> Routes {
+    Routes::new("/telemetry", Some(Role::User)).route(
+        Method::POST,
+        "/client-errors",
+        client_telemetry::report_client_error_handler,
+    )
+}
+
+fn longpoll_service() -> Routes {
+    Routes::new("/longpoll/{index}", Some(Role::User)).route(Method::GET, "", long_poll_handler)
+}
+
+/**
+ * - WebSocket:
+ *   - Upgrades to a WebSocket that pushes the caller's messages as they are sent -- the same ChannelMessages the long
+ *     poller returns, one JSON text frame each.  The service pings every 5 seconds and closes the socket when the
+ *     client stops answering, or when the login expires or is ended.
+ *   - URL: `wss://localhost:8080/auth/api/v1/ws`
+ *   - Method: `GET`
+ */
+fn websocket_service() -> Routes {
+    Routes::new("/ws", Some(Role::User)).route(Method::GET, "", websocket::websocket_handler)
+}
+
+/**
+ * - Events:
+ *   - Streams the caller's messages as Server-Sent Events, for clients that can't use a WebSocket.  Each event's id
+ *     is the last sequence number sent on each channel; send it back as Last-Event-ID when reconnecting to get what
+ *     was missed first.
+ *   - URL: `https://localhost:8080/auth/api/v1/events`
+ *   - Method: `GET`
+ */
+fn events_service() -> Routes {
+    Routes::new("/events", Some(Role::User)).route(Method::GET, "", sse::events_handler)
+}
+
+fn profile_service() -> Routes {
+    Routes::new("/profile", Some(Role::User)).route(
+        Method::GET,
+        "/{email}",
+        user_handlers::get_profile_handler,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        create_test_service,
+        games_service::game_container::game_messages::GameHeader,
+        init_env_logger,
+        middleware::{request_context_mw::TestContext, security_context::SecurityContext},
+        shared::service_models::Claims,
+    };
+    use actix_web::test;
+    use futures::FutureExt;
+    use std::{collections::HashSet, panic::AssertUnwindSafe, time::Duration};
+
+    /// long enough for any handler to answer a request it can't do anything with -- except a long poll, which waits
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// the path with something made up for each of its parameters
+    fn probe_path(path: &str) -> String {
+        path.split('/')
+            .map(|segment| if segment.starts_with('{') { "probe" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn is_no_route(status: StatusCode, body: &[u8]) -> bool {
+        status == StatusCode::NOT_FOUND
+            && serde_json::from_slice::<ServiceResponse>(body).map_or(false, |sr| sr.message == NO_ROUTE)
+    }
+
+    #[test]
+    fn test_match_path() {
+        let pattern = "/auth/api/v1/action/trade/accept/{game_id}/{offer_id}";
+        let params = match_path(pattern, "/auth/api/v1/action/trade/accept/g/o").expect("the path matches");
+        assert_eq!(params.get("game_id").map(String::as_str), Some("g"));
+        assert_eq!(params.get("offer_id").map(String::as_str), Some("o"));
+        assert!(match_path("/auth/api/v1/action/roll/{game_id}", "/auth/api/v1/action/roll/").is_none());
+        assert!(match_path("/auth/api/v1/action/roll/{game_id}", "/auth/api/v1/action/roll/g/more").is_none());
+        assert!(match_path("/auth/api/v1/action/roll/{game_id}", "/auth/api/v1/action/build/g").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_manifest_matches_app() {
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
+        let app = create_test_service!();
+        let manifest = manifest();
+
+        // a route registered twice never gets to its second handler
+        let mut seen = HashSet::new();
+        for route in &manifest {
+            assert!(
+                seen.insert((route.method.clone(), route.path.clone())),
+                "{} {} is in the manifest twice",
+                route.method,
+                route.path
+            );
+        }
+
+        // a user who isn't an admin, against the mocked database
+        let claims = Claims::new("routes-test", "routes-test@example.com", 60, &vec![Role::User], &None);
+        let token = SecurityContext::cached_secrets()
+            .login_keys
+            .sign_claims(&claims)
+            .expect("signing the claims should work");
+        let test_context = serde_json::to_string(&TestContext::new(false, None)).unwrap();
+
+        for route in &manifest {
+            let mut request = test::TestRequest::default()
+                .method(route.method.clone())
+                .uri(&probe_path(&route.path))
+                .append_header((GameHeader::TEST, test_context.clone()));
+            if route.role.is_some() {
+                request = request.append_header(("Authorization", format!("Bearer {}", token)));
+            }
+            let call = AssertUnwindSafe(test::call_service(&app, request.to_request())).catch_unwind();
+            let response = match tokio::time::timeout(PROBE_TIMEOUT, call).await {
+                Ok(Ok(response)) => response,
+                // a handler that is still waiting, or that gave up on what it was sent, was still reached
+                Ok(Err(_)) | Err(_) => continue,
+            };
+            let status = response.status();
+            if status == StatusCode::NOT_FOUND {
+                // only read the 404s: an event stream's body never ends
+                let body = test::read_body(response).await;
+                assert!(!is_no_route(status, &body), "{} {} isn't served", route.method, route.path);
+            }
+            if route.role == Some(Role::Admin) {
+                assert!(!status.is_success(), "{} {} let a user who isn't an admin in", route.method, route.path);
+            }
+        }
+
+        // and nothing that isn't in the manifest is
+        for path in &["/api/v1/no-such-route", "/no-such-route", "/api/v1/version/no-such-route"] {
+            let request = test::TestRequest::get().uri(path).to_request();
+            let response = test::call_service(&app, request).await;
+            let status = response.status();
+            let body = test::read_body(response).await;
+            assert!(is_no_route(status, &body), "{} is served", path);
+        }
+        let request = test::TestRequest::post().uri("/api/v1/version").to_request();
+        let response = test::call_service(&app, request).await;
+        let status = response.status();
+        assert!(is_no_route(status, &test::read_body(response).await));
+    }
+}
@@ -0,0 +1,343 @@
+#![allow(dead_code)]
+/**
+ *  the analytics export writes CSV extracts for BI tools, so that analysts can work from files instead of being given
+ *  access to Cosmos.  every run writes one folder, named for when it ran, with three files:
+ *
+ *  1. match_history.csv: one row per finished game (see PersistGame)
+ *  2. user_aggregates.csv: one row per user with their games played and won.  no PII -- no email or phone number
+ *  3. daily_active_users.csv: one row per day with the number of users that made a request (see usage_tracker.rs).
+ *     that is kept in memory, so it only goes back to when the service last started
+ *
+ *  the export runs every EXPORT_INTERVAL when ANALYTICS_EXPORT_DIR is set (point it at a mounted blob storage
+ *  container to land the files in blob storage), and an admin can run it on demand.  only CSV is written -- Parquet
+ *  needs a writer we don't have as a dependency yet.
+ *
+ *  the games and users are streamed from the database and only their rows are kept, so a big collection doesn't have
+ *  to fit in memory as documents (a finished game carries its whole board and history).
+ */
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::HttpResponse;
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    middleware::{
+        request_context_mw::RequestContext,
+        security_context::SecurityContext,
+        service_config::SERVICE_CONFIG,
+        usage_tracker::{self, DailyActiveUsers},
+    },
+    new_unauthorized_response,
+    shared::{
+        error_reporting,
+        service_models::{PersistGame, PersistUser, Role},
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+/// how often the scheduled export runs
+pub const EXPORT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExportedFile {
+    pub name: String,
+    pub rows: usize, // not counting the header
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExportReport {
+    pub folder: String,
+    pub exported_at: u64, // seconds since the epoch
+    pub files: Vec<ExportedFile>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// seconds since the epoch as an ISO 8601 UTC timestamp
+fn timestamp(seconds: u64) -> String {
+    chrono::NaiveDateTime::from_timestamp_opt(seconds as i64, 0)
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_default()
+}
+
+/// quote a field if it has anything in it that would break the row.  a field that starts with =, +, - or @ gets a ' in
+/// front so a spreadsheet shows it instead of running it as a formula (a display name like "=HYPERLINK(...)")
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_owned()
+    };
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn csv(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut text = header.join(",");
+    text.push('\n');
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        text.push_str(&fields.join(","));
+        text.push('\n');
+    }
+    text
+}
+
+/// the game's row in match_history.csv, with when it finished to sort by
+fn match_history_row(game: &PersistGame) -> (u64, Vec<String>) {
+    let mut player_ids = game.player_ids.clone();
+    player_ids.sort();
+    let row = vec![
+        game.id.clone(),
+        timestamp(game.finished_at),
+        format!("{:?}", game.game.game_type),
+        game.player_ids.len().to_string(),
+        game.winner_id.clone().unwrap_or_default(),
+        player_ids.join(";"),
+    ];
+    (game.finished_at, row)
+}
+
+/// the rows in the order the games finished (then by id, the first field)
+fn match_history_from_rows(mut rows: Vec<(u64, Vec<String>)>) -> String {
+    rows.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1[0].cmp(&b.1[0])));
+    let rows: Vec<Vec<String>> = rows.into_iter().map(|(_, row)| row).collect();
+    csv(
+        &["GameId", "FinishedAt", "GameType", "PlayerCount", "WinnerId", "PlayerIds"],
+        &rows,
+    )
+}
+
+pub fn match_history_csv(games: &[PersistGame]) -> String {
+    match_history_from_rows(games.iter().map(match_history_row).collect())
+}
+
+fn user_aggregates_row(user: &PersistUser) -> Vec<String> {
+    let profile = &user.user_profile;
+    vec![
+        user.id.clone(),
+        profile.display_name.clone(),
+        format!("{:?}", profile.user_type),
+        profile.games_played.unwrap_or(0).to_string(),
+        profile.games_won.unwrap_or(0).to_string(),
+    ]
+}
+
+/// the rows in user id (the first field) order
+fn user_aggregates_from_rows(mut rows: Vec<Vec<String>>) -> String {
+    rows.sort_by(|a, b| a[0].cmp(&b[0]));
+    csv(
+        &["UserId", "DisplayName", "UserType", "GamesPlayed", "GamesWon"],
+        &rows,
+    )
+}
+
+pub fn user_aggregates_csv(users: &[PersistUser]) -> String {
+    user_aggregates_from_rows(users.iter().map(user_aggregates_row).collect())
+}
+
+pub fn daily_active_users_csv(days: &[DailyActiveUsers]) -> String {
+    let rows: Vec<Vec<String>> = days
+        .iter()
+        .map(|day| {
+            vec![
+                timestamp(day.day * 24 * 60 * 60)
+                    .get(..10)
+                    .unwrap_or_default()
+                    .to_owned(),
+                day.user_count.to_string(),
+            ]
+        })
+        .collect();
+    csv(&["Day", "ActiveUsers"], &rows)
+}
+
+fn write_file(folder: &Path, name: &str, contents: &str) -> Result<(), ServiceResponse> {
+    fs::write(folder.join(name), contents).map_err(|e| {
+        ServiceResponse::new(
+            &format!("failed to write {}", name),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ResponseType::ErrorInfo(e.to_string()),
+            GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+        )
+    })
+}
+
+/// Runs the export into a new folder under export_dir, reading from the request context's database.
+pub async fn export_to(
+    export_dir: &str,
+    request_context: &RequestContext,
+) -> Result<ExportReport, ServiceResponse> {
+    let mut game_rows = Vec::new();
+    let mut games = request_context.database.stream_games(0);
+    while let Some(game) = games.next().await {
+        game_rows.push(match_history_row(&game?));
+    }
+    let mut user_rows = Vec::new();
+    let mut users = request_context.database.stream_users();
+    while let Some(user) = users.next().await {
+        user_rows.push(user_aggregates_row(&user?));
+    }
+    let days = usage_tracker::daily_active_users();
+
+    let exported_at = now();
+    let folder: PathBuf = Path::new(export_dir).join(timestamp(exported_at).replace(':', "-"));
+    fs::create_dir_all(&folder).map_err(|e| {
+        ServiceResponse::new(
+            &format!("failed to create {}", folder.display()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ResponseType::ErrorInfo(e.to_string()),
+            GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+        )
+    })?;
+
+    let files = vec![
+        ("match_history.csv", match_history_from_rows(game_rows.clone()), game_rows.len()),
+        ("user_aggregates.csv", user_aggregates_from_rows(user_rows.clone()), user_rows.len()),
+        ("daily_active_users.csv", daily_active_users_csv(&days), days.len()),
+    ];
+    let mut report = ExportReport {
+        folder: folder.display().to_string(),
+        exported_at,
+        files: vec![],
+    };
+    for (name, contents, rows) in files {
+        write_file(&folder, name, &contents)?;
+        report.files.push(ExportedFile {
+            name: name.to_owned(),
+            rows,
+        });
+    }
+    tracing::info!("analytics export written to {}", report.folder);
+    Ok(report)
+}
+
+/**
+ *  run the export against the production database every interval, for the life of the service.  a failed run is
+ *  reported and the next one is tried on schedule
+ */
+pub fn start_schedule(export_dir: String, interval: Duration) -> tokio::task::JoinHandle<()> {
+    actix_web::rt::spawn(async move {
+        let request_context = RequestContext::new(
+            &None,
+            &None,
+            &SERVICE_CONFIG,
+            &SecurityContext::cached_secrets(),
+        );
+        loop {
+            if let Err(e) = export_to(&export_dir, &request_context).await {
+                let message = format!("the analytics export failed: {:#?}", e);
+                tracing::error!("{}", message);
+                error_reporting::report_background_failure("analytics_export", &message);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
+
+/// run the export now -- admins and internal services only, and only if ANALYTICS_EXPORT_DIR is set
+pub async fn run_export(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) && !request_context.is_service_call() {
+        return new_unauthorized_response!("");
+    }
+    let export_dir = request_context.config.analytics_export_dir.clone().ok_or_else(|| {
+        ServiceResponse::new(
+            "the analytics export is not configured",
+            StatusCode::NOT_FOUND,
+            ResponseType::ErrorInfo("set ANALYTICS_EXPORT_DIR to turn it on".to_owned()),
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        )
+    })?;
+    let report = export_to(&export_dir, request_context).await?;
+    Ok(ServiceResponse::new(
+        "exported",
+        StatusCode::OK,
+        ResponseType::AnalyticsExport(report),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn run_export_handler(request_context: RequestContext) -> HttpResponse {
+    run_export(&request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::catan_games::games::regular::regular_game::RegularGame,
+        shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("@sum(a1)"), "'@sum(a1)");
+        assert_eq!(csv_field("-2, +3"), "\"'-2, +3\"");
+        assert_eq!(csv_field("a-b"), "a-b");
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+
+        let creator = UserProfile::new_test_user(Some("1".to_string()));
+        let mut game = PersistGame::from_game(&RegularGame::new(&creator), Some("1".to_string()));
+        game.finished_at = 86_400;
+        let history = match_history_csv(&[game.clone()]);
+        let lines: Vec<&str> = history.lines().collect();
+        assert_eq!(lines[0], "GameId,FinishedAt,GameType,PlayerCount,WinnerId,PlayerIds");
+        assert_eq!(lines[1], format!("{},1970-01-02T00:00:00Z,Regular,1,1,1", game.id));
+
+        let days = daily_active_users_csv(&[DailyActiveUsers {
+            day: 1,
+            user_count: 3,
+        }]);
+        assert_eq!(days, "Day,ActiveUsers\n1970-01-02,3\n");
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let mut request_context = RequestContext::test_default(false);
+        let export_dir = std::env::temp_dir().join(format!("catan-export-{}", PersistUser::new_id()));
+        let export_dir = export_dir.display().to_string();
+
+        // only admins can run it
+        request_context.config.analytics_export_dir = Some(export_dir.clone());
+        let sr = run_export(&request_context).await.expect_err("not an admin");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+
+        let report = export_to(&export_dir, &request_context)
+            .await
+            .expect("the mocked db should export");
+        let names: Vec<&str> = report.files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["match_history.csv", "user_aggregates.csv", "daily_active_users.csv"]
+        );
+        for file in &report.files {
+            let text = fs::read_to_string(Path::new(&report.folder).join(&file.name))
+                .expect("every file is written");
+            assert_eq!(text.lines().count(), file.rows + 1);
+        }
+        let _ = fs::remove_dir_all(&export_dir);
+    }
+}
@@ -0,0 +1,223 @@
+#![allow(dead_code)]
+/**
+ *  who the service says it is.  a community hosting its own copy sets the name, support email, logo and terms link in
+ *  the environment (BRAND_SERVICE_NAME, BRAND_SUPPORT_EMAIL, BRAND_LOGO_URL and BRAND_TERMS_URL) and everything the
+ *  service sends to people -- the emails, the text messages, the page a validation link opens -- is built here from
+ *  them, so nothing says "Catan Service" unless the deployment does.  the info api returns the branding so clients can
+ *  match it.
+ */
+use serde::{Deserialize, Serialize};
+
+/// the name used when the deployment doesn't set one
+pub const DEFAULT_SERVICE_NAME: &str = "Catan Service";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Branding {
+    pub service_name: String,
+    pub support_email: Option<String>,
+    pub logo_url: Option<String>,
+    pub terms_url: Option<String>,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            service_name: DEFAULT_SERVICE_NAME.to_owned(),
+            support_email: None,
+            logo_url: None,
+            terms_url: None,
+        }
+    }
+}
+
+/// the html escapes for text we put in a page
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Branding {
+    pub fn from_env() -> Self {
+        let set = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        Self {
+            service_name: set("BRAND_SERVICE_NAME").unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_owned()),
+            support_email: set("BRAND_SUPPORT_EMAIL"),
+            logo_url: set("BRAND_LOGO_URL"),
+            terms_url: set("BRAND_TERMS_URL"),
+        }
+    }
+
+    /// the lines at the bottom of every email: where to get help, and the terms
+    fn footer(&self) -> String {
+        let mut footer = format!("\n\n-- {}", self.service_name);
+        if let Some(email) = &self.support_email {
+            footer.push_str(&format!("\nQuestions? Write to {}", email));
+        }
+        if let Some(terms) = &self.terms_url {
+            footer.push_str(&format!("\nTerms of service: {}", terms));
+        }
+        footer
+    }
+
+    /// the subject and body of the email with the link that validates a new user's email
+    pub fn validation_email(&self, url: &str) -> (String, String) {
+        (
+            format!("Please validate your email for {}", self.service_name),
+            format!(
+                "Thank you for registering with {}.\n\n\
+                 Click on this link to validate your email: {}\n\n\
+                 If you did not register, you can ignore this email.{}",
+                self.service_name,
+                url,
+                self.footer()
+            ),
+        )
+    }
+
+    /// the subject and body of the email with the link that sets the password of an account that was imported
+    pub fn password_reset_email(&self, url: &str) -> (String, String) {
+        (
+            format!("Set your {} password", self.service_name),
+            format!(
+                "Your account has moved to {}.  Before you can log in, you need to set a password.\n\n\
+                 Use this link to set it: {}\n\n\
+                 The link works for a day.  If it has expired, log in again to get a new one.{}",
+                self.service_name,
+                url,
+                self.footer()
+            ),
+        )
+    }
+
+    /// the subject and body of the email with the link that confirms a new address, sent to the new address
+    pub fn email_change_email(&self, url: &str) -> (String, String) {
+        (
+            format!("Confirm your new email for {}", self.service_name),
+            format!(
+                "Somebody asked to use this address for their {} account.\n\n\
+                 Click on this link to confirm it: {}\n\n\
+                 The link works for a day.  Until it is used, the account keeps its old address.  If you didn't ask \
+                 for this, you can ignore this email.{}",
+                self.service_name,
+                url,
+                self.footer()
+            ),
+        )
+    }
+
+    /// the subject and body of the email sent to the old address once a new one is confirmed
+    pub fn email_changed_email(&self, new_email: &str) -> (String, String) {
+        (
+            format!("Your {} email has changed", self.service_name),
+            format!(
+                "Your account now uses {}, and this address won't get any more mail from us.\n\n\
+                 If you didn't make this change, somebody else may have your password -- contact us.{}",
+                new_email,
+                self.footer()
+            ),
+        )
+    }
+
+    /// the subject and body of the email sent when too many failed logins lock an account
+    pub fn account_locked_email(&self, minutes: u64) -> (String, String) {
+        (
+            format!("Your {} account has been locked", self.service_name),
+            format!(
+                "There were too many failed attempts to log in to your account, so it has been locked for {} minutes.\n\n\
+                 If that was you, wait and try again.  If it wasn't, somebody may be trying to guess your password -- \
+                 a longer, unique password will keep them out.{}",
+                minutes,
+                self.footer()
+            ),
+        )
+    }
+
+    /// the subject and body of the email sent to a player who wasn't connected when their game ended
+    pub fn game_ended_email(&self, game_id: &str) -> (String, String) {
+        (
+            format!("Your {} game has ended", self.service_name),
+            format!(
+                "The game you were playing ({}) has ended.  Log in to see the final board.{}",
+                game_id,
+                self.footer()
+            ),
+        )
+    }
+
+    /// the text message with the code that validates a phone number
+    pub fn phone_code_text(&self, code: i32) -> String {
+        format!(
+            "This is your 6 digit code for {}. If you did not request this code, ignore this message. code: {}",
+            self.service_name, code
+        )
+    }
+
+    /// the page a validation link opens in a browser
+    pub fn validation_page(&self, validated: bool) -> String {
+        let name = escape_html(&self.service_name);
+        let message = if validated {
+            "Your email has been validated.  You can close this page."
+        } else {
+            "This link has expired or isn't valid.  Ask for a new one from the app."
+        };
+        let logo = self.logo_url.as_ref().map_or(String::new(), |url| {
+            format!("<img src=\"{}\" alt=\"{}\">", escape_html(url), name)
+        });
+        let mut links = String::new();
+        if let Some(email) = &self.support_email {
+            let email = escape_html(email);
+            links.push_str(&format!("<p>Questions? <a href=\"mailto:{}\">{}</a></p>", email, email));
+        }
+        if let Some(terms) = &self.terms_url {
+            links.push_str(&format!("<p><a href=\"{}\">Terms of service</a></p>", escape_html(terms)));
+        }
+        format!(
+            "<html><head><title>{}</title></head><body>{}<h1>{}</h1><p>{}</p>{}</body></html>",
+            name, logo, name, message, links
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branding_templates() {
+        let default = Branding::default();
+        let (subject, body) = default.validation_email("https://host/validate");
+        assert!(subject.contains(DEFAULT_SERVICE_NAME));
+        assert!(body.contains("https://host/validate"));
+        assert!(!body.contains("Questions?"));
+
+        let branding = Branding {
+            service_name: "Settlers of <Maple> Street".to_owned(),
+            support_email: Some("help@maple.example".to_owned()),
+            logo_url: Some("https://maple.example/logo.png".to_owned()),
+            terms_url: Some("https://maple.example/terms".to_owned()),
+        };
+        let (subject, body) = branding.game_ended_email("game-1");
+        assert_eq!(subject, "Your Settlers of <Maple> Street game has ended");
+        assert!(body.contains("game-1"));
+        assert!(body.contains("help@maple.example"));
+        assert!(body.contains("https://maple.example/terms"));
+        assert!(!body.contains(DEFAULT_SERVICE_NAME));
+        assert!(branding.phone_code_text(123456).contains("Maple"));
+        let (subject, body) = branding.password_reset_email("https://host/reset");
+        assert_eq!(subject, "Set your Settlers of <Maple> Street password");
+        assert!(body.contains("https://host/reset"));
+        let (subject, body) = branding.email_change_email("https://host/change");
+        assert_eq!(subject, "Confirm your new email for Settlers of <Maple> Street");
+        assert!(body.contains("https://host/change"));
+        assert!(branding.email_changed_email("new@maple.example").1.contains("new@maple.example"));
+
+        let page = branding.validation_page(true);
+        assert!(page.contains("Settlers of &lt;Maple&gt; Street"));
+        assert!(page.contains("https://maple.example/logo.png"));
+        assert!(page.contains("validated"));
+        assert!(branding.validation_page(false).contains("expired"));
+    }
+}
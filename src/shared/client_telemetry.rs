@@ -0,0 +1,377 @@
+#![allow(dead_code)]
+/**
+ *  errors the clients report: crashes (of the web client or a native one) and desyncs -- a client that finds its game
+ *  doesn't match what it should be.  clients POST a ClientErrorReport to /auth/api/v1/telemetry/client-errors, and
+ *  admins see the counts and the recent reports at GET /auth/api/v1/admin/telemetry/client-errors.
+ *
+ *  crashes are sampled: CLIENT_ERROR_SAMPLE_PERCENT (100 unless it is set) of them are kept, so a crash loop on a lot
+ *  of devices can't flood us.  every report is counted, kept or not.  desyncs are always kept, and are checked against
+ *  the game right away: the report records the game_index the service is at, and if the client is behind it is sent
+ *  the current game on the long poller so it can catch up.  a desync at the service's own game_index means the client
+ *  worked out a different game from the same changes, which is a bug somebody has to look at (see the snapshot diff
+ *  api).
+ *
+ *  the service reports desyncs too: a client echoes the checksum of its game on every action, and one that doesn't
+ *  match is kept as a ChecksumMismatch (see checksum.rs).  clients can't send those.
+ *
+ *  the reports are kept in memory -- the newest MAX_CLIENT_ERRORS of them -- so they start over when the service
+ *  restarts.
+ */
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::RwLock,
+};
+
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::{
+        game_container::{game_container::GameContainer, game_messages::CatanMessage},
+        long_poller::long_poller::LongPoller,
+    },
+    middleware::{request_context_mw::RequestContext, service_config::SERVICE_CONFIG},
+    new_unauthorized_response,
+    shared::{
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+/// how many reports are kept
+pub const MAX_CLIENT_ERRORS: usize = 1_000;
+
+/// the longest message and stack trace that are kept.  longer ones are cut
+pub const MAX_MESSAGE_LENGTH: usize = 1_000;
+pub const MAX_STACK_LENGTH: usize = 16 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientErrorKind {
+    Crash,
+    Desync,
+    ChecksumMismatch, // the service found it -- the checksum a client echoed wasn't the game's (see checksum.rs)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientPlatform {
+    Web,
+    Ios,
+    Android,
+    Desktop,
+    Unknown, // the service reported it, and the client didn't say
+}
+
+/// what a client sends
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClientErrorReport {
+    pub kind: ClientErrorKind,
+    pub platform: ClientPlatform,
+    pub client_version: String,
+    pub message: String,
+    #[serde(default)]
+    pub stack: Option<String>,
+    #[serde(default)]
+    pub game_id: Option<String>, // required for a desync
+    #[serde(default)]
+    pub game_index: Option<u32>, // the game_index the client is at.  required for a desync
+}
+
+/// how a desync compares with the game the service has
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DesyncVerdict {
+    ClientBehind,   // the client missed changes.  it was sent the current game
+    ClientAhead,    // the client has a game_index the service never made
+    SameIndex,      // the client worked out a different game from the same changes
+    GameNotRunning, // the game is over or was never here
+}
+
+/// what is kept
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct StoredClientError {
+    pub user_id: String,
+    pub received_at: u64, // seconds since the epoch
+    pub report: ClientErrorReport,
+    pub server_game_index: Option<u32>,
+    pub verdict: Option<DesyncVerdict>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClientErrorSummary {
+    pub reported: u64,
+    pub kept: u64,
+    pub crashes: u64,
+    pub desyncs: u64,
+    pub checksum_mismatches: u64,
+    pub by_version: Vec<(String, u64)>, // client version -> reports, most first
+    pub recent: Vec<StoredClientError>, // newest first
+}
+
+#[derive(Debug, Default)]
+struct ClientErrors {
+    reports: VecDeque<StoredClientError>,
+    reported: u64,
+    kept: u64,
+    crashes: u64,
+    desyncs: u64,
+    checksum_mismatches: u64,
+    by_version: HashMap<String, u64>,
+}
+
+lazy_static::lazy_static! {
+    static ref CLIENT_ERRORS: RwLock<ClientErrors> = RwLock::new(ClientErrors::default());
+}
+
+fn cut(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+/// the report, or what is wrong with it
+fn check_report(report: &ClientErrorReport) -> Result<ClientErrorReport, String> {
+    if report.kind == ClientErrorKind::ChecksumMismatch {
+        return Err("only the service reports checksum mismatches".to_owned());
+    }
+    if report.message.trim().is_empty() {
+        return Err("the report needs a message".to_owned());
+    }
+    if report.kind == ClientErrorKind::Desync && (report.game_id.is_none() || report.game_index.is_none()) {
+        return Err("a desync needs the GameId and GameIndex".to_owned());
+    }
+    let mut report = report.clone();
+    report.message = cut(report.message.trim(), MAX_MESSAGE_LENGTH);
+    report.stack = report.stack.map(|stack| cut(&stack, MAX_STACK_LENGTH));
+    report.client_version = cut(report.client_version.trim(), 64);
+    Ok(report)
+}
+
+pub fn verdict(client_index: u32, server_index: Option<u32>) -> DesyncVerdict {
+    match server_index {
+        None => DesyncVerdict::GameNotRunning,
+        Some(server_index) if client_index < server_index => DesyncVerdict::ClientBehind,
+        Some(server_index) if client_index > server_index => DesyncVerdict::ClientAhead,
+        Some(_) => DesyncVerdict::SameIndex,
+    }
+}
+
+/// count the report, and keep it if it is kept
+fn record(stored: StoredClientError, keep: bool) {
+    let mut errors = CLIENT_ERRORS.write().expect("the client error lock shouldn't be poisoned");
+    errors.reported += 1;
+    match stored.report.kind {
+        ClientErrorKind::Crash => errors.crashes += 1,
+        ClientErrorKind::Desync => errors.desyncs += 1,
+        ClientErrorKind::ChecksumMismatch => errors.checksum_mismatches += 1,
+    }
+    *errors.by_version.entry(stored.report.client_version.clone()).or_default() += 1;
+    if keep {
+        errors.kept += 1;
+        errors.reports.push_front(stored);
+        errors.reports.truncate(MAX_CLIENT_ERRORS);
+    }
+}
+
+/// the player echoed a checksum that isn't the game's at client_index.  always kept
+pub fn record_checksum_mismatch(
+    user_id: &str,
+    game_id: &str,
+    client_index: u32,
+    server_index: u32,
+    message: &str,
+    request_context: &RequestContext,
+) {
+    let stored = StoredClientError {
+        user_id: user_id.to_owned(),
+        received_at: request_context.environment.now(),
+        report: ClientErrorReport {
+            kind: ClientErrorKind::ChecksumMismatch,
+            platform: ClientPlatform::Unknown,
+            client_version: String::default(),
+            message: cut(message, MAX_MESSAGE_LENGTH),
+            stack: None,
+            game_id: Some(game_id.to_owned()),
+            game_index: Some(client_index),
+        },
+        server_game_index: Some(server_index),
+        verdict: Some(verdict(client_index, Some(server_index))),
+    };
+    record(stored, true);
+}
+
+/// take a report from the caller
+pub async fn report_client_error(
+    report: &ClientErrorReport,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let user_id = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call")
+        .id
+        .clone();
+    let report = check_report(report).map_err(|e| {
+        ServiceResponse::new(
+            "bad client error report",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(e.clone()),
+            GameError::BadActionData(e),
+        )
+    })?;
+
+    let mut stored = StoredClientError {
+        user_id: user_id.clone(),
+        received_at: request_context.environment.now(),
+        report: report.clone(),
+        server_game_index: None,
+        verdict: None,
+    };
+    let keep = match (&report.kind, &report.game_id, report.game_index) {
+        (ClientErrorKind::Desync, Some(game_id), Some(client_index)) => {
+            let current = GameContainer::current_game(game_id).await.ok().map(|(game, _)| game);
+            stored.server_game_index = current.as_ref().map(|game| game.game_index);
+            let verdict = verdict(client_index, stored.server_game_index);
+            stored.verdict = Some(verdict);
+            tracing::warn!(
+                "{} reported a desync in {} at {} ({:?}, the service is at {:?})",
+                user_id,
+                game_id,
+                client_index,
+                verdict,
+                stored.server_game_index
+            );
+            //  resync the client -- it only gets its own copy, redacted like every other, and nobody else's view changes
+            if let (DesyncVerdict::ClientBehind, Some(game)) = (verdict, current) {
+                if !game.players.contains_key(&user_id) {
+                    return Err(ServiceResponse::new(
+                        &format!("{} isn't playing in {}", user_id, game_id),
+                        StatusCode::FORBIDDEN,
+                        ResponseType::NoData,
+                        GameError::HttpError(StatusCode::FORBIDDEN),
+                    ));
+                }
+                let update = CatanMessage::GameUpdate(game.redacted_for(&user_id));
+                if let Err(e) = LongPoller::send_message(vec![user_id.clone()], &update).await {
+                    tracing::warn!("failed to resync {} in {}: {:#?}", user_id, game_id, e);
+                }
+            }
+            true
+        }
+        _ => request_context.environment.random_below(100) < SERVICE_CONFIG.client_error_sample_percent,
+    };
+    let response = ServiceResponse::new(
+        if keep { "recorded" } else { "counted" },
+        StatusCode::ACCEPTED,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    );
+    record(stored, keep);
+    Ok(response)
+}
+
+/// the counts and the recent reports.  admins only
+pub fn client_error_summary(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    let errors = CLIENT_ERRORS.read().expect("the client error lock shouldn't be poisoned");
+    let mut by_version: Vec<(String, u64)> = errors
+        .by_version
+        .iter()
+        .map(|(version, count)| (version.clone(), *count))
+        .collect();
+    by_version.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::ClientErrors(ClientErrorSummary {
+            reported: errors.reported,
+            kept: errors.kept,
+            crashes: errors.crashes,
+            desyncs: errors.desyncs,
+            checksum_mismatches: errors.checksum_mismatches,
+            by_version,
+            recent: errors.reports.iter().cloned().collect(),
+        }),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn report_client_error_handler(
+    report: web::Json<ClientErrorReport>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    report_client_error(&report, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn client_error_summary_handler(request_context: RequestContext) -> HttpResponse {
+    client_error_summary(&request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::service_models::Claims;
+
+    #[test]
+    fn test_check_report() {
+        let mut report = ClientErrorReport {
+            kind: ClientErrorKind::Desync,
+            platform: ClientPlatform::Web,
+            client_version: "1.2.3".to_owned(),
+            message: "  the board doesn't match  ".to_owned(),
+            stack: Some("x".repeat(MAX_STACK_LENGTH + 10)),
+            game_id: Some("game".to_owned()),
+            game_index: None,
+        };
+        assert!(check_report(&report).is_err());
+        report.game_index = Some(4);
+        let checked = check_report(&report).expect("a complete desync");
+        assert_eq!(checked.message, "the board doesn't match");
+        assert_eq!(checked.stack.map(|stack| stack.len()), Some(MAX_STACK_LENGTH));
+        report.kind = ClientErrorKind::ChecksumMismatch;
+        assert!(check_report(&report).is_err());
+
+        assert_eq!(verdict(3, Some(5)), DesyncVerdict::ClientBehind);
+        assert_eq!(verdict(5, Some(5)), DesyncVerdict::SameIndex);
+        assert_eq!(verdict(6, Some(5)), DesyncVerdict::ClientAhead);
+        assert_eq!(verdict(6, None), DesyncVerdict::GameNotRunning);
+    }
+
+    #[tokio::test]
+    async fn test_report_client_error() {
+        let mut request_context = RequestContext::test_default(false);
+        request_context.set_claims(&Claims::new("reporter", "reporter@example.com", 60, &vec![Role::User], &None));
+        let report = ClientErrorReport {
+            kind: ClientErrorKind::Desync,
+            platform: ClientPlatform::Ios,
+            client_version: "telemetry-test".to_owned(),
+            message: "desync".to_owned(),
+            stack: None,
+            game_id: Some("no-such-game".to_owned()),
+            game_index: Some(3),
+        };
+        let sr = report_client_error(&report, &request_context).await.expect("a good report");
+        assert_eq!(sr.status, StatusCode::ACCEPTED);
+        assert!(client_error_summary(&request_context).is_err());
+
+        request_context.set_claims(&Claims::new("admin", "admin@example.com", 60, &vec![Role::Admin], &None));
+        let summary = match client_error_summary(&request_context).expect("an admin").response_type {
+            ResponseType::ClientErrors(summary) => summary,
+            other => panic!("expected ClientErrors, got {:?}", other),
+        };
+        let stored = summary
+            .recent
+            .iter()
+            .find(|stored| stored.report.client_version == "telemetry-test")
+            .expect("desyncs are always kept");
+        assert_eq!(stored.verdict, Some(DesyncVerdict::GameNotRunning));
+        assert_eq!(stored.server_game_index, None);
+    }
+}
@@ -0,0 +1,124 @@
+#![allow(dead_code)]
+/**
+ *  the ids, time and randomness the services use, behind a trait so that tests can make them predictable.  every
+ *  RequestContext carries one: ProductionEnvironment normally, and a TestEnvironment when the test context asks for
+ *  one with a seed.  with the same seed the dice, codes and other random choices come out the same on every run, the
+ *  ids count up ("test-00000001"...) and the clock only moves when the test advances it.
+ *
+ *  anything that runs for a request, or for a timer a request started, takes the time from here -- the database
+ *  calls that filter out what has expired are told the time rather than reading the clock.  some things still use
+ *  the real clock and thread_rng on purpose: token expiry, which is checked by jsonwebtoken against the real time, the
+ *  game model and its container (the board shuffle, stealing a card, when an event was logged), which don't see a
+ *  RequestContext -- tests that need a known board pass in a test game instead -- and the service's own jobs that
+ *  don't run for any request (presence, usage, the status page, keeping messages in the outbox).
+ */
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use uuid::Uuid;
+
+pub trait Environment: Send + Sync {
+    /// a new unique id
+    fn new_id(&self) -> String;
+    /// seconds since the UNIX epoch
+    fn now(&self) -> u64;
+    /// a random number in 0..upper.  upper has to be more than 0
+    fn random_below(&self, upper: u32) -> u32;
+
+    /// the total of two six sided dice
+    fn roll_dice(&self) -> u32 {
+        self.random_below(6) + 1 + self.random_below(6) + 1
+    }
+}
+
+fn system_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub struct ProductionEnvironment;
+
+impl Environment for ProductionEnvironment {
+    fn new_id(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+
+    fn now(&self) -> u64 {
+        system_now()
+    }
+
+    fn random_below(&self, upper: u32) -> u32 {
+        rand::thread_rng().gen_range(0..upper)
+    }
+}
+
+lazy_static::lazy_static! {
+    // shared by every TestEnvironment, so that ids stay unique across the requests (and tests) in a process
+    static ref NEXT_TEST_ID: AtomicU64 = AtomicU64::new(1);
+}
+
+pub struct TestEnvironment {
+    rng: Mutex<StdRng>,
+    now: AtomicU64,
+}
+
+impl TestEnvironment {
+    /// the clock starts at the real time, so that what the test makes isn't already out of date
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            now: AtomicU64::new(system_now()),
+        }
+    }
+
+    /// move the clock forward
+    pub fn advance(&self, seconds: u64) {
+        self.now.fetch_add(seconds, Ordering::SeqCst);
+    }
+}
+
+impl Environment for TestEnvironment {
+    fn new_id(&self) -> String {
+        format!("test-{:08}", NEXT_TEST_ID.fetch_add(1, Ordering::SeqCst))
+    }
+
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+
+    fn random_below(&self, upper: u32) -> u32 {
+        self.rng
+            .lock()
+            .expect("the test rng lock shouldn't be poisoned")
+            .gen_range(0..upper)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_is_repeatable() {
+        let first = TestEnvironment::new(42);
+        let second = TestEnvironment::new(42);
+        let rolls: Vec<u32> = (0..20).map(|_| first.roll_dice()).collect();
+        assert_eq!(rolls, (0..20).map(|_| second.roll_dice()).collect::<Vec<u32>>());
+        assert!(rolls.iter().all(|roll| (2..=12).contains(roll)));
+
+        assert_ne!(first.new_id(), second.new_id());
+
+        let start = first.now();
+        assert_eq!(first.now(), start);
+        first.advance(90);
+        assert_eq!(first.now(), start + 90);
+    }
+}
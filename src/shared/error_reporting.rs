@@ -0,0 +1,145 @@
+#![allow(dead_code)]
+/**
+ *  optional error reporting to Sentry.  it is off unless SENTRY_DSN is set, and when it is off every function in here
+ *  is a no-op.
+ *
+ *  what gets reported:
+ *  1. panics (Sentry installs a panic hook when it is initialized)
+ *  2. any ServiceResponse with a 5xx status that is turned into an HttpResponse
+ *  3. background jobs that fail (game over cleanup, the database health check)
+ *  4. games quarantined because they broke one of the game invariants
+ *
+ *  every request runs with its own Hub (see request_context_mw), tagged with the route and correlation id.  auth_mw
+ *  adds the caller -- as a hash of the user id, never the id itself.  the release is the crate name and version.
+ */
+use std::sync::Arc;
+
+use sentry::{protocol::User, Hub, Level};
+use sha2::{Digest, Sha256};
+
+use super::shared_models::ServiceResponse;
+
+pub const RELEASE: &str = concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION"));
+
+/**
+ *  start reporting if a DSN is configured.  the guard flushes queued events when it is dropped, so main() has to hold
+ *  on to it for the life of the service.
+ */
+pub fn init_error_reporting(dsn: Option<&str>) -> Option<sentry::ClientInitGuard> {
+    let dsn = dsn.filter(|dsn| !dsn.is_empty())?;
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: Some(RELEASE.into()),
+            ..Default::default()
+        },
+    ));
+    if guard.is_enabled() {
+        tracing::info!("error reporting enabled for release {}", RELEASE);
+        Some(guard)
+    } else {
+        tracing::warn!("SENTRY_DSN is set but isn't a valid DSN. error reporting is off");
+        None
+    }
+}
+
+pub fn is_enabled() -> bool {
+    Hub::current()
+        .client()
+        .map_or(false, |client| client.is_enabled())
+}
+
+/// the user id as it is sent to Sentry -- enough to tell users apart, but not to identify them
+pub fn hash_user_id(user_id: &str) -> String {
+    Sha256::digest(user_id.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// a hub for one request, so that its tags don't leak into other requests running on the same thread
+pub fn request_hub(route: &str, correlation_id: &str) -> Arc<Hub> {
+    let hub = Arc::new(Hub::new_from_top(Hub::current()));
+    hub.configure_scope(|scope| {
+        scope.set_tag("route", route);
+        scope.set_tag("correlation_id", correlation_id);
+    });
+    hub
+}
+
+/// tag everything reported for the rest of the request with the (hashed) caller
+pub fn set_user(user_id: &str) {
+    sentry::configure_scope(|scope| {
+        scope.set_user(Some(User {
+            id: Some(hash_user_id(user_id)),
+            ..Default::default()
+        }))
+    });
+}
+
+pub fn report_service_error(response: &ServiceResponse) {
+    if !response.status.is_server_error() || !is_enabled() {
+        return;
+    }
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("status", response.status.as_u16());
+            scope.set_extra("game_error", format!("{:?}", response.game_error).into());
+        },
+        || sentry::capture_message(&response.message, Level::Error),
+    );
+}
+
+pub fn report_background_failure(job: &str, error: &str) {
+    if !is_enabled() {
+        return;
+    }
+    sentry::with_scope(
+        |scope| scope.set_tag("job", job),
+        || sentry::capture_message(&format!("{} failed: {}", job, error), Level::Error),
+    );
+}
+
+/// a corrupted game is a bug that somebody has to look at, so it is reported as fatal
+pub fn report_quarantined_game(game_id: &str, violations: &[String]) {
+    if !is_enabled() {
+        return;
+    }
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("game_id", game_id);
+            scope.set_extra("violations", violations.join("\n").into());
+        },
+        || sentry::capture_message(&format!("game {} quarantined", game_id), Level::Fatal),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::shared_models::{GameError, ResponseType};
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_error_reporting_off() {
+        assert!(init_error_reporting(None).is_none());
+        assert!(init_error_reporting(Some("")).is_none());
+        assert!(!is_enabled());
+
+        // nothing to send to, so these have to be quiet no-ops
+        report_service_error(&ServiceResponse::new(
+            "boom",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+        ));
+        report_background_failure("test", "boom");
+        report_quarantined_game("game-1", &["boom".to_owned()]);
+
+        let hashed = hash_user_id("user-1");
+        assert_eq!(hashed, hash_user_id("user-1"));
+        assert_ne!(hashed, hash_user_id("user-2"));
+        assert!(!hashed.contains("user-1"));
+        assert_eq!(hashed.len(), 64);
+    }
+}
@@ -0,0 +1,305 @@
+#![allow(dead_code)]
+/**
+ *  the i18n catalog: every piece of text the service writes for the players in a game, in each language it speaks.
+ *
+ *  a game has a language, which its creator sets, and a player can override it for themselves (see
+ *  regular/language.rs).  the text is rendered for each player as the message is queued for them (see
+ *  LongPoller::send_to_channel), so everybody in a game gets the same message in their own language -- the turn
+ *  summaries, the game's system messages (a win, a new host, a player removed) and the push notifications.  the
+ *  structured fields are the same for everybody; only Text changes.
+ *
+ *  the game container keeps what it knows about each game's languages here (see sync_game), so rendering a message
+ *  doesn't have to ask the game.
+ */
+use std::{collections::HashMap, sync::RwLock};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::games_service::game_container::game_messages::CatanMessage;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, JsonSchema)]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::English
+    }
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [Language::English, Language::Spanish, Language::French, Language::German];
+
+    /// the ISO 639-1 code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+            Language::French => "fr",
+            Language::German => "de",
+        }
+    }
+
+    /// the language with the code -- "es", or "es-MX" for that matter
+    pub fn from_code(code: &str) -> Option<Self> {
+        let primary = code.trim().split(|c| c == '-' || c == '_').next()?.to_lowercase();
+        Self::ALL.iter().copied().find(|language| language.code() == primary)
+    }
+}
+
+/// everything in the catalog.  the templates fill in {0}, {1}... from the arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phrase {
+    TurnStartedTitle,
+    TurnStartedBody,
+    InvitationTitle,
+    InvitationBody, // {0}: who sent it
+    GameOverTitle,
+    GameOverBody,
+    TurnOver,       // {0}: whose turn it was
+    TurnBuilt,      // {0} roads, {1} settlements, {2} cities, {3} ships
+    GameWon,        // {0}: the winner, {1}: their victory points
+    HostChanged,    // {0}: the new host
+    PlayerRemoved,  // {0}: the player
+    PlayerBanned,   // {0}: the player
+    DigestTitle,
+    DigestBody, // {0}: how many games
+}
+
+fn template(phrase: Phrase, language: Language) -> &'static str {
+    use Language::*;
+    use Phrase::*;
+    match (phrase, language) {
+        (TurnStartedTitle, English) => "It's your turn",
+        (TurnStartedTitle, Spanish) => "Es tu turno",
+        (TurnStartedTitle, French) => "C'est votre tour",
+        (TurnStartedTitle, German) => "Du bist am Zug",
+
+        (TurnStartedBody, English) => "The other players are waiting for you to roll.",
+        (TurnStartedBody, Spanish) => "Los demás jugadores esperan a que tires los dados.",
+        (TurnStartedBody, French) => "Les autres joueurs attendent que vous lanciez les dés.",
+        (TurnStartedBody, German) => "Die anderen Spieler warten darauf, dass du würfelst.",
+
+        (InvitationTitle, English) => "You've been invited to a game",
+        (InvitationTitle, Spanish) => "Te han invitado a una partida",
+        (InvitationTitle, French) => "Vous êtes invité à une partie",
+        (InvitationTitle, German) => "Du wurdest zu einem Spiel eingeladen",
+
+        (InvitationBody, English) => "{0} wants you to play.",
+        (InvitationBody, Spanish) => "{0} quiere que juegues.",
+        (InvitationBody, French) => "{0} vous invite à jouer.",
+        (InvitationBody, German) => "{0} möchte mit dir spielen.",
+
+        (GameOverTitle, English) => "Your game has ended",
+        (GameOverTitle, Spanish) => "Tu partida ha terminado",
+        (GameOverTitle, French) => "Votre partie est terminée",
+        (GameOverTitle, German) => "Dein Spiel ist vorbei",
+
+        (GameOverBody, English) => "Take a look at the final board.",
+        (GameOverBody, Spanish) => "Echa un vistazo al tablero final.",
+        (GameOverBody, French) => "Jetez un œil au plateau final.",
+        (GameOverBody, German) => "Sieh dir das Spielbrett am Ende an.",
+
+        (TurnOver, English) => "{0}'s turn is over.",
+        (TurnOver, Spanish) => "El turno de {0} ha terminado.",
+        (TurnOver, French) => "Le tour de {0} est terminé.",
+        (TurnOver, German) => "{0} hat den Zug beendet.",
+
+        (TurnBuilt, English) => "Built {0} roads, {1} settlements, {2} cities and {3} ships.",
+        (TurnBuilt, Spanish) => "Construyó {0} caminos, {1} poblados, {2} ciudades y {3} barcos.",
+        (TurnBuilt, French) => "A construit {0} routes, {1} colonies, {2} villes et {3} bateaux.",
+        (TurnBuilt, German) => "Hat {0} Straßen, {1} Siedlungen, {2} Städte und {3} Schiffe gebaut.",
+
+        (GameWon, English) => "{0} won with {1} victory points!",
+        (GameWon, Spanish) => "¡{0} ganó con {1} puntos de victoria!",
+        (GameWon, French) => "{0} a gagné avec {1} points de victoire !",
+        (GameWon, German) => "{0} hat mit {1} Siegpunkten gewonnen!",
+
+        (HostChanged, English) => "{0} is the host now.",
+        (HostChanged, Spanish) => "{0} es ahora el anfitrión.",
+        (HostChanged, French) => "{0} est maintenant l'hôte.",
+        (HostChanged, German) => "{0} ist jetzt der Gastgeber.",
+
+        (PlayerRemoved, English) => "{0} was removed from the game.",
+        (PlayerRemoved, Spanish) => "{0} fue expulsado de la partida.",
+        (PlayerRemoved, French) => "{0} a été retiré de la partie.",
+        (PlayerRemoved, German) => "{0} wurde aus dem Spiel entfernt.",
+
+        (PlayerBanned, English) => "{0} was removed from the game and can't rejoin.",
+        (PlayerBanned, Spanish) => "{0} fue expulsado de la partida y no puede volver.",
+        (PlayerBanned, French) => "{0} a été retiré de la partie et ne peut pas revenir.",
+        (PlayerBanned, German) => "{0} wurde aus dem Spiel entfernt und kann nicht zurückkehren.",
+
+        (DigestTitle, English) => "While you were away",
+        (DigestTitle, Spanish) => "Mientras no estabas",
+        (DigestTitle, French) => "Pendant votre absence",
+        (DigestTitle, German) => "Während du weg warst",
+
+        (DigestBody, English) => "{0} of your games have news.",
+        (DigestBody, Spanish) => "Hay novedades en {0} de tus partidas.",
+        (DigestBody, French) => "Il y a du nouveau dans {0} de vos parties.",
+        (DigestBody, German) => "In {0} deiner Spiele gibt es Neuigkeiten.",
+    }
+}
+
+/// the phrase in the language, with the arguments filled in
+pub fn text(phrase: Phrase, language: Language, args: &[&str]) -> String {
+    args.iter()
+        .enumerate()
+        .fold(template(phrase, language).to_owned(), |text, (index, arg)| {
+            text.replace(&format!("{{{}}}", index), arg)
+        })
+}
+
+/// what the game container last told us about a game's languages
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct GameLanguages {
+    language: Language,
+    overrides: HashMap<String, Language>, // user_id -> the language they read the game in
+}
+
+lazy_static::lazy_static! {
+    static ref GAME_LANGUAGES: RwLock<HashMap<String, GameLanguages>> = RwLock::new(HashMap::new());
+}
+
+/// keep the game's languages, if they have changed
+pub fn sync_game(game_id: &str, language: Language, overrides: &HashMap<String, Language>) {
+    let known = GAME_LANGUAGES
+        .read()
+        .expect("the language lock shouldn't be poisoned")
+        .get(game_id)
+        .map_or(false, |known| known.language == language && &known.overrides == overrides);
+    if !known {
+        GAME_LANGUAGES.write().expect("the language lock shouldn't be poisoned").insert(
+            game_id.to_owned(),
+            GameLanguages {
+                language,
+                overrides: overrides.clone(),
+            },
+        );
+    }
+}
+
+/// the game is gone
+pub fn forget_game(game_id: &str) {
+    GAME_LANGUAGES
+        .write()
+        .expect("the language lock shouldn't be poisoned")
+        .remove(game_id);
+}
+
+/// the language the user reads the game in: theirs if they have overridden it, else the game's
+pub fn language_for(game_id: &str, user_id: &str) -> Language {
+    GAME_LANGUAGES
+        .read()
+        .expect("the language lock shouldn't be poisoned")
+        .get(game_id)
+        .map(|game| game.overrides.get(user_id).copied().unwrap_or(game.language))
+        .unwrap_or_default()
+}
+
+/// the message with its text in the language, or None if it doesn't have any text
+pub fn localize(message: &CatanMessage, language: Language) -> Option<CatanMessage> {
+    let mut message = message.clone();
+    match &mut message {
+        CatanMessage::TurnSummary(summary) => {
+            let mut rendered = text(Phrase::TurnOver, language, &[&summary.user_name]);
+            let built = [
+                summary.roads_built,
+                summary.settlements_built,
+                summary.cities_built,
+                summary.ships_built,
+            ];
+            if built.iter().any(|count| *count > 0) {
+                let counts: Vec<String> = built.iter().map(|count| count.to_string()).collect();
+                let counts: Vec<&str> = counts.iter().map(|count| count.as_str()).collect();
+                rendered.push(' ');
+                rendered.push_str(&text(Phrase::TurnBuilt, language, &counts));
+            }
+            summary.text = rendered;
+        }
+        CatanMessage::GameWon(won) => {
+            let points = won
+                .scores
+                .iter()
+                .find(|score| score.user_id == won.winner_id)
+                .map(|score| score.victory_points)
+                .unwrap_or_default()
+                .to_string();
+            won.text = text(Phrase::GameWon, language, &[&won.winner_name, &points]);
+        }
+        CatanMessage::HostChanged(changed) => {
+            changed.text = text(Phrase::HostChanged, language, &[&changed.to_name]);
+        }
+        CatanMessage::PlayerRemoved(removed) => {
+            let phrase = if removed.banned { Phrase::PlayerBanned } else { Phrase::PlayerRemoved };
+            removed.text = text(phrase, language, &[&removed.user_name]);
+        }
+        _ => return None,
+    }
+    Some(message)
+}
+
+/// the message as the user should read it, or None if it doesn't have any text
+pub fn localized_for(user_id: &str, message: &CatanMessage) -> Option<CatanMessage> {
+    let game_id = match message {
+        CatanMessage::TurnSummary(summary) => &summary.game_id,
+        CatanMessage::GameWon(won) => &won.game_id,
+        CatanMessage::HostChanged(changed) => &changed.game_id,
+        CatanMessage::PlayerRemoved(removed) => &removed.game_id,
+        _ => return None,
+    };
+    localize(message, language_for(game_id, user_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games_service::game_container::game_messages::HostChangedData;
+
+    #[test]
+    fn test_catalog() {
+        assert_eq!(Language::from_code("es-MX"), Some(Language::Spanish));
+        assert_eq!(Language::from_code("DE"), Some(Language::German));
+        assert_eq!(Language::from_code("xx"), None);
+        assert_eq!(
+            text(Phrase::GameWon, Language::French, &["Ana", "10"]),
+            "Ana a gagné avec 10 points de victoire !"
+        );
+        // every phrase is there in every language
+        for language in Language::ALL {
+            assert!(!text(Phrase::PlayerBanned, language, &["x"]).contains("{0}"));
+        }
+
+        let mut overrides = HashMap::new();
+        overrides.insert("reader".to_owned(), Language::German);
+        sync_game("i18n-game", Language::Spanish, &overrides);
+        assert_eq!(language_for("i18n-game", "anybody"), Language::Spanish);
+        assert_eq!(language_for("i18n-game", "reader"), Language::German);
+
+        let message = CatanMessage::HostChanged(HostChangedData {
+            game_id: "i18n-game".to_owned(),
+            from_id: "1".to_owned(),
+            to_id: "2".to_owned(),
+            to_name: "Bea".to_owned(),
+            text: String::default(),
+        });
+        match localized_for("anybody", &message) {
+            Some(CatanMessage::HostChanged(changed)) => assert_eq!(changed.text, "Bea es ahora el anfitrión."),
+            other => panic!("expected a localized HostChanged, got {:?}", other),
+        }
+        match localized_for("reader", &message) {
+            Some(CatanMessage::HostChanged(changed)) => assert_eq!(changed.text, "Bea ist jetzt der Gastgeber."),
+            other => panic!("expected a localized HostChanged, got {:?}", other),
+        }
+        assert!(localized_for("anybody", &CatanMessage::Started("i18n-game".to_owned())).is_none());
+
+        forget_game("i18n-game");
+        assert_eq!(language_for("i18n-game", "reader"), Language::English);
+    }
+}
@@ -0,0 +1,273 @@
+#![allow(dead_code)]
+/**
+ *  the integrity job keeps the database consistent as users and their data are deleted.  every INTEGRITY_INTERVAL it
+ *  looks for
+ *
+ *  1. local users whose owner (connected_user_id) no longer exists.  nobody can log in as them or play them, so they
+ *     are deleted
+ *  2. finished games with players who no longer exist.  the games are the other players' match history, so they are
+ *     kept -- archived with the missing players in deleted_player_ids, so history and stats don't go looking for them
+ *  3. invitations that have expired: their used nonces are forgotten (see invitation_token.rs)
+ *
+ *  what it found and what it did is logged and kept as the last report, which admins can read.  an admin can also run
+ *  it now, and with ?dry_run=true it only reports what it would do.
+ */
+use std::{
+    collections::HashSet,
+    sync::RwLock,
+    time::Duration,
+};
+
+use actix_web::{web, HttpResponse};
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::lobby::invitation_token,
+    middleware::{
+        request_context_mw::RequestContext, security_context::SecurityContext,
+        service_config::SERVICE_CONFIG,
+    },
+    new_unauthorized_response,
+    shared::{
+        error_reporting,
+        service_models::{PersistGame, PersistUser, Role},
+        shared_models::{GameError, ResponseType, ServiceResponse, UserType},
+    },
+};
+
+/// how often the scheduled check runs
+pub const INTEGRITY_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+lazy_static::lazy_static! {
+    static ref LAST_REPORT: RwLock<Option<IntegrityReport>> = RwLock::new(None);
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct IntegrityReport {
+    pub checked_at: u64, // seconds since the epoch
+    pub dry_run: bool,
+    pub users_checked: usize,
+    pub games_checked: usize,
+    pub orphaned_local_users: Vec<String>, // deleted
+    pub archived_games: Vec<String>,       // marked with the players who have been deleted
+    pub expired_invites: usize,
+    pub errors: Vec<String>, // what couldn't be repaired; the next run tries again
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IntegrityQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// the local users whose owner isn't one of the users
+pub fn orphaned_local_users(users: &[PersistUser]) -> Vec<String> {
+    let user_ids: HashSet<&str> = users.iter().map(|user| user.id.as_str()).collect();
+    let mut orphans: Vec<String> = users
+        .iter()
+        .filter(|user| user.user_profile.user_type == UserType::Local)
+        .filter(|user| {
+            user.connected_user_id
+                .as_deref()
+                .map_or(true, |owner| !user_ids.contains(owner))
+        })
+        .map(|user| user.id.clone())
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+/// the game's players that aren't users and haven't already been marked as deleted
+pub fn newly_deleted_players(game: &PersistGame, user_ids: &HashSet<String>) -> Vec<String> {
+    let mut deleted: Vec<String> = game
+        .player_ids
+        .iter()
+        .filter(|id| !user_ids.contains(*id) && !game.deleted_player_ids.contains(id))
+        .cloned()
+        .collect();
+    deleted.sort();
+    deleted
+}
+
+/// Runs the check against the request context's database.  Unless it is a dry run, orphaned local users are deleted
+/// and games are archived as it goes.
+pub async fn check(request_context: &RequestContext, dry_run: bool) -> Result<IntegrityReport, ServiceResponse> {
+    let mut report = IntegrityReport {
+        checked_at: request_context.environment.now(),
+        dry_run,
+        ..Default::default()
+    };
+
+    let mut users = Vec::new();
+    let mut stream = request_context.database.stream_users();
+    while let Some(user) = stream.next().await {
+        users.push(user?);
+    }
+    drop(stream);
+    report.users_checked = users.len();
+    report.orphaned_local_users = orphaned_local_users(&users);
+    if !dry_run {
+        for user_id in &report.orphaned_local_users {
+            if let Err(e) = request_context.database.delete_user(user_id).await {
+                report.errors.push(format!("couldn't delete {}: {}", user_id, e.message));
+            }
+        }
+    }
+    //  the orphans are gone (or would be), so they count as deleted players too
+    let user_ids: HashSet<String> = users
+        .into_iter()
+        .map(|user| user.id)
+        .filter(|id| !report.orphaned_local_users.contains(id))
+        .collect();
+
+    let mut archive = Vec::new();
+    let mut games = request_context.database.stream_games(0);
+    while let Some(game) = games.next().await {
+        let game = game?;
+        report.games_checked += 1;
+        let deleted = newly_deleted_players(&game, &user_ids);
+        if !deleted.is_empty() {
+            let mut archived = game;
+            archived.deleted_player_ids.extend(deleted);
+            archived.deleted_player_ids.sort();
+            archive.push(archived);
+        }
+    }
+    drop(games);
+    for game in archive {
+        report.archived_games.push(game.id.clone());
+        if !dry_run {
+            if let Err(e) = request_context.database.update_or_create_game(&game).await {
+                report.errors.push(format!("couldn't archive {}: {}", game.id, e.message));
+            }
+        }
+    }
+
+    report.expired_invites = if dry_run { 0 } else { invitation_token::forget_expired(report.checked_at) };
+
+    tracing::info!(
+        "integrity check{}: {} users and {} games checked, {} orphaned local users, {} games archived, {} expired \
+         invites, {} errors",
+        if dry_run { " (dry run)" } else { "" },
+        report.users_checked,
+        report.games_checked,
+        report.orphaned_local_users.len(),
+        report.archived_games.len(),
+        report.expired_invites,
+        report.errors.len()
+    );
+    if !dry_run {
+        *LAST_REPORT.write().unwrap() = Some(report.clone());
+    }
+    Ok(report)
+}
+
+/**
+ *  run the check against the production database every interval, for the life of the service.  a run that fails, or
+ *  can't repair everything it finds, is reported and the next one is tried on schedule
+ */
+pub fn start_schedule(interval: Duration) -> tokio::task::JoinHandle<()> {
+    actix_web::rt::spawn(async move {
+        let request_context = RequestContext::new(
+            &None,
+            &None,
+            &SERVICE_CONFIG,
+            &SecurityContext::cached_secrets(),
+        );
+        loop {
+            tokio::time::sleep(interval).await;
+            let problem = match check(&request_context, false).await {
+                Ok(report) if report.errors.is_empty() => None,
+                Ok(report) => Some(format!("the integrity check couldn't repair everything: {:?}", report.errors)),
+                Err(e) => Some(format!("the integrity check failed: {:#?}", e)),
+            };
+            if let Some(message) = problem {
+                tracing::error!("{}", message);
+                error_reporting::report_background_failure("integrity", &message);
+            }
+        }
+    })
+}
+
+fn integrity_response(report: IntegrityReport) -> ServiceResponse {
+    ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::IntegrityReport(report),
+        GameError::NoError(String::default()),
+    )
+}
+
+/// the last scheduled (or admin) run's report -- admins only
+pub fn last_report(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    match LAST_REPORT.read().unwrap().clone() {
+        Some(report) => Ok(integrity_response(report)),
+        None => Err(ServiceResponse::new(
+            "the integrity check hasn't run yet",
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        )),
+    }
+}
+
+/// run the check now -- admins and internal services only
+pub async fn run_check(request_context: &RequestContext, dry_run: bool) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) && !request_context.is_service_call() {
+        return new_unauthorized_response!("");
+    }
+    check(request_context, dry_run).await.map(integrity_response)
+}
+
+pub async fn last_report_handler(request_context: RequestContext) -> HttpResponse {
+    last_report(&request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn run_check_handler(query: web::Query<IntegrityQuery>, request_context: RequestContext) -> HttpResponse {
+    run_check(&request_context, query.dry_run)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::catan_games::games::regular::regular_game::RegularGame, shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_integrity() {
+        let mut owner = PersistUser::new();
+        owner.id = "owner".to_owned();
+        let local = |id: &str, owner_id: &str| {
+            let mut profile = UserProfile::default();
+            profile.user_type = UserType::Local;
+            let mut user = PersistUser::from_local_user(owner_id, &profile);
+            user.id = id.to_owned();
+            user
+        };
+        let users = vec![owner, local("kept", "owner"), local("orphan", "deleted owner")];
+        assert_eq!(orphaned_local_users(&users), vec!["orphan".to_string()]);
+
+        let mut game = PersistGame::from_game(&RegularGame::new(&UserProfile::default()), None);
+        game.player_ids = vec!["owner".to_owned(), "orphan".to_owned(), "gone".to_owned()];
+        let user_ids: HashSet<String> = ["owner".to_string(), "kept".to_string()].into_iter().collect();
+        assert_eq!(
+            newly_deleted_players(&game, &user_ids),
+            vec!["gone".to_string(), "orphan".to_string()]
+        );
+        // already archived: nothing new to do
+        game.deleted_player_ids = vec!["gone".to_owned(), "orphan".to_owned()];
+        assert!(newly_deleted_players(&game, &user_ids).is_empty());
+    }
+}
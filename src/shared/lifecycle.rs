@@ -0,0 +1,247 @@
+#![allow(dead_code)]
+/**
+ *  starting and stopping the service's subsystems.  each subsystem (the database, the long poller, the background
+ *  jobs...) registers an init hook, and optionally a shutdown hook, with an order and a timeout.  start runs the init
+ *  hooks in order -- lowest first -- each bounded by its timeout, and stop runs the shutdown hooks of the ones that
+ *  started in the reverse order.
+ *
+ *  a required subsystem that fails (or times out) stops the start: the ones already started are shut down again and
+ *  start returns the error, so main can refuse to serve.  an optional one that fails is logged and reported, and the
+ *  service runs without it.  what happened to each is kept in components(), so tests -- and the status api -- can see
+ *  which subsystems are running.
+ */
+use std::{future::Future, pin::Pin, sync::RwLock, time::Duration};
+
+use serde::Serialize;
+
+pub type HookFuture = Pin<Box<dyn Future<Output = Result<(), String>>>>;
+pub type Hook = Box<dyn Fn() -> HookFuture>;
+
+/// the timeout a subsystem gets if it doesn't ask for another
+pub const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref COMPONENTS: RwLock<Vec<ComponentReport>> = RwLock::new(Vec::new());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum ComponentState {
+    NotStarted,
+    Started,
+    Failed(String),
+    TimedOut,
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ComponentReport {
+    pub name: String,
+    pub required: bool,
+    pub state: ComponentState,
+}
+
+struct Subsystem {
+    name: String,
+    order: u32,
+    required: bool,
+    timeout: Duration,
+    init: Hook,
+    shutdown: Option<Hook>,
+}
+
+#[derive(Default)]
+pub struct Lifecycle {
+    subsystems: Vec<Subsystem>,
+    reports: Vec<ComponentReport>,
+}
+
+/// run a hook, turning a timeout into a state
+async fn run_hook(hook: &Hook, timeout: Duration) -> ComponentState {
+    match tokio::time::timeout(timeout, hook()).await {
+        Ok(Ok(())) => ComponentState::Started,
+        Ok(Err(e)) => ComponentState::Failed(e),
+        Err(_) => ComponentState::TimedOut,
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add a subsystem the service can't run without
+    pub fn require<I, IF>(&mut self, name: &str, order: u32, timeout: Duration, init: I) -> &mut Self
+    where
+        I: Fn() -> IF + 'static,
+        IF: Future<Output = Result<(), String>> + 'static,
+    {
+        self.register(name, order, true, timeout, init)
+    }
+
+    /// add a subsystem the service can run without
+    pub fn optional<I, IF>(&mut self, name: &str, order: u32, timeout: Duration, init: I) -> &mut Self
+    where
+        I: Fn() -> IF + 'static,
+        IF: Future<Output = Result<(), String>> + 'static,
+    {
+        self.register(name, order, false, timeout, init)
+    }
+
+    fn register<I, IF>(&mut self, name: &str, order: u32, required: bool, timeout: Duration, init: I) -> &mut Self
+    where
+        I: Fn() -> IF + 'static,
+        IF: Future<Output = Result<(), String>> + 'static,
+    {
+        self.subsystems.push(Subsystem {
+            name: name.to_owned(),
+            order,
+            required,
+            timeout,
+            init: Box::new(move || Box::pin(init())),
+            shutdown: None,
+        });
+        self
+    }
+
+    /// what the subsystem registered last does when the service stops
+    pub fn on_shutdown<S, SF>(&mut self, shutdown: S) -> &mut Self
+    where
+        S: Fn() -> SF + 'static,
+        SF: Future<Output = Result<(), String>> + 'static,
+    {
+        if let Some(subsystem) = self.subsystems.last_mut() {
+            subsystem.shutdown = Some(Box::new(move || Box::pin(shutdown())));
+        }
+        self
+    }
+
+    /// what has happened to each subsystem so far, in the order they start
+    pub fn reports(&self) -> &[ComponentReport] {
+        &self.reports
+    }
+
+    fn set_state(&mut self, index: usize, state: ComponentState) {
+        self.reports[index].state = state;
+        *COMPONENTS.write().unwrap() = self.reports.clone();
+    }
+
+    /// run the init hooks in order.  Err names the required subsystem that didn't start
+    pub async fn start(&mut self) -> Result<(), String> {
+        self.subsystems.sort_by_key(|subsystem| subsystem.order); // stable, so equal orders start as registered
+        self.reports = self
+            .subsystems
+            .iter()
+            .map(|subsystem| ComponentReport {
+                name: subsystem.name.clone(),
+                required: subsystem.required,
+                state: ComponentState::NotStarted,
+            })
+            .collect();
+        *COMPONENTS.write().unwrap() = self.reports.clone();
+
+        for index in 0..self.subsystems.len() {
+            let subsystem = &self.subsystems[index];
+            let state = run_hook(&subsystem.init, subsystem.timeout).await;
+            let (name, required) = (subsystem.name.clone(), subsystem.required);
+            self.set_state(index, state.clone());
+            match state {
+                ComponentState::Started => tracing::info!("started {}", name),
+                _ if !required => tracing::warn!("{} didn't start, running without it: {:?}", name, state),
+                _ => {
+                    tracing::error!("{} didn't start: {:?}", name, state);
+                    self.stop().await;
+                    return Err(format!("{} didn't start: {:?}", name, state));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// run the shutdown hooks of the subsystems that started, last started first
+    pub async fn stop(&mut self) {
+        for index in (0..self.subsystems.len()).rev() {
+            if self.reports.get(index).map(|report| &report.state) != Some(&ComponentState::Started) {
+                continue;
+            }
+            let subsystem = &self.subsystems[index];
+            if let Some(shutdown) = &subsystem.shutdown {
+                let name = subsystem.name.clone();
+                match run_hook(shutdown, subsystem.timeout).await {
+                    ComponentState::Started => tracing::info!("stopped {}", name),
+                    state => tracing::warn!("{} didn't stop cleanly: {:?}", name, state),
+                }
+            }
+            self.set_state(index, ComponentState::Stopped);
+        }
+    }
+}
+
+/// what has happened to each of the service's subsystems
+pub fn components() -> Vec<ComponentReport> {
+    COMPONENTS.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[tokio::test]
+    async fn test_lifecycle() {
+        let log = Rc::new(RefCell::new(Vec::<String>::new()));
+        let logger = |entry: &'static str| {
+            let log = log.clone();
+            move || {
+                let log = log.clone();
+                async move {
+                    log.borrow_mut().push(entry.to_owned());
+                    Ok(())
+                }
+            }
+        };
+        let mut lifecycle = Lifecycle::new();
+        lifecycle
+            .require("jobs", 30, DEFAULT_HOOK_TIMEOUT, logger("start jobs"))
+            .on_shutdown(logger("stop jobs"))
+            .require("database", 10, DEFAULT_HOOK_TIMEOUT, logger("start database"))
+            .on_shutdown(logger("stop database"))
+            .optional("metrics", 20, Duration::from_millis(10), || async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(())
+            });
+        assert_eq!(lifecycle.start().await, Ok(()));
+        let states: Vec<(&str, &ComponentState)> = lifecycle
+            .reports()
+            .iter()
+            .map(|report| (report.name.as_str(), &report.state))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                ("database", &ComponentState::Started),
+                ("metrics", &ComponentState::TimedOut),
+                ("jobs", &ComponentState::Started)
+            ]
+        );
+
+        lifecycle.stop().await;
+        assert_eq!(
+            *log.borrow(),
+            vec!["start database", "start jobs", "stop jobs", "stop database"]
+        );
+
+        // a required subsystem that fails stops the start, and what had started is stopped again
+        log.borrow_mut().clear();
+        let mut lifecycle = Lifecycle::new();
+        lifecycle
+            .require("database", 10, DEFAULT_HOOK_TIMEOUT, logger("start database"))
+            .on_shutdown(logger("stop database"))
+            .require("long poller", 20, DEFAULT_HOOK_TIMEOUT, || async { Err("no".to_owned()) })
+            .require("jobs", 30, DEFAULT_HOOK_TIMEOUT, logger("start jobs"));
+        assert!(lifecycle.start().await.is_err());
+        assert_eq!(*log.borrow(), vec!["start database", "stop database"]);
+        assert_eq!(lifecycle.reports()[2].state, ComponentState::NotStarted);
+    }
+}
@@ -0,0 +1,200 @@
+#![allow(dead_code)]
+/**
+ *  logging is set up with a reloadable filter so that the levels can be changed while the service is running -- eg.
+ *  crank catan_service::cosmos_db up to trace during an incident and back down afterwards, without a restart.
+ *
+ *  the service logs with tracing.  every HTTP request runs in a "request" span (user, game and correlation id) and
+ *  every game action in its own span, so all of the events for one request or one game can be pulled out of the
+ *  logs.  crates that still use the log crate (actix, azure) are forwarded to tracing, so the filter applies to them
+ *  too.  the filter uses the RUST_LOG syntax: "info,catan_service::cosmos_db=trace"
+ *
+ *  set LOG_FORMAT=json to write one JSON object per line (with the span fields) for log aggregation.
+ */
+use std::{fs::File, sync::Mutex};
+
+use actix_web::{web, HttpResponse};
+use once_cell::sync::OnceCell;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Layer, Registry};
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+static FILTER_HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct LogFilter {
+    pub filter: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+impl LogFormat {
+    /// the format named by the LOG_FORMAT environment variable.  anything but "json" is Text
+    pub fn from_env() -> Self {
+        match std::env::var("LOG_FORMAT") {
+            Ok(format) if format.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/**
+ *  install the global subscriber with the given filter, writing to stdout and, if log_file is set, to that file as
+ *  well.  returns false if a subscriber was already installed, in which case the filter can't be changed at runtime.
+ */
+pub fn init_logging(directives: &str, format: LogFormat, log_file: Option<File>) -> bool {
+    let filter = EnvFilter::try_new(directives).unwrap_or_else(|e| {
+        eprintln!("invalid log filter \"{}\" ({}). using \"info\"", directives, e);
+        EnvFilter::new("info")
+    });
+    let (filter, handle) = reload::Layer::new(filter);
+    let stdout = match format {
+        LogFormat::Text => fmt::layer().boxed(),
+        LogFormat::Json => fmt::layer().json().boxed(),
+    };
+    let file = log_file.map(|file| {
+        fmt::layer()
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .boxed()
+    });
+    match tracing_subscriber::registry()
+        .with(filter)
+        .with(stdout)
+        .with(file)
+        .try_init()
+    {
+        Ok(()) => {
+            let _ = FILTER_HANDLE.set(handle);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn current_filter() -> Option<String> {
+    FILTER_HANDLE
+        .get()
+        .and_then(|handle| handle.with_current(|filter| filter.to_string()).ok())
+}
+
+/// replace the filter. returns the new filter, or a description of what was wrong with the directives
+pub fn set_filter(directives: &str) -> Result<String, String> {
+    let handle = FILTER_HANDLE
+        .get()
+        .ok_or_else(|| "logging was not initialized with a reloadable filter".to_owned())?;
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|e| format!("\"{}\" is not a valid filter: {}", directives, e))?;
+    handle.reload(filter).map_err(|e| e.to_string())?;
+    tracing::warn!("log filter changed to \"{}\"", directives);
+    current_filter().ok_or_else(|| "unable to read back the filter".to_owned())
+}
+
+fn filter_response(filter: String) -> ServiceResponse {
+    ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::LogFilter(LogFilter { filter }),
+        GameError::NoError(String::default()),
+    )
+}
+
+pub fn get_log_filter(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    match current_filter() {
+        Some(filter) => Ok(filter_response(filter)),
+        None => Err(ServiceResponse::new(
+            "logging was not initialized with a reloadable filter",
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        )),
+    }
+}
+
+pub fn set_log_filter(
+    log_filter: &LogFilter,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    match set_filter(&log_filter.filter) {
+        Ok(filter) => Ok(filter_response(filter)),
+        Err(e) => Err(ServiceResponse::new(
+            "bad log filter",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(e),
+            GameError::HttpError(StatusCode::BAD_REQUEST),
+        )),
+    }
+}
+
+pub async fn get_log_filter_handler(request_context: RequestContext) -> HttpResponse {
+    get_log_filter(&request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn set_log_filter_handler(
+    log_filter: web::Json<LogFilter>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    set_log_filter(&log_filter, &request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init_env_logger;
+
+    #[tokio::test]
+    async fn test_set_log_filter() {
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
+        let original = current_filter().expect("init_env_logger installs a reloadable filter");
+
+        let filter = set_filter("info,catan_service::cosmos_db=trace").expect("valid filter");
+        assert!(filter.contains("catan_service::cosmos_db=trace"));
+        assert!(set_filter("catan_service=notalevel").is_err());
+        assert_eq!(current_filter(), Some(filter));
+
+        // only admins can look at or change the filter
+        let request_context = RequestContext::test_default(false);
+        let sr = get_log_filter(&request_context).expect_err("not an admin");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+        let sr = set_log_filter(
+            &LogFilter {
+                filter: "trace".to_owned(),
+            },
+            &request_context,
+        )
+        .expect_err("not an admin");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+
+        set_filter(&original).expect("the original filter should still be valid");
+    }
+}
@@ -1,5 +1,18 @@
+pub mod analytics_export;
+pub mod branding;
+pub mod client_telemetry;
+pub mod environment;
+pub mod error_reporting;
+pub mod i18n;
+pub mod integrity;
+pub mod lifecycle;
+pub mod log_filter;
+pub mod profiling;
 pub mod shared_models;
 pub mod proxy;
+pub mod service_info;
+pub mod smoke_test;
+pub mod status_page;
 pub mod utility;
 pub mod service_response;
 pub mod service_models;
@@ -0,0 +1,255 @@
+#![allow(dead_code)]
+/**
+ *  timings for the hot paths we want to make faster: waiting for a game's container, serializing responses, and
+ *  broadcasting games to the players.  each path gets a histogram of how long it took, and admins can also take a
+ *  CPU profile of the whole service and get it back as a flamegraph.
+ *
+ *  all of this is opt-in: build with `--features profiling` to turn it on.  without the feature the timers compile to
+ *  nothing, the timings are empty and the flamegraph api returns 501.
+ */
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+/// the upper bound of each histogram bucket, in microseconds.  anything slower goes in one last bucket
+pub const BUCKET_BOUNDS_MICROS: [u64; 10] = [
+    10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 1_000_000,
+];
+
+/// how long a flamegraph samples for if the caller doesn't say, and the longest it can
+pub const DEFAULT_FLAMEGRAPH_SECONDS: u64 = 10;
+pub const MAX_FLAMEGRAPH_SECONDS: u64 = 60;
+const FLAMEGRAPH_FREQUENCY: i32 = 99; // samples a second
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotPath {
+    ContainerLock, // waiting for a game's actor to answer (see GameContainer::call)
+    Serialization, // turning a ServiceResponse into json
+    Broadcast,     // sending a game to every player in it
+}
+
+pub const HOT_PATHS: [HotPath; 3] = [
+    HotPath::ContainerLock,
+    HotPath::Serialization,
+    HotPath::Broadcast,
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct TimingHistogram {
+    pub path: HotPath,
+    pub bucket_bounds_micros: Vec<u64>,
+    pub counts: Vec<u64>, // one more than the bounds: the last count is everything over the largest bound
+    pub count: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+}
+
+impl TimingHistogram {
+    fn new(path: HotPath) -> Self {
+        Self {
+            path,
+            bucket_bounds_micros: BUCKET_BOUNDS_MICROS.to_vec(),
+            counts: vec![0; BUCKET_BOUNDS_MICROS.len() + 1],
+            count: 0,
+            total_micros: 0,
+            max_micros: 0,
+        }
+    }
+
+    fn record(&mut self, micros: u64) {
+        let bucket = BUCKET_BOUNDS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_MICROS.len());
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.total_micros += micros;
+        self.max_micros = self.max_micros.max(micros);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProfilingReport {
+    pub enabled: bool, // false if the service was built without the profiling feature
+    pub timings: Vec<TimingHistogram>,
+}
+
+lazy_static::lazy_static! {
+    static ref HISTOGRAMS: Mutex<HashMap<HotPath, TimingHistogram>> = Mutex::new(HashMap::new());
+}
+
+/// true if the service was built with the profiling feature
+pub fn enabled() -> bool {
+    cfg!(feature = "profiling")
+}
+
+/// times a hot path from now until it is dropped
+pub struct HotPathTimer {
+    path: HotPath,
+    start: Instant,
+}
+
+impl Drop for HotPathTimer {
+    fn drop(&mut self) {
+        if enabled() {
+            record(self.path, self.start.elapsed());
+        }
+    }
+}
+
+/// eg. `let _timer = profiling::timer(HotPath::Broadcast);` at the top of the code to time
+pub fn timer(path: HotPath) -> HotPathTimer {
+    HotPathTimer {
+        path,
+        start: Instant::now(),
+    }
+}
+
+fn record(path: HotPath, elapsed: Duration) {
+    HISTOGRAMS
+        .lock()
+        .expect("the profiling lock shouldn't be poisoned")
+        .entry(path)
+        .or_insert_with(|| TimingHistogram::new(path))
+        .record(elapsed.as_micros() as u64);
+}
+
+/// the histograms so far, one for every hot path
+pub fn report() -> ProfilingReport {
+    let histograms = HISTOGRAMS
+        .lock()
+        .expect("the profiling lock shouldn't be poisoned");
+    ProfilingReport {
+        enabled: enabled(),
+        timings: HOT_PATHS
+            .iter()
+            .map(|path| {
+                histograms
+                    .get(path)
+                    .cloned()
+                    .unwrap_or_else(|| TimingHistogram::new(*path))
+            })
+            .collect(),
+    }
+}
+
+#[cfg(feature = "profiling")]
+async fn flamegraph_svg(seconds: u64) -> Result<Vec<u8>, ServiceResponse> {
+    let failed = |e: pprof::Error| {
+        ServiceResponse::new(
+            "couldn't take a profile -- is another one running?",
+            StatusCode::CONFLICT,
+            ResponseType::ErrorInfo(e.to_string()),
+            GameError::HttpError(StatusCode::CONFLICT),
+        )
+    };
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(FLAMEGRAPH_FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(failed)?;
+    tokio::time::sleep(Duration::from_secs(seconds)).await;
+    let report = guard.report().build().map_err(failed)?;
+    let mut svg = Vec::new();
+    report.flamegraph(&mut svg).map_err(failed)?;
+    Ok(svg)
+}
+
+#[cfg(not(feature = "profiling"))]
+async fn flamegraph_svg(_seconds: u64) -> Result<Vec<u8>, ServiceResponse> {
+    Err(ServiceResponse::new(
+        "the service was built without the profiling feature",
+        StatusCode::NOT_IMPLEMENTED,
+        ResponseType::NoData,
+        GameError::HttpError(StatusCode::NOT_IMPLEMENTED),
+    ))
+}
+
+/// the timing histograms -- admin only
+pub fn get_timings(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::ProfilingReport(report()),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn get_timings_handler(request_context: RequestContext) -> HttpResponse {
+    get_timings(&request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FlamegraphQuery {
+    pub seconds: Option<u64>,
+}
+
+/// profiles the service for the number of seconds and returns the flamegraph as an svg -- admin only
+pub async fn flamegraph(
+    seconds: Option<u64>,
+    request_context: &RequestContext,
+) -> Result<Vec<u8>, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    let seconds = seconds
+        .unwrap_or(DEFAULT_FLAMEGRAPH_SECONDS)
+        .clamp(1, MAX_FLAMEGRAPH_SECONDS);
+    flamegraph_svg(seconds).await
+}
+
+pub async fn flamegraph_handler(
+    query: web::Query<FlamegraphQuery>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    match flamegraph(query.seconds, &request_context).await {
+        Ok(svg) => HttpResponse::Ok().content_type("image/svg+xml").body(svg),
+        Err(sr) => sr.to_http_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = TimingHistogram::new(HotPath::Broadcast);
+        for micros in [5, 10, 11, 750, 2_000_000].iter() {
+            histogram.record(*micros);
+        }
+        assert_eq!(histogram.counts.len(), BUCKET_BOUNDS_MICROS.len() + 1);
+        assert_eq!(histogram.counts[0], 2); // the bounds are inclusive
+        assert_eq!(histogram.counts[1], 1);
+        assert_eq!(histogram.counts[4], 1);
+        assert_eq!(histogram.counts[BUCKET_BOUNDS_MICROS.len()], 1);
+        assert_eq!(histogram.count, 5);
+        assert_eq!(histogram.max_micros, 2_000_000);
+
+        let report = report();
+        assert_eq!(report.enabled, cfg!(feature = "profiling"));
+        assert_eq!(report.timings.len(), HOT_PATHS.len());
+    }
+}
@@ -11,9 +11,10 @@ use url::Url;
 
 use crate::{
     games_service::{
+        bots::engine::BotDifficulty,
         catan_games::games::regular::regular_game::RegularGame,
         game_container::game_messages::{GameHeader, Invitation, InvitationResponseData},
-        shared::game_enums::CatanGames,
+        shared::game_enums::{CatanGames, GameAction},
     },
     middleware::request_context_mw::TestContext,
     shared::shared_models::GameError,
@@ -203,6 +204,47 @@ impl ServiceProxy {
         }
     }
 
+    /// Makes a DELETE request to the specified URL with optional headers
+    pub async fn delete(
+        &self,
+        url: &str,
+        headers: impl IntoIterator<Item = (HeaderName, HeaderValue)>,
+    ) -> ServiceResponse {
+        let url = match self.host.join(url) {
+            Ok(url) => url,
+            Err(_) => {
+                panic!("Bad URL passed into delete: {}", url);
+            }
+        };
+        let mut request_builder = self.client.delete(url);
+        for (key, value) in headers {
+            request_builder = request_builder.header(key, value);
+        }
+        if let Some(test_context) = &self.test_context {
+            let json = serde_json::to_string(test_context).unwrap();
+            request_builder = request_builder.header(
+                HeaderName::from_static(GameHeader::TEST),
+                HeaderValue::from_str(&json).expect("valid header value"),
+            );
+        }
+        match request_builder.send().await {
+            Ok(response) => response.json().await.unwrap_or_else(|_| {
+                ServiceResponse::new(
+                    "unknown error",
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ResponseType::NoData,
+                    GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+                )
+            }),
+            Err(reqwest_error) => ServiceResponse::new(
+                "reqwest error",
+                StatusCode::SERVICE_UNAVAILABLE,
+                ResponseType::ErrorInfo(format!("{:#?}", reqwest_error)),
+                GameError::HttpError(StatusCode::SERVICE_UNAVAILABLE),
+            ),
+        }
+    }
+
     pub async fn setup(&self) -> ServiceResponse {
         let headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
         let url = "/api/v1/test/verify-service";
@@ -296,6 +338,17 @@ impl ServiceProxy {
         self.get(&url, headers).await
     }
 
+    pub async fn explain_action(&self, game_id: &str, action: &GameAction) -> ServiceResponse {
+        let url = format!("/auth/api/v1/games/{}/actions/explain/{:?}", game_id, action);
+        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            HeaderValue::from_str(&self.auth_token).expect("Invalid header value"),
+        );
+
+        self.get(&url, headers).await
+    }
+
     pub async fn long_poll(&self, game_id: &str, index: u32) -> ServiceResponse {
         let url = format!("/auth/api/v1/longpoll/{}", index);
         let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
@@ -393,4 +446,41 @@ impl ServiceProxy {
 
         self.post::<()>(&url, headers, None).await
     }
+
+    pub async fn add_bot(&self, game_id: &str, difficulty: BotDifficulty) -> ServiceResponse {
+        let url = format!("/auth/api/v1/lobby/add-bot/{:?}", difficulty);
+        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            HeaderValue::from_str(&self.auth_token).expect("Invalid header value"),
+        );
+        headers.insert(
+            HeaderName::from_static(GameHeader::GAME_ID),
+            HeaderValue::from_str(game_id).expect("Invalid header value"),
+        );
+
+        self.post::<()>(&url, headers, None).await
+    }
+
+    pub async fn delete_user(&self, id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/users/{}", id);
+        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            HeaderValue::from_str(&self.auth_token).expect("Invalid header value"),
+        );
+
+        self.delete(&url, headers).await
+    }
+
+    pub async fn delete_local_user(&self, id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/users/local/{}", id);
+        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            HeaderValue::from_str(&self.auth_token).expect("Invalid header value"),
+        );
+
+        self.delete(&url, headers).await
+    }
 }
@@ -0,0 +1,127 @@
+#![allow(dead_code)]
+/**
+ *  what is deployed: the version, the commit and build time (stamped by build.rs), the cargo features and runtime
+ *  flags that are on, the game types the service can create, the deployment's branding and its concurrent session
+ *  policy.  clients use it to check that they are compatible (to show the right name and logo, and to explain a
+ *  forced logout) and operators use it to confirm a deployment; the same information is printed as a banner at
+ *  startup.
+ */
+use actix_web::HttpResponse;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::{game::SUPPORTED_GAMES, shared::game_enums::CatanGames},
+    middleware::service_config::SERVICE_CONFIG,
+    shared::{
+        branding::Branding,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+    user_service::sessions::SessionPolicy,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceInfo {
+    pub version: String,         // the crate's semantic version
+    pub git_commit: String,      // "unknown" when it wasn't built from a git checkout
+    pub build_timestamp: u64,    // seconds since the UNIX epoch
+    pub features: Vec<String>,   // the cargo features the service was built with
+    pub flags: Vec<String>,      // the optional behavior turned on by the environment
+    pub supported_games: Vec<CatanGames>,
+    pub branding: Branding,
+    pub session_policy: SessionPolicy,
+}
+
+/// the cargo features the service was built with
+fn features() -> Vec<String> {
+    let mut features = vec![];
+    if cfg!(feature = "profiling") {
+        features.push("profiling".to_owned());
+    }
+    features
+}
+
+/// the optional behavior the environment turned on
+fn flags() -> Vec<String> {
+    let mut flags = vec![];
+    if SERVICE_CONFIG.sentry_dsn.is_some() {
+        flags.push("error_reporting".to_owned());
+    }
+    if SERVICE_CONFIG.analytics_export_dir.is_some() {
+        flags.push("analytics_export".to_owned());
+    }
+    if SERVICE_CONFIG.chat_profanity_filter {
+        flags.push("chat_profanity_filter".to_owned());
+    }
+    flags
+}
+
+pub fn service_info() -> ServiceInfo {
+    ServiceInfo {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        git_commit: option_env!("CATAN_GIT_COMMIT").unwrap_or("unknown").to_owned(),
+        build_timestamp: option_env!("CATAN_BUILD_TIMESTAMP")
+            .and_then(|timestamp| timestamp.parse().ok())
+            .unwrap_or_default(),
+        features: features(),
+        flags: flags(),
+        supported_games: SUPPORTED_GAMES.to_vec(),
+        branding: SERVICE_CONFIG.branding.clone(),
+        session_policy: SERVICE_CONFIG.session_policy,
+    }
+}
+
+/// one line for the log at startup
+pub fn banner(info: &ServiceInfo) -> String {
+    let list = |items: &[String]| {
+        if items.is_empty() {
+            "none".to_owned()
+        } else {
+            items.join(",")
+        }
+    };
+    format!(
+        "catan_service {} [commit={}] [built={}] [features={}] [flags={}] [games={:?}]",
+        info.version,
+        info.git_commit,
+        info.build_timestamp,
+        list(&info.features),
+        list(&info.flags),
+        info.supported_games
+    )
+}
+
+pub async fn get_info() -> HttpResponse {
+    ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::ServiceInfo(service_info()),
+        GameError::NoError(String::default()),
+    )
+    .to_http_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_banner() {
+        let info = ServiceInfo {
+            version: "1.2.3".to_owned(),
+            git_commit: "abc123".to_owned(),
+            build_timestamp: 42,
+            features: vec![],
+            flags: vec!["error_reporting".to_owned(), "analytics_export".to_owned()],
+            supported_games: vec![CatanGames::Regular],
+            branding: Branding::default(),
+            session_policy: SessionPolicy::AllowAll,
+        };
+        assert_eq!(
+            banner(&info),
+            "catan_service 1.2.3 [commit=abc123] [built=42] [features=none] \
+             [flags=error_reporting,analytics_export] [games=[Regular]]"
+        );
+    }
+}
@@ -4,7 +4,19 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use azure_data_cosmos::CosmosEntity;
 use serde::{Deserialize, Serialize};
 
-use crate::{middleware::request_context_mw::TestContext, shared::shared_models::UserType};
+use crate::{
+    games_service::{
+        achievements::achievements::Achievement,
+        actions::reconnect,
+        catan_games::games::regular::regular_game::RegularGame,
+        game_container::snapshot_diff::GameSnapshotDiff, long_poller::channels::ChannelMessage,
+        shared::game_models::ResourceCards,
+    },
+    middleware::request_context_mw::TestContext, shared::shared_models::UserType,
+    user_service::{
+        automation_preferences::AutomationPreferences, notification_preferences::NotificationPreferences, oauth::ExternalIdentity, push_notifications::PushToken,
+    },
+};
 
 use super::shared_models::UserProfile;
 use uuid::Uuid;
@@ -40,6 +52,24 @@ pub struct PersistUser {
     pub user_profile: UserProfile,
     pub phone_code: Option<String>,
     pub roles: Vec<Role>,
+    #[serde(default)]
+    pub must_reset_password: bool, // imported users set a password before they can log in (see user_import.rs)
+    #[serde(default)]
+    pub push_tokens: Vec<PushToken>, // the user's devices, for push notifications (see push_notifications.rs)
+    #[serde(default)]
+    pub public_results: bool, // opted in to the public leaderboards, under an alias (see public_results.rs)
+    #[serde(default)]
+    pub notification_preferences: NotificationPreferences, // batching and quiet hours for pushes
+    #[serde(default)]
+    pub external_identities: Vec<ExternalIdentity>, // the Google/GitHub accounts the user logs in with (see oauth.rs)
+    #[serde(default)]
+    pub automation_preferences: AutomationPreferences, // what the service does for them in their games
+    #[serde(default)]
+    pub pending_email: Option<String>, // the address they are changing to, until they confirm it (see users.rs)
+    #[serde(default)]
+    pub keycloak_id: Option<String>, // the user made for them in the KeyCloak realm, if there is one (see kc_proxy.rs)
+    #[serde(default)]
+    pub counted_games: Vec<String>, // the last games counted in games_played/won, newest last (see game_over.rs)
 }
 
 impl PersistUser {
@@ -52,6 +82,15 @@ impl PersistUser {
             user_profile: UserProfile::default(),
             phone_code: None,
             roles: vec![Role::User],
+            must_reset_password: false,
+            push_tokens: Vec::new(),
+            public_results: false,
+            notification_preferences: NotificationPreferences::default(),
+            external_identities: Vec::new(),
+            automation_preferences: AutomationPreferences::default(),
+            pending_email: None,
+            keycloak_id: None,
+            counted_games: Vec::new(),
         }
     }
 
@@ -68,6 +107,15 @@ impl PersistUser {
             user_profile: profile.clone(),
             phone_code: None,
             roles: vec![Role::User],
+            must_reset_password: false,
+            push_tokens: Vec::new(),
+            public_results: false,
+            notification_preferences: NotificationPreferences::default(),
+            external_identities: Vec::new(),
+            automation_preferences: AutomationPreferences::default(),
+            pending_email: None,
+            keycloak_id: None,
+            counted_games: Vec::new(),
         }
     }
  
@@ -84,6 +132,15 @@ impl PersistUser {
             user_profile: profile.clone(),
             phone_code: None,
             roles: vec![Role::User],
+            must_reset_password: false,
+            push_tokens: Vec::new(),
+            public_results: false,
+            notification_preferences: NotificationPreferences::default(),
+            external_identities: Vec::new(),
+            automation_preferences: AutomationPreferences::default(),
+            pending_email: None,
+            keycloak_id: None,
+            counted_games: Vec::new(),
         }
     }
 
@@ -108,6 +165,254 @@ impl Default for PersistUser {
     }
 }
 
+impl CosmosEntity for PersistGame {
+    type Entity = u64;
+
+    fn partition_key(&self) -> Self::Entity {
+        self.partition_key
+    }
+}
+
+/**
+ * a finished game as it is stored in the Game collection.  it has the final board plus who played and who won, so it
+ * is also the match history record for each of the players.  the changes from one action to the next are kept too,
+ * so the game can be replayed (see game_history.rs)
+ */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PersistGame {
+    pub id: String, // the game id
+    #[serde(rename = "partitionKey")]
+    pub partition_key: u64,
+    pub player_ids: Vec<String>,
+    pub winner_id: Option<String>,
+    pub finished_at: u64, // seconds since the UNIX epoch
+    pub game: RegularGame,
+    #[serde(default)]
+    pub history: Vec<GameSnapshotDiff>,
+    #[serde(default)]
+    pub deleted_player_ids: Vec<String>, // the players whose accounts have since been deleted (see integrity.rs)
+}
+
+impl PersistGame {
+    pub fn from_game(game: &RegularGame, winner_id: Option<String>) -> Self {
+        Self {
+            id: game.id.clone(),
+            partition_key: 1,
+            player_ids: game.players.keys().cloned().collect(),
+            winner_id,
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            game: game.clone(),
+            history: Vec::new(),
+            deleted_player_ids: Vec::new(),
+        }
+    }
+}
+
+impl CosmosEntity for PersistGameEvent {
+    type Entity = String;
+
+    fn partition_key(&self) -> Self::Entity {
+        self.partition_key.clone()
+    }
+}
+
+/// events written before they were partitioned by game all have the partitionKey 1
+fn event_partition_key<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(partition_key) => partition_key,
+        other => other.to_string(),
+    })
+}
+
+/**
+ * one step in a game, as it is stored in the GameEvent collection: the action, who did it (None when the service did
+ * it, eg. a timer), who they did it for if it was somebody else's turn they were playing, and a json diff from the game
+ * before it.  an admin's repair of the game also has their reason (see surgery.rs).  see event_log.rs
+ */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PersistGameEvent {
+    pub id: String, // "{game_id}:{game_index}"
+    #[serde(rename = "partitionKey", deserialize_with = "event_partition_key")]
+    pub partition_key: String, // the game id, so a game's events are written and read together
+    pub game_id: String,
+    pub game_index: u32,
+    pub action: String,
+    pub actor_id: Option<String>,
+    #[serde(default)]
+    pub on_behalf_of: Option<String>, // the player whose seat actor_id was playing (see RegularGame::delegate)
+    pub at: u64, // seconds since the UNIX epoch
+    pub diff: serde_json::Value,
+    #[serde(default)]
+    pub note: Option<String>, // why an admin changed the game by hand
+}
+
+impl CosmosEntity for PersistGameStats {
+    type Entity = u64;
+
+    fn partition_key(&self) -> Self::Entity {
+        self.partition_key
+    }
+}
+
+/**
+ * how one player did in one finished game, as it is stored in the GameStats collection.  games_played and games_won
+ * stay in the profile; these are the details behind them (see user_stats.rs)
+ */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PersistGameStats {
+    pub id: String, // "{game_id}:{user_id}"
+    #[serde(rename = "partitionKey")]
+    pub partition_key: u64,
+    pub game_id: String,
+    pub user_id: String,
+    pub finished_at: u64, // seconds since the UNIX epoch
+    pub won: bool,
+    pub forfeited: bool,
+    pub victory_points: u32,
+    pub resources_collected: ResourceCards,
+    pub dev_cards_played: u32,
+    pub knights_played: u32,
+    pub longest_road: u32,
+    #[serde(default)]
+    pub reconnects: u32, // times the player came back after their connection dropped (see reconnect.rs)
+}
+
+impl PersistGameStats {
+    /// the player's stats in a game that has just ended.  None if they weren't in it
+    pub fn from_game(game: &RegularGame, user_id: &str, winner_id: Option<&str>) -> Option<Self> {
+        let player = game.players.get(user_id)?;
+        Some(Self {
+            id: format!("{}:{}", game.id, user_id),
+            partition_key: 1,
+            game_id: game.id.clone(),
+            user_id: user_id.to_owned(),
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            won: winner_id == Some(user_id),
+            forfeited: game.has_forfeited(user_id),
+            victory_points: game.victory_points(user_id),
+            resources_collected: player.resources_collected.clone(),
+            dev_cards_played: player.dev_cards_played,
+            knights_played: game.knights_played(user_id) as u32,
+            longest_road: game.longest_road_for(user_id) as u32,
+            reconnects: reconnect::reconnects(&game.id, user_id),
+        })
+    }
+}
+
+impl CosmosEntity for PersistAchievement {
+    type Entity = u64;
+
+    fn partition_key(&self) -> Self::Entity {
+        self.partition_key
+    }
+}
+
+/**
+ * an achievement a user has unlocked, as it is stored in the Achievement collection.  the id is made from the user and
+ * the achievement, so each can only be unlocked once (see achievements.rs)
+ */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PersistAchievement {
+    pub id: String, // "{user_id}:{achievement:?}"
+    #[serde(rename = "partitionKey")]
+    pub partition_key: u64,
+    pub user_id: String,
+    pub achievement: Achievement,
+    pub game_id: String,   // the game it was unlocked in
+    pub unlocked_at: u64, // seconds since the UNIX epoch
+}
+
+impl PersistAchievement {
+    pub fn new(user_id: &str, achievement: Achievement, game_id: &str, unlocked_at: u64) -> Self {
+        Self {
+            id: format!("{}:{:?}", user_id, achievement),
+            partition_key: 1,
+            user_id: user_id.to_owned(),
+            achievement,
+            game_id: game_id.to_owned(),
+            unlocked_at,
+        }
+    }
+}
+
+impl CosmosEntity for PersistOutboxMessage {
+    type Entity = u64;
+
+    fn partition_key(&self) -> Self::Entity {
+        self.partition_key
+    }
+}
+
+/**
+ * a message for a user who wasn't connected when it was sent, as it is stored in the Outbox collection until they log
+ * in again (see outbox.rs).  Cosmos deletes it ttl seconds after it was written if nobody has collected it
+ */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PersistOutboxMessage {
+    pub id: String,
+    #[serde(rename = "partitionKey")]
+    pub partition_key: u64,
+    pub user_id: String,
+    pub boot_id: String, // the run of the service that numbered the message
+    pub message: ChannelMessage,
+    pub queued_at: u64, // seconds since the UNIX epoch
+    pub ttl: u64,       // seconds
+}
+
+impl PersistOutboxMessage {
+    pub fn new(user_id: &str, boot_id: &str, message: &ChannelMessage, queued_at: u64, ttl: u64) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            partition_key: 1,
+            user_id: user_id.to_owned(),
+            boot_id: boot_id.to_owned(),
+            message: message.clone(),
+            queued_at,
+            ttl,
+        }
+    }
+
+    pub fn expired(&self, now: u64) -> bool {
+        self.queued_at + self.ttl <= now
+    }
+}
+
+impl CosmosEntity for PersistRefreshToken {
+    type Entity = String;
+
+    fn partition_key(&self) -> Self::Entity {
+        self.partition_key.clone()
+    }
+}
+
+/**
+ *  a refresh token, as it is stored in the RefreshToken collection (see refresh_tokens.rs).  the token itself is never
+ *  stored: the id is the SHA-256 of it.  every token from one login is in the same family, so reusing one that has
+ *  already been exchanged can revoke them all.  a user's tokens share a partition (the user id), so they can all be
+ *  found, and deleted, cheaply.  Cosmos deletes it ttl seconds after it was written
+ */
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct PersistRefreshToken {
+    pub id: String, // hex SHA-256 of the token
+    #[serde(rename = "partitionKey")]
+    pub partition_key: String, // the user id
+    pub user_id: String,
+    pub family_id: String,
+    pub session_id: String, // the session the login started (see sessions.rs)
+    pub issued_at: u64,     // seconds since the UNIX epoch
+    pub expires_at: u64,
+    pub used: bool, // exchanged for a new one.  a used token that comes back has been stolen, or replayed
+    pub ttl: u64,   // seconds
+    #[serde(rename = "_etag", default, skip_serializing)]
+    pub etag: Option<String>, // Cosmos's version of the document when it was read -- None if it wasn't
+}
+
 //
 //  an enum of roles that a user can be in
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
@@ -116,8 +421,14 @@ pub enum Role {
     User,
     TestUser,
     Validation,
+    PasswordReset, // the claims in a password reset link
+    EmailChange,   // the claims in the link that confirms a new email -- the sub is the new address
+    Service, // an internal component (a background worker, the webhook dispatcher...), never a person
 }
 
+/// the audience of every service token -- user tokens don't have one
+pub const SERVICE_AUDIENCE: &str = "catan-internal";
+
 // DO NOT ADD A #[serde(rename_all = "PascalCase")] macro to this struct!
 // it will throw an error and you'll spend hours figuring out why it doesn't work - the rust bcrypt library cares about
 // capitalization and enforces standard claim names
@@ -128,6 +439,19 @@ pub struct Claims {
     pub exp: usize,
     pub roles: Vec<Role>,
     pub test_context: Option<TestContext>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>, // login tokens only: the session the token belongs to (see sessions.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seat: Option<SeatScope>, // seat tokens only: the one seat the token plays (see seat_tokens.rs)
+}
+
+/// the local user, and the game, a seat token plays
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct SeatScope {
+    pub game_id: String,
+    pub local_user_id: String,
 }
 
 impl Claims {
@@ -148,6 +472,32 @@ impl Claims {
             exp,
             roles: roles.clone(),
             test_context: test_context.clone(),
+            aud: None,
+            session_id: None,
+            seat: None,
         }
     }
+
+    /// the claims of a service token: the id is the name of the component, prefixed with "service:" so that it can
+    /// never be mistaken for a user id
+    pub fn new_service(service_name: &str, duration_secs: u64) -> Self {
+        let id = format!("service:{}", service_name);
+        let mut claims = Self::new(&id, &id, duration_secs, &vec![Role::Service], &None);
+        claims.aud = Some(SERVICE_AUDIENCE.to_owned());
+        claims
+    }
+
+    /// the claims of a seat token: the owner's login, limited to playing the local user's seat in the game, and never
+    /// with more than the User role.  it is part of the owner's session, so it stops working when that session ends
+    pub fn new_seat(owner: &Claims, seat: SeatScope, duration_secs: u64) -> Self {
+        let mut claims = Self::new(&owner.id, &owner.sub, duration_secs, &vec![Role::User], &owner.test_context);
+        claims.session_id = owner.session_id.clone();
+        claims.seat = Some(seat);
+        claims
+    }
+
+    /// true if these claims came from a service token rather than a user's login
+    pub fn is_service(&self) -> bool {
+        self.aud.as_deref() == Some(SERVICE_AUDIENCE) && self.roles.contains(&Role::Service)
+    }
 }
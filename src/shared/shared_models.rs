@@ -8,6 +8,7 @@ use actix_web::HttpResponse;
 use reqwest::StatusCode;
 
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use strum_macros::Display;
 
 use std::{fmt, fmt::Display, fmt::Formatter, sync::Arc};
@@ -15,13 +16,42 @@ use tokio::sync::{mpsc, RwLock};
 
 use anyhow::Result;
 
+use crate::cosmos_db::connection_manager::DbHealth;
+use crate::middleware::usage_tracker::{UsageSummary, UserUsage};
+use crate::shared::analytics_export::ExportReport;
+use crate::shared::integrity::IntegrityReport;
+use crate::shared::service_info::ServiceInfo;
+use crate::games_service::long_poller::presence::PresenceData;
+use crate::shared::status_page::{Announcement, ServiceStatus};
+use crate::user_service::user_import::ImportReport;
+use crate::user_service::user_stats::UserStats;
+use crate::user_service::refresh_tokens::LoginTokens;
+use crate::user_service::automation_preferences::AutomationPreferences;
+use crate::user_service::password_policy::PasswordProblem;
+use crate::user_service::notification_preferences::NotificationPreferences;
+use crate::games_service::webhooks::GameWebhook;
+use crate::shared::client_telemetry::ClientErrorSummary;
 use crate::games_service::{
     catan_games::games::regular::regular_game::RegularGame,
-    game_container::game_messages::CatanMessage,
-    shared::game_enums::{CatanGames, GameAction},
+    game_container::{
+        game_history::GameHistory,
+        game_messages::{CatanMessage, RejoinState},
+        snapshot_diff::GameSnapshotDiff,
+    },
+    lobby::game_browser::GameBrowserPage,
+    long_poller::channels::ChannelMessage,
+    shared::{
+        game_enums::{CatanGames, GameAction},
+        game_models::{ActionExplanation, GameMember, TradeOffer},
+    },
 };
 
-use super::service_models::PersistUser;
+use super::{
+    error_reporting,
+    log_filter::LogFilter,
+    profiling::{self, HotPath, ProfilingReport},
+    service_models::PersistUser,
+};
 
 //
 //  this also supports Eq, PartialEq, Clone, Serialize, and Deserialize via custom implementation
@@ -35,11 +65,13 @@ pub enum GameError {
     ActionError(String),
     TooFewPlayers(usize),
     TooManyPlayers(usize),
+    GameFull(Vec<String>), // the players in the game when the caller tried to take a seat
     ReqwestError(String),
     NoError(String),
     #[serde(serialize_with = "serialize_status_code")]
     #[serde(deserialize_with = "deserialize_status_code")]
     HttpError(reqwest::StatusCode),
+    AccountLocked(u64), // too many failed logins: the seconds until the account can log in again (see login_guard.rs)
     AzError(String),
     SerdeError(String),
     AzureCoreError(String),
@@ -85,9 +117,11 @@ impl fmt::Display for GameError {
             GameError::BadId(desc) => write!(f, "Bad Id {}", desc),
             GameError::TooFewPlayers(s) => write!(f, "Min Players {}", s),
             GameError::TooManyPlayers(c) => write!(f, "Max Players {}", c),
+            GameError::GameFull(players) => write!(f, "Game Full: {:?}", players),
             GameError::ReqwestError(c) => write!(f, "ReqwestError error: {}", c),
             GameError::NoError(s) => write!(f, "Success!: {}", s),
             GameError::HttpError(code) => write!(f, "HttpError. {:#?}", code),
+            GameError::AccountLocked(seconds) => write!(f, "Account Locked for {} seconds", seconds),
             GameError::AzError(e) => write!(f, "AzError: {:#?}", e),
             GameError::SerdeError(e) => write!(f, "Serde Error: {:#?}", e),
             GameError::AzureCoreError(e) => write!(f, "Azure Core error: {:#?}", e),
@@ -99,14 +133,14 @@ impl fmt::Display for GameError {
 /// Connected users are must be actively connected to the system and particpate in long_polling
 /// LocalUsers do not, and instead get messages on the creators thread.  Only local users for the creater
 /// should be shown by the client
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub enum UserType {
     Connected,
     Local,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct PersonalInformation {
     pub phone_number: String,
@@ -132,7 +166,7 @@ impl PersonalInformation {
 ///
 /// UserProfile is just information about the client.  this can be as much or little information as the app needs
 /// to run
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct UserProfile {
     pub user_id: Option<String>,
@@ -147,6 +181,8 @@ pub struct UserProfile {
     pub games_won: Option<u16>,
     pub validated_email: bool,         // has the mail been validated?
     pub validated_phone: bool,         // has the phone number been validated?
+    #[serde(default)]
+    pub blocked_user_ids: Vec<String>, // users whose direct messages are dropped.  see direct_messages.rs
 }
 impl Default for UserProfile {
     fn default() -> Self {
@@ -162,7 +198,8 @@ impl Default for UserProfile {
             games_played: None,
             games_won: None,
             validated_email: false,
-            validated_phone: false
+            validated_phone: false,
+            blocked_user_ids: Vec::new(),
         }
     }
 }
@@ -291,7 +328,8 @@ impl UserProfile {
             games_played: None,
             games_won: None,
             validated_email: false,
-            validated_phone: false
+            validated_phone: false,
+            blocked_user_ids: Vec::new(),
         }
     }
 }
@@ -301,15 +339,42 @@ pub enum ResponseType {
     Profile(UserProfile),
     Profiles(Vec<UserProfile>),
     Token(String),
+    LoginTokens(LoginTokens),
     Url(String),
     ErrorInfo(String),
     Todo(String),
     NoData,
     ValidActions(Vec<GameAction>),
+    ActionExplanation(ActionExplanation),
+    TradeOffer(TradeOffer),
+    GameMembers(Vec<GameMember>),
     Game(RegularGame),
+    DbHealth(DbHealth),
+    LogFilter(LogFilter),
+    Usage(UserUsage),
+    UsageSummary(UsageSummary),
+    AnalyticsExport(ExportReport),
+    IntegrityReport(IntegrityReport),
+    SnapshotDiff(GameSnapshotDiff),
+    GameHistory(GameHistory),
+    GameBrowser(GameBrowserPage),
+    RejoinState(RejoinState),
+    UserStats(UserStats),
+    UserImport(ImportReport),
+    Webhook(GameWebhook),
+    ClientErrors(ClientErrorSummary),
+    NotificationPreferences(NotificationPreferences),
+    AutomationPreferences(AutomationPreferences),
+    PasswordProblems(Vec<PasswordProblem>),
+    ServiceInfo(ServiceInfo),
+    ServiceStatus(ServiceStatus),
+    Announcements(Vec<Announcement>),
+    Presence(Vec<PresenceData>),
+    ProfilingReport(ProfilingReport),
     SupportedGames(Vec<CatanGames>),
     SendMessageError(Vec<(String, GameError)>),
     ServiceMessage(CatanMessage),
+    ChannelMessage(ChannelMessage),
     AzError(String),
     SerdeError(String),
 }
@@ -388,7 +453,10 @@ impl ServiceResponse {
     }
 
     pub fn to_http_response(&self) -> HttpResponse {
+        error_reporting::report_service_error(self);
+        let timer = profiling::timer(HotPath::Serialization);
         let serialized = serde_json::to_string(self).expect("Failed to serialize ServiceResponse");
+        drop(timer);
 
         let response = HttpResponse::build(self.status).body(serialized);
         response
@@ -444,6 +512,7 @@ impl ServiceResponse {
         // Extract auth token from response
         match &self.response_type {
             ResponseType::Token(token) => Some(token.clone()),
+            ResponseType::LoginTokens(tokens) => Some(tokens.access_token.clone()),
             _ => None,
         }
     }
@@ -473,9 +542,22 @@ impl ServiceResponse {
             _ => None,
         }
     }
+    pub fn get_action_explanation(&self) -> Option<ActionExplanation> {
+        match &self.response_type {
+            ResponseType::ActionExplanation(explanation) => Some(explanation.clone()),
+            _ => None,
+        }
+    }
+    pub fn get_trade_offer(&self) -> Option<TradeOffer> {
+        match &self.response_type {
+            ResponseType::TradeOffer(offer) => Some(offer.clone()),
+            _ => None,
+        }
+    }
     pub fn get_service_message(&self) -> Option<CatanMessage> {
         match &self.response_type {
             ResponseType::ServiceMessage(msg) => Some(msg.clone()),
+            ResponseType::ChannelMessage(channel_message) => Some(channel_message.message.clone()),
             _ => None,
         }
     }
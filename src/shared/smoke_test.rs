@@ -0,0 +1,327 @@
+#![allow(dead_code)]
+/**
+ *  the post-deploy smoke test: `catan_service --smoke-test <url> [report.xml]` runs a short scripted game against a
+ *  live service through ServiceProxy and writes a junit report, so a release pipeline can tell whether the deployment
+ *  works before anybody plays on it.  it doesn't need the service's secrets or database -- only the url.
+ *
+ *  the scenario: register a throwaway user, log in, create a game, fill it with bots, start it and take a few turns,
+ *  then delete the bots and the user.  each of those is a test case in the report.  a step that fails stops the
+ *  scenario -- the ones after it are reported as skipped -- but the clean up always runs for whatever was created.
+ *
+ *  the connection is TLS with the certificate checked (reqwest's default), so a deployment with a bad certificate
+ *  fails the smoke test like it would fail a real client.
+ */
+use std::time::Instant;
+
+use crate::games_service::{bots::engine::BotDifficulty, shared::game_enums::CatanGames};
+
+use super::{
+    proxy::ServiceProxy,
+    shared_models::{ServiceResponse, UserProfile},
+};
+
+/// the report is written here if the caller doesn't say where
+pub const DEFAULT_REPORT: &str = "smoke-test-report.xml";
+/// how many times the creator moves the game on once it has started
+const SMOKE_TURNS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmokeOutcome {
+    Passed,
+    Failed(String),
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmokeStep {
+    pub name: String,
+    pub seconds: f64,
+    pub outcome: SmokeOutcome,
+}
+
+#[derive(Debug, Default)]
+pub struct SmokeReport {
+    pub host: String,
+    pub steps: Vec<SmokeStep>,
+}
+
+impl SmokeReport {
+    pub fn passed(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| matches!(step.outcome, SmokeOutcome::Passed))
+    }
+
+    fn failures(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step.outcome, SmokeOutcome::Failed(_)))
+            .count()
+    }
+
+    fn skipped(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| step.outcome == SmokeOutcome::Skipped)
+            .count()
+    }
+
+    /// the report as junit xml: one testsuite, one testcase per step
+    pub fn to_junit(&self) -> String {
+        let total: f64 = self.steps.iter().map(|step| step.seconds).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"smoke-test\" hostname=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(&self.host),
+            self.steps.len(),
+            self.failures(),
+            self.skipped(),
+            total
+        ));
+        for step in self.steps.iter() {
+            let name = xml_escape(&step.name);
+            match &step.outcome {
+                SmokeOutcome::Passed => xml.push_str(&format!(
+                    "  <testcase classname=\"smoke\" name=\"{}\" time=\"{:.3}\"/>\n",
+                    name, step.seconds
+                )),
+                SmokeOutcome::Failed(message) => xml.push_str(&format!(
+                    "  <testcase classname=\"smoke\" name=\"{}\" time=\"{:.3}\">\n    <failure message=\"{}\"/>\n  </testcase>\n",
+                    name,
+                    step.seconds,
+                    xml_escape(message)
+                )),
+                SmokeOutcome::Skipped => xml.push_str(&format!(
+                    "  <testcase classname=\"smoke\" name=\"{}\" time=\"0.000\">\n    <skipped/>\n  </testcase>\n",
+                    name
+                )),
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// the step failed unless the service said it worked
+fn check(response: &ServiceResponse) -> Result<(), String> {
+    if response.status.is_success() {
+        Ok(())
+    } else {
+        Err(format!("{}: {}", response.status, response.message))
+    }
+}
+
+/// runs the steps in order, timing each, and skips the rest once one fails
+struct Steps {
+    report: SmokeReport,
+    failed: bool,
+}
+
+impl Steps {
+    async fn run<T, F>(&mut self, name: &str, step: F) -> Option<T>
+    where
+        F: std::future::Future<Output = Result<T, String>>,
+    {
+        if self.failed {
+            self.record(name, 0.0, SmokeOutcome::Skipped);
+            return None;
+        }
+        let start = Instant::now();
+        let result = step.await;
+        let seconds = start.elapsed().as_secs_f64();
+        match result {
+            Ok(value) => {
+                self.record(name, seconds, SmokeOutcome::Passed);
+                Some(value)
+            }
+            Err(message) => {
+                self.failed = true;
+                self.record(name, seconds, SmokeOutcome::Failed(message));
+                None
+            }
+        }
+    }
+
+    /// clean up runs whatever happened before it
+    async fn cleanup<F>(&mut self, name: &str, step: F)
+    where
+        F: std::future::Future<Output = Result<(), String>>,
+    {
+        let start = Instant::now();
+        let outcome = match step.await {
+            Ok(()) => SmokeOutcome::Passed,
+            Err(message) => SmokeOutcome::Failed(message),
+        };
+        self.record(name, start.elapsed().as_secs_f64(), outcome);
+    }
+
+    fn record(&mut self, name: &str, seconds: f64, outcome: SmokeOutcome) {
+        tracing::info!("smoke test: {} {:?}", name, outcome);
+        self.report.steps.push(SmokeStep {
+            name: name.to_owned(),
+            seconds,
+            outcome,
+        });
+    }
+}
+
+/// run the scenario against the service at host
+pub async fn run(host: &str) -> SmokeReport {
+    let mut steps = Steps {
+        report: SmokeReport {
+            host: host.to_owned(),
+            steps: Vec::new(),
+        },
+        failed: false,
+    };
+    let password = format!("Smoke-{}", uuid::Uuid::new_v4());
+    let mut profile = UserProfile::new_test_user(None);
+    profile.display_name = "Smoke Test".to_owned();
+    let email = profile.pii.as_ref().map(|pii| pii.email.clone()).unwrap_or_default();
+
+    let anonymous = ServiceProxy::new_non_auth(None, host);
+    let registered = steps
+        .run("register", async {
+            let response = anonymous.register(&profile, &password).await;
+            check(&response)?;
+            response
+                .to_profile()
+                .and_then(|profile| profile.user_id)
+                .ok_or_else(|| "register didn't return the new user's id".to_owned())
+        })
+        .await;
+
+    let proxy = steps
+        .run("login", async {
+            ServiceProxy::new(&email, &password, None, host)
+                .await
+                .map_err(|sr| format!("{}: {}", sr.status, sr.message))
+        })
+        .await;
+
+    let game_id = steps
+        .run("create game", async {
+            let proxy = proxy.as_ref().ok_or_else(|| "not logged in".to_owned())?;
+            let response = proxy.new_game(CatanGames::Regular, None).await;
+            check(&response)?;
+            response
+                .get_game()
+                .map(|game| game.id)
+                .ok_or_else(|| "new game didn't return the game".to_owned())
+        })
+        .await;
+
+    let mut bots = Vec::new();
+    for n in 0..2 {
+        let bot = steps
+            .run(&format!("add bot {}", n + 1), async {
+                let (proxy, game_id) = match (&proxy, &game_id) {
+                    (Some(proxy), Some(game_id)) => (proxy, game_id),
+                    _ => return Err("no game".to_owned()),
+                };
+                let response = proxy.add_bot(game_id, BotDifficulty::Easy).await;
+                check(&response)?;
+                Ok(response.to_profile().and_then(|profile| profile.user_id))
+            })
+            .await;
+        bots.extend(bot.flatten());
+    }
+
+    steps
+        .run("start game", async {
+            let (proxy, game_id) = match (&proxy, &game_id) {
+                (Some(proxy), Some(game_id)) => (proxy, game_id),
+                _ => return Err("no game".to_owned()),
+            };
+            check(&proxy.start_game(game_id).await)
+        })
+        .await;
+
+    for turn in 0..SMOKE_TURNS {
+        steps
+            .run(&format!("turn {}", turn + 1), async {
+                let (proxy, game_id) = match (&proxy, &game_id) {
+                    (Some(proxy), Some(game_id)) => (proxy, game_id),
+                    _ => return Err("no game".to_owned()),
+                };
+                check(&proxy.next(game_id).await)
+            })
+            .await;
+    }
+
+    //
+    //  clean up whatever was made, even if the scenario failed part way
+    if let Some(proxy) = &proxy {
+        for bot_id in bots.iter() {
+            steps
+                .cleanup(&format!("delete bot {}", bot_id), async {
+                    check(&proxy.delete_local_user(bot_id).await)
+                })
+                .await;
+        }
+        if let Some(user_id) = &registered {
+            steps
+                .cleanup("delete user", async { check(&proxy.delete_user(user_id).await) })
+                .await;
+        }
+    } else if registered.is_some() {
+        steps
+            .cleanup("delete user", async {
+                Err(format!("couldn't log in to delete {}", email))
+            })
+            .await;
+    }
+    steps.report
+}
+
+/// run the smoke test and write the report.  returns false if any step failed
+pub async fn run_and_report(host: &str, report_path: &str) -> bool {
+    let report = run(host).await;
+    if let Err(e) = std::fs::write(report_path, report.to_junit()) {
+        tracing::error!("couldn't write the smoke test report to {}: {:#?}", report_path, e);
+        return false;
+    }
+    report.passed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_report() {
+        let report = SmokeReport {
+            host: "https://example.com".to_owned(),
+            steps: vec![
+                SmokeStep {
+                    name: "register".to_owned(),
+                    seconds: 0.25,
+                    outcome: SmokeOutcome::Passed,
+                },
+                SmokeStep {
+                    name: "login".to_owned(),
+                    seconds: 0.5,
+                    outcome: SmokeOutcome::Failed("401 <Unauthorized>".to_owned()),
+                },
+                SmokeStep {
+                    name: "create game".to_owned(),
+                    seconds: 0.0,
+                    outcome: SmokeOutcome::Skipped,
+                },
+            ],
+        };
+        assert!(!report.passed());
+        let xml = report.to_junit();
+        assert!(xml.contains("tests=\"3\" failures=\"1\" skipped=\"1\" time=\"0.750\""));
+        assert!(xml.contains("<failure message=\"401 &lt;Unauthorized&gt;\"/>"));
+        assert!(xml.contains("<testcase classname=\"smoke\" name=\"register\" time=\"0.250\"/>"));
+    }
+}
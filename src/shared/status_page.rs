@@ -0,0 +1,370 @@
+#![allow(dead_code)]
+/**
+ *  the data for a public status page: GET /api/v1/status needs no login and says how each part of the service is
+ *  doing, what incidents or maintenance the operators have announced, and how often the database was reachable over
+ *  the last day and week.
+ *
+ *  it is written for the public, so it only has a status per component -- never the errors, hosts or credentials
+ *  behind it (those are in /ready and the admin apis).  the components are
+ *      Database:           the last health checks of the database (see ConnectionManager)
+ *      Notifications:      the long poller, which carries every message to the clients
+ *      Background Jobs:    matchmaking and the analytics export
+ *  a component the service didn't start (because it runs without the lifecycle, in tests) is Unknown.
+ *
+ *  announcements are kept in memory, so they have to be posted again after a restart.  admins manage them with
+ *  /auth/api/v1/admin/status/announcements.
+ */
+use std::{
+    collections::VecDeque,
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cosmos_db::connection_manager::{ConnectionManager, DbHealth},
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        lifecycle::{self, ComponentState},
+        service_models::Role,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+pub const DAY_SECONDS: u64 = 24 * 60 * 60;
+pub const WEEK_SECONDS: u64 = 7 * DAY_SECONDS;
+/// enough for a week of health checks a minute apart
+const MAX_CHECKS: usize = 7 * 24 * 60;
+
+lazy_static::lazy_static! {
+    static ref STARTED_AT: u64 = now();
+    static ref CHECKS: RwLock<VecDeque<(u64, bool)>> = RwLock::new(VecDeque::new());
+    static ref ANNOUNCEMENTS: RwLock<Vec<Announcement>> = RwLock::new(Vec::new());
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentStatus {
+    Operational,
+    Degraded,
+    Outage,
+    Unknown,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublicComponent {
+    pub name: String,
+    pub status: ComponentStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AnnouncementKind {
+    Incident,
+    Maintenance,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Announcement {
+    #[serde(default)]
+    pub id: String, // set by the service
+    pub kind: AnnouncementKind,
+    pub title: String,
+    pub message: String,
+    pub starts_at: u64,       // seconds since the UNIX epoch.  0 means now
+    pub ends_at: Option<u64>, // None until it is over
+}
+
+impl Announcement {
+    pub fn is_current(&self, now: u64) -> bool {
+        self.starts_at <= now && self.ends_at.map_or(true, |ends_at| now < ends_at)
+    }
+}
+
+/// how many health checks ran over the last period_seconds, and how many of them found the database reachable
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct UptimeWindow {
+    pub period_seconds: u64,
+    pub checks: u32,
+    pub healthy_checks: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServiceStatus {
+    pub status: ComponentStatus, // the worst of the components
+    pub components: Vec<PublicComponent>,
+    pub announcements: Vec<Announcement>,
+    pub uptime: Vec<UptimeWindow>,
+    pub running_since: u64, // seconds since the UNIX epoch
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// start counting the time the service has been running from now
+pub fn start_clock() {
+    lazy_static::initialize(&STARTED_AT);
+}
+
+/// remember the result of a database health check, for the uptime
+pub fn record_check(checked_at: u64, healthy: bool) {
+    let mut checks = CHECKS.write().unwrap();
+    if checks.len() >= MAX_CHECKS {
+        checks.pop_front();
+    }
+    checks.push_back((checked_at, healthy));
+}
+
+pub fn uptime(checks: &VecDeque<(u64, bool)>, period_seconds: u64, now: u64) -> UptimeWindow {
+    let recent = checks
+        .iter()
+        .filter(|(checked_at, _)| *checked_at + period_seconds > now);
+    let (checks, healthy_checks) = recent.fold((0, 0), |(checks, healthy), (_, ok)| {
+        (checks + 1, healthy + if *ok { 1 } else { 0 })
+    });
+    UptimeWindow {
+        period_seconds,
+        checks,
+        healthy_checks,
+    }
+}
+
+/// a couple of failed checks in a row is Degraded -- the next one may well work -- more than that is an Outage
+pub fn database_status(health: &DbHealth) -> ComponentStatus {
+    match (health.last_checked, health.consecutive_failures) {
+        (None, _) => ComponentStatus::Unknown,
+        (Some(_), 0) => ComponentStatus::Operational,
+        (Some(_), 1..=2) => ComponentStatus::Degraded,
+        _ => ComponentStatus::Outage,
+    }
+}
+
+/// how a subsystem started by the lifecycle is doing.  optional subsystems that failed leave the service Degraded
+pub fn subsystem_status(name: &str, components: &[lifecycle::ComponentReport]) -> ComponentStatus {
+    match components.iter().find(|report| report.name == name) {
+        None => ComponentStatus::Unknown,
+        Some(report) => match (&report.state, report.required) {
+            (ComponentState::Started, _) => ComponentStatus::Operational,
+            (ComponentState::NotStarted, _) => ComponentStatus::Unknown,
+            (_, false) => ComponentStatus::Degraded,
+            (_, true) => ComponentStatus::Outage,
+        },
+    }
+}
+
+/// the worst status of the components that are known
+pub fn overall(components: &[PublicComponent]) -> ComponentStatus {
+    let statuses = || components.iter().map(|component| component.status);
+    if statuses().any(|status| status == ComponentStatus::Outage) {
+        ComponentStatus::Outage
+    } else if statuses().any(|status| status == ComponentStatus::Degraded) {
+        ComponentStatus::Degraded
+    } else if statuses().all(|status| status == ComponentStatus::Unknown) {
+        ComponentStatus::Unknown
+    } else {
+        ComponentStatus::Operational
+    }
+}
+
+pub fn service_status() -> ServiceStatus {
+    let now = now();
+    let subsystems = lifecycle::components();
+    let components = vec![
+        PublicComponent {
+            name: "Database".to_owned(),
+            status: database_status(&ConnectionManager::health()),
+        },
+        PublicComponent {
+            name: "Notifications".to_owned(),
+            status: subsystem_status("long poller", &subsystems),
+        },
+        PublicComponent {
+            name: "Background Jobs".to_owned(),
+            status: subsystem_status("job scheduler", &subsystems),
+        },
+    ];
+    let checks = CHECKS.read().unwrap();
+    ServiceStatus {
+        status: overall(&components),
+        components,
+        announcements: ANNOUNCEMENTS
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|announcement| announcement.is_current(now))
+            .cloned()
+            .collect(),
+        uptime: vec![uptime(&checks, DAY_SECONDS, now), uptime(&checks, WEEK_SECONDS, now)],
+        running_since: *STARTED_AT,
+    }
+}
+
+pub async fn get_status() -> HttpResponse {
+    ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::ServiceStatus(service_status()),
+        GameError::NoError(String::default()),
+    )
+    .to_http_response()
+}
+
+fn announcements_response(announcements: Vec<Announcement>) -> ServiceResponse {
+    ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::Announcements(announcements),
+        GameError::NoError(String::default()),
+    )
+}
+
+/// every announcement, including the ones that are over or haven't started
+pub fn list_announcements(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    Ok(announcements_response(ANNOUNCEMENTS.read().unwrap().clone()))
+}
+
+/// add an announcement, or replace the one with the same id (to end it, set its EndsAt)
+pub fn post_announcement(
+    announcement: &Announcement,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    if announcement.title.trim().is_empty() {
+        return Err(ServiceResponse::new(
+            "an announcement needs a title",
+            StatusCode::BAD_REQUEST,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::BAD_REQUEST),
+        ));
+    }
+    let mut announcement = announcement.clone();
+    if announcement.id.is_empty() {
+        announcement.id = uuid::Uuid::new_v4().to_string();
+    }
+    if announcement.starts_at == 0 {
+        announcement.starts_at = now();
+    }
+    let mut announcements = ANNOUNCEMENTS.write().unwrap();
+    announcements.retain(|existing| existing.id != announcement.id);
+    announcements.push(announcement);
+    Ok(announcements_response(announcements.clone()))
+}
+
+pub fn delete_announcement(id: &str, request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    let mut announcements = ANNOUNCEMENTS.write().unwrap();
+    let before = announcements.len();
+    announcements.retain(|existing| existing.id != id);
+    if announcements.len() == before {
+        return Err(ServiceResponse::new(
+            &format!("no announcement {}", id),
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        ));
+    }
+    Ok(announcements_response(announcements.clone()))
+}
+
+pub async fn list_announcements_handler(request_context: RequestContext) -> HttpResponse {
+    list_announcements(&request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn post_announcement_handler(
+    announcement: web::Json<Announcement>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    post_announcement(&announcement, &request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn delete_announcement_handler(id: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    delete_announcement(&id, &request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::lifecycle::ComponentReport;
+
+    #[test]
+    fn test_status() {
+        let checks: VecDeque<(u64, bool)> =
+            vec![(1_000, false), (DAY_SECONDS + 10, true), (DAY_SECONDS + 20, false), (DAY_SECONDS + 30, true)]
+                .into_iter()
+                .collect();
+        let now = DAY_SECONDS + 100;
+        assert_eq!(
+            uptime(&checks, DAY_SECONDS, now),
+            UptimeWindow {
+                period_seconds: DAY_SECONDS,
+                checks: 3,
+                healthy_checks: 2
+            }
+        );
+        assert_eq!(uptime(&checks, WEEK_SECONDS, now).checks, 4);
+
+        let subsystems = vec![
+            ComponentReport {
+                name: "long poller".to_owned(),
+                required: true,
+                state: ComponentState::Started,
+            },
+            ComponentReport {
+                name: "job scheduler".to_owned(),
+                required: false,
+                state: ComponentState::TimedOut,
+            },
+        ];
+        assert_eq!(subsystem_status("long poller", &subsystems), ComponentStatus::Operational);
+        assert_eq!(subsystem_status("job scheduler", &subsystems), ComponentStatus::Degraded);
+        assert_eq!(subsystem_status("metrics", &subsystems), ComponentStatus::Unknown);
+
+        let component = |status| PublicComponent {
+            name: "x".to_owned(),
+            status,
+        };
+        assert_eq!(
+            overall(&[component(ComponentStatus::Operational), component(ComponentStatus::Unknown)]),
+            ComponentStatus::Operational
+        );
+        assert_eq!(
+            overall(&[component(ComponentStatus::Degraded), component(ComponentStatus::Outage)]),
+            ComponentStatus::Outage
+        );
+
+        let maintenance = Announcement {
+            id: "1".to_owned(),
+            kind: AnnouncementKind::Maintenance,
+            title: "database upgrade".to_owned(),
+            message: String::default(),
+            starts_at: 100,
+            ends_at: Some(200),
+        };
+        assert!(!maintenance.is_current(99));
+        assert!(maintenance.is_current(150));
+        assert!(!maintenance.is_current(200));
+    }
+}
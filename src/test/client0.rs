@@ -104,6 +104,7 @@ pub(crate) async fn client0_thread(mut rx: Receiver<CatanMessage>) {
             message: invite_message.clone(),
             from_picture: cloned_profile.picture_url.clone(),
             game_id: game_id.to_owned(),
+            token: String::default(),
         };
         trace_thread_info!(name, "Sending GameInvite");
         let response = proxy
@@ -139,6 +140,15 @@ pub(crate) async fn client0_thread(mut rx: Receiver<CatanMessage>) {
     }
     trace_thread_info!(name, "all players accepted: {:#?}", players);
 
+    let explanation = proxy
+        .explain_action(&game_id, &GameAction::Next)
+        .await
+        .assert_success("explain_action should not fail")
+        .get_action_explanation()
+        .expect("explain_action should have an ActionExplanation in the body");
+    assert!(explanation.is_legal, "{:#?}", explanation);
+    assert!(explanation.preconditions.iter().all(|p| p.satisfied));
+
     proxy
         .start_game(&game_id)
         .await
@@ -43,7 +43,7 @@ pub mod test {
     use azure_core::auth;
     use futures::stream::FuturesUnordered;
     use futures::StreamExt;
-    use log::{error, info, trace};
+    use tracing::{error, info, trace};
     use reqwest::{Client, StatusCode};
     use serde::{Deserialize, Serialize};
     use serde_json::json;
@@ -279,6 +279,7 @@ pub mod test {
                 games_won: Some(0),
                 validated_email: false,
                 validated_phone: false,
+                blocked_user_ids: Vec::new(),
             };
 
             let client_user = proxy
@@ -4,5 +4,7 @@ mod client1;
 mod client2;
 mod test_structs;
 mod polling_thread;
+#[cfg(test)]
+mod replay_corpus;
 pub mod test_helpers;
 pub mod test_proxy;
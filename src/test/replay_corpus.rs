@@ -0,0 +1,415 @@
+/**
+ *  the replay corpus: recorded games, played again through the current rules every time the tests run, so a change
+ *  to the game logic that would have rejected a move real players made -- or scored a game differently -- shows up as
+ *  a failing test instead of a bug report.
+ *
+ *  a replay is a game's event log, exported as it is stored: a json array of the game's documents from the GameEvent
+ *  collection (see event_log.rs), starting with its Created event.  drop the file in src/test/replays and it is part
+ *  of the corpus.
+ *
+ *  the event log has what each move did, not what the player sent, so the runner works the move out from the
+ *  snapshots on either side of it -- the new road, the cards discarded, the roll that pays out what was paid out --
+ *  and makes it again with the same game methods the action apis use.  the move has to be accepted and has to give
+ *  the recorded game (see checksum::canonical_json), apart from the cards that were picked at random: a steal or a
+ *  forced discard only has to move the same number of cards.  the runner carries on from the recorded game, so one
+ *  difference doesn't hide the rest.  moves it can't work out (trades, development cards, admin and lobby changes) are
+ *  skipped and reported.  at the end, the final game is scored again and every player's score and the winner have to
+ *  be what they were.
+ */
+use std::{fs, path::Path};
+
+use serde_json::Value;
+
+use crate::{
+    games_service::{
+        buildings::building_enums::BuildingState,
+        catan_games::{games::regular::regular_game::RegularGame, traits::game_trait::GameTrait},
+        game_container::{checksum, event_log, snapshot_diff::diff_games},
+        roads::road_enums::RoadState,
+        shared::{
+            game_enums::GameState,
+            game_models::{BuildData, MoveBaronData},
+        },
+    },
+    shared::{service_models::PersistGameEvent, shared_models::GameError},
+};
+
+pub const REPLAY_CORPUS_DIR: &str = "./src/test/replays";
+
+/// what happened when a replay was played through the current rules
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub game_id: String,
+    pub replayed: usize,
+    pub skipped: Vec<String>,  // "{game_index} {action}": moves the runner can't work out
+    pub failures: Vec<String>, // "{game_index} {action}: why"
+}
+
+/// the game after every event, oldest first
+fn snapshots(events: &[PersistGameEvent]) -> Result<Vec<RegularGame>, GameError> {
+    let mut events: Vec<&PersistGameEvent> = events.iter().collect();
+    events.sort_by_key(|event| event.game_index);
+    let mut snapshots = Vec::new();
+    let mut game = Value::Null;
+    for (n, event) in events.iter().enumerate() {
+        if n == 0 {
+            game = event.diff.clone();
+        } else if event.game_index != events[n - 1].game_index + 1 {
+            return Err(GameError::BadActionData(format!(
+                "event {} is missing",
+                events[n - 1].game_index + 1
+            )));
+        } else {
+            event_log::apply_diff(&mut game, &event.diff);
+        }
+        snapshots.push(serde_json::from_value(game.clone()).map_err(|e| GameError::BadActionData(e.to_string()))?);
+    }
+    Ok(snapshots)
+}
+
+/// is the replayed game the recorded one?  the hands in random_hands (and the bank, if random_bank) only have to have
+/// the same number of cards
+fn same(
+    replayed: &RegularGame,
+    recorded: &RegularGame,
+    random_hands: &[String],
+    random_bank: bool,
+) -> Result<(), String> {
+    let mut replayed = replayed.clone();
+    for user_id in random_hands {
+        match (replayed.players.get_mut(user_id), recorded.players.get(user_id)) {
+            (Some(mine), Some(theirs)) if mine.resources.total() == theirs.resources.total() => {
+                mine.resources = theirs.resources.clone();
+            }
+            _ => return Err(format!("{} has a different number of cards", user_id)),
+        }
+    }
+    if random_bank {
+        if replayed.bank.total() != recorded.bank.total() {
+            return Err("the bank has a different number of cards".to_owned());
+        }
+        replayed.bank = recorded.bank.clone();
+    }
+    replayed.game_index = recorded.game_index;
+    if checksum::canonical_json(&replayed) == checksum::canonical_json(recorded) {
+        Ok(())
+    } else {
+        Err(format!(
+            "the move gives a different game: {}",
+            serde_json::to_string(&diff_games(recorded, &replayed)).unwrap_or_default()
+        ))
+    }
+}
+
+/// what was built in the move
+fn build_data(before: &RegularGame, after: &RegularGame, seat: &str) -> Option<BuildData> {
+    let (was, is) = (before.players.get(seat)?, after.players.get(seat)?);
+    let building = is.buildings.iter().find(|building| {
+        !was.buildings
+            .iter()
+            .any(|old| old.building_key == building.building_key && old.state == building.state)
+    });
+    match building.map(|building| (&building.state, building.building_key)) {
+        Some((BuildingState::Settlement, key)) => return Some(BuildData::Settlement(key)),
+        Some((BuildingState::City, key)) => return Some(BuildData::City(key)),
+        _ => {}
+    }
+    let road = is
+        .roads
+        .iter()
+        .find(|road| !was.roads.iter().any(|old| old.primary_key() == road.primary_key()))?;
+    match road.state() {
+        RoadState::Road => Some(BuildData::Road(road.primary_key().clone())),
+        RoadState::Ship => Some(BuildData::Ship(road.primary_key().clone())),
+        RoadState::Unbuilt => None,
+    }
+}
+
+/// make the move again.  Ok(false) if the runner can't work out what the move was
+fn replay_step(before: &RegularGame, event: &PersistGameEvent, after: &RegularGame) -> Result<bool, String> {
+    //  a move made for somebody else's seat is made as them (see delegation.rs)
+    let seat = event
+        .on_behalf_of
+        .as_deref()
+        .or(event.actor_id.as_deref())
+        .unwrap_or_default()
+        .to_owned();
+    let at = event.at;
+    //  what the action apis do to every game they push (see push_and_return_actions)
+    let scored = |game: Result<RegularGame, GameError>| -> Result<RegularGame, String> {
+        let mut game = game.map_err(|e| format!("the move was rejected: {}", e))?;
+        game.update_scores();
+        game.end_if_time_is_up(at);
+        Ok(game)
+    };
+    match event.action.as_str() {
+        "AddPlayer" => {
+            let profile = after
+                .players
+                .iter()
+                .find(|(user_id, _)| !before.players.contains_key(*user_id))
+                .map(|(_, player)| player.profile.clone())
+                .ok_or_else(|| "nobody joined".to_owned())?;
+            let game = before
+                .add_user(&profile)
+                .map_err(|e| format!("the player was turned away: {:?}", e))?;
+            same(&game, after, &[], false)?;
+        }
+        "Next" => {
+            let mut game = GameTrait::set_next_state(before);
+            if let Ok(game) = game.as_mut() {
+                if before.game_state == GameState::AllocateResourceReverse
+                    && game.game_state == GameState::WaitingForRoll
+                {
+                    game.start_clock(at);
+                }
+            }
+            same(&scored(game)?, after, &[], false)?;
+        }
+        "Roll" => {
+            //  the game has the roll.  games recorded before it did don't, but only the roll that was made pays out
+            //  what was paid out
+            let mut why = String::default();
+            let rolls: Vec<u32> = match after.last_roll {
+                Some(roll) => vec![roll],
+                None => (2..=12).collect(),
+            };
+            let rolled = rolls.into_iter().any(|roll| {
+                let replayed = scored(before.roll(roll)).map(|mut game| {
+                    game.last_roll = game.last_roll.filter(|_| after.last_roll.is_some());
+                    game
+                });
+                match replayed.and_then(|game| same(&game, after, &[], false)) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        why = e;
+                        false
+                    }
+                }
+            });
+            if !rolled {
+                return Err(format!("no roll gives the recorded game -- a 12: {}", why));
+            }
+        }
+        "Build" => {
+            let data = build_data(before, after, &seat).ok_or_else(|| "nothing was built".to_owned())?;
+            same(&scored(before.build(&seat, &data))?, after, &[], false)?;
+        }
+        "Discard" => {
+            let mut cards = before.players.get(&seat).map(|player| player.resources.clone()).unwrap_or_default();
+            let kept = after.players.get(&seat).map(|player| player.resources.clone()).unwrap_or_default();
+            cards.subtract(&kept).map_err(|e| format!("the hand grew: {}", e))?;
+            same(&scored(before.discard(&seat, &cards))?, after, &[], false)?;
+        }
+        "ForcedDiscard" => {
+            let owed: Vec<String> = before.pending_discards.keys().cloned().collect();
+            same(&scored(before.force_discards(at))?, after, &owed, true)?;
+        }
+        "MoveBaron" => {
+            //  the victim is whoever lost a card; which card was stolen is random
+            let victim_id = before
+                .players
+                .iter()
+                .filter(|(user_id, _)| **user_id != seat)
+                .find(|(user_id, player)| {
+                    after
+                        .players
+                        .get(*user_id)
+                        .map_or(false, |now| now.resources.total() < player.resources.total())
+                })
+                .map(|(user_id, _)| user_id.clone());
+            let data = MoveBaronData {
+                tile_key: after.baron_tile,
+                victim_id: victim_id.clone(),
+            };
+            let random_hands: Vec<String> = std::iter::once(seat.clone()).chain(victim_id).collect();
+            same(&scored(before.move_baron(&seat, &data))?, after, &random_hands, false)?;
+        }
+        "TimeUp" => {
+            let mut game = before.clone();
+            if !game.end_if_time_is_up(at) {
+                return Err("the game's time wasn't up".to_owned());
+            }
+            same(&scored(Ok(game))?, after, &[], false)?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// play the recorded game through the current rules
+pub fn run_replay(events: &[PersistGameEvent]) -> Result<ReplayReport, GameError> {
+    let snapshots = snapshots(events)?;
+    let mut events: Vec<&PersistGameEvent> = events.iter().collect();
+    events.sort_by_key(|event| event.game_index);
+    let mut report = ReplayReport {
+        game_id: snapshots.first().map(|game| game.id.clone()).unwrap_or_default(),
+        ..Default::default()
+    };
+    for (n, event) in events.iter().enumerate().skip(1) {
+        let step = format!("{} {}", event.game_index, event.action);
+        match replay_step(&snapshots[n - 1], event, &snapshots[n]) {
+            Ok(true) => report.replayed += 1,
+            Ok(false) => report.skipped.push(step),
+            Err(why) => report.failures.push(format!("{}: {}", step, why)),
+        }
+    }
+
+    if let Some(last) = snapshots.last() {
+        let mut rescored = last.clone();
+        rescored.update_scores();
+        for (user_id, player) in &last.players {
+            let score = rescored.players[user_id].state.known_score();
+            if score != player.state.known_score() {
+                report.failures.push(format!(
+                    "final score: {} had {}, and would have {} now",
+                    user_id,
+                    player.state.known_score(),
+                    score
+                ));
+            }
+        }
+        if rescored.winner_id != last.winner_id {
+            report.failures.push(format!(
+                "final score: {:?} won, and {:?} would win now",
+                last.winner_id, rescored.winner_id
+            ));
+        }
+    }
+    Ok(report)
+}
+
+/// every replay in the corpus
+fn corpus_files() -> Vec<std::path::PathBuf> {
+    let mut files: Vec<std::path::PathBuf> = match fs::read_dir(Path::new(REPLAY_CORPUS_DIR)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |extension| extension == "json"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        games_service::{
+            bots::engine::{choose_move, BotDifficulty, BotMove},
+            shared::game_models::{LedgerReason, ResourceCards},
+        },
+        shared::shared_models::UserProfile,
+    };
+
+    #[test]
+    fn test_replay_corpus() {
+        for path in corpus_files() {
+            let json = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            let events: Vec<PersistGameEvent> =
+                serde_json::from_str(&json).unwrap_or_else(|e| panic!("{} isn't an event log: {}", path.display(), e));
+            let report = run_replay(&events).unwrap_or_else(|e| panic!("{}: {:?}", path.display(), e));
+            assert!(
+                report.failures.is_empty(),
+                "{} ({}) doesn't replay: {:#?}",
+                path.display(),
+                report.game_id,
+                report.failures
+            );
+            tracing::info!(
+                "{}: {} moves replayed, {} skipped",
+                path.display(),
+                report.replayed,
+                report.skipped.len()
+            );
+        }
+    }
+
+    /// a game played by bots, as (action, player, game after it)
+    fn bot_game() -> Vec<(String, String, RegularGame)> {
+        let mut game = RegularGame::new(&UserProfile::new_test_user(Some("1".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("2".to_string())));
+        GameTrait::add_user(&mut game, &UserProfile::new_test_user(Some("3".to_string())));
+        game.set_player_order(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+            .unwrap();
+        game.game_state = GameState::AllocateResourceForward;
+        game.current_player_id = "1".to_string();
+
+        let mut steps = vec![("Created".to_owned(), "1".to_owned(), game.clone())];
+        let rolls = [8, 7, 6, 5, 9, 4, 10, 7, 3, 11, 6, 8];
+        let mut turns = 0;
+        while turns < rolls.len() && steps.len() < 500 && game.game_state != GameState::GameOver {
+            let bot_id = game
+                .pending_discards
+                .keys()
+                .next()
+                .cloned()
+                .unwrap_or_else(|| game.current_player_id.clone());
+            let bot_move = choose_move(&game, &bot_id, BotDifficulty::Medium).expect("the bot has a move");
+            let (action, next) = match &bot_move {
+                BotMove::Build(data) => ("Build", game.build(&bot_id, data)),
+                BotMove::Roll => {
+                    turns += 1;
+                    ("Roll", game.roll(rolls[turns - 1]))
+                }
+                BotMove::Discard(cards) => ("Discard", game.discard(&bot_id, cards)),
+                BotMove::MoveBaron(data) => ("MoveBaron", game.move_baron(&bot_id, data)),
+                BotMove::Next => ("Next", game.set_next_state()),
+            };
+            game = next.expect("the bot's move is legal");
+            game.update_scores();
+            game.game_index += 1;
+            steps.push((action.to_owned(), bot_id, game.clone()));
+        }
+        steps
+    }
+
+    fn event_log_of(steps: &[(String, String, RegularGame)]) -> Vec<PersistGameEvent> {
+        steps
+            .iter()
+            .enumerate()
+            .map(|(n, (action, player, game))| {
+                let previous = if n == 0 { None } else { Some(&steps[n - 1].2) };
+                event_log::new_event(previous, game, action, Some(player))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_replay_runner() {
+        let steps = bot_game();
+        let report = run_replay(&event_log_of(&steps)).unwrap();
+        assert!(report.failures.is_empty(), "{:#?}", report.failures);
+        assert!(report.skipped.is_empty(), "{:#?}", report.skipped);
+        assert_eq!(report.replayed, steps.len() - 1);
+
+        // a roll that paid out cards the rules wouldn't have is caught
+        let mut tampered = steps.clone();
+        let (n, roller) = tampered
+            .iter()
+            .enumerate()
+            .filter(|(_, (action, _, game))| action == "Roll" && game.game_state == GameState::BuyingAndTrading)
+            .map(|(n, (_, player, _))| (n, player.clone()))
+            .nth(2)
+            .expect("the bots rolled");
+        tampered[n]
+            .2
+            .take_from_bank(&roller, &ResourceCards::new(1, 1, 1, 1, 1), LedgerReason::Roll)
+            .unwrap();
+        let report = run_replay(&event_log_of(&tampered)).unwrap();
+        assert!(
+            report.failures[0].starts_with(&format!("{} Roll", tampered[n].2.game_index)),
+            "{:#?}",
+            report.failures
+        );
+
+        // and so is a final score the rules don't agree with
+        let mut tampered = steps;
+        let last = tampered.last_mut().unwrap();
+        let player = last.2.players.get_mut("1").unwrap();
+        player.state.set_known_score(player.state.known_score() + 1);
+        let report = run_replay(&event_log_of(&tampered)).unwrap();
+        assert!(report.failures.iter().any(|failure| failure.starts_with("final score")));
+    }
+}
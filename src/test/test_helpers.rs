@@ -24,7 +24,7 @@ pub mod test {
 
     #[tokio::test]
     async fn test_new_proxy() {
-        crate::init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        crate::init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
         let test_service = test::init_service(create_service!()).await;
         let mut test_proxy = TestProxy::new(&test_service, None);
 
@@ -94,17 +94,17 @@ pub mod test {
 
     #[tokio::test]
     async fn test_get_auth_token() {
-        crate::init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        crate::init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
         let _admin_token = TestHelpers::admin_login().await;
         let test_users = TestHelpers::load_test_users_from_config();
-        log::trace!("{}", serde_json::to_string(&test_users).unwrap());
+        tracing::trace!("{}", serde_json::to_string(&test_users).unwrap());
         let _test_context = TestContext::new(true, None);
 
         print!("ok");
     }
     #[tokio::test]
     async fn test_service_response_serialization() {
-        init_env_logger(log::LevelFilter::Trace, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::TRACE, crate::LevelFilter::ERROR).await;
         let sr = ServiceResponse::new(
             "already exists",
             StatusCode::ACCEPTED,
@@ -113,10 +113,10 @@ pub mod test {
         );
 
         let json = serde_json::to_string(&sr).unwrap();
-        log::info!("to_http_response: {}", json);
+        tracing::info!("to_http_response: {}", json);
         match serde_json::from_str::<ServiceResponse>(&json) {
             Ok(_) => {
-                log::trace!("round trip succeeded");
+                tracing::trace!("round trip succeeded");
             }
             Err(e) => {
                 panic!("failed to roundtrip ServiceResponse: {:#?}", e);
@@ -125,7 +125,7 @@ pub mod test {
     }
     #[tokio::test]
     async fn register_test_users_test() {
-        init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
         let app = create_test_service!();
         let mut proxy = TestProxy::new(&app, Some(TestContext::new(true, None)));
         //  setup_test!(&app, true);
@@ -144,7 +144,7 @@ pub mod test {
      */
     #[tokio::test]
     async fn delete_test_users() {
-        init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
         let app = create_test_service!();
         let mut proxy = TestProxy::new(&app, Some(TestContext::new(true, None)));
         delete_all_test_users(&mut proxy).await;
@@ -222,9 +222,9 @@ pub mod test {
                     "JSON contains an underscore character"
                 );
     
-                log::trace!("registered client_user: {:#?}", pretty_json);
+                tracing::trace!("registered client_user: {:#?}", pretty_json);
             } else {
-                log::trace!("{} already registered", user.display_name.clone());
+                tracing::trace!("{} already registered", user.display_name.clone());
                 assert_eq!(service_response.status, StatusCode::CONFLICT);
                 let email = user.pii.clone().unwrap().email;
                 let service_response = proxy.get_profile(&email).await;
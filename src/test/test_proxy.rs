@@ -4,11 +4,20 @@ use actix_web::http::header::{self, HeaderName, HeaderValue};
 
 use actix_web::test::{self, TestRequest};
 use serde::Serialize;
+use crate::games_service::bots::engine::BotDifficulty;
+use crate::games_service::chat::chat::ChatData;
+use crate::games_service::lobby::matchmaking::MatchPreferences;
 use crate::games_service::catan_games::games::regular::regular_game::RegularGame;
 use crate::games_service::game_container::game_messages::{
     GameHeader, Invitation, InvitationResponseData,
 };
-use crate::games_service::shared::game_enums::CatanGames;
+use crate::games_service::shared::game_enums::{
+    CatanGames, DevCardType, GameAction, ResourceType,
+};
+use crate::games_service::shared::game_models::{
+    BankTradeData, BestBankTradeData, BuildData, CustomBoardData, DevCardResolutionData,
+    GameOptions, MoveBaronData, ResourceCards, RollData, TradeOfferData,
+};
 use crate::middleware::request_context_mw::TestContext;
 use crate::shared::shared_models::UserProfile;
 use crate::shared::shared_models::ServiceResponse;
@@ -283,6 +292,36 @@ where
         service_response
     }
 
+    pub async fn new_custom_game(&self, data: &CustomBoardData) -> ServiceResponse {
+        self.post::<&CustomBoardData>("/auth/api/v1/games/custom", None, Some(data))
+            .await
+    }
+
+    pub async fn get_my_usage(&self) -> ServiceResponse {
+        self.get("/auth/api/v1/users/self/usage", None).await
+    }
+
+    pub async fn get_all_usage(&self) -> ServiceResponse {
+        self.get("/auth/api/v1/admin/usage", None).await
+    }
+
+    pub async fn snapshot_diff(&self, game_id: &str, from_index: u32, to_index: u32) -> ServiceResponse {
+        let url = format!(
+            "/auth/api/v1/admin/games/{}/diff/{}/{}",
+            game_id, from_index, to_index
+        );
+        self.get(&url, None).await
+    }
+
+    pub async fn profiling_timings(&self) -> ServiceResponse {
+        self.get("/auth/api/v1/admin/profiling/timings", None).await
+    }
+
+    pub async fn export_analytics(&self) -> ServiceResponse {
+        self.post::<()>("/auth/api/v1/admin/analytics/export", None, None)
+            .await
+    }
+
     pub async fn get_lobby(&self) -> ServiceResponse {
         let url = "/auth/api/v1/lobby";
         self.get(url, None).await
@@ -300,6 +339,72 @@ where
         self.get(&url, Some(&headers)).await
     }
 
+    pub async fn explain_action(&self, game_id: &str, action: &GameAction) -> ServiceResponse {
+        let url = format!("/auth/api/v1/games/{}/actions/explain/{:?}", game_id, action);
+        self.get(&url, None).await
+    }
+
+    pub async fn set_game_options(&self, game_id: &str, options: &GameOptions) -> ServiceResponse {
+        let url = format!("/auth/api/v1/games/{}/options", game_id);
+        self.post::<&GameOptions>(&url, None, Some(options)).await
+    }
+
+    pub async fn game_history(&self, game_id: &str, offset: usize, count: usize) -> ServiceResponse {
+        let url = format!(
+            "/auth/api/v1/games/{}/history?offset={}&count={}",
+            game_id, offset, count
+        );
+        self.get(&url, None).await
+    }
+
+    pub async fn user_stats(&self, user_id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/users/{}/stats", user_id);
+        self.get(&url, None).await
+    }
+
+    pub async fn rejoin_state(&self, game_id: &str, since: u64) -> ServiceResponse {
+        let url = format!("/auth/api/v1/games/{}/state?since={}", game_id, since);
+        self.get(&url, None).await
+    }
+
+    pub async fn new_private_game(&self, game_type: CatanGames) -> ServiceResponse {
+        let url = format!("/auth/api/v1/games/{:?}?visibility=private", game_type);
+        self.post::<()>(&url, None, None).await
+    }
+
+    pub async fn join_by_code(&self, code: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/lobby/join-by-code/{}", code);
+        self.post::<()>(&url, None, None).await
+    }
+
+    /// the public games still taking players.  query is the filters and paging, eg "game_type=Regular&count=10"
+    pub async fn browse_games(&self, query: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/lobby/games?{}", query);
+        self.get(&url, None).await
+    }
+
+    pub async fn matchmake(&self, preferences: &MatchPreferences) -> ServiceResponse {
+        let url = "/auth/api/v1/lobby/matchmake";
+        self.post::<&MatchPreferences>(&url, None, Some(&preferences)).await
+    }
+
+    pub async fn leave_matchmaking(&self) -> ServiceResponse {
+        self.delete("/auth/api/v1/lobby/matchmake", None).await
+    }
+
+    pub async fn chat(&self, game_id: &str, text: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/games/{}/chat", game_id);
+        let data = ChatData {
+            text: text.to_owned(),
+        };
+        self.post::<&ChatData>(&url, None, Some(&data)).await
+    }
+
+    pub async fn game_members(&self, game_id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/games/{}/members", game_id);
+        self.get(&url, None).await
+    }
+
     pub async fn long_poll(&self, game_id: &str, index: u32) -> ServiceResponse {
         let url = format!("/auth/api/v1/longpoll/{}", index);
         let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
@@ -312,11 +417,47 @@ where
         self.get(&url, Some(&headers)).await
     }
 
+    /// long poll for the messages on one channel ("lobby", "game:{game_id}" or "dm:{user_id}")
+    pub async fn long_poll_channel(&self, index: u32, channel: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/longpoll/{}?channel={}", index, channel);
+        self.get(&url, None).await
+    }
+
+    /// long poll, acknowledging every message up to the user_sequence `since`
+    pub async fn long_poll_since(&self, index: u32, since: u64) -> ServiceResponse {
+        let url = format!("/auth/api/v1/longpoll/{}?since={}", index, since);
+        self.get(&url, None).await
+    }
+
     pub async fn send_invite(&self, invite: &Invitation) -> ServiceResponse {
         let url = "/auth/api/v1/lobby/invite";
         self.post::<&Invitation>(&url, None, Some(&invite)).await
     }
 
+    pub async fn add_bot(&self, game_id: &str, difficulty: BotDifficulty) -> ServiceResponse {
+        let url = format!("/auth/api/v1/lobby/add-bot/{:?}", difficulty);
+        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
+
+        headers.insert(
+            HeaderName::from_static(GameHeader::GAME_ID),
+            HeaderValue::from_str(game_id).expect("Invalid header value"),
+        );
+
+        self.post::<()>(&url, Some(&headers), None).await
+    }
+
+    pub async fn kick(&self, game_id: &str, user_id: &str, ban: bool) -> ServiceResponse {
+        let url = format!("/auth/api/v1/lobby/kick/{}?ban={}", user_id, ban);
+        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
+
+        headers.insert(
+            HeaderName::from_static(GameHeader::GAME_ID),
+            HeaderValue::from_str(game_id).expect("Invalid header value"),
+        );
+
+        self.post::<()>(&url, Some(&headers), None).await
+    }
+
     pub async fn invitation_response(&self, invite: &InvitationResponseData) -> ServiceResponse {
         let url = "/auth/api/v1/lobby/acceptinvite";
 
@@ -333,6 +474,107 @@ where
         self.post::<&Invitation>(&url, None, None).await
     }
 
+    pub async fn roll(&self, game_id: &str, test_roll: Option<&RollData>) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/roll/{}", game_id);
+        match test_roll {
+            Some(roll) => self.post::<&RollData>(&url, None, Some(roll)).await,
+            None => self.post::<()>(&url, None, None).await,
+        }
+    }
+
+    pub async fn discard(&self, game_id: &str, cards: &ResourceCards) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/discard/{}", game_id);
+        self.post::<&ResourceCards>(&url, None, Some(cards)).await
+    }
+
+    pub async fn move_baron(&self, game_id: &str, data: &MoveBaronData) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/move-baron/{}", game_id);
+        self.post::<&MoveBaronData>(&url, None, Some(data)).await
+    }
+
+    pub async fn build(&self, game_id: &str, data: &BuildData) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/build/{}", game_id);
+        self.post::<&BuildData>(&url, None, Some(data)).await
+    }
+
+    pub async fn offer_trade(&self, game_id: &str, data: &TradeOfferData) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/trade/offer/{}", game_id);
+        self.post::<&TradeOfferData>(&url, None, Some(data)).await
+    }
+
+    pub async fn accept_trade(&self, game_id: &str, offer_id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/trade/accept/{}/{}", game_id, offer_id);
+        self.post::<()>(&url, None, None).await
+    }
+
+    pub async fn reject_trade(&self, game_id: &str, offer_id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/trade/reject/{}/{}", game_id, offer_id);
+        self.post::<()>(&url, None, None).await
+    }
+
+    pub async fn counter_trade(
+        &self,
+        game_id: &str,
+        offer_id: &str,
+        data: &TradeOfferData,
+    ) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/trade/counter/{}/{}", game_id, offer_id);
+        self.post::<&TradeOfferData>(&url, None, Some(data)).await
+    }
+
+    pub async fn bank_trade(&self, game_id: &str, data: &BankTradeData) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/trade/bank/{}", game_id);
+        self.post::<&BankTradeData>(&url, None, Some(data)).await
+    }
+
+    pub async fn best_bank_trade(&self, game_id: &str, data: &BestBankTradeData) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/trade/bank/best/{}", game_id);
+        self.post::<&BestBankTradeData>(&url, None, Some(data)).await
+    }
+
+    pub async fn play_dev_card(&self, game_id: &str, card: DevCardType) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/dev-card/play/{}", game_id);
+        self.post::<DevCardType>(&url, None, Some(card)).await
+    }
+
+    pub async fn resolve_dev_card(
+        &self,
+        game_id: &str,
+        data: &DevCardResolutionData,
+    ) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/dev-card/resolve/{}", game_id);
+        self.post::<&DevCardResolutionData>(&url, None, Some(data)).await
+    }
+
+    pub async fn play_monopoly(&self, game_id: &str, resource: ResourceType) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/dev-card/monopoly/{}", game_id);
+        self.post::<ResourceType>(&url, None, Some(resource)).await
+    }
+
+    pub async fn play_year_of_plenty(
+        &self,
+        game_id: &str,
+        resources: [ResourceType; 2],
+    ) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/dev-card/year-of-plenty/{}", game_id);
+        self.post::<[ResourceType; 2]>(&url, None, Some(resources)).await
+    }
+
+    pub async fn undo(&self, game_id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/undo/{}", game_id);
+        self.post::<()>(&url, None, None).await
+    }
+
+    pub async fn redo(&self, game_id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/redo/{}", game_id);
+        self.post::<()>(&url, None, None).await
+    }
+
+    pub async fn forfeit(&self, game_id: &str) -> ServiceResponse {
+        let url = format!("/auth/api/v1/action/forfeit/{}", game_id);
+        self.post::<()>(&url, None, None).await
+    }
+
     pub async fn rotate_login_keys(&self, game_id: &str) -> ServiceResponse {
         let url = format!("/auth/api/v1/action/start/{}", game_id);
         self.post::<()>(&url, None, None).await
@@ -372,6 +614,21 @@ where
         self.get(&url, None).await
     }
 
+    pub async fn request_email_change(&self, email: &str) -> ServiceResponse {
+        let mut headers: HashMap<HeaderName, HeaderValue> = HashMap::new();
+        headers.insert(
+            HeaderName::from_static(GameHeader::EMAIL),
+            HeaderValue::from_str(email).expect("Invalid header value"),
+        );
+        let url = "/auth/api/v1/users/email/change";
+        self.post::<()>(url, Some(&headers), None).await
+    }
+
+    pub async fn confirm_email_change(&self, token: &str) -> ServiceResponse {
+        let url = format!("/api/v1/users/confirm-email/{}", token);
+        self.get(&url, None).await
+    }
+
     pub async fn create_local_user(&self, new_profile: &UserProfile) -> ServiceResponse {
         let url = "/auth/api/v1/users/local";
         self.post::<&UserProfile>(url, None, Some(new_profile))
@@ -1,16 +1,19 @@
 #![allow(dead_code)]
 use futures::Future;
-use log4rs::encode::pattern::PatternEncoder;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::{env, fs::OpenOptions};
+use std::fs::OpenOptions;
 use std::pin::Pin;
 use std::sync::atomic::Ordering;
 use tokio::sync::mpsc::Receiver;
 
 
 use crate::{
-    games_service::game_container::game_messages::CatanMessage, shared::shared_models::UserProfile,
+    games_service::game_container::game_messages::CatanMessage,
+    shared::{
+        log_filter::{init_logging, LogFormat},
+        shared_models::UserProfile,
+    },
     LOGGER_INIT, LOGGER_INIT_LOCK,
 };
 
@@ -99,6 +102,43 @@ pub fn log_for_message(message: &CatanMessage) -> String {
         CatanMessage::Ended(_) => {
             format!("Ended")
         }
+        CatanMessage::GameWon(won) => {
+            format!("GameWon [id={}] [winner={}]", won.game_id, won.winner_id)
+        }
+        CatanMessage::ResolveDevCard(pending) => {
+            format!("ResolveDevCard [user={}] [card={:?}]", pending.user_id, pending.card)
+        }
+        CatanMessage::TurnSummary(summary) => {
+            format!("TurnSummary [id={}] [user={}]", summary.game_id, summary.user_id)
+        }
+        CatanMessage::AchievementUnlocked(data) => {
+            format!("AchievementUnlocked [user={}] [achievement={:?}]", data.user_id, data.achievement)
+        }
+        CatanMessage::Chat(chat) => {
+            format!("Chat [id={}] [from={}]", chat.game_id, chat.from_id)
+        }
+        CatanMessage::DirectMessage(dm) => {
+            format!("DirectMessage [from={}] [to={}]", dm.from_id, dm.to_id)
+        }
+        CatanMessage::PlayerRemoved(removed) => {
+            format!("PlayerRemoved [id={}] [user={}] [banned={}]", removed.game_id, removed.user_id, removed.banned)
+        }
+        CatanMessage::HostChanged(changed) => {
+            format!("HostChanged [id={}] [from={}] [to={}]", changed.game_id, changed.from_id, changed.to_id)
+        }
+        CatanMessage::SessionEnded(data) => {
+            format!("SessionEnded [user={}] [session={}]", data.user_id, data.session_id)
+        }
+        CatanMessage::PresenceChanged(presence) => {
+            format!("PresenceChanged [user={}] [online={}]", presence.user_id, presence.online)
+        }
+        CatanMessage::PlayerDisconnected(data) => {
+            format!("PlayerDisconnected [id={}] [user={}]", data.game_id, data.user_id)
+        }
+        CatanMessage::PlayerReconnected(data) => {
+            format!("PlayerReconnected [id={}] [user={}] [away={}]", data.game_id, data.user_id, data.away_seconds)
+        }
+        CatanMessage::Heartbeat(now) => format!("Heartbeat [now={}]", now),
         CatanMessage::Error(e) => {format!("Error: {:#?}", e)},
     }
 }
@@ -110,32 +150,15 @@ pub async fn init_test_logger() {
     if LOGGER_INIT.load(Ordering::Relaxed) {
         return;
     }
-    let mut path = env::current_exe().expect("Failed to get current executable path");
-    path.pop(); // Remove the binary name
-    path.pop(); // Remove the 'debug' directory
-    path.pop(); // Remove the 'target' directory
-    path.pop();
-    path.push("log4rs.yaml");
-   
-    let _dir =   fs::create_dir("log");
-    let file = OpenOptions::new().create(true).write(true).truncate(true).open("log/log.csv").unwrap();
-    file.set_len(0).unwrap();
-    drop(file);
-
-    log4rs::init_file(path, Default::default()).unwrap();
-
+    // the full game test is noisy -- everything also goes to log/log.txt so it can be read after the run
+    let _dir = fs::create_dir("log");
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open("log/log.txt")
+        .unwrap();
+    init_logging("info", LogFormat::from_env(), Some(file));
 
     LOGGER_INIT.store(true, Ordering::Relaxed);
 }
-#[derive(Debug)]
-struct OneLineEncoder {
-    encoder: PatternEncoder,
-}
-
-impl OneLineEncoder {
-    pub fn new(pattern: &str) -> Self {
-        OneLineEncoder {
-            encoder: PatternEncoder::new(pattern),
-        }
-    }
-}
@@ -0,0 +1,128 @@
+#![allow(dead_code)]
+/**
+ *  what a user has asked the service to do for them in their games, so an asynchronous game doesn't sit waiting on
+ *  them (see automation.rs for when each one is done).  everything is off until the user turns it on with
+ *  PUT /auth/api/v1/users/automation-preferences:
+ *
+ *  - DiscardPriority: the order to give cards up in when a 7 is rolled and they hold too many.  their discard is made
+ *    for them as soon as the 7 is rolled.  cards they owe past the ones listed come from what they hold most of
+ *  - AcceptTradesFrom: players whose offers to them are accepted right away, if they have the cards
+ *  - DeclineTradesWhenAway: offers made to them while their connection is down are turned down instead of waiting
+ *    until they expire
+ */
+use std::collections::HashSet;
+
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::shared::{game_enums::ResourceType, game_models::ResourceCards},
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+/// the most players a user can accept every offer from
+pub const MAX_ACCEPT_TRADES_FROM: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct AutomationPreferences {
+    #[serde(default)]
+    pub discard_priority: Vec<ResourceType>, // empty: the user discards for themselves
+    #[serde(default)]
+    pub accept_trades_from: Vec<String>, // user ids
+    #[serde(default)]
+    pub decline_trades_when_away: bool,
+}
+
+impl AutomationPreferences {
+    /// what is wrong with the preferences, if anything
+    pub fn check(&self) -> Result<(), String> {
+        let mut seen = HashSet::new();
+        for resource in &self.discard_priority {
+            if !ResourceCards::RESOURCES.contains(resource) {
+                return Err(format!("{:?} isn't a card you can hold", resource));
+            }
+            if !seen.insert(*resource) {
+                return Err(format!("{:?} is in DiscardPriority twice", resource));
+            }
+        }
+        if self.accept_trades_from.len() > MAX_ACCEPT_TRADES_FROM {
+            return Err(format!(
+                "offers can be accepted from at most {} players",
+                MAX_ACCEPT_TRADES_FROM
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn accepts_trades_from(&self, user_id: &str) -> bool {
+        self.accept_trades_from.iter().any(|id| id == user_id)
+    }
+}
+
+fn caller_id(request_context: &RequestContext) -> String {
+    request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call")
+        .id
+        .clone()
+}
+
+pub async fn get_preferences(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let user = request_context
+        .database
+        .find_user_by_id(&caller_id(request_context))
+        .await?;
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::AutomationPreferences(user.automation_preferences),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn set_preferences(
+    preferences: &AutomationPreferences,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    preferences.check().map_err(|e| {
+        ServiceResponse::new(
+            "bad automation preferences",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(e.clone()),
+            GameError::BadActionData(e),
+        )
+    })?;
+    let mut user = request_context
+        .database
+        .find_user_by_id(&caller_id(request_context))
+        .await?;
+    user.automation_preferences = preferences.clone();
+    request_context.database.update_or_create_user(&user).await?;
+    Ok(ServiceResponse::new(
+        "saved",
+        StatusCode::OK,
+        ResponseType::AutomationPreferences(preferences.clone()),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn get_preferences_handler(request_context: RequestContext) -> HttpResponse {
+    get_preferences(&request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn set_preferences_handler(
+    preferences: web::Json<AutomationPreferences>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    set_preferences(&preferences, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
@@ -0,0 +1,177 @@
+#![allow(dead_code)]
+/**
+ *  users talking to each other outside of a game.  POST /users/{id}/message sends a DirectMessage to the user through
+ *  the long poller, on the sender's direct channel (dm:{from_id}), so a client can wait for the conversation with one
+ *  user.  the text is cleaned the same way game chat is (see chat.rs).
+ *
+ *  a user can block somebody: PUT /users/blocked/{id}, and DELETE to unblock.  the block list is kept on the user's
+ *  profile, where only they (and admins) can see it.  a message from somebody the recipient has blocked is dropped --
+ *  the sender is told it was sent, so a harasser can't tell they have been muted.
+ */
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::{
+        chat::chat::{clean_text, ChatData},
+        game_container::game_messages::CatanMessage,
+        long_poller::long_poller::LongPoller,
+    },
+    middleware::{request_context_mw::RequestContext, service_config::SERVICE_CONFIG},
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+/// the most users somebody can block.  blocking another unblocks the one blocked longest ago
+pub const MAX_BLOCKED_USERS: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct DirectMessage {
+    pub from_id: String,
+    pub from_name: String, // the sender's display name, so clients don't have to look it up
+    pub to_id: String,
+    pub text: String, // trimmed, and filtered if the chat filter is on
+    pub sent_at: u64, // seconds since the UNIX epoch
+}
+
+/// the block list with the user added: once, as the newest, and only the newest MAX_BLOCKED_USERS
+pub fn with_blocked(blocked: &[String], user_id: &str) -> Vec<String> {
+    let mut blocked: Vec<String> = blocked.iter().filter(|id| *id != user_id).cloned().collect();
+    blocked.push(user_id.to_owned());
+    if blocked.len() > MAX_BLOCKED_USERS {
+        blocked.drain(..blocked.len() - MAX_BLOCKED_USERS);
+    }
+    blocked
+}
+
+fn caller_id(request_context: &RequestContext) -> String {
+    request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call")
+        .id
+        .clone()
+}
+
+fn ok(message: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        message,
+        StatusCode::OK,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    )
+}
+
+/// send a message from the caller to the user
+#[tracing::instrument(skip_all, fields(to = %to_id))]
+pub async fn send_direct_message(
+    to_id: &str,
+    data: &ChatData,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let from_id = caller_id(request_context);
+    if from_id == to_id {
+        return Err(ServiceResponse::new(
+            "you can't message yourself",
+            StatusCode::BAD_REQUEST,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::BAD_REQUEST),
+        ));
+    }
+    let text = clean_text(&data.text, SERVICE_CONFIG.chat_profanity_filter).map_err(|e| {
+        ServiceResponse::new(
+            "bad message",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(format!("{:?}", e)),
+            e,
+        )
+    })?;
+    let sender = request_context.database.find_user_by_id(&from_id).await?;
+    let recipient = request_context.database.find_user_by_id(to_id).await?;
+    if recipient.user_profile.blocked_user_ids.contains(&from_id) {
+        tracing::info!("dropped a message from {}, who {} has blocked", from_id, to_id);
+        return Ok(ok("sent"));
+    }
+    let message = CatanMessage::DirectMessage(DirectMessage {
+        from_id,
+        from_name: sender.user_profile.display_name.clone(),
+        to_id: to_id.to_owned(),
+        text,
+        sent_at: request_context.environment.now(),
+    });
+    LongPoller::send_message(vec![to_id.to_owned()], &message).await
+}
+
+/// stop getting messages from the user
+pub async fn block_user(user_id: &str, request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let caller_id = caller_id(request_context);
+    if caller_id == user_id {
+        return Err(ServiceResponse::new(
+            "you can't block yourself",
+            StatusCode::BAD_REQUEST,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::BAD_REQUEST),
+        ));
+    }
+    // only real users can be blocked, so the list doesn't fill up with junk
+    request_context.database.find_user_by_id(user_id).await?;
+    let mut caller = request_context.database.find_user_by_id(&caller_id).await?;
+    caller.user_profile.blocked_user_ids = with_blocked(&caller.user_profile.blocked_user_ids, user_id);
+    request_context.database.update_or_create_user(&caller).await?;
+    Ok(ok("blocked"))
+}
+
+/// get messages from the user again
+pub async fn unblock_user(user_id: &str, request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let mut caller = request_context
+        .database
+        .find_user_by_id(&caller_id(request_context))
+        .await?;
+    caller.user_profile.blocked_user_ids.retain(|id| id != user_id);
+    request_context.database.update_or_create_user(&caller).await?;
+    Ok(ok("unblocked"))
+}
+
+pub async fn send_direct_message_handler(
+    to_id: web::Path<String>,
+    data: web::Json<ChatData>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    send_direct_message(&to_id, &data, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn block_user_handler(user_id: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    block_user(&user_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn unblock_user_handler(user_id: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    unblock_user(&user_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_list() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<String>>();
+        assert_eq!(with_blocked(&ids(&["a", "b"]), "a"), ids(&["b", "a"]));
+        assert_eq!(with_blocked(&[], "a"), ids(&["a"]));
+        let many: Vec<String> = (0..MAX_BLOCKED_USERS).map(|i| i.to_string()).collect();
+        let blocked = with_blocked(&many, "newest");
+        assert_eq!(blocked.len(), MAX_BLOCKED_USERS);
+        assert_eq!(blocked[0], "1");
+        assert_eq!(blocked.last().map(|id| id.as_str()), Some("newest"));
+    }
+}
@@ -1,3 +1,14 @@
+pub mod automation_preferences;
+pub mod direct_messages;
+pub mod notification_preferences;
+pub mod oauth;
+pub mod password_policy;
+pub mod profile_projection;
+pub mod push_notifications;
+pub mod refresh_tokens;
 pub mod send_mail;
+pub mod sessions;
 pub mod users;
 pub mod user_handlers;
+pub mod user_import;
+pub mod user_stats;
@@ -0,0 +1,365 @@
+#![allow(dead_code)]
+/**
+ *  how often a user wants to be pushed (see push_notifications.rs).  the preferences are kept with the user, and set
+ *  with PUT /auth/api/v1/users/notification-preferences:
+ *
+ *  - GamePushMinutes: at most one push for a game every this many minutes.  0, the default, pushes everything
+ *  - QuietHours: a time of day, in the user's time zone, when nothing is pushed
+ *
+ *  a push that can't go yet is held, not dropped.  the user's held pushes go out together when the first of them is
+ *  due: one for a single game is sent as it is, and several are sent as one digest ("3 of your games have news").  a
+ *  push for a game that is still inside its batching window stays held for the next round, and anything held for a
+ *  user who has come back online is dropped -- they have seen it.  held pushes are kept in memory, so a restart
+ *  drops them.
+ */
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
+
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    games_service::long_poller::presence,
+    middleware::request_context_mw::RequestContext,
+    shared::{
+        i18n,
+        service_models::PersistUser,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+use super::push_notifications::{self, PushNotification};
+
+/// the longest batching window a user can ask for
+pub const MAX_GAME_PUSH_MINUTES: u32 = 24 * 60;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct QuietHours {
+    pub start_minute: u32,       // minutes after the user's midnight
+    pub end_minute: u32,         // can be before the start: 22:00 to 07:00 is 1320 to 420
+    pub utc_offset_minutes: i32, // the user's time zone
+}
+
+impl QuietHours {
+    /// minutes after the user's midnight at the time
+    fn local_minute(&self, at: u64) -> i64 {
+        (at as i64 / 60 + self.utc_offset_minutes as i64).rem_euclid(MINUTES_PER_DAY)
+    }
+
+    pub fn contains(&self, at: u64) -> bool {
+        let minute = self.local_minute(at);
+        let (start, end) = (self.start_minute as i64, self.end_minute as i64);
+        if start <= end {
+            start <= minute && minute < end
+        } else {
+            minute >= start || minute < end
+        }
+    }
+
+    /// when the quiet hours the time is in end
+    pub fn end_after(&self, at: u64) -> u64 {
+        let minutes = (self.end_minute as i64 - self.local_minute(at)).rem_euclid(MINUTES_PER_DAY);
+        at - at % 60 + minutes as u64 * 60
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct NotificationPreferences {
+    #[serde(default)]
+    pub game_push_minutes: u32,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl NotificationPreferences {
+    /// what is wrong with the preferences, if anything
+    pub fn check(&self) -> Result<(), String> {
+        if self.game_push_minutes > MAX_GAME_PUSH_MINUTES {
+            return Err(format!("GamePushMinutes can't be more than {}", MAX_GAME_PUSH_MINUTES));
+        }
+        if let Some(quiet) = &self.quiet_hours {
+            if quiet.start_minute as i64 >= MINUTES_PER_DAY || quiet.end_minute as i64 >= MINUTES_PER_DAY {
+                return Err("quiet hours start and end in the same day: 0 to 1439".to_owned());
+            }
+            if quiet.start_minute == quiet.end_minute {
+                return Err("quiet hours have to start and end at different times".to_owned());
+            }
+            if quiet.utc_offset_minutes.abs() > 14 * 60 {
+                return Err("UtcOffsetMinutes has to be a real time zone".to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// when a push for a game last pushed at last_pushed can go out.  now, unless it has to be held
+    pub fn release_at(&self, last_pushed: Option<u64>, now: u64) -> u64 {
+        let mut at = now;
+        if let (Some(last_pushed), true) = (last_pushed, self.game_push_minutes > 0) {
+            at = at.max(last_pushed + self.game_push_minutes as u64 * 60);
+        }
+        match &self.quiet_hours {
+            Some(quiet) if quiet.contains(at) => quiet.end_after(at),
+            _ => at,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Held {
+    notifications: Vec<PushNotification>, // oldest first
+    release_at: u64,                      // when the timer that sends them fires
+}
+
+lazy_static::lazy_static! {
+    // (user_id, game_id) -> when the user was last pushed about the game
+    static ref LAST_PUSHED: Mutex<HashMap<(String, String), u64>> = Mutex::new(HashMap::new());
+    // user_id -> the pushes waiting for them
+    static ref HELD: Mutex<HashMap<String, Held>> = Mutex::new(HashMap::new());
+}
+
+fn last_pushed(user_id: &str, game_id: &str) -> Option<u64> {
+    LAST_PUSHED
+        .lock()
+        .expect("the push lock shouldn't be poisoned")
+        .get(&(user_id.to_owned(), game_id.to_owned()))
+        .copied()
+}
+
+fn pushed(user_id: &str, game_ids: &[String], now: u64) {
+    let mut last_pushed = LAST_PUSHED.lock().expect("the push lock shouldn't be poisoned");
+    //  nothing older than the longest window matters any more
+    last_pushed.retain(|_, at| *at + MAX_GAME_PUSH_MINUTES as u64 * 60 > now);
+    for game_id in game_ids {
+        last_pushed.insert((user_id.to_owned(), game_id.clone()), now);
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// the held pushes that are due: one as it is, or a digest of them
+fn combine(user_id: &str, due: &[PushNotification]) -> Option<PushNotification> {
+    let mut game_ids: Vec<&str> = Vec::new();
+    for notification in due {
+        if !game_ids.contains(&notification.game_id.as_str()) {
+            game_ids.push(&notification.game_id);
+        }
+    }
+    match game_ids.len() {
+        0 => None,
+        1 => due.last().cloned(),
+        count => Some(PushNotification::digest(count, i18n::language_for(game_ids[0], user_id))),
+    }
+}
+
+/**
+ *  the push, if it can go out now -- it is counted as pushed.  otherwise it is held, and a timer is started to send it
+ *  when it is due
+ */
+pub fn schedule(
+    user: &PersistUser,
+    notification: PushNotification,
+    request_context: &RequestContext,
+) -> Option<PushNotification> {
+    let now = now();
+    let preferences = &user.notification_preferences;
+    let release_at = preferences.release_at(last_pushed(&user.id, &notification.game_id), now);
+    let mut held = HELD.lock().expect("the push lock shouldn't be poisoned");
+    if release_at <= now && !held.contains_key(&user.id) {
+        drop(held);
+        pushed(&user.id, &[notification.game_id.clone()], now);
+        return Some(notification);
+    }
+    let start_timer = !held.contains_key(&user.id);
+    let waiting = held.entry(user.id.clone()).or_insert_with(|| Held {
+        notifications: Vec::new(),
+        release_at,
+    });
+    waiting.notifications.push(notification);
+    if start_timer {
+        start_release_timer(&user.id, release_at.saturating_sub(now), request_context);
+    }
+    None
+}
+
+fn start_release_timer(user_id: &str, seconds: u64, request_context: &RequestContext) {
+    let user_id = user_id.to_owned();
+    let request_context = request_context.clone();
+    actix_web::rt::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(seconds)).await;
+        release(&user_id, &request_context).await;
+    });
+}
+
+/// send what is due of the user's held pushes, and wait for the rest
+async fn release(user_id: &str, request_context: &RequestContext) {
+    let waiting = match HELD.lock().expect("the push lock shouldn't be poisoned").remove(user_id) {
+        Some(waiting) => waiting,
+        None => return,
+    };
+    if presence::is_online(user_id).await {
+        return; // they have seen it all
+    }
+    let mut user = match request_context.database.find_user_by_id(user_id).await {
+        Ok(user) => user,
+        Err(_) => return,
+    };
+    let now = now();
+    let preferences = user.notification_preferences;
+    let (due, not_yet): (Vec<PushNotification>, Vec<PushNotification>) = waiting
+        .notifications
+        .into_iter()
+        .partition(|notification| preferences.release_at(last_pushed(user_id, &notification.game_id), now) <= now);
+
+    if let Some(next) = not_yet
+        .iter()
+        .map(|notification| preferences.release_at(last_pushed(user_id, &notification.game_id), now))
+        .min()
+    {
+        HELD.lock().expect("the push lock shouldn't be poisoned").insert(
+            user_id.to_owned(),
+            Held {
+                notifications: not_yet,
+                release_at: next,
+            },
+        );
+        start_release_timer(user_id, next.saturating_sub(now), request_context);
+    }
+    if let Some(notification) = combine(user_id, &due) {
+        let game_ids: HashSet<String> = due.iter().map(|notification| notification.game_id.clone()).collect();
+        pushed(user_id, &game_ids.into_iter().collect::<Vec<String>>(), now);
+        push_notifications::push_to_devices(&mut user, &notification, request_context).await;
+    }
+}
+
+fn caller_id(request_context: &RequestContext) -> String {
+    request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call")
+        .id
+        .clone()
+}
+
+pub async fn get_preferences(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let user = request_context
+        .database
+        .find_user_by_id(&caller_id(request_context))
+        .await?;
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::NotificationPreferences(user.notification_preferences),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn set_preferences(
+    preferences: &NotificationPreferences,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    preferences.check().map_err(|e| {
+        ServiceResponse::new(
+            "bad notification preferences",
+            StatusCode::BAD_REQUEST,
+            ResponseType::ErrorInfo(e.clone()),
+            GameError::BadActionData(e),
+        )
+    })?;
+    let mut user = request_context
+        .database
+        .find_user_by_id(&caller_id(request_context))
+        .await?;
+    user.notification_preferences = *preferences;
+    request_context.database.update_or_create_user(&user).await?;
+    Ok(ServiceResponse::new(
+        "saved",
+        StatusCode::OK,
+        ResponseType::NotificationPreferences(*preferences),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn get_preferences_handler(request_context: RequestContext) -> HttpResponse {
+    get_preferences(&request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn set_preferences_handler(
+    preferences: web::Json<NotificationPreferences>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    set_preferences(&preferences, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::i18n::Language;
+
+    #[test]
+    fn test_release_at() {
+        // 22:00 to 07:00 in UTC-1
+        let quiet = QuietHours {
+            start_minute: 22 * 60,
+            end_minute: 7 * 60,
+            utc_offset_minutes: -60,
+        };
+        let day = 10 * 24 * 60 * 60;
+        let at = |hour: u64, minute: u64| day + hour * 3600 + minute * 60; // UTC
+        assert!(quiet.contains(at(23, 30))); // 22:30 for them
+        assert!(quiet.contains(at(3, 0)));
+        assert!(!quiet.contains(at(8, 0))); // 07:00 for them
+        assert!(!quiet.contains(at(12, 0)));
+        assert_eq!(quiet.end_after(at(23, 30)), at(8, 0) + 24 * 60 * 60);
+        assert_eq!(quiet.end_after(at(3, 0) + 17), at(8, 0));
+
+        let preferences = NotificationPreferences {
+            game_push_minutes: 10,
+            quiet_hours: Some(quiet),
+        };
+        assert_eq!(preferences.release_at(None, at(12, 0)), at(12, 0));
+        assert_eq!(preferences.release_at(Some(at(11, 55)), at(12, 0)), at(12, 5));
+        assert_eq!(preferences.release_at(Some(at(11, 0)), at(12, 0)), at(12, 0));
+        assert_eq!(preferences.release_at(None, at(3, 0)), at(8, 0));
+        // the batching window runs into the quiet hours
+        assert_eq!(preferences.release_at(Some(at(22, 55)), at(23, 0)), at(8, 0) + 24 * 60 * 60);
+        assert_eq!(NotificationPreferences::default().release_at(Some(at(11, 59)), at(12, 0)), at(12, 0));
+
+        assert!(preferences.check().is_ok());
+        let mut bad = preferences;
+        bad.game_push_minutes = MAX_GAME_PUSH_MINUTES + 1;
+        assert!(bad.check().is_err());
+        let mut bad = preferences;
+        bad.quiet_hours = Some(QuietHours { end_minute: 22 * 60, ..quiet });
+        assert!(bad.check().is_err());
+        bad.quiet_hours = Some(QuietHours { start_minute: 24 * 60, ..quiet });
+        assert!(bad.check().is_err());
+    }
+
+    #[test]
+    fn test_combine() {
+        let turn = |game_id: &str| PushNotification::turn_started(game_id, Language::English);
+        assert_eq!(combine("combine", &[]), None);
+        assert_eq!(combine("combine", &[turn("a"), turn("a")]), Some(turn("a")));
+        let digest = combine("combine", &[turn("a"), turn("b"), turn("a")]).expect("a digest");
+        assert_eq!(digest, PushNotification::digest(2, Language::English));
+    }
+}
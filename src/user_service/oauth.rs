@@ -0,0 +1,892 @@
+#![allow(dead_code)]
+/**
+ *  logging in with a Google or GitHub account instead of a password.  the client sends the browser to
+ *
+ *      GET /api/v1/users/oauth/{google|github}/start
+ *
+ *  which sets a cookie on the browser (OAUTH_COOKIE) and redirects it to the provider.  once the user has said yes
+ *  there, the provider sends the browser back to
+ *
+ *      GET /api/v1/users/oauth/{provider}/callback?code=...&state=...
+ *
+ *  the state has to be one that /start handed out for the same provider in the last OAUTH_STATE_SECONDS, to the same
+ *  browser -- the cookie has to come back with it -- and it is only good once.  so nobody can finish a login somebody
+ *  else started, or get a victim's browser to finish theirs.  the code is swapped for an access token with the PKCE
+ *  verifier only the service has, the token gets the account's id and verified email from the provider, and a new
+ *  session is started.  its tokens are never put in the browser: it is sent back to OAUTH_CLIENT_REDIRECT_URL with a
+ *  one time code (or an error), which the client swaps for the LoginTokens at
+ *
+ *      POST /api/v1/users/oauth/exchange
+ *
+ *  within LOGIN_CODE_SECONDS.
+ *
+ *  the provider's account is found by its id (see PersistUser::external_identities).  the first time it is seen a new
+ *  user is made for it, with the validated email the provider has checked and no password -- unless a user already
+ *  has that email, which is refused: the account is only linked to an existing user who signs in and asks for it, with
+ *
+ *      GET /auth/api/v1/users/oauth/{provider}/link
+ *
+ *  which starts the same dance for their user (their email has to be validated), and sends the browser back with
+ *  `linked` instead of a code.  a provider is only offered when its OAUTH_GOOGLE_CLIENT_ID and
+ *  OAUTH_GOOGLE_CLIENT_SECRET (or OAUTH_GITHUB_...) are set, OAUTH_REDIRECT_BASE_URL is the service's public url, which
+ *  the callback urls registered with the providers start with, and OAUTH_CLIENT_REDIRECT_URL is set.
+ */
+use std::{collections::HashMap, sync::Mutex};
+
+use actix_web::{
+    cookie::{time::Duration, Cookie, SameSite},
+    web, HttpRequest, HttpResponse,
+};
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::form_urlencoded;
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    shared::{
+        service_models::{PersistUser, Role},
+        shared_models::{GameError, PersonalInformation, ResponseType, ServiceResponse, UserProfile},
+    },
+    user_service::{refresh_tokens::LoginTokens, users},
+};
+
+/// how long the browser has to come back from the provider
+pub const OAUTH_STATE_SECONDS: u64 = 10 * 60;
+
+/// how long the client has to swap the callback's code for the login's tokens
+pub const LOGIN_CODE_SECONDS: u64 = 60;
+
+/// the cookie that ties a login to the browser that started it
+pub const OAUTH_COOKIE: &str = "catan_oauth";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OAuthProvider {
+    Google,
+    GitHub,
+}
+
+impl OAuthProvider {
+    /// the provider named in the url
+    pub fn from_path(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "google" => Some(OAuthProvider::Google),
+            "github" => Some(OAuthProvider::GitHub),
+            _ => None,
+        }
+    }
+
+    pub fn path(self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "google",
+            OAuthProvider::GitHub => "github",
+        }
+    }
+
+    fn authorize_url(self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+            OAuthProvider::GitHub => "https://github.com/login/oauth/authorize",
+        }
+    }
+
+    fn token_url(self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "https://oauth2.googleapis.com/token",
+            OAuthProvider::GitHub => "https://github.com/login/oauth/access_token",
+        }
+    }
+
+    fn scope(self) -> &'static str {
+        match self {
+            OAuthProvider::Google => "openid email profile",
+            OAuthProvider::GitHub => "read:user user:email",
+        }
+    }
+}
+
+/// an account at a provider that a user logs in with
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExternalIdentity {
+    pub provider: OAuthProvider,
+    pub subject: String, // the provider's id for the account -- unlike the email, it never changes
+    #[serde(default)]
+    pub linked_at: u64, // seconds since the epoch
+}
+
+/// the service's registration with a provider
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct OAuthConfig {
+    pub redirect_base_url: String, // https://host:port -- the callback urls registered with the providers start with it
+    pub client_redirect_url: String, // the client's page the callback sends the browser back to
+    pub google: Option<OAuthClient>,
+    pub github: Option<OAuthClient>,
+}
+
+impl OAuthConfig {
+    pub fn from_env() -> Self {
+        let set = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        let client = |prefix: &str| match (
+            set(&format!("{}_CLIENT_ID", prefix)),
+            set(&format!("{}_CLIENT_SECRET", prefix)),
+        ) {
+            (Some(client_id), Some(client_secret)) => Some(OAuthClient {
+                client_id,
+                client_secret,
+            }),
+            _ => None,
+        };
+        Self {
+            redirect_base_url: set("OAUTH_REDIRECT_BASE_URL")
+                .map(|url| url.trim_end_matches('/').to_owned())
+                .unwrap_or_default(),
+            client_redirect_url: set("OAUTH_CLIENT_REDIRECT_URL").unwrap_or_default(),
+            google: client("OAUTH_GOOGLE"),
+            github: client("OAUTH_GITHUB"),
+        }
+    }
+
+    pub fn client(&self, provider: OAuthProvider) -> Option<&OAuthClient> {
+        match provider {
+            OAuthProvider::Google => self.google.as_ref(),
+            OAuthProvider::GitHub => self.github.as_ref(),
+        }
+    }
+
+    /// where the provider sends the browser back to
+    pub fn redirect_uri(&self, provider: OAuthProvider) -> String {
+        format!(
+            "{}/api/v1/users/oauth/{}/callback",
+            self.redirect_base_url,
+            provider.path()
+        )
+    }
+
+    /// the client's page, with the callback's answer in the query
+    pub fn client_redirect(&self, pairs: &[(&str, &str)]) -> String {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish();
+        let separator = if self.client_redirect_url.contains('?') { '&' } else { '?' };
+        format!("{}{}{}", self.client_redirect_url, separator, query)
+    }
+}
+
+/// what the provider told us about the account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderAccount {
+    pub provider: OAuthProvider,
+    pub subject: String,
+    pub email: Option<String>, // only an email the provider has verified
+    pub display_name: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub picture_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>, // the user said no, or the provider didn't like the request
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExchangeData {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingLogin {
+    provider: OAuthProvider,
+    browser: String,              // the OAUTH_COOKIE set on the browser that started it
+    verifier: String,             // the PKCE code verifier -- only its hash went to the provider
+    link_user_id: Option<String>, // the signed in user the account is being linked to
+    expires_at: u64,
+}
+
+#[derive(Debug, Clone)]
+struct IssuedLogin {
+    tokens: LoginTokens,
+    expires_at: u64,
+}
+
+lazy_static::lazy_static! {
+    // state -> the login it was handed out for
+    static ref PENDING_LOGINS: Mutex<HashMap<String, PendingLogin>> = Mutex::new(HashMap::new());
+    // one time code -> the tokens of the login the callback started
+    static ref LOGIN_CODES: Mutex<HashMap<String, IssuedLogin>> = Mutex::new(HashMap::new());
+}
+
+fn bad_request(message: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        message,
+        StatusCode::BAD_REQUEST,
+        ResponseType::NoData,
+        GameError::HttpError(StatusCode::BAD_REQUEST),
+    )
+}
+
+fn conflict(message: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        message,
+        StatusCode::CONFLICT,
+        ResponseType::NoData,
+        GameError::HttpError(StatusCode::CONFLICT),
+    )
+}
+
+fn provider_error(provider: OAuthProvider, e: &str) -> ServiceResponse {
+    tracing::warn!("the {:?} login failed: {}", provider, e);
+    ServiceResponse::new(
+        &format!("{:?} didn't log you in -- try again", provider),
+        StatusCode::BAD_GATEWAY,
+        ResponseType::ErrorInfo(e.to_owned()),
+        GameError::HttpError(StatusCode::BAD_GATEWAY),
+    )
+}
+
+/// the provider, if it is one we know and it has been set up
+fn configured(
+    provider_name: &str,
+    request_context: &RequestContext,
+) -> Result<(OAuthProvider, OAuthClient), ServiceResponse> {
+    let not_offered = || {
+        ServiceResponse::new(
+            &format!("logging in with {} isn't offered", provider_name),
+            StatusCode::NOT_FOUND,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::NOT_FOUND),
+        )
+    };
+    let provider = OAuthProvider::from_path(provider_name).ok_or_else(not_offered)?;
+    let client = request_context.config.oauth.client(provider).cloned().ok_or_else(not_offered)?;
+    if request_context.config.oauth.client_redirect_url.is_empty() {
+        return Err(not_offered());
+    }
+    Ok((provider, client))
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, bytes)
+}
+
+/// the S256 PKCE challenge the provider is sent for the verifier
+pub fn code_challenge(verifier: &str) -> String {
+    base64::Engine::encode(
+        &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+        Sha256::digest(verifier.as_bytes()),
+    )
+}
+
+/// a new login with the provider.  returns its state and what is kept until the browser comes back
+fn new_login(provider: OAuthProvider, link_user_id: Option<String>, now: u64) -> (String, PendingLogin) {
+    let state = random_token();
+    let login = PendingLogin {
+        provider,
+        browser: random_token(),
+        verifier: random_token(),
+        link_user_id,
+        expires_at: now + OAUTH_STATE_SECONDS,
+    };
+    let mut pending = PENDING_LOGINS.lock().expect("the oauth lock shouldn't be poisoned");
+    pending.retain(|_, login| login.expires_at > now);
+    pending.insert(state.clone(), login.clone());
+    (state, login)
+}
+
+/**
+ *  the login, if /start handed out the state for this provider to this browser and it hasn't run out.  either way the
+ *  state can't be used again
+ */
+fn take_state(state: &str, provider: OAuthProvider, browser: Option<&str>, now: u64) -> Option<PendingLogin> {
+    PENDING_LOGINS
+        .lock()
+        .expect("the oauth lock shouldn't be poisoned")
+        .remove(state)
+        .filter(|login| login.provider == provider && login.expires_at > now && browser == Some(&login.browser))
+}
+
+/// where to send the browser to start a login with the provider, and the value of the cookie to set on it
+pub fn start(
+    provider_name: &str,
+    link_user_id: Option<String>,
+    request_context: &RequestContext,
+) -> Result<(String, String), ServiceResponse> {
+    let (provider, client) = configured(provider_name, request_context)?;
+    let (state, login) = new_login(provider, link_user_id, request_context.environment.now());
+    let query: String = form_urlencoded::Serializer::new(String::new())
+        .append_pair("client_id", &client.client_id)
+        .append_pair("redirect_uri", &request_context.config.oauth.redirect_uri(provider))
+        .append_pair("response_type", "code")
+        .append_pair("scope", provider.scope())
+        .append_pair("state", &state)
+        .append_pair("code_challenge", &code_challenge(&login.verifier))
+        .append_pair("code_challenge_method", "S256")
+        .finish();
+    Ok((format!("{}?{}", provider.authorize_url(), query), login.browser))
+}
+
+/// start linking an account at the provider to the caller.  their email has to be validated
+pub async fn link(provider_name: &str, request_context: &RequestContext) -> Result<(String, String), ServiceResponse> {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call");
+    let user = request_context.database.find_user_by_id(&claims.id).await?;
+    if !user.user_profile.validated_email {
+        return Err(bad_request("validate your email before linking an account to it"));
+    }
+    start(provider_name, Some(user.id), request_context)
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>, // GitHub says what went wrong with a 200
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleUser {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    name: Option<String>,
+    given_name: Option<String>,
+    family_name: Option<String>,
+    picture: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    id: u64,
+    login: String,
+    name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// swap the code for an access token, and ask the provider who it is for
+async fn fetch_account(
+    provider: OAuthProvider,
+    client: &OAuthClient,
+    code: &str,
+    verifier: &str,
+    redirect_uri: &str,
+) -> Result<ProviderAccount, String> {
+    let http = reqwest::Client::new();
+    let token: TokenResponse = http
+        .post(provider.token_url())
+        .header("Accept", "application/json")
+        .form(&[
+            ("client_id", client.client_id.as_str()),
+            ("client_secret", client.client_secret.as_str()),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("grant_type", "authorization_code"),
+            ("code_verifier", verifier),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    let access_token = match (token.access_token, token.error) {
+        (Some(access_token), _) => access_token,
+        (None, error) => return Err(error.unwrap_or_else(|| "no access token".to_owned())),
+    };
+    //  GitHub turns away calls without a User-Agent
+    let get = |url: &str| {
+        http.get(url)
+            .bearer_auth(&access_token)
+            .header("User-Agent", "CatanService")
+            .header("Accept", "application/json")
+    };
+    let split_name = |name: &str| {
+        let mut parts = name.splitn(2, ' ');
+        let first = parts.next().unwrap_or_default().to_owned();
+        (first, parts.next().unwrap_or_default().to_owned())
+    };
+    match provider {
+        OAuthProvider::Google => {
+            let user: GoogleUser = get("https://openidconnect.googleapis.com/v1/userinfo")
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .json()
+                .await
+                .map_err(|e| e.to_string())?;
+            let display_name = user.name.clone().unwrap_or_default();
+            let (first, last) = split_name(&display_name);
+            Ok(ProviderAccount {
+                provider,
+                subject: user.sub,
+                email: user.email.filter(|_| user.email_verified),
+                display_name,
+                first_name: user.given_name.unwrap_or(first),
+                last_name: user.family_name.unwrap_or(last),
+                picture_url: user.picture.unwrap_or_default(),
+            })
+        }
+        OAuthProvider::GitHub => {
+            let user: GitHubUser = get("https://api.github.com/user")
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .json()
+                .await
+                .map_err(|e| e.to_string())?;
+            //  the profile's email is whatever the user typed in: the verified ones are a separate call
+            let emails: Vec<GitHubEmail> = get("https://api.github.com/user/emails")
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .json()
+                .await
+                .map_err(|e| e.to_string())?;
+            let email = emails
+                .iter()
+                .filter(|email| email.verified)
+                .max_by_key(|email| email.primary)
+                .map(|email| email.email.clone());
+            let display_name = user.name.unwrap_or_else(|| user.login.clone());
+            let (first_name, last_name) = split_name(&display_name);
+            Ok(ProviderAccount {
+                provider,
+                subject: user.id.to_string(),
+                email,
+                display_name,
+                first_name,
+                last_name,
+                picture_url: user.avatar_url.unwrap_or_default(),
+            })
+        }
+    }
+}
+
+/// a user for an account we haven't seen before, and whose email isn't one of ours
+fn new_user(account: &ProviderAccount, email: &str, request_context: &RequestContext) -> PersistUser {
+    let mut user = PersistUser::new();
+    user.password_hash = None; // they log in with the provider
+    user.user_profile = UserProfile {
+        user_id: Some(user.id.clone()),
+        pii: Some(PersonalInformation {
+            phone_number: String::default(),
+            email: email.to_owned(),
+            first_name: account.first_name.clone(),
+            last_name: account.last_name.clone(),
+        }),
+        display_name: account.display_name.clone(),
+        picture_url: account.picture_url.clone(),
+        games_played: Some(0),
+        games_won: Some(0),
+        validated_email: true, // the provider has checked that the email is theirs
+        ..UserProfile::default()
+    };
+    //  the same bootstrap as register: whoever owns the admin email is the admin
+    if email == request_context.config.admin_email {
+        user.roles.push(Role::Admin);
+    }
+    user
+}
+
+/**
+ *  link the provider's account to the signed in user.  it can't already be another user's, and their email has to be
+ *  validated
+ */
+pub async fn link_account(
+    account: &ProviderAccount,
+    user_id: &str,
+    request_context: &RequestContext,
+) -> Result<(), ServiceResponse> {
+    let database = &request_context.database;
+    match database
+        .find_user_by_external_identity(account.provider, &account.subject)
+        .await
+    {
+        Ok(owner) if owner.id == user_id => return Ok(()),
+        Ok(_) => {
+            return Err(conflict(&format!(
+                "the {:?} account is already linked to another user",
+                account.provider
+            )))
+        }
+        Err(e) if e.status == StatusCode::NOT_FOUND => {}
+        Err(e) => return Err(e),
+    }
+    let mut user = database.find_user_by_id(user_id).await?;
+    if !user.user_profile.validated_email {
+        return Err(bad_request("validate your email before linking an account to it"));
+    }
+    tracing::info!("linking a {:?} account to {}", account.provider, user.id);
+    user.external_identities.push(ExternalIdentity {
+        provider: account.provider,
+        subject: account.subject.clone(),
+        linked_at: request_context.environment.now(),
+    });
+    database.update_or_create_user(&user).await?;
+    Ok(())
+}
+
+/**
+ *  log in the user the provider's account belongs to, making a new user for it the first time it is used.  an account
+ *  with the email of a user it isn't linked to is refused -- that user has to sign in and link it
+ */
+pub async fn login_with_account(
+    account: &ProviderAccount,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let database = &request_context.database;
+    let user = match database
+        .find_user_by_external_identity(account.provider, &account.subject)
+        .await
+    {
+        Ok(user) => user,
+        Err(e) if e.status == StatusCode::NOT_FOUND => {
+            let email = match &account.email {
+                Some(email) => email.clone(),
+                None => {
+                    return Err(bad_request(&format!(
+                        "the {:?} account doesn't have a verified email",
+                        account.provider
+                    )))
+                }
+            };
+            match database.find_user_by_email(&email).await {
+                Ok(_) => {
+                    return Err(conflict(&format!(
+                        "{} already has an account -- log in to it and link your {:?} account from there",
+                        email, account.provider
+                    )))
+                }
+                Err(e) if e.status == StatusCode::NOT_FOUND => {}
+                Err(e) => return Err(e),
+            }
+            let mut user = new_user(account, &email, request_context);
+            user.external_identities.push(ExternalIdentity {
+                provider: account.provider,
+                subject: account.subject.clone(),
+                linked_at: request_context.environment.now(),
+            });
+            database.update_or_create_user(&user).await?;
+            user
+        }
+        Err(e) => return Err(e),
+    };
+    if user.must_reset_password {
+        return Err(users::require_password_reset(&user, request_context));
+    }
+    let email = user
+        .user_profile
+        .pii
+        .as_ref()
+        .map_or_else(|| user.id.clone(), |pii| pii.email.clone());
+    users::start_login(&user, &email, request_context).await
+}
+
+/// a one time code for the login's tokens
+fn new_login_code(tokens: LoginTokens, now: u64) -> String {
+    let code = random_token();
+    let mut codes = LOGIN_CODES.lock().expect("the oauth lock shouldn't be poisoned");
+    codes.retain(|_, issued| issued.expires_at > now);
+    codes.insert(
+        code.clone(),
+        IssuedLogin {
+            tokens,
+            expires_at: now + LOGIN_CODE_SECONDS,
+        },
+    );
+    code
+}
+
+/// the tokens of the login the code was handed out for.  the code only works once
+pub fn exchange(code: &str, request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let now = request_context.environment.now();
+    match LOGIN_CODES
+        .lock()
+        .expect("the oauth lock shouldn't be poisoned")
+        .remove(code.trim())
+        .filter(|issued| issued.expires_at > now)
+    {
+        Some(issued) => Ok(ServiceResponse::new(
+            "",
+            StatusCode::OK,
+            ResponseType::LoginTokens(issued.tokens),
+            GameError::NoError("ok".to_owned()),
+        )),
+        None => Err(ServiceResponse::new(
+            "the code has run out, or has been used",
+            StatusCode::UNAUTHORIZED,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::UNAUTHORIZED),
+        )),
+    }
+}
+
+/// the browser is back from the provider.  returns where to send it: the client's page, with a code or `linked`
+pub async fn callback(
+    provider_name: &str,
+    query: &CallbackQuery,
+    browser: Option<&str>,
+    request_context: &RequestContext,
+) -> Result<String, ServiceResponse> {
+    let (provider, client) = configured(provider_name, request_context)?;
+    if let Some(error) = &query.error {
+        return Err(bad_request(&format!("{:?} said: {}", provider, error)));
+    }
+    let (code, state) = match (&query.code, &query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(bad_request("the code and state are missing")),
+    };
+    let now = request_context.environment.now();
+    let login = match take_state(state, provider, browser, now) {
+        Some(login) => login,
+        None => return Err(bad_request("the login has run out, or wasn't started here -- start again")),
+    };
+    let redirect_uri = request_context.config.oauth.redirect_uri(provider);
+    let account = fetch_account(provider, &client, code, &login.verifier, &redirect_uri)
+        .await
+        .map_err(|e| provider_error(provider, &e))?;
+    let oauth = &request_context.config.oauth;
+    if let Some(user_id) = &login.link_user_id {
+        link_account(&account, user_id, request_context).await?;
+        return Ok(oauth.client_redirect(&[("linked", provider.path())]));
+    }
+    match login_with_account(&account, request_context).await?.response_type {
+        ResponseType::LoginTokens(tokens) => {
+            let code = new_login_code(tokens, now);
+            Ok(oauth.client_redirect(&[("code", code.as_str())]))
+        },
+        other => Err(ServiceResponse::new(
+            "the login didn't make any tokens",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ResponseType::ErrorInfo(format!("{:?}", other)),
+            GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+        )),
+    }
+}
+
+/// the cookie that ties the login to the browser.  only sent back to the callback
+fn browser_cookie(value: &str, max_age: u64) -> Cookie<'static> {
+    Cookie::build(OAUTH_COOKIE, value.to_owned())
+        .path("/api/v1/users/oauth")
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Lax) // the provider's redirect back is a top level GET, which Lax lets it come with
+        .max_age(Duration::seconds(max_age as i64))
+        .finish()
+}
+
+pub async fn start_handler(provider: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    match start(&provider, None, &request_context) {
+        Ok((url, browser)) => HttpResponse::Found()
+            .append_header(("Location", url))
+            .cookie(browser_cookie(&browser, OAUTH_STATE_SECONDS))
+            .finish(),
+        Err(sr) => sr.to_http_response(),
+    }
+}
+
+/// like start, but answers with the url for the client to open instead of a redirect
+pub async fn link_handler(provider: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    match link(&provider, &request_context).await {
+        Ok((url, browser)) => {
+            let mut response = ServiceResponse::new(
+                "",
+                StatusCode::OK,
+                ResponseType::Url(url),
+                GameError::NoError(String::default()),
+            )
+            .to_http_response();
+            if let Err(e) = response.add_cookie(&browser_cookie(&browser, OAUTH_STATE_SECONDS)) {
+                tracing::error!("couldn't set the oauth cookie: {}", e);
+            }
+            response
+        }
+        Err(sr) => sr.to_http_response(),
+    }
+}
+
+pub async fn callback_handler(
+    req: HttpRequest,
+    provider: web::Path<String>,
+    query: web::Query<CallbackQuery>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    //  the provider isn't one of ours: there is no client page to go back to
+    if let Err(sr) = configured(&provider, &request_context) {
+        return sr.to_http_response();
+    }
+    let browser = req.cookie(OAUTH_COOKIE).map(|cookie| cookie.value().to_owned());
+    let location = match callback(&provider, &query, browser.as_deref(), &request_context).await {
+        Ok(location) => location,
+        Err(sr) => request_context.config.oauth.client_redirect(&[("error", sr.message.as_str())]),
+    };
+    HttpResponse::Found()
+        .append_header(("Location", location))
+        .cookie(browser_cookie("", 0)) // the login is over, one way or the other
+        .finish()
+}
+
+pub async fn exchange_handler(data: web::Json<ExchangeData>, request_context: RequestContext) -> HttpResponse {
+    exchange(&data.code, &request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(subject: &str, email: Option<&str>) -> ProviderAccount {
+        ProviderAccount {
+            provider: OAuthProvider::GitHub,
+            subject: subject.to_owned(),
+            email: email.map(|email| email.to_owned()),
+            display_name: "Octo Cat".to_owned(),
+            first_name: "Octo".to_owned(),
+            last_name: "Cat".to_owned(),
+            picture_url: String::default(),
+        }
+    }
+
+    #[test]
+    fn test_state() {
+        let (state, login) = new_login(OAuthProvider::Google, None, 1000);
+        assert!(take_state(&state, OAuthProvider::GitHub, Some(&login.browser), 1001).is_none());
+        let (state, login) = new_login(OAuthProvider::Google, None, 1000);
+        assert_eq!(
+            take_state(&state, OAuthProvider::Google, Some(&login.browser), 1001),
+            Some(login.clone())
+        );
+        assert!(take_state(&state, OAuthProvider::Google, Some(&login.browser), 1002).is_none()); // only once
+        let (state, login) = new_login(OAuthProvider::Google, None, 1000);
+        assert!(take_state(&state, OAuthProvider::Google, Some(&login.browser), 1000 + OAUTH_STATE_SECONDS).is_none());
+        assert!(take_state("made up", OAuthProvider::Google, Some(&login.browser), 1001).is_none());
+
+        // another browser -- or one without the cookie -- can't finish the login
+        let (state, _) = new_login(OAuthProvider::Google, None, 1000);
+        assert!(take_state(&state, OAuthProvider::Google, Some("another browser"), 1001).is_none());
+        let (state, _) = new_login(OAuthProvider::Google, None, 1000);
+        assert!(take_state(&state, OAuthProvider::Google, None, 1001).is_none());
+
+        assert_eq!(OAuthProvider::from_path("GitHub"), Some(OAuthProvider::GitHub));
+        assert_eq!(OAuthProvider::from_path("myspace"), None);
+
+        // the example from RFC 7636
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mJ92IrQpDH-pjyt8p6BmXFJ1bsaJWo"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_with_account() {
+        let request_context = RequestContext::test_default(false);
+        let database = &request_context.database;
+
+        // no verified email, no account
+        assert!(login_with_account(&account("1001", None), &request_context).await.is_err());
+
+        // the first login makes a user
+        login_with_account(&account("1001", Some("octocat@example.com")), &request_context)
+            .await
+            .expect("a new user");
+        let user = database
+            .find_user_by_external_identity(OAuthProvider::GitHub, "1001")
+            .await
+            .expect("the account is linked");
+        assert!(user.password_hash.is_none());
+        assert!(user.user_profile.validated_email);
+
+        // the account is found by its id, even if its email has changed since
+        login_with_account(&account("1001", Some("moved@example.com")), &request_context)
+            .await
+            .expect("the same user");
+        let again = database.find_user_by_external_identity(OAuthProvider::GitHub, "1001").await.unwrap();
+        assert_eq!(again.id, user.id);
+        assert!(database.find_user_by_email("moved@example.com").await.is_err());
+
+        // an account with the email of a user who registered with a password isn't linked to them...
+        let mut registered = PersistUser::new();
+        registered.user_profile.pii = Some(PersonalInformation {
+            phone_number: String::default(),
+            email: "registered@example.com".to_owned(),
+            first_name: "Reg".to_owned(),
+            last_name: "Istered".to_owned(),
+        });
+        database.update_or_create_user(&registered).await.unwrap();
+        let refused = login_with_account(&account("1002", Some("registered@example.com")), &request_context)
+            .await
+            .expect_err("the email is somebody else's");
+        assert_eq!(refused.status, StatusCode::CONFLICT);
+        assert!(database.find_user_by_external_identity(OAuthProvider::GitHub, "1002").await.is_err());
+        let unchanged = database.find_user_by_id(&registered.id).await.unwrap();
+        assert!(!unchanged.user_profile.validated_email);
+
+        // ...unless they sign in and link it, once their email is validated
+        assert!(link_account(&account("1002", Some("registered@example.com")), &registered.id, &request_context)
+            .await
+            .is_err());
+        registered.user_profile.validated_email = true;
+        database.update_or_create_user(&registered).await.unwrap();
+        link_account(&account("1002", Some("registered@example.com")), &registered.id, &request_context)
+            .await
+            .expect("the registered user links it");
+        let linked = database.find_user_by_external_identity(OAuthProvider::GitHub, "1002").await.unwrap();
+        assert_eq!(linked.id, registered.id);
+
+        // an account can't be taken from the user it is linked to
+        let taken = link_account(&account("1001", None), &registered.id, &request_context)
+            .await
+            .expect_err("it is octocat's");
+        assert_eq!(taken.status, StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_exchange() {
+        let request_context = RequestContext::test_default(false);
+        let now = request_context.environment.now();
+        let tokens = LoginTokens {
+            access_token: "access".to_owned(),
+            expires_in: 60,
+            refresh_token: "refresh".to_owned(),
+            refresh_expires_at: now + 60,
+        };
+        let code = new_login_code(tokens.clone(), now);
+        match exchange(&code, &request_context).expect("the code is good").response_type {
+            ResponseType::LoginTokens(exchanged) => assert_eq!(exchanged, tokens),
+            other => panic!("expected LoginTokens, got {:?}", other),
+        }
+        assert!(exchange(&code, &request_context).is_err()); // only once
+        assert!(exchange("made up", &request_context).is_err());
+
+        let config = OAuthConfig {
+            client_redirect_url: "https://catan.example.com/login?from=oauth".to_owned(),
+            ..OAuthConfig::default()
+        };
+        assert_eq!(
+            config.client_redirect(&[("error", "no way")]),
+            "https://catan.example.com/login?from=oauth&error=no+way"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+#![allow(dead_code)]
+/**
+ *  what a password has to be.  register and reset_password check the password a user picks (test users, which only an
+ *  admin can make, aren't checked) and refuse it with a 400 that lists every rule it broke as PasswordProblems, so a
+ *  client can say what to fix:
+ *
+ *  - TooGuessable: its estimated entropy is less than PASSWORD_MIN_ENTROPY_BITS (40).  the estimate is its length
+ *    times the bits of the kinds of characters in it (lowercase, uppercase, digits, symbols, anything else), so a
+ *    long password does better than a clever short one
+ *  - Common: it is one of the most used passwords, ignoring case and any digits or symbols tacked on the end
+ *  - Breached: it has turned up in a breach.  only checked if PASSWORD_BREACH_CHECK is set: HaveIBeenPwned is asked for
+ *    the breached passwords whose SHA-1 starts with the same 5 characters as this one's, and the rest of the hash is
+ *    matched here -- so neither the password nor its hash leaves the service.  if HaveIBeenPwned can't be reached the
+ *    password isn't refused for it
+ */
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    shared::shared_models::{GameError, ResponseType, ServiceResponse},
+};
+
+/// the HaveIBeenPwned range api: the 5 character prefix of the SHA-1 goes on the end
+pub const PWNED_RANGE_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+/// how long a user waits on HaveIBeenPwned before the check is given up on
+const BREACH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// the most used passwords, lowercase
+const COMMON_PASSWORDS: [&str; 40] = [
+    "123456", "123456789", "12345678", "12345", "1234567", "1234567890", "111111", "000000", "123123", "654321",
+    "password", "passw0rd", "qwerty", "qwertyuiop", "abc123", "letmein", "welcome", "monkey", "dragon", "football",
+    "baseball", "iloveyou", "admin", "login", "princess", "sunshine", "master", "shadow", "superman", "trustno1",
+    "starwars", "whatever", "freedom", "hello", "charlie", "michael", "jennifer", "catan", "settlers", "changeme",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PasswordPolicy {
+    pub min_entropy_bits: u32,
+    pub check_breaches: bool, // ask HaveIBeenPwned
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_entropy_bits: 40,
+            check_breaches: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            min_entropy_bits: std::env::var("PASSWORD_MIN_ENTROPY_BITS")
+                .ok()
+                .and_then(|value| value.trim().parse::<u32>().ok())
+                .unwrap_or(default.min_entropy_bits),
+            check_breaches: std::env::var("PASSWORD_BREACH_CHECK")
+                .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+                .unwrap_or(default.check_breaches),
+        }
+    }
+}
+
+/// a rule the password broke
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum PasswordProblem {
+    TooGuessable { entropy_bits: u32, required_bits: u32 },
+    Common,
+    Breached { times_seen: u64 },
+}
+
+/// the length of the password times the bits of the kinds of characters in it
+pub fn entropy_bits(password: &str) -> u32 {
+    let has = |kind: fn(&char) -> bool| password.chars().any(|c| kind(&c));
+    let mut pool = 0;
+    if has(char::is_ascii_lowercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_uppercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_digit) {
+        pool += 10;
+    }
+    if has(|c| c.is_ascii_punctuation() || *c == ' ') {
+        pool += 33;
+    }
+    if has(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0;
+    }
+    (password.chars().count() as f64 * (pool as f64).log2()) as u32
+}
+
+/// one of COMMON_PASSWORDS, whatever the case, with or without digits and symbols on the end
+pub fn is_common(password: &str) -> bool {
+    let password = password.to_lowercase();
+    let stem = password.trim_end_matches(|c: char| !c.is_alphabetic());
+    COMMON_PASSWORDS.contains(&password.as_str()) || COMMON_PASSWORDS.contains(&stem)
+}
+
+/// the rules the password breaks that can be checked here
+pub fn problems(password: &str, policy: &PasswordPolicy) -> Vec<PasswordProblem> {
+    let mut problems = Vec::new();
+    let entropy_bits = entropy_bits(password);
+    if entropy_bits < policy.min_entropy_bits {
+        problems.push(PasswordProblem::TooGuessable {
+            entropy_bits,
+            required_bits: policy.min_entropy_bits,
+        });
+    }
+    if is_common(password) {
+        problems.push(PasswordProblem::Common);
+    }
+    problems
+}
+
+/// how many times the hash suffix is in a range HaveIBeenPwned returned ("SUFFIX:COUNT" lines)
+pub fn times_seen(range: &str, suffix: &str) -> u64 {
+    range
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(line_suffix, _)| line_suffix.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn sha1_hex(password: &str) -> String {
+    openssl::sha::sha1(password.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect()
+}
+
+/// how many times the password has been seen in a breach.  only the first 5 characters of its hash are sent
+async fn breach_count(password: &str) -> Result<u64, String> {
+    let hash = sha1_hex(password);
+    let (prefix, suffix) = hash.split_at(5);
+    let client = reqwest::Client::builder()
+        .timeout(BREACH_CHECK_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let range = client
+        .get(format!("{}{}", PWNED_RANGE_URL, prefix))
+        .header("Add-Padding", "true") // so the size of the answer doesn't say anything either
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(times_seen(&range, suffix))
+}
+
+/// Ok if the password can be used, or a 400 with the PasswordProblems.  breaches aren't checked in tests
+pub async fn check(password: &str, request_context: &RequestContext) -> Result<(), ServiceResponse> {
+    let policy = &request_context.config.password_policy;
+    let mut problems = problems(password, policy);
+    if policy.check_breaches && !request_context.is_test() {
+        match breach_count(password).await {
+            Ok(0) => {}
+            Ok(times_seen) => problems.push(PasswordProblem::Breached { times_seen }),
+            Err(e) => tracing::warn!("couldn't check a password against the known breaches: {}", e),
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(ServiceResponse::new(
+        "the password isn't strong enough",
+        StatusCode::BAD_REQUEST,
+        ResponseType::PasswordProblems(problems),
+        GameError::HttpError(StatusCode::BAD_REQUEST),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entropy_bits() {
+        assert_eq!(entropy_bits(""), 0);
+        // 8 lowercase letters: 8 * log2(26)
+        assert_eq!(entropy_bits("abcdefgh"), 37);
+        assert!(entropy_bits("correct horse battery staple") > entropy_bits("Tr0ub4dor&3"));
+        assert!(entropy_bits("ünïcödé") > entropy_bits("unicode"));
+    }
+
+    #[test]
+    fn test_problems() {
+        let policy = PasswordPolicy::default();
+        assert!(is_common("Password123!"));
+        assert!(is_common("QWERTY"));
+        assert!(!is_common("a new password"));
+        assert_eq!(
+            problems("password", &policy),
+            vec![
+                PasswordProblem::TooGuessable {
+                    entropy_bits: 37,
+                    required_bits: 40
+                },
+                PasswordProblem::Common,
+            ]
+        );
+        assert_eq!(problems("Sunshine2024!!!", &policy), vec![PasswordProblem::Common]);
+        assert!(problems("a new password", &policy).is_empty());
+        let lax = PasswordPolicy {
+            min_entropy_bits: 0,
+            ..policy
+        };
+        assert!(problems("zq", &lax).is_empty());
+    }
+
+    #[test]
+    fn test_times_seen() {
+        // "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        let hash = sha1_hex("password");
+        assert_eq!(hash, "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8");
+        let range = "003D68EB55068C33ACE09247EE4C639306B:3\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n\
+                     0018A45C4D1DEF81644B54AB7F969B88D65:0";
+        assert_eq!(times_seen(range, &hash[5..]), 9659365);
+        assert_eq!(times_seen(range, &sha1_hex("a new password")[5..]), 0);
+    }
+}
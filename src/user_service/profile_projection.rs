@@ -0,0 +1,126 @@
+#![allow(dead_code)]
+/**
+ *  what a caller gets to see of somebody's profile.  every api that returns another user's profile goes through
+ *  project(), so the rules are in one place:
+ *
+ *      Admin, Myself   everything
+ *      Friend          the public fields, their stats, and their name -- but not their email or phone number, and not
+ *                      whether those have been validated
+ *      Stranger        the public fields only: display name, picture, colors and user type
+ *
+ *  nobody but the user and admins sees who the user has blocked.
+ *
+ *  a friend is one of the caller's own local users (relationship), or somebody they are playing a game with
+ *  (co_player_relationship -- the game apis know who is in the game, so they use that one for the players in it).
+ */
+use crate::shared::{
+    service_models::{Claims, PersistUser, Role},
+    shared_models::{PersonalInformation, UserProfile},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relationship {
+    Admin,
+    Myself,
+    Friend,
+    Stranger,
+}
+
+/// how the caller is related to the user
+pub fn relationship(claims: Option<&Claims>, user: &PersistUser) -> Relationship {
+    match claims {
+        None => Relationship::Stranger,
+        Some(claims) if claims.roles.contains(&Role::Admin) => Relationship::Admin,
+        Some(claims) if claims.id == user.id => Relationship::Myself,
+        Some(claims) if user.connected_user_id.as_deref() == Some(claims.id.as_str()) => Relationship::Friend,
+        Some(_) => Relationship::Stranger,
+    }
+}
+
+/// how the caller is related to somebody in a game they are playing: everybody else in the game is a friend
+pub fn co_player_relationship(claims: Option<&Claims>, user_id: &str) -> Relationship {
+    match claims {
+        None => Relationship::Stranger,
+        Some(claims) if claims.roles.contains(&Role::Admin) => Relationship::Admin,
+        Some(claims) if claims.id == user_id => Relationship::Myself,
+        Some(_) => Relationship::Friend,
+    }
+}
+
+/// the profile with only what the relationship allows
+pub fn project(profile: &UserProfile, relationship: Relationship) -> UserProfile {
+    let mut projected = profile.clone();
+    match relationship {
+        Relationship::Admin | Relationship::Myself => {}
+        Relationship::Friend => {
+            projected.pii = profile.pii.as_ref().map(|pii| PersonalInformation {
+                phone_number: String::default(),
+                email: String::default(),
+                first_name: pii.first_name.clone(),
+                last_name: pii.last_name.clone(),
+            });
+            projected.validated_email = false;
+            projected.validated_phone = false;
+            projected.blocked_user_ids = Vec::new();
+        }
+        Relationship::Stranger => {
+            projected.pii = None;
+            projected.validated_email = false;
+            projected.validated_phone = false;
+            projected.games_played = None;
+            projected.games_won = None;
+            projected.blocked_user_ids = Vec::new();
+        }
+    }
+    projected
+}
+
+/// the user's profile as the caller is allowed to see it
+pub fn project_user(user: &PersistUser, claims: Option<&Claims>) -> UserProfile {
+    project(&UserProfile::from_persist_user(user), relationship(claims, user))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_profile() {
+        let profile = UserProfile::new_test_user(Some("1".to_string()));
+        let mut user = PersistUser::from_user_profile(&profile, "hash".to_string());
+        user.user_profile.validated_email = true;
+        user.user_profile.games_played = Some(3);
+
+        let me = Claims::new("1", "me@test.com", 60, &vec![Role::User], &None);
+        let other = Claims::new("2", "other@test.com", 60, &vec![Role::User], &None);
+        let admin = Claims::new("3", "admin@test.com", 60, &vec![Role::Admin], &None);
+
+        assert_eq!(relationship(Some(&me), &user), Relationship::Myself);
+        assert_eq!(relationship(Some(&other), &user), Relationship::Stranger);
+        assert_eq!(relationship(Some(&admin), &user), Relationship::Admin);
+        assert_eq!(project_user(&user, Some(&me)), UserProfile::from_persist_user(&user));
+
+        let stranger = project_user(&user, Some(&other));
+        assert!(stranger.pii.is_none());
+        assert!(!stranger.validated_email);
+        assert_eq!(stranger.games_played, None);
+        assert_eq!(stranger.display_name, profile.display_name);
+
+        let friend = project(&user.user_profile, Relationship::Friend);
+        let pii = friend.pii.expect("friends see the name");
+        assert_eq!(pii.email, "");
+        assert_eq!(pii.first_name, profile.pii.unwrap().first_name);
+        assert_eq!(friend.games_played, Some(3));
+
+        // the caller's own local users are friends
+        let mut local = UserProfile::default();
+        local.user_type = crate::shared::shared_models::UserType::Local;
+        let local = PersistUser::from_local_user("2", &local);
+        assert_eq!(relationship(Some(&other), &local), Relationship::Friend);
+
+        // and so are the players in a game with them
+        assert_eq!(co_player_relationship(Some(&other), "1"), Relationship::Friend);
+        assert_eq!(co_player_relationship(Some(&other), "2"), Relationship::Myself);
+        assert_eq!(co_player_relationship(None, "1"), Relationship::Stranger);
+    }
+}
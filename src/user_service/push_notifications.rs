@@ -0,0 +1,480 @@
+#![allow(dead_code)]
+/**
+ *  push notifications, for players who aren't looking at the game.  a client registers its device's token with
+ *  POST /auth/api/v1/users/push-token (and drops it with DELETE when the user logs out of the device), and the tokens
+ *  are kept with the user.
+ *
+ *  a turn starting, an invitation and a game ending are pushed to each of the user's devices -- but only when the user
+ *  is offline (see presence.rs): somebody with a long poll, socket or event stream open already got the message.
+ *
+ *  the pushes go through the provider the deployment configures:
+ *
+ *  1. Azure Notification Hubs: PUSH_NOTIFICATION_HUB_CONNECTION_STRING (the hub's connection string, with a key that
+ *     can Send) and PUSH_NOTIFICATION_HUB_NAME.  each device is sent to directly, by its token
+ *  2. a generic webhook: PUSH_WEBHOOK_URL is POSTed a PushRequest for each device, for a deployment that has its own
+ *     push service
+ *
+ *  how often a user is pushed -- batching per game, and quiet hours -- is up to them (see notification_preferences.rs).
+ *
+ *  without either, nothing is pushed.  pushing is best effort: it happens off the request, a failure is logged, and a
+ *  token the provider says is gone is dropped.
+ */
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{web, HttpResponse};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use url::form_urlencoded;
+
+use crate::{
+    games_service::long_poller::presence,
+    middleware::request_context_mw::RequestContext,
+    shared::{
+        i18n::{self, Language, Phrase},
+        service_models::PersistUser,
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+use super::notification_preferences;
+
+/// the most devices a user can have registered.  registering another drops the oldest
+pub const MAX_PUSH_TOKENS: usize = 10;
+
+/// how long the shared access signature for a Notification Hubs call is good for
+const SAS_SECONDS: u64 = 5 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PushPlatform {
+    Apple,
+    Android,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PushToken {
+    pub token: String,
+    pub platform: PushPlatform,
+    #[serde(default)]
+    pub registered_at: u64, // seconds since the epoch.  set by the service
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PushKind {
+    TurnStarted,
+    Invitation,
+    GameOver,
+    Digest, // pushes that were held, sent as one.  it has no GameId
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PushNotification {
+    pub kind: PushKind,
+    pub game_id: String,
+    pub title: String,
+    pub body: String,
+}
+
+impl PushNotification {
+    fn new(kind: PushKind, game_id: &str, title: Phrase, body: Phrase, args: &[&str], language: Language) -> Self {
+        Self {
+            kind,
+            game_id: game_id.to_owned(),
+            title: i18n::text(title, language, &[]),
+            body: i18n::text(body, language, args),
+        }
+    }
+
+    pub fn turn_started(game_id: &str, language: Language) -> Self {
+        Self::new(
+            PushKind::TurnStarted,
+            game_id,
+            Phrase::TurnStartedTitle,
+            Phrase::TurnStartedBody,
+            &[],
+            language,
+        )
+    }
+
+    pub fn invitation(game_id: &str, from_name: &str, language: Language) -> Self {
+        Self::new(
+            PushKind::Invitation,
+            game_id,
+            Phrase::InvitationTitle,
+            Phrase::InvitationBody,
+            &[from_name],
+            language,
+        )
+    }
+
+    pub fn game_over(game_id: &str, language: Language) -> Self {
+        Self::new(
+            PushKind::GameOver,
+            game_id,
+            Phrase::GameOverTitle,
+            Phrase::GameOverBody,
+            &[],
+            language,
+        )
+    }
+
+    pub fn digest(game_count: usize, language: Language) -> Self {
+        Self::new(
+            PushKind::Digest,
+            "",
+            Phrase::DigestTitle,
+            Phrase::DigestBody,
+            &[&game_count.to_string()],
+            language,
+        )
+    }
+}
+
+/// what the webhook is POSTed, once per device
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct PushRequest {
+    pub user_id: String,
+    pub token: PushToken,
+    pub notification: PushNotification,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct NotificationHub {
+    pub endpoint: String, // https://{namespace}.servicebus.windows.net
+    pub hub_name: String,
+    pub key_name: String,
+    pub key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum PushProvider {
+    Off,
+    NotificationHub(NotificationHub),
+    Webhook(String), // the url
+}
+
+impl Default for PushProvider {
+    fn default() -> Self {
+        PushProvider::Off
+    }
+}
+
+impl NotificationHub {
+    /// the hub from its connection string: Endpoint=sb://...;SharedAccessKeyName=...;SharedAccessKey=...
+    pub fn parse(connection_string: &str, hub_name: &str) -> Option<Self> {
+        let part = |name: &str| {
+            connection_string
+                .split(';')
+                .filter_map(|part| part.trim().split_once('='))
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.to_owned())
+        };
+        let endpoint = part("Endpoint")?;
+        let host = endpoint.trim_start_matches("sb://").trim_end_matches('/');
+        Some(Self {
+            endpoint: format!("https://{}", host),
+            hub_name: hub_name.to_owned(),
+            key_name: part("SharedAccessKeyName")?,
+            key: part("SharedAccessKey")?,
+        })
+    }
+
+    /// the Authorization header for a call to the hub, good until `expiry` (seconds since the epoch)
+    pub fn sas_token(&self, expiry: u64) -> String {
+        let resource = format!("{}/{}", self.endpoint, self.hub_name).to_lowercase();
+        let encode = |value: &str| form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>();
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(self.key.as_bytes()).expect("hmac takes a key of any length");
+        mac.update(format!("{}\n{}", encode(&resource), expiry).as_bytes());
+        let signature = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+        format!(
+            "SharedAccessSignature sr={}&sig={}&se={}&skn={}",
+            encode(&resource),
+            encode(&signature),
+            expiry,
+            self.key_name
+        )
+    }
+
+    /// the format header and body of the push for the device's platform
+    fn payload(platform: PushPlatform, notification: &PushNotification) -> (&'static str, serde_json::Value) {
+        match platform {
+            PushPlatform::Apple => (
+                "apple",
+                serde_json::json!({
+                    "aps": { "alert": { "title": notification.title, "body": notification.body } },
+                    "Kind": notification.kind,
+                    "GameId": notification.game_id,
+                }),
+            ),
+            PushPlatform::Android => (
+                "gcm",
+                serde_json::json!({
+                    "notification": { "title": notification.title, "body": notification.body },
+                    "data": { "Kind": notification.kind, "GameId": notification.game_id },
+                }),
+            ),
+        }
+    }
+}
+
+impl PushProvider {
+    pub fn from_env() -> Self {
+        let set = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        if let (Some(connection_string), Some(hub_name)) = (
+            set("PUSH_NOTIFICATION_HUB_CONNECTION_STRING"),
+            set("PUSH_NOTIFICATION_HUB_NAME"),
+        ) {
+            return match NotificationHub::parse(&connection_string, &hub_name) {
+                Some(hub) => PushProvider::NotificationHub(hub),
+                None => {
+                    tracing::warn!("PUSH_NOTIFICATION_HUB_CONNECTION_STRING can't be read -- push notifications are off");
+                    PushProvider::Off
+                }
+            };
+        }
+        match set("PUSH_WEBHOOK_URL") {
+            Some(url) => PushProvider::Webhook(url),
+            None => PushProvider::Off,
+        }
+    }
+
+    /**
+     *  push the notification to one device.  Ok(false) means the provider says the token is no good any more, and
+     *  it should be dropped
+     */
+    async fn send(
+        &self,
+        user_id: &str,
+        token: &PushToken,
+        notification: &PushNotification,
+    ) -> Result<bool, String> {
+        let client = reqwest::Client::new();
+        let request = match self {
+            PushProvider::Off => return Ok(true),
+            PushProvider::NotificationHub(hub) => {
+                let (format, body) = NotificationHub::payload(token.platform, notification);
+                client
+                    .post(format!(
+                        "{}/{}/messages/?direct&api-version=2015-04",
+                        hub.endpoint, hub.hub_name
+                    ))
+                    .header("Authorization", hub.sas_token(now() + SAS_SECONDS))
+                    .header("ServiceBusNotification-Format", format)
+                    .header("ServiceBusNotification-DeviceHandle", &token.token)
+                    .json(&body)
+            }
+            PushProvider::Webhook(url) => client.post(url).json(&PushRequest {
+                user_id: user_id.to_owned(),
+                token: token.clone(),
+                notification: notification.clone(),
+            }),
+        };
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Ok(false),
+            status => Err(format!("the push provider answered {}", status)),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// the user's tokens with this one registered: once, as the newest, and only the newest MAX_PUSH_TOKENS
+pub fn with_token(tokens: &[PushToken], token: PushToken) -> Vec<PushToken> {
+    let mut tokens: Vec<PushToken> = tokens.iter().filter(|t| t.token != token.token).cloned().collect();
+    tokens.push(token);
+    if tokens.len() > MAX_PUSH_TOKENS {
+        tokens.drain(..tokens.len() - MAX_PUSH_TOKENS);
+    }
+    tokens
+}
+
+fn caller_id(request_context: &RequestContext) -> String {
+    request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call")
+        .id
+        .clone()
+}
+
+/// register a device for the caller
+pub async fn register_token(
+    token: &PushToken,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    if token.token.trim().is_empty() {
+        return Err(ServiceResponse::new(
+            "the token is empty",
+            StatusCode::BAD_REQUEST,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::BAD_REQUEST),
+        ));
+    }
+    let mut user = request_context
+        .database
+        .find_user_by_id(&caller_id(request_context))
+        .await?;
+    let mut token = token.clone();
+    token.registered_at = now();
+    user.push_tokens = with_token(&user.push_tokens, token);
+    request_context.database.update_or_create_user(&user).await?;
+    Ok(ServiceResponse::new(
+        "registered",
+        StatusCode::OK,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    ))
+}
+
+/// stop pushing to a device of the caller's
+pub async fn unregister_token(
+    token: &PushToken,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let mut user = request_context
+        .database
+        .find_user_by_id(&caller_id(request_context))
+        .await?;
+    let count = user.push_tokens.len();
+    user.push_tokens.retain(|t| t.token != token.token);
+    if user.push_tokens.len() != count {
+        request_context.database.update_or_create_user(&user).await?;
+    }
+    Ok(ServiceResponse::new(
+        "unregistered",
+        StatusCode::OK,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    ))
+}
+
+/// push the notification to each of the user's devices, dropping the tokens the provider says are gone
+pub async fn push_to_devices(user: &mut PersistUser, notification: &PushNotification, request_context: &RequestContext) {
+    let provider = &request_context.config.push_provider;
+    let mut gone = Vec::new();
+    for token in &user.push_tokens {
+        match provider.send(&user.id, token, notification).await {
+            Ok(true) => {}
+            Ok(false) => gone.push(token.token.clone()),
+            Err(e) => tracing::warn!(
+                "failed to push {:?} for {} to {}: {}",
+                notification.kind,
+                notification.game_id,
+                user.id,
+                e
+            ),
+        }
+    }
+    if !gone.is_empty() {
+        user.push_tokens.retain(|t| !gone.contains(&t.token));
+        if let Err(e) = request_context.database.update_or_create_user(user).await {
+            tracing::warn!("failed to drop {}'s expired push tokens: {:#?}", user.id, e);
+        }
+    }
+}
+
+/**
+ *  push each user's notification -- in their language for the game (see i18n.rs) -- to their devices, if they are
+ *  offline and their preferences let it go now.  it happens in the background, so it never holds up the call that
+ *  caused it, and does nothing at all if no provider is configured
+ */
+pub fn notify(
+    user_ids: &[String],
+    game_id: &str,
+    notification_for: impl Fn(Language) -> PushNotification,
+    request_context: &RequestContext,
+) {
+    if request_context.config.push_provider == PushProvider::Off || user_ids.is_empty() {
+        return;
+    }
+    let notifications: Vec<(String, PushNotification)> = user_ids
+        .iter()
+        .map(|user_id| (user_id.clone(), notification_for(i18n::language_for(game_id, user_id))))
+        .collect();
+    let request_context = request_context.clone();
+    actix_web::rt::spawn(async move {
+        for (user_id, notification) in notifications {
+            if presence::is_online(&user_id).await {
+                continue;
+            }
+            let mut user = match request_context.database.find_user_by_id(&user_id).await {
+                Ok(user) => user,
+                Err(_) => continue, // a bot, or deleted
+            };
+            if user.push_tokens.is_empty() {
+                continue;
+            }
+            if let Some(notification) = notification_preferences::schedule(&user, notification, &request_context) {
+                push_to_devices(&mut user, &notification, &request_context).await;
+            }
+        }
+    });
+}
+
+pub async fn register_token_handler(
+    token: web::Json<PushToken>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    register_token(&token, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn unregister_token_handler(
+    token: web::Json<PushToken>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    unregister_token(&token, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_tokens() {
+        let token = |name: &str| PushToken {
+            token: name.to_owned(),
+            platform: PushPlatform::Apple,
+            registered_at: 0,
+        };
+        let tokens = with_token(&[token("a"), token("b")], token("a"));
+        assert_eq!(tokens, vec![token("b"), token("a")]);
+        let many: Vec<PushToken> = (0..MAX_PUSH_TOKENS).map(|i| token(&i.to_string())).collect();
+        let tokens = with_token(&many, token("newest"));
+        assert_eq!(tokens.len(), MAX_PUSH_TOKENS);
+        assert_eq!(tokens[0], token("1"));
+        assert_eq!(tokens.last(), Some(&token("newest")));
+
+        let hub = NotificationHub::parse(
+            "Endpoint=sb://catan.servicebus.windows.net/;SharedAccessKeyName=Send;SharedAccessKey=c2VjcmV0",
+            "players",
+        )
+        .expect("a valid connection string");
+        assert_eq!(hub.endpoint, "https://catan.servicebus.windows.net");
+        assert_eq!(hub.key_name, "Send");
+        let sas = hub.sas_token(1_000);
+        assert!(sas.starts_with(
+            "SharedAccessSignature sr=https%3A%2F%2Fcatan.servicebus.windows.net%2Fplayers&sig="
+        ));
+        assert!(sas.ends_with("&se=1000&skn=Send"));
+        assert!(NotificationHub::parse("Endpoint=sb://catan.servicebus.windows.net/", "players").is_none());
+    }
+}
@@ -0,0 +1,340 @@
+#![allow(dead_code)]
+/**
+ *  a login gets a short lived access token (ACCESS_TOKEN_SECONDS, 15 minutes unless it is set) and a refresh token
+ *  that is good for REFRESH_TOKEN_SECONDS.  when the access token runs out, the client POSTs the refresh token to
+ *  /api/v1/users/refresh and gets a new pair, so it doesn't have to send the password again until the user has been
+ *  away for a month.
+ *
+ *  refresh tokens are random, and only their SHA-256 is kept (in the RefreshToken collection).  every refresh uses the
+ *  token up and hands out the next one in the same family -- all the tokens that came from one login.  a used token
+ *  that comes back means two clients have it, so one of them stole it: the whole family is revoked, the session is
+ *  ended, and the user has to log in again.
+ *
+ *  a token is marked used with a write conditioned on the version that was read, so when two refreshes race with the
+ *  same token only one wins -- the other is treated as reuse.  a user's tokens are kept in their own partition, and
+ *  are all deleted when the password is reset or the email changes.  logging out deletes the tokens of the caller's
+ *  session.
+ *
+ *  a refresh carries the login's session on (see sessions.rs), so a session that has been ended can't be refreshed.
+ */
+use actix_web::{web, HttpResponse};
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    games_service::{game_container::game_messages::CatanMessage, long_poller::long_poller::LongPoller},
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        service_models::{Claims, PersistRefreshToken, PersistUser},
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+    user_service::sessions::{self, SessionEndedData},
+};
+
+/// how long a refresh token is good for
+pub const REFRESH_TOKEN_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// what a login (or a refresh) returns
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct LoginTokens {
+    pub access_token: String,
+    pub expires_in: u64, // seconds the access token is good for
+    pub refresh_token: String,
+    pub refresh_expires_at: u64, // seconds since the UNIX epoch
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct RefreshData {
+    pub refresh_token: String,
+}
+
+/// what is stored instead of the token
+pub fn hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn signing_error(e: Box<dyn std::error::Error>) -> ServiceResponse {
+    ServiceResponse::new(
+        "Error Hashing token",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        ResponseType::ErrorInfo(format!("{:#?}", e)),
+        GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+    )
+}
+
+/// a new pair of tokens for the user's session.  family_id is the login's -- a new one for a login
+pub async fn issue(
+    user: &PersistUser,
+    email: &str,
+    family_id: &str,
+    session_id: &str,
+    request_context: &RequestContext,
+) -> Result<LoginTokens, ServiceResponse> {
+    let expires_in = request_context.config.access_token_seconds;
+    let mut claims = Claims::new(&user.id, email, expires_in, &user.roles, &request_context.test_context);
+    claims.session_id = Some(session_id.to_owned());
+    let access_token = request_context
+        .security_context
+        .login_keys
+        .sign_claims(&claims)
+        .map_err(signing_error)?;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let refresh_token = base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, bytes);
+    let now = request_context.environment.now();
+    let stored = PersistRefreshToken {
+        id: hash(&refresh_token),
+        partition_key: user.id.clone(),
+        user_id: user.id.clone(),
+        family_id: family_id.to_owned(),
+        session_id: session_id.to_owned(),
+        issued_at: now,
+        expires_at: now + REFRESH_TOKEN_SECONDS,
+        used: false,
+        ttl: REFRESH_TOKEN_SECONDS,
+        etag: None,
+    };
+    request_context.database.save_refresh_token(&stored).await?;
+    Ok(LoginTokens {
+        access_token,
+        expires_in,
+        refresh_token,
+        refresh_expires_at: stored.expires_at,
+    })
+}
+
+/// tell the sessions that were ended to make room for a new one why they were logged out
+pub async fn tell_displaced(user_id: &str, displaced: Vec<String>, request_context: &RequestContext) {
+    for session_id in displaced {
+        let message = CatanMessage::SessionEnded(SessionEndedData {
+            user_id: user_id.to_owned(),
+            session_id,
+            reason: format!(
+                "logged in somewhere else ({:?})",
+                request_context.config.session_policy
+            ),
+        });
+        let _ = LongPoller::send_message(vec![user_id.to_owned()], &message).await;
+    }
+}
+
+/// the token was used twice: nothing from its login can be trusted any more
+async fn revoke(stored: &PersistRefreshToken, reason: &str, request_context: &RequestContext) {
+    if let Err(e) = request_context.database.delete_refresh_tokens(&stored.family_id).await {
+        tracing::error!("failed to revoke the refresh tokens of {}: {:#?}", stored.user_id, e);
+    }
+    sessions::end(&stored.user_id, &stored.session_id, request_context.environment.as_ref());
+    let message = CatanMessage::SessionEnded(SessionEndedData {
+        user_id: stored.user_id.clone(),
+        session_id: stored.session_id.clone(),
+        reason: reason.to_owned(),
+    });
+    let _ = LongPoller::send_message(vec![stored.user_id.clone()], &message).await;
+}
+
+/// swap a refresh token for a new access token and refresh token
+pub async fn refresh(refresh_token: &str, request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let stored = match request_context
+        .database
+        .find_refresh_token(&hash(refresh_token.trim()), request_context.environment.now())
+        .await
+    {
+        Ok(stored) => stored,
+        Err(_) => return new_unauthorized_response!("the refresh token has expired, or isn't one of ours"),
+    };
+    if stored.used {
+        tracing::warn!(
+            "a used refresh token of {} came back -- revoking every token from that login",
+            stored.user_id
+        );
+        revoke(&stored, "the login's refresh token was used twice", request_context).await;
+        return new_unauthorized_response!("the refresh token has already been used");
+    }
+    let user = match request_context.database.find_user_by_id(&stored.user_id).await {
+        Ok(user) if !user.must_reset_password => user,
+        _ => {
+            revoke(&stored, "the account has to log in again", request_context).await;
+            return new_unauthorized_response!("log in again");
+        }
+    };
+    let displaced = match sessions::resume(
+        &user.id,
+        &stored.session_id,
+        request_context.config.session_policy,
+        request_context.environment.as_ref(),
+    ) {
+        Some(displaced) => displaced,
+        None => {
+            let _ = request_context.database.delete_refresh_tokens(&stored.family_id).await;
+            return new_unauthorized_response!("the session has ended -- log in again");
+        }
+    };
+
+    if !request_context.database.use_refresh_token(&stored).await? {
+        // another refresh used it between the read and now -- the same as it coming back used
+        tracing::warn!(
+            "a refresh token of {} was used twice at once -- revoking every token from that login",
+            stored.user_id
+        );
+        revoke(&stored, "the login's refresh token was used twice", request_context).await;
+        return new_unauthorized_response!("the refresh token has already been used");
+    }
+    let email = user
+        .user_profile
+        .pii
+        .as_ref()
+        .map_or_else(|| user.id.clone(), |pii| pii.email.clone());
+    let tokens = issue(&user, &email, &stored.family_id, &stored.session_id, request_context).await?;
+    tell_displaced(&user.id, displaced, request_context).await;
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::LoginTokens(tokens),
+        GameError::NoError("ok".to_owned()),
+    ))
+}
+
+pub async fn refresh_handler(data: web::Json<RefreshData>, request_context: RequestContext) -> HttpResponse {
+    refresh(&data.refresh_token, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+/// delete every refresh token the user has -- their credentials changed, so every login has to be made again
+pub async fn revoke_all(user_id: &str, request_context: &RequestContext) {
+    if let Err(e) = request_context.database.delete_user_refresh_tokens(user_id, None).await {
+        tracing::error!("failed to revoke the refresh tokens of {}: {:#?}", user_id, e);
+    }
+}
+
+/// end the caller's session and delete its refresh tokens.  their other logins carry on
+pub async fn logout(request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call");
+    if let Some(session_id) = &claims.session_id {
+        request_context
+            .database
+            .delete_user_refresh_tokens(&claims.id, Some(session_id))
+            .await?;
+        sessions::end(&claims.id, session_id, request_context.environment.as_ref());
+    }
+    Ok(ServiceResponse::new(
+        "logged out",
+        StatusCode::OK,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn logout_handler(request_context: RequestContext) -> HttpResponse {
+    logout(&request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens_of(sr: &ServiceResponse) -> LoginTokens {
+        match &sr.response_type {
+            ResponseType::LoginTokens(tokens) => tokens.clone(),
+            other => panic!("expected LoginTokens, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotation() {
+        let request_context = RequestContext::test_default(false);
+        let user = PersistUser::new();
+        request_context
+            .database
+            .update_or_create_user(&user)
+            .await
+            .expect("the mocked db takes anything");
+        let (session_id, _) = sessions::start(
+            &user.id,
+            request_context.config.session_policy,
+            request_context.environment.as_ref(),
+        );
+        let first = issue(&user, "refresh@example.com", "family", &session_id, &request_context)
+            .await
+            .expect("tokens for the login");
+        assert_ne!(hash(&first.refresh_token), first.refresh_token);
+        assert_eq!(first.expires_in, request_context.config.access_token_seconds);
+
+        // each refresh rotates the token
+        let second = tokens_of(&refresh(&first.refresh_token, &request_context).await.expect("a good token"));
+        assert_ne!(second.refresh_token, first.refresh_token);
+        let third = tokens_of(&refresh(&second.refresh_token, &request_context).await.expect("the new token"));
+
+        // the first one again: everything from the login is revoked
+        assert!(refresh(&first.refresh_token, &request_context).await.is_err());
+        assert!(refresh(&third.refresh_token, &request_context).await.is_err());
+        assert!(!sessions::is_active(&user.id, &session_id));
+
+        assert!(refresh("not-a-token", &request_context).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_race_and_logout() {
+        let mut request_context = RequestContext::test_default(false);
+        let user = PersistUser::new();
+        request_context
+            .database
+            .update_or_create_user(&user)
+            .await
+            .expect("the mocked db takes anything");
+        let (session_id, _) = sessions::start(
+            &user.id,
+            request_context.config.session_policy,
+            request_context.environment.as_ref(),
+        );
+        let tokens = issue(&user, "race@example.com", "race-family", &session_id, &request_context)
+            .await
+            .expect("tokens for the login");
+
+        // two refreshes read the token before either marks it used: only one of them gets to
+        let stored = request_context
+            .database
+            .find_refresh_token(&hash(&tokens.refresh_token), request_context.environment.now())
+            .await
+            .expect("the token was just issued");
+        assert_eq!(stored.partition_key, user.id);
+        assert!(request_context.database.use_refresh_token(&stored).await.unwrap());
+        assert!(!request_context.database.use_refresh_token(&stored).await.unwrap());
+
+        // logging out ends the session and deletes its tokens
+        let (session_id, _) = sessions::start(
+            &user.id,
+            request_context.config.session_policy,
+            request_context.environment.as_ref(),
+        );
+        let tokens = issue(&user, "race@example.com", "logout-family", &session_id, &request_context)
+            .await
+            .expect("tokens for the second login");
+        let mut claims = Claims::new(&user.id, "race@example.com", 60, &user.roles, &request_context.test_context);
+        claims.session_id = Some(session_id.clone());
+        request_context.claims = Some(claims);
+        logout(&request_context).await.expect("logging out works");
+        assert!(!sessions::is_active(&user.id, &session_id));
+        assert!(request_context
+            .database
+            .find_refresh_token(&hash(&tokens.refresh_token), request_context.environment.now())
+            .await
+            .is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use reqwest::Error;
 use serde::{Deserialize, Serialize};
 
+use crate::middleware::service_config::SERVICE_CONFIG;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EmailData {
     #[serde(rename = "headers")]
@@ -98,7 +100,7 @@ pub fn build_json_doc(
     to_address: &str,
     name: &str,
 ) -> Result<String, serde_json::Error> {
-    let body = format!("You have registered as a user of the Catan Services.  Click on this url to validate your email.\n{}", url);
+    let (subject, body) = SERVICE_CONFIG.branding.validation_email(url);
     let email_data = EmailData {
         headers: Headers {
             client_correlation_id: "1".to_string(),
@@ -107,10 +109,10 @@ pub fn build_json_doc(
         sender_address: "no_replay@longshotdev.com".to_string(),
 
         content: Content {
-            subject: "Catan Register User".to_string(),
+            subject,
             plain_text: body.clone(),
             html: format!(
-                "<html><head><title>Validate Email</title></head><body><h1>{}</h1></body></html>",
+                "<html><head><title>Validate Email</title></head><body><pre>{}</pre></body></html>",
                 body
             ),
         },
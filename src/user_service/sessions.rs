@@ -0,0 +1,318 @@
+#![allow(dead_code)]
+/**
+ *  the sessions each user has open, one for every login, and the policy for how many they can have at once.  a login
+ *  token carries its session id, and the auth middleware turns away a token whose session has ended -- so a session
+ *  displaced by a newer login is logged out even though its token hasn't expired.  the displaced session is told why
+ *  with a SessionEnded message on the user's long poller.
+ *
+ *  the policy comes from SESSION_POLICY: "allow-all" (the default), "newest-wins", or "max-devices:N", where the
+ *  oldest sessions are ended to make room for a new one.  sessions are kept in memory, so a restart forgets them --
+ *  their tokens still work, the way tokens without a session (service and test tokens) always do, until a refresh
+ *  opens them again.  only a session that was ended is turned away, and a restart forgets that too.
+ *
+ *  a refresh token carries the session on (see refresh_tokens.rs): refreshing resumes a session the restart forgot,
+ *  but not one that was ended.  a session that hasn't been refreshed for SESSION_IDLE_SECONDS can't be any more (its
+ *  refresh token has run out), so it is forgotten, and so is a session that was ended that long ago.
+ */
+use std::{collections::HashMap, sync::Mutex};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::environment::Environment;
+
+use super::refresh_tokens::REFRESH_TOKEN_SECONDS;
+
+/// how long a session is remembered after it was last started or refreshed, or after it was ended
+pub const SESSION_IDLE_SECONDS: u64 = REFRESH_TOKEN_SECONDS;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SessionPolicy {
+    AllowAll,
+    NewestWins,       // a login ends every other session the user has
+    MaxDevices(u32),  // a login ends the oldest sessions past this many
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        SessionPolicy::AllowAll
+    }
+}
+
+impl SessionPolicy {
+    /// the policy named by SESSION_POLICY, or None if it doesn't name one
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().split_once(':') {
+            None if value.trim().eq_ignore_ascii_case("allow-all") => Some(SessionPolicy::AllowAll),
+            None if value.trim().eq_ignore_ascii_case("newest-wins") => Some(SessionPolicy::NewestWins),
+            Some(("max-devices", count)) => match count.parse::<u32>() {
+                Ok(count) if count > 0 => Some(SessionPolicy::MaxDevices(count)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        match std::env::var("SESSION_POLICY") {
+            Ok(value) => Self::parse(&value).unwrap_or_else(|| {
+                tracing::warn!("SESSION_POLICY {} isn't a policy -- allowing all sessions", value);
+                SessionPolicy::AllowAll
+            }),
+            Err(_) => SessionPolicy::AllowAll,
+        }
+    }
+
+    /// how many sessions a user can have open at once, or None for no limit
+    fn limit(&self) -> Option<usize> {
+        match self {
+            SessionPolicy::AllowAll => None,
+            SessionPolicy::NewestWins => Some(1),
+            SessionPolicy::MaxDevices(count) => Some(*count as usize),
+        }
+    }
+}
+
+///
+/// sent to a user's long poller when one of their sessions is ended by a newer login.  the client compares the
+/// session_id with the one in its token to know whether it is the one that was logged out
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct SessionEndedData {
+    pub user_id: String,
+    pub session_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    id: String,
+    started_at: u64,
+    last_seen: u64, // when it was started or last refreshed
+}
+
+lazy_static::lazy_static! {
+    // user_id -> their open sessions, oldest first
+    static ref SESSIONS: Mutex<HashMap<String, Vec<Session>>> = Mutex::new(HashMap::new());
+    // the sessions ended in the last SESSION_IDLE_SECONDS -> when, so they can't be resumed
+    static ref ENDED: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+}
+
+fn ended(session_ids: &[String], now: u64) {
+    ENDED
+        .lock()
+        .expect("the session lock shouldn't be poisoned")
+        .extend(session_ids.iter().map(|id| (id.clone(), now)));
+}
+
+/// forget the sessions that haven't been seen for SESSION_IDLE_SECONDS, and the ones ended that long ago
+fn expire_from(sessions: &mut HashMap<String, Vec<Session>>, ended: &mut HashMap<String, u64>, now: u64) {
+    let cutoff = now.saturating_sub(SESSION_IDLE_SECONDS);
+    sessions.retain(|_, open| {
+        open.retain(|session| session.last_seen > cutoff);
+        !open.is_empty()
+    });
+    ended.retain(|_, ended_at| *ended_at > cutoff);
+}
+
+fn expire(now: u64) {
+    let mut sessions = SESSIONS.lock().expect("the session lock shouldn't be poisoned");
+    let mut ended = ENDED.lock().expect("the session lock shouldn't be poisoned");
+    expire_from(&mut sessions, &mut ended, now);
+}
+
+/// add the session, ending the oldest ones past the policy's limit.  returns the ids of the ones it ended
+fn open(user_id: &str, session: Session, policy: SessionPolicy) -> Vec<String> {
+    let now = session.last_seen;
+    expire(now);
+    let mut sessions = SESSIONS.lock().expect("the session lock shouldn't be poisoned");
+    let open = sessions.entry(user_id.to_owned()).or_default();
+    let mut displaced = Vec::new();
+    if let Some(limit) = policy.limit() {
+        while open.len() + 1 > limit {
+            displaced.push(open.remove(0).id);
+        }
+    }
+    open.push(session);
+    drop(sessions);
+    ended(&displaced, now);
+    displaced
+}
+
+/// a new session for the user.  returns its id and the ids of the sessions the policy ended to make room for it
+pub fn start(user_id: &str, policy: SessionPolicy, environment: &dyn Environment) -> (String, Vec<String>) {
+    let now = environment.now();
+    let session = Session {
+        id: environment.new_id(),
+        started_at: now,
+        last_seen: now,
+    };
+    let id = session.id.clone();
+    (id, open(user_id, session, policy))
+}
+
+/**
+ *  carry on with a session when its login is refreshed.  an open session carries on as it is, and one the service
+ *  has forgotten (it restarted) is opened again under the policy -- returning the sessions that ended to make room.
+ *  None if the session was ended: it can't come back
+ */
+pub fn resume(
+    user_id: &str,
+    session_id: &str,
+    policy: SessionPolicy,
+    environment: &dyn Environment,
+) -> Option<Vec<String>> {
+    if is_ended(session_id) {
+        return None;
+    }
+    let now = environment.now();
+    let mut sessions = SESSIONS.lock().expect("the session lock shouldn't be poisoned");
+    if let Some(session) = sessions
+        .get_mut(user_id)
+        .and_then(|open| open.iter_mut().find(|session| session.id == session_id))
+    {
+        session.last_seen = now;
+        return Some(Vec::new());
+    }
+    drop(sessions);
+    let session = Session {
+        id: session_id.to_owned(),
+        started_at: now,
+        last_seen: now,
+    };
+    Some(open(user_id, session, policy))
+}
+
+fn is_ended(session_id: &str) -> bool {
+    ENDED
+        .lock()
+        .expect("the session lock shouldn't be poisoned")
+        .contains_key(session_id)
+}
+
+/// true if the session hasn't been ended.  one a restart forgot is still good
+pub fn is_active(_user_id: &str, session_id: &str) -> bool {
+    !is_ended(session_id)
+}
+
+pub fn end(user_id: &str, session_id: &str, environment: &dyn Environment) {
+    let mut sessions = SESSIONS.lock().expect("the session lock shouldn't be poisoned");
+    if let Some(open) = sessions.get_mut(user_id) {
+        open.retain(|session| session.id != session_id);
+        if open.is_empty() {
+            sessions.remove(user_id);
+        }
+    }
+    drop(sessions);
+    ended(&[session_id.to_owned()], environment.now());
+}
+
+/// end every session the user has open.  returns their ids
+pub fn end_all(user_id: &str, environment: &dyn Environment) -> Vec<String> {
+    let ids: Vec<String> = SESSIONS
+        .lock()
+        .expect("the session lock shouldn't be poisoned")
+        .remove(user_id)
+        .map_or_else(Vec::new, |open| open.into_iter().map(|session| session.id).collect());
+    ended(&ids, environment.now());
+    ids
+}
+
+/// how many sessions the user has open
+pub fn count(user_id: &str) -> usize {
+    SESSIONS
+        .lock()
+        .expect("the session lock shouldn't be poisoned")
+        .get(user_id)
+        .map_or(0, |open| open.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::environment::TestEnvironment;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(SessionPolicy::parse("allow-all"), Some(SessionPolicy::AllowAll));
+        assert_eq!(SessionPolicy::parse("Newest-Wins"), Some(SessionPolicy::NewestWins));
+        assert_eq!(SessionPolicy::parse("max-devices:3"), Some(SessionPolicy::MaxDevices(3)));
+        assert_eq!(SessionPolicy::parse("max-devices:0"), None);
+        assert_eq!(SessionPolicy::parse("newest"), None);
+    }
+
+    #[test]
+    fn test_session_policies() {
+        let environment = TestEnvironment::new(7);
+        let (first, displaced) = start("sessions-all", SessionPolicy::AllowAll, &environment);
+        assert!(displaced.is_empty());
+        start("sessions-all", SessionPolicy::AllowAll, &environment);
+        assert_eq!(count("sessions-all"), 2);
+        assert!(is_active("sessions-all", &first));
+
+        let (first, _) = start("sessions-newest", SessionPolicy::NewestWins, &environment);
+        let (second, displaced) = start("sessions-newest", SessionPolicy::NewestWins, &environment);
+        assert_eq!(displaced, vec![first.clone()]);
+        assert!(!is_active("sessions-newest", &first));
+        assert!(is_active("sessions-newest", &second));
+
+        // the oldest go first
+        let policy = SessionPolicy::MaxDevices(2);
+        let (first, _) = start("sessions-max", policy, &environment);
+        let (second, _) = start("sessions-max", policy, &environment);
+        let (_, displaced) = start("sessions-max", policy, &environment);
+        assert_eq!(displaced, vec![first]);
+        assert!(is_active("sessions-max", &second));
+        assert_eq!(count("sessions-max"), 2);
+
+        end("sessions-max", &second, &environment);
+        assert!(!is_active("sessions-max", &second));
+        assert_eq!(count("sessions-max"), 1);
+    }
+
+    #[test]
+    fn test_resume_session() {
+        let environment = TestEnvironment::new(8);
+        let policy = SessionPolicy::AllowAll;
+        let (session, _) = start("sessions-resume", policy, &environment);
+        assert_eq!(resume("sessions-resume", &session, policy, &environment), Some(vec![]));
+        assert_eq!(count("sessions-resume"), 1);
+
+        // one a restart forgot comes back
+        assert_eq!(resume("sessions-resume", "forgotten", policy, &environment), Some(vec![]));
+        assert!(is_active("sessions-resume", "forgotten"));
+
+        // one that was ended doesn't
+        end("sessions-resume", &session, &environment);
+        assert_eq!(resume("sessions-resume", &session, policy, &environment), None);
+        assert!(!is_active("sessions-resume", &session));
+    }
+
+    #[test]
+    fn test_expire_sessions() {
+        // a token whose session the service doesn't know (it restarted) still works
+        assert!(is_active("sessions-expire", "from-before-the-restart"));
+
+        let session = |id: &str, last_seen: u64| Session {
+            id: id.to_owned(),
+            started_at: 0,
+            last_seen,
+        };
+        let now = 10 * SESSION_IDLE_SECONDS;
+        let mut sessions = HashMap::new();
+        sessions.insert("idle".to_owned(), vec![session("old", now - SESSION_IDLE_SECONDS - 1)]);
+        sessions.insert(
+            "busy".to_owned(),
+            vec![session("old", now - SESSION_IDLE_SECONDS), session("new", now - 1)],
+        );
+        let mut ended = HashMap::new();
+        ended.insert("long-ago".to_owned(), now - SESSION_IDLE_SECONDS);
+        ended.insert("recently".to_owned(), now - 1);
+
+        expire_from(&mut sessions, &mut ended, now);
+        assert!(!sessions.contains_key("idle"));
+        assert_eq!(sessions["busy"].len(), 1);
+        assert_eq!(sessions["busy"][0].id, "new");
+        assert_eq!(ended.keys().collect::<Vec<_>>(), vec!["recently"]);
+    }
+}
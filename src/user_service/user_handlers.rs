@@ -1,15 +1,19 @@
 #![allow(dead_code)]
 use crate::{
     get_header_value,
-    middleware::{header_extractor::HeadersExtractor, request_context_mw::RequestContext},
+    middleware::{
+        header_extractor::HeadersExtractor, request_context_mw::RequestContext,
+        service_config::SERVICE_CONFIG,
+    },
     shared::{
         service_models::Role,
         shared_models::{GameError, ResponseType, ServiceResponse, UserProfile},
     },
 };
 use actix_web::{
+    http::header,
     web::{self},
-    HttpResponse, Responder,
+    HttpRequest, HttpResponse, Responder,
 };
 use reqwest::StatusCode;
 
@@ -70,6 +74,18 @@ pub async fn login_handler(
         .unwrap_or_else(|sr| sr.to_http_response())
 }
 
+// Set the password of an account that has to have one set, with the token from the link the user was mailed
+pub async fn reset_password_handler(
+    token: web::Path<String>,
+    headers: HeadersExtractor,
+) -> HttpResponse {
+    let password = get_header_value!(password, headers);
+    super::users::reset_password(&token, &password)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
 // List users
 pub async fn list_users_handler(request_context: RequestContext) -> HttpResponse {
     super::users::list_users(&request_context)
@@ -137,9 +153,48 @@ pub async fn delete_handler(
         .unwrap_or_else(|sr| sr.to_http_response())
 }
 
-pub async fn validate_email(token: web::Path<String>) -> HttpResponse {
-    super::users::validate_email(&token)
-        .await
+///
+/// the link in the validation email.  a browser (anything that accepts html) gets a page in the deployment's branding;
+/// anything else gets the ServiceResponse
+pub async fn validate_email(token: web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let result = super::users::validate_email(&token).await;
+    let wants_html = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map_or(false, |accept| accept.contains("text/html"));
+    if wants_html {
+        let status = match &result {
+            Ok(_) => StatusCode::OK,
+            Err(sr) => sr.status,
+        };
+        return HttpResponse::build(status)
+            .content_type("text/html; charset=utf-8")
+            .body(SERVICE_CONFIG.branding.validation_page(result.is_ok()));
+    }
+    result
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+/// like validate_email: a browser that opens the link gets a page
+pub async fn confirm_email_change_handler(token: web::Path<String>, req: HttpRequest) -> HttpResponse {
+    let result = super::users::confirm_email_change(&token).await;
+    let wants_html = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map_or(false, |accept| accept.contains("text/html"));
+    if wants_html {
+        let status = match &result {
+            Ok(_) => StatusCode::OK,
+            Err(sr) => sr.status,
+        };
+        return HttpResponse::build(status)
+            .content_type("text/html; charset=utf-8")
+            .body(SERVICE_CONFIG.branding.validation_page(result.is_ok()));
+    }
+    result
         .map(|sr| sr.to_http_response())
         .unwrap_or_else(|sr| sr.to_http_response())
 }
@@ -186,6 +241,14 @@ pub async fn send_validation_email(request_context: RequestContext) -> HttpRespo
         .unwrap_or_else(|sr| sr.to_http_response())
 }
 
+pub async fn request_email_change_handler(headers: HeadersExtractor, request_context: RequestContext) -> HttpResponse {
+    let email = get_header_value!(email, headers);
+    super::users::request_email_change(&email, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
 pub async fn rotate_login_keys_handler(request_context: RequestContext) -> HttpResponse {
     super::users::rotate_login_keys(&request_context)
         .await
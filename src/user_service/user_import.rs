@@ -0,0 +1,451 @@
+#![allow(dead_code)]
+/**
+ *  bulk import, for moving an existing community onto the service.  an admin POSTs a file of users to
+ *  /auth/api/v1/admin/users/import, either
+ *
+ *  1. CSV (Content-Type: text/csv) with a header row.  Email and DisplayName are required; GamesPlayed and GamesWon are
+ *     optional.  the header names don't care about case, spaces or underscores, so "display_name" works too.  a field
+ *     can be quoted, but it can't span lines
+ *  2. JSON: an array of ImportedUser
+ *
+ *  each row becomes a connected user with no password and must_reset_password set.  the first time they log in they
+ *  are mailed a link to set their password (see users::reset_password), and they can't log in until they do.
+ *
+ *  every row is reported on its own: the id it was given, or why it wasn't imported (a bad email, a user with that
+ *  email already, stats that don't add up).  a file with more than BACKGROUND_ROWS rows is imported as a background
+ *  job -- the call returns 202 with the job's id right away, and GET /auth/api/v1/admin/users/import/{job_id} returns
+ *  the report as it fills in.  jobs are only kept in memory, so they are gone when the service restarts.
+ */
+use std::{collections::HashMap, sync::RwLock};
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    new_not_found_error, new_unauthorized_response,
+    shared::{
+        service_models::{PersistUser, Role},
+        shared_models::{GameError, PersonalInformation, ResponseType, ServiceResponse, UserProfile, UserType},
+    },
+};
+
+/// files with more rows than this are imported in the background
+pub const BACKGROUND_ROWS: usize = 100;
+
+/// the biggest file the import accepts
+pub const MAX_IMPORT_BYTES: usize = 16 * 1024 * 1024;
+
+lazy_static::lazy_static! {
+    // job id -> the report so far
+    static ref IMPORT_JOBS: RwLock<HashMap<String, ImportReport>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImportedUser {
+    pub email: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub games_played: Option<u16>,
+    #[serde(default)]
+    pub games_won: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImportRowResult {
+    pub row: usize,              // the line in the CSV, or the position in the JSON array, counting from 1
+    pub email: String,
+    pub user_id: Option<String>, // set if the user was imported
+    pub error: Option<String>,   // set if they weren't
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImportReport {
+    pub job_id: String,
+    pub finished: bool,
+    pub total: usize,
+    pub imported: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRowResult>,
+}
+
+/// a row that couldn't even be read, with the row number and what is wrong with it
+type RowError = (usize, String);
+
+fn bad_request(message: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        message,
+        StatusCode::BAD_REQUEST,
+        ResponseType::ErrorInfo(message.to_owned()),
+        GameError::BadActionData(message.to_owned()),
+    )
+}
+
+/// the fields of one line of CSV, unquoting the quoted ones
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|field| field.trim().to_owned()).collect()
+}
+
+/// "Display Name", "display_name" and "DisplayName" are all "displayname"
+fn column_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn stat(value: Option<&String>, name: &str) -> Result<Option<u16>, String> {
+    match value.map(|value| value.as_str()) {
+        None | Some("") => Ok(None),
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{} isn't a number: {}", name, value)),
+    }
+}
+
+/**
+ *  the users in a CSV file, by line.  the whole file is rejected if the header doesn't have the required columns; a
+ *  line that can't be read only fails that row
+ */
+pub fn parse_csv(text: &str) -> Result<Vec<Result<(usize, ImportedUser), RowError>>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header: Vec<String> = match lines.next() {
+        Some((_, line)) => csv_fields(line).iter().map(|name| column_name(name)).collect(),
+        None => return Err("the file is empty".to_owned()),
+    };
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let (email, display_name) = match (column("email"), column("displayname")) {
+        (Some(email), Some(display_name)) => (email, display_name),
+        _ => return Err("the header has to have Email and DisplayName columns".to_owned()),
+    };
+    let (games_played, games_won) = (column("gamesplayed"), column("gameswon"));
+
+    Ok(lines
+        .map(|(row, line)| {
+            let fields = csv_fields(line);
+            let field = |index: Option<usize>| index.and_then(|index| fields.get(index));
+            let user = ImportedUser {
+                email: field(Some(email)).cloned().unwrap_or_default(),
+                display_name: field(Some(display_name)).cloned().unwrap_or_default(),
+                games_played: stat(field(games_played), "GamesPlayed").map_err(|e| (row, e))?,
+                games_won: stat(field(games_won), "GamesWon").map_err(|e| (row, e))?,
+            };
+            Ok((row, user))
+        })
+        .collect())
+}
+
+/// the users in a JSON array, numbered from 1
+pub fn parse_json(body: &[u8]) -> Result<Vec<Result<(usize, ImportedUser), RowError>>, String> {
+    let users: Vec<ImportedUser> = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    Ok(users
+        .into_iter()
+        .enumerate()
+        .map(|(index, user)| Ok((index + 1, user)))
+        .collect())
+}
+
+/// true if it looks like an email address: something, an @, and a domain with a dot in it
+pub fn is_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((name, domain)) => !name.is_empty() && domain.contains('.') && !email.contains(char::is_whitespace),
+        None => false,
+    }
+}
+
+/// the reason the user can't be imported, if there is one that doesn't need the database
+pub fn check_user(user: &ImportedUser) -> Result<(), String> {
+    if !is_email(user.email.trim()) {
+        return Err(format!("not an email address: {}", user.email));
+    }
+    if user.display_name.trim().is_empty() {
+        return Err("no display name".to_owned());
+    }
+    if user.games_won.unwrap_or(0) > user.games_played.unwrap_or(0) {
+        return Err("more games won than played".to_owned());
+    }
+    Ok(())
+}
+
+/// create the user, returning their id
+async fn import_user(user: &ImportedUser, request_context: &RequestContext) -> Result<String, String> {
+    check_user(user)?;
+    let email = user.email.trim().to_owned();
+    if request_context.database.find_user_by_email(&email).await.is_ok() {
+        return Err("a user with this email already exists".to_owned());
+    }
+
+    let mut persist_user = PersistUser::new();
+    persist_user.must_reset_password = true;
+    persist_user.user_profile = UserProfile {
+        user_id: Some(persist_user.id.clone()),
+        user_type: UserType::Connected,
+        pii: Some(PersonalInformation {
+            phone_number: String::default(),
+            email,
+            first_name: String::default(),
+            last_name: String::default(),
+        }),
+        display_name: user.display_name.trim().to_owned(),
+        games_played: Some(user.games_played.unwrap_or(0)),
+        games_won: Some(user.games_won.unwrap_or(0)),
+        ..Default::default()
+    };
+    request_context
+        .database
+        .update_or_create_user(&persist_user)
+        .await
+        .map_err(|e| format!("couldn't save the user: {}", e.message))?;
+    Ok(persist_user.id)
+}
+
+/// import the rows one at a time, so that a duplicate further down the file is caught, adding each to the report
+async fn import_rows(
+    rows: Vec<Result<(usize, ImportedUser), RowError>>,
+    report: &mut ImportReport,
+    on_row: impl Fn(&ImportReport),
+    request_context: &RequestContext,
+) {
+    for row in rows {
+        let result = match row {
+            Ok((row, user)) => match import_user(&user, request_context).await {
+                Ok(user_id) => ImportRowResult {
+                    row,
+                    email: user.email,
+                    user_id: Some(user_id),
+                    error: None,
+                },
+                Err(error) => ImportRowResult {
+                    row,
+                    email: user.email,
+                    user_id: None,
+                    error: Some(error),
+                },
+            },
+            Err((row, error)) => ImportRowResult {
+                row,
+                email: String::default(),
+                user_id: None,
+                error: Some(error),
+            },
+        };
+        match result.user_id {
+            Some(_) => report.imported += 1,
+            None => report.failed += 1,
+        }
+        report.rows.push(result);
+        on_row(report);
+    }
+    report.finished = true;
+    tracing::info!(
+        "user import {}: {} imported, {} failed",
+        report.job_id,
+        report.imported,
+        report.failed
+    );
+}
+
+fn save_job(report: &ImportReport) {
+    IMPORT_JOBS
+        .write()
+        .expect("the import lock shouldn't be poisoned")
+        .insert(report.job_id.clone(), report.clone());
+}
+
+fn import_response(status: StatusCode, report: ImportReport) -> ServiceResponse {
+    ServiceResponse::new(
+        if report.finished { "imported" } else { "importing" },
+        status,
+        ResponseType::UserImport(report),
+        GameError::NoError(String::default()),
+    )
+}
+
+/**
+ *  import the users in the file -- admins only.  small files are imported before the call returns; bigger ones are
+ *  imported by a background job and the call returns 202 with the job's (empty) report
+ */
+pub async fn import_users(
+    content_type: &str,
+    body: &[u8],
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    let rows = if content_type.contains("csv") {
+        let text = std::str::from_utf8(body).map_err(|_| bad_request("the CSV has to be UTF-8"))?;
+        parse_csv(text)
+    } else {
+        parse_json(body)
+    }
+    .map_err(|e| bad_request(&format!("can't read the file: {}", e)))?;
+
+    let mut report = ImportReport {
+        job_id: request_context.environment.new_id(),
+        total: rows.len(),
+        ..Default::default()
+    };
+    if rows.len() <= BACKGROUND_ROWS {
+        import_rows(rows, &mut report, |_| {}, request_context).await;
+        save_job(&report);
+        return Ok(import_response(StatusCode::OK, report));
+    }
+
+    save_job(&report);
+    let accepted = report.clone();
+    let request_context = request_context.clone();
+    actix_web::rt::spawn(async move {
+        import_rows(rows, &mut report, save_job, &request_context).await;
+        save_job(&report);
+    });
+    Ok(import_response(StatusCode::ACCEPTED, accepted))
+}
+
+/// the report of an import job, so far -- admins only
+pub fn import_job(job_id: &str, request_context: &RequestContext) -> Result<ServiceResponse, ServiceResponse> {
+    if !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    let report = IMPORT_JOBS
+        .read()
+        .expect("the import lock shouldn't be poisoned")
+        .get(job_id)
+        .cloned();
+    match report {
+        Some(report) => Ok(import_response(StatusCode::OK, report)),
+        None => new_not_found_error!(&format!("no import job {}", job_id)),
+    }
+}
+
+pub async fn import_users_handler(
+    req: HttpRequest,
+    body: web::Bytes,
+    request_context: RequestContext,
+) -> HttpResponse {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    import_users(&content_type, &body, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+pub async fn import_job_handler(job_id: web::Path<String>, request_context: RequestContext) -> HttpResponse {
+    import_job(&job_id, &request_context)
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{shared::service_models::Claims, user_service::users};
+
+    #[test]
+    fn test_parse_import() {
+        assert_eq!(csv_fields("a, \"b, c\" ,\"say \"\"hi\"\"\""), vec!["a", "b, c", "say \"hi\""]);
+
+        let csv = "email,Display Name,games_won\n\
+                   one@example.com,One,2\n\
+                   \n\
+                   two@example.com,\"Two, Jr\",lots\n";
+        let rows = parse_csv(csv).expect("the header has the required columns");
+        assert_eq!(rows.len(), 2);
+        let (row, user) = rows[0].clone().expect("the first row is fine");
+        assert_eq!(row, 2);
+        assert_eq!(user.display_name, "One");
+        assert_eq!(user.games_won, Some(2));
+        assert_eq!(user.games_played, None);
+        assert_eq!(rows[1].clone().expect_err("lots isn't a number").0, 4);
+        assert!(parse_csv("email,name\n").is_err());
+
+        let rows = parse_json(br#"[{"Email": "three@example.com", "DisplayName": "Three", "GamesPlayed": 4}]"#)
+            .expect("valid json");
+        assert_eq!(rows[0].clone().unwrap().1.games_played, Some(4));
+        assert!(parse_json(b"not json").is_err());
+
+        let mut user = ImportedUser {
+            email: "four@example.com".to_owned(),
+            display_name: "Four".to_owned(),
+            games_played: Some(1),
+            games_won: Some(1),
+        };
+        assert!(check_user(&user).is_ok());
+        user.games_won = Some(2);
+        assert!(check_user(&user).is_err());
+        user.games_won = None;
+        user.email = "four at example.com".to_owned();
+        assert!(check_user(&user).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_users() {
+        let mut request_context = RequestContext::test_default(false);
+        let email = format!("{}@import.example.com", PersistUser::new_id());
+        let csv = format!(
+            "Email,DisplayName,GamesPlayed,GamesWon\n{0},Imported,10,3\n{0},Twice,,\nnobody,Bad,,\n",
+            email
+        );
+        let sr = import_users("text/csv", csv.as_bytes(), &request_context)
+            .await
+            .expect_err("not an admin");
+        assert_eq!(sr.status, StatusCode::UNAUTHORIZED);
+
+        request_context.set_claims(&Claims::new("admin", "admin@example.com", 60, &vec![Role::Admin], &None));
+        let sr = import_users("text/csv", csv.as_bytes(), &request_context)
+            .await
+            .expect("an admin can import");
+        let report = match sr.response_type {
+            ResponseType::UserImport(report) => report,
+            other => panic!("expected an import report, got {:?}", other),
+        };
+        assert!(report.finished);
+        assert_eq!((report.total, report.imported, report.failed), (3, 1, 2));
+        assert!(report.rows[1].error.as_deref().unwrap().contains("already exists"));
+        assert!(import_job(&report.job_id, &request_context).is_ok());
+
+        // the imported user can't log in until they set a password with the link they are mailed
+        let sr = users::login(&email, "anything", &request_context)
+            .await
+            .expect_err("the password has to be set first");
+        assert_eq!(sr.status, StatusCode::FORBIDDEN);
+        let user_id = report.rows[0].user_id.clone().unwrap();
+        let url = users::get_password_reset_url("host", &user_id, &email, &request_context);
+        let token = url.rsplit('/').next().unwrap();
+        users::reset_password(token, "a new password").await.expect("the link works");
+        assert!(users::reset_password(token, "again").await.is_err());
+        users::login(&email, "a new password", &request_context)
+            .await
+            .expect("the password is set");
+    }
+}
@@ -0,0 +1,67 @@
+#![allow(dead_code)]
+/**
+ *  a player's record: how many games they have played and won (kept in their profile), and how they did in each of
+ *  the games, newest first (the GameStats collection -- written when a game ends, see CleanupStep::UpdateStats), and
+ *  the achievements they have unlocked.
+ */
+use actix_web::{web, HttpResponse};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    middleware::request_context_mw::RequestContext,
+    new_unauthorized_response,
+    shared::{
+        service_models::{PersistAchievement, PersistGameStats, Role},
+        shared_models::{GameError, ResponseType, ServiceResponse},
+    },
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserStats {
+    pub user_id: String,
+    pub games_played: u16,
+    pub games_won: u16,
+    pub games: Vec<PersistGameStats>, // newest first
+    pub achievements: Vec<PersistAchievement>, // oldest first
+}
+
+/// the user's stats.  users can see their own, admins can see anybody's
+pub async fn user_stats(
+    user_id: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let caller_id = match &request_context.claims {
+        Some(claims) => claims.id.clone(),
+        None => return new_unauthorized_response!(""),
+    };
+    if caller_id != user_id && !request_context.is_caller_in_role(Role::Admin) {
+        return new_unauthorized_response!("");
+    }
+    let persist_user = request_context.database.find_user_by_id(user_id).await?;
+    let games = request_context.database.find_game_stats(user_id).await?;
+    let achievements = request_context.database.find_achievements(user_id).await?;
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::UserStats(UserStats {
+            user_id: user_id.to_owned(),
+            games_played: persist_user.user_profile.games_played.unwrap_or(0),
+            games_won: persist_user.user_profile.games_won.unwrap_or(0),
+            games,
+            achievements,
+        }),
+        GameError::NoError(String::default()),
+    ))
+}
+
+pub async fn user_stats_handler(
+    user_id: web::Path<String>,
+    request_context: RequestContext,
+) -> HttpResponse {
+    user_stats(&user_id, &request_context)
+        .await
+        .map(|sr| sr.to_http_response())
+        .unwrap_or_else(|sr| sr.to_http_response())
+}
@@ -3,13 +3,15 @@
 #![allow(unused_imports)]
 
 use bcrypt::{hash, verify};
-use rand::Rng;
+use futures::StreamExt;
 use url::form_urlencoded;
 
 use crate::azure_setup::azure_wrapper::{
     cosmos_account_exists, cosmos_collection_exists, cosmos_database_exists, key_vault_get_secret,
     key_vault_save_secret, keyvault_exists, send_email, send_text_message, verify_login_or_panic,
 };
+use crate::middleware::kc_proxy::KeyCloakProxy;
+use crate::middleware::login_guard::{self, Refused};
 use crate::middleware::security_context::{KeyKind, SecurityContext};
 use crate::middleware::service_config::SERVICE_CONFIG;
 use crate::shared::service_models::{Claims, PersistUser, Role};
@@ -23,7 +25,14 @@ use crate::{
     new_unexpected_server_error, trace_function, unexpected_server_error_from_string,
 };
 
+use crate::games_service::game_container::game_messages::CatanMessage;
 use crate::games_service::long_poller::long_poller::LongPoller;
+use crate::games_service::long_poller::outbox;
+use crate::user_service::profile_projection::project_user;
+use crate::user_service::password_policy;
+use crate::user_service::refresh_tokens;
+use crate::user_service::user_import::is_email;
+use crate::user_service::sessions;
 
 use crate::middleware::request_context_mw::RequestContext;
 use crate::shared::shared_models::{
@@ -113,16 +122,37 @@ pub async fn verify_cosmosdb(context: &RequestContext) -> Result<ServiceResponse
     ))
 }
 
+fn keycloak_error(message: &str, e: String) -> ServiceResponse {
+    tracing::warn!("{}", e);
+    ServiceResponse::new(
+        message,
+        StatusCode::BAD_GATEWAY,
+        ResponseType::ErrorInfo(e),
+        GameError::HttpError(StatusCode::BAD_GATEWAY),
+    )
+}
+
+/// the realm, and the user's id in it, if the service is in KeyCloak mode and the user was made there
+fn keycloak_user(user: &PersistUser, request_context: &RequestContext) -> Option<(KeyCloakProxy, String)> {
+    let keycloak = &request_context.config.keycloak;
+    match &user.keycloak_id {
+        Some(keycloak_id) if keycloak.enabled() => Some((KeyCloakProxy::new(keycloak), keycloak_id.clone())),
+        _ => None,
+    }
+}
+
 async fn internal_register_user(
     password: &str,
     profile_in: &UserProfile,
     roles: &mut Vec<Role>,
+    provision_keycloak: bool,
     request_context: &RequestContext,
 ) -> Result<ServiceResponse, ServiceResponse> {
-    let email = match &profile_in.pii {
-        Some(pii) => pii.email.clone(),
+    let pii = match &profile_in.pii {
+        Some(pii) => pii.clone(),
         None => return Err(bad_request_from_string!("no email specified")),
     };
+    let email = pii.email.clone();
 
     if request_context
         .database
@@ -166,13 +196,29 @@ async fn internal_register_user(
     persist_user.user_profile.games_played = Some(0);
     persist_user.user_profile.games_won = Some(0);
     persist_user.roles = roles.clone();
-    request_context
-        .database
-        .update_or_create_user(&persist_user)
+
+    // in KeyCloak mode the realm gets the user too, and if either can't be made neither is kept (see kc_proxy.rs)
+    let keycloak = &request_context.config.keycloak;
+    if !provision_keycloak || !keycloak.enabled() {
+        return request_context.database.update_or_create_user(&persist_user).await;
+    }
+    let proxy = KeyCloakProxy::new(keycloak);
+    let keycloak_id = proxy
+        .provision_user(&persist_user.id, &pii, password)
         .await
+        .map_err(|e| keycloak_error("the user couldn't be made in KeyCloak -- try again", e))?;
+    persist_user.keycloak_id = Some(keycloak_id.clone());
+    let result = request_context.database.update_or_create_user(&persist_user).await;
+    if result.is_err() {
+        if let Err(e) = proxy.delete_user(&keycloak_id).await {
+            tracing::error!("{} is in KeyCloak but not in the database: {}", email, e);
+        }
+    }
+    result
 }
 
 /// Registers a new user by hashing the provided password and creating a `PersistUser` record in the database.
+/// The password has to meet the password policy (see password_policy.rs).
 ///
 /// # Arguments
 ///
@@ -194,7 +240,8 @@ pub async fn register(
             "can't create a test user through this api.  use register-test-user"
         );
     }
-    internal_register_user(password, profile_in, &mut vec![Role::User], request_context).await
+    password_policy::check(password, request_context).await?;
+    internal_register_user(password, profile_in, &mut vec![Role::User], true, request_context).await
 }
 
 pub async fn update_profile(
@@ -204,7 +251,16 @@ pub async fn update_profile(
     let claims = request_context.claims.as_ref().unwrap();
 
     let mut persist_user = request_context.database.find_user_by_id(&claims.id).await?;
+    //  the email only changes once the new address is confirmed (see request_email_change), and only a link proves
+    //  one is valid
+    if email_of(profile_in) != email_of(&persist_user.user_profile) {
+        return Err(bad_request_from_string!(
+            "the email can't be changed here.  use POST /auth/api/v1/users/email/change"
+        ));
+    }
+    let validated_email = persist_user.user_profile.validated_email;
     persist_user.update_profile(&profile_in);
+    persist_user.user_profile.validated_email = validated_email;
 
     request_context
         .database
@@ -231,6 +287,7 @@ pub async fn register_test_user(
         password,
         &profile,
         &mut vec![Role::User, Role::TestUser],
+        false,
         request_context,
     )
     .await
@@ -241,21 +298,36 @@ pub async fn register_test_user(
  * a cleartext password is passed in (depending on HTTPS to stop MitM attacks and encrypt payload)
  * find the user in the database
  * hash the password and make sure it matches the hash in the db
- * if it does, return a signed JWT token
+ * if it does, return a short lived signed JWT token and a refresh token (see refresh_tokens.rs)
  * add the user to the ALL_USERS_MAP
+ * a login that is locked out is refused before it gets here (see LoginGuard in login_guard.rs)
  */
 pub async fn login(
     username: &str,
     password: &str,
     request_context: &RequestContext,
 ) -> Result<ServiceResponse, ServiceResponse> {
-    let user = request_context
-        .database
-        .find_user_by_email(username)
-        .await?;
+    let user = match request_context.database.find_user_by_email(username).await {
+        Ok(user) => user,
+        Err(e) => return Err(failed_login(username, None, e, request_context)),
+    };
+
+    if user.must_reset_password {
+        return Err(require_password_reset(&user, request_context));
+    }
 
-    let password_hash: String = match user.password_hash {
-        Some(p) => p,
+    let password_hash: String = match &user.password_hash {
+        Some(p) => p.clone(),
+        //  the account was made by an external login (see oauth.rs) and has never had a password
+        None if !user.external_identities.is_empty() => {
+            let failure = ServiceResponse::new(
+                &format!("this account logs in with {:?}", user.external_identities[0].provider),
+                StatusCode::UNAUTHORIZED,
+                ResponseType::NoData,
+                GameError::HttpError(StatusCode::UNAUTHORIZED),
+            );
+            return Err(failed_login(username, Some(&user), failure, request_context));
+        }
         None => {
             return Err(ServiceResponse::new(
                 "user document does not contain a password hash",
@@ -279,78 +351,126 @@ pub async fn login(
     };
 
     if is_password_match {
-        let claims = Claims::new(
-            &user.id,
-            username,
-            24 * 60 * 60,
-            &user.roles,
-            &request_context.test_context,
-        );
-        let token_result = request_context
-            .security_context
-            .login_keys
-            .sign_claims(&claims);
-        match token_result {
-            Ok(token) => {
-                let _ = LongPoller::add_user(&user.id, &user.user_profile).await;
-                Ok(ServiceResponse::new(
-                    "",
-                    StatusCode::OK,
-                    ResponseType::Token(token),
-                    GameError::NoError("ok".to_owned()),
-                ))
-            }
-            Err(e) => {
-                return Err(ServiceResponse::new(
-                    "Error Hashing token",
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    ResponseType::ErrorInfo(format!("{:#?}", e)),
-                    GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
-                ));
-            }
-        }
+        login_guard::record_success(username);
+        start_login(&user, username, request_context).await
     } else {
-        return new_unauthorized_response!("");
+        let failure = ServiceResponse::new(
+            "",
+            StatusCode::UNAUTHORIZED,
+            ResponseType::NoData,
+            GameError::HttpError(StatusCode::UNAUTHORIZED),
+        );
+        Err(failed_login(username, Some(&user), failure, request_context))
     }
 }
 
 /**
- *  this will get a list of all documents.  Note this does *not* do pagination. This would be a reasonable next step to
- *  show in the sample
+ *  the user has proved who they are -- with their password, or with an external identity (see oauth.rs): start a
+ *  session and return its tokens
  */
-pub async fn list_users(
+pub async fn start_login(
+    user: &PersistUser,
+    email: &str,
     request_context: &RequestContext,
 ) -> Result<ServiceResponse, ServiceResponse> {
-    // Get list of users
-    match request_context.database.list().await {
-        Ok(users) => {
-            let user_profiles: Vec<UserProfile> = users
-                .iter()
-                .map(|user| UserProfile::from_persist_user(&user))
-                .collect();
-
+    let (session_id, displaced) = sessions::start(
+        &user.id,
+        request_context.config.session_policy,
+        request_context.environment.as_ref(),
+    );
+    //  every login starts a family of refresh tokens (see refresh_tokens.rs)
+    let family_id = request_context.environment.new_id();
+    match refresh_tokens::issue(user, email, &family_id, &session_id, request_context).await {
+        Ok(tokens) => {
+            let _ = LongPoller::add_user(&user.id, &user.user_profile).await;
+            outbox::start_delivery(&user.id, request_context.environment.now());
+            refresh_tokens::tell_displaced(&user.id, displaced, request_context).await;
             Ok(ServiceResponse::new(
                 "",
                 StatusCode::OK,
-                ResponseType::Profiles(user_profiles),
-                GameError::NoError(String::default()),
+                ResponseType::LoginTokens(tokens),
+                GameError::NoError("ok".to_owned()),
             ))
         }
-        Err(err) => {
-            return Err(ServiceResponse::new(
-                "",
-                StatusCode::NOT_FOUND,
-                ResponseType::ErrorInfo(format!("Failed to retrieve user list: {}", err)),
-                GameError::HttpError(StatusCode::NOT_FOUND),
-            ));
+        Err(e) => {
+            sessions::end(&user.id, &session_id, request_context.environment.as_ref());
+            Err(e)
+        }
+    }
+}
+
+/**
+ *  count a failed login against the account and the caller's address (see login_guard.rs).  the failure that locks the
+ *  account is answered with the lock instead, and the owner is mailed -- if it wasn't them, somebody is guessing
+ */
+fn failed_login(
+    email: &str,
+    user: Option<&PersistUser>,
+    failure: ServiceResponse,
+    request_context: &RequestContext,
+) -> ServiceResponse {
+    let policy = request_context.config.lockout_policy;
+    if !login_guard::record_failure(
+        email,
+        request_context.client_address.as_deref(),
+        &policy,
+        request_context.environment.now(),
+    ) {
+        return failure;
+    }
+    let user_id = user.map_or("an unknown account", |user| user.id.as_str());
+    tracing::warn!(
+        "{} is locked for {} seconds after {} failed logins",
+        user_id,
+        policy.lockout_seconds,
+        policy.max_failures
+    );
+    if let (Some(_), false) = (user, request_context.is_test()) {
+        let (subject, msg) = SERVICE_CONFIG
+            .branding
+            .account_locked_email((policy.lockout_seconds + 59) / 60);
+        if let Err(e) = send_email(email, &SERVICE_CONFIG.service_email, &subject, &msg) {
+            tracing::error!("couldn't send the lockout email to {}: {}", user_id, e);
         }
     }
+    Refused::AccountLocked(policy.lockout_seconds).to_response()
+}
+
+/**
+ *  this will get a list of all documents.  Note this does *not* do pagination. This would be a reasonable next step to
+ *  show in the sample.  the users are streamed from the database and only their profiles are kept
+ */
+pub async fn list_users(
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    let mut user_profiles: Vec<UserProfile> = Vec::new();
+    let mut users = request_context.database.stream_users();
+    while let Some(user) = users.next().await {
+        match user {
+            Ok(user) => user_profiles.push(project_user(&user, request_context.claims.as_ref())),
+            Err(err) => {
+                return Err(ServiceResponse::new(
+                    "",
+                    StatusCode::NOT_FOUND,
+                    ResponseType::ErrorInfo(format!("Failed to retrieve user list: {}", err)),
+                    GameError::HttpError(StatusCode::NOT_FOUND),
+                ));
+            }
+        }
+    }
+    Ok(ServiceResponse::new(
+        "",
+        StatusCode::OK,
+        ResponseType::Profiles(user_profiles),
+        GameError::NoError(String::default()),
+    ))
 }
 ///
 ///     1. email should be id or email
 ///         - figure out which one it is by context
 ///     2. check the claims -- you can always look up your own profile
 ///     3. an admin can look up anybody's profile
+///     4. anybody else can only look up their own profile, by id or by email
 pub async fn get_profile(
     id_or_email: &str,
     request_context: &RequestContext,
@@ -384,10 +504,11 @@ pub async fn get_profile(
 
     // lookup value is either id or email...is it different than the one in the context?
 
-    if lookup_value != user_id
-        && lookup_value != user_email
-        && !request_context.is_caller_in_role(Role::Admin)
-    {
+    let is_own = match lookup_value.contains("@") {
+        true => lookup_value == user_email,
+        false => lookup_value == user_id,
+    };
+    if !is_own && !request_context.is_caller_in_role(Role::Admin) {
         // if you aren't the admin, you can only look up your own profile
         return new_unauthorized_response!("");
     }
@@ -417,7 +538,7 @@ pub async fn get_profile(
     Ok(ServiceResponse::new(
         "",
         StatusCode::OK,
-        ResponseType::Profile(UserProfile::from_persist_user(&user)),
+        ResponseType::Profile(project_user(&user, request_context.claims.as_ref())),
         GameError::NoError(String::default()),
     ))
 }
@@ -437,8 +558,18 @@ pub async fn delete(
         return new_unauthorized_response!("only an admin can delete another user");
     }
 
+    let keycloak = match request_context.database.find_user_by_id(id).await {
+        Ok(user) => keycloak_user(&user, request_context),
+        Err(_) => None,
+    };
     let result = request_context.database.delete_user(id).await;
 
+    //  the realm's user goes too.  one left behind can't log in -- there is no user for it (see kc_proxy.rs)
+    if let (Ok(..), Some((proxy, keycloak_id))) = (&result, keycloak) {
+        if let Err(e) = proxy.delete_user(&keycloak_id).await {
+            tracing::error!("{} was deleted, but not from KeyCloak: {}", id, e);
+        }
+    }
     match result {
         Ok(..) => Ok(ServiceResponse::new(
             &format!("deleted user with id: {}", user_id),
@@ -474,8 +605,8 @@ pub async fn validate_email(token: &str) -> Result<ServiceResponse, ServiceRespo
         .validation_keys
         .validate_token(&decoded_token)
     {
-        Some(c) => c,
-        None => return new_unauthorized_response!(""),
+        Some(c) if c.roles.contains(&Role::Validation) => c,
+        _ => return new_unauthorized_response!(""),
     };
 
     //  we have to embed the TestContext in the claim because we come through a GET from a URL where
@@ -490,6 +621,10 @@ pub async fn validate_email(token: &str) -> Result<ServiceResponse, ServiceRespo
     let id = &claims.id; // Borrowing here.
     let mut user = request_context.database.find_user_by_id(id).await?;
 
+    //  a link mailed to an address the user has since changed from doesn't validate the one they have now
+    if email_of(&user.user_profile).as_deref() != Some(claims.sub.as_str()) {
+        return new_unauthorized_response!("");
+    }
     user.user_profile.validated_email = true;
     request_context.database.update_or_create_user(&user).await
 }
@@ -522,6 +657,304 @@ pub fn get_validation_url(
         host, encoded_token
     )
 }
+fn email_of(profile: &UserProfile) -> Option<String> {
+    profile.pii.as_ref().map(|pii| pii.email.clone())
+}
+
+/// how long a password reset link works
+pub const PASSWORD_RESET_SECONDS: u64 = 24 * 60 * 60;
+
+//
+//  url is in the form of host://api/v1/users/reset-password/<token>.  the client POSTs the new password to it
+pub fn get_password_reset_url(
+    host: &str,
+    id: &str,
+    email: &str,
+    request_context: &RequestContext,
+) -> String {
+    let claims = Claims::new(
+        id,
+        email,
+        PASSWORD_RESET_SECONDS,
+        &vec![Role::PasswordReset],
+        &request_context.test_context,
+    );
+    let token = request_context
+        .security_context
+        .validation_keys
+        .sign_claims(&claims)
+        .expect("Token creation should not fail");
+
+    let encoded_token = form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>();
+
+    format!(
+        "https://{}/api/v1/users/reset-password/{}",
+        host, encoded_token
+    )
+}
+
+/**
+ *  the user has to set a password before they can log in: mail them a link to set it, and tell the client why the
+ *  login failed.  the link is only ever mailed -- anybody can try to log in as anybody
+ */
+pub fn require_password_reset(user: &PersistUser, request_context: &RequestContext) -> ServiceResponse {
+    let email = user
+        .user_profile
+        .pii
+        .as_ref()
+        .map(|pii| pii.email.clone())
+        .unwrap_or_default();
+    if !request_context.is_test() {
+        let host_name = std::env::var("HOST_NAME").expect("HOST_NAME must be set");
+        let url = get_password_reset_url(&host_name, &user.id, &email, request_context);
+        let (subject, msg) = SERVICE_CONFIG.branding.password_reset_email(&url);
+        if let Err(e) = send_email(&email, &SERVICE_CONFIG.service_email, &subject, &msg) {
+            tracing::error!("couldn't send the password reset email to {}: {}", user.id, e);
+        }
+    }
+    ServiceResponse::new(
+        "a password has to be set for this account.  check your email for the link to set it",
+        StatusCode::FORBIDDEN,
+        ResponseType::NoData,
+        GameError::HttpError(StatusCode::FORBIDDEN),
+    )
+}
+
+/**
+ *  set the password of an account that has to have one set (see user_import.rs).  the token is the one in the link
+ *  that was mailed to the user, so it proves they own the email -- which is validated too.  the password has to meet
+ *  the password policy (see password_policy.rs)
+ */
+pub async fn reset_password(token: &str, password: &str) -> Result<ServiceResponse, ServiceResponse> {
+    trace_function!("reset_password");
+    let decoded_token = form_urlencoded::parse(token.as_bytes())
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>()
+        .join("");
+
+    let security_context = SecurityContext::cached_secrets();
+    let claims = match security_context
+        .validation_keys
+        .validate_token(&decoded_token)
+    {
+        Some(c) if c.roles.contains(&Role::PasswordReset) => c,
+        _ => return new_unauthorized_response!(""),
+    };
+
+    //  like validate_email, the TestContext comes in the claim
+    let request_context = RequestContext::new(
+        &Some(claims.clone()),
+        &claims.test_context,
+        &SERVICE_CONFIG,
+        &security_context,
+    );
+
+    let mut user = request_context.database.find_user_by_id(&claims.id).await?;
+    if !user.must_reset_password {
+        // the link has been used already
+        return new_unauthorized_response!("");
+    }
+    password_policy::check(password, &request_context).await?;
+    let password_hash = hash(password, bcrypt::DEFAULT_COST).map_err(|e| {
+        ServiceResponse::new(
+            "Error Hashing Password",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ResponseType::ErrorInfo(format!("{:#?}", e)),
+            GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+        )
+    })?;
+    //  the realm has to have the password before the service does, or the two would disagree about it
+    if let Some((proxy, keycloak_id)) = keycloak_user(&user, &request_context) {
+        proxy
+            .update_password(&keycloak_id, password)
+            .await
+            .map_err(|e| keycloak_error("the password couldn't be set in KeyCloak -- try again", e))?;
+    }
+    user.password_hash = Some(password_hash);
+    user.must_reset_password = false;
+    user.user_profile.validated_email = true;
+    request_context.database.update_or_create_user(&user).await?;
+    refresh_tokens::revoke_all(&user.id, &request_context).await;
+    Ok(ServiceResponse::new(
+        "password set",
+        StatusCode::OK,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    ))
+}
+
+/// how long the link that confirms a new email works
+pub const EMAIL_CHANGE_SECONDS: u64 = 24 * 60 * 60;
+
+//
+//  url is in the form of host://api/v1/users/confirm-email/<token>.  the sub of the claims is the new address
+pub fn get_email_change_url(host: &str, id: &str, new_email: &str, request_context: &RequestContext) -> String {
+    let claims = Claims::new(
+        id,
+        new_email,
+        EMAIL_CHANGE_SECONDS,
+        &vec![Role::EmailChange],
+        &request_context.test_context,
+    );
+    let token = request_context
+        .security_context
+        .validation_keys
+        .sign_claims(&claims)
+        .expect("Token creation should not fail");
+
+    let encoded_token = form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>();
+
+    format!("https://{}/api/v1/users/confirm-email/{}", host, encoded_token)
+}
+
+/// nobody else has the address: it is free, or it is the user's
+async fn email_is_free(email: &str, user_id: &str, request_context: &RequestContext) -> bool {
+    match request_context.database.find_user_by_email(email).await {
+        Ok(other) => other.id == user_id,
+        Err(_) => true,
+    }
+}
+
+fn email_taken(email: &str) -> ServiceResponse {
+    ServiceResponse::new(
+        &format!("{} is already in use", email),
+        StatusCode::CONFLICT,
+        ResponseType::NoData,
+        GameError::HttpError(StatusCode::CONFLICT),
+    )
+}
+
+/**
+ *  start changing the caller's email: mail a link to the new address that confirms it (see confirm_email_change).
+ *  nothing else changes until it is used -- they still log in with, and are mailed at, the old address.  asking again
+ *  replaces the address being changed to, so only the link from the last ask works.  in a test nothing is mailed and
+ *  the Ok() response has the link in it instead -- otherwise the link only goes to the new address
+ */
+pub async fn request_email_change(
+    new_email: &str,
+    request_context: &RequestContext,
+) -> Result<ServiceResponse, ServiceResponse> {
+    trace_function!("request_email_change");
+    let new_email = new_email.trim();
+    if !is_email(new_email) {
+        return Err(bad_request_from_string!(&format!("not an email address: {}", new_email)));
+    }
+    let claims = request_context
+        .claims
+        .as_ref()
+        .expect("auth_mw should have added this or rejected the call");
+    let mut user = request_context.database.find_user_by_id(&claims.id).await?;
+    if email_of(&user.user_profile).as_deref() == Some(new_email) {
+        return Err(bad_request_from_string!("that is already your email"));
+    }
+    if !email_is_free(new_email, &user.id, request_context).await {
+        return Err(email_taken(new_email));
+    }
+    user.pending_email = Some(new_email.to_owned());
+    request_context.database.update_or_create_user(&user).await?;
+
+    let host_name = std::env::var("HOST_NAME").expect("HOST_NAME must be set");
+    let url = get_email_change_url(&host_name, &user.id, new_email, request_context);
+    if request_context.is_test() {
+        return Ok(ServiceResponse::new(
+            "sent",
+            StatusCode::OK,
+            ResponseType::Url(url),
+            GameError::NoError(String::default()),
+        ));
+    }
+    let (subject, msg) = SERVICE_CONFIG.branding.email_change_email(&url);
+    send_email(new_email, &SERVICE_CONFIG.service_email, &subject, &msg).map_err(|e| {
+        ServiceResponse::new(
+            "Error sending email",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ResponseType::ErrorInfo(e),
+            GameError::HttpError(StatusCode::INTERNAL_SERVER_ERROR),
+        )
+    })?;
+    Ok(ServiceResponse::new_generic_ok("sent"))
+}
+
+/**
+ *  the link from request_email_change was used: the new address becomes the user's email, and it is validated --
+ *  the link could only be opened from it.  the old address is told about the change, and every session the user had is
+ *  ended and its refresh tokens revoked, so they log in again with the new address.  the link works once, and only
+ *  if it is for the address the user asked for last, and somebody else hasn't taken the address in the meantime
+ */
+pub async fn confirm_email_change(token: &str) -> Result<ServiceResponse, ServiceResponse> {
+    trace_function!("confirm_email_change");
+    let decoded_token = form_urlencoded::parse(token.as_bytes())
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>()
+        .join("");
+
+    let security_context = SecurityContext::cached_secrets();
+    let claims = match security_context
+        .validation_keys
+        .validate_token(&decoded_token)
+    {
+        Some(c) if c.roles.contains(&Role::EmailChange) => c,
+        _ => return new_unauthorized_response!(""),
+    };
+
+    //  like validate_email, the TestContext comes in the claim
+    let request_context = RequestContext::new(
+        &Some(claims.clone()),
+        &claims.test_context,
+        &SERVICE_CONFIG,
+        &security_context,
+    );
+
+    let mut user = request_context.database.find_user_by_id(&claims.id).await?;
+    if user.pending_email.as_deref() != Some(claims.sub.as_str()) {
+        // used already, or there has been a newer ask
+        return new_unauthorized_response!("");
+    }
+    if !email_is_free(&claims.sub, &user.id, &request_context).await {
+        return Err(email_taken(&claims.sub));
+    }
+    let old_email = email_of(&user.user_profile);
+    match user.user_profile.pii.as_mut() {
+        Some(pii) => pii.email = claims.sub.clone(),
+        None => return Err(bad_request_from_string!("the user has no personal information to change")),
+    }
+    user.user_profile.validated_email = true;
+    user.pending_email = None;
+    //  the realm changes first, and back again if the service can't
+    let keycloak = keycloak_user(&user, &request_context);
+    if let Some((proxy, keycloak_id)) = &keycloak {
+        proxy
+            .update_email(keycloak_id, &claims.sub)
+            .await
+            .map_err(|e| keycloak_error("the email couldn't be changed in KeyCloak -- try again", e))?;
+    }
+    if let Err(e) = request_context.database.update_or_create_user(&user).await {
+        if let (Some((proxy, keycloak_id)), Some(old_email)) = (&keycloak, &old_email) {
+            if let Err(undo_error) = proxy.update_email(keycloak_id, old_email).await {
+                tracing::error!("{} has a new email in KeyCloak only: {}", user.id, undo_error);
+            }
+        }
+        return Err(e);
+    }
+    tracing::info!("{} changed their email", user.id);
+    //  the address is what they log in with: every login has to be made again with the new one
+    refresh_tokens::revoke_all(&user.id, &request_context).await;
+    sessions::end_all(&user.id, request_context.environment.as_ref());
+
+    if let Some(old_email) = old_email.filter(|_| !request_context.is_test()) {
+        let (subject, msg) = SERVICE_CONFIG.branding.email_changed_email(&claims.sub);
+        if let Err(e) = send_email(&old_email, &SERVICE_CONFIG.service_email, &subject, &msg) {
+            tracing::error!("couldn't tell {} their email changed: {}", user.id, e);
+        }
+    }
+    Ok(ServiceResponse::new(
+        "email changed",
+        StatusCode::OK,
+        ResponseType::NoData,
+        GameError::NoError(String::default()),
+    ))
+}
+
 ///
 /// Send a validation email
 /// returns an error or a ServiceResponse that has the validation URL embedded in it.  RegistgerUser should call
@@ -536,18 +969,8 @@ pub fn send_validation_email(
         .clone()
         .expect("claims are set by auth middleware, or the call is rejected");
     let url = get_validation_url(&host_name, &claims.id, &claims.sub, &request_context);
-    let msg = format!(
-        "Thank you for registering with our Service.\n\n\
-         Click on this link to validate your email: {}\n\n\
-         If you did not register with the service, something has gone terribly wrong.",
-        url
-    );
-    let result = send_email(
-        &claims.sub,
-        &SERVICE_CONFIG.service_email,
-        "Please validate your email",
-        &msg,
-    );
+    let (subject, msg) = SERVICE_CONFIG.branding.validation_email(&url);
+    let result = send_email(&claims.sub, &SERVICE_CONFIG.service_email, &subject, &msg);
     match result {
         Ok(_) => Ok(ServiceResponse::new(
             "sent",
@@ -579,7 +1002,7 @@ pub async fn send_phone_code(
         .id
         .clone();
 
-    let random_code: i32 = rand::thread_rng().gen_range(100_000..=999_999);
+    let random_code = (request_context.environment.random_below(900_000) + 100_000) as i32;
 
     let code: i32 = request_context
         .test_context
@@ -612,12 +1035,7 @@ async fn internal_send_phone_code(
         .database
         .update_or_create_user(&persist_user)
         .await?;
-    let msg = format!(
-        "This is your 6 digit code for the Catan Service. \
-                   If You did not request this code, ignore this message. \
-                   code: {}",
-        code
-    );
+    let msg = SERVICE_CONFIG.branding.phone_code_text(code);
     send_text_message(&phone_number, &msg)
 }
 
@@ -909,13 +1327,13 @@ mod tests {
     // Test the login function
     #[tokio::test]
     async fn test_login_mocked() {
-        init_env_logger(log::LevelFilter::Trace, log::LevelFilter::Trace).await;
+        init_env_logger(crate::LevelFilter::TRACE, crate::LevelFilter::TRACE).await;
         test_login(false).await;
     }
 
     #[tokio::test]
     async fn test_local_users() {
-        init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
         let app = create_test_service!();
         let code = 569342;
         let mut proxy = TestProxy::new(&app, Some(TestContext::new(true, Some(code))));
@@ -1013,7 +1431,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_login_cosmos() {
-        init_env_logger(log::LevelFilter::Info, log::LevelFilter::Error).await;
+        init_env_logger(crate::LevelFilter::INFO, crate::LevelFilter::ERROR).await;
         test_login(true).await;
     }
 